@@ -0,0 +1,129 @@
+//! Compares tuned ACL fragmentation ([`ACLFragmenter`]) against naive fixed-size fragmentation.
+//!
+//! Air time is simulated for an LE 1M PHY link (every link layer PDU costs its header, CRC, IFS
+//! and the peer's empty ack) so the numbers are repeatable without hardware. Host CPU time for
+//! fragmenting and packing is measured for real.
+//!
+//! Run with `cargo bench --bench acl_fragmentation`.
+use btle::hci::acl::{ACLDataPacket, ACLFragmenter};
+use btle::le::connection::ConnectionHandle;
+use std::time::{Duration, Instant};
+
+/// Preamble + access address + header + CRC.
+const PDU_OVERHEAD_BYTES: u64 = 1 + 4 + 2 + 3;
+const US_PER_BYTE: u64 = 8;
+const IFS_US: u64 = 150;
+/// Empty PDU the peer sends back to ack each PDU.
+const EMPTY_PDU_US: u64 = PDU_OVERHEAD_BYTES * US_PER_BYTE;
+/// Bytes sent per run.
+const TOTAL_BYTES: usize = 64 * 1024;
+/// L2CAP SDU size used by the sender.
+const PDU_LEN: usize = 512;
+
+/// Air time to send `fragments` (given as fragment lengths) over a link with `max_tx_octets`.
+fn air_time(fragments: impl Iterator<Item = usize>, max_tx_octets: usize) -> Duration {
+    let mut us = 0;
+    for len in fragments {
+        let mut rest = len;
+        loop {
+            let payload = rest.min(max_tx_octets);
+            us += (PDU_OVERHEAD_BYTES + payload as u64) * US_PER_BYTE
+                + IFS_US
+                + EMPTY_PDU_US
+                + IFS_US;
+            rest -= payload;
+            if rest == 0 {
+                break;
+            }
+        }
+    }
+    Duration::from_micros(us)
+}
+fn naive_fragments(fixed_len: usize) -> impl Iterator<Item = usize> {
+    (0..TOTAL_BYTES / PDU_LEN).flat_map(move |_| {
+        (0..PDU_LEN)
+            .step_by(fixed_len)
+            .map(move |start| (PDU_LEN - start).min(fixed_len))
+    })
+}
+fn tuned_fragments(fragmenter: ACLFragmenter) -> impl Iterator<Item = usize> {
+    let fragment_len = fragmenter.fragment_len();
+    naive_fragments(fragment_len)
+}
+fn throughput(air: Duration) -> f64 {
+    (TOTAL_BYTES as f64 * 8.0) / air.as_secs_f64() / 1000.0
+}
+/// Host time to fragment and pack [`TOTAL_BYTES`] into HCI ACL packets.
+fn pack_time(fragmenter: &ACLFragmenter) -> Duration {
+    let handle = ConnectionHandle::new(0x0040);
+    let pdu = vec![0xA5_u8; PDU_LEN];
+    let mut out =
+        vec![0_u8; usize::from(fragmenter.buffer_len()) + ACLDataPacket::<&[u8]>::HEADER_LEN];
+    let start = Instant::now();
+    for _ in 0..TOTAL_BYTES / PDU_LEN {
+        for fragment in fragmenter.fragment(handle, &pdu) {
+            let len = fragment.byte_len();
+            fragment
+                .pack_into(&mut out[..len])
+                .expect("buffer sized for fragment");
+            std::hint::black_box(&out);
+        }
+    }
+    start.elapsed()
+}
+fn main() {
+    let buffer_len = 251_u16;
+    println!(
+        "{} bytes as {} byte L2CAP PDUs, controller LE ACL buffer {} bytes",
+        TOTAL_BYTES, PDU_LEN, buffer_len
+    );
+    println!(
+        "{:<40} {:>10} {:>12} {:>12}",
+        "strategy", "fragments", "air time", "kbit/s"
+    );
+    for &max_tx_octets in &[
+        ACLFragmenter::DEFAULT_TX_OCTETS,
+        100,
+        ACLFragmenter::MAX_TX_OCTETS,
+    ] {
+        let tx = usize::from(max_tx_octets);
+        let mut fragmenter = ACLFragmenter::new(buffer_len);
+        fragmenter.set_max_tx_octets(max_tx_octets);
+        let rows: [(String, Vec<usize>); 3] = [
+            (
+                format!("naive 27 byte (tx_octets {})", tx),
+                naive_fragments(27).collect(),
+            ),
+            (
+                format!("naive buffer len (tx_octets {})", tx),
+                naive_fragments(usize::from(buffer_len)).collect(),
+            ),
+            (
+                format!(
+                    "tuned {} byte (tx_octets {})",
+                    fragmenter.fragment_len(),
+                    tx
+                ),
+                tuned_fragments(fragmenter).collect(),
+            ),
+        ];
+        for (name, fragments) in rows.iter() {
+            let air = air_time(fragments.iter().copied(), tx);
+            println!(
+                "{:<40} {:>10} {:>10}ms {:>12.1}",
+                name,
+                fragments.len(),
+                air.as_millis(),
+                throughput(air)
+            );
+        }
+    }
+    let fragmenter = ACLFragmenter::new(buffer_len);
+    let runs = 100;
+    let total: Duration = (0..runs).map(|_| pack_time(&fragmenter)).sum();
+    println!(
+        "host fragment + pack: {:?} per {} bytes",
+        total / runs,
+        TOTAL_BYTES
+    );
+}
@@ -0,0 +1,71 @@
+//! Decode path benchmarks. Gateways parse thousands of advertising reports a second so these
+//! cover raw HCI event packets through to individual AD structures.
+//!
+//! Run with `cargo bench --bench decode`.
+use btle::bytes::{decode_hex, encode_hex};
+use btle::hci::event::EventPacket;
+use btle::hci::le::report::AdvertisingReport;
+use btle::hci::le::{MetaEvent, RawMetaEvent};
+use btle::hci::packet::RawPacket;
+use btle::le::advertisement::StaticAdvBuffer;
+use btle::le::report::ReportInfo;
+use core::convert::TryFrom;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+type Report = AdvertisingReport<Vec<ReportInfo<StaticAdvBuffer>>>;
+/// Flags, a 128-bit service UUID and some manufacturer data (31 bytes).
+const ADV_DATA: [u8; 31] = [
+    0x02, 0x01, 0x06, 0x11, 0x07, 0xF4, 0xCB, 0xE9, 0xD2, 0x10, 0x48, 0x49, 0x91, 0xB6, 0x45, 0xA3,
+    0x32, 0x97, 0x7C, 0xCF, 0x70, 0x09, 0xFF, 0x59, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06,
+];
+/// Full HCI event packet (with packet type) holding an LE Advertising Report with `num_reports`
+/// reports.
+fn report_packet(num_reports: u8) -> Vec<u8> {
+    let mut params = vec![0x02, num_reports];
+    for i in 0..num_reports {
+        params.extend_from_slice(&[0x00, 0x01]); // ADV_IND, Random address
+        params.extend_from_slice(&[i, 0x22, 0x33, 0x44, 0x55, 0xC6]);
+        params.push(ADV_DATA.len() as u8);
+        params.extend_from_slice(&ADV_DATA);
+        params.push(-60_i8 as u8);
+    }
+    let mut packet = vec![0x04, 0x3E, params.len() as u8];
+    packet.extend_from_slice(&params);
+    packet
+}
+fn decode_event(packet: &[u8]) -> Report {
+    let raw = RawPacket::try_from(packet).expect("valid packet type");
+    let event = EventPacket::try_from(raw).expect("valid event");
+    let meta = RawMetaEvent::try_from(event).expect("LE meta event");
+    Report::meta_unpack_packet(meta).expect("valid report")
+}
+fn bench_decode(c: &mut Criterion) {
+    let single = report_packet(1);
+    let multi = report_packet(6);
+    let mut group = c.benchmark_group("advertising_report");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("decode_1", |b| b.iter(|| decode_event(black_box(&single))));
+    group.throughput(Throughput::Elements(6));
+    group.bench_function("decode_6", |b| b.iter(|| decode_event(black_box(&multi))));
+    group.finish();
+
+    let report = decode_event(&single);
+    let info = report.reports[0];
+    c.bench_function("ad_structures", |b| {
+        b.iter(|| black_box(&info).data.iter().count())
+    });
+
+    let mut group = c.benchmark_group("hex");
+    group.throughput(Throughput::Bytes(ADV_DATA.len() as u64));
+    let mut hex = [0_u8; ADV_DATA.len() * 2];
+    group.bench_function("encode", |b| {
+        b.iter(|| encode_hex(black_box(&ADV_DATA), &mut hex))
+    });
+    let mut bytes = [0_u8; ADV_DATA.len()];
+    group.bench_function("decode", |b| {
+        b.iter(|| decode_hex(black_box(&hex), &mut bytes))
+    });
+    group.finish();
+}
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+//! Loops SDUs from an `IsoSource` through the ISO Data packet path into an `IsoSink`. Real
+//! applications would send the packets to the controller and push received packets into the sink.
+use btle::hci::packet::Packet;
+use btle::le::connection::ConnectionHandle;
+use btle::le::iso::{IsoSink, SduScheduler};
+
+/// 10ms SDU interval (LC3 frame duration).
+const SDU_INTERVAL_US: u32 = 10_000;
+/// 40 bytes per frame (32kbps LC3).
+const SDU_LEN: usize = 40;
+
+pub fn main() {
+    let handle = ConnectionHandle::new(0x0060);
+    let mut scheduler = SduScheduler::new(handle, SDU_INTERVAL_US, 27);
+    let mut sink = IsoSink::new();
+    // Silent audio frames.
+    let mut source = |_sequence_number: u16| Some(vec![0_u8; SDU_LEN].into_boxed_slice());
+    let mut buf = [0_u8; 64];
+    for now_us in (0..10).map(|i| i * SDU_INTERVAL_US) {
+        for packet in scheduler
+            .poll_source(&mut source, now_us)
+            .expect("sdu fits in a packet")
+        {
+            let len = packet.packet_byte_len();
+            packet
+                .packet_pack_into(&mut buf[..len])
+                .expect("buf is big enough");
+            let received =
+                btle::hci::iso::ISODataPacket::<Vec<u8>>::packet_unpack_from(&buf[..len])
+                    .expect("valid iso packet");
+            sink.push_packet(&received);
+        }
+        while let Some(sdu) = sink.pull_sdu() {
+            println!(
+                "sdu #{} at {:?}us ({} bytes, {:?})",
+                sdu.sequence_number,
+                sdu.time_stamp,
+                sdu.data.len(),
+                sdu.status
+            );
+        }
+    }
+}
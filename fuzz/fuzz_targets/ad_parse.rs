@@ -0,0 +1,12 @@
+//! Feeds AD shaped payloads (valid and malformed, see `btle::le::ad_corpus`) to the
+//! advertisement parser. Run with `cargo fuzz run ad_parse`.
+#![no_main]
+use btle::le::ad_corpus::payload_from_bytes;
+use btle::le::advertisement::RawAdvertisement;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let entry = payload_from_bytes(data);
+    for _ in RawAdvertisement(entry.payload.as_slice()).iter() {}
+    for _ in RawAdvertisement(data).iter() {}
+});
@@ -0,0 +1,10 @@
+//! Checks the pack/unpack invariants of every HCI message type against fuzzed bytes. The first
+//! byte selects the message type. Run with `cargo fuzz run hci_round_trip`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Err(e) = btle::hci::invariants::fuzz_message(data) {
+        panic!("{:?} for {:02X?}", e, data);
+    }
+});
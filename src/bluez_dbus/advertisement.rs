@@ -0,0 +1,401 @@
+//! Advertising through `org.bluez.LEAdvertisingManager1`. `bluetoothd` builds the payload
+//! itself from the properties of an `org.bluez.LEAdvertisement1` object we export, so a raw
+//! payload is split into [`AdContents`] (manufacturer data, service data, UUIDs, ...) and
+//! anything without a property of its own goes in the experimental `Data` property (only used by
+//! `bluetoothd` started with `--experimental`). Flags are always set by `bluetoothd`.
+use super::{BluezAdapter, DBusError, ADVERTISING_MANAGER_INTERFACE};
+use crate::le::advertisement::AdType;
+use crate::le::advertiser::{AdvertisingParameters, AdvertisingType};
+use crate::{ConversionError, PackError};
+use core::convert::TryFrom;
+use std::collections::HashMap;
+use zbus::fdo;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, Value};
+
+/// UUID from an AD structure (little endian) as `bluetoothd` writes it (`180d`, `0000fd6f` or
+/// `6e400001-b5a3-f393-e0a9-e50e24dcca9e`).
+pub fn uuid_string(uuid: &[u8]) -> String {
+    let hex: String = uuid.iter().rev().map(|b| format!("{:02x}", b)).collect();
+    if uuid.len() == 16 {
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    } else {
+        hex
+    }
+}
+/// One advertising (or scan response) payload split into `org.bluez.LEAdvertisement1`
+/// properties.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AdContents {
+    pub service_uuids: Vec<String>,
+    pub manufacturer_data: Vec<(u16, Vec<u8>)>,
+    pub service_data: Vec<(String, Vec<u8>)>,
+    pub local_name: Option<String>,
+    pub appearance: Option<u16>,
+    /// `bluetoothd` fills in the TX Power Level itself.
+    pub include_tx_power: bool,
+    /// AD structures without a property of their own (`Data`).
+    pub data: Vec<(u8, Vec<u8>)>,
+}
+impl AdContents {
+    pub fn from_payload(payload: &[u8]) -> Result<AdContents, PackError> {
+        let mut contents = AdContents::default();
+        let mut i = 0;
+        while i < payload.len() {
+            let len = usize::from(payload[i]);
+            // A zero length ends the significant part.
+            if len == 0 {
+                break;
+            }
+            if i + len >= payload.len() {
+                return Err(PackError::bad_index(i));
+            }
+            let ad_type = payload[i + 1];
+            let data = &payload[i + 2..=i + len];
+            contents
+                .insert(ad_type, data)
+                .map_err(|_| PackError::bad_index(i + 2))?;
+            i += len + 1;
+        }
+        Ok(contents)
+    }
+    fn insert(&mut self, ad_type: u8, data: &[u8]) -> Result<(), ConversionError> {
+        let uuids = |size: usize| -> Result<Vec<String>, ConversionError> {
+            if data.len() % size != 0 {
+                return Err(ConversionError(()));
+            }
+            Ok(data.chunks(size).map(uuid_string).collect())
+        };
+        let service_data = |size: usize| -> Result<(String, Vec<u8>), ConversionError> {
+            if data.len() < size {
+                return Err(ConversionError(()));
+            }
+            Ok((uuid_string(&data[..size]), data[size..].to_vec()))
+        };
+        match AdType::try_from(ad_type) {
+            Ok(AdType::Flags) => (),
+            Ok(AdType::IncompleteList16bitUUID) | Ok(AdType::CompleteList16bitUUID) => {
+                self.service_uuids.extend(uuids(2)?);
+            }
+            Ok(AdType::IncompleteList32bitUUID) | Ok(AdType::CompleteList32bitUUID) => {
+                self.service_uuids.extend(uuids(4)?);
+            }
+            Ok(AdType::IncompleteList128bitUUID) | Ok(AdType::CompleteList128bitUUID) => {
+                self.service_uuids.extend(uuids(16)?);
+            }
+            Ok(AdType::ShortenLocalName) | Ok(AdType::CompleteLocalName) => {
+                self.local_name = Some(String::from_utf8_lossy(data).into_owned());
+            }
+            Ok(AdType::TxPowerLevel) => self.include_tx_power = true,
+            Ok(AdType::Appearance) => {
+                let bytes = <[u8; 2]>::try_from(data).map_err(|_| ConversionError(()))?;
+                self.appearance = Some(u16::from_le_bytes(bytes));
+            }
+            Ok(AdType::ManufacturerData) => {
+                if data.len() < 2 {
+                    return Err(ConversionError(()));
+                }
+                self.manufacturer_data
+                    .push((u16::from_le_bytes([data[0], data[1]]), data[2..].to_vec()));
+            }
+            Ok(AdType::ServiceData) => self.service_data.push(service_data(2)?),
+            Ok(AdType::ServiceData32bitUUID) => self.service_data.push(service_data(4)?),
+            Ok(AdType::ServiceData128bitUUID) => self.service_data.push(service_data(16)?),
+            _ => self.data.push((ad_type, data.to_vec())),
+        }
+        Ok(())
+    }
+    pub fn is_empty(&self) -> bool {
+        *self == AdContents::default()
+    }
+}
+fn unknown_property<T>(name: &str) -> fdo::Result<T> {
+    Err(fdo::Error::UnknownProperty(name.into()))
+}
+fn non_empty<T>(name: &str, items: &[T]) -> fdo::Result<()> {
+    if items.is_empty() {
+        unknown_property(name)
+    } else {
+        Ok(())
+    }
+}
+fn bytes_dict<K: Clone + Eq + core::hash::Hash>(
+    name: &str,
+    entries: &[(K, Vec<u8>)],
+) -> fdo::Result<HashMap<K, Value<'static>>> {
+    non_empty(name, entries)?;
+    Ok(entries
+        .iter()
+        .map(|(key, data)| (key.clone(), Value::from(data.clone())))
+        .collect())
+}
+/// The exported `org.bluez.LEAdvertisement1` object. Properties without a value are left out
+/// of `GetAll` so `bluetoothd` doesn't add empty AD structures.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LEAdvertisement {
+    /// `"peripheral"` (connectable) or `"broadcast"`.
+    pub kind: &'static str,
+    pub advertising: AdContents,
+    pub scan_response: AdContents,
+    /// Advertising interval range in milliseconds.
+    pub interval_ms: Option<(u32, u32)>,
+}
+impl Default for LEAdvertisement {
+    fn default() -> Self {
+        LEAdvertisement {
+            kind: "peripheral",
+            advertising: AdContents::default(),
+            scan_response: AdContents::default(),
+            interval_ms: None,
+        }
+    }
+}
+#[zbus::dbus_interface(name = "org.bluez.LEAdvertisement1")]
+impl LEAdvertisement {
+    /// `bluetoothd` dropped the advertisement (adapter removed or `bluetoothd` stopping).
+    fn release(&self) {}
+    #[dbus_interface(property, name = "Type")]
+    fn kind(&self) -> String {
+        self.kind.into()
+    }
+    #[dbus_interface(property, name = "ServiceUUIDs")]
+    fn service_uuids(&self) -> fdo::Result<Vec<String>> {
+        non_empty("ServiceUUIDs", &self.advertising.service_uuids)?;
+        Ok(self.advertising.service_uuids.clone())
+    }
+    #[dbus_interface(property)]
+    fn manufacturer_data(&self) -> fdo::Result<HashMap<u16, Value<'static>>> {
+        bytes_dict("ManufacturerData", &self.advertising.manufacturer_data)
+    }
+    #[dbus_interface(property)]
+    fn service_data(&self) -> fdo::Result<HashMap<String, Value<'static>>> {
+        bytes_dict("ServiceData", &self.advertising.service_data)
+    }
+    #[dbus_interface(property)]
+    fn data(&self) -> fdo::Result<HashMap<u8, Value<'static>>> {
+        bytes_dict("Data", &self.advertising.data)
+    }
+    #[dbus_interface(property)]
+    fn local_name(&self) -> fdo::Result<String> {
+        match self
+            .advertising
+            .local_name
+            .as_ref()
+            .or(self.scan_response.local_name.as_ref())
+        {
+            Some(name) => Ok(name.clone()),
+            None => unknown_property("LocalName"),
+        }
+    }
+    #[dbus_interface(property)]
+    fn appearance(&self) -> fdo::Result<u16> {
+        match self
+            .advertising
+            .appearance
+            .or(self.scan_response.appearance)
+        {
+            Some(appearance) => Ok(appearance),
+            None => unknown_property("Appearance"),
+        }
+    }
+    #[dbus_interface(property)]
+    fn includes(&self) -> fdo::Result<Vec<String>> {
+        if self.advertising.include_tx_power || self.scan_response.include_tx_power {
+            Ok(vec!["tx-power".into()])
+        } else {
+            unknown_property("Includes")
+        }
+    }
+    #[dbus_interface(property)]
+    fn min_interval(&self) -> fdo::Result<u32> {
+        match self.interval_ms {
+            Some((min, _)) => Ok(min),
+            None => unknown_property("MinInterval"),
+        }
+    }
+    #[dbus_interface(property)]
+    fn max_interval(&self) -> fdo::Result<u32> {
+        match self.interval_ms {
+            Some((_, max)) => Ok(max),
+            None => unknown_property("MaxInterval"),
+        }
+    }
+    #[dbus_interface(property, name = "ScanResponseServiceUUIDs")]
+    fn scan_response_service_uuids(&self) -> fdo::Result<Vec<String>> {
+        non_empty(
+            "ScanResponseServiceUUIDs",
+            &self.scan_response.service_uuids,
+        )?;
+        Ok(self.scan_response.service_uuids.clone())
+    }
+    #[dbus_interface(property)]
+    fn scan_response_manufacturer_data(&self) -> fdo::Result<HashMap<u16, Value<'static>>> {
+        bytes_dict(
+            "ScanResponseManufacturerData",
+            &self.scan_response.manufacturer_data,
+        )
+    }
+    #[dbus_interface(property)]
+    fn scan_response_service_data(&self) -> fdo::Result<HashMap<String, Value<'static>>> {
+        bytes_dict("ScanResponseServiceData", &self.scan_response.service_data)
+    }
+    #[dbus_interface(property)]
+    fn scan_response_data(&self) -> fdo::Result<HashMap<u8, Value<'static>>> {
+        bytes_dict("ScanResponseData", &self.scan_response.data)
+    }
+}
+/// Advertisement of one [`BluezAdapter`] and whether it's registered with `bluetoothd`.
+pub(crate) struct AdvertisementState {
+    path: OwnedObjectPath,
+    advertisement: LEAdvertisement,
+    is_registered: bool,
+}
+impl AdvertisementState {
+    /// The object lives at `/btle/<adapter name>/advertisement`.
+    pub(crate) fn new(adapter_path: &ObjectPath<'_>) -> Result<AdvertisementState, DBusError> {
+        let name = adapter_path.as_str().rsplit('/').next().unwrap_or_default();
+        Ok(AdvertisementState {
+            path: OwnedObjectPath::try_from(format!("/btle/{}/advertisement", name))?,
+            advertisement: LEAdvertisement::default(),
+            is_registered: false,
+        })
+    }
+    /// Directed advertising isn't supported by `bluetoothd`.
+    pub(crate) fn set_parameters(
+        &mut self,
+        parameters: AdvertisingParameters,
+    ) -> Result<(), ConversionError> {
+        self.advertisement.kind = match parameters.advertising_type {
+            AdvertisingType::AdvInd => "peripheral",
+            AdvertisingType::AdvScanInd | AdvertisingType::AdvNonnConnInd => "broadcast",
+            AdvertisingType::AdvDirectIndHighDutyCycle
+            | AdvertisingType::AdvDirectIndLowDutyCycle => return Err(ConversionError(())),
+        };
+        let ms = |interval: crate::le::advertiser::AdvertisingInterval| {
+            interval.as_duration().as_millis() as u32
+        };
+        self.advertisement.interval_ms =
+            Some((ms(parameters.interval_min), ms(parameters.interval_max)));
+        Ok(())
+    }
+    pub(crate) fn set_data(&mut self, payload: &[u8]) -> Result<(), PackError> {
+        self.advertisement.advertising = AdContents::from_payload(payload)?;
+        Ok(())
+    }
+    pub(crate) fn set_scan_response(&mut self, payload: &[u8]) -> Result<(), PackError> {
+        self.advertisement.scan_response = AdContents::from_payload(payload)?;
+        Ok(())
+    }
+}
+impl BluezAdapter {
+    /// What will be (or is) registered with `bluetoothd`.
+    pub fn advertisement(&self) -> &LEAdvertisement {
+        &self.advertisement.advertisement
+    }
+    pub fn is_advertising(&self) -> bool {
+        self.advertisement.is_registered
+    }
+    /// Export the advertisement object and register it. Does nothing if it's already registered.
+    pub async fn register_advertisement(&mut self) -> Result<(), DBusError> {
+        if self.advertisement.is_registered {
+            return Ok(());
+        }
+        let path = self.advertisement.path.clone();
+        self.connection
+            .object_server()
+            .at(&path, self.advertisement.advertisement.clone())
+            .await?;
+        let options: HashMap<&str, Value<'_>> = HashMap::new();
+        let registered = self
+            .call(
+                self.path(),
+                ADVERTISING_MANAGER_INTERFACE,
+                "RegisterAdvertisement",
+                &(&path, options),
+            )
+            .await;
+        if let Err(e) = registered {
+            self.connection
+                .object_server()
+                .remove::<LEAdvertisement, _>(&path)
+                .await?;
+            return Err(e);
+        }
+        self.advertisement.is_registered = true;
+        Ok(())
+    }
+    pub async fn unregister_advertisement(&mut self) -> Result<(), DBusError> {
+        if !self.advertisement.is_registered {
+            return Ok(());
+        }
+        let path = self.advertisement.path.clone();
+        self.advertisement.is_registered = false;
+        let unregistered = self
+            .call(
+                self.path(),
+                ADVERTISING_MANAGER_INTERFACE,
+                "UnregisterAdvertisement",
+                &(&path,),
+            )
+            .await;
+        self.connection
+            .object_server()
+            .remove::<LEAdvertisement, _>(&path)
+            .await?;
+        unregistered.map(|_| ())
+    }
+    /// `bluetoothd` only reads the properties when registering so a registered advertisement
+    /// is registered again to update it.
+    pub(crate) async fn refresh_advertisement(&mut self) -> Result<(), DBusError> {
+        if self.advertisement.is_registered {
+            self.unregister_advertisement().await?;
+            self.register_advertisement().await?;
+        }
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_ad_contents() {
+        let payload = [
+            0x02, 0x01, 0x06, // Flags
+            0x03, 0x03, 0xAA, 0xFE, // 16-bit UUIDs
+            0x05, 0x09, b'b', b't', b'l', b'e', // Complete Local Name
+            0x05, 0xFF, 0x4C, 0x00, 0x02, 0x15, // Manufacturer Data
+            0x04, 0x16, 0xAA, 0xFE, 0x10, // Service Data
+            0x02, 0x2A, 0x00, // Mesh PDU
+        ];
+        let contents = AdContents::from_payload(&payload[..]).unwrap();
+        assert_eq!(
+            contents,
+            AdContents {
+                service_uuids: vec!["feaa".into()],
+                manufacturer_data: vec![(0x004C, vec![0x02, 0x15])],
+                service_data: vec![("feaa".into(), vec![0x10])],
+                local_name: Some("btle".into()),
+                appearance: None,
+                include_tx_power: false,
+                data: vec![(0x2A, vec![0x00])],
+            }
+        );
+        assert_eq!(
+            uuid_string(&[
+                0x9E, 0xCA, 0xDC, 0x24, 0x0E, 0xE5, 0xA9, 0xE0, 0x93, 0xF3, 0xA3, 0xB5, 0x01, 0x00,
+                0x40, 0x6E
+            ]),
+            "6e400001-b5a3-f393-e0a9-e50e24dcca9e"
+        );
+        assert_eq!(
+            AdContents::from_payload(&[0x05, 0xFF, 0x4C]),
+            Err(PackError::bad_index(0))
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! `org.bluez.Device1` properties to [`ReportInfo`]. `bluetoothd` parses advertisements into
+//! device properties and drops the payload so the payload of a report is rebuilt from the
+//! advertising related properties that changed. AD structures that don't fit in one legacy
+//! payload are left out.
+use super::Properties;
+use crate::bytes::Storage;
+use crate::le::advertisement::{
+    AdType, RawAdStructureBuffer, RawAdvertisement, StaticAdvStructBuf, MAX_AD_LEN,
+};
+use crate::le::report::{AddressType, EventType, ReportInfo, ReportOrigin};
+use crate::{BTAddress, DeviceAddress, RSSI};
+use core::convert::TryFrom;
+use std::collections::HashMap;
+use zbus::zvariant::{OwnedValue, Value};
+
+/// Properties that change when an advertisement is received.
+const ADVERTISING_PROPERTIES: [&str; 5] = [
+    "RSSI",
+    "ManufacturerData",
+    "ServiceData",
+    "AdvertisingData",
+    "TxPower",
+];
+/// `bluetoothd` writes addresses most significant byte first. [`BTAddress`] is least
+/// significant byte first (as in HCI packets).
+pub fn parse_address(s: &str) -> Option<BTAddress> {
+    let mut address: BTAddress = s.parse().ok()?;
+    address.0.reverse();
+    Some(address)
+}
+/// `/org/bluez/hci0/dev_AA_BB_CC_DD_EE_FF` -> address.
+pub fn address_from_path(path: &str) -> Option<BTAddress> {
+    let name = path.rsplit('/').next()?.strip_prefix("dev_")?;
+    parse_address(&name.replace('_', ":"))
+}
+/// `AddressType` property (`"public"` or `"random"`).
+pub fn is_random_address_type(value: &Value<'_>) -> Option<bool> {
+    match value {
+        Value::Str(s) if s.as_str() == "public" => Some(false),
+        Value::Str(s) if s.as_str() == "random" => Some(true),
+        Value::Value(inner) => is_random_address_type(inner),
+        _ => None,
+    }
+}
+/// `Address` and `AddressType` of a device (both are in the properties of a new device).
+pub fn device_address(properties: &Properties) -> Option<DeviceAddress> {
+    let address = match &**properties.get("Address")? {
+        Value::Str(s) => parse_address(s.as_str())?,
+        _ => return None,
+    };
+    let is_random = is_random_address_type(properties.get("AddressType")?)?;
+    Some(DeviceAddress::new(is_random, address))
+}
+pub fn has_advertising_data(properties: &Properties) -> bool {
+    ADVERTISING_PROPERTIES
+        .iter()
+        .any(|name| properties.contains_key(*name))
+}
+fn rssi(properties: &Properties) -> Option<RSSI> {
+    match &**properties.get("RSSI")? {
+        Value::I16(dbm) => Some(RSSI::new(
+            (*dbm).clamp(RSSI::MIN_RSSI_I8.into(), RSSI::MAX_RSSI_I8.into()) as i8,
+        )),
+        _ => None,
+    }
+}
+fn dict<K, V>(properties: &Properties, name: &str) -> Vec<(K, V)>
+where
+    K: zbus::zvariant::Basic + TryFrom<Value<'static>> + Ord + core::hash::Hash,
+    V: TryFrom<Value<'static>>,
+    K::Error: Into<zbus::zvariant::Error>,
+    V::Error: Into<zbus::zvariant::Error>,
+{
+    let mut entries: Vec<(K, V)> = properties
+        .get(name)
+        .and_then(|value| HashMap::<K, V>::try_from(value.clone()).ok())
+        .map(|map| map.into_iter().collect())
+        .unwrap_or_default();
+    // Same payload for the same properties.
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+/// Service data UUID as it goes in the AD structure (shortened if it's a Bluetooth Base UUID).
+fn service_data_uuid(uuid: &str) -> Option<(AdType, Vec<u8>)> {
+    const BASE_SUFFIX: &str = "-0000-1000-8000-00805f9b34fb";
+    let uuid = uuid.to_ascii_lowercase();
+    let mut bytes = match uuid.strip_suffix(BASE_SUFFIX) {
+        Some(short) if short.starts_with("0000") => u16::from_str_radix(&short[4..], 16)
+            .ok()?
+            .to_be_bytes()
+            .to_vec(),
+        Some(short) => u32::from_str_radix(short, 16).ok()?.to_be_bytes().to_vec(),
+        None => {
+            let mut out = [0_u8; 16];
+            crate::bytes::decode_hex(uuid.replace('-', "").as_bytes(), &mut out).ok()?;
+            out.to_vec()
+        }
+    };
+    // Little endian in AD structures.
+    bytes.reverse();
+    let ad_type = match bytes.len() {
+        2 => AdType::ServiceData,
+        4 => AdType::ServiceData32bitUUID,
+        _ => AdType::ServiceData128bitUUID,
+    };
+    Some((ad_type, bytes))
+}
+/// Rebuild an advertising payload from `AdvertisingData`, `ManufacturerData`, `ServiceData` and
+/// `TxPower`. Structures that don't fit are skipped.
+pub fn payload_from_properties(properties: &Properties) -> RawAdvertisement {
+    let mut structures: Vec<(AdType, Vec<u8>)> = Vec::new();
+    for (ad_type, data) in dict::<u8, Vec<u8>>(properties, "AdvertisingData") {
+        if let Ok(ad_type) = AdType::try_from(ad_type) {
+            structures.push((ad_type, data));
+        }
+    }
+    for (company_id, data) in dict::<u16, Vec<u8>>(properties, "ManufacturerData") {
+        let mut bytes = company_id.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&data);
+        structures.push((AdType::ManufacturerData, bytes));
+    }
+    for (uuid, data) in dict::<String, Vec<u8>>(properties, "ServiceData") {
+        if let Some((ad_type, mut bytes)) = service_data_uuid(&uuid) {
+            bytes.extend_from_slice(&data);
+            structures.push((ad_type, bytes));
+        }
+    }
+    if let Some(Value::I16(dbm)) = properties.get("TxPower").map(|v| &**v) {
+        structures.push((AdType::TxPowerLevel, alloc::vec![*dbm as i8 as u8]));
+    }
+    let mut payload = RawAdvertisement::new();
+    for (ad_type, data) in structures {
+        if data.len() > MAX_AD_LEN {
+            continue;
+        }
+        let structure = RawAdStructureBuffer::new(ad_type, StaticAdvStructBuf::from_slice(&data));
+        // Full, the rest wouldn't fit either.
+        if payload.insert(&structure).is_err() {
+            break;
+        }
+    }
+    payload
+}
+/// Report for a device whose advertising properties are `properties`. `None` if none of them
+/// are advertising related (only the name or the connection state changed).
+pub fn report_from_properties(
+    address: DeviceAddress,
+    properties: &Properties,
+) -> Option<ReportInfo> {
+    if !has_advertising_data(properties) {
+        return None;
+    }
+    Some(ReportInfo {
+        // `bluetoothd` doesn't say which PDU it was.
+        event_type: EventType::AdvInd,
+        address_type: if address.is_random() {
+            AddressType::RandomDevice
+        } else {
+            AddressType::PublicDevice
+        },
+        address: address.addr,
+        data: payload_from_properties(properties),
+        rssi: rssi(properties),
+        origin: ReportOrigin::LEGACY,
+    })
+}
+/// `OwnedValue` of a property for building [`Properties`] by hand.
+pub fn property_value<'a, V: Into<Value<'a>>>(value: V) -> OwnedValue {
+    OwnedValue::from(value.into())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_report_from_properties() {
+        let mut properties = Properties::new();
+        properties.insert("Address".into(), property_value("C6:55:44:33:22:11"));
+        properties.insert("AddressType".into(), property_value("random"));
+        let address = device_address(&properties).unwrap();
+        assert_eq!(
+            address,
+            DeviceAddress::random(BTAddress([0x11, 0x22, 0x33, 0x44, 0x55, 0xC6]))
+        );
+        assert_eq!(
+            address_from_path("/org/bluez/hci0/dev_C6_55_44_33_22_11"),
+            Some(address.addr)
+        );
+        assert!(report_from_properties(address, &properties).is_none());
+        let mut manufacturer_data = HashMap::new();
+        manufacturer_data.insert(0x004C_u16, Value::from(alloc::vec![0x02_u8, 0x15]));
+        properties.insert("ManufacturerData".into(), property_value(manufacturer_data));
+        let mut service_data = HashMap::new();
+        service_data.insert(
+            "0000feaa-0000-1000-8000-00805f9b34fb",
+            Value::from(alloc::vec![0x10_u8]),
+        );
+        properties.insert("ServiceData".into(), property_value(service_data));
+        properties.insert("RSSI".into(), property_value(-60_i16));
+        let report = report_from_properties(address, &properties).unwrap();
+        assert_eq!(report.address_type, AddressType::RandomDevice);
+        assert_eq!(report.rssi, Some(RSSI::new(-60)));
+        assert_eq!(
+            report.data.as_ref(),
+            &[0x05, 0xFF, 0x4C, 0x00, 0x02, 0x15, 0x04, 0x16, 0xAA, 0xFE, 0x10][..]
+        );
+    }
+}
@@ -0,0 +1,412 @@
+//! Cooperative Linux backend talking to `bluetoothd` over D-Bus instead of a raw HCI socket.
+//! Raw HCI access needs `bluetoothd` stopped (or `CAP_NET_ADMIN` and a fight over the controller)
+//! which isn't an option on most desktops. [`BluezAdapter`] scans through `org.bluez.Adapter1`
+//! discovery and advertises through `org.bluez.LEAdvertisingManager1`, implementing
+//! [`Observer`] and [`Advertiser`] so code written against the HCI adapter keeps working.
+//!
+//! `bluetoothd` doesn't hand out raw advertising payloads so reports are rebuilt from the
+//! `org.bluez.Device1` properties (see [`device`]) and outgoing payloads are split into
+//! `org.bluez.LEAdvertisement1` properties (see [`advertisement`]). Filtering can be offloaded
+//! to `bluetoothd` with advertisement monitors (see [`monitor`]).
+use crate::error::IOError;
+use crate::hci::adapter;
+use crate::hci::le::whitelist::WhitelistEntry;
+use crate::le::adapter::{Backend, Capabilities};
+use crate::le::advertisement::{StaticAdvBuffer, MAX_ADV_LEN};
+use crate::le::advertiser::{Advertiser, AdvertisingParameters};
+use crate::le::report::ReportInfo;
+use crate::le::scan::{Observer, ScanParameters};
+use crate::{DeviceAddress, LocalBoxFuture};
+use core::convert::TryFrom;
+use futures_util::stream::{LocalBoxStream, StreamExt};
+use std::collections::HashMap;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+pub mod advertisement;
+pub mod device;
+pub mod monitor;
+
+/// Bus name of `bluetoothd`.
+pub const SERVICE: &str = "org.bluez";
+pub const ADAPTER_INTERFACE: &str = "org.bluez.Adapter1";
+pub const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+pub const ADVERTISING_MANAGER_INTERFACE: &str = "org.bluez.LEAdvertisingManager1";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const OBJECT_MANAGER_INTERFACE: &str = "org.freedesktop.DBus.ObjectManager";
+/// Properties of one interface, as returned by `GetAll` or sent in `PropertiesChanged`.
+pub type Properties = HashMap<String, OwnedValue>;
+
+#[derive(Debug)]
+pub struct DBusError(pub zbus::Error);
+impl DBusError {
+    /// Closest [`IOError`] for `bluetoothd`'s `org.bluez.Error.*` replies.
+    pub fn io_error(&self) -> IOError {
+        match &self.0 {
+            zbus::Error::MethodError(name, _, _) => match name.as_str() {
+                "org.bluez.Error.InvalidArguments" => IOError::InvalidArgument,
+                "org.bluez.Error.NotPermitted" | "org.bluez.Error.NotAuthorized" => {
+                    IOError::PermissionDenied
+                }
+                "org.bluez.Error.NotReady" => IOError::NotConnected,
+                "org.bluez.Error.InProgress" => IOError::IllegalCall,
+                "org.bluez.Error.AlreadyExists" => IOError::AlreadyExists,
+                "org.bluez.Error.DoesNotExist" | "org.freedesktop.DBus.Error.UnknownObject" => {
+                    IOError::NotFound
+                }
+                "org.bluez.Error.NotSupported" => IOError::NotImplemented,
+                "org.freedesktop.DBus.Error.AccessDenied" => IOError::AccessDenied,
+                "org.freedesktop.DBus.Error.ServiceUnknown" => IOError::Closed,
+                _ => IOError::Other,
+            },
+            zbus::Error::InputOutput(e) => IOError::from(e.kind()),
+            _ => IOError::Other,
+        }
+    }
+}
+impl From<zbus::Error> for DBusError {
+    fn from(e: zbus::Error) -> Self {
+        DBusError(e)
+    }
+}
+impl From<zbus::zvariant::Error> for DBusError {
+    fn from(e: zbus::zvariant::Error) -> Self {
+        DBusError(e.into())
+    }
+}
+impl std::fmt::Display for DBusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bluez dbus error: {}", self.0)
+    }
+}
+impl std::error::Error for DBusError {}
+impl From<DBusError> for adapter::Error {
+    fn from(e: DBusError) -> Self {
+        adapter::Error::IOError(e.io_error())
+    }
+}
+/// One `bluetoothd` controller (`/org/bluez/hciN`).
+pub struct BluezAdapter {
+    connection: zbus::Connection,
+    path: OwnedObjectPath,
+    scan_parameters: ScanParameters,
+    advertisement: advertisement::AdvertisementState,
+    monitors: monitor::MonitorState,
+}
+impl BluezAdapter {
+    /// Open controller `name` (`hci0`) on the system bus.
+    pub async fn open(name: &str) -> Result<BluezAdapter, DBusError> {
+        let connection = zbus::Connection::system().await?;
+        Self::with_connection(connection, name).await
+    }
+    /// Fails with [`IOError::NotFound`] (through [`DBusError::io_error`]) if `bluetoothd` doesn't
+    /// know controller `name`.
+    pub async fn with_connection(
+        connection: zbus::Connection,
+        name: &str,
+    ) -> Result<BluezAdapter, DBusError> {
+        let path = OwnedObjectPath::try_from(format!("/org/bluez/{}", name))?;
+        let adapter = BluezAdapter {
+            advertisement: advertisement::AdvertisementState::new(&path)?,
+            monitors: monitor::MonitorState::new(&path)?,
+            connection,
+            path,
+            scan_parameters: ScanParameters::DEFAULT,
+        };
+        // Make sure the adapter exists before handing it out.
+        adapter.property(ADAPTER_INTERFACE, "Address").await?;
+        Ok(adapter)
+    }
+    pub fn connection(&self) -> &zbus::Connection {
+        &self.connection
+    }
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+    /// Last parameters passed to [`Observer::set_scan_parameters`].
+    pub fn scan_parameters(&self) -> ScanParameters {
+        self.scan_parameters
+    }
+    pub(crate) async fn call<B>(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+        body: &B,
+    ) -> Result<std::sync::Arc<zbus::Message>, DBusError>
+    where
+        B: zbus::export::serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        Ok(self
+            .connection
+            .call_method(Some(SERVICE), path, Some(interface), method, body)
+            .await?)
+    }
+    /// `Get` one property of an object of `bluetoothd`.
+    pub async fn property(&self, interface: &str, name: &str) -> Result<OwnedValue, DBusError> {
+        let reply = self
+            .call(self.path(), PROPERTIES_INTERFACE, "Get", &(interface, name))
+            .await?;
+        Ok(reply.body()?)
+    }
+    pub async fn set_powered(&self, is_powered: bool) -> Result<(), DBusError> {
+        self.call(
+            self.path(),
+            PROPERTIES_INTERFACE,
+            "Set",
+            &(ADAPTER_INTERFACE, "Powered", Value::from(is_powered)),
+        )
+        .await?;
+        Ok(())
+    }
+    /// LE only discovery. `bluetoothd` only repeats reports of the same device when
+    /// `filter_duplicates` is `false`. Active scanning is `bluetoothd`'s default and can't be
+    /// turned off (passive scanning isn't exposed).
+    pub async fn set_discovery_filter(&self, filter_duplicates: bool) -> Result<(), DBusError> {
+        let mut filter = HashMap::new();
+        filter.insert("Transport", Value::from("le"));
+        filter.insert("DuplicateData", Value::from(!filter_duplicates));
+        self.call(
+            self.path(),
+            ADAPTER_INTERFACE,
+            "SetDiscoveryFilter",
+            &(filter,),
+        )
+        .await?;
+        Ok(())
+    }
+    pub async fn set_discovery_enable(&self, is_enabled: bool) -> Result<(), DBusError> {
+        let method = if is_enabled {
+            "StartDiscovery"
+        } else {
+            "StopDiscovery"
+        };
+        self.call(self.path(), ADAPTER_INTERFACE, method, &())
+            .await?;
+        Ok(())
+    }
+    /// Reports rebuilt from devices appearing (`InterfacesAdded`) and their advertising
+    /// properties changing (`PropertiesChanged`). Only delivers anything while discovery is
+    /// enabled.
+    pub async fn report_stream(
+        &self,
+    ) -> Result<LocalBoxStream<'_, Result<ReportInfo, DBusError>>, DBusError> {
+        let changed = zbus::MatchRule::builder()
+            .msg_type(zbus::MessageType::Signal)
+            .sender(SERVICE)?
+            .interface(PROPERTIES_INTERFACE)?
+            .member("PropertiesChanged")?
+            .path_namespace(self.path())?
+            .build();
+        let added = zbus::MatchRule::builder()
+            .msg_type(zbus::MessageType::Signal)
+            .sender(SERVICE)?
+            .interface(OBJECT_MANAGER_INTERFACE)?
+            .member("InterfacesAdded")?
+            .build();
+        let changed = zbus::MessageStream::for_match_rule(changed, &self.connection, None).await?;
+        let added = zbus::MessageStream::for_match_rule(added, &self.connection, None).await?;
+        let signals = futures_util::stream::select(changed, added);
+        // `PropertiesChanged` doesn't repeat the address type so it's remembered per device.
+        let addresses: HashMap<OwnedObjectPath, DeviceAddress> = HashMap::new();
+        Ok(Box::pin(futures_util::stream::unfold(
+            (signals, addresses),
+            move |(mut signals, mut addresses)| async move {
+                loop {
+                    let message = match signals.next().await? {
+                        Ok(message) => message,
+                        Err(e) => return Some((Err(DBusError(e)), (signals, addresses))),
+                    };
+                    match self.report_from_signal(&message, &mut addresses).await {
+                        Ok(Some(report)) => return Some((Ok(report), (signals, addresses))),
+                        Ok(None) => (),
+                        Err(e) => return Some((Err(e), (signals, addresses))),
+                    }
+                }
+            },
+        )))
+    }
+    async fn report_from_signal(
+        &self,
+        message: &zbus::Message,
+        addresses: &mut HashMap<OwnedObjectPath, DeviceAddress>,
+    ) -> Result<Option<ReportInfo>, DBusError> {
+        let header = message.header()?;
+        match header.member()?.map(|m| m.as_str()) {
+            Some("InterfacesAdded") => {
+                let (path, mut interfaces): (OwnedObjectPath, HashMap<String, Properties>) =
+                    message.body()?;
+                if !path.as_str().starts_with(self.path()) {
+                    return Ok(None);
+                }
+                let properties = match interfaces.remove(DEVICE_INTERFACE) {
+                    Some(properties) => properties,
+                    None => return Ok(None),
+                };
+                let address = match device::device_address(&properties) {
+                    Some(address) => address,
+                    None => return Ok(None),
+                };
+                addresses.insert(path, address);
+                Ok(device::report_from_properties(address, &properties))
+            }
+            Some("PropertiesChanged") => {
+                let (interface, properties, _): (String, Properties, Vec<String>) =
+                    message.body()?;
+                let path = match header.path()? {
+                    Some(path) if interface == DEVICE_INTERFACE => {
+                        OwnedObjectPath::from(path.to_owned())
+                    }
+                    _ => return Ok(None),
+                };
+                if !device::has_advertising_data(&properties) {
+                    return Ok(None);
+                }
+                let address = match addresses.get(&path) {
+                    Some(address) => *address,
+                    None => {
+                        let address_type = self
+                            .call(
+                                path.as_str(),
+                                PROPERTIES_INTERFACE,
+                                "Get",
+                                &(DEVICE_INTERFACE, "AddressType"),
+                            )
+                            .await?
+                            .body::<OwnedValue>()?;
+                        let address = device::address_from_path(path.as_str())
+                            .zip(device::is_random_address_type(&address_type))
+                            .map(|(address, is_random)| DeviceAddress::new(is_random, address));
+                        match address {
+                            Some(address) => {
+                                addresses.insert(path, address);
+                                address
+                            }
+                            None => return Ok(None),
+                        }
+                    }
+                };
+                Ok(device::report_from_properties(address, &properties))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+impl Backend for BluezAdapter {
+    /// `bluetoothd` always scans actively, owns the White List and only takes legacy sized
+    /// advertising data. Connections aren't exposed yet.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_advertising: true,
+            supports_active_scan: true,
+            max_advertising_data_len: MAX_ADV_LEN,
+            ..Capabilities::NONE
+        }
+    }
+}
+impl Observer for BluezAdapter {
+    /// `bluetoothd` picks the scan parameters itself (and always scans actively) so they're
+    /// only remembered. See [`BluezAdapter::scan_parameters`].
+    fn set_scan_parameters<'a>(
+        &'a mut self,
+        scan_parameters: ScanParameters,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            self.scan_parameters = scan_parameters;
+            Ok(())
+        })
+    }
+
+    fn set_scan_enable<'a>(
+        &'a mut self,
+        is_enabled: bool,
+        filter_duplicates: bool,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            if is_enabled {
+                self.set_discovery_filter(filter_duplicates).await?;
+            }
+            self.set_discovery_enable(is_enabled).await?;
+            Ok(())
+        })
+    }
+
+    fn advertisement_stream<'a>(
+        &'a mut self,
+    ) -> LocalBoxFuture<
+        'a,
+        Result<
+            LocalBoxStream<'a, Result<ReportInfo<StaticAdvBuffer>, adapter::Error>>,
+            adapter::Error,
+        >,
+    > {
+        Box::pin(async move {
+            let reports = self.report_stream().await?;
+            Ok(Box::pin(reports.map(|r| r.map_err(adapter::Error::from))) as LocalBoxStream<'a, _>)
+        })
+    }
+}
+impl Advertiser for BluezAdapter {
+    /// Registers (or unregisters) the advertisement with `bluetoothd`. Changing the data or
+    /// parameters while enabled registers it again.
+    fn set_advertising_enable<'a>(
+        &'a mut self,
+        is_enabled: bool,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            if is_enabled {
+                self.register_advertisement().await?;
+            } else {
+                self.unregister_advertisement().await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn set_advertising_parameters<'a>(
+        &'a mut self,
+        advertising_parameters: AdvertisingParameters,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            self.advertisement
+                .set_parameters(advertising_parameters)
+                .map_err(|_| adapter::Error::BadParameter)?;
+            self.refresh_advertisement().await?;
+            Ok(())
+        })
+    }
+
+    fn set_advertising_data<'d, 'a: 'd>(
+        &'a mut self,
+        data: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            self.advertisement
+                .set_data(data)
+                .map_err(|_| adapter::Error::BadParameter)?;
+            self.refresh_advertisement().await?;
+            Ok(())
+        })
+    }
+
+    fn set_scan_response_data<'d, 'a: 'd>(
+        &'a mut self,
+        data: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            self.advertisement
+                .set_scan_response(data)
+                .map_err(|_| adapter::Error::BadParameter)?;
+            self.refresh_advertisement().await?;
+            Ok(())
+        })
+    }
+
+    /// `bluetoothd` owns the White List (it fills it from the paired devices) so it can't be
+    /// set through D-Bus.
+    fn set_whitelist<'d, 'a: 'd>(
+        &'a mut self,
+        _entries: &'d [WhitelistEntry],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+        Box::pin(async move { Err(adapter::Error::IOError(IOError::NotImplemented)) })
+    }
+}
@@ -0,0 +1,390 @@
+//! Advertisement Monitor offload (`org.bluez.AdvertisementMonitorManager1`). Patterns are handed
+//! to `bluetoothd`, which filters advertisements itself (or has the controller do it with the
+//! MSFT/AOSP extensions) so the process only wakes up when a matching device is found or lost
+//! instead of for every report. `bluetoothd` keeps updating the `org.bluez.Device1` properties of
+//! matching devices so [`BluezAdapter::report_stream`] still delivers their reports without
+//! discovery being enabled.
+use super::{BluezAdapter, DBusError, DEVICE_INTERFACE, PROPERTIES_INTERFACE, SERVICE};
+use crate::bytes::ToFromBytesEndian;
+use crate::le::advertisement::{AdType, MAX_ADV_LEN};
+use crate::{CompanyID, ConversionError, DeviceAddress, Stream};
+use core::convert::TryFrom;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use zbus::fdo;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+
+pub const MONITOR_MANAGER_INTERFACE: &str = "org.bluez.AdvertisementMonitorManager1";
+/// The only monitor type `bluetoothd` supports so far.
+pub const OR_PATTERNS: &str = "or_patterns";
+/// `content` must be found in the data of an AD structure of type `ad_type`, starting at
+/// `start_position`.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct MonitorPattern {
+    pub start_position: u8,
+    pub ad_type: u8,
+    pub content: Vec<u8>,
+}
+impl MonitorPattern {
+    /// `content` has to fit in one legacy advertisement after `start_position`.
+    pub fn new(
+        start_position: u8,
+        ad_type: AdType,
+        content: &[u8],
+    ) -> Result<MonitorPattern, ConversionError> {
+        if content.is_empty() || usize::from(start_position) + content.len() > MAX_ADV_LEN {
+            return Err(ConversionError(()));
+        }
+        Ok(MonitorPattern {
+            start_position,
+            ad_type: ad_type.into(),
+            content: content.to_vec(),
+        })
+    }
+    /// Manufacturer data from `company_id` starting with `prefix`.
+    pub fn manufacturer_data(
+        company_id: CompanyID,
+        prefix: &[u8],
+    ) -> Result<MonitorPattern, ConversionError> {
+        let mut content = company_id.to_bytes_le().to_vec();
+        content.extend_from_slice(prefix);
+        Self::new(0, AdType::ManufacturerData, &content)
+    }
+    /// Check a raw advertising payload the way `bluetoothd` does. Useful for checking the
+    /// patterns without a controller.
+    pub fn matches(&self, payload: &[u8]) -> bool {
+        let mut rest = payload;
+        while rest.len() >= 2 {
+            let len = usize::from(rest[0]);
+            if len == 0 || len >= rest.len() {
+                return false;
+            }
+            let data = &rest[2..=len];
+            let start = usize::from(self.start_position);
+            if rest[1] == self.ad_type
+                && data.get(start..start + self.content.len()) == Some(self.content.as_slice())
+            {
+                return true;
+            }
+            rest = &rest[len + 1..];
+        }
+        false
+    }
+}
+/// RSSI hysteresis of a monitor. A device is found once its RSSI stays at or above
+/// `high_threshold` for `high_timeout` seconds and lost once it stays below `low_threshold` for
+/// `low_timeout` seconds.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct RssiFilter {
+    pub high_threshold: i16,
+    pub high_timeout: u16,
+    pub low_threshold: i16,
+    pub low_timeout: u16,
+    /// How often (in 100 ms units) matching advertisements are forwarded. `0` forwards all of
+    /// them, `0xFF` only the first.
+    pub sampling_period: u16,
+}
+/// Patterns (any of them matching is a match) and optional RSSI filter of one monitor.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct MonitorConfig {
+    pub patterns: Vec<MonitorPattern>,
+    pub rssi: Option<RssiFilter>,
+}
+impl MonitorConfig {
+    pub fn new(patterns: Vec<MonitorPattern>) -> MonitorConfig {
+        MonitorConfig {
+            patterns,
+            rssi: None,
+        }
+    }
+    pub fn matches(&self, payload: &[u8]) -> bool {
+        self.patterns.iter().any(|p| p.matches(payload))
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct MonitorHandle(pub u16);
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum MonitorEvent {
+    /// `bluetoothd` accepted the monitor.
+    Activated,
+    DeviceFound(DeviceAddress),
+    DeviceLost(DeviceAddress),
+    /// `bluetoothd` dropped the monitor (invalid patterns, adapter removed, ...).
+    Released,
+}
+#[derive(Default)]
+struct EventQueueInner {
+    events: VecDeque<(MonitorHandle, MonitorEvent)>,
+    waker: Option<Waker>,
+}
+/// Events of every monitor of an adapter, pushed from the D-Bus method handlers.
+#[derive(Clone, Default)]
+struct EventQueue(Arc<Mutex<EventQueueInner>>);
+impl EventQueue {
+    fn push(&self, handle: MonitorHandle, event: MonitorEvent) {
+        let mut inner = self.0.lock().expect("monitor event queue poisoned");
+        inner.events.push_back((handle, event));
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+}
+/// Stream of [`MonitorEvent`]s from [`BluezAdapter::monitor_events`]. Never ends.
+pub struct MonitorEvents(EventQueue);
+impl Stream for MonitorEvents {
+    type Item = (MonitorHandle, MonitorEvent);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut inner = (self.0).0.lock().expect("monitor event queue poisoned");
+        match inner.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None => {
+                inner.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+/// The exported `org.bluez.AdvertisementMonitor1` object.
+struct AdvertisementMonitor {
+    handle: MonitorHandle,
+    config: MonitorConfig,
+    events: EventQueue,
+}
+impl AdvertisementMonitor {
+    fn rssi<T>(&self, name: &str, f: impl Fn(&RssiFilter) -> T) -> fdo::Result<T> {
+        self.config
+            .rssi
+            .as_ref()
+            .map(f)
+            .ok_or_else(|| fdo::Error::UnknownProperty(name.into()))
+    }
+    async fn device_address(
+        connection: &zbus::Connection,
+        device: &ObjectPath<'_>,
+    ) -> Option<DeviceAddress> {
+        let address = super::device::address_from_path(device.as_str())?;
+        let reply = connection
+            .call_method(
+                Some(SERVICE),
+                device,
+                Some(PROPERTIES_INTERFACE),
+                "Get",
+                &(DEVICE_INTERFACE, "AddressType"),
+            )
+            .await
+            .ok()?;
+        let address_type: OwnedValue = reply.body().ok()?;
+        let is_random = super::device::is_random_address_type(&address_type)?;
+        Some(DeviceAddress::new(is_random, address))
+    }
+}
+#[zbus::dbus_interface(name = "org.bluez.AdvertisementMonitor1")]
+impl AdvertisementMonitor {
+    fn release(&self) {
+        self.events.push(self.handle, MonitorEvent::Released);
+    }
+    fn activate(&self) {
+        self.events.push(self.handle, MonitorEvent::Activated);
+    }
+    // The device object exists by the time `bluetoothd` calls these. If it's already gone
+    // there's nothing to report anyway.
+    async fn device_found(
+        &self,
+        device: ObjectPath<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) {
+        if let Some(address) = Self::device_address(connection, &device).await {
+            self.events
+                .push(self.handle, MonitorEvent::DeviceFound(address));
+        }
+    }
+    async fn device_lost(
+        &self,
+        device: ObjectPath<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) {
+        if let Some(address) = Self::device_address(connection, &device).await {
+            self.events
+                .push(self.handle, MonitorEvent::DeviceLost(address));
+        }
+    }
+    #[dbus_interface(property, name = "Type")]
+    fn kind(&self) -> String {
+        OR_PATTERNS.into()
+    }
+    #[dbus_interface(property)]
+    fn patterns(&self) -> Vec<(u8, u8, Vec<u8>)> {
+        self.config
+            .patterns
+            .iter()
+            .map(|p| (p.start_position, p.ad_type, p.content.clone()))
+            .collect()
+    }
+    #[dbus_interface(property, name = "RSSIHighThreshold")]
+    fn rssi_high_threshold(&self) -> fdo::Result<i16> {
+        self.rssi("RSSIHighThreshold", |r| r.high_threshold)
+    }
+    #[dbus_interface(property, name = "RSSIHighTimeout")]
+    fn rssi_high_timeout(&self) -> fdo::Result<u16> {
+        self.rssi("RSSIHighTimeout", |r| r.high_timeout)
+    }
+    #[dbus_interface(property, name = "RSSILowThreshold")]
+    fn rssi_low_threshold(&self) -> fdo::Result<i16> {
+        self.rssi("RSSILowThreshold", |r| r.low_threshold)
+    }
+    #[dbus_interface(property, name = "RSSILowTimeout")]
+    fn rssi_low_timeout(&self) -> fdo::Result<u16> {
+        self.rssi("RSSILowTimeout", |r| r.low_timeout)
+    }
+    #[dbus_interface(property, name = "RSSISamplingPeriod")]
+    fn rssi_sampling_period(&self) -> fdo::Result<u16> {
+        self.rssi("RSSISamplingPeriod", |r| r.sampling_period)
+    }
+}
+/// Monitors of one [`BluezAdapter`]. They all live under one object tree (`/btle/<adapter
+/// name>/monitors`) registered with `bluetoothd` when the first monitor is added.
+pub(crate) struct MonitorState {
+    root: OwnedObjectPath,
+    handles: Vec<MonitorHandle>,
+    next_handle: u16,
+    is_registered: bool,
+    events: EventQueue,
+}
+impl MonitorState {
+    pub(crate) fn new(adapter_path: &ObjectPath<'_>) -> Result<MonitorState, DBusError> {
+        let name = adapter_path.as_str().rsplit('/').next().unwrap_or_default();
+        Ok(MonitorState {
+            root: OwnedObjectPath::try_from(format!("/btle/{}/monitors", name))?,
+            handles: Vec::new(),
+            next_handle: 0,
+            is_registered: false,
+            events: EventQueue::default(),
+        })
+    }
+    fn path(&self, handle: MonitorHandle) -> Result<OwnedObjectPath, DBusError> {
+        Ok(OwnedObjectPath::try_from(format!(
+            "{}/{}",
+            self.root.as_str(),
+            handle.0
+        ))?)
+    }
+}
+impl BluezAdapter {
+    /// Whether `bluetoothd` (5.65+, or older started with `--experimental`) supports pattern
+    /// monitors on this adapter.
+    pub async fn supports_monitors(&self) -> Result<bool, DBusError> {
+        let types = match self
+            .property(MONITOR_MANAGER_INTERFACE, "SupportedMonitorTypes")
+            .await
+        {
+            Ok(types) => types,
+            Err(e) if e.io_error() == crate::error::IOError::NotFound => return Ok(false),
+            Err(e) if matches!(e.0, zbus::Error::MethodError(_, _, _)) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let types = Vec::<String>::try_from(types)?;
+        Ok(types.iter().any(|t| t == OR_PATTERNS))
+    }
+    /// Hand `config` to `bluetoothd`. Results come through [`BluezAdapter::monitor_events`]
+    /// ([`MonitorEvent::Released`] straight away if `bluetoothd` rejects the patterns).
+    pub async fn add_monitor(&mut self, config: MonitorConfig) -> Result<MonitorHandle, DBusError> {
+        if config.patterns.is_empty() {
+            return Err(DBusError(zbus::Error::MissingParameter("patterns")));
+        }
+        let handle = MonitorHandle(self.monitors.next_handle);
+        let path = self.monitors.path(handle)?;
+        let monitor = AdvertisementMonitor {
+            handle,
+            config,
+            events: self.monitors.events.clone(),
+        };
+        let server = self.connection.object_server();
+        if !self.monitors.is_registered {
+            server.at(&self.monitors.root, fdo::ObjectManager).await?;
+        }
+        // Added under the registered root, `bluetoothd` picks it up from `InterfacesAdded`.
+        server.at(&path, monitor).await?;
+        if !self.monitors.is_registered {
+            let registered = self
+                .call(
+                    self.path(),
+                    MONITOR_MANAGER_INTERFACE,
+                    "RegisterMonitor",
+                    &(&self.monitors.root,),
+                )
+                .await;
+            if let Err(e) = registered {
+                server.remove::<AdvertisementMonitor, _>(&path).await?;
+                server
+                    .remove::<fdo::ObjectManager, _>(&self.monitors.root)
+                    .await?;
+                return Err(e);
+            }
+            self.monitors.is_registered = true;
+        }
+        self.monitors.next_handle = self.monitors.next_handle.wrapping_add(1);
+        self.monitors.handles.push(handle);
+        Ok(handle)
+    }
+    /// Stop monitoring with `handle`. The object tree is unregistered with the last monitor.
+    pub async fn remove_monitor(&mut self, handle: MonitorHandle) -> Result<(), DBusError> {
+        let index = match self.monitors.handles.iter().position(|h| *h == handle) {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        self.monitors.handles.remove(index);
+        let server = self.connection.object_server();
+        server
+            .remove::<AdvertisementMonitor, _>(&self.monitors.path(handle)?)
+            .await?;
+        if self.monitors.handles.is_empty() && self.monitors.is_registered {
+            self.monitors.is_registered = false;
+            let unregistered = self
+                .call(
+                    self.path(),
+                    MONITOR_MANAGER_INTERFACE,
+                    "UnregisterMonitor",
+                    &(&self.monitors.root,),
+                )
+                .await;
+            server
+                .remove::<fdo::ObjectManager, _>(&self.monitors.root)
+                .await?;
+            unregistered?;
+        }
+        Ok(())
+    }
+    pub fn monitors(&self) -> &[MonitorHandle] {
+        &self.monitors.handles
+    }
+    /// Events of every monitor of this adapter. Every call returns a view of the same queue so
+    /// only one stream should be polled.
+    pub fn monitor_events(&self) -> MonitorEvents {
+        MonitorEvents(self.monitors.events.clone())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_monitor_pattern() {
+        let ibeacon = MonitorPattern::manufacturer_data(CompanyID(0x004C), &[0x02, 0x15]).unwrap();
+        assert_eq!(ibeacon.content, alloc::vec![0x4C, 0x00, 0x02, 0x15]);
+        let config = MonitorConfig::new(alloc::vec![
+            ibeacon,
+            MonitorPattern::new(0, AdType::ServiceData, &[0xAA, 0xFE]).unwrap(),
+        ]);
+        assert!(config.matches(&[0x02, 0x01, 0x06, 0x05, 0xFF, 0x4C, 0x00, 0x02, 0x15]));
+        assert!(config.matches(&[0x04, 0x16, 0xAA, 0xFE, 0x10]));
+        assert!(!config.matches(&[0x05, 0xFF, 0x4C, 0x00, 0x12, 0x15]));
+        // Content past the end of the structure.
+        assert!(!config.matches(&[0x02, 0x16, 0xAA]));
+        assert_eq!(
+            MonitorPattern::new(30, AdType::ManufacturerData, &[1, 2]),
+            Err(ConversionError(()))
+        );
+    }
+}
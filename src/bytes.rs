@@ -394,3 +394,105 @@ impl<T: Copy + Unpin + Default, ArrayBuf: AsRef<[T]> + AsMut<[T]> + Default + Co
         self.len
     }
 }
+const HEX_UPPER: &[u8; 16] = b"0123456789ABCDEF";
+/// Marks non hex digits in [`HEX_VALUES`].
+const NOT_HEX: u8 = 0xFF;
+/// ASCII byte to hex digit value lookup table.
+const HEX_VALUES: [u8; 256] = {
+    let mut table = [NOT_HEX; 256];
+    let mut i = 0;
+    while i < 10 {
+        table[b'0' as usize + i] = i as u8;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 6 {
+        table[b'a' as usize + i] = 10 + i as u8;
+        table[b'A' as usize + i] = 10 + i as u8;
+        i += 1;
+    }
+    table
+};
+/// Encode `bytes` as uppercase hex into `out` (`out.len()` must be `bytes.len() * 2`). Works on
+/// whole output pairs so the loop has no per-byte bounds checks.
+pub fn encode_hex(bytes: &[u8], out: &mut [u8]) -> Result<(), BufError> {
+    if out.len() != bytes.len() * 2 {
+        return Err(BufError::OutOfRange(out.len()));
+    }
+    for (pair, &b) in out.chunks_exact_mut(2).zip(bytes) {
+        pair[0] = HEX_UPPER[usize::from(b >> 4)];
+        pair[1] = HEX_UPPER[usize::from(b & 0x0F)];
+    }
+    Ok(())
+}
+/// Decode (upper or lowercase) hex digits into `out` (`hex.len()` must be `out.len() * 2`).
+/// Returns `BufError::BadBytes(index)` with the index of the first non hex digit.
+pub fn decode_hex(hex: &[u8], out: &mut [u8]) -> Result<(), BufError> {
+    if hex.len() != out.len() * 2 {
+        return Err(BufError::OutOfRange(hex.len()));
+    }
+    for (i, (pair, o)) in hex.chunks_exact(2).zip(out.iter_mut()).enumerate() {
+        let high = HEX_VALUES[usize::from(pair[0])];
+        let low = HEX_VALUES[usize::from(pair[1])];
+        // Digits are `0..=15` so any high bit set means a bad digit (one branch per pair).
+        if (high | low) & 0xF0 != 0 {
+            let index = if high == NOT_HEX { i * 2 } else { i * 2 + 1 };
+            return Err(BufError::BadBytes(index));
+        }
+        *o = (high << 4) | low;
+    }
+    Ok(())
+}
+/// Longest prefix of `name` that's at most `max_len` bytes and still valid UTF-8.
+pub fn truncate_name(name: &str, max_len: usize) -> &str {
+    if name.len() <= max_len {
+        return name;
+    }
+    let mut end = max_len;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+/// Formats a byte slice as uppercase hex (no separators) without allocating.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct HexSlice<'a>(pub &'a [u8]);
+impl<'a> core::fmt::Display for HexSlice<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Encode in chunks so `f` is written a few times instead of once per byte.
+        let mut buf = [0_u8; 64];
+        for chunk in self.0.chunks(buf.len() / 2) {
+            let out = &mut buf[..chunk.len() * 2];
+            encode_hex(chunk, out).expect("out sized for chunk");
+            f.write_str(core::str::from_utf8(out).expect("hex digits are ASCII"))?;
+        }
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_hex() {
+        let bytes = [0x00, 0x1F, 0xAB, 0xFF];
+        let mut hex = [0_u8; 8];
+        encode_hex(&bytes, &mut hex).unwrap();
+        assert_eq!(&hex, b"001FABFF");
+        let mut out = [0_u8; 4];
+        decode_hex(b"001fAbFF", &mut out).unwrap();
+        assert_eq!(out, bytes);
+        assert_eq!(
+            decode_hex(b"00x1ABFF", &mut out),
+            Err(BufError::BadBytes(2))
+        );
+        assert_eq!(
+            decode_hex(b"001", &mut out),
+            Err(BufError::OutOfRange(3))
+        );
+        let long: Vec<u8> = (0..=255).collect();
+        let formatted = alloc::format!("{}", HexSlice(&long));
+        assert_eq!(formatted.len(), 512);
+        assert!(formatted.starts_with("000102"));
+        assert!(formatted.ends_with("FEFF"));
+    }
+}
@@ -0,0 +1,341 @@
+//! Class of Device (Assigned Numbers 2.8). 24 bits sent in inquiry results and EIR data: major
+//! service class bits (13..=23), the major device class (8..=12) and a minor device class (2..=7)
+//! whose meaning depends on the major class.
+use crate::le::advertisement::{
+    AdStructureType, AdType, ConstAdStructType, UnpackableAdStructType,
+};
+use crate::{ConversionError, PackError};
+use core::convert::TryFrom;
+use core::fmt::Formatter;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum ServiceClass {
+    LimitedDiscoverableMode = 13,
+    LEAudio = 14,
+    Positioning = 16,
+    Networking = 17,
+    Rendering = 18,
+    Capturing = 19,
+    ObjectTransfer = 20,
+    Audio = 21,
+    Telephony = 22,
+    Information = 23,
+}
+impl ServiceClass {
+    pub const ALL: [ServiceClass; 10] = [
+        ServiceClass::LimitedDiscoverableMode,
+        ServiceClass::LEAudio,
+        ServiceClass::Positioning,
+        ServiceClass::Networking,
+        ServiceClass::Rendering,
+        ServiceClass::Capturing,
+        ServiceClass::ObjectTransfer,
+        ServiceClass::Audio,
+        ServiceClass::Telephony,
+        ServiceClass::Information,
+    ];
+    /// Bit in the Class of Device.
+    pub const fn mask(self) -> u32 {
+        1_u32 << (self as u8)
+    }
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ServiceClass::LimitedDiscoverableMode => "Limited Discoverable Mode",
+            ServiceClass::LEAudio => "LE Audio",
+            ServiceClass::Positioning => "Positioning",
+            ServiceClass::Networking => "Networking",
+            ServiceClass::Rendering => "Rendering",
+            ServiceClass::Capturing => "Capturing",
+            ServiceClass::ObjectTransfer => "Object Transfer",
+            ServiceClass::Audio => "Audio",
+            ServiceClass::Telephony => "Telephony",
+            ServiceClass::Information => "Information",
+        }
+    }
+}
+impl core::fmt::Display for ServiceClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum MajorDeviceClass {
+    Miscellaneous = 0x00,
+    Computer = 0x01,
+    Phone = 0x02,
+    NetworkAccessPoint = 0x03,
+    AudioVideo = 0x04,
+    Peripheral = 0x05,
+    Imaging = 0x06,
+    Wearable = 0x07,
+    Toy = 0x08,
+    Health = 0x09,
+    Uncategorized = 0x1F,
+}
+impl MajorDeviceClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MajorDeviceClass::Miscellaneous => "Miscellaneous",
+            MajorDeviceClass::Computer => "Computer",
+            MajorDeviceClass::Phone => "Phone",
+            MajorDeviceClass::NetworkAccessPoint => "Network Access Point",
+            MajorDeviceClass::AudioVideo => "Audio/Video",
+            MajorDeviceClass::Peripheral => "Peripheral",
+            MajorDeviceClass::Imaging => "Imaging",
+            MajorDeviceClass::Wearable => "Wearable",
+            MajorDeviceClass::Toy => "Toy",
+            MajorDeviceClass::Health => "Health",
+            MajorDeviceClass::Uncategorized => "Uncategorized",
+        }
+    }
+}
+impl From<MajorDeviceClass> for u8 {
+    fn from(class: MajorDeviceClass) -> Self {
+        class as u8
+    }
+}
+impl TryFrom<u8> for MajorDeviceClass {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(MajorDeviceClass::Miscellaneous),
+            0x01 => Ok(MajorDeviceClass::Computer),
+            0x02 => Ok(MajorDeviceClass::Phone),
+            0x03 => Ok(MajorDeviceClass::NetworkAccessPoint),
+            0x04 => Ok(MajorDeviceClass::AudioVideo),
+            0x05 => Ok(MajorDeviceClass::Peripheral),
+            0x06 => Ok(MajorDeviceClass::Imaging),
+            0x07 => Ok(MajorDeviceClass::Wearable),
+            0x08 => Ok(MajorDeviceClass::Toy),
+            0x09 => Ok(MajorDeviceClass::Health),
+            0x1F => Ok(MajorDeviceClass::Uncategorized),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+impl core::fmt::Display for MajorDeviceClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+/// 24-bit Class of Device. The format type (bits 0..=1) is always `0b00`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ClassOfDevice(u32);
+impl ClassOfDevice {
+    pub const BYTE_LEN: usize = 3;
+    pub const MAX_U32: u32 = 0x00FF_FFFC;
+    pub const AD_TYPE: AdType = AdType::ClassOfDevice;
+    const MINOR_MAX: u8 = 0x3F;
+    /// Panics if `minor` is more than 6 bits.
+    pub fn new(major: MajorDeviceClass, minor: u8, services: &[ServiceClass]) -> ClassOfDevice {
+        assert!(
+            minor <= Self::MINOR_MAX,
+            "minor device class out of range (`{}`)",
+            minor
+        );
+        let services = services.iter().fold(0, |bits, s| bits | s.mask());
+        ClassOfDevice(services | u32::from(u8::from(major)) << 8 | u32::from(minor) << 2)
+    }
+    /// Returns `None` if `major` is reserved.
+    pub fn major_device_class(self) -> Option<MajorDeviceClass> {
+        MajorDeviceClass::try_from(((self.0 >> 8) & 0x1F) as u8).ok()
+    }
+    pub fn minor_device_class(self) -> u8 {
+        ((self.0 >> 2) & u32::from(Self::MINOR_MAX)) as u8
+    }
+    pub fn has_service(self, service: ServiceClass) -> bool {
+        self.0 & service.mask() != 0
+    }
+    pub fn services(self) -> impl Iterator<Item = ServiceClass> {
+        ServiceClass::ALL
+            .iter()
+            .copied()
+            .filter(move |&s| self.has_service(s))
+    }
+    /// Name of the minor device class for the major classes with a fixed list (`None` for
+    /// unknown or unassigned values).
+    pub fn minor_name(self) -> Option<&'static str> {
+        let minor = self.minor_device_class();
+        let name = match self.major_device_class()? {
+            MajorDeviceClass::Computer => match minor {
+                0x00 => "Uncategorized",
+                0x01 => "Desktop",
+                0x02 => "Server",
+                0x03 => "Laptop",
+                0x04 => "Handheld PC/PDA",
+                0x05 => "Palm-size PC/PDA",
+                0x06 => "Wearable computer",
+                0x07 => "Tablet",
+                _ => return None,
+            },
+            MajorDeviceClass::Phone => match minor {
+                0x00 => "Uncategorized",
+                0x01 => "Cellular",
+                0x02 => "Cordless",
+                0x03 => "Smartphone",
+                0x04 => "Wired modem or voice gateway",
+                0x05 => "Common ISDN access",
+                _ => return None,
+            },
+            MajorDeviceClass::AudioVideo => match minor {
+                0x00 => "Uncategorized",
+                0x01 => "Wearable Headset",
+                0x02 => "Hands-free",
+                0x04 => "Microphone",
+                0x05 => "Loudspeaker",
+                0x06 => "Headphones",
+                0x07 => "Portable Audio",
+                0x08 => "Car audio",
+                0x09 => "Set-top box",
+                0x0A => "HiFi Audio",
+                0x0B => "VCR",
+                0x0C => "Video Camera",
+                0x0D => "Camcorder",
+                0x0E => "Video Monitor",
+                0x0F => "Video Display and Loudspeaker",
+                0x10 => "Video Conferencing",
+                0x12 => "Gaming/Toy",
+                _ => return None,
+            },
+            // Bits 4..=5 are keyboard/pointing, bits 0..=3 the device type.
+            MajorDeviceClass::Peripheral => match minor >> 4 {
+                0b00 => "Not Keyboard/Not Pointing Device",
+                0b01 => "Keyboard",
+                0b10 => "Pointing device",
+                _ => "Combo keyboard/pointing device",
+            },
+            MajorDeviceClass::Wearable => match minor {
+                0x01 => "Wristwatch",
+                0x02 => "Pager",
+                0x03 => "Jacket",
+                0x04 => "Helmet",
+                0x05 => "Glasses",
+                _ => return None,
+            },
+            MajorDeviceClass::Toy => match minor {
+                0x01 => "Robot",
+                0x02 => "Vehicle",
+                0x03 => "Doll / Action figure",
+                0x04 => "Controller",
+                0x05 => "Game",
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(name)
+    }
+    pub fn pack_into(self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&self.0.to_le_bytes()[..Self::BYTE_LEN]);
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<ClassOfDevice, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        ClassOfDevice::try_from(u32::from_le_bytes([buf[0], buf[1], buf[2], 0]))
+            .map_err(|_| PackError::bad_index(0))
+    }
+}
+impl From<ClassOfDevice> for u32 {
+    fn from(class: ClassOfDevice) -> Self {
+        class.0
+    }
+}
+impl TryFrom<u32> for ClassOfDevice {
+    type Error = ConversionError;
+
+    /// Errors if `value` is more than 24 bits or the format type isn't `0b00`.
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        if value & !Self::MAX_U32 != 0 {
+            Err(ConversionError(()))
+        } else {
+            Ok(ClassOfDevice(value))
+        }
+    }
+}
+/// Like `Phone: Smartphone [Networking, Telephony]`.
+impl core::fmt::Display for ClassOfDevice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self.major_device_class() {
+            Some(major) => write!(f, "{}", major)?,
+            None => write!(f, "Reserved (0x{:02X})", (self.0 >> 8) & 0x1F)?,
+        }
+        match self.minor_name() {
+            Some(minor) => write!(f, ": {}", minor)?,
+            None => write!(f, ": 0x{:02X}", self.minor_device_class())?,
+        }
+        let mut services = self.services().peekable();
+        if services.peek().is_some() {
+            f.write_str(" [")?;
+            for (i, service) in services.enumerate() {
+                if i != 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", service)?;
+            }
+            f.write_str("]")?;
+        }
+        Ok(())
+    }
+}
+impl AdStructureType for ClassOfDevice {
+    fn ad_type(&self) -> AdType {
+        Self::AD_TYPE
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        ClassOfDevice::pack_into(*self, buf)
+    }
+}
+impl UnpackableAdStructType for ClassOfDevice {
+    fn unpack_from(ad_type: AdType, buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if ad_type != Self::AD_TYPE {
+            return Err(PackError::BadOpcode);
+        }
+        ClassOfDevice::unpack_from(buf)
+    }
+}
+impl ConstAdStructType for ClassOfDevice {
+    const AD_TYPE: AdType = AdType::ClassOfDevice;
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_class_of_device() {
+        // A typical smartphone.
+        let class = ClassOfDevice::unpack_from(&[0x0C, 0x02, 0x5A]).unwrap();
+        assert_eq!(class.major_device_class(), Some(MajorDeviceClass::Phone));
+        assert_eq!(class.minor_device_class(), 0x03);
+        assert!(class.has_service(ServiceClass::Telephony));
+        assert_eq!(
+            alloc::format!("{}", class),
+            "Phone: Smartphone [Networking, Capturing, Object Transfer, Telephony]"
+        );
+        let built = ClassOfDevice::new(
+            MajorDeviceClass::Phone,
+            0x03,
+            &[
+                ServiceClass::Networking,
+                ServiceClass::Capturing,
+                ServiceClass::ObjectTransfer,
+                ServiceClass::Telephony,
+            ],
+        );
+        assert_eq!(built, class);
+        let mut buf = [0_u8; 3];
+        built.pack_into(&mut buf).unwrap();
+        assert_eq!(buf, [0x0C, 0x02, 0x5A]);
+        assert!(ClassOfDevice::try_from(0x0000_0001).is_err());
+        assert!(ClassOfDevice::try_from(0x0100_0000).is_err());
+    }
+}
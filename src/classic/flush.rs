@@ -0,0 +1,92 @@
+//! Automatic flushing for time-sensitive ACL data. Instead of retransmitting a packet until it
+//! gets through, the controller drops it once its [`FlushTimeout`] expires and sends a
+//! [`crate::hci::baseband::FlushOccurred`] event. [`FlushPolicies`] holds the timeout for each
+//! connection and the hook called when data is dropped.
+use crate::hci::baseband::FlushTimeout;
+use crate::le::connection::ConnectionHandle;
+use alloc::vec::Vec;
+
+/// Called with the connection and the number of flushes on it so far. Lets the application
+/// resend, skip ahead or change the timeout.
+pub type FlushHook = fn(ConnectionHandle, u32);
+/// [`FlushTimeout`] for each connection. Connections without an override use `default`.
+#[derive(Clone, Debug, Default)]
+pub struct FlushPolicies {
+    pub default: FlushTimeout,
+    overrides: Vec<(ConnectionHandle, FlushTimeout)>,
+    flushes: Vec<(ConnectionHandle, u32)>,
+    pub on_flush: Option<FlushHook>,
+}
+impl FlushPolicies {
+    /// Never flush (the controller default).
+    pub fn new() -> FlushPolicies {
+        FlushPolicies::default()
+    }
+    /// Use `timeout` for `handle`. Applied by
+    /// [`crate::hci::adapters::classic::ClassicAdapter::apply_flush_policy`].
+    pub fn set(&mut self, handle: ConnectionHandle, timeout: FlushTimeout) {
+        match self.overrides.iter_mut().find(|(h, _)| *h == handle) {
+            Some((_, t)) => *t = timeout,
+            None => self.overrides.push((handle, timeout)),
+        }
+    }
+    /// Forget `handle` (when it disconnects).
+    pub fn clear(&mut self, handle: ConnectionHandle) {
+        self.overrides.retain(|(h, _)| *h != handle);
+        self.flushes.retain(|(h, _)| *h != handle);
+    }
+    pub fn timeout_for(&self, handle: ConnectionHandle) -> FlushTimeout {
+        self.overrides
+            .iter()
+            .find(|(h, _)| *h == handle)
+            .map_or(self.default, |(_, t)| *t)
+    }
+    /// Number of flushes reported on `handle`.
+    pub fn flush_count(&self, handle: ConnectionHandle) -> u32 {
+        self.flushes
+            .iter()
+            .find(|(h, _)| *h == handle)
+            .map_or(0, |(_, n)| *n)
+    }
+    /// Count a flush on `handle` and call the hook.
+    pub fn record_flush(&mut self, handle: ConnectionHandle) {
+        let count = match self.flushes.iter_mut().find(|(h, _)| *h == handle) {
+            Some((_, n)) => {
+                *n = n.saturating_add(1);
+                *n
+            }
+            None => {
+                self.flushes.push((handle, 1));
+                1
+            }
+        };
+        if let Some(hook) = self.on_flush {
+            hook(handle, count);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::time::Duration;
+    #[test]
+    fn test_flush_policies() {
+        let audio = ConnectionHandle::new(1);
+        let file = ConnectionHandle::new(2);
+        let mut policies = FlushPolicies::new();
+        let timeout = FlushTimeout::from_duration(Duration::from_millis(20)).unwrap();
+        assert_eq!(u16::from(timeout), 32);
+        assert_eq!(timeout.duration(), Some(Duration::from_millis(20)));
+        policies.set(audio, timeout);
+        assert_eq!(policies.timeout_for(audio), timeout);
+        assert_eq!(policies.timeout_for(file), FlushTimeout::INFINITE);
+        policies.record_flush(audio);
+        policies.record_flush(audio);
+        assert_eq!(policies.flush_count(audio), 2);
+        assert_eq!(policies.flush_count(file), 0);
+        policies.clear(audio);
+        assert_eq!(policies.timeout_for(audio), FlushTimeout::INFINITE);
+        assert_eq!(policies.flush_count(audio), 0);
+        assert_eq!(FlushTimeout::from_duration(Duration::from_secs(2)), None);
+    }
+}
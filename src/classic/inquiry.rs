@@ -0,0 +1,448 @@
+//! Inquiry result events. Every discovered device gets an [`InquiryResponse`] with its
+//! [`ClassOfDevice`]. [`ExtendedInquiryResult`] also carries the device's EIR data (same format as
+//! LE advertising data). [`Inquiry`] starts discovering and finishes with [`InquiryComplete`].
+use crate::bytes::Storage;
+use crate::classic::class_of_device::ClassOfDevice;
+use crate::hci::command::{Command, LongCommand};
+use crate::hci::event::{
+    CommandComplete, CommandStatus, Event, EventCode, StaticHCIBuffer, StatusReturn,
+};
+use crate::hci::link_control::LinkControlOpcode;
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::advertisement::RawAdvertisement;
+use crate::{BTAddress, PackError, BT_ADDRESS_LEN, RSSI};
+use core::convert::TryFrom;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct InquiryResponse {
+    pub address: BTAddress,
+    pub page_scan_repetition_mode: u8,
+    pub class_of_device: ClassOfDevice,
+    /// Bits 2..=16 of the difference between the device's clock and ours.
+    pub clock_offset: u16,
+    /// `None` for [`InquiryResult`] (no RSSI) or if the controller couldn't measure it.
+    pub rssi: Option<RSSI>,
+}
+impl Default for InquiryResponse {
+    fn default() -> Self {
+        InquiryResponse {
+            address: BTAddress::ZEROED,
+            page_scan_repetition_mode: 0,
+            class_of_device: ClassOfDevice::default(),
+            clock_offset: 0,
+            rssi: None,
+        }
+    }
+}
+/// Bytes per response in [`InquiryResult`] and [`InquiryResultWithRSSI`] (they have the same
+/// size, the RSSI replaces a reserved byte).
+const RESPONSE_LEN: usize = BT_ADDRESS_LEN + 1 + 2 + ClassOfDevice::BYTE_LEN + 2;
+/// Number of responses in a [`InquiryResult`] or [`InquiryResultWithRSSI`].
+fn responses_len(buf: &[u8], max_len: usize) -> Result<usize, PackError> {
+    let len = usize::from(*buf.get(0).ok_or(PackError::BadLength {
+        expected: 1,
+        got: 0,
+    })?);
+    if len > max_len {
+        return Err(PackError::bad_index(0));
+    }
+    PackError::expect_length(1 + RESPONSE_LEN * len, buf)?;
+    Ok(len)
+}
+/// Every field is an array with one entry per response. `with_rssi` has 1 reserved byte and an
+/// RSSI at the end instead of 2 reserved bytes. `out` must have one entry per response.
+fn unpack_responses(
+    buf: &[u8],
+    with_rssi: bool,
+    out: &mut [InquiryResponse],
+) -> Result<(), PackError> {
+    let len = out.len();
+    PackError::expect_length(1 + RESPONSE_LEN * len, buf)?;
+    let reserved_len = if with_rssi { 1 } else { 2 };
+    let (addresses, rest) = buf[1..].split_at(BT_ADDRESS_LEN * len);
+    let (repetition_modes, rest) = rest.split_at(len);
+    let (_, rest) = rest.split_at(reserved_len * len);
+    let (classes, rest) = rest.split_at(ClassOfDevice::BYTE_LEN * len);
+    let (clock_offsets, rssis) = rest.split_at(2 * len);
+    let classes_index = 1 + (BT_ADDRESS_LEN + 1 + reserved_len) * len;
+    for (i, response) in out.iter_mut().enumerate() {
+        let class = &classes[i * ClassOfDevice::BYTE_LEN..(i + 1) * ClassOfDevice::BYTE_LEN];
+        *response = InquiryResponse {
+            address: BTAddress::new(&addresses[i * BT_ADDRESS_LEN..(i + 1) * BT_ADDRESS_LEN]),
+            page_scan_repetition_mode: repetition_modes[i],
+            class_of_device: ClassOfDevice::unpack_from(class)
+                .map_err(|_| PackError::bad_index(classes_index + i * ClassOfDevice::BYTE_LEN))?,
+            clock_offset: u16::from_le_bytes([clock_offsets[2 * i], clock_offsets[2 * i + 1]]),
+            rssi: if with_rssi {
+                RSSI::maybe_rssi(rssis[i] as i8)
+                    .map_err(|_| PackError::bad_index(buf.len() - len + i))?
+            } else {
+                None
+            },
+        };
+    }
+    Ok(())
+}
+fn pack_responses(
+    responses: &[InquiryResponse],
+    with_rssi: bool,
+    buf: &mut [u8],
+) -> Result<(), PackError> {
+    let len = responses.len();
+    if len > usize::from(u8::MAX) {
+        return Err(PackError::InvalidFields);
+    }
+    PackError::expect_length(1 + RESPONSE_LEN * len, buf)?;
+    buf[0] = len as u8;
+    let reserved_len = if with_rssi { 1 } else { 2 };
+    let (addresses, rest) = buf[1..].split_at_mut(BT_ADDRESS_LEN * len);
+    let (repetition_modes, rest) = rest.split_at_mut(len);
+    let (reserved, rest) = rest.split_at_mut(reserved_len * len);
+    let (classes, rest) = rest.split_at_mut(ClassOfDevice::BYTE_LEN * len);
+    let (clock_offsets, rssis) = rest.split_at_mut(2 * len);
+    reserved.iter_mut().for_each(|b| *b = 0);
+    for (i, response) in responses.iter().enumerate() {
+        response
+            .address
+            .pack_into(&mut addresses[i * BT_ADDRESS_LEN..(i + 1) * BT_ADDRESS_LEN])?;
+        repetition_modes[i] = response.page_scan_repetition_mode;
+        response.class_of_device.pack_into(
+            &mut classes[i * ClassOfDevice::BYTE_LEN..(i + 1) * ClassOfDevice::BYTE_LEN],
+        )?;
+        clock_offsets[2 * i..2 * i + 2].copy_from_slice(&response.clock_offset.to_le_bytes());
+        if with_rssi {
+            rssis[i] = response
+                .rssi
+                .map(i8::from)
+                .unwrap_or(RSSI::UNSUPPORTED_RSSI) as u8;
+        }
+    }
+    Ok(())
+}
+/// Devices found by an inquiry (without RSSI).
+#[derive(Copy, Clone, Debug)]
+pub struct InquiryResult<T: AsRef<[InquiryResponse]>> {
+    pub responses: T,
+}
+impl<T: AsRef<[InquiryResponse]>> InquiryResult<T> {
+    pub fn new(responses: T) -> Self {
+        InquiryResult { responses }
+    }
+}
+impl<T: Storage<InquiryResponse>> Event for InquiryResult<T> {
+    const EVENT_CODE: EventCode = EventCode::InquiryResult;
+
+    fn event_byte_len(&self) -> usize {
+        1 + RESPONSE_LEN * self.responses.as_ref().len()
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        let mut responses = T::with_size(responses_len(buf, T::max_len())?);
+        unpack_responses(buf, false, responses.as_mut())?;
+        Ok(InquiryResult::new(responses))
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        pack_responses(self.responses.as_ref(), false, buf)
+    }
+}
+/// Devices found by an inquiry (with `InquiryMode::WithRSSI`).
+#[derive(Copy, Clone, Debug)]
+pub struct InquiryResultWithRSSI<T: AsRef<[InquiryResponse]>> {
+    pub responses: T,
+}
+impl<T: AsRef<[InquiryResponse]>> InquiryResultWithRSSI<T> {
+    pub fn new(responses: T) -> Self {
+        InquiryResultWithRSSI { responses }
+    }
+}
+impl<T: Storage<InquiryResponse>> Event for InquiryResultWithRSSI<T> {
+    const EVENT_CODE: EventCode = EventCode::InquiryResultWithRSSI;
+
+    fn event_byte_len(&self) -> usize {
+        1 + RESPONSE_LEN * self.responses.as_ref().len()
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        let mut responses = T::with_size(responses_len(buf, T::max_len())?);
+        unpack_responses(buf, true, responses.as_mut())?;
+        Ok(InquiryResultWithRSSI::new(responses))
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        pack_responses(self.responses.as_ref(), true, buf)
+    }
+}
+/// One device found by an inquiry with its EIR data (`InquiryMode::Extended`).
+#[derive(Copy, Clone, Debug)]
+pub struct ExtendedInquiryResult<B: AsRef<[u8]> = StaticHCIBuffer> {
+    pub response: InquiryResponse,
+    /// Significant part of the EIR data (the zero padding is dropped).
+    pub eir: RawAdvertisement<B>,
+}
+impl<B: AsRef<[u8]>> ExtendedInquiryResult<B> {
+    pub const EIR_LEN: usize = 240;
+    pub const BYTE_LEN: usize = 1 + RESPONSE_LEN + Self::EIR_LEN;
+    /// Length of the significant part of `eir` (up to the first zero length structure).
+    pub fn significant_len(eir: &[u8]) -> usize {
+        let mut len = 0;
+        while let Some(&struct_len) = eir.get(len) {
+            if struct_len == 0 || len + 1 + usize::from(struct_len) > eir.len() {
+                break;
+            }
+            len += 1 + usize::from(struct_len);
+        }
+        len
+    }
+}
+impl<B: Storage<u8>> Event for ExtendedInquiryResult<B> {
+    const EVENT_CODE: EventCode = EventCode::ExtendedInquiryResult;
+
+    fn event_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        if buf[0] != 1 {
+            return Err(PackError::bad_index(0));
+        }
+        let mut response = [InquiryResponse::default()];
+        unpack_responses(&buf[..1 + RESPONSE_LEN], true, &mut response)?;
+        let eir = &buf[1 + RESPONSE_LEN..];
+        let eir = &eir[..Self::significant_len(eir)];
+        if eir.len() > B::max_len() {
+            return Err(PackError::bad_index(1 + RESPONSE_LEN));
+        }
+        Ok(ExtendedInquiryResult {
+            response: response[0],
+            eir: RawAdvertisement(B::from_slice(eir)),
+        })
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let eir = self.eir.as_ref();
+        if eir.len() > Self::EIR_LEN {
+            return Err(PackError::InvalidFields);
+        }
+        pack_responses(
+            core::slice::from_ref(&self.response),
+            true,
+            &mut buf[..1 + RESPONSE_LEN],
+        )?;
+        let (data, padding) = buf[1 + RESPONSE_LEN..].split_at_mut(eir.len());
+        data.copy_from_slice(eir);
+        padding.iter_mut().for_each(|b| *b = 0);
+        Ok(())
+    }
+}
+/// Inquiry Access Code Lower Address Part. Only devices listening for this code answer.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct InquiryLAP(u32);
+impl InquiryLAP {
+    pub const BYTE_LEN: usize = 3;
+    /// General Inquiry Access Code. Every discoverable device.
+    pub const GIAC: InquiryLAP = InquiryLAP(0x9E8B33);
+    /// Limited Inquiry Access Code. Devices in limited discoverable mode.
+    pub const LIAC: InquiryLAP = InquiryLAP(0x9E8B00);
+    /// Returns `None` if `lap` is outside the reserved IAC range `0x9E8B00..=0x9E8B3F`.
+    pub fn new_checked(lap: u32) -> Option<InquiryLAP> {
+        if (Self::LIAC.0..=0x9E8B3F).contains(&lap) {
+            Some(InquiryLAP(lap))
+        } else {
+            None
+        }
+    }
+}
+impl From<InquiryLAP> for u32 {
+    fn from(lap: InquiryLAP) -> Self {
+        lap.0
+    }
+}
+/// `HCI_Inquiry`. Discover classic devices for `inquiry_length` (in units of 1.28 seconds).
+/// Devices are reported with [`InquiryResult`] events (or the RSSI/extended versions depending on
+/// the inquiry mode) and the inquiry ends with [`InquiryComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct Inquiry {
+    pub lap: InquiryLAP,
+    pub inquiry_length: u8,
+    /// Stop after this many responses. `0` is unlimited.
+    pub num_responses: u8,
+}
+impl Inquiry {
+    pub const OPCODE: LinkControlOpcode = LinkControlOpcode::Inquiry;
+    pub const BYTE_LEN: usize = InquiryLAP::BYTE_LEN + 1 + 1;
+    pub const MIN_LENGTH: u8 = 0x01;
+    pub const MAX_LENGTH: u8 = 0x30;
+    /// Inquiry with the [`InquiryLAP::GIAC`] (every discoverable device) and no response limit.
+    pub fn general(inquiry_length: u8) -> Inquiry {
+        Inquiry {
+            lap: InquiryLAP::GIAC,
+            inquiry_length,
+            num_responses: 0,
+        }
+    }
+}
+impl Command for Inquiry {
+    type Return = CommandStatus;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        if !(Self::MIN_LENGTH..=Self::MAX_LENGTH).contains(&self.inquiry_length) {
+            return Err(PackError::InvalidFields);
+        }
+        buf[..3].copy_from_slice(&self.lap.0.to_le_bytes()[..3]);
+        buf[3] = self.inquiry_length;
+        buf[4] = self.num_responses;
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let lap = u32::from_le_bytes([buf[0], buf[1], buf[2], 0]);
+        if !(Self::MIN_LENGTH..=Self::MAX_LENGTH).contains(&buf[3]) {
+            return Err(PackError::bad_index(3));
+        }
+        Ok(Inquiry {
+            lap: InquiryLAP::new_checked(lap).ok_or(PackError::bad_index(0))?,
+            inquiry_length: buf[3],
+            num_responses: buf[4],
+        })
+    }
+}
+impl LongCommand for Inquiry {
+    type Completion = InquiryComplete;
+    type Cancel = InquiryCancel;
+
+    fn cancel_command(&self) -> InquiryCancel {
+        InquiryCancel
+    }
+}
+/// `HCI_Inquiry_Cancel`. Stop an [`Inquiry`]. Unlike most cancels, no [`InquiryComplete`] is
+/// sent afterwards.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct InquiryCancel;
+impl InquiryCancel {
+    pub const OPCODE: LinkControlOpcode = LinkControlOpcode::InquiryCancel;
+}
+impl Command for InquiryCancel {
+    type Return = CommandComplete<StatusReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(InquiryCancel)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct InquiryComplete {
+    pub status: ErrorCode,
+}
+impl Event for InquiryComplete {
+    const EVENT_CODE: EventCode = EventCode::InquiryComplete;
+
+    fn event_byte_len(&self) -> usize {
+        ErrorCode::BYTE_LEN
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(ErrorCode::BYTE_LEN, buf)?;
+        Ok(InquiryComplete {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+        })
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(ErrorCode::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classic::class_of_device::{MajorDeviceClass, ServiceClass};
+    use crate::le::advertisement::{AdType, UnpackableAdStructType};
+    use alloc::vec::Vec;
+    #[test]
+    fn test_inquiry_results() {
+        let response = InquiryResponse {
+            address: BTAddress([1, 2, 3, 4, 5, 6]),
+            page_scan_repetition_mode: 1,
+            class_of_device: ClassOfDevice::new(
+                MajorDeviceClass::AudioVideo,
+                0x06,
+                &[ServiceClass::Audio, ServiceClass::Rendering],
+            ),
+            clock_offset: 0x1234,
+            rssi: Some(RSSI::new(-50)),
+        };
+        let result = InquiryResultWithRSSI::new(alloc::vec![response, response]);
+        let packet = result.event_pack_packet::<Vec<u8>>().unwrap();
+        let unpacked =
+            InquiryResultWithRSSI::<Vec<InquiryResponse>>::unpack_event_packet(&packet).unwrap();
+        assert_eq!(unpacked.responses, [response, response]);
+        assert!(InquiryResult::<Vec<InquiryResponse>>::unpack_event_packet(&packet).is_err());
+
+        let mut eir = Vec::new();
+        eir.extend_from_slice(&[4, AdType::ClassOfDevice.into()]);
+        eir.extend_from_slice(&[0x18, 0x04, 0x24]);
+        let extended = ExtendedInquiryResult {
+            response,
+            eir: RawAdvertisement(StaticHCIBuffer::from_slice(&eir)),
+        };
+        let packet = extended.event_pack_packet::<Vec<u8>>().unwrap();
+        assert_eq!(packet.parameters().len(), 255);
+        let unpacked =
+            ExtendedInquiryResult::<StaticHCIBuffer>::unpack_event_packet(&packet).unwrap();
+        assert_eq!(unpacked.response, response);
+        assert_eq!(unpacked.eir.as_ref(), &eir[..]);
+        let structure = unpacked.eir.iter().next().unwrap();
+        assert_eq!(
+            <ClassOfDevice as UnpackableAdStructType>::unpack_from(
+                structure.ad_type,
+                structure.buf.as_ref()
+            )
+            .unwrap(),
+            response.class_of_device
+        );
+    }
+}
@@ -0,0 +1,5 @@
+//! BR/EDR (Classic) Bluetooth.
+pub mod class_of_device;
+pub mod flush;
+pub mod inquiry;
+pub mod settings;
@@ -0,0 +1,293 @@
+//! BR/EDR controller settings from the Controller & Baseband group. Whether the controller can
+//! be found ([`ScanEnable`]) and how often it listens ([`ScanActivity`]), its
+//! [`ClassOfDevice`] and Secure Simple Pairing.
+use crate::classic::class_of_device::ClassOfDevice;
+use crate::hci::baseband::ControllerBasebandOpcode;
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::Opcode;
+use crate::{ConversionError, PackError};
+use core::convert::TryFrom;
+use core::time::Duration;
+
+/// Which scans the controller runs. Inquiry scan makes it discoverable, page scan connectable.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum ScanEnable {
+    NoScans = 0x00,
+    InquiryScan = 0x01,
+    PageScan = 0x02,
+    InquiryAndPageScan = 0x03,
+}
+impl ScanEnable {
+    pub fn is_discoverable(self) -> bool {
+        matches!(
+            self,
+            ScanEnable::InquiryScan | ScanEnable::InquiryAndPageScan
+        )
+    }
+    pub fn is_connectable(self) -> bool {
+        matches!(self, ScanEnable::PageScan | ScanEnable::InquiryAndPageScan)
+    }
+}
+impl From<ScanEnable> for u8 {
+    fn from(s: ScanEnable) -> Self {
+        s as u8
+    }
+}
+impl TryFrom<u8> for ScanEnable {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(ScanEnable::NoScans),
+            0x01 => Ok(ScanEnable::InquiryScan),
+            0x02 => Ok(ScanEnable::PageScan),
+            0x03 => Ok(ScanEnable::InquiryAndPageScan),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// `HCI_Write_Scan_Enable`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct WriteScanEnable {
+    pub scan_enable: ScanEnable,
+}
+impl WriteScanEnable {
+    pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::WriteScanEnable;
+}
+impl Command for WriteScanEnable {
+    type Return = CommandComplete<StatusReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        1
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(1, buf)?;
+        buf[0] = self.scan_enable.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(1, buf)?;
+        Ok(WriteScanEnable {
+            scan_enable: ScanEnable::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+        })
+    }
+}
+/// How often (`interval`) and how long (`window`) a page or inquiry scan listens, in 0.625ms
+/// slots. A longer window finds the controller faster but uses more power.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ScanActivity {
+    pub interval: u16,
+    pub window: u16,
+}
+impl ScanActivity {
+    pub const BYTE_LEN: usize = 4;
+    pub const SLOT: Duration = Duration::from_micros(625);
+    pub const INTERVAL_MIN: u16 = 0x0012;
+    pub const WINDOW_MIN: u16 = 0x0011;
+    pub const MAX: u16 = 0x1000;
+    /// Controller default, 11.25ms every 1.28s.
+    pub const DEFAULT: ScanActivity = ScanActivity {
+        interval: 0x0800,
+        window: 0x0012,
+    };
+    /// The interval must be even and at least as long as the window.
+    pub fn is_valid(self) -> bool {
+        (Self::INTERVAL_MIN..=Self::MAX).contains(&self.interval)
+            && self.interval & 1 == 0
+            && (Self::WINDOW_MIN..=self.interval).contains(&self.window)
+    }
+    pub fn interval_duration(self) -> Duration {
+        Self::SLOT * u32::from(self.interval)
+    }
+    pub fn window_duration(self) -> Duration {
+        Self::SLOT * u32::from(self.window)
+    }
+    pub fn pack_into(self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        if !self.is_valid() {
+            return Err(PackError::InvalidFields);
+        }
+        buf[..2].copy_from_slice(&self.interval.to_le_bytes());
+        buf[2..].copy_from_slice(&self.window.to_le_bytes());
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<ScanActivity, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let activity = ScanActivity {
+            interval: u16::from_le_bytes([buf[0], buf[1]]),
+            window: u16::from_le_bytes([buf[2], buf[3]]),
+        };
+        if activity.is_valid() {
+            Ok(activity)
+        } else {
+            Err(PackError::InvalidFields)
+        }
+    }
+}
+impl Default for ScanActivity {
+    fn default() -> Self {
+        ScanActivity::DEFAULT
+    }
+}
+/// Commands that only write a [`ScanActivity`].
+macro_rules! scan_activity_command {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+        pub struct $name {
+            pub activity: ScanActivity,
+        }
+        impl $name {
+            pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::$name;
+        }
+        impl Command for $name {
+            type Return = CommandComplete<StatusReturn>;
+
+            fn opcode() -> Opcode {
+                Self::OPCODE.into()
+            }
+
+            fn byte_len(&self) -> usize {
+                ScanActivity::BYTE_LEN
+            }
+
+            fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+                self.activity.pack_into(buf)
+            }
+
+            fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+            where
+                Self: Sized,
+            {
+                Ok($name {
+                    activity: ScanActivity::unpack_from(buf)?,
+                })
+            }
+        }
+    };
+}
+scan_activity_command!(
+    /// `HCI_Write_Page_Scan_Activity`. How often the controller listens for connections.
+    WritePageScanActivity
+);
+scan_activity_command!(
+    /// `HCI_Write_Inquiry_Scan_Activity`. How often the controller listens for inquiries.
+    WriteInquiryScanActivity
+);
+/// `HCI_Write_Class_Of_Device`. Sent in inquiry responses.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct WriteClassOfDevice {
+    pub class_of_device: ClassOfDevice,
+}
+impl WriteClassOfDevice {
+    pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::WriteClassOfDevice;
+}
+impl Command for WriteClassOfDevice {
+    type Return = CommandComplete<StatusReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ClassOfDevice::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.class_of_device.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(WriteClassOfDevice {
+            class_of_device: ClassOfDevice::unpack_from(buf)?,
+        })
+    }
+}
+/// `HCI_Write_Simple_Pairing_Mode`. Enables Secure Simple Pairing. It can't be disabled again
+/// without a Reset.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct WriteSimplePairingMode {
+    pub is_enabled: bool,
+}
+impl WriteSimplePairingMode {
+    pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::WriteSimplePairingMode;
+}
+impl Command for WriteSimplePairingMode {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V2_1;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        1
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(1, buf)?;
+        buf[0] = self.is_enabled.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(1, buf)?;
+        match buf[0] {
+            0 => Ok(WriteSimplePairingMode { is_enabled: false }),
+            1 => Ok(WriteSimplePairingMode { is_enabled: true }),
+            _ => Err(PackError::bad_index(0)),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_scan_activity() {
+        assert!(ScanActivity::DEFAULT.is_valid());
+        assert_eq!(
+            ScanActivity::DEFAULT.interval_duration(),
+            Duration::from_millis(1280)
+        );
+        let odd = ScanActivity {
+            interval: 0x0801,
+            window: 0x0012,
+        };
+        assert!(!odd.is_valid());
+        let too_wide = ScanActivity {
+            interval: 0x0012,
+            window: 0x0014,
+        };
+        assert_eq!(
+            WritePageScanActivity { activity: too_wide }
+                .pack_command_packet::<Box<[u8]>>()
+                .err(),
+            Some(PackError::InvalidFields)
+        );
+        let command = WriteInquiryScanActivity::default();
+        let packet = command.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(packet.parameters.as_ref(), &[0x00, 0x08, 0x12, 0x00]);
+        assert_eq!(
+            WriteInquiryScanActivity::unpack_command_packet(&packet),
+            Ok(command)
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Time sources. Anything that times out, expires or rotates takes `now: Duration` from a
+//! [`Clock`] so the same logic runs on `std` ([`StdClock`]), embedded (`EmbassyClock` with the
+//! `embassy` feature) and in tests ([`MockClock`]) where time only moves when told to. With `std`,
+//! [`sim`] runs async code on simulated time.
+use alloc::rc::Rc;
+use core::cell::Cell;
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+pub mod sim;
+
+/// Monotonic time since some fixed point (boot, clock creation, etc). Implemented for
+/// `Fn() -> Duration` closures and function pointers.
+pub trait Clock {
+    /// Current time. Never goes backwards.
+    fn now(&self) -> Duration;
+    /// Time since `earlier` (zero if `earlier` is in the future).
+    fn elapsed_since(&self, earlier: Duration) -> Duration {
+        self.now().checked_sub(earlier).unwrap_or_default()
+    }
+}
+impl<F: Fn() -> Duration> Clock for F {
+    fn now(&self) -> Duration {
+        self()
+    }
+}
+/// Point in time something expires, measured on a [`Clock`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct Deadline(pub Duration);
+impl Deadline {
+    /// `timeout` from now.
+    pub fn after<C: Clock>(clock: &C, timeout: Duration) -> Deadline {
+        Deadline(clock.now() + timeout)
+    }
+    pub fn is_expired<C: Clock>(self, clock: &C) -> bool {
+        clock.now() >= self.0
+    }
+    /// Time left until the deadline (zero if expired).
+    pub fn remaining<C: Clock>(self, clock: &C) -> Duration {
+        self.0.checked_sub(clock.now()).unwrap_or_default()
+    }
+}
+/// `std::time::Instant` based clock. `now` is the time since the clock was created.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug)]
+pub struct StdClock {
+    start: std::time::Instant,
+}
+#[cfg(feature = "std")]
+impl StdClock {
+    pub fn new() -> StdClock {
+        StdClock {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl Default for StdClock {
+    fn default() -> Self {
+        StdClock::new()
+    }
+}
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+/// `embassy_time::Instant` based clock. `now` is the time since boot.
+#[cfg(feature = "embassy")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EmbassyClock;
+#[cfg(feature = "embassy")]
+impl Clock for EmbassyClock {
+    fn now(&self) -> Duration {
+        Duration::from_micros(embassy_time::Instant::now().as_micros())
+    }
+}
+/// Clock that only moves when [`MockClock::advance`] or [`MockClock::set`] is called. Clones
+/// share the same time so one can be handed to the code under test and the other kept to drive it.
+#[derive(Clone, Debug, Default)]
+pub struct MockClock {
+    now: Rc<Cell<Duration>>,
+}
+impl MockClock {
+    pub fn new(start: Duration) -> MockClock {
+        MockClock {
+            now: Rc::new(Cell::new(start)),
+        }
+    }
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+    /// Jump to `now`. Panics if that would move the clock backwards.
+    pub fn set(&self, now: Duration) {
+        assert!(now >= self.now.get(), "clock can't go backwards");
+        self.now.set(now);
+    }
+}
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::default();
+        let handle = clock.clone();
+        let deadline = Deadline::after(&clock, Duration::from_millis(100));
+        handle.advance(Duration::from_millis(60));
+        assert_eq!(clock.now(), Duration::from_millis(60));
+        assert!(!deadline.is_expired(&clock));
+        assert_eq!(deadline.remaining(&clock), Duration::from_millis(40));
+        handle.set(Duration::from_millis(100));
+        assert!(deadline.is_expired(&clock));
+        assert_eq!(
+            clock.elapsed_since(Duration::from_millis(150)),
+            Duration::ZERO
+        );
+        let fixed = || Duration::from_secs(1);
+        assert_eq!(fixed.now(), Duration::from_secs(1));
+    }
+}
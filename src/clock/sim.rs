@@ -0,0 +1,167 @@
+//! Simulated time for testing schedulers, timeouts and retries without real sleeps.
+//! [`SimTime::block_on`] runs a future and, whenever it's only waiting on [`SimTime::sleep`]s (or
+//! [`delay`]s), jumps the virtual clock straight to the next wake up.
+//!
+//! ```
+//! use btle::clock::{sim::SimTime, Clock};
+//! use core::time::Duration;
+//! let sim = SimTime::new();
+//! let clock = sim.clock();
+//! sim.block_on(async {
+//!     btle::clock::sim::delay(Duration::from_secs(60)).await;
+//! });
+//! assert_eq!(clock.now(), Duration::from_secs(60));
+//! ```
+use crate::clock::{Clock, MockClock};
+use crate::LocalBoxFuture;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+use futures_util::task::noop_waker;
+
+std::thread_local! {
+    static CURRENT: RefCell<Option<SimTime>> = const { RefCell::new(None) };
+}
+/// Virtual clock plus the wake up times of the sleeps waiting on it. Clones share the same time.
+#[derive(Clone, Debug, Default)]
+pub struct SimTime {
+    clock: MockClock,
+    wake_ups: Rc<RefCell<Vec<Duration>>>,
+}
+impl SimTime {
+    /// Starts at zero.
+    pub fn new() -> SimTime {
+        SimTime::default()
+    }
+    /// Clock to hand to the code under test.
+    pub fn clock(&self) -> MockClock {
+        self.clock.clone()
+    }
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
+    /// Move time forward by `by`. Sleeps that expire complete the next time they're polled.
+    pub fn advance(&self, by: Duration) {
+        self.clock.advance(by);
+        let now = self.now();
+        self.wake_ups.borrow_mut().retain(|&at| at > now);
+    }
+    /// Earliest wake up of any pending sleep.
+    pub fn next_wake_up(&self) -> Option<Duration> {
+        self.wake_ups.borrow().iter().copied().min()
+    }
+    /// Future that completes once virtual time has moved `duration` on.
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        Sleep {
+            sim: self.clone(),
+            wake_up: self.now() + duration,
+            is_registered: false,
+        }
+    }
+    /// Run `future` to completion. Panics if it stalls (is pending with no sleeps to wake it).
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.block_on_for(future, Duration::MAX)
+            .expect("future stalled with no pending sleeps")
+    }
+    /// Run `future` for up to `limit` of virtual time. Returns `None` if it's still pending after
+    /// that (the clock is left at `limit`) or stalls. [`delay`] uses this `SimTime` while running.
+    pub fn block_on_for<F: Future>(&self, future: F, limit: Duration) -> Option<F::Output> {
+        let end = self.now().checked_add(limit).unwrap_or(Duration::MAX);
+        let previous = CURRENT.with(|current| current.borrow_mut().replace(self.clone()));
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        let output = loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                break Some(output);
+            }
+            match self.next_wake_up() {
+                Some(at) if at <= end => {
+                    self.advance(at.checked_sub(self.now()).unwrap_or_default());
+                }
+                Some(_) => {
+                    self.advance(end.checked_sub(self.now()).unwrap_or_default());
+                    break None;
+                }
+                None => break None,
+            }
+        };
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+        output
+    }
+}
+/// Sleep on the [`SimTime`] running the current [`SimTime::block_on`]. Matches the delay
+/// functions taken by retries and schedulers (`hci::adapters::retry::DelayFn`, etc). Panics
+/// outside of `block_on`.
+pub fn delay(duration: Duration) -> LocalBoxFuture<'static, ()> {
+    let sim = CURRENT.with(|current| current.borrow().clone());
+    Box::pin(
+        sim.expect("clock::sim::delay used outside SimTime::block_on")
+            .sleep(duration),
+    )
+}
+/// Future returned by [`SimTime::sleep`].
+#[derive(Debug)]
+pub struct Sleep {
+    sim: SimTime,
+    wake_up: Duration,
+    is_registered: bool,
+}
+impl Future for Sleep {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        if self.sim.now() >= self.wake_up {
+            return Poll::Ready(());
+        }
+        if !self.is_registered {
+            self.sim.wake_ups.borrow_mut().push(self.wake_up);
+            self.is_registered = true;
+        }
+        Poll::Pending
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::le::proximity::{ProximityConfig, ProximityEventKind, ProximityMonitor};
+    use crate::{BTAddress, RSSI};
+    #[test]
+    fn test_sim_time() {
+        let sim = SimTime::new();
+        let clock = sim.clock();
+        let mut ticks = 0;
+        let ticked = sim.block_on_for(
+            async {
+                loop {
+                    delay(Duration::from_millis(100)).await;
+                    ticks += 1;
+                }
+            },
+            Duration::from_millis(350),
+        );
+        assert!(ticked.is_none());
+        assert_eq!(ticks, 3);
+        assert_eq!(clock.now(), Duration::from_millis(350));
+
+        // Proximity exit after the timeout with nothing but virtual time passing.
+        let mut monitor = ProximityMonitor::new(ProximityConfig {
+            dwell: Duration::from_secs(0),
+            ..ProximityConfig::DEFAULT
+        });
+        let address = BTAddress([1, 2, 3, 4, 5, 6]);
+        monitor.track(address, clock.now());
+        monitor.on_rssi(address, RSSI::new(-40), clock.now());
+        assert!(monitor.is_near(address));
+        let events = sim.block_on(async {
+            delay(ProximityConfig::DEFAULT.timeout).await;
+            monitor.check_timeouts(clock.now())
+        });
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, ProximityEventKind::Exit);
+    }
+}
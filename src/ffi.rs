@@ -0,0 +1,336 @@
+//! Stable C ABI over the scanner and advertiser API, for non-Rust applications and language
+//! bindings. Build the crate as a C library with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`) and generate a
+//! header with `cbindgen`.
+//!
+//! Adapters are opened by URI through [`crate::hci::adapters::registry`]. The transports built
+//! into the crate ([`registry::register_builtin`], `hci:0` for a BlueZ HCI socket with the
+//! `bluez_socket` feature and `tcp:host:port` with `remote`) are registered on the first open.
+//! Other transport factories have to be registered from Rust before handing control to C.
+//!
+//! Every call blocks the calling thread on the adapter's own single threaded tokio runtime (so
+//! tokio based transports like the BlueZ socket have a reactor and timers) until the adapter
+//! answers and returns [`BTLE_OK`], a negative `BTLE_ERR_*` code or, when the controller rejected
+//! a command, its positive HCI error code. An adapter must only be used from one thread at a
+//! time.
+//!
+//! ```c
+//! bool on_report(const BtleReport *report, void *user_data) {
+//!     printf("%d dBm\n", report->rssi);
+//!     return true; /* keep scanning */
+//! }
+//! BtleAdapter *adapter;
+//! if (btle_adapter_open("hci:0", &adapter) == BTLE_OK) {
+//!     btle_scan(adapter, true, on_report, NULL);
+//!     btle_adapter_close(adapter);
+//! }
+//! ```
+use crate::error::IOError;
+use crate::hci::adapter;
+use crate::hci::adapters::le::LEAdapter;
+use crate::hci::adapters::registry::{self, DynStream};
+use crate::hci::adapters::{Adapter, DummyUnrecognizedEventHandler};
+use crate::le::config::{AdvertisingSetConfig, ConfigError};
+use crate::le::scan::{Observer, ScanParameters, ScanType};
+use crate::RSSI;
+use core::convert::TryFrom;
+use futures_util::StreamExt;
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+use tokio::runtime::{Builder, Runtime};
+
+pub const BTLE_OK: i32 = 0;
+/// Null pointer, invalid UTF-8, out of range interval, too much advertising data, etc.
+pub const BTLE_ERR_BAD_PARAMETER: i32 = -1;
+/// The transport failed or was closed.
+pub const BTLE_ERR_IO: i32 = -2;
+/// No adapter (or no transport factory) for the URI.
+pub const BTLE_ERR_NOT_FOUND: i32 = -3;
+/// The controller is too old for a command.
+pub const BTLE_ERR_UNSUPPORTED: i32 = -4;
+/// The library panicked. The adapter shouldn't be used again.
+pub const BTLE_ERR_PANIC: i32 = -5;
+
+/// Opaque adapter handle.
+pub struct BtleAdapter {
+    adapter: LEAdapter<DynStream, DummyUnrecognizedEventHandler>,
+    /// Drives every call on the adapter. The transport was opened on it so it must outlive it.
+    runtime: Runtime,
+}
+/// One advertising report. `data` is only valid during the callback.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct BtleReport {
+    /// Little endian (as sent over the air).
+    pub address: [u8; 6],
+    /// HCI `Address_Type` (`0` public, `1` random, ...).
+    pub address_type: u8,
+    /// HCI `Event_Type` (`0` `ADV_IND`, ..., `4` `SCAN_RSP`).
+    pub event_type: u8,
+    /// dBm or `127` if the controller doesn't report RSSI.
+    pub rssi: i8,
+    /// Up to 31 bytes for legacy advertising or 1650 for extended advertising.
+    pub data_len: u16,
+    pub data: *const u8,
+}
+/// Called for every report. Return `false` to stop scanning.
+pub type BtleReportCallback =
+    extern "C" fn(report: *const BtleReport, user_data: *mut c_void) -> bool;
+
+static REGISTER_BUILTIN: Once = Once::new();
+/// Single threaded runtime (with IO and timers) for one adapter.
+fn runtime() -> Result<Runtime, adapter::Error> {
+    Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .map_err(|e| adapter::Error::IOError(e.into()))
+}
+fn error_code(error: adapter::Error) -> i32 {
+    match error {
+        adapter::Error::BadParameter | adapter::Error::InvalidPeer(_) => BTLE_ERR_BAD_PARAMETER,
+        adapter::Error::IOError(IOError::NotFound) => BTLE_ERR_NOT_FOUND,
+        adapter::Error::ChannelClosed
+        | adapter::Error::IOError(_)
+        | adapter::Error::StreamError(_) => BTLE_ERR_IO,
+        adapter::Error::ErrorCode(code) => i32::from(u8::from(code)),
+        adapter::Error::UnsupportedVersion { .. } => BTLE_ERR_UNSUPPORTED,
+    }
+}
+/// Run `f` and turn its result (or a panic) into a return code.
+fn guard<F: FnOnce() -> Result<(), adapter::Error>>(f: F) -> i32 {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => BTLE_OK,
+        Ok(Err(e)) => error_code(e),
+        Err(_) => BTLE_ERR_PANIC,
+    }
+}
+/// Open the adapter at `uri` and store its handle in `*out`.
+///
+/// # Safety
+/// `uri` must be a NUL terminated string and `out` valid for writes (or null).
+#[no_mangle]
+pub unsafe extern "C" fn btle_adapter_open(uri: *const c_char, out: *mut *mut BtleAdapter) -> i32 {
+    if uri.is_null() || out.is_null() {
+        return BTLE_ERR_BAD_PARAMETER;
+    }
+    let uri = match CStr::from_ptr(uri).to_str() {
+        Ok(uri) => uri,
+        Err(_) => return BTLE_ERR_BAD_PARAMETER,
+    };
+    guard(|| {
+        REGISTER_BUILTIN.call_once(registry::register_builtin);
+        let mut runtime = runtime()?;
+        let stream = runtime.block_on(registry::connect(uri))?;
+        let adapter = Box::new(BtleAdapter {
+            adapter: Adapter::new(stream).le(),
+            runtime,
+        });
+        *out = Box::into_raw(adapter);
+        Ok(())
+    })
+}
+/// Close an adapter from [`btle_adapter_open`]. Null is ignored.
+///
+/// # Safety
+/// `adapter` must come from [`btle_adapter_open`] and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn btle_adapter_close(adapter: *mut BtleAdapter) {
+    if !adapter.is_null() {
+        drop(Box::from_raw(adapter));
+    }
+}
+/// Scan (actively if `active`) and call `callback` with every report until it returns `false`,
+/// then stop scanning.
+///
+/// # Safety
+/// `adapter` must be a live handle from [`btle_adapter_open`].
+#[no_mangle]
+pub unsafe extern "C" fn btle_scan(
+    adapter: *mut BtleAdapter,
+    active: bool,
+    callback: BtleReportCallback,
+    user_data: *mut c_void,
+) -> i32 {
+    let BtleAdapter { adapter, runtime } = match adapter.as_mut() {
+        Some(adapter) => adapter,
+        None => return BTLE_ERR_BAD_PARAMETER,
+    };
+    guard(|| {
+        runtime.block_on(async {
+            adapter.set_scan_enable(false, false).await?;
+            adapter
+                .set_scan_parameters(ScanParameters {
+                    scan_type: if active {
+                        ScanType::Active
+                    } else {
+                        ScanType::Passive
+                    },
+                    ..ScanParameters::DEFAULT
+                })
+                .await?;
+            adapter.set_scan_enable(true, false).await?;
+            let result = {
+                let mut reports = Observer::advertisement_stream(adapter).await?;
+                loop {
+                    let report = match reports.next().await {
+                        Some(Ok(report)) => report,
+                        Some(Err(e)) => break Err(e),
+                        None => break Ok(()),
+                    };
+                    let data = report.data.as_ref();
+                    let c_report = BtleReport {
+                        address: report.address.0,
+                        address_type: report.address_type.into(),
+                        event_type: report.event_type.into(),
+                        rssi: report.rssi.map_or(RSSI::UNSUPPORTED_RSSI, i8::from),
+                        data_len: u16::try_from(data.len())
+                            .expect("advertising data is at most 1650 bytes"),
+                        data: data.as_ptr(),
+                    };
+                    if !callback(&c_report, user_data) {
+                        break Ok(());
+                    }
+                }
+            };
+            adapter.set_scan_enable(false, false).await?;
+            result
+        })
+    })
+}
+/// Advertise `data` (and answer scan requests with `scan_response`) every `interval_ms`.
+/// Replaces any advertising already running.
+///
+/// # Safety
+/// `adapter` must be a live handle from [`btle_adapter_open`]. `data` and `scan_response` must
+/// point to `data_len` and `scan_response_len` bytes (or be null with a length of 0).
+#[no_mangle]
+pub unsafe extern "C" fn btle_advertise_start(
+    adapter: *mut BtleAdapter,
+    data: *const u8,
+    data_len: usize,
+    scan_response: *const u8,
+    scan_response_len: usize,
+    interval_ms: u32,
+    connectable: bool,
+) -> i32 {
+    let BtleAdapter { adapter, runtime } = match adapter.as_mut() {
+        Some(adapter) => adapter,
+        None => return BTLE_ERR_BAD_PARAMETER,
+    };
+    let (data, scan_response) = match (
+        bytes(data, data_len),
+        bytes(scan_response, scan_response_len),
+    ) {
+        (Some(data), Some(scan_response)) => (data, scan_response),
+        _ => return BTLE_ERR_BAD_PARAMETER,
+    };
+    let set = AdvertisingSetConfig {
+        interval_ms,
+        connectable,
+        data: data.to_vec(),
+        scan_response: scan_response.to_vec(),
+    };
+    let parameters = match set.parameters() {
+        Ok(parameters) => parameters,
+        Err(ConfigError::Adapter(e)) => return error_code(e),
+        Err(_) => return BTLE_ERR_BAD_PARAMETER,
+    };
+    guard(|| {
+        runtime.block_on(async {
+            adapter.set_advertising_enable(false).await?;
+            adapter.set_advertising_parameters(parameters).await?;
+            adapter.set_advertising_data(&set.data).await?;
+            adapter.set_scan_response_data(&set.scan_response).await?;
+            adapter.set_advertising_enable(true).await
+        })
+    })
+}
+/// Stop advertising.
+///
+/// # Safety
+/// `adapter` must be a live handle from [`btle_adapter_open`].
+#[no_mangle]
+pub unsafe extern "C" fn btle_advertise_stop(adapter: *mut BtleAdapter) -> i32 {
+    match adapter.as_mut() {
+        Some(BtleAdapter { adapter, runtime }) => {
+            guard(|| runtime.block_on(adapter.set_advertising_enable(false)))
+        }
+        None => BTLE_ERR_BAD_PARAMETER,
+    }
+}
+/// `len` bytes at `ptr`. `None` for a null pointer with a non-zero length.
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    if ptr.is_null() {
+        if len == 0 {
+            Some(&[])
+        } else {
+            None
+        }
+    } else {
+        Some(core::slice::from_raw_parts(ptr, len))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hci::ErrorCode;
+    use core::ptr;
+    #[test]
+    fn test_ffi_errors() {
+        let mut adapter = ptr::null_mut();
+        unsafe {
+            let uri = b"nothing-registered:0\0".as_ptr().cast::<c_char>();
+            assert_eq!(btle_adapter_open(uri, &mut adapter), BTLE_ERR_NOT_FOUND);
+            assert!(adapter.is_null());
+            assert_eq!(
+                btle_adapter_open(ptr::null(), &mut adapter),
+                BTLE_ERR_BAD_PARAMETER
+            );
+            assert_eq!(btle_advertise_stop(adapter), BTLE_ERR_BAD_PARAMETER);
+            btle_adapter_close(adapter);
+            assert_eq!(bytes(ptr::null(), 0), Some(&[][..]));
+            assert_eq!(bytes(ptr::null(), 1), None);
+        }
+        assert_eq!(
+            error_code(adapter::Error::ErrorCode(ErrorCode::CommandDisallowed)),
+            0x0C
+        );
+        assert_eq!(guard(|| panic!("boom")), BTLE_ERR_PANIC);
+    }
+    #[test]
+    fn test_ffi_runtime() {
+        // A transport using tokio timers (or IO) would panic outside a runtime.
+        registry::register("ffi-timer", |_: &registry::AdapterUri<'_>| {
+            Box::pin(async {
+                tokio::time::delay_for(core::time::Duration::from_millis(1)).await;
+                Err(adapter::Error::BadParameter)
+            }) as crate::LocalBoxFuture<'static, _>
+        });
+        let mut adapter = ptr::null_mut();
+        let uri = b"ffi-timer:0\0".as_ptr().cast::<c_char>();
+        assert_eq!(
+            unsafe { btle_adapter_open(uri, &mut adapter) },
+            BTLE_ERR_BAD_PARAMETER
+        );
+        registry::unregister("ffi-timer");
+    }
+    #[cfg(feature = "remote")]
+    #[test]
+    fn test_ffi_builtin_tcp() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = alloc::format!("tcp:{}\0", listener.local_addr().unwrap());
+        let mut adapter = ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                btle_adapter_open(uri.as_ptr().cast::<c_char>(), &mut adapter),
+                BTLE_OK
+            );
+            assert!(!adapter.is_null());
+            btle_adapter_close(adapter);
+        }
+        assert!(listener.accept().is_ok());
+    }
+}
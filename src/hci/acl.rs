@@ -0,0 +1,279 @@
+//! HCI ACL Data packets and outbound fragmentation. [`ACLFragmenter`] picks fragment sizes from
+//! the controller's LE ACL buffer size and the link's Data Length Extension state so each HCI
+//! fragment maps onto whole link layer PDUs.
+use crate::bytes::Storage;
+use crate::hci::le::connection::{BufferSizeV1, BufferSizeV2};
+use crate::hci::packet::{Packet, PacketType};
+use crate::le::connection::ConnectionHandle;
+use crate::{ConversionError, PackError};
+use core::convert::{TryFrom, TryInto};
+
+/// `PB_Flag` of an ACL Data packet.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum ACLPacketBoundary {
+    /// First fragment of a non-automatically-flushable PDU. The only start flag allowed on LE.
+    FirstNonFlushable = 0b00,
+    ContinuingFragment = 0b01,
+    FirstFlushable = 0b10,
+    /// Complete automatically-flushable L2CAP PDU (deprecated).
+    CompletePDU = 0b11,
+}
+impl ACLPacketBoundary {
+    pub fn is_start(self) -> bool {
+        self != ACLPacketBoundary::ContinuingFragment
+    }
+}
+impl From<ACLPacketBoundary> for u8 {
+    fn from(b: ACLPacketBoundary) -> Self {
+        b as u8
+    }
+}
+impl TryFrom<u8> for ACLPacketBoundary {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(ACLPacketBoundary::FirstNonFlushable),
+            0b01 => Ok(ACLPacketBoundary::ContinuingFragment),
+            0b10 => Ok(ACLPacketBoundary::FirstFlushable),
+            0b11 => Ok(ACLPacketBoundary::CompletePDU),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// `BC_Flag` of an ACL Data packet. LE only uses `PointToPoint`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum ACLBroadcast {
+    PointToPoint = 0b00,
+    BREDRBroadcast = 0b01,
+}
+impl Default for ACLBroadcast {
+    fn default() -> Self {
+        ACLBroadcast::PointToPoint
+    }
+}
+impl From<ACLBroadcast> for u8 {
+    fn from(b: ACLBroadcast) -> Self {
+        b as u8
+    }
+}
+impl TryFrom<u8> for ACLBroadcast {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(ACLBroadcast::PointToPoint),
+            0b01 => Ok(ACLBroadcast::BREDRBroadcast),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// HCI ACL Data packet.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ACLDataPacket<Buf> {
+    pub handle: ConnectionHandle,
+    pub boundary: ACLPacketBoundary,
+    pub broadcast: ACLBroadcast,
+    pub data: Buf,
+}
+impl<Buf> ACLDataPacket<Buf> {
+    pub const HEADER_LEN: usize = 4;
+}
+impl<Buf: AsRef<[u8]>> ACLDataPacket<Buf> {
+    pub fn byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.data.as_ref().len()
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        let handle = u16::from(self.handle)
+            | (u16::from(u8::from(self.boundary)) << 12)
+            | (u16::from(u8::from(self.broadcast)) << 14);
+        let data_len =
+            u16::try_from(self.data.as_ref().len()).map_err(|_| PackError::InvalidFields)?;
+        buf[0..2].copy_from_slice(&handle.to_le_bytes());
+        buf[2..4].copy_from_slice(&data_len.to_le_bytes());
+        buf[Self::HEADER_LEN..].copy_from_slice(self.data.as_ref());
+        Ok(())
+    }
+}
+impl<Buf: Storage<u8>> Packet for ACLDataPacket<Buf> {
+    const PACKET_TYPE: PacketType = PacketType::ACLData;
+
+    fn packet_byte_len(&self) -> usize {
+        self.byte_len()
+    }
+
+    fn packet_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.pack_into(buf)
+    }
+
+    fn packet_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        let handle = u16::from_le_bytes(buf[0..2].try_into().expect("length checked above"));
+        let data_len = u16::from_le_bytes(buf[2..4].try_into().expect("length checked above"));
+        PackError::expect_length(Self::HEADER_LEN + usize::from(data_len), buf)?;
+        Ok(ACLDataPacket {
+            handle: ConnectionHandle::new_checked(handle & 0x0FFF)
+                .ok_or_else(|| PackError::bad_index(0))?,
+            boundary: ACLPacketBoundary::try_from(((handle >> 12) & 0b11) as u8)
+                .expect("masked to 2 bits"),
+            broadcast: ACLBroadcast::try_from(((handle >> 14) & 0b11) as u8)
+                .map_err(|_| PackError::bad_index(1))?,
+            data: Buf::from_slice(&buf[Self::HEADER_LEN..]),
+        })
+    }
+}
+/// Picks outbound ACL fragment sizes.
+///
+/// The controller splits each HCI fragment into link layer PDUs of at most `max_tx_octets`. A
+/// fragment that isn't a multiple of `max_tx_octets` leaves a short PDU at its end, costing a
+/// whole PDU's overhead (header, IFS and ack) for a few bytes. The fragmenter rounds the
+/// controller buffer size down to whole PDUs to avoid that.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ACLFragmenter {
+    buffer_len: u16,
+    max_tx_octets: u16,
+}
+impl ACLFragmenter {
+    /// Link layer payload size without Data Length Extension.
+    pub const DEFAULT_TX_OCTETS: u16 = 27;
+    /// Largest link layer payload with Data Length Extension.
+    pub const MAX_TX_OCTETS: u16 = 251;
+    /// `buffer_len` is the controller's `LE_ACL_Data_Packet_Length`. Panics if it is `0`.
+    pub fn new(buffer_len: u16) -> ACLFragmenter {
+        assert_ne!(buffer_len, 0, "zero ACL buffer length");
+        ACLFragmenter {
+            buffer_len,
+            max_tx_octets: Self::DEFAULT_TX_OCTETS,
+        }
+    }
+    /// Returns `None` if the controller has no separate LE buffers
+    /// (`LE_ACL_Data_Packet_Length == 0`). Use the BR/EDR buffer size instead.
+    pub fn from_buffer_size(size: BufferSizeV1) -> Option<ACLFragmenter> {
+        if size.le_acl_data_packet_len == 0 {
+            None
+        } else {
+            Some(ACLFragmenter::new(size.le_acl_data_packet_len))
+        }
+    }
+    pub fn from_buffer_size_v2(size: BufferSizeV2) -> Option<ACLFragmenter> {
+        Self::from_buffer_size(BufferSizeV1 {
+            status: size.status,
+            le_acl_data_packet_len: size.le_acl_data_packet_len,
+            total_num_le_acl_data_packets: size.total_num_le_acl_data_packets,
+        })
+    }
+    pub fn buffer_len(&self) -> u16 {
+        self.buffer_len
+    }
+    pub fn max_tx_octets(&self) -> u16 {
+        self.max_tx_octets
+    }
+    /// Update the link's `connMaxTxOctets` (from a Data Length Change event). Clamped to
+    /// `DEFAULT_TX_OCTETS..=MAX_TX_OCTETS`.
+    pub fn set_max_tx_octets(&mut self, max_tx_octets: u16) {
+        self.max_tx_octets = max_tx_octets.clamp(Self::DEFAULT_TX_OCTETS, Self::MAX_TX_OCTETS);
+    }
+    /// Size of each HCI fragment (the last one of a PDU may be shorter).
+    pub fn fragment_len(&self) -> usize {
+        let buffer_len = usize::from(self.buffer_len);
+        let tx_octets = usize::from(self.max_tx_octets);
+        if buffer_len > tx_octets {
+            buffer_len - buffer_len % tx_octets
+        } else {
+            buffer_len
+        }
+    }
+    /// Split `pdu` (usually an L2CAP PDU) into ACL Data packets.
+    pub fn fragment<'a>(&self, handle: ConnectionHandle, pdu: &'a [u8]) -> ACLFragments<'a> {
+        ACLFragments {
+            handle,
+            rest: pdu,
+            fragment_len: self.fragment_len(),
+            is_first: true,
+        }
+    }
+    /// Number of link layer PDUs needed to send `fragment_len` fragments of a `pdu_len` byte PDU.
+    pub fn link_layer_pdus(&self, pdu_len: usize, fragment_len: usize) -> usize {
+        let tx_octets = usize::from(self.max_tx_octets);
+        let fragment_len = fragment_len.max(1);
+        let full = pdu_len / fragment_len;
+        let last = pdu_len % fragment_len;
+        full * ((fragment_len + tx_octets - 1) / tx_octets) + (last + tx_octets - 1) / tx_octets
+    }
+}
+/// Iterator returned by [`ACLFragmenter::fragment`].
+#[derive(Clone, Debug)]
+pub struct ACLFragments<'a> {
+    handle: ConnectionHandle,
+    rest: &'a [u8],
+    fragment_len: usize,
+    is_first: bool,
+}
+impl<'a> Iterator for ACLFragments<'a> {
+    type Item = ACLDataPacket<&'a [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() && !self.is_first {
+            return None;
+        }
+        let (data, rest) = self
+            .rest
+            .split_at(core::cmp::min(self.fragment_len, self.rest.len()));
+        self.rest = rest;
+        let boundary = if self.is_first {
+            ACLPacketBoundary::FirstNonFlushable
+        } else {
+            ACLPacketBoundary::ContinuingFragment
+        };
+        self.is_first = false;
+        Some(ACLDataPacket {
+            handle: self.handle,
+            boundary,
+            broadcast: ACLBroadcast::PointToPoint,
+            data,
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    #[test]
+    fn test_fragment() {
+        let mut fragmenter = ACLFragmenter::new(251);
+        assert_eq!(fragmenter.fragment_len(), 243);
+        fragmenter.set_max_tx_octets(251);
+        assert_eq!(fragmenter.fragment_len(), 251);
+        fragmenter.set_max_tx_octets(100);
+        assert_eq!(fragmenter.fragment_len(), 200);
+        assert_eq!(ACLFragmenter::new(20).fragment_len(), 20);
+
+        let pdu: Vec<u8> = (0..=255).collect();
+        let handle = ConnectionHandle::new(0x0040);
+        let fragments: Vec<_> = fragmenter.fragment(handle, &pdu).collect();
+        assert_eq!(fragments.len(), 2);
+        assert_eq!(fragments[0].boundary, ACLPacketBoundary::FirstNonFlushable);
+        assert_eq!(fragments[1].boundary, ACLPacketBoundary::ContinuingFragment);
+        assert_eq!(fragments[1].data, &pdu[200..]);
+        assert_eq!(fragmenter.link_layer_pdus(pdu.len(), 200), 3);
+        assert_eq!(fragmenter.link_layer_pdus(pdu.len(), 251), 4);
+
+        let mut buf = [0_u8; 60];
+        fragments[1].pack_into(&mut buf[..]).unwrap();
+        let unpacked = ACLDataPacket::<Vec<u8>>::packet_unpack_from(&buf).unwrap();
+        assert_eq!(unpacked.handle, handle);
+        assert_eq!(unpacked.data, &pdu[200..]);
+        assert_eq!(fragmenter.fragment(handle, &[]).count(), 1);
+    }
+}
@@ -2,8 +2,10 @@ use crate::bytes::Storage;
 use crate::error::IOError;
 use crate::hci::command::{Command, CommandPacket};
 use crate::hci::event::EventPacket;
+use crate::hci::informational::CoreVersion;
 use crate::hci::stream::HCI_EVENT_READ_TRIES;
 use crate::hci::StreamError;
+use crate::le::advertiser::DirectedPeerError;
 use crate::{hci, LocalBoxFuture};
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
@@ -13,6 +15,14 @@ pub enum Error {
     IOError(IOError),
     StreamError(hci::StreamError),
     ErrorCode(hci::ErrorCode),
+    /// The controller's Core version is older than the command's `MIN_VERSION`.
+    UnsupportedVersion {
+        opcode: hci::Opcode,
+        required: CoreVersion,
+        controller: CoreVersion,
+    },
+    /// Peer address rejected before sending the command. See `DirectedPeer::check`.
+    InvalidPeer(DirectedPeerError),
 }
 impl core::fmt::Display for Error {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -29,6 +39,11 @@ impl From<hci::StreamError> for Error {
         Error::StreamError(e)
     }
 }
+impl From<DirectedPeerError> for Error {
+    fn from(e: DirectedPeerError) -> Self {
+        Error::InvalidPeer(e)
+    }
+}
 impl From<hci::ErrorCode> for Error {
     fn from(e: hci::ErrorCode) -> Self {
         Error::ErrorCode(e)
@@ -0,0 +1,280 @@
+use crate::classic::class_of_device::ClassOfDevice;
+use crate::classic::flush::FlushPolicies;
+use crate::classic::settings::{
+    ScanActivity, ScanEnable, WriteClassOfDevice, WriteInquiryScanActivity, WritePageScanActivity,
+    WriteScanEnable, WriteSimplePairingMode,
+};
+use crate::hci::adapters::{Adapter, UnrecognizedEventHandler};
+use crate::hci::baseband::{
+    EnhancedFlush, Flush, FlushOccurred, FlushPacketType, FlushTimeout, ReadAutomaticFlushTimeout,
+    WriteAutomaticFlushTimeout,
+};
+use crate::hci::event::{Event, EventCode, EventPacket};
+use crate::hci::link_control::{
+    AuthenticationRequested, LinkKeyNotification, LinkKeyRequest, LinkKeyRequestNegativeReply,
+    LinkKeyRequestReply, PINCode, PINCodeRequest, PINCodeRequestNegativeReply, PINCodeRequestReply,
+};
+use crate::hci::{adapter, StreamError};
+use crate::key_store::{KeyStore, LinkKey};
+use crate::le::connection::ConnectionHandle;
+use crate::BTAddress;
+
+/// BR/EDR commands on top of a HCI [`Adapter`]. Bonds use a [`KeyStore`] passed in by the
+/// caller so it can be shared with the rest of the application.
+pub struct ClassicAdapter<A: adapter::Adapter, H: UnrecognizedEventHandler> {
+    pub adapter: Adapter<A, H>,
+    /// PIN used to answer legacy pairing requests. Legacy pairing is refused without one.
+    pub pin_code: Option<PINCode>,
+    /// Store link keys made from the debug keys. They're only useful for testing (anyone can
+    /// compute them) so they are dropped by default.
+    pub store_debug_keys: bool,
+}
+impl<A: adapter::Adapter, H: UnrecognizedEventHandler> ClassicAdapter<A, H> {
+    pub fn new(adapter: Adapter<A, H>) -> Self {
+        Self {
+            adapter,
+            pin_code: None,
+            store_debug_keys: false,
+        }
+    }
+    /// Start authenticating the connection. The controller will ask for the link key (and PIN if
+    /// pairing) which [`ClassicAdapter::handle_security_event`] answers. Finishes with a
+    /// [`crate::hci::link_control::AuthenticationComplete`] event.
+    pub async fn authenticate(&mut self, handle: ConnectionHandle) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(AuthenticationRequested { handle })
+            .await?
+            .status
+            .error()?;
+        Ok(())
+    }
+    pub async fn link_key_request_reply(
+        &mut self,
+        address: BTAddress,
+        link_key: LinkKey,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(LinkKeyRequestReply { address, link_key })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    pub async fn link_key_request_negative_reply(
+        &mut self,
+        address: BTAddress,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(LinkKeyRequestNegativeReply { address })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    pub async fn pin_code_request_reply(
+        &mut self,
+        address: BTAddress,
+        pin_code: PINCode,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(PINCodeRequestReply { address, pin_code })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    pub async fn pin_code_request_negative_reply(
+        &mut self,
+        address: BTAddress,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(PINCodeRequestNegativeReply { address })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Make the controller discoverable and/or connectable.
+    pub async fn write_scan_enable(
+        &mut self,
+        scan_enable: ScanEnable,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(WriteScanEnable { scan_enable })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    pub async fn write_page_scan_activity(
+        &mut self,
+        activity: ScanActivity,
+    ) -> Result<(), adapter::Error> {
+        if !activity.is_valid() {
+            return Err(adapter::Error::BadParameter);
+        }
+        self.adapter
+            .hci_send_command(WritePageScanActivity { activity })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    pub async fn write_inquiry_scan_activity(
+        &mut self,
+        activity: ScanActivity,
+    ) -> Result<(), adapter::Error> {
+        if !activity.is_valid() {
+            return Err(adapter::Error::BadParameter);
+        }
+        self.adapter
+            .hci_send_command(WriteInquiryScanActivity { activity })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    pub async fn write_class_of_device(
+        &mut self,
+        class_of_device: ClassOfDevice,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(WriteClassOfDevice { class_of_device })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Enable Secure Simple Pairing. Only a Reset disables it again.
+    pub async fn enable_simple_pairing(&mut self) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(WriteSimplePairingMode { is_enabled: true })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Drop every ACL packet waiting to be sent on `handle`.
+    pub async fn flush(&mut self, handle: ConnectionHandle) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(Flush { handle })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Drop the automatically-flushable packets waiting on `handle`. Finishes with a
+    /// [`crate::hci::baseband::EnhancedFlushComplete`] event.
+    pub async fn enhanced_flush(&mut self, handle: ConnectionHandle) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(EnhancedFlush {
+                handle,
+                packet_type: FlushPacketType::AutomaticallyFlushable,
+            })
+            .await?
+            .status
+            .error()?;
+        Ok(())
+    }
+    pub async fn read_automatic_flush_timeout(
+        &mut self,
+        handle: ConnectionHandle,
+    ) -> Result<FlushTimeout, adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(ReadAutomaticFlushTimeout { handle })
+            .await?
+            .params;
+        r.status.error()?;
+        Ok(r.flush_timeout)
+    }
+    pub async fn write_automatic_flush_timeout(
+        &mut self,
+        handle: ConnectionHandle,
+        flush_timeout: FlushTimeout,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(WriteAutomaticFlushTimeout {
+                handle,
+                flush_timeout,
+            })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Write the flush timeout `policies` has for `handle`. Call when the connection is made
+    /// and after changing its policy.
+    pub async fn apply_flush_policy(
+        &mut self,
+        policies: &FlushPolicies,
+        handle: ConnectionHandle,
+    ) -> Result<(), adapter::Error> {
+        self.write_automatic_flush_timeout(handle, policies.timeout_for(handle))
+            .await
+    }
+    /// Record [`FlushOccurred`] events in `policies` (calling its hook). Returns `false` for any
+    /// other event.
+    pub fn handle_flush_event<B: AsRef<[u8]>>(
+        &mut self,
+        policies: &mut FlushPolicies,
+        event: &EventPacket<B>,
+    ) -> Result<bool, adapter::Error> {
+        if event.event_code() != EventCode::FlushOccurred {
+            return Ok(false);
+        }
+        let handle = FlushOccurred::unpack_event_packet(event)
+            .map_err(StreamError::EventError)?
+            .handle;
+        policies.record_flush(handle);
+        Ok(true)
+    }
+    /// Answer link key and PIN requests from `store` and [`ClassicAdapter::pin_code`], and store
+    /// new link keys. Returns `false` if `event` isn't one of those events.
+    pub async fn handle_security_event<S: KeyStore, B: AsRef<[u8]>>(
+        &mut self,
+        store: &mut S,
+        event: &EventPacket<B>,
+    ) -> Result<bool, adapter::Error> {
+        match event.event_code() {
+            EventCode::LinkKeyRequest => {
+                let address = LinkKeyRequest::unpack_event_packet(event)
+                    .map_err(StreamError::EventError)?
+                    .address;
+                match store.link_key(address) {
+                    Some(stored) => self.link_key_request_reply(address, stored.key).await?,
+                    None => self.link_key_request_negative_reply(address).await?,
+                }
+            }
+            EventCode::PINCodeRequest => {
+                let address = PINCodeRequest::unpack_event_packet(event)
+                    .map_err(StreamError::EventError)?
+                    .address;
+                match self.pin_code {
+                    Some(pin_code) => self.pin_code_request_reply(address, pin_code).await?,
+                    None => self.pin_code_request_negative_reply(address).await?,
+                }
+            }
+            EventCode::LinkKeyNotification => {
+                let notification = LinkKeyNotification::unpack_event_packet(event)
+                    .map_err(StreamError::EventError)?;
+                if self.store_debug_keys || !notification.key_type.is_debug() {
+                    store.store_link_key(notification.address, notification.stored_key());
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,58 @@
+//! ACL flow control setup (see [`crate::hci::flow_control`]).
+use crate::hci::adapter;
+use crate::hci::adapters::{Adapter, UnrecognizedEventHandler};
+use crate::hci::flow_control::{
+    ACLCredits, BufferSize, DataBlockSize, FlowControlMode, ReadBufferSize, ReadDataBlockSize,
+    ReadFlowControlMode, WriteFlowControlMode,
+};
+use crate::hci::StreamError;
+use crate::PackError;
+
+impl<A: adapter::Adapter, H: UnrecognizedEventHandler> Adapter<A, H> {
+    pub async fn read_flow_control_mode(&mut self) -> Result<FlowControlMode, adapter::Error> {
+        let r = self.hci_send_command(ReadFlowControlMode).await?.params;
+        r.status.error()?;
+        Ok(r.mode)
+    }
+    /// Switch between packet and data block based flow control. Only allowed while there are no
+    /// connections.
+    pub async fn write_flow_control_mode(
+        &mut self,
+        mode: FlowControlMode,
+    ) -> Result<(), adapter::Error> {
+        self.hci_send_command(WriteFlowControlMode { mode })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Read the BR/EDR ACL and synchronous buffer sizes.
+    pub async fn read_buffer_size(&mut self) -> Result<BufferSize, adapter::Error> {
+        let r = self.hci_send_command(ReadBufferSize).await?.params;
+        r.status.error()?;
+        Ok(r)
+    }
+    pub async fn read_data_block_size(&mut self) -> Result<DataBlockSize, adapter::Error> {
+        let r = self.hci_send_command(ReadDataBlockSize).await?.params;
+        r.status.error()?;
+        Ok(r)
+    }
+    /// [`ACLCredits`] for the controller's current flow control mode. `total_packets` is the
+    /// controller's `Total_Num_ACL_Data_Packets` (from a Read Buffer Size command), only used if
+    /// it's packet based.
+    pub async fn read_acl_credits(
+        &mut self,
+        total_packets: u16,
+    ) -> Result<ACLCredits, adapter::Error> {
+        match self.read_flow_control_mode().await? {
+            FlowControlMode::PacketBased => Ok(ACLCredits::packet_based(total_packets)),
+            FlowControlMode::DataBlockBased => {
+                let size = self.read_data_block_size().await?;
+                ACLCredits::block_based(size).ok_or(adapter::Error::StreamError(
+                    StreamError::EventError(PackError::bad_index(3)),
+                ))
+            }
+        }
+    }
+}
@@ -1,37 +1,83 @@
+use crate::hci::adapters::pending::PendingCommand;
+use crate::hci::adapters::retry::DelayFn;
 use crate::hci::adapters::{Adapter, UnrecognizedEventHandler};
 use crate::hci::baseband::{EventMask, EventMaskFlags};
+use crate::hci::flow_control::ControllerBufferInfo;
+use crate::hci::le::channel_map::DataChannelMap;
+use crate::hci::le::connection::{ConnectionUpdate, ConnectionUpdateComplete};
+use crate::hci::le::data_length::MaximumDataLengthReturn;
+use crate::hci::le::encryption::{EncryptionChange, EncryptionKeyRefreshComplete, StartEncryption};
+use crate::hci::le::extended_scan::ExtendedScanParameters;
+use crate::hci::le::features::{
+    LEFeatures, ReadLocalFeatures, ReadRemoteFeatures, ReadRemoteFeaturesComplete,
+};
+use crate::hci::le::host_feature::{HostFeatureBit, SetHostFeature};
 use crate::hci::le::mask::{MetaEventMask, SetMetaEventMask};
+use crate::hci::le::path_loss::{PathLossThreshold, SetPathLossReportingParameters};
+use crate::hci::le::periodic_advertise::PeriodicAdvertisingParameters;
+use crate::hci::le::periodic_sync::{
+    PeriodicAdvertiserListEntry, PeriodicAdvertisingCreateSync, SyncHandle,
+};
+use crate::hci::le::phy::{ConnectionPHYs, PHYUpdateComplete, SetDefaultPHY, SetPHY};
+use crate::hci::le::resolving_list::{
+    PeerIdentity, PrivacyMode, RPATimeout, ResolvingListEntry, SetPrivacyMode,
+};
+use crate::hci::le::sync_transfer::SetPeriodicAdvertisingSyncTransferParameters;
+use crate::hci::le::whitelist::WhitelistEntry;
 use crate::hci::le::MetaEventCode;
-use crate::le::advertiser::Advertiser;
+use crate::le::adapter::{Backend, Capabilities};
+use crate::le::advertiser::{
+    Advertiser, AdvertisingSetHandle, AdvertisingTxPower, PeriodicAdvertiser,
+};
+use crate::le::connection::qos::{ConnectionParameters, DataLength};
+use crate::le::connection::ConnectionHandle;
 use crate::le::scan::Observer;
+use crate::le::smp::keys::LTK;
 use crate::{
     bytes::Storage,
     hci::{
         adapter,
-        event::{EventCode, EventPacket},
-        le::{self, random::RAND_LEN, report::AdvertisingReport, MetaEvent, RawMetaEvent},
+        anomaly::{self, Anomaly},
+        event::{Event, EventCode, EventPacket},
+        le::{
+            self,
+            random::{AES_BLOCK_LEN, RAND_LEN},
+            report::AdvertisingReport,
+            MetaEvent, RawMetaEvent,
+        },
         StreamError,
     },
     le::{
         advertisement::{StaticAdvBuffer, MAX_ADV_LEN},
         advertiser::AdvertisingParameters,
+        batch::{BatchDelivery, Batched},
+        calibration::RssiCalibration,
         report::ReportInfo,
         scan::ScanParameters,
     },
-    Stream,
+    DeviceAddress, Stream,
 };
 use core::convert::TryFrom;
 use futures_util::future::LocalBoxFuture;
 use futures_util::stream::LocalBoxStream;
 use futures_util::{FutureExt, StreamExt};
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 
 pub struct LEAdapter<A: adapter::Adapter, H: UnrecognizedEventHandler> {
     pub adapter: Adapter<A, H>,
+    /// Applied to the RSSI of reports from [`LEAdapter::advertisement_stream`]. Does nothing by
+    /// default.
+    pub rssi_calibration: RssiCalibration,
+    local_features: Option<LEFeatures>,
 }
 impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
     pub fn new(adapter: Adapter<A, H>) -> Self {
-        Self { adapter }
+        Self {
+            adapter,
+            rssi_calibration: RssiCalibration::IDENTITY,
+            local_features: None,
+        }
     }
     /// Read the advertising channel TX power in dBm. See [`le::advertise::TxPowerLevel`] for more.
     pub async fn get_advertising_tx_power(
@@ -75,6 +121,34 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
             .error()?;
         Ok(())
     }
+    /// Set extended (Bluetooth 5.0) scanning parameters, one set per primary PHY. Use instead of
+    /// [`LEAdapter::set_scan_parameters`] to scan LE Coded.
+    pub async fn set_extended_scan_parameters(
+        &mut self,
+        parameters: ExtendedScanParameters,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::SetExtendedScanParameters(parameters))
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Start or stop extended scanning. See [`le::commands::SetExtendedScanEnable::enable`] for
+    /// duplicate filtering and scan duration/period.
+    pub async fn set_extended_scan_enable(
+        &mut self,
+        enable: le::commands::SetExtendedScanEnable,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(enable)
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
     /// Enable or disable advertising. Make sure to set advertising parameters
     /// ([`LEAdapter::set_advertising_parameters`]) and advertising data
     /// ([`LEAdapter::set_advertising_data`]) before calling this function.
@@ -88,10 +162,13 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
         Ok(())
     }
     /// Set advertising parameters. See [`le::commands::SetAdvertisingParameters`] for more.
+    /// Returns `adapter::Error::InvalidPeer` without sending the command if the peer address
+    /// can't be used with the own address type (see [`AdvertisingParameters::check_peer`]).
     pub async fn set_advertising_parameters(
         &mut self,
         parameters: AdvertisingParameters,
     ) -> Result<(), adapter::Error> {
+        parameters.check_peer()?;
         self.adapter
             .hci_send_command(le::commands::SetAdvertisingParameters(parameters))
             .await?
@@ -106,6 +183,20 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
         r.params.status.error()?;
         Ok(r.params.random_bytes)
     }
+    /// AES-128 encrypt `plaintext` with `key` on the controller. Everything is least
+    /// significant octet first (see [`le::random::Encrypt`]).
+    pub async fn encrypt(
+        &mut self,
+        key: [u8; AES_BLOCK_LEN],
+        plaintext: [u8; AES_BLOCK_LEN],
+    ) -> Result<[u8; AES_BLOCK_LEN], adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(le::commands::Encrypt { key, plaintext })
+            .await?;
+        r.params.status.error()?;
+        Ok(r.params.encrypted_data)
+    }
     /// Returns Max number of ACL Packets and Packet length.
     pub async fn read_buffer_size_v1(
         &mut self,
@@ -129,6 +220,22 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
         r.params.status.error()?;
         Ok(r.params)
     }
+    /// Read every data buffer pool of the controller. Uses LE Read Buffer Size V2 if the
+    /// controller supports it (V1 otherwise) and also reads the BR/EDR buffers if LE ACL data
+    /// has to share them.
+    pub async fn read_buffer_info(&mut self) -> Result<ControllerBufferInfo, adapter::Error> {
+        let commands = self.adapter.read_supported_commands().await?;
+        let mut info = ControllerBufferInfo::NONE;
+        if commands.le_read_buffer_size_v2() {
+            info.set_le_buffer_size_v2(&self.read_buffer_size_v2().await?);
+        } else {
+            info.set_le_buffer_size_v1(&self.read_buffer_size_v1().await?);
+        }
+        if info.le_acl.is_none() && commands.read_buffer_size() {
+            info.set_buffer_size(&self.adapter.read_buffer_size().await?);
+        }
+        Ok(info)
+    }
     pub async fn set_scan_response_data(&mut self, data: &[u8]) -> Result<(), adapter::Error> {
         let rsp =
             le::commands::SetScanResponseData::try_from(data).map_err(StreamError::CommandError)?;
@@ -140,6 +247,422 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
             .error()?;
         Ok(())
     }
+    /// Read how many entries the White List can hold.
+    pub async fn read_whitelist_size(&mut self) -> Result<u8, adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(le::commands::ReadWhitelistSize {})
+            .await?;
+        r.params.status.error()?;
+        Ok(r.params.size)
+    }
+    pub async fn add_to_whitelist(&mut self, entry: WhitelistEntry) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::AddDeviceToWhitelist(entry))
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    pub async fn remove_from_whitelist(
+        &mut self,
+        entry: WhitelistEntry,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::RemoveDeviceFromWhitelist(entry))
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Clear the White List and add `entries` to it.
+    /// # Errors
+    /// Returns `adapter::Error::BadParameter` (leaving the White List as it was) if there are more
+    /// `entries` than [`LEAdapter::read_whitelist_size`].
+    pub async fn set_whitelist(
+        &mut self,
+        entries: &[WhitelistEntry],
+    ) -> Result<(), adapter::Error> {
+        if entries.len() > usize::from(self.read_whitelist_size().await?) {
+            return Err(adapter::Error::BadParameter);
+        }
+        self.adapter
+            .hci_send_command(le::commands::ClearWhitelist {})
+            .await?
+            .params
+            .status
+            .error()?;
+        for entry in entries {
+            self.add_to_whitelist(*entry).await?;
+        }
+        Ok(())
+    }
+    /// [`LEAdapter::set_whitelist`] from device addresses. Use before scanning or advertising
+    /// with a filter policy that uses the White List.
+    pub async fn load_whitelist(
+        &mut self,
+        devices: &[DeviceAddress],
+    ) -> Result<(), adapter::Error> {
+        let entries: Vec<WhitelistEntry> =
+            devices.iter().map(|d| WhitelistEntry::from(*d)).collect();
+        self.set_whitelist(&entries).await
+    }
+    /// Read how many entries the Resolving List can hold.
+    pub async fn read_resolving_list_size(&mut self) -> Result<u8, adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(le::commands::ReadResolvingListSize {})
+            .await?;
+        r.params.status.error()?;
+        Ok(r.params.size)
+    }
+    pub async fn add_to_resolving_list(
+        &mut self,
+        entry: ResolvingListEntry,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::AddDeviceToResolvingList(entry))
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    pub async fn remove_from_resolving_list(
+        &mut self,
+        peer: PeerIdentity,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::RemoveDeviceFromResolvingList(peer))
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Clear the Resolving List and add `entries` to it. Address resolution has to be disabled
+    /// (or advertising, scanning and connecting stopped) while the list is changed.
+    /// # Errors
+    /// Returns `adapter::Error::BadParameter` (leaving the Resolving List as it was) if there are
+    /// more `entries` than [`LEAdapter::read_resolving_list_size`].
+    pub async fn set_resolving_list(
+        &mut self,
+        entries: &[ResolvingListEntry],
+    ) -> Result<(), adapter::Error> {
+        if entries.len() > usize::from(self.read_resolving_list_size().await?) {
+            return Err(adapter::Error::BadParameter);
+        }
+        self.adapter
+            .hci_send_command(le::commands::ClearResolvingList {})
+            .await?
+            .params
+            .status
+            .error()?;
+        for entry in entries {
+            self.add_to_resolving_list(*entry).await?;
+        }
+        Ok(())
+    }
+    pub async fn set_address_resolution_enable(
+        &mut self,
+        is_enabled: bool,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::SetAddressResolutionEnable { is_enabled })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Set how often the controller generates new RPAs.
+    pub async fn set_rpa_timeout(&mut self, timeout: RPATimeout) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::SetResolvablePrivateAddressTimeout { timeout })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Set the privacy mode of `peer` (already in the Resolving List).
+    pub async fn set_privacy_mode(
+        &mut self,
+        peer: PeerIdentity,
+        mode: PrivacyMode,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(SetPrivacyMode { peer, mode })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Read the PHYs `connection_handle` is using.
+    pub async fn read_phy(
+        &mut self,
+        connection_handle: ConnectionHandle,
+    ) -> Result<ConnectionPHYs, adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(le::commands::ReadPHY { connection_handle })
+            .await?;
+        r.params.status.error()?;
+        Ok(r.params)
+    }
+    /// Set the PHYs preferred for new connections.
+    /// # Errors
+    /// Returns `adapter::Error::BadParameter` if a preference is an empty set.
+    pub async fn set_default_phy(&mut self, phys: SetDefaultPHY) -> Result<(), adapter::Error> {
+        if !phys.is_valid() {
+            return Err(adapter::Error::BadParameter);
+        }
+        self.adapter
+            .hci_send_command(phys)
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Ask to switch `set_phy.connection_handle` to the preferred PHYs and wait for the
+    /// `PHYUpdateComplete`. The PHYs can be other than the ones asked for if the peer doesn't
+    /// support them. Other events are passed to the event handler while waiting.
+    /// # Errors
+    /// Returns `adapter::Error::BadParameter` if a preference is an empty set.
+    pub async fn set_phy(&mut self, set_phy: SetPHY) -> Result<ConnectionPHYs, adapter::Error> {
+        if !set_phy.is_valid() {
+            return Err(adapter::Error::BadParameter);
+        }
+        self.adapter
+            .hci_send_command(set_phy)
+            .await?
+            .status
+            .error()?;
+        loop {
+            let event = self.adapter.hci_read_event().await?;
+            if let Ok(PHYUpdateComplete(phys)) = PHYUpdateComplete::unpack_event_packet(&event) {
+                if phys.connection_handle == set_phy.connection_handle {
+                    phys.status.error()?;
+                    return Ok(phys);
+                }
+            }
+            self.adapter.event_handler.handle(event)?;
+        }
+    }
+    /// Encrypt the link (central only) and wait for the [`EncryptionChange`] (or
+    /// [`EncryptionKeyRefreshComplete`] if the link was already encrypted). Fails with the status
+    /// of the event if the peer rejected the key.
+    pub async fn start_encryption(
+        &mut self,
+        start_encryption: StartEncryption,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(start_encryption)
+            .await?
+            .status
+            .error()?;
+        let connection_handle = start_encryption.connection_handle;
+        loop {
+            let event = self.adapter.hci_read_event().await?;
+            if let Ok(change) = EncryptionChange::unpack_event_packet(&event) {
+                if change.connection_handle == connection_handle {
+                    change.status.error()?;
+                    return Ok(());
+                }
+            }
+            if let Ok(refresh) = EncryptionKeyRefreshComplete::unpack_event_packet(&event) {
+                if refresh.connection_handle == connection_handle {
+                    refresh.status.error()?;
+                    return Ok(());
+                }
+            }
+            self.adapter.event_handler.handle(event)?;
+        }
+    }
+    /// Answer a [`LongTermKeyRequest`](le::encryption::LongTermKeyRequest) with the key or,
+    /// with `None`, reject it.
+    pub async fn reply_long_term_key(
+        &mut self,
+        connection_handle: ConnectionHandle,
+        ltk: Option<LTK>,
+    ) -> Result<(), adapter::Error> {
+        match ltk {
+            Some(ltk) => self
+                .adapter
+                .hci_send_command(le::commands::LongTermKeyRequestReply {
+                    connection_handle,
+                    ltk,
+                })
+                .await?
+                .params
+                .status
+                .error()?,
+            None => self
+                .adapter
+                .hci_send_command(le::commands::LongTermKeyRequestNegativeReply {
+                    connection_handle,
+                })
+                .await?
+                .params
+                .status
+                .error()?,
+        }
+        Ok(())
+    }
+    /// Read (and cache) the controller's LE features. The cached features are used by
+    /// [`LEAdapter::capabilities`](Backend::capabilities).
+    pub async fn read_local_features(&mut self) -> Result<LEFeatures, adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(ReadLocalFeatures)
+            .await?
+            .params;
+        r.status.error()?;
+        self.local_features = Some(r.features);
+        Ok(r.features)
+    }
+    /// Cached features from the last [`LEAdapter::read_local_features`].
+    pub fn local_features(&self) -> Option<LEFeatures> {
+        self.local_features
+    }
+    /// Read the LE features of the peer on `connection_handle`. Waits for the
+    /// [`ReadRemoteFeaturesComplete`] event.
+    pub async fn read_remote_features(
+        &mut self,
+        connection_handle: ConnectionHandle,
+    ) -> Result<LEFeatures, adapter::Error> {
+        self.adapter
+            .hci_send_command(ReadRemoteFeatures { connection_handle })
+            .await?
+            .status
+            .error()?;
+        loop {
+            let event = self.adapter.hci_read_event().await?;
+            if let Ok(complete) = ReadRemoteFeaturesComplete::unpack_event_packet(&event) {
+                if complete.connection_handle == connection_handle {
+                    complete.status.error()?;
+                    return Ok(complete.features);
+                }
+            }
+            self.adapter.event_handler.handle(event)?;
+        }
+    }
+    /// Mark the channels cleared in `channel_map` as bad for every connection.
+    pub async fn set_host_channel_classification(
+        &mut self,
+        channel_map: DataChannelMap,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::SetHostChannelClassification { channel_map })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Channels `connection_handle` currently hops over.
+    pub async fn read_channel_map(
+        &mut self,
+        connection_handle: ConnectionHandle,
+    ) -> Result<DataChannelMap, adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(le::commands::ReadChannelMap { connection_handle })
+            .await?
+            .params;
+        r.status.error()?;
+        Ok(r.channel_map)
+    }
+    /// Stop the running Direct Test Mode test. Returns the number of packets received (`0` for a
+    /// transmitter test).
+    pub async fn test_end(&mut self) -> Result<u16, adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(le::commands::TestEnd)
+            .await?
+            .params;
+        r.status.error()?;
+        Ok(r.num_packets)
+    }
+    /// Change the connection parameters of `connection_handle`. Returns the Connection Update
+    /// Complete event, with the parameters the connection ended up with.
+    pub async fn connection_update(
+        &mut self,
+        connection_handle: ConnectionHandle,
+        parameters: ConnectionParameters,
+    ) -> Result<ConnectionUpdateComplete, adapter::Error> {
+        self.adapter
+            .hci_send_command(ConnectionUpdate::new(connection_handle, parameters))
+            .await?
+            .status
+            .error()?;
+        loop {
+            let event = self.adapter.hci_read_event().await?;
+            if let Ok(complete) = ConnectionUpdateComplete::unpack_event_packet(&event) {
+                if complete.connection_handle == connection_handle {
+                    complete.status.error()?;
+                    return Ok(complete);
+                }
+            }
+            self.adapter.event_handler.handle(event)?;
+        }
+    }
+    /// Ask for TX payloads up to `tx` on `connection_handle`. The link only changes once the
+    /// peer agrees, reported by a [`DataLengthChange`](le::data_length::DataLengthChange) event.
+    pub async fn set_data_length(
+        &mut self,
+        connection_handle: ConnectionHandle,
+        tx: DataLength,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::SetDataLength {
+                connection_handle,
+                tx,
+            })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// TX data length the controller uses for new connections.
+    pub async fn read_suggested_default_data_length(
+        &mut self,
+    ) -> Result<DataLength, adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(le::commands::ReadSuggestedDefaultDataLength {})
+            .await?;
+        r.params.status.error()?;
+        Ok(r.params.tx)
+    }
+    pub async fn write_suggested_default_data_length(
+        &mut self,
+        tx: DataLength,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::WriteSuggestedDefaultDataLength { tx })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Longest TX and RX data lengths the controller supports.
+    pub async fn read_maximum_data_length(
+        &mut self,
+    ) -> Result<MaximumDataLengthReturn, adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(le::commands::ReadMaximumDataLength {})
+            .await?;
+        r.params.status.error()?;
+        Ok(r.params)
+    }
     pub async fn set_meta_event_mask(&mut self, mask: MetaEventMask) -> Result<(), adapter::Error> {
         self.adapter
             .hci_send_command(SetMetaEventMask(mask))
@@ -147,6 +670,24 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
             .params
             .status
             .error()?;
+        self.adapter.controller_state_mut().set_meta_event_mask(mask);
+        Ok(())
+    }
+    /// Set one of the host supported bits of the LE feature mask. Applied again by
+    /// [`Adapter::restore`].
+    pub async fn set_host_feature(
+        &mut self,
+        bit: HostFeatureBit,
+        is_enabled: bool,
+    ) -> Result<(), adapter::Error> {
+        let feature = SetHostFeature::new(bit, is_enabled);
+        self.adapter
+            .hci_send_command(feature)
+            .await?
+            .params
+            .status
+            .error()?;
+        self.adapter.controller_state_mut().set_host_feature(feature);
         Ok(())
     }
 
@@ -165,20 +706,294 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
             .error()?;
         Ok(())
     }
+    /// Read how many advertising sets the controller can advertise at once. Handles from `0` to
+    /// one less than this are valid.
+    pub async fn read_number_of_supported_advertising_sets(
+        &mut self,
+    ) -> Result<u8, adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(le::extended_advertise::ReadNumberOfSupportedAdvertisingSets {})
+            .await?;
+        r.params.status.error()?;
+        Ok(r.params.num_sets)
+    }
+    /// Read how many bytes of advertising (or scan response) data one advertising set can hold.
+    pub async fn read_maximum_advertising_data_length(&mut self) -> Result<u16, adapter::Error> {
+        let r = self
+            .adapter
+            .hci_send_command(le::extended_advertise::ReadMaximumAdvertisingDataLength {})
+            .await?;
+        r.params.status.error()?;
+        Ok(r.params.max_data_len)
+    }
+    /// Remove advertising set `handle`. It must be disabled first.
+    pub async fn remove_advertising_set(
+        &mut self,
+        handle: AdvertisingSetHandle,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::extended_advertise::RemoveAdvertisingSet { handle })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Remove every advertising set. They must all be disabled first.
+    pub async fn clear_advertising_sets(&mut self) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::extended_advertise::ClearAdvertisingSets {})
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Set the periodic advertising parameters of advertising set `parameters.handle`.
+    /// # Errors
+    /// Returns `adapter::Error::BadParameter` if `parameters.interval_min` is more than
+    /// `parameters.interval_max`.
+    pub async fn set_periodic_advertising_parameters(
+        &mut self,
+        parameters: PeriodicAdvertisingParameters,
+    ) -> Result<(), adapter::Error> {
+        if !parameters.is_valid() {
+            return Err(adapter::Error::BadParameter);
+        }
+        self.adapter
+            .hci_send_command(le::commands::SetPeriodicAdvertisingParameters(parameters))
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Set the periodic advertising data of advertising set `handle`, sent in as many fragments
+    /// as needed.
+    /// # Errors
+    /// Returns `adapter::Error::BadParameter` if `data.len()` is more than
+    /// [`le::commands::SetPeriodicAdvertisingData::MAX_DATA_LEN`] (1650).
+    pub async fn set_periodic_advertising_data(
+        &mut self,
+        handle: AdvertisingSetHandle,
+        data: &[u8],
+    ) -> Result<(), adapter::Error> {
+        if data.len() > le::commands::SetPeriodicAdvertisingData::MAX_DATA_LEN {
+            return Err(adapter::Error::BadParameter);
+        }
+        for fragment in le::commands::SetPeriodicAdvertisingData::fragments(handle, data) {
+            self.adapter
+                .hci_send_command(fragment)
+                .await?
+                .params
+                .status
+                .error()?;
+        }
+        Ok(())
+    }
+    /// Enable or disable periodic advertising of advertising set `handle`. The train is only
+    /// sent while the set is enabled too.
+    pub async fn set_periodic_advertising_enable(
+        &mut self,
+        handle: AdvertisingSetHandle,
+        is_enabled: bool,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::SetPeriodicAdvertisingEnable::new(
+                handle, is_enabled,
+            ))
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Start syncing to a periodic advertising train. Await [`PendingCommand::complete`] for
+    /// the [`SyncHandle`] (in the sync established event) or [`PendingCommand::cancel`] it.
+    /// # Errors
+    /// Returns `adapter::Error::BadParameter` if `skip` or `sync_timeout` are out of range.
+    pub async fn start_periodic_sync(
+        &mut self,
+        create_sync: PeriodicAdvertisingCreateSync,
+    ) -> Result<PendingCommand<'_, A, H, PeriodicAdvertisingCreateSync>, adapter::Error> {
+        if !create_sync.is_valid() {
+            return Err(adapter::Error::BadParameter);
+        }
+        self.adapter.hci_start_command(create_sync).await
+    }
+    /// Stop receiving the periodic advertising train of `sync_handle`.
+    pub async fn terminate_periodic_sync(
+        &mut self,
+        sync_handle: SyncHandle,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::PeriodicAdvertisingTerminateSync { sync_handle })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Clear the Periodic Advertiser List and add `advertisers` to it.
+    pub async fn set_periodic_advertiser_list(
+        &mut self,
+        advertisers: &[PeriodicAdvertiserListEntry],
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::ClearPeriodicAdvertiserList {})
+            .await?
+            .params
+            .status
+            .error()?;
+        for advertiser in advertisers {
+            self.adapter
+                .hci_send_command(le::commands::AddDeviceToPeriodicAdvertiserList(*advertiser))
+                .await?
+                .params
+                .status
+                .error()?;
+        }
+        Ok(())
+    }
+    /// Send the sync info of `sync_handle` to the peer of `connection_handle` (PAST).
+    /// `service_data` is passed to the peer's host.
+    pub async fn transfer_periodic_sync(
+        &mut self,
+        connection_handle: ConnectionHandle,
+        service_data: u16,
+        sync_handle: SyncHandle,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::PeriodicAdvertisingSyncTransfer {
+                connection_handle,
+                service_data,
+                sync_handle,
+            })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Set how sync info received from the peer of `parameters.connection_handle` is handled.
+    /// Syncs from it are reported with `PeriodicAdvertisingSyncTransferReceived` events.
+    /// # Errors
+    /// Returns `adapter::Error::BadParameter` if `skip` or `sync_timeout` are out of range.
+    pub async fn set_periodic_sync_transfer_parameters(
+        &mut self,
+        parameters: SetPeriodicAdvertisingSyncTransferParameters,
+    ) -> Result<(), adapter::Error> {
+        if !parameters.is_valid() {
+            return Err(adapter::Error::BadParameter);
+        }
+        self.adapter
+            .hci_send_command(parameters)
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Set the path loss thresholds of `parameters.connection_handle` and start reporting zone
+    /// changes (see [`LEAdapter::path_loss_stream`]).
+    /// # Errors
+    /// Returns `adapter::Error::BadParameter` if the thresholds overlap.
+    pub async fn enable_path_loss_reporting(
+        &mut self,
+        parameters: SetPathLossReportingParameters,
+    ) -> Result<(), adapter::Error> {
+        if !parameters.is_valid() {
+            return Err(adapter::Error::BadParameter);
+        }
+        let connection_handle = parameters.connection_handle;
+        self.adapter
+            .hci_send_command(parameters)
+            .await?
+            .params
+            .status
+            .error()?;
+        self.set_path_loss_reporting_enable(connection_handle, true)
+            .await
+    }
+    pub async fn disable_path_loss_reporting(
+        &mut self,
+        connection_handle: ConnectionHandle,
+    ) -> Result<(), adapter::Error> {
+        self.set_path_loss_reporting_enable(connection_handle, false)
+            .await
+    }
+    async fn set_path_loss_reporting_enable(
+        &mut self,
+        connection_handle: ConnectionHandle,
+        is_enabled: bool,
+    ) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(le::commands::SetPathLossReportingEnable {
+                connection_handle,
+                is_enabled,
+            })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Enables `PathLossThreshold` events (using the event masks) and returns a Stream of the
+    /// zone changes of every connection with path loss reporting enabled.
+    pub async fn path_loss_stream<'a>(
+        &'a mut self,
+    ) -> Result<impl Stream<Item = Result<PathLossThreshold, adapter::Error>> + 'a, adapter::Error>
+    {
+        let mut meta_mask = MetaEventMask::zeroed();
+        meta_mask.enable_event(MetaEventCode::PathLossThreshold);
+        let mut event_mask = EventMask::zeroed();
+        event_mask.enable_event(EventMaskFlags::LEMetaEvent);
+        self.adapter.set_event_mask(event_mask).await?;
+        self.set_meta_event_mask(meta_mask).await?;
+        Ok(self.path_loss_stream_without_mask())
+    }
+    /// Stream of `PathLossThreshold` events without setting the event masks. Other meta events
+    /// are skipped.
+    pub fn path_loss_stream_without_mask<'a>(
+        &'a mut self,
+    ) -> impl Stream<Item = Result<PathLossThreshold, adapter::Error>> + 'a {
+        self.meta_event_stream_without_mask().filter_map(
+            |meta_event: Result<RawMetaEvent<Box<[u8]>>, adapter::Error>| async move {
+                match meta_event {
+                    Ok(event) if event.code == MetaEventCode::PathLossThreshold => Some(
+                        PathLossThreshold::meta_unpack_packet(event.as_ref())
+                            .map_err(|e| adapter::Error::StreamError(StreamError::EventError(e))),
+                    ),
+                    Ok(_) => None,
+                    Err(e) => Some(Err(e)),
+                }
+            },
+        )
+    }
     /// Returns a Stream of `RawMetaEvent`s without setting the HCI `EventMask`.
     pub fn meta_event_stream_without_mask<'a, 'b: 'a, Buf: Storage<u8> + 'b>(
         &'a mut self,
     ) -> impl Stream<Item = Result<RawMetaEvent<Buf>, adapter::Error>> + 'a {
+        let on_anomaly = self.adapter.on_anomaly;
         self.adapter.hci_event_stream().filter_map(
-            |p: Result<EventPacket<Buf>, adapter::Error>| async move {
+            move |p: Result<EventPacket<H::Buf>, adapter::Error>| async move {
                 let event = match p {
                     Ok(event) => event,
                     Err(e) => return Some(Err(e)),
                 };
                 // Ignore all non-LEMeta HCI Events
                 if event.event_code == EventCode::LEMeta {
-                    let meta_event = RawMetaEvent::try_from(event.as_ref())
-                        .map_err(|e| adapter::Error::StreamError(StreamError::EventError(e)));
+                    let meta_event = RawMetaEvent::try_from(event.as_ref()).map_err(|error| {
+                        anomaly::report(
+                            on_anomaly,
+                            Anomaly::ParseFailure {
+                                event_code: EventCode::LEMeta.into(),
+                                error,
+                            },
+                        );
+                        adapter::Error::StreamError(StreamError::EventError(error))
+                    });
                     Some(meta_event.map(|e| e.to_owned()))
                 } else {
                     None
@@ -221,16 +1036,27 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
     >(
         &'a mut self,
     ) -> impl Stream<Item = Result<AdvertisingReport<Buf>, adapter::Error>> + 'a {
+        let on_anomaly = self.adapter.on_anomaly;
         self.meta_event_stream_without_mask().filter_map(
-            |meta_event: Result<RawMetaEvent<Box<[u8]>>, adapter::Error>| async move {
+            move |meta_event: Result<RawMetaEvent<Box<[u8]>>, adapter::Error>| async move {
                 // We expect only AdvertisingReport Meta events to get through because the HCI
                 // filter should be set for that. Otherwise if a non-`AdvertisingReport`
                 // packet gets through, this will return `PackError::BadOpcode` because
                 // an LEMeta event with an Event Code of anything but `AdvertisingReport` got
                 // through.
                 Some(meta_event.and_then(|event| {
-                    AdvertisingReport::meta_unpack_packet(event.as_ref().as_ref())
-                        .map_err(|e| adapter::Error::StreamError(StreamError::EventError(e)))
+                    AdvertisingReport::meta_unpack_packet(event.as_ref().as_ref()).map_err(
+                        |error| {
+                            anomaly::report(
+                                on_anomaly,
+                                Anomaly::ParseFailure {
+                                    event_code: EventCode::LEMeta.into(),
+                                    error,
+                                },
+                            );
+                            adapter::Error::StreamError(StreamError::EventError(error))
+                        },
+                    )
                 }))
             },
         )
@@ -245,6 +1071,7 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
         impl Stream<Item = Result<ReportInfo<StaticAdvBuffer>, adapter::Error>> + 'a,
         adapter::Error,
     > {
+        let calibration = self.rssi_calibration.clone();
         Ok(self
             .advertising_report_stream::<Buf>()
             .await?
@@ -258,10 +1085,39 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
                     )),
                 },
             )
-            .flatten())
+            .flatten()
+            .map(move |r| {
+                r.map(|mut report| {
+                    calibration.calibrate_report(&mut report);
+                    report
+                })
+            }))
+    }
+    /// [`LEAdapter::advertisement_stream`] delivered in batches (see [`Batched`]). `delay` is the
+    /// async runtime's sleep.
+    pub async fn advertisement_batch_stream<
+        'a,
+        'b: 'a,
+        Buf: Storage<ReportInfo<StaticAdvBuffer>> + 'b,
+    >(
+        &'a mut self,
+        config: BatchDelivery,
+        delay: DelayFn,
+    ) -> Result<
+        impl Stream<Item = Result<Vec<ReportInfo<StaticAdvBuffer>>, adapter::Error>> + 'a,
+        adapter::Error,
+    > {
+        let reports = self.advertisement_stream::<Buf>().await?.boxed_local();
+        Ok(Batched::new(reports, config, delay))
     }
 }
 
+impl<A: adapter::Adapter, H: UnrecognizedEventHandler> Backend for LEAdapter<A, H> {
+    /// Only legacy advertising is reported until [`LEAdapter::read_local_features`] is called.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::from_le_features(self.local_features)
+    }
+}
 impl<A: adapter::Adapter, H: UnrecognizedEventHandler> Advertiser for LEAdapter<A, H> {
     fn set_advertising_enable(
         &mut self,
@@ -286,6 +1142,64 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> Advertiser for LEAdapter<
     ) -> LocalBoxFuture<'s, Result<(), adapter::Error>> {
         Box::pin(LEAdapter::set_advertising_data(self, data))
     }
+
+    fn set_scan_response_data<'s, 'b: 's>(
+        &'b mut self,
+        data: &'s [u8],
+    ) -> LocalBoxFuture<'s, Result<(), adapter::Error>> {
+        Box::pin(LEAdapter::set_scan_response_data(self, data))
+    }
+
+    fn set_whitelist<'s, 'b: 's>(
+        &'b mut self,
+        entries: &'s [WhitelistEntry],
+    ) -> LocalBoxFuture<'s, Result<(), adapter::Error>> {
+        Box::pin(LEAdapter::set_whitelist(self, entries))
+    }
+}
+
+impl<A: adapter::Adapter, H: UnrecognizedEventHandler> PeriodicAdvertiser for LEAdapter<A, H> {
+    fn set_periodic_advertising_parameters<'a>(
+        &'a mut self,
+        parameters: PeriodicAdvertisingParameters,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(LEAdapter::set_periodic_advertising_parameters(
+            self, parameters,
+        ))
+    }
+
+    fn set_periodic_advertising_data<'s, 'b: 's>(
+        &'b mut self,
+        handle: AdvertisingSetHandle,
+        data: &'s [u8],
+    ) -> LocalBoxFuture<'s, Result<(), adapter::Error>> {
+        Box::pin(LEAdapter::set_periodic_advertising_data(self, handle, data))
+    }
+
+    fn set_periodic_advertising_enable<'a>(
+        &'a mut self,
+        handle: AdvertisingSetHandle,
+        is_enabled: bool,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(LEAdapter::set_periodic_advertising_enable(
+            self, handle, is_enabled,
+        ))
+    }
+}
+
+impl<A: adapter::Adapter, H: UnrecognizedEventHandler> AdvertisingTxPower for LEAdapter<A, H> {
+    /// Only legacy advertising is supported so `set` must be `None`.
+    fn advertising_tx_power<'a>(
+        &'a mut self,
+        set: Option<AdvertisingSetHandle>,
+    ) -> LocalBoxFuture<'a, Result<le::advertise::TxPowerLevel, adapter::Error>> {
+        Box::pin(async move {
+            if set.is_some() {
+                return Err(adapter::Error::BadParameter);
+            }
+            self.get_advertising_tx_power().await
+        })
+    }
 }
 
 impl<A: adapter::Adapter, H: UnrecognizedEventHandler> Observer for LEAdapter<A, H> {
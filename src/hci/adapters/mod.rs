@@ -1,20 +1,77 @@
 //! Contains logic for HCI Adapters (usually byte streams).
 pub mod buffer;
+#[cfg(feature = "classic")]
+pub mod classic;
+pub mod flow_control;
 pub mod le;
+pub mod pending;
+pub mod registry;
+pub mod restore;
+pub mod retry;
+pub mod split;
+pub mod testing;
+pub mod trace;
 
 use alloc::boxed::Box;
 use crate::bytes::Storage;
 use crate::hci::adapter;
 use crate::hci::adapters::le::LEAdapter;
-use crate::hci::baseband::{EventMask, Reset, SetEventMask};
-use crate::hci::command::Command;
-use crate::hci::event::EventPacket;
-use crate::Stream;
+use crate::hci::adapters::pending::PendingCommand;
+use crate::hci::adapters::restore::{ControllerState, ReadyCondition};
+use crate::hci::adapters::retry::{RetryPolicies, RetryPolicy};
+use crate::hci::adapters::trace::Tracer;
+use crate::hci::anomaly::{self, Anomaly, AnomalyHook};
+use crate::hci::baseband::{
+    EventMask, LocalName, ReadLocalName, ReadTransmitPowerLevel, Reset, SetEventMask,
+    TransmitPowerLevelType, WriteLocalName,
+};
+use crate::hci::command::{Command, LongCommand};
+use crate::hci::event::{Event, EventPacket};
+use crate::hci::stream::HCI_EVENT_READ_TRIES;
+use crate::hci::{ErrorCode, Opcode, StreamError};
+use alloc::collections::VecDeque;
+use crate::hci::informational::{
+    LMPFeatures, LocalVersionInformation, ReadBDADDR, ReadLocalSupportedCommands,
+    ReadLocalSupportedFeatures, ReadLocalVersionInformation, SupportedCommands,
+};
+use crate::hci::link_control::{
+    Disconnect, ReadRemoteVersionInformation, ReadRemoteVersionInformationComplete,
+    RemoteVersion,
+};
+use crate::hci::le::advertise::TxPowerLevel;
+use crate::hci::status::ReadRSSI;
+use crate::le::connection::ConnectionHandle;
+use crate::{BTAddress, PackError, Stream, RSSI};
 
+/// Maximum number of abandoned commands to remember. Controllers only allow a few outstanding
+/// commands so older entries will never see a return.
+pub const MAX_ABANDONED_COMMANDS: usize = 8;
+/// Who waits for the return of a command written to the controller.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+enum Waiter {
+    /// [`Adapter::hci_send_command`] reads the return itself.
+    InFlight,
+    /// The [`Adapter::hci_send_command`] future was dropped. The return is passed to
+    /// [`UnrecognizedEventHandler::handle_late_return`].
+    Abandoned,
+    /// Written with [`Adapter::hci_write_command`]. The return is kept for
+    /// [`Adapter::hci_read_return`].
+    Pipelined,
+}
 // TODO: Make this more generic
 pub trait UnrecognizedEventHandler {
     type Buf: Storage<u8>;
     fn handle(&mut self, event: EventPacket<Self::Buf>) -> Result<(), adapter::Error>;
+    /// Called with the Command Complete/Status of a command whose future was dropped before its
+    /// return arrived. Defaults to [`UnrecognizedEventHandler::handle`].
+    fn handle_late_return(
+        &mut self,
+        opcode: Opcode,
+        event: EventPacket<Self::Buf>,
+    ) -> Result<(), adapter::Error> {
+        let _ = opcode;
+        self.handle(event)
+    }
 }
 pub struct DummyUnrecognizedEventHandler<Buf = Box<[u8]>>(core::marker::PhantomData<Buf>);
 impl<Buf> DummyUnrecognizedEventHandler<Buf> {
@@ -33,9 +90,100 @@ impl<Buf: Storage<u8>> UnrecognizedEventHandler for DummyUnrecognizedEventHandle
         Ok(())
     }
 }
+/// What to do when a command is newer than the controller's Core version.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum VersionPolicy {
+    /// Return `adapter::Error::UnsupportedVersion` without sending the command.
+    Error,
+    /// Send the command anyway (some controllers support newer commands than they report).
+    SendAnyway,
+}
+impl Default for VersionPolicy {
+    fn default() -> Self {
+        VersionPolicy::Error
+    }
+}
+/// How the read loop shares time with other tasks and keeps events from delaying command
+/// returns (a busy RF environment can produce hundreds of advertising reports a second).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct Fairness {
+    /// Yield to the executor after this many events read back to back. `0` never yields. Reads
+    /// from a busy transport are often ready immediately so other tasks would never get a turn.
+    pub yield_every: u16,
+    /// Events held back (instead of passed to the event handler) while waiting for a command
+    /// return, so a slow handler doesn't delay it. They're delivered in order by
+    /// [`Adapter::hci_read_event`] or before the next command. Past this many the oldest are
+    /// handled first to keep the order. `0` handles every event on the spot.
+    pub max_deferred: usize,
+    /// Events that aren't command returns read while waiting for a return before giving up
+    /// with `StreamError::StreamFailed`, in case the return was lost. `0` never gives up.
+    pub max_events_per_return: u16,
+}
+impl Fairness {
+    pub const DEFAULT: Fairness = Fairness {
+        yield_every: 16,
+        max_deferred: 64,
+        max_events_per_return: 1024,
+    };
+}
+impl Default for Fairness {
+    fn default() -> Self {
+        Fairness::DEFAULT
+    }
+}
+/// Yield the task back to the executor. Returns `Poll::Pending` once after waking itself.
+pub async fn yield_now() {
+    struct YieldNow {
+        yielded: bool,
+    }
+
+    impl core::future::Future for YieldNow {
+        type Output = ();
+
+        fn poll(
+            mut self: core::pin::Pin<&mut Self>,
+            cx: &mut core::task::Context<'_>,
+        ) -> core::task::Poll<()> {
+            if self.yielded {
+                return core::task::Poll::Ready(());
+            }
+
+            self.yielded = true;
+            cx.waker().wake_by_ref();
+            core::task::Poll::Pending
+        }
+    }
+
+    YieldNow { yielded: false }.await
+}
 pub struct Adapter<A: adapter::Adapter, H: UnrecognizedEventHandler> {
     pub adapter: A,
     pub event_handler: H,
+    pub version_policy: VersionPolicy,
+    /// Retries for commands failing with transient status codes. Nothing is retried by default.
+    pub retry_policies: RetryPolicies,
+    /// Records the round trip latency of every command when set.
+    pub tracer: Option<Tracer>,
+    /// When [`Adapter::restore`] can start sending commands after a Reset.
+    pub ready_condition: ReadyCondition,
+    pub fairness: Fairness,
+    /// Called with command credit stalls and returns that can't be unpacked.
+    pub on_anomaly: Option<AnomalyHook>,
+    /// Masks, features and setup commands to apply again after a Reset.
+    controller_state: ControllerState,
+    local_version: Option<LocalVersionInformation>,
+    /// Commands written whose return hasn't arrived yet, oldest first. Returns go to the oldest
+    /// command with the same opcode.
+    outstanding: VecDeque<(Opcode, Waiter)>,
+    /// `Num_HCI_Command_Packets` from the last Command Complete/Status, minus the commands
+    /// written since.
+    command_credits: u8,
+    /// Returns of pipelined commands waiting for [`Adapter::hci_read_return`].
+    returns: VecDeque<EventPacket<H::Buf>>,
+    /// Events read while waiting for a command return (see [`Fairness::max_deferred`]).
+    deferred: VecDeque<EventPacket<H::Buf>>,
+    /// Events read since the read loop last yielded.
+    events_since_yield: u16,
 }
 impl<A: adapter::Adapter> Adapter<A, DummyUnrecognizedEventHandler<Box<[u8]>>> {
     pub fn new(adapter: A) -> Self {
@@ -50,33 +198,427 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> Adapter<A, H> {
         Self {
             adapter,
             event_handler,
+            version_policy: VersionPolicy::default(),
+            retry_policies: RetryPolicies::default(),
+            tracer: None,
+            ready_condition: ReadyCondition::default(),
+            fairness: Fairness::default(),
+            on_anomaly: None,
+            controller_state: ControllerState::default(),
+            local_version: None,
+            outstanding: VecDeque::new(),
+            // Controllers accept one command until they say otherwise.
+            command_credits: 1,
+            returns: VecDeque::new(),
+            deferred: VecDeque::new(),
+            events_since_yield: 0,
         }
     }
     pub fn le(self) -> le::LEAdapter<A, H> {
         LEAdapter::new(self)
     }
+    #[cfg(feature = "classic")]
+    pub fn classic(self) -> classic::ClassicAdapter<A, H> {
+        classic::ClassicAdapter::new(self)
+    }
     pub async fn hci_send_command<'a, 'c: 'a, Cmd: Command + 'c>(
         &mut self,
         cmd: Cmd,
     ) -> Result<Cmd::Return, adapter::Error> {
-        let event_handler = &mut self.event_handler;
-        adapter::send_command::<_, _, H::Buf, _>(
-            &mut self.adapter,
-            cmd,
-            Some(|e| event_handler.handle(e)),
-        )
-        .await
+        #[cfg(feature = "version_check")]
+        self.check_version::<Cmd>()?;
+        // A previous command future was dropped before its return was read.
+        self.abandon_in_flight();
+        // Events held back by the previous command shouldn't wait any longer.
+        self.handle_deferred()?;
+        let packet = cmd
+            .pack_command_packet::<H::Buf>()
+            .map_err(StreamError::CommandError)?;
+        let policy = self.retry_policies.policy_for(Cmd::opcode());
+        let mut retry = 0_u8;
+        // Retries are part of the same traced round trip.
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.start_command(Cmd::opcode());
+        }
+        loop {
+            self.wait_for_credit(Cmd::opcode()).await?;
+            self.adapter.write_command(packet.as_ref()).await?;
+            self.command_credits -= 1;
+            // Only track the command once it's been written. If this future is dropped from here
+            // on, its return will be treated as a late one.
+            self.outstanding
+                .push_back((Cmd::opcode(), Waiter::InFlight));
+            let (ret, status) = self.read_return::<Cmd>().await?;
+            match status {
+                Some(status) if retry < policy.max_retries && RetryPolicy::is_transient(status) => {
+                    retry += 1;
+                    if let Some(delay) = self.retry_policies.delay {
+                        delay(policy.backoff(retry)).await;
+                    }
+                }
+                _ => {
+                    if let Some(tracer) = self.tracer.as_mut() {
+                        tracer.finish_command(Cmd::opcode());
+                    }
+                    return Ok(ret);
+                }
+            }
+        }
     }
-    pub async fn hci_read_event<Buf: Storage<u8>>(
+    /// Send a [`LongCommand`] and wait for its Command Status. Returns once the controller has
+    /// accepted it; await [`PendingCommand::complete`] for the result or
+    /// [`PendingCommand::cancel`] it.
+    pub async fn hci_start_command<Cmd: LongCommand + Clone>(
         &mut self,
-    ) -> Result<EventPacket<Buf>, adapter::Error> {
+        cmd: Cmd,
+    ) -> Result<PendingCommand<'_, A, H, Cmd>, adapter::Error> {
+        self.hci_send_command(cmd.clone()).await?.status.error()?;
+        Ok(PendingCommand::new(self, cmd))
+    }
+    /// Read events until the return of `Cmd` arrives. Also returns the status it carries. Other
+    /// events are deferred (see [`Fairness::max_deferred`]).
+    async fn read_return<Cmd: Command>(
+        &mut self,
+    ) -> Result<(Cmd::Return, Option<ErrorCode>), adapter::Error> {
+        let mut tries = 0;
+        let mut events = 0_u16;
+        while tries < HCI_EVENT_READ_TRIES {
+            let event: EventPacket<H::Buf> = self.read_next_event().await?;
+            let event = match self.sort_event(event, true)? {
+                Some(event) => event,
+                None => continue,
+            };
+            let ret = Cmd::unpack_return(event.as_ref()).map_err(|error| {
+                anomaly::report(
+                    self.on_anomaly,
+                    Anomaly::ParseFailure {
+                        event_code: event.event_code.into(),
+                        error,
+                    },
+                );
+                StreamError::EventError(error)
+            })?;
+            if let Some(ret) = ret {
+                return Ok((ret, event.return_status()));
+            }
+            if event.return_opcode() == Some(Cmd::opcode()) {
+                // Command Status for a command answered with Command Complete (or the other way
+                // round), usually the controller rejecting the command outright.
+                if let Some(status) = event.return_status() {
+                    status.error()?;
+                }
+                return Err(StreamError::EventError(PackError::BadOpcode).into());
+            }
+            // Returns of other commands and other events are counted separately. Hundreds of
+            // reports can arrive before the return on a busy controller.
+            if event.return_opcode().is_some() {
+                tries += 1;
+            } else {
+                events = events.saturating_add(1);
+            }
+            self.defer_event(event)?;
+            if events == self.fairness.max_events_per_return {
+                break;
+            }
+        }
+        Err(adapter::Error::StreamError(StreamError::StreamFailed))
+    }
+    /// Read the next event from the transport, yielding to the executor every
+    /// [`Fairness::yield_every`] events.
+    async fn read_next_event(&mut self) -> Result<EventPacket<H::Buf>, adapter::Error> {
+        if self.fairness.yield_every != 0 {
+            self.events_since_yield += 1;
+            if self.events_since_yield >= self.fairness.yield_every {
+                self.events_since_yield = 0;
+                yield_now().await;
+            }
+        }
         self.adapter.read_event().await
     }
-    pub fn hci_event_stream<'a, 'b: 'a, Buf: Storage<u8> + 'b>(
+    /// Hold `event` back until the command return arrives. If [`Fairness::max_deferred`] events
+    /// are already held back the oldest is passed to the event handler first so events are
+    /// always handled in the order they arrived.
+    fn defer_event(&mut self, event: EventPacket<H::Buf>) -> Result<(), adapter::Error> {
+        if self.fairness.max_deferred == 0 {
+            return self.event_handler.handle(event);
+        }
+        if self.deferred.len() >= self.fairness.max_deferred {
+            if let Some(oldest) = self.deferred.pop_front() {
+                self.event_handler.handle(oldest)?;
+            }
+        }
+        self.deferred.push_back(event);
+        Ok(())
+    }
+    /// Pass the deferred events to the event handler.
+    fn handle_deferred(&mut self) -> Result<(), adapter::Error> {
+        while let Some(event) = self.deferred.pop_front() {
+            self.event_handler.handle(event)?;
+        }
+        Ok(())
+    }
+    /// Number of events deferred while waiting for command returns and not delivered yet.
+    pub fn deferred_events(&self) -> usize {
+        self.deferred.len()
+    }
+    /// Update the command credits from `event` and take it if it's the return of an abandoned
+    /// or pipelined command. Otherwise gives `event` back. Returns go to the oldest outstanding
+    /// command with the same opcode. `awaiting_return` is set when [`Adapter::hci_send_command`]
+    /// reads the return of the in-flight command itself. Anywhere else that command's future
+    /// must have been dropped so its return is a late one too.
+    fn sort_event(
+        &mut self,
+        event: EventPacket<H::Buf>,
+        awaiting_return: bool,
+    ) -> Result<Option<EventPacket<H::Buf>>, adapter::Error> {
+        if let Some(credits) = event.return_num_command_packets() {
+            self.command_credits = credits;
+        }
+        let opcode = match event.return_opcode() {
+            Some(opcode) => opcode,
+            None => return Ok(Some(event)),
+        };
+        let (i, waiter) = match self.outstanding.iter().position(|(o, _)| *o == opcode) {
+            Some(i) => (i, self.outstanding[i].1),
+            None => return Ok(Some(event)),
+        };
+        self.outstanding.remove(i);
+        match waiter {
+            Waiter::InFlight if awaiting_return => Ok(Some(event)),
+            Waiter::Pipelined => {
+                self.returns.push_back(event);
+                Ok(None)
+            }
+            Waiter::InFlight | Waiter::Abandoned => {
+                if let Some(tracer) = self.tracer.as_mut() {
+                    tracer.finish_command(opcode);
+                }
+                self.event_handler.handle_late_return(opcode, event)?;
+                Ok(None)
+            }
+        }
+    }
+    /// Mark the in-flight command (if any) as abandoned. Only the newest
+    /// [`MAX_ABANDONED_COMMANDS`] abandoned commands are remembered.
+    fn abandon_in_flight(&mut self) {
+        let waiter = self
+            .outstanding
+            .iter_mut()
+            .find(|(_, waiter)| *waiter == Waiter::InFlight);
+        if let Some((_, waiter)) = waiter {
+            *waiter = Waiter::Abandoned;
+        }
+        let abandoned = |(_, waiter): &(Opcode, Waiter)| *waiter == Waiter::Abandoned;
+        if self.outstanding.iter().filter(|c| abandoned(c)).count() > MAX_ABANDONED_COMMANDS {
+            if let Some(i) = self.outstanding.iter().position(abandoned) {
+                self.outstanding.remove(i);
+            }
+        }
+    }
+    /// Wait (reading events) until the controller has a free command slot.
+    async fn wait_for_credit(&mut self, opcode: Opcode) -> Result<(), adapter::Error> {
+        if self.command_credits == 0 {
+            anomaly::report(self.on_anomaly, Anomaly::CreditExhaustion { opcode });
+        }
+        while self.command_credits == 0 {
+            self.handle_next_event().await?;
+        }
+        Ok(())
+    }
+    /// Check `Cmd::MIN_VERSION` against the cached local version. Commands are always allowed if
+    /// the local version hasn't been read yet (see [`Adapter::read_local_version`]).
+    pub fn check_version<Cmd: Command>(&self) -> Result<(), adapter::Error> {
+        match (self.version_policy, self.local_version) {
+            (VersionPolicy::Error, Some(local)) if local.core_version() < Cmd::MIN_VERSION => {
+                Err(adapter::Error::UnsupportedVersion {
+                    opcode: Cmd::opcode(),
+                    required: Cmd::MIN_VERSION,
+                    controller: local.core_version(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+    /// Read (and cache) the controller's version information. The cached version is used to
+    /// check commands before they are sent.
+    pub async fn read_local_version(
+        &mut self,
+    ) -> Result<LocalVersionInformation, adapter::Error> {
+        let r = self
+            .hci_send_command(ReadLocalVersionInformation)
+            .await?
+            .params;
+        r.status.error()?;
+        self.local_version = Some(r);
+        Ok(r)
+    }
+    /// Cached version from the last [`Adapter::read_local_version`].
+    pub fn local_version(&self) -> Option<LocalVersionInformation> {
+        self.local_version
+    }
+    /// Read the commands the controller supports. Check optional commands with
+    /// [`SupportedCommands::supports`] before sending them.
+    pub async fn read_supported_commands(&mut self) -> Result<SupportedCommands, adapter::Error> {
+        let r = self
+            .hci_send_command(ReadLocalSupportedCommands)
+            .await?
+            .params;
+        r.status.error()?;
+        Ok(r.commands)
+    }
+    /// Read the controller's LMP features (page 0). See
+    /// [`LEAdapter::read_local_features`] for the LE features.
+    pub async fn read_lmp_features(&mut self) -> Result<LMPFeatures, adapter::Error> {
+        let r = self
+            .hci_send_command(ReadLocalSupportedFeatures)
+            .await?
+            .params;
+        r.status.error()?;
+        Ok(r.features)
+    }
+    /// Read the controller's public address (BD_ADDR). All zeros if the controller doesn't have
+    /// one.
+    pub async fn public_address(&mut self) -> Result<BTAddress, adapter::Error> {
+        let r = self.hci_send_command(ReadBDADDR).await?.params;
+        r.status.error()?;
+        Ok(r.address)
+    }
+    /// Read the RSSI of the connection `handle` (from the last packets received on it).
+    pub async fn read_rssi(&mut self, handle: ConnectionHandle) -> Result<RSSI, adapter::Error> {
+        let r = self.hci_send_command(ReadRSSI { handle }).await?.params;
+        r.status.error()?;
+        Ok(r.rssi)
+    }
+    /// Read the current or maximum TX power of the connection `handle`.
+    pub async fn read_transmit_power_level(
+        &mut self,
+        handle: ConnectionHandle,
+        level_type: TransmitPowerLevelType,
+    ) -> Result<TxPowerLevel, adapter::Error> {
+        let r = self
+            .hci_send_command(ReadTransmitPowerLevel { handle, level_type })
+            .await?
+            .params;
+        r.status.error()?;
+        Ok(r.power_level)
+    }
+    /// Close the connection `handle`. Doesn't wait for the
+    /// [`DisconnectionComplete`](crate::hci::link_control::DisconnectionComplete) event.
+    pub async fn disconnect(
+        &mut self,
+        handle: ConnectionHandle,
+        reason: ErrorCode,
+    ) -> Result<(), adapter::Error> {
+        self.hci_send_command(Disconnect { handle, reason })
+            .await?
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Version of the peer's controller on the connection `handle`. Events arriving while waiting
+    /// go to the event handler.
+    pub async fn read_remote_version(
+        &mut self,
+        handle: ConnectionHandle,
+    ) -> Result<RemoteVersion, adapter::Error> {
+        self.hci_send_command(ReadRemoteVersionInformation { handle })
+            .await?
+            .status
+            .error()?;
+        loop {
+            let event = self.hci_read_event().await?;
+            if let Ok(complete) = ReadRemoteVersionInformationComplete::unpack_event_packet(&event)
+            {
+                if complete.handle == handle {
+                    complete.status.error()?;
+                    return Ok(complete.version);
+                }
+            }
+            self.event_handler.handle(event)?;
+        }
+    }
+    /// Number of commands the controller can accept right now (`Num_HCI_Command_Packets` of
+    /// the last Command Complete/Status minus the commands written since).
+    pub fn command_credits(&self) -> u8 {
+        self.command_credits
+    }
+    /// Write `cmd` without waiting for its return, so several commands can be outstanding at
+    /// once. Waits (reading events) until the controller has a free command slot. Read the
+    /// return with [`Adapter::hci_read_return`]; returns arriving in the meantime are buffered
+    /// in any order.
+    pub async fn hci_write_command<Cmd: Command>(
+        &mut self,
+        cmd: &Cmd,
+    ) -> Result<(), adapter::Error> {
+        #[cfg(feature = "version_check")]
+        self.check_version::<Cmd>()?;
+        let packet = cmd
+            .pack_command_packet::<H::Buf>()
+            .map_err(StreamError::CommandError)?;
+        self.wait_for_credit(Cmd::opcode()).await?;
+        self.adapter.write_command(packet.as_ref()).await?;
+        self.command_credits -= 1;
+        self.outstanding
+            .push_back((Cmd::opcode(), Waiter::Pipelined));
+        Ok(())
+    }
+    /// Return of a command written with [`Adapter::hci_write_command`]. Reads events until it
+    /// arrives unless it's already buffered. Other events are passed to the event handler.
+    pub async fn hci_read_return<Cmd: Command>(&mut self) -> Result<Cmd::Return, adapter::Error> {
+        loop {
+            let opcode = Some(Cmd::opcode());
+            if let Some(i) = self
+                .returns
+                .iter()
+                .position(|e| e.return_opcode() == opcode)
+            {
+                let event = self.returns.remove(i).expect("index from position");
+                return Cmd::unpack_return(event.as_ref())
+                    .map_err(StreamError::EventError)?
+                    // Command Status for a command answered with Command Complete (or the
+                    // other way round).
+                    .ok_or(adapter::Error::StreamError(StreamError::EventError(
+                        PackError::BadOpcode,
+                    )));
+            }
+            let pipelined = (Cmd::opcode(), Waiter::Pipelined);
+            if !self.outstanding.contains(&pipelined) {
+                // Never written so nothing will arrive.
+                return Err(adapter::Error::BadParameter);
+            }
+            self.handle_next_event().await?;
+        }
+    }
+    /// Read one event and pass it to the event handler unless [`Adapter::sort_event`] takes it.
+    async fn handle_next_event(&mut self) -> Result<(), adapter::Error> {
+        if let Some(event) = self.deferred.pop_front() {
+            return self.event_handler.handle(event);
+        }
+        let event: EventPacket<H::Buf> = self.read_next_event().await?;
+        if let Some(event) = self.sort_event(event, false)? {
+            self.event_handler.handle(event)?;
+        }
+        Ok(())
+    }
+    /// Read the next event, starting with the deferred ones. Late returns of abandoned commands
+    /// are passed to the event handler and returns of pipelined commands are kept for
+    /// [`Adapter::hci_read_return`].
+    pub async fn hci_read_event(&mut self) -> Result<EventPacket<H::Buf>, adapter::Error> {
+        if let Some(event) = self.deferred.pop_front() {
+            return Ok(event);
+        }
+        loop {
+            let event: EventPacket<H::Buf> = self.read_next_event().await?;
+            if let Some(event) = self.sort_event(event, false)? {
+                return Ok(event);
+            }
+        }
+    }
+    pub fn hci_event_stream<'a>(
         &'a mut self,
-    ) -> impl Stream<Item = Result<EventPacket<Buf>, adapter::Error>> + 'a {
+    ) -> impl Stream<Item = Result<EventPacket<H::Buf>, adapter::Error>> + 'a {
         futures_util::stream::unfold(self, move |s| async move {
-            Some((s.adapter.read_event().await, s))
+            Some((s.hci_read_event().await, s))
         })
     }
     pub async fn set_event_mask(&mut self, mask: EventMask) -> Result<(), adapter::Error> {
@@ -85,8 +627,25 @@ impl<A: adapter::Adapter, H: UnrecognizedEventHandler> Adapter<A, H> {
             .params
             .status
             .error()?;
+        self.controller_state.set_event_mask(mask);
+        Ok(())
+    }
+    /// Set the controller's name. Use [`LocalName::truncated`] for names that may be longer
+    /// than [`LocalName::MAX_LEN`] bytes.
+    pub async fn write_local_name(&mut self, name: LocalName) -> Result<(), adapter::Error> {
+        self.hci_send_command(WriteLocalName { name })
+            .await?
+            .params
+            .status
+            .error()?;
         Ok(())
     }
+    pub async fn read_local_name(&mut self) -> Result<LocalName, adapter::Error> {
+        let r = self.hci_send_command(ReadLocalName).await?.params;
+        r.status.error()?;
+        Ok(r.name)
+    }
+    /// Reset the controller. The [`ControllerState`] is kept, see [`Adapter::restore`].
     pub async fn reset(&mut self) -> Result<(), adapter::Error> {
         self.hci_send_command(Reset).await?.params.status.error()?;
         Ok(())
@@ -157,3 +716,300 @@ impl<R: HCIStreamable> AsMut<Stream<R>> for Adapter<R> {
     }
 }
 */
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::hci::adapter::DummyAdapter;
+    use crate::hci::informational::CoreVersion;
+    use crate::hci::le::big::BIGTerminateSync;
+    use crate::hci::le::scan::SetScanEnable;
+    use crate::hci::ErrorCode;
+    use alloc::rc::Rc;
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+    #[test]
+    fn test_check_version() {
+        let mut adapter = Adapter::new(DummyAdapter);
+        assert!(adapter.check_version::<BIGTerminateSync>().is_ok());
+        adapter.local_version = Some(LocalVersionInformation {
+            status: ErrorCode::Ok,
+            hci_version: CoreVersion::V4_0.into(),
+            hci_subversion: 0,
+            lmp_version: CoreVersion::V4_0.into(),
+            company_identifier: 0,
+            lmp_subversion: 0,
+        });
+        assert!(adapter.check_version::<SetScanEnable>().is_ok());
+        assert_eq!(
+            adapter.check_version::<BIGTerminateSync>(),
+            Err(adapter::Error::UnsupportedVersion {
+                opcode: BIGTerminateSync::opcode(),
+                required: CoreVersion::V5_2,
+                controller: CoreVersion::V4_0,
+            })
+        );
+        adapter.version_policy = VersionPolicy::SendAnyway;
+        assert!(adapter.check_version::<BIGTerminateSync>().is_ok());
+    }
+    pub(crate) type Script = Rc<RefCell<VecDeque<EventPacket<Box<[u8]>>>>>;
+    /// Replies to every read with the next scripted event. Reads wait while the script is empty,
+    /// like a quiet controller. The script is shared so events can be added while the adapter is
+    /// borrowed (poll again to pick them up).
+    pub(crate) struct ScriptedAdapter {
+        pub(crate) events: Script,
+        pub(crate) written: Rc<RefCell<Vec<Opcode>>>,
+        /// Parameters of every written command, in the same order as `written`.
+        pub(crate) parameters: Rc<RefCell<Vec<Box<[u8]>>>>,
+    }
+    impl ScriptedAdapter {
+        pub(crate) fn new(events: VecDeque<EventPacket<Box<[u8]>>>) -> Self {
+            ScriptedAdapter {
+                events: Rc::new(RefCell::new(events)),
+                written: Rc::new(RefCell::new(Vec::new())),
+                parameters: Rc::new(RefCell::new(Vec::new())),
+            }
+        }
+    }
+    impl adapter::Adapter for ScriptedAdapter {
+        fn write_command<'s, 'p: 's>(
+            &'s mut self,
+            packet: crate::hci::command::CommandPacket<&'p [u8]>,
+        ) -> crate::LocalBoxFuture<'s, Result<(), adapter::Error>> {
+            self.written.borrow_mut().push(packet.opcode);
+            self.parameters.borrow_mut().push(packet.parameters.into());
+            Box::pin(async { Ok(()) })
+        }
+
+        fn read_event<'s, 'p: 's, S: Storage<u8> + 'p>(
+            &'s mut self,
+        ) -> crate::LocalBoxFuture<'s, Result<EventPacket<S>, adapter::Error>> {
+            let events = self.events.clone();
+            Box::pin(futures_util::future::poll_fn(move |_| {
+                match events.borrow_mut().pop_front() {
+                    Some(event) => core::task::Poll::Ready(Ok(event.to_new_storage())),
+                    None => core::task::Poll::Pending,
+                }
+            }))
+        }
+    }
+    pub(crate) fn command_complete(opcode: Opcode, status: ErrorCode) -> EventPacket<Box<[u8]>> {
+        use crate::hci::event::{CommandComplete, StatusReturn};
+        CommandComplete {
+            num_command_packets: 1,
+            opcode,
+            params: StatusReturn { status },
+        }
+        .event_pack_packet::<Box<[u8]>>()
+        .unwrap()
+    }
+    #[test]
+    fn test_pipelined_commands() {
+        use crate::hci::event::{CommandComplete, Event, StatusReturn};
+        use futures_util::FutureExt;
+        let complete = |num_command_packets, opcode| {
+            CommandComplete {
+                num_command_packets,
+                opcode,
+                params: StatusReturn {
+                    status: ErrorCode::Ok,
+                },
+            }
+            .event_pack_packet::<Box<[u8]>>()
+            .unwrap()
+        };
+        let mask = SetEventMask(EventMask::DEFAULT);
+        let mut events = VecDeque::new();
+        // The reset frees two command slots, then both returns arrive together (mask first).
+        events.push_back(complete(2, Reset::opcode()));
+        events.push_back(complete(2, SetEventMask::opcode()));
+        let mut adapter = Adapter::new(ScriptedAdapter::new(events));
+        adapter
+            .hci_write_command(&Reset)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(adapter.command_credits(), 0);
+        // Waits for the Reset return to free a slot.
+        adapter
+            .hci_write_command(&mask)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(adapter.command_credits(), 1);
+        let ret = adapter
+            .hci_read_return::<SetEventMask>()
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(ret.opcode, SetEventMask::opcode());
+        assert_eq!(ret.num_command_packets, 2);
+        // Already buffered.
+        assert!(adapter
+            .hci_read_return::<Reset>()
+            .now_or_never()
+            .unwrap()
+            .is_ok());
+        assert_eq!(
+            *adapter.adapter.written.borrow(),
+            [Reset::opcode(), SetEventMask::opcode()]
+        );
+        assert_eq!(
+            adapter.hci_read_return::<Reset>().now_or_never().unwrap(),
+            Err(adapter::Error::BadParameter)
+        );
+    }
+    #[test]
+    fn test_same_opcode_returns() {
+        use core::future::Future;
+        use core::task::{Context, Poll};
+        use futures_util::FutureExt;
+        let mut adapter = Adapter::new(ScriptedAdapter::new(VecDeque::new()));
+        let script = adapter.adapter.events.clone();
+        let written = adapter.adapter.written.clone();
+        adapter
+            .hci_write_command(&Reset)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        let waker = futures_util::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        {
+            let mut reset = Box::pin(adapter.hci_send_command(Reset));
+            // No command slot until the pipelined Reset returns.
+            assert!(reset.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(written.borrow().len(), 1);
+            script.borrow_mut().extend([
+                command_complete(Reset::opcode(), ErrorCode::CommandDisallowed),
+                command_complete(Reset::opcode(), ErrorCode::Ok),
+            ]);
+            // The first return belongs to the pipelined Reset.
+            match reset.as_mut().poll(&mut cx) {
+                Poll::Ready(ret) => assert_eq!(ret.unwrap().params.status, ErrorCode::Ok),
+                Poll::Pending => panic!("return not read"),
+            }
+        }
+        let ret = adapter
+            .hci_read_return::<Reset>()
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(ret.params.status, ErrorCode::CommandDisallowed);
+        assert_eq!(written.borrow().len(), 2);
+    }
+    #[test]
+    fn test_abandoned_command() {
+        use crate::hci::event::{Event, EventCode};
+        use crate::hci::le::encryption::EncryptionKeyRefreshComplete;
+        use futures_util::FutureExt;
+        let mut adapter = Adapter::new(ScriptedAdapter::new(VecDeque::new()));
+        // Written then dropped while waiting for the return.
+        assert!(adapter.hci_send_command(Reset).now_or_never().is_none());
+        // Its return arrives while something else reads events.
+        let refresh = EncryptionKeyRefreshComplete {
+            status: ErrorCode::Ok,
+            connection_handle: ConnectionHandle::new(1),
+        };
+        let mut script = adapter.adapter.events.borrow_mut();
+        script.push_back(command_complete(
+            Reset::opcode(),
+            ErrorCode::CommandDisallowed,
+        ));
+        script.push_back(refresh.event_pack_packet::<Box<[u8]>>().unwrap());
+        drop(script);
+        let event = adapter.hci_read_event().now_or_never().unwrap().unwrap();
+        assert_eq!(event.event_code, EventCode::EncryptionKeyRefreshComplete);
+        // The next command with the same opcode gets its own return.
+        adapter
+            .adapter
+            .events
+            .borrow_mut()
+            .push_back(command_complete(Reset::opcode(), ErrorCode::Ok));
+        let ret = adapter
+            .hci_send_command(Reset)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(ret.params.status, ErrorCode::Ok);
+        assert!(adapter.adapter.events.borrow().is_empty());
+    }
+    #[test]
+    fn test_report_flood() {
+        use crate::hci::adapters::buffer::HCIEventBuffer;
+        use crate::hci::event::{CommandComplete, Event, StatusReturn};
+        use crate::hci::le::encryption::EncryptionKeyRefreshComplete;
+        use crate::le::connection::ConnectionHandle;
+        use core::future::Future;
+        use core::task::{Context, Poll};
+        use futures_util::FutureExt;
+        let refresh = |i| {
+            EncryptionKeyRefreshComplete {
+                status: ErrorCode::Ok,
+                connection_handle: ConnectionHandle::new(i),
+            }
+            .event_pack_packet::<Box<[u8]>>()
+            .unwrap()
+        };
+        let handle_of = |event: &EventPacket<Box<[u8]>>| {
+            EncryptionKeyRefreshComplete::unpack_event_packet(event)
+                .unwrap()
+                .connection_handle
+        };
+        // More events than HCI_EVENT_READ_TRIES before the return.
+        let mut events: VecDeque<_> = (0..100).map(refresh).collect();
+        let complete = CommandComplete {
+            num_command_packets: 1,
+            opcode: Reset::opcode(),
+            params: StatusReturn {
+                status: ErrorCode::Ok,
+            },
+        };
+        events.push_back(complete.event_pack_packet::<Box<[u8]>>().unwrap());
+        let mut adapter = Adapter::new_with_handler(
+            ScriptedAdapter::new(events),
+            HCIEventBuffer::<Box<[u8]>>::new(),
+        );
+        let waker = futures_util::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut yields = 0_u16;
+        {
+            let mut reset = Box::pin(adapter.reset());
+            loop {
+                match reset.as_mut().poll(&mut cx) {
+                    Poll::Ready(r) => break r.unwrap(),
+                    Poll::Pending => yields += 1,
+                }
+            }
+        }
+        assert_eq!(yields, 101 / Fairness::DEFAULT.yield_every);
+        // Past `max_deferred` the oldest events go to the handler, in order.
+        assert_eq!(adapter.deferred_events(), Fairness::DEFAULT.max_deferred);
+        let handled = adapter.event_handler.inner();
+        assert_eq!(handled.len(), 100 - Fairness::DEFAULT.max_deferred);
+        for (i, event) in (0..).zip(handled) {
+            assert_eq!(handle_of(event), ConnectionHandle::new(i));
+        }
+        // Deferred events are read before new ones (the script is empty now).
+        for i in (0..100).skip(handled.len()) {
+            let event = adapter.hci_read_event().now_or_never().unwrap().unwrap();
+            assert_eq!(handle_of(&event), ConnectionHandle::new(i));
+        }
+        assert_eq!(adapter.deferred_events(), 0);
+
+        // A lost return doesn't keep the command reading reports forever.
+        adapter.fairness = Fairness {
+            yield_every: 0,
+            max_events_per_return: 10,
+            ..Fairness::DEFAULT
+        };
+        adapter
+            .adapter
+            .events
+            .borrow_mut()
+            .extend((0..20).map(refresh));
+        assert_eq!(
+            adapter.reset().now_or_never().unwrap(),
+            Err(adapter::Error::StreamError(StreamError::StreamFailed))
+        );
+        assert_eq!(adapter.adapter.events.borrow().len(), 10);
+    }
+}
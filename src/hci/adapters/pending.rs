@@ -0,0 +1,49 @@
+//! Commands that finish with a later event instead of their Command Status (see
+//! [`LongCommand`]). [`Adapter::hci_start_command`] returns a [`PendingCommand`] once the
+//! controller accepts the command, which can then be awaited or cancelled.
+use crate::hci::adapter;
+use crate::hci::adapters::{Adapter, UnrecognizedEventHandler};
+use crate::hci::command::LongCommand;
+use crate::hci::event::Event;
+
+/// A [`LongCommand`] accepted by the controller but not completed yet. Holds the adapter so
+/// nothing else is sent until it completes or is cancelled. Dropping it leaves the command
+/// running and its completion event goes to the event handler.
+pub struct PendingCommand<'a, A: adapter::Adapter, H: UnrecognizedEventHandler, Cmd: LongCommand> {
+    adapter: &'a mut Adapter<A, H>,
+    command: Cmd,
+}
+impl<'a, A: adapter::Adapter, H: UnrecognizedEventHandler, Cmd: LongCommand>
+    PendingCommand<'a, A, H, Cmd>
+{
+    pub(crate) fn new(adapter: &'a mut Adapter<A, H>, command: Cmd) -> Self {
+        PendingCommand { adapter, command }
+    }
+    pub fn command(&self) -> &Cmd {
+        &self.command
+    }
+    /// Read events until the completion event arrives. Other events (including completions of
+    /// other commands) are passed to the event handler. The completion's status isn't checked.
+    pub async fn complete(self) -> Result<Cmd::Completion, adapter::Error> {
+        loop {
+            let event = self.adapter.hci_read_event().await?;
+            if let Ok(completion) = Cmd::Completion::unpack_event_packet(&event) {
+                if self.command.is_completion(&completion) {
+                    return Ok(completion);
+                }
+            }
+            self.adapter.event_handler.handle(event)?;
+        }
+    }
+    /// Send the cancel command. Some commands still send their completion event (with an error
+    /// status) after being cancelled; it goes to the event handler.
+    pub async fn cancel(self) -> Result<(), adapter::Error> {
+        self.adapter
+            .hci_send_command(self.command.cancel_command())
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+}
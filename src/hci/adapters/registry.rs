@@ -0,0 +1,324 @@
+//! Adapter factories keyed by URI scheme. Lets other crates plug in their own transports (vendor
+//! SDK bridges, proxies, etc) and open them with [`AdapterRegistry::connect`] (or [`connect`]
+//! with the `std` feature) without this crate knowing about them.
+//!
+//! URIs are `scheme:path` (or `scheme://path`). The path is passed to the factory as is, for
+//! example `vendor:/dev/ttyACM0` or `proxy://10.0.0.5:4000`.
+use crate::error::IOError;
+use crate::hci::adapter;
+use crate::hci::stream::{HCIReader, HCIWriter, Stream};
+use crate::LocalBoxFuture;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// Byte stream to a HCI controller.
+pub trait Transport: HCIReader + HCIWriter {}
+impl<T: HCIReader + HCIWriter> Transport for T {}
+/// Type erased [`Transport`] returned by factories.
+pub struct DynTransport(pub Pin<Box<dyn Transport>>);
+impl DynTransport {
+    pub fn new<T: Transport + 'static>(transport: T) -> DynTransport {
+        DynTransport(Box::pin(transport))
+    }
+}
+impl core::fmt::Debug for DynTransport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("DynTransport")
+    }
+}
+impl HCIReader for DynTransport {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, adapter::Error>> {
+        self.0.as_mut().poll_read(cx, buf)
+    }
+}
+impl HCIWriter for DynTransport {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, adapter::Error>> {
+        self.0.as_mut().poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), adapter::Error>> {
+        self.0.as_mut().poll_flush(cx)
+    }
+}
+/// HCI stream over a transport opened from a URI. Implements [`adapter::Adapter`] so it can be
+/// wrapped in [`crate::hci::adapters::Adapter`].
+pub type DynStream = Stream<DynTransport, Box<DynTransport>>;
+/// Adapter URI split into scheme and path.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct AdapterUri<'a> {
+    pub scheme: &'a str,
+    pub path: &'a str,
+}
+impl<'a> AdapterUri<'a> {
+    /// Returns `None` if `uri` has no scheme or the scheme isn't `ALPHA *( ALPHA / DIGIT / "+" /
+    /// "-" / "." )` (RFC 3986).
+    pub fn parse(uri: &'a str) -> Option<AdapterUri<'a>> {
+        let colon = uri.find(':')?;
+        let (scheme, path) = (&uri[..colon], &uri[colon + 1..]);
+        let mut chars = scheme.chars();
+        if !chars.next()?.is_ascii_alphabetic()
+            || !chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        {
+            return None;
+        }
+        Some(AdapterUri {
+            scheme,
+            path: path.strip_prefix("//").unwrap_or(path),
+        })
+    }
+}
+impl<'a> core::fmt::Display for AdapterUri<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}", self.scheme, self.path)
+    }
+}
+/// Opens a transport from the path of an [`AdapterUri`]. Implemented for
+/// `Fn(&AdapterUri) -> LocalBoxFuture<Result<DynTransport, adapter::Error>>` closures.
+pub trait AdapterFactory: Send + Sync {
+    fn open(
+        &self,
+        uri: &AdapterUri<'_>,
+    ) -> LocalBoxFuture<'static, Result<DynTransport, adapter::Error>>;
+}
+impl<F> AdapterFactory for F
+where
+    F: Fn(&AdapterUri<'_>) -> LocalBoxFuture<'static, Result<DynTransport, adapter::Error>>
+        + Send
+        + Sync,
+{
+    fn open(
+        &self,
+        uri: &AdapterUri<'_>,
+    ) -> LocalBoxFuture<'static, Result<DynTransport, adapter::Error>> {
+        self(uri)
+    }
+}
+/// Adapter factories by URI scheme. Schemes are case insensitive.
+#[derive(Default)]
+pub struct AdapterRegistry {
+    factories: Vec<(String, Box<dyn AdapterFactory>)>,
+}
+impl AdapterRegistry {
+    pub fn new() -> AdapterRegistry {
+        AdapterRegistry {
+            factories: Vec::new(),
+        }
+    }
+    fn index_of(&self, scheme: &str) -> Option<usize> {
+        self.factories
+            .iter()
+            .position(|(s, _)| s.eq_ignore_ascii_case(scheme))
+    }
+    /// Register `factory` for `scheme`. Returns the factory it replaced (if any).
+    pub fn register<F: AdapterFactory + 'static>(
+        &mut self,
+        scheme: &str,
+        factory: F,
+    ) -> Option<Box<dyn AdapterFactory>> {
+        let factory: Box<dyn AdapterFactory> = Box::new(factory);
+        match self.index_of(scheme) {
+            Some(i) => Some(core::mem::replace(&mut self.factories[i].1, factory)),
+            None => {
+                self.factories.push((scheme.to_ascii_lowercase(), factory));
+                None
+            }
+        }
+    }
+    pub fn unregister(&mut self, scheme: &str) -> Option<Box<dyn AdapterFactory>> {
+        let i = self.index_of(scheme)?;
+        Some(self.factories.remove(i).1)
+    }
+    pub fn is_registered(&self, scheme: &str) -> bool {
+        self.index_of(scheme).is_some()
+    }
+    /// Registered schemes (lowercase) in registration order.
+    pub fn schemes(&self) -> impl Iterator<Item = &str> {
+        self.factories.iter().map(|(s, _)| s.as_str())
+    }
+    /// Open the transport for `uri`. Returns `adapter::Error::BadParameter` if `uri` can't be
+    /// parsed and `IOError::NotFound` if no factory is registered for the scheme.
+    pub fn open(&self, uri: &str) -> LocalBoxFuture<'static, Result<DynTransport, adapter::Error>> {
+        let opened = AdapterUri::parse(uri)
+            .ok_or(adapter::Error::BadParameter)
+            .and_then(|uri| {
+                let i = self
+                    .index_of(uri.scheme)
+                    .ok_or(adapter::Error::IOError(IOError::NotFound))?;
+                Ok(self.factories[i].1.open(&uri))
+            });
+        match opened {
+            Ok(future) => future,
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+    /// Open the transport for `uri` as a HCI stream. See [`AdapterRegistry::open`].
+    pub fn connect(&self, uri: &str) -> LocalBoxFuture<'static, Result<DynStream, adapter::Error>> {
+        let open = self.open(uri);
+        Box::pin(async move { Ok(Stream::new(Box::pin(open.await?))) })
+    }
+}
+#[cfg(feature = "std")]
+static GLOBAL_REGISTRY: std::sync::Mutex<Option<AdapterRegistry>> = std::sync::Mutex::new(None);
+/// Register `factory` for `scheme` in the process wide registry used by [`connect`].
+#[cfg(feature = "std")]
+pub fn register<F: AdapterFactory + 'static>(
+    scheme: &str,
+    factory: F,
+) -> Option<Box<dyn AdapterFactory>> {
+    GLOBAL_REGISTRY
+        .lock()
+        .expect("adapter registry poisoned")
+        .get_or_insert_with(AdapterRegistry::new)
+        .register(scheme, factory)
+}
+#[cfg(feature = "std")]
+pub fn unregister(scheme: &str) -> Option<Box<dyn AdapterFactory>> {
+    GLOBAL_REGISTRY
+        .lock()
+        .expect("adapter registry poisoned")
+        .as_mut()?
+        .unregister(scheme)
+}
+/// Open a HCI stream to the adapter at `uri` with a factory from [`register`].
+#[cfg(feature = "std")]
+pub fn connect(uri: &str) -> LocalBoxFuture<'static, Result<DynStream, adapter::Error>> {
+    match GLOBAL_REGISTRY
+        .lock()
+        .expect("adapter registry poisoned")
+        .as_ref()
+    {
+        Some(registry) => registry.connect(uri),
+        None => Box::pin(async { Err(adapter::Error::IOError(IOError::NotFound)) }),
+    }
+}
+/// Register the transports built into this crate (for the enabled features) under their
+/// schemes, unless a factory is already registered for the scheme:
+///
+/// - `hci:0` (or `hci:hci0`): BlueZ HCI socket (`bluez_socket`).
+/// - `tcp:host:port`: H4 over TCP (`remote`).
+///
+/// Both must be opened on a tokio runtime with IO enabled. The C API calls this before the first
+/// open.
+#[cfg(feature = "std")]
+pub fn register_builtin() {
+    type Factory =
+        fn(&AdapterUri<'_>) -> LocalBoxFuture<'static, Result<DynTransport, adapter::Error>>;
+    let mut registry = GLOBAL_REGISTRY.lock().expect("adapter registry poisoned");
+    let registry = registry.get_or_insert_with(AdapterRegistry::new);
+    let builtin: &[(&str, Factory)] = &[
+        #[cfg(all(unix, feature = "bluez_socket"))]
+        ("hci", open_bluez_socket),
+        #[cfg(feature = "remote")]
+        ("tcp", open_tcp),
+    ];
+    for &(scheme, factory) in builtin {
+        if !registry.is_registered(scheme) {
+            registry.register(scheme, factory);
+        }
+    }
+}
+#[cfg(all(unix, feature = "bluez_socket"))]
+fn open_bluez_socket(
+    uri: &AdapterUri<'_>,
+) -> LocalBoxFuture<'static, Result<DynTransport, adapter::Error>> {
+    use crate::hci::bluez_socket::{AdapterID, AsyncHCISocket, Manager};
+    use core::convert::TryFrom;
+    let id = uri.path.strip_prefix("hci").unwrap_or(uri.path).parse();
+    Box::pin(async move {
+        let id = AdapterID(id.map_err(|_| adapter::Error::BadParameter)?);
+        let socket = Manager::new()?.get_adapter_socket(id)?;
+        let socket =
+            AsyncHCISocket::try_from(socket).map_err(|e| adapter::Error::IOError(e.into()))?;
+        Ok(DynTransport::new(socket))
+    })
+}
+#[cfg(feature = "remote")]
+fn open_tcp(uri: &AdapterUri<'_>) -> LocalBoxFuture<'static, Result<DynTransport, adapter::Error>> {
+    let address = String::from(uri.path);
+    Box::pin(async move {
+        Ok(DynTransport::new(
+            crate::hci::remote::TcpTransport::connect(&address).await?,
+        ))
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+    struct Loopback;
+    impl HCIReader for Loopback {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &mut [u8],
+        ) -> Poll<Result<usize, adapter::Error>> {
+            Poll::Ready(Ok(0))
+        }
+    }
+    impl HCIWriter for Loopback {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<Result<usize, adapter::Error>> {
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), adapter::Error>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+    #[test]
+    fn test_registry() {
+        assert_eq!(
+            AdapterUri::parse("Vendor://dev/0"),
+            Some(AdapterUri {
+                scheme: "Vendor",
+                path: "dev/0"
+            })
+        );
+        assert!(AdapterUri::parse("no scheme").is_none());
+        assert!(AdapterUri::parse("1abc:x").is_none());
+        let mut registry = AdapterRegistry::new();
+        let factory = |uri: &AdapterUri<'_>| -> LocalBoxFuture<'static, _> {
+            let ok = uri.path == "0";
+            Box::pin(async move {
+                if ok {
+                    Ok(DynTransport::new(Loopback))
+                } else {
+                    Err(adapter::Error::BadParameter)
+                }
+            })
+        };
+        assert!(registry.register("vendor", factory).is_none());
+        assert!(registry.is_registered("VENDOR"));
+        assert!(registry.connect("vendor:0").now_or_never().unwrap().is_ok());
+        assert_eq!(
+            registry.connect("vendor:1").now_or_never().unwrap().err(),
+            Some(adapter::Error::BadParameter)
+        );
+        assert_eq!(
+            registry.connect("other:0").now_or_never().unwrap().err(),
+            Some(adapter::Error::IOError(IOError::NotFound))
+        );
+        assert!(registry.unregister("Vendor").is_some());
+        assert_eq!(registry.schemes().count(), 0);
+    }
+}
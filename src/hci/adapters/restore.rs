@@ -0,0 +1,230 @@
+//! Controller state lost on HCI Reset (event masks, LE host features and other setup commands).
+//! The [`Adapter`] remembers what was applied through it so [`Adapter::restore`] can apply it
+//! again after a Reset, in dependency order: the event mask (which enables LE Meta events),
+//! then the LE event mask, the LE host features and last any remembered setup commands (event
+//! filters, vendor commands, etc) in the order they were first applied.
+use crate::hci::adapter;
+use crate::hci::adapters::{Adapter, UnrecognizedEventHandler};
+use crate::hci::baseband::{EventMask, SetEventMask};
+use crate::hci::command::{Command, CommandPacket};
+use crate::hci::event::{EventCode, EventPacket};
+use crate::hci::le::host_feature::SetHostFeature;
+use crate::hci::le::mask::{MetaEventMask, SetMetaEventMask};
+use crate::hci::stream::HCI_EVENT_READ_TRIES;
+use crate::hci::{Opcode, StreamError};
+use crate::PackError;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// When a controller accepts commands again after a Reset.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum ReadyCondition {
+    /// As soon as the Reset completes.
+    Immediate,
+    /// After a fixed time (waited with [`crate::hci::adapters::retry::RetryPolicies::delay`]).
+    /// Some controllers drop commands sent right after the Reset completes.
+    Delay(Duration),
+    /// Once an event with this code arrives (a vendor specific "ready" event, etc). Events
+    /// before it are passed to the event handler.
+    Event(EventCode),
+}
+impl Default for ReadyCondition {
+    fn default() -> Self {
+        ReadyCondition::Immediate
+    }
+}
+/// What has been applied to the controller through an [`Adapter`] since it was created.
+#[derive(Clone, Debug, Default)]
+pub struct ControllerState {
+    event_mask: Option<EventMask>,
+    meta_event_mask: Option<MetaEventMask>,
+    /// `(bit_number, bit_value)` of each LE host feature set, last value only.
+    host_features: Vec<(u8, bool)>,
+    commands: Vec<(Opcode, Box<[u8]>)>,
+}
+impl ControllerState {
+    pub fn event_mask(&self) -> Option<EventMask> {
+        self.event_mask
+    }
+    pub fn meta_event_mask(&self) -> Option<MetaEventMask> {
+        self.meta_event_mask
+    }
+    pub fn host_features(&self) -> &[(u8, bool)] {
+        &self.host_features
+    }
+    /// Opcodes of the remembered setup commands in the order they are restored.
+    pub fn commands(&self) -> impl Iterator<Item = Opcode> + '_ {
+        self.commands.iter().map(|(opcode, _)| *opcode)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.event_mask.is_none()
+            && self.meta_event_mask.is_none()
+            && self.host_features.is_empty()
+            && self.commands.is_empty()
+    }
+    /// Forget everything (nothing is restored after the next Reset).
+    pub fn clear(&mut self) {
+        *self = ControllerState::default();
+    }
+    pub(crate) fn set_event_mask(&mut self, mask: EventMask) {
+        self.event_mask = Some(mask);
+    }
+    pub(crate) fn set_meta_event_mask(&mut self, mask: MetaEventMask) {
+        self.meta_event_mask = Some(mask);
+    }
+    pub(crate) fn set_host_feature(&mut self, feature: SetHostFeature) {
+        match self
+            .host_features
+            .iter_mut()
+            .find(|(bit, _)| *bit == feature.bit_number)
+        {
+            Some((_, value)) => *value = feature.bit_value,
+            None => self
+                .host_features
+                .push((feature.bit_number, feature.bit_value)),
+        }
+    }
+    /// Replay `command` after every Reset, after the masks and host features. Commands are
+    /// replayed in the order they were remembered and each one is expected to return a Command
+    /// Complete.
+    pub fn remember_command<Cmd: Command>(&mut self, command: &Cmd) -> Result<(), PackError> {
+        let packet = command.pack_command_packet::<Box<[u8]>>()?;
+        self.commands.push((packet.opcode, packet.parameters));
+        Ok(())
+    }
+    /// Stop replaying every remembered command with `opcode`.
+    pub fn forget_commands(&mut self, opcode: Opcode) {
+        self.commands.retain(|(o, _)| *o != opcode);
+    }
+}
+impl<A: adapter::Adapter, H: UnrecognizedEventHandler> Adapter<A, H> {
+    pub fn controller_state(&self) -> &ControllerState {
+        &self.controller_state
+    }
+    pub fn controller_state_mut(&mut self) -> &mut ControllerState {
+        &mut self.controller_state
+    }
+    /// Reset the controller and apply the [`ControllerState`] again once it's ready.
+    pub async fn reset_and_restore(&mut self) -> Result<(), adapter::Error> {
+        self.reset().await?;
+        self.restore().await
+    }
+    /// Wait for the [`ReadyCondition`] then apply the [`ControllerState`] again. Call after the
+    /// controller was reset (by [`Adapter::reset`] or from outside, after a Hardware Error
+    /// event, etc).
+    pub async fn restore(&mut self) -> Result<(), adapter::Error> {
+        self.wait_until_ready().await?;
+        let state = self.controller_state.clone();
+        if let Some(mask) = state.event_mask {
+            self.hci_send_command(SetEventMask(mask))
+                .await?
+                .params
+                .status
+                .error()?;
+        }
+        if let Some(mask) = state.meta_event_mask {
+            self.hci_send_command(SetMetaEventMask(mask))
+                .await?
+                .params
+                .status
+                .error()?;
+        }
+        for &(bit_number, bit_value) in &state.host_features {
+            self.hci_send_command(SetHostFeature {
+                bit_number,
+                bit_value,
+            })
+            .await?
+            .params
+            .status
+            .error()?;
+        }
+        for (opcode, parameters) in &state.commands {
+            self.replay_command(CommandPacket {
+                opcode: *opcode,
+                parameters,
+            })
+            .await?;
+        }
+        Ok(())
+    }
+    async fn wait_until_ready(&mut self) -> Result<(), adapter::Error> {
+        match self.ready_condition {
+            ReadyCondition::Immediate => Ok(()),
+            ReadyCondition::Delay(duration) => {
+                // Can't wait without a delay function.
+                let delay = self
+                    .retry_policies
+                    .delay
+                    .ok_or(adapter::Error::BadParameter)?;
+                delay(duration).await;
+                Ok(())
+            }
+            ReadyCondition::Event(code) => {
+                for _try_i in 0..HCI_EVENT_READ_TRIES {
+                    let event = self.hci_read_event().await?;
+                    if event.event_code == code {
+                        return Ok(());
+                    }
+                    self.event_handler.handle(event)?;
+                }
+                Err(adapter::Error::StreamError(StreamError::StreamFailed))
+            }
+        }
+    }
+    /// Send a remembered command and check the status of its Command Complete.
+    async fn replay_command(&mut self, packet: CommandPacket<&[u8]>) -> Result<(), adapter::Error> {
+        self.wait_for_credit(packet.opcode).await?;
+        self.adapter.write_command(packet.as_ref()).await?;
+        self.command_credits -= 1;
+        for _try_i in 0..HCI_EVENT_READ_TRIES {
+            let event: EventPacket<H::Buf> = self.adapter.read_event().await?;
+            let event = match self.sort_event(event, false)? {
+                Some(event) => event,
+                None => continue,
+            };
+            if event.return_opcode() == Some(packet.opcode) {
+                return match event.return_status() {
+                    Some(status) => Ok(status.error()?),
+                    None => Err(adapter::Error::StreamError(StreamError::EventError(
+                        PackError::InvalidFields,
+                    ))),
+                };
+            }
+            self.event_handler.handle(event)?;
+        }
+        Err(adapter::Error::StreamError(StreamError::StreamFailed))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hci::baseband::EventMaskFlags;
+    use crate::hci::le::host_feature::HostFeatureBit;
+    #[test]
+    fn test_controller_state() {
+        let mut state = ControllerState::default();
+        assert!(state.is_empty());
+        let mut mask = EventMask::zeroed();
+        mask.enable_event(EventMaskFlags::LEMetaEvent);
+        state.set_event_mask(mask);
+        state.set_host_feature(SetHostFeature::new(
+            HostFeatureBit::ConnectedIsochronousStream,
+            true,
+        ));
+        state.set_host_feature(SetHostFeature::new(
+            HostFeatureBit::ConnectedIsochronousStream,
+            false,
+        ));
+        assert_eq!(state.host_features(), &[(32, false)]);
+        state
+            .remember_command(&SetHostFeature::new(HostFeatureBit::ChannelSounding, true))
+            .unwrap();
+        assert_eq!(state.commands().count(), 1);
+        state.forget_commands(SetHostFeature::OPCODE.into());
+        assert_eq!(state.commands().count(), 0);
+        state.clear();
+        assert!(state.is_empty());
+    }
+}
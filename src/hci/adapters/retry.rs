@@ -0,0 +1,123 @@
+//! Retrying commands that fail with transient status codes (`ControllerBusy`, `MemoryFull`, etc).
+//! Policies are set per command class (the command's [`OGF`]).
+use crate::hci::{ErrorCode, Opcode, OGF};
+use crate::LocalBoxFuture;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// How many times (and how quickly) to resend a command that failed with a transient status.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct RetryPolicy {
+    /// Number of resends before the error is returned. `0` disables retrying.
+    pub max_retries: u8,
+    /// Backoff before the first resend. Doubled for every resend after that.
+    pub initial_backoff: Duration,
+    /// Upper bound for the backoff.
+    pub max_backoff: Duration,
+}
+impl RetryPolicy {
+    /// Never retry.
+    pub const NONE: RetryPolicy = RetryPolicy {
+        max_retries: 0,
+        initial_backoff: Duration::from_millis(0),
+        max_backoff: Duration::from_millis(0),
+    };
+    /// 3 retries with 10ms, 20ms and 40ms backoffs.
+    pub const DEFAULT: RetryPolicy = RetryPolicy {
+        max_retries: 3,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(200),
+    };
+    /// Backoff before resend number `retry` (starting at 1).
+    pub fn backoff(&self, retry: u8) -> Duration {
+        let shift = u32::from(retry.saturating_sub(1)).min(16);
+        self.initial_backoff
+            .checked_mul(1 << shift)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+    /// Returns if `status` is worth retrying (the controller is temporarily out of resources).
+    pub fn is_transient(status: ErrorCode) -> bool {
+        match status {
+            ErrorCode::ControllerBusy
+            | ErrorCode::MemoryFull
+            | ErrorCode::HostRejectedDueToLimitedResources
+            | ErrorCode::HostBusyPairing => true,
+            _ => false,
+        }
+    }
+}
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::DEFAULT
+    }
+}
+/// Waits for the given backoff. Usually wraps the async runtime's sleep/delay.
+pub type DelayFn = fn(Duration) -> LocalBoxFuture<'static, ()>;
+/// [`RetryPolicy`] for each command class. Commands without an override use `default`.
+#[derive(Clone, Debug)]
+pub struct RetryPolicies {
+    pub default: RetryPolicy,
+    overrides: Vec<(OGF, RetryPolicy)>,
+    /// Used to wait between resends. Without one, commands are resent immediately.
+    pub delay: Option<DelayFn>,
+}
+impl RetryPolicies {
+    /// Retry nothing. Errors are returned as soon as they are received.
+    pub const fn none() -> RetryPolicies {
+        RetryPolicies {
+            default: RetryPolicy::NONE,
+            overrides: Vec::new(),
+            delay: None,
+        }
+    }
+    pub fn new(default: RetryPolicy, delay: Option<DelayFn>) -> RetryPolicies {
+        RetryPolicies {
+            default,
+            overrides: Vec::new(),
+            delay,
+        }
+    }
+    /// Use `policy` for all commands in the `ogf` class.
+    pub fn set(&mut self, ogf: OGF, policy: RetryPolicy) {
+        match self.overrides.iter_mut().find(|(o, _)| *o == ogf) {
+            Some((_, p)) => *p = policy,
+            None => self.overrides.push((ogf, policy)),
+        }
+    }
+    /// Go back to the `default` policy for the `ogf` class.
+    pub fn clear(&mut self, ogf: OGF) {
+        self.overrides.retain(|(o, _)| *o != ogf);
+    }
+    pub fn policy_for(&self, opcode: Opcode) -> RetryPolicy {
+        self.overrides
+            .iter()
+            .find(|(o, _)| *o == opcode.0)
+            .map_or(self.default, |(_, p)| *p)
+    }
+}
+impl Default for RetryPolicies {
+    fn default() -> Self {
+        RetryPolicies::none()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::DEFAULT;
+        assert_eq!(policy.backoff(1), Duration::from_millis(10));
+        assert_eq!(policy.backoff(3), Duration::from_millis(40));
+        assert_eq!(policy.backoff(10), Duration::from_millis(200));
+        assert_eq!(policy.backoff(u8::MAX), Duration::from_millis(200));
+        let mut policies = RetryPolicies::none();
+        policies.set(OGF::LEController, policy);
+        let le_opcode = Opcode(OGF::LEController, crate::hci::OCF::new(0x000B));
+        let reset_opcode = Opcode(OGF::HCIControlBaseband, crate::hci::OCF::new(0x0003));
+        assert_eq!(policies.policy_for(le_opcode), policy);
+        assert_eq!(policies.policy_for(reset_opcode), RetryPolicy::NONE);
+        policies.clear(OGF::LEController);
+        assert_eq!(policies.policy_for(le_opcode), RetryPolicy::NONE);
+    }
+}
@@ -0,0 +1,598 @@
+//! Splitting one [`LEAdapter`] into an [`AdvertiserHandle`] and an [`ObserverHandle`] so
+//! advertising and scanning can run in separate tasks. Both handles share the adapter through a
+//! [`CommandQueue`] which lets one command (or event read) use the adapter at a time.
+//!
+//! LE Meta events read while a command is waiting for its Command Complete/Status are queued
+//! for the [`ObserverHandle`] instead of being dropped. The observer drops its pending read
+//! whenever a command is waiting, so the transport's `read_event` must not lose data when
+//! dropped before it's ready (true for [`Stream`](crate::hci::stream::Stream)).
+use crate::hci::adapter;
+use crate::hci::adapters::le::LEAdapter;
+use crate::hci::adapters::{Adapter, UnrecognizedEventHandler};
+use crate::hci::anomaly::{self, Anomaly, AnomalyHook};
+use crate::hci::baseband::{EventMask, EventMaskFlags};
+use crate::hci::event::{EventCode, EventPacket};
+use crate::hci::le::mask::MetaEventMask;
+use crate::hci::le::report::AdvertisingReport;
+use crate::hci::le::whitelist::WhitelistEntry;
+use crate::hci::le::{MetaEvent, MetaEventCode, RawMetaEvent};
+use crate::hci::{Opcode, StreamError};
+use crate::le::advertisement::StaticAdvBuffer;
+use crate::le::advertiser::{Advertiser, AdvertisingParameters};
+use crate::le::report::ReportInfo;
+use crate::le::scan::{Observer, ScanParameters};
+use crate::LocalBoxFuture;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell, RefMut};
+use core::convert::TryFrom;
+use core::task::{Context, Poll, Waker};
+use futures_util::future::Either;
+use futures_util::stream::LocalBoxStream;
+use futures_util::StreamExt;
+
+/// FIFO lock serializing access to a shared adapter. Single threaded like the rest of the
+/// adapter futures.
+pub struct CommandQueue<T> {
+    value: RefCell<T>,
+    locked: Cell<bool>,
+    next_ticket: Cell<u64>,
+    /// Tasks waiting for the lock, oldest first. Only the head is woken when it's released.
+    waiters: RefCell<VecDeque<(u64, Waker)>>,
+    /// Woken when a task starts waiting (see [`CommandQueue::contended`]).
+    holder: RefCell<Option<Waker>>,
+}
+impl<T> CommandQueue<T> {
+    pub fn new(value: T) -> Self {
+        CommandQueue {
+            value: RefCell::new(value),
+            locked: Cell::new(false),
+            next_ticket: Cell::new(0),
+            waiters: RefCell::new(VecDeque::new()),
+            holder: RefCell::new(None),
+        }
+    }
+    /// Wait for exclusive access. Access is released when the [`QueueGuard`] is dropped.
+    pub async fn lock(&self) -> QueueGuard<'_, T> {
+        Lock {
+            queue: self,
+            ticket: None,
+        }
+        .await;
+        QueueGuard {
+            queue: self,
+            value: self.value.borrow_mut(),
+        }
+    }
+    /// Finishes once another task is waiting for the lock, so a holder that is only waiting
+    /// (for events) can give it up.
+    pub async fn contended(&self) {
+        futures_util::future::poll_fn(|cx| {
+            if self.waiters.borrow().is_empty() {
+                *self.holder.borrow_mut() = Some(cx.waker().clone());
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+        .await
+    }
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+    fn wake_head(&self) {
+        if let Some((_, waker)) = self.waiters.borrow().front() {
+            waker.wake_by_ref();
+        }
+    }
+}
+/// Future of [`CommandQueue::lock`]. Holds a place in the queue once it had to wait.
+struct Lock<'a, T> {
+    queue: &'a CommandQueue<T>,
+    ticket: Option<u64>,
+}
+impl<'a, T> core::future::Future for Lock<'a, T> {
+    type Output = ();
+
+    fn poll(mut self: core::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let queue = self.queue;
+        let mut waiters = queue.waiters.borrow_mut();
+        match self.ticket {
+            None if !queue.locked.get() && waiters.is_empty() => {
+                queue.locked.set(true);
+                Poll::Ready(())
+            }
+            None => {
+                let ticket = queue.next_ticket.get();
+                queue.next_ticket.set(ticket.wrapping_add(1));
+                waiters.push_back((ticket, cx.waker().clone()));
+                self.ticket = Some(ticket);
+                drop(waiters);
+                if let Some(holder) = queue.holder.borrow_mut().take() {
+                    holder.wake();
+                }
+                Poll::Pending
+            }
+            Some(ticket) => {
+                if !queue.locked.get() && waiters.front().map(|(t, _)| *t) == Some(ticket) {
+                    waiters.pop_front();
+                    queue.locked.set(true);
+                    self.ticket = None;
+                    return Poll::Ready(());
+                }
+                if let Some((_, waker)) = waiters.iter_mut().find(|(t, _)| *t == ticket) {
+                    waker.clone_from(cx.waker());
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+impl<'a, T> Drop for Lock<'a, T> {
+    fn drop(&mut self) {
+        let ticket = match self.ticket {
+            Some(ticket) => ticket,
+            None => return,
+        };
+        let mut waiters = self.queue.waiters.borrow_mut();
+        let was_head = waiters.front().map(|(t, _)| *t) == Some(ticket);
+        waiters.retain(|(t, _)| *t != ticket);
+        drop(waiters);
+        // The head may have been woken for a lock it will never take. Pass it on.
+        if was_head && !self.queue.locked.get() {
+            self.queue.wake_head();
+        }
+    }
+}
+pub struct QueueGuard<'a, T> {
+    queue: &'a CommandQueue<T>,
+    value: RefMut<'a, T>,
+}
+impl<'a, T> core::ops::Deref for QueueGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+impl<'a, T> core::ops::DerefMut for QueueGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+impl<'a, T> Drop for QueueGuard<'a, T> {
+    fn drop(&mut self) {
+        self.queue.locked.set(false);
+        self.queue.holder.borrow_mut().take();
+        self.queue.wake_head();
+    }
+}
+/// Event handler used by split adapters. Queues LE Meta events for the [`ObserverHandle`] and
+/// passes everything else to the original handler.
+pub struct SplitEventHandler<H: UnrecognizedEventHandler> {
+    pub inner: H,
+    meta_events: VecDeque<EventPacket<H::Buf>>,
+    /// Maximum number of queued LE Meta events. The oldest events are dropped first.
+    pub capacity: usize,
+    /// Called with [`Anomaly::DroppedReport`] for every dropped LE Meta event.
+    pub on_anomaly: Option<AnomalyHook>,
+}
+impl<H: UnrecognizedEventHandler> SplitEventHandler<H> {
+    pub const DEFAULT_CAPACITY: usize = 64;
+    pub fn new(inner: H) -> Self {
+        SplitEventHandler {
+            inner,
+            meta_events: VecDeque::new(),
+            capacity: Self::DEFAULT_CAPACITY,
+            on_anomaly: None,
+        }
+    }
+    pub fn pop_meta_event(&mut self) -> Option<EventPacket<H::Buf>> {
+        self.meta_events.pop_front()
+    }
+}
+impl<H: UnrecognizedEventHandler> UnrecognizedEventHandler for SplitEventHandler<H> {
+    type Buf = H::Buf;
+
+    fn handle(&mut self, event: EventPacket<Self::Buf>) -> Result<(), adapter::Error> {
+        if event.event_code != EventCode::LEMeta {
+            return self.inner.handle(event);
+        }
+        if self.capacity == 0 {
+            anomaly::report(self.on_anomaly, Anomaly::DroppedReport);
+            return Ok(());
+        }
+        while self.meta_events.len() >= self.capacity {
+            self.meta_events.pop_front();
+            anomaly::report(self.on_anomaly, Anomaly::DroppedReport);
+        }
+        self.meta_events.push_back(event);
+        Ok(())
+    }
+    fn handle_late_return(
+        &mut self,
+        opcode: Opcode,
+        event: EventPacket<Self::Buf>,
+    ) -> Result<(), adapter::Error> {
+        self.inner.handle_late_return(opcode, event)
+    }
+}
+type Shared<A, H> = Rc<CommandQueue<LEAdapter<A, SplitEventHandler<H>>>>;
+impl<A: adapter::Adapter, H: UnrecognizedEventHandler> LEAdapter<A, H> {
+    /// Split the adapter into independently usable advertiser and observer handles.
+    pub fn split(self) -> (AdvertiserHandle<A, H>, ObserverHandle<A, H>) {
+        let Adapter {
+            adapter,
+            event_handler,
+            version_policy,
+            retry_policies,
+            tracer,
+            ready_condition,
+            fairness,
+            on_anomaly,
+            controller_state,
+            local_version,
+            outstanding,
+            command_credits,
+            returns,
+            deferred,
+            events_since_yield,
+        } = self.adapter;
+        let shared = Rc::new(CommandQueue::new(LEAdapter::new(Adapter {
+            adapter,
+            event_handler: SplitEventHandler::new(event_handler),
+            version_policy,
+            retry_policies,
+            tracer,
+            ready_condition,
+            fairness,
+            on_anomaly,
+            controller_state,
+            local_version,
+            outstanding,
+            command_credits,
+            returns,
+            deferred,
+            events_since_yield,
+        })));
+        (
+            AdvertiserHandle {
+                shared: shared.clone(),
+            },
+            ObserverHandle { shared },
+        )
+    }
+}
+/// Reunite split handles into the original adapter. Returns the handles back if they weren't
+/// split from the same adapter. Any queued LE Meta events are dropped.
+pub fn reunite<A: adapter::Adapter, H: UnrecognizedEventHandler>(
+    advertiser: AdvertiserHandle<A, H>,
+    observer: ObserverHandle<A, H>,
+) -> Result<LEAdapter<A, H>, (AdvertiserHandle<A, H>, ObserverHandle<A, H>)> {
+    if !Rc::ptr_eq(&advertiser.shared, &observer.shared) {
+        return Err((advertiser, observer));
+    }
+    drop(observer);
+    let shared = match Rc::try_unwrap(advertiser.shared) {
+        Ok(shared) => shared,
+        Err(_) => unreachable!("only two handles are created by `split`"),
+    };
+    let Adapter {
+        adapter,
+        event_handler,
+        version_policy,
+        retry_policies,
+        tracer,
+        ready_condition,
+        fairness,
+        on_anomaly,
+        controller_state,
+        local_version,
+        outstanding,
+        command_credits,
+        returns,
+        deferred,
+        events_since_yield,
+    } = shared.into_inner().adapter;
+    Ok(LEAdapter::new(Adapter {
+        adapter,
+        event_handler: event_handler.inner,
+        version_policy,
+        retry_policies,
+        tracer,
+        ready_condition,
+        fairness,
+        on_anomaly,
+        controller_state,
+        local_version,
+        outstanding,
+        command_credits,
+        returns,
+        deferred,
+        events_since_yield,
+    }))
+}
+/// Advertising half of a split [`LEAdapter`].
+pub struct AdvertiserHandle<A: adapter::Adapter, H: UnrecognizedEventHandler> {
+    shared: Shared<A, H>,
+}
+impl<A: adapter::Adapter, H: UnrecognizedEventHandler> Advertiser for AdvertiserHandle<A, H> {
+    fn set_advertising_enable<'a>(
+        &'a mut self,
+        is_enabled: bool,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            let mut adapter = self.shared.lock().await;
+            adapter.set_advertising_enable(is_enabled).await
+        })
+    }
+
+    fn set_advertising_parameters<'a>(
+        &'a mut self,
+        advertising_parameters: AdvertisingParameters,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            let mut adapter = self.shared.lock().await;
+            adapter
+                .set_advertising_parameters(advertising_parameters)
+                .await
+        })
+    }
+
+    fn set_advertising_data<'d, 'a: 'd>(
+        &'a mut self,
+        data: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            let mut adapter = self.shared.lock().await;
+            adapter.set_advertising_data(data).await
+        })
+    }
+
+    fn set_scan_response_data<'d, 'a: 'd>(
+        &'a mut self,
+        data: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            let mut adapter = self.shared.lock().await;
+            adapter.set_scan_response_data(data).await
+        })
+    }
+
+    fn set_whitelist<'d, 'a: 'd>(
+        &'a mut self,
+        entries: &'d [WhitelistEntry],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            let mut adapter = self.shared.lock().await;
+            adapter.set_whitelist(entries).await
+        })
+    }
+}
+/// Scanning half of a split [`LEAdapter`]. The advertisement stream only holds the adapter
+/// while reading each event so advertiser commands can run in between.
+pub struct ObserverHandle<A: adapter::Adapter, H: UnrecognizedEventHandler> {
+    shared: Shared<A, H>,
+}
+impl<A: adapter::Adapter, H: UnrecognizedEventHandler> ObserverHandle<A, H> {
+    /// Next LE Meta event, either one queued while a command was running or one read from the
+    /// adapter. Other events are passed to the original event handler. A pending read is
+    /// dropped as soon as a command waits for the adapter and started again once it's done.
+    async fn next_meta_event(&self) -> Result<EventPacket<H::Buf>, adapter::Error> {
+        loop {
+            let mut adapter = self.shared.lock().await;
+            let handler = &mut adapter.adapter.event_handler;
+            if let Some(event) = handler.pop_meta_event() {
+                return Ok(event);
+            }
+            let event = {
+                let read = adapter.adapter.hci_read_event();
+                let contended = self.shared.contended();
+                futures_util::pin_mut!(read, contended);
+                match futures_util::future::select(read, contended).await {
+                    Either::Left((event, _)) => event?,
+                    Either::Right(_) => continue,
+                }
+            };
+            if event.event_code == EventCode::LEMeta {
+                return Ok(event);
+            }
+            adapter.adapter.event_handler.inner.handle(event)?;
+        }
+    }
+    fn report_stream(
+        &mut self,
+    ) -> LocalBoxStream<'_, Result<ReportInfo<StaticAdvBuffer>, adapter::Error>> {
+        let reports = futures_util::stream::unfold(self, |s| async move {
+            let reports = s.next_meta_event().await.and_then(|event| {
+                let meta = RawMetaEvent::try_from(event.as_ref())
+                    .map_err(|e| adapter::Error::StreamError(StreamError::EventError(e)))?;
+                if meta.code != MetaEventCode::AdvertisingReport {
+                    return Ok(None);
+                }
+                AdvertisingReport::<Box<[ReportInfo<StaticAdvBuffer>]>>::meta_unpack_packet(meta)
+                    .map(Some)
+                    .map_err(|e| adapter::Error::StreamError(StreamError::EventError(e)))
+            });
+            Some((reports, s))
+        });
+        Box::pin(reports.flat_map(|r| {
+            let items: Vec<_> = match r {
+                Ok(Some(report)) => report.into_iter().map(Ok).collect(),
+                Ok(None) => Vec::new(),
+                Err(e) => vec![Err(e)],
+            };
+            futures_util::stream::iter(items)
+        }))
+    }
+}
+impl<A: adapter::Adapter, H: UnrecognizedEventHandler> Observer for ObserverHandle<A, H> {
+    fn set_scan_parameters<'a>(
+        &'a mut self,
+        scan_parameters: ScanParameters,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            let mut adapter = self.shared.lock().await;
+            adapter.set_scan_parameters(scan_parameters).await
+        })
+    }
+
+    fn set_scan_enable<'a>(
+        &'a mut self,
+        is_enabled: bool,
+        filter_duplicates: bool,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            let mut adapter = self.shared.lock().await;
+            adapter.set_scan_enable(is_enabled, filter_duplicates).await
+        })
+    }
+
+    fn advertisement_stream<'a>(
+        &'a mut self,
+    ) -> LocalBoxFuture<
+        'a,
+        Result<
+            LocalBoxStream<'a, Result<ReportInfo<StaticAdvBuffer>, adapter::Error>>,
+            adapter::Error,
+        >,
+    > {
+        Box::pin(async move {
+            {
+                let mut adapter = self.shared.lock().await;
+                let mut event_mask = EventMask::zeroed();
+                event_mask.enable_event(EventMaskFlags::LEMetaEvent);
+                adapter.adapter.set_event_mask(event_mask).await?;
+                let mut meta_mask = MetaEventMask::zeroed();
+                meta_mask.enable_event(MetaEventCode::AdvertisingReport);
+                adapter.set_meta_event_mask(meta_mask).await?;
+            }
+            Ok(self.report_stream())
+        })
+    }
+
+    fn advertisement_stream_without_mask<'a>(
+        &'a mut self,
+    ) -> LocalBoxFuture<
+        'a,
+        Result<
+            LocalBoxStream<'a, Result<ReportInfo<StaticAdvBuffer>, adapter::Error>>,
+            adapter::Error,
+        >,
+    > {
+        Box::pin(async move { Ok(self.report_stream()) })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes::Storage;
+    use crate::hci::adapters::tests::{command_complete, ScriptedAdapter};
+    use crate::hci::command::Command;
+    use crate::hci::event::Event;
+    use crate::hci::le::advertise::SetAdvertisingEnable;
+    use crate::hci::ErrorCode;
+    use crate::le::advertisement::RawAdvertisement;
+    use crate::le::report::{AddressType, EventType, ReportOrigin};
+    use crate::{BTAddress, RSSI};
+    use core::future::Future;
+    use core::task::Context;
+    use futures_util::FutureExt;
+    #[test]
+    fn test_command_queue() {
+        let queue = CommandQueue::new(0_u32);
+        let mut first = queue.lock().now_or_never().expect("queue is free");
+        *first += 1;
+        let mut second = Box::pin(queue.lock());
+        let mut third = Box::pin(queue.lock());
+        let waker = futures_util::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert!(third.as_mut().poll(&mut cx).is_pending());
+        drop(first);
+        // First come, first served even if the third is polled first.
+        assert!(third.as_mut().poll(&mut cx).is_pending());
+        let mut second = second.now_or_never().expect("first guard dropped");
+        *second += 1;
+        drop(second);
+        assert_eq!(*third.now_or_never().expect("second guard dropped"), 2);
+        // A waiter that gives up doesn't hold up the ones behind it.
+        let first = queue.lock().now_or_never().unwrap();
+        let mut second = Box::pin(queue.lock());
+        let mut third = Box::pin(queue.lock());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert!(third.as_mut().poll(&mut cx).is_pending());
+        drop(first);
+        drop(second);
+        assert!(third.now_or_never().is_some());
+    }
+    fn report() -> EventPacket<Box<[u8]>> {
+        AdvertisingReport::<Vec<ReportInfo>>::new(alloc::vec![ReportInfo {
+            event_type: EventType::AdvInd,
+            address_type: AddressType::RandomDevice,
+            address: BTAddress([1, 2, 3, 4, 5, 6]),
+            data: RawAdvertisement(StaticAdvBuffer::from_slice(&[0x02, 0x01, 0x06])),
+            rssi: Some(RSSI::new(-40)),
+            origin: ReportOrigin::LEGACY,
+        }])
+        .event_pack_packet()
+        .unwrap()
+    }
+    #[test]
+    fn test_command_while_observing() {
+        let adapter = Adapter::new(ScriptedAdapter::new(VecDeque::new()));
+        let script = adapter.adapter.events.clone();
+        let (mut advertiser, mut observer) = adapter.le().split();
+        let waker = futures_util::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        {
+            let mut reports = observer
+                .advertisement_stream_without_mask()
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+            // Nothing to read so the observer waits on the adapter.
+            assert!(reports.poll_next_unpin(&mut cx).is_pending());
+            let mut enable = advertiser.set_advertising_enable(true);
+            assert!(enable.as_mut().poll(&mut cx).is_pending());
+            // The observer gives the adapter up for the command.
+            assert!(reports.poll_next_unpin(&mut cx).is_pending());
+            script.borrow_mut().extend([
+                report(),
+                command_complete(SetAdvertisingEnable::opcode(), ErrorCode::Ok),
+            ]);
+            match enable.as_mut().poll(&mut cx) {
+                Poll::Ready(r) => r.unwrap(),
+                Poll::Pending => panic!("command starved by the observer"),
+            }
+            // The report read while waiting for the return isn't lost.
+            let report = reports.next().now_or_never().unwrap().unwrap().unwrap();
+            assert_eq!(report.address, BTAddress([1, 2, 3, 4, 5, 6]));
+        }
+        let adapter = reunite(advertiser, observer).ok().unwrap();
+        assert_eq!(
+            *adapter.adapter.adapter.written.borrow(),
+            [SetAdvertisingEnable::opcode()]
+        );
+    }
+    #[test]
+    fn test_split_reunite() {
+        let mut adapter = Adapter::new(ScriptedAdapter::new(VecDeque::new())).le();
+        adapter.adapter.fairness.max_deferred = 3;
+        let (advertiser, observer) = adapter.split();
+        let (other_advertiser, other_observer) =
+            Adapter::new(ScriptedAdapter::new(VecDeque::new()))
+                .le()
+                .split();
+        // Handles of different adapters don't go together.
+        let (advertiser, other_observer) = match reunite(advertiser, other_observer) {
+            Ok(_) => panic!("reunited handles of different adapters"),
+            Err(handles) => handles,
+        };
+        assert!(reunite(other_advertiser, other_observer).is_ok());
+        let adapter = reunite(advertiser, observer).ok().unwrap();
+        assert_eq!(adapter.adapter.fairness.max_deferred, 3);
+    }
+}
@@ -0,0 +1,120 @@
+//! Loopback and Device Under Test helpers for controller/transport bring-up (see
+//! [`crate::hci::testing`]). [`Adapter::loopback_echo_test`] puts the controller in local
+//! loopback and checks that commands of every length come back unchanged.
+use crate::hci::adapter;
+use crate::hci::adapters::{Adapter, UnrecognizedEventHandler};
+use crate::hci::command::CommandPacket;
+use crate::hci::event::{Event, EventCode, EventPacket};
+use crate::hci::stream::HCI_EVENT_READ_TRIES;
+use crate::hci::testing::{
+    echo_pattern, EnableDeviceUnderTestMode, LoopbackCommand, LoopbackMode, ReadLoopbackMode,
+    WriteLoopbackMode,
+};
+use crate::hci::{Opcode, StreamError};
+
+/// Result of [`Adapter::loopback_echo_test`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct EchoTestReport {
+    pub sent: u16,
+    /// Echoes that didn't match the command sent.
+    pub mismatched: u16,
+    /// Parameter length of the first mismatched echo.
+    pub first_mismatch: Option<u8>,
+}
+impl EchoTestReport {
+    pub fn passed(&self) -> bool {
+        self.mismatched == 0
+    }
+}
+impl<A: adapter::Adapter, H: UnrecognizedEventHandler> Adapter<A, H> {
+    pub async fn read_loopback_mode(&mut self) -> Result<LoopbackMode, adapter::Error> {
+        let r = self.hci_send_command(ReadLoopbackMode).await?.params;
+        r.status.error()?;
+        Ok(r.mode)
+    }
+    pub async fn write_loopback_mode(&mut self, mode: LoopbackMode) -> Result<(), adapter::Error> {
+        self.hci_send_command(WriteLoopbackMode { mode })
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Hand the controller over to a tester. Only [`Adapter::reset`] gets it back.
+    pub async fn enable_device_under_test_mode(&mut self) -> Result<(), adapter::Error> {
+        self.hci_send_command(EnableDeviceUnderTestMode)
+            .await?
+            .params
+            .status
+            .error()?;
+        Ok(())
+    }
+    /// Write `packet` and wait for its [`LoopbackCommand`]. The controller must already be in
+    /// [`LoopbackMode::LocalLoopback`]. Returns whether the echo matches `packet`. Other events
+    /// are passed to the event handler.
+    pub async fn loopback_echo(
+        &mut self,
+        packet: CommandPacket<&[u8]>,
+    ) -> Result<bool, adapter::Error> {
+        // Looped back commands don't use up a command slot (no Command Complete frees it).
+        self.adapter.write_command(packet.as_ref()).await?;
+        for _try_i in 0..HCI_EVENT_READ_TRIES {
+            let event: EventPacket<H::Buf> = self.adapter.read_event().await?;
+            let event = match self.sort_event(event, false)? {
+                Some(event) => event,
+                None => continue,
+            };
+            if event.event_code == EventCode::LoopbackCommand {
+                let echo = LoopbackCommand::<H::Buf>::unpack_event_packet(&event)
+                    .map_err(StreamError::EventError)?;
+                return Ok(echo.matches(&packet));
+            }
+            self.event_handler.handle(event)?;
+        }
+        Err(adapter::Error::StreamError(StreamError::StreamFailed))
+    }
+    /// Enter local loopback, echo an `opcode` command with every parameter length from 0 to
+    /// `max_len` (filled with [`echo_pattern`]) and leave loopback again. Pick an `opcode` the
+    /// controller doesn't act on (a vendor specific one) in case it isn't looped back. Errors with
+    /// `BadParameter` if `max_len` is over [`LoopbackCommand::MAX_PARAMETERS_LEN`].
+    pub async fn loopback_echo_test(
+        &mut self,
+        opcode: Opcode,
+        max_len: u8,
+    ) -> Result<EchoTestReport, adapter::Error> {
+        if usize::from(max_len) > LoopbackCommand::<&[u8]>::MAX_PARAMETERS_LEN {
+            return Err(adapter::Error::BadParameter);
+        }
+        self.write_loopback_mode(LoopbackMode::LocalLoopback)
+            .await?;
+        let mut report = EchoTestReport::default();
+        let echoed = self.echo_lengths(opcode, max_len, &mut report).await;
+        // Leave loopback even if the transport failed part way.
+        let left = self.write_loopback_mode(LoopbackMode::NoLoopback).await;
+        echoed?;
+        left?;
+        Ok(report)
+    }
+    async fn echo_lengths(
+        &mut self,
+        opcode: Opcode,
+        max_len: u8,
+        report: &mut EchoTestReport,
+    ) -> Result<(), adapter::Error> {
+        let mut buf = [0_u8; LoopbackCommand::<&[u8]>::MAX_PARAMETERS_LEN];
+        for len in 0..=max_len {
+            let parameters = &mut buf[..usize::from(len)];
+            echo_pattern(len, parameters);
+            let packet = CommandPacket {
+                opcode,
+                parameters: &*parameters,
+            };
+            report.sent += 1;
+            if !self.loopback_echo(packet).await? {
+                report.mismatched += 1;
+                report.first_mismatch.get_or_insert(len);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+//! Per-operation latency tracing. Every traced operation (a command round trip, an outbound data
+//! unit, a notification) gets a [`TraceId`] when it starts. Finishing it records the latency in a
+//! [`LatencyHistogram`] for its [`TraceKind`] and passes a [`TraceRecord`] to the completion hook.
+use crate::clock::Clock;
+use crate::hci::Opcode;
+use alloc::collections::VecDeque;
+use core::convert::TryFrom;
+use core::time::Duration;
+
+/// Monotonic time since any fixed point. Usually wraps `std::time::Instant` or a hardware timer.
+/// Any [`Clock`] can be used instead.
+pub type ClockFn = fn() -> Duration;
+/// Called with every finished operation (export to metrics, logs, etc).
+pub type TraceHook = fn(&TraceRecord);
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct TraceId(pub u32);
+impl core::fmt::Display for TraceId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "trace-{:08x}", self.0)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum TraceKind {
+    /// Command sent to its Command Complete/Status.
+    CommandRoundTrip = 0,
+    /// Outbound data unit (ACL/ISO) queued to its Number Of Completed Packets.
+    DataTransmit = 1,
+    /// Inbound notification received to delivered to the application.
+    NotificationDelivery = 2,
+}
+impl TraceKind {
+    pub const COUNT: usize = 3;
+    pub const ALL: [TraceKind; Self::COUNT] = [
+        TraceKind::CommandRoundTrip,
+        TraceKind::DataTransmit,
+        TraceKind::NotificationDelivery,
+    ];
+}
+/// A finished operation.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct TraceRecord {
+    pub id: TraceId,
+    pub kind: TraceKind,
+    /// Opcode of traced commands.
+    pub opcode: Option<Opcode>,
+    pub latency: Duration,
+}
+/// Histogram with power of 2 microsecond buckets. Bucket `i` counts latencies below `2^i` us
+/// (and at least `2^(i-1)` us).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct LatencyHistogram {
+    buckets: [u32; LatencyHistogram::BUCKETS],
+    count: u32,
+    total_micros: u64,
+    max_micros: u64,
+}
+impl LatencyHistogram {
+    /// Last bucket catches everything above ~1 minute.
+    pub const BUCKETS: usize = 27;
+    pub fn record(&mut self, latency: Duration) {
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        let bucket = (64 - micros.leading_zeros()) as usize;
+        let bucket = bucket.min(Self::BUCKETS - 1);
+        self.buckets[bucket] = self.buckets[bucket].saturating_add(1);
+        self.count = self.count.saturating_add(1);
+        self.total_micros = self.total_micros.saturating_add(micros);
+        self.max_micros = self.max_micros.max(micros);
+    }
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+    pub fn buckets(&self) -> &[u32; Self::BUCKETS] {
+        &self.buckets
+    }
+    /// Upper bound (exclusive) of bucket `index`.
+    pub fn bucket_bound(index: usize) -> Duration {
+        Duration::from_micros(1_u64 << index.min(63))
+    }
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(Duration::from_micros(
+                self.total_micros / u64::from(self.count),
+            ))
+        }
+    }
+    pub fn max(&self) -> Option<Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(Duration::from_micros(self.max_micros))
+        }
+    }
+    /// Upper bound of the bucket containing the `percent` percentile (`50` for the median).
+    pub fn percentile(&self, percent: u8) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let target = (u64::from(self.count) * u64::from(percent.min(100)) + 99) / 100;
+        let mut seen = 0_u64;
+        for (i, &n) in self.buckets.iter().enumerate() {
+            seen += u64::from(n);
+            if seen >= target.max(1) {
+                return Some(Self::bucket_bound(i));
+            }
+        }
+        self.max()
+    }
+    pub fn clear(&mut self) {
+        *self = LatencyHistogram::default();
+    }
+}
+#[derive(Copy, Clone, Debug)]
+struct PendingTrace {
+    id: TraceId,
+    kind: TraceKind,
+    opcode: Option<Opcode>,
+    started: Duration,
+}
+/// Hands out [`TraceId`]s and collects the latency of each finished operation.
+#[derive(Clone, Debug)]
+pub struct Tracer<C: Clock = ClockFn> {
+    clock: C,
+    next_id: u32,
+    pending: VecDeque<PendingTrace>,
+    histograms: [LatencyHistogram; TraceKind::COUNT],
+    /// Called for every finished operation.
+    pub on_finish: Option<TraceHook>,
+}
+impl<C: Clock> Tracer<C> {
+    /// Operations never finished (dropped futures, lost events) are forgotten after this many
+    /// newer ones are started.
+    pub const MAX_PENDING: usize = 64;
+    pub fn new(clock: C) -> Tracer<C> {
+        Tracer {
+            clock,
+            next_id: 0,
+            pending: VecDeque::new(),
+            histograms: [LatencyHistogram::default(); TraceKind::COUNT],
+            on_finish: None,
+        }
+    }
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+    /// Start tracing an operation.
+    pub fn start(&mut self, kind: TraceKind) -> TraceId {
+        self.start_inner(kind, None)
+    }
+    /// Start tracing a command round trip.
+    pub fn start_command(&mut self, opcode: Opcode) -> TraceId {
+        self.start_inner(TraceKind::CommandRoundTrip, Some(opcode))
+    }
+    fn start_inner(&mut self, kind: TraceKind, opcode: Option<Opcode>) -> TraceId {
+        let id = TraceId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        if self.pending.len() >= Self::MAX_PENDING {
+            self.pending.pop_front();
+        }
+        let started = self.now();
+        self.pending.push_back(PendingTrace {
+            id,
+            kind,
+            opcode,
+            started,
+        });
+        id
+    }
+    /// Finish `id`. Returns `None` if it isn't pending (already finished or forgotten).
+    pub fn finish(&mut self, id: TraceId) -> Option<TraceRecord> {
+        let index = self.pending.iter().position(|p| p.id == id)?;
+        self.finish_index(index)
+    }
+    /// Finish the oldest pending round trip of `opcode`.
+    pub fn finish_command(&mut self, opcode: Opcode) -> Option<TraceRecord> {
+        let index = self.pending.iter().position(|p| p.opcode == Some(opcode))?;
+        self.finish_index(index)
+    }
+    /// Forget `id` without recording it (the operation failed or was cancelled).
+    pub fn cancel(&mut self, id: TraceId) {
+        self.pending.retain(|p| p.id != id);
+    }
+    fn finish_index(&mut self, index: usize) -> Option<TraceRecord> {
+        let pending = self.pending.remove(index)?;
+        let latency = self.now().checked_sub(pending.started).unwrap_or_default();
+        self.histograms[pending.kind as usize].record(latency);
+        let record = TraceRecord {
+            id: pending.id,
+            kind: pending.kind,
+            opcode: pending.opcode,
+            latency,
+        };
+        if let Some(hook) = self.on_finish {
+            hook(&record);
+        }
+        Some(record)
+    }
+    pub fn is_pending(&self, id: TraceId) -> bool {
+        self.pending.iter().any(|p| p.id == id)
+    }
+    pub fn histogram(&self, kind: TraceKind) -> &LatencyHistogram {
+        &self.histograms[kind as usize]
+    }
+    pub fn clear_histograms(&mut self) {
+        self.histograms.iter_mut().for_each(LatencyHistogram::clear);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicU64, Ordering};
+    static NOW_MICROS: AtomicU64 = AtomicU64::new(0);
+    fn clock() -> Duration {
+        Duration::from_micros(NOW_MICROS.load(Ordering::Relaxed))
+    }
+    #[test]
+    fn test_tracer() {
+        let mut tracer = Tracer::new(clock);
+        let opcode = Opcode(
+            crate::hci::OGF::HCIControlBaseband,
+            crate::hci::OCF::new(0x0003),
+        );
+        let command = tracer.start_command(opcode);
+        let data = tracer.start(TraceKind::DataTransmit);
+        assert_ne!(command, data);
+        NOW_MICROS.fetch_add(1500, Ordering::Relaxed);
+        let record = tracer.finish_command(opcode).unwrap();
+        assert_eq!(record.id, command);
+        assert_eq!(record.latency, Duration::from_micros(1500));
+        assert!(tracer.finish(command).is_none());
+        assert!(tracer.is_pending(data));
+        tracer.cancel(data);
+        assert!(tracer.finish(data).is_none());
+
+        let histogram = tracer.histogram(TraceKind::CommandRoundTrip);
+        assert_eq!(histogram.count(), 1);
+        assert_eq!(histogram.percentile(50), Some(Duration::from_micros(2048)));
+        assert_eq!(histogram.max(), Some(Duration::from_micros(1500)));
+        assert_eq!(tracer.histogram(TraceKind::DataTransmit).count(), 0);
+    }
+}
@@ -0,0 +1,69 @@
+//! Allocation free reporting of anomalies (malformed packets, dropped reports, lost framing,
+//! command credit stalls). Each component that can hit one has an `on_anomaly` hook called with
+//! an [`Anomaly`]. Nothing is formatted or allocated so a hook can count anomalies in `no_std`
+//! builds without a logging framework.
+use crate::hci::{Opcode, StreamError};
+use crate::PackError;
+
+/// Called with every anomaly. Keep it short, it's called from the read loop.
+pub type AnomalyHook = fn(Anomaly);
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum Anomaly {
+    /// An event couldn't be unpacked. `event_code` is the raw HCI event code.
+    ParseFailure { event_code: u8, error: PackError },
+    /// A report (LE Meta event) was dropped because its queue was full.
+    DroppedReport,
+    /// The transport lost packet framing. The partial packet was dropped and decoding restarts
+    /// with the next byte.
+    Resync(StreamError),
+    /// A command had to wait for the controller to free a command slot.
+    CreditExhaustion { opcode: Opcode },
+}
+impl Anomaly {
+    pub const KINDS: usize = 4;
+    /// Index of the kind of anomaly (`0..Anomaly::KINDS`), for a table of counters.
+    pub fn kind_index(&self) -> usize {
+        match self {
+            Anomaly::ParseFailure { .. } => 0,
+            Anomaly::DroppedReport => 1,
+            Anomaly::Resync(_) => 2,
+            Anomaly::CreditExhaustion { .. } => 3,
+        }
+    }
+}
+/// Call `hook` (if there is one) with `anomaly`.
+pub(crate) fn report(hook: Option<AnomalyHook>, anomaly: Anomaly) {
+    if let Some(hook) = hook {
+        hook(anomaly);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hci::decoder::Decoder;
+    use core::sync::atomic::{AtomicU32, Ordering};
+    static COUNTS: [AtomicU32; Anomaly::KINDS] = [
+        AtomicU32::new(0),
+        AtomicU32::new(0),
+        AtomicU32::new(0),
+        AtomicU32::new(0),
+    ];
+    fn count(anomaly: Anomaly) {
+        COUNTS[anomaly.kind_index()].fetch_add(1, Ordering::Relaxed);
+    }
+    #[test]
+    fn test_count_anomalies() {
+        let mut decoder = Decoder::default();
+        decoder.on_anomaly = Some(count);
+        assert!(decoder.push(&[0x09]).is_err());
+        // Decoding restarts with the next byte.
+        assert_eq!(decoder.push(&[0x04, 0x0E, 0x00]), Ok(3));
+        assert!(decoder.is_complete());
+        let resync = Anomaly::Resync(StreamError::UnsupportedPacketType(0x09));
+        assert_eq!(COUNTS[resync.kind_index()].load(Ordering::Relaxed), 1);
+        assert_eq!(
+            COUNTS[Anomaly::DroppedReport.kind_index()].load(Ordering::Relaxed),
+            0
+        );
+    }
+}
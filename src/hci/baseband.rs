@@ -1,8 +1,15 @@
+use crate::bytes::truncate_name;
 use crate::hci::command::Command;
-use crate::hci::event::{CommandComplete, StatusReturn};
-use crate::hci::{Opcode, OCF, OGF};
+use crate::hci::event::{
+    CommandComplete, CommandStatus, Event, EventCode, ReturnParameters, StatusReturn,
+};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::advertise::TxPowerLevel;
+use crate::hci::{ErrorCode, Opcode, OCF, OGF};
+use crate::le::connection::ConnectionHandle;
 use crate::PackError;
-use core::convert::TryInto;
+use core::convert::{TryFrom, TryInto};
+use core::time::Duration;
 
 pub enum ControllerBasebandOpcode {
     SetEventMask = 0x0001,
@@ -12,6 +19,19 @@ pub enum ControllerBasebandOpcode {
     ReadPIN = 0x0009,
     WritePIN = 0x000A,
     ReadStoredLinkKey = 0x000D,
+    WriteLocalName = 0x0013,
+    ReadLocalName = 0x0014,
+    WriteScanEnable = 0x001A,
+    WritePageScanActivity = 0x001C,
+    WriteInquiryScanActivity = 0x001E,
+    WriteClassOfDevice = 0x0024,
+    ReadTransmitPowerLevel = 0x002D,
+    ReadAutomaticFlushTimeout = 0x0027,
+    WriteAutomaticFlushTimeout = 0x0028,
+    WriteSimplePairingMode = 0x0056,
+    EnhancedFlush = 0x005F,
+    ReadFlowControlMode = 0x0066,
+    WriteFlowControlMode = 0x0067,
 }
 impl From<ControllerBasebandOpcode> for u16 {
     fn from(opcode: ControllerBasebandOpcode) -> Self {
@@ -28,6 +48,7 @@ impl From<ControllerBasebandOpcode> for Opcode {
         Opcode(OGF::HCIControlBaseband, ocf.into())
     }
 }
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
 pub struct Reset;
 impl Reset {
     pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::Reset;
@@ -111,6 +132,18 @@ pub enum EventMaskFlags {
     RemoteHouseSupportedFeaturesNotification = 0x3C,
     LEMetaEvent = 0x3D,
 }
+/// `Event_Mask` of [`SetEventMask`]. Build one per application instead of taking every event:
+/// ```
+/// use btle::hci::baseband::EventMask;
+/// use btle::hci::event::EventCode;
+/// let mask = EventMask::ZEROED
+///     .enable(EventCode::DisconnectionComplete)
+///     .enable(EventCode::LEMeta);
+/// assert!(mask.is_enabled(EventCode::LEMeta) && !mask.is_enabled(EventCode::InquiryComplete));
+/// // Always sent.
+/// assert!(mask.is_enabled(EventCode::CommandComplete));
+/// assert_eq!(mask.0, (1 << 4) | (1 << 61));
+/// ```
 #[derive(Copy, Clone, Eq, Hash, Debug, PartialEq)]
 pub struct EventMask(pub u64);
 impl From<EventMaskFlags> for u8 {
@@ -138,6 +171,44 @@ impl EventMask {
     pub fn get_event(&mut self, flag: EventMaskFlags) -> bool {
         self.0 & (1 << u64::from(flag)) != 0
     }
+    /// Mask bit of `event` or `None` if it can't be masked (Command Complete, Command Status and
+    /// Number Of Completed Packets are always sent, the AMP events are on page 2).
+    pub fn event_bit(event: EventCode) -> Option<u32> {
+        match event {
+            EventCode::CommandComplete
+            | EventCode::CommandStatus
+            | EventCode::NumberOfCompletedPackets
+            | EventCode::VendorSpecific => None,
+            event if u8::from(event) <= u8::from(EventCode::LEMeta) => {
+                Some(u32::from(u8::from(event) - 1))
+            }
+            _ => None,
+        }
+    }
+    /// Unmask `event`. Events without a mask bit (see [`EventMask::event_bit`]) are ignored.
+    #[must_use]
+    pub fn enable(self, event: EventCode) -> EventMask {
+        match Self::event_bit(event) {
+            Some(bit) => EventMask(self.0 | 1 << bit),
+            None => self,
+        }
+    }
+    /// Mask `event`. Events without a mask bit (see [`EventMask::event_bit`]) are ignored.
+    #[must_use]
+    pub fn disable(self, event: EventCode) -> EventMask {
+        match Self::event_bit(event) {
+            Some(bit) => EventMask(self.0 & !(1 << bit)),
+            None => self,
+        }
+    }
+    /// Whether the controller will send `event`. Events that can't be masked are always sent
+    /// (except the page 2 events, which this mask doesn't control).
+    pub fn is_enabled(self, event: EventCode) -> bool {
+        match Self::event_bit(event) {
+            Some(bit) => self.0 & (1 << bit) != 0,
+            None => u8::from(event) <= u8::from(EventCode::LEMeta),
+        }
+    }
 }
 impl Default for EventMask {
     fn default() -> Self {
@@ -180,3 +251,636 @@ impl Command for SetEventMask {
         ))))
     }
 }
+/// `Status` and `Connection_Handle` returned by the flush commands (and other commands that only
+/// return the handle).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct HandleReturn {
+    pub status: ErrorCode,
+    pub handle: ConnectionHandle,
+}
+impl HandleReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN;
+}
+impl ReturnParameters for HandleReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(HandleReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            handle: ConnectionHandle::unpack_from(buf, 1)?,
+        })
+    }
+}
+/// `HCI_Flush`. Drop every ACL packet waiting to be sent on `handle` (including the one being
+/// retransmitted). Answered by [`FlushOccurred`] before the Command Complete.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct Flush {
+    pub handle: ConnectionHandle,
+}
+impl Flush {
+    pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::Flush;
+}
+impl Command for Flush {
+    type Return = CommandComplete<HandleReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ConnectionHandle::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        Ok(Flush {
+            handle: ConnectionHandle::unpack_from(buf, 0)?,
+        })
+    }
+}
+/// Which packets [`EnhancedFlush`] drops. Only automatically-flushable packets are defined.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum FlushPacketType {
+    AutomaticallyFlushable = 0x00,
+}
+impl From<FlushPacketType> for u8 {
+    fn from(t: FlushPacketType) -> Self {
+        t as u8
+    }
+}
+impl TryFrom<u8> for FlushPacketType {
+    type Error = crate::ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(FlushPacketType::AutomaticallyFlushable),
+            _ => Err(crate::ConversionError(())),
+        }
+    }
+}
+/// `HCI_Enhanced_Flush`. Like [`Flush`] but only drops `packet_type` packets. Finishes with
+/// [`EnhancedFlushComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct EnhancedFlush {
+    pub handle: ConnectionHandle,
+    pub packet_type: FlushPacketType,
+}
+impl EnhancedFlush {
+    pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::EnhancedFlush;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + 1;
+}
+impl Command for EnhancedFlush {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V2_1;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[..2].copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        buf[2] = self.packet_type.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(EnhancedFlush {
+            handle: ConnectionHandle::unpack_from(buf, 0)?,
+            packet_type: FlushPacketType::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+        })
+    }
+}
+/// How long the controller keeps retransmitting an automatically-flushable ACL packet before
+/// dropping it (in 0.625ms slots). `0` never flushes.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct FlushTimeout(u16);
+impl FlushTimeout {
+    pub const BYTE_LEN: usize = 2;
+    pub const INFINITE: FlushTimeout = FlushTimeout(0);
+    pub const MAX_U16: u16 = 0x07FF;
+    pub const MAX: FlushTimeout = FlushTimeout(Self::MAX_U16);
+    pub const SLOT: Duration = Duration::from_micros(625);
+    pub fn new(value: u16) -> Self {
+        match Self::new_checked(value) {
+            Some(s) => s,
+            None => panic!("flush timeout out of range (`{}`)", value),
+        }
+    }
+    pub fn new_checked(value: u16) -> Option<Self> {
+        if value > Self::MAX_U16 {
+            None
+        } else {
+            Some(Self(value))
+        }
+    }
+    /// Shortest timeout that's at least `duration`. `None` if it's longer than [`Self::MAX`].
+    pub fn from_duration(duration: Duration) -> Option<FlushTimeout> {
+        let slot = Self::SLOT.as_micros();
+        let slots = duration.as_micros().div_ceil(slot);
+        Self::new_checked(u16::try_from(slots).ok()?.max(1))
+    }
+    /// `None` for [`Self::INFINITE`].
+    pub fn duration(self) -> Option<Duration> {
+        if self == Self::INFINITE {
+            None
+        } else {
+            Some(Self::SLOT * u32::from(self.0))
+        }
+    }
+}
+impl From<FlushTimeout> for u16 {
+    fn from(t: FlushTimeout) -> Self {
+        t.0
+    }
+}
+/// `HCI_Read_Automatic_Flush_Timeout`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReadAutomaticFlushTimeout {
+    pub handle: ConnectionHandle,
+}
+impl ReadAutomaticFlushTimeout {
+    pub const OPCODE: ControllerBasebandOpcode =
+        ControllerBasebandOpcode::ReadAutomaticFlushTimeout;
+}
+impl Command for ReadAutomaticFlushTimeout {
+    type Return = CommandComplete<FlushTimeoutReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ConnectionHandle::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        Ok(ReadAutomaticFlushTimeout {
+            handle: ConnectionHandle::unpack_from(buf, 0)?,
+        })
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct FlushTimeoutReturn {
+    pub status: ErrorCode,
+    pub handle: ConnectionHandle,
+    pub flush_timeout: FlushTimeout,
+}
+impl FlushTimeoutReturn {
+    pub const BYTE_LEN: usize = HandleReturn::BYTE_LEN + FlushTimeout::BYTE_LEN;
+}
+impl ReturnParameters for FlushTimeoutReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        buf[3..5].copy_from_slice(&u16::from(self.flush_timeout).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(FlushTimeoutReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            handle: ConnectionHandle::unpack_from(buf, 1)?,
+            flush_timeout: FlushTimeout::new_checked(u16::from_le_bytes([buf[3], buf[4]]))
+                .ok_or(PackError::bad_index(3))?,
+        })
+    }
+}
+/// `HCI_Write_Automatic_Flush_Timeout`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct WriteAutomaticFlushTimeout {
+    pub handle: ConnectionHandle,
+    pub flush_timeout: FlushTimeout,
+}
+impl WriteAutomaticFlushTimeout {
+    pub const OPCODE: ControllerBasebandOpcode =
+        ControllerBasebandOpcode::WriteAutomaticFlushTimeout;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + FlushTimeout::BYTE_LEN;
+}
+impl Command for WriteAutomaticFlushTimeout {
+    type Return = CommandComplete<HandleReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[..2].copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        buf[2..].copy_from_slice(&u16::from(self.flush_timeout).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(WriteAutomaticFlushTimeout {
+            handle: ConnectionHandle::unpack_from(buf, 0)?,
+            flush_timeout: FlushTimeout::new_checked(u16::from_le_bytes([buf[2], buf[3]]))
+                .ok_or(PackError::bad_index(2))?,
+        })
+    }
+}
+/// Which TX power [`ReadTransmitPowerLevel`] reads.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum TransmitPowerLevelType {
+    Current = 0x00,
+    Maximum = 0x01,
+}
+impl From<TransmitPowerLevelType> for u8 {
+    fn from(t: TransmitPowerLevelType) -> Self {
+        t as u8
+    }
+}
+impl TryFrom<u8> for TransmitPowerLevelType {
+    type Error = crate::ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(TransmitPowerLevelType::Current),
+            0x01 => Ok(TransmitPowerLevelType::Maximum),
+            _ => Err(crate::ConversionError(())),
+        }
+    }
+}
+/// `HCI_Read_Transmit_Power_Level`. The TX power used on the connection `handle`. Together with
+/// the [`crate::hci::status::ReadRSSI`] of the same connection it gives the path loss.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReadTransmitPowerLevel {
+    pub handle: ConnectionHandle,
+    pub level_type: TransmitPowerLevelType,
+}
+impl ReadTransmitPowerLevel {
+    pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::ReadTransmitPowerLevel;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + 1;
+}
+impl Command for ReadTransmitPowerLevel {
+    type Return = CommandComplete<TransmitPowerLevelReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[..2].copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        buf[2] = self.level_type.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(ReadTransmitPowerLevel {
+            handle: ConnectionHandle::unpack_from(buf, 0)?,
+            level_type: TransmitPowerLevelType::try_from(buf[2])
+                .map_err(|_| PackError::bad_index(2))?,
+        })
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct TransmitPowerLevelReturn {
+    pub status: ErrorCode,
+    pub handle: ConnectionHandle,
+    pub power_level: TxPowerLevel,
+}
+impl TransmitPowerLevelReturn {
+    pub const BYTE_LEN: usize = HandleReturn::BYTE_LEN + 1;
+}
+impl ReturnParameters for TransmitPowerLevelReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        buf[3] = self.power_level.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(TransmitPowerLevelReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            handle: ConnectionHandle::unpack_from(buf, 1)?,
+            power_level: TxPowerLevel::try_from(buf[3]).map_err(|_| PackError::bad_index(3))?,
+        })
+    }
+}
+/// `Local_Name` parameter. UTF-8, up to 248 bytes. Shorter names are zero padded.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct LocalName {
+    bytes: [u8; LocalName::MAX_LEN],
+    len: u8,
+}
+impl LocalName {
+    pub const MAX_LEN: usize = 248;
+    pub const BYTE_LEN: usize = Self::MAX_LEN;
+    pub const EMPTY: LocalName = LocalName {
+        bytes: [0; Self::MAX_LEN],
+        len: 0,
+    };
+    /// Returns `None` if `name` is longer than [`LocalName::MAX_LEN`] bytes or contains a `\0`
+    /// (the controller would end the name there).
+    pub fn new_checked(name: &str) -> Option<LocalName> {
+        if name.len() > Self::MAX_LEN || name.contains('\0') {
+            return None;
+        }
+        let mut bytes = [0_u8; Self::MAX_LEN];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Some(LocalName {
+            bytes,
+            len: name.len() as u8,
+        })
+    }
+    /// `name` cut at its first `\0`, then to its longest prefix of whole characters that fits in
+    /// [`LocalName::MAX_LEN`] bytes.
+    pub fn truncated(name: &str) -> LocalName {
+        let name = name.split('\0').next().unwrap_or("");
+        Self::new_checked(truncate_name(name, Self::MAX_LEN)).expect("truncated to MAX_LEN")
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.len)]
+    }
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(self.as_bytes()).expect("checked when created")
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&self.bytes[..]);
+        Ok(())
+    }
+    /// The name ends at the first zero byte (or is all 248 bytes). A character cut off at the
+    /// end of a full 248 byte name (a longer name truncated without care) is dropped.
+    pub fn unpack_from(buf: &[u8]) -> Result<LocalName, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let len = buf.iter().position(|b| *b == 0).unwrap_or(Self::MAX_LEN);
+        let name = match core::str::from_utf8(&buf[..len]) {
+            Ok(name) => name,
+            Err(e) if e.error_len().is_none() && len == Self::MAX_LEN => {
+                core::str::from_utf8(&buf[..e.valid_up_to()]).expect("valid up to here")
+            }
+            Err(e) => return Err(PackError::bad_index(e.valid_up_to())),
+        };
+        Ok(Self::new_checked(name).expect("length checked above"))
+    }
+}
+impl Default for LocalName {
+    fn default() -> Self {
+        LocalName::EMPTY
+    }
+}
+impl core::fmt::Debug for LocalName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("LocalName").field(&self.as_str()).finish()
+    }
+}
+/// `HCI_Write_Local_Name`. The user friendly name of the controller (the BR/EDR name, also used
+/// by some stacks as the default LE name).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct WriteLocalName {
+    pub name: LocalName,
+}
+impl WriteLocalName {
+    pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::WriteLocalName;
+}
+impl Command for WriteLocalName {
+    type Return = CommandComplete<StatusReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        LocalName::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.name.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(WriteLocalName {
+            name: LocalName::unpack_from(buf)?,
+        })
+    }
+}
+/// `HCI_Read_Local_Name`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadLocalName;
+impl ReadLocalName {
+    pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::ReadLocalName;
+}
+impl Command for ReadLocalName {
+    type Return = CommandComplete<LocalNameReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadLocalName)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct LocalNameReturn {
+    pub status: ErrorCode,
+    pub name: LocalName,
+}
+impl LocalNameReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + LocalName::BYTE_LEN;
+}
+impl ReturnParameters for LocalNameReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        self.name.pack_into(&mut buf[1..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LocalNameReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            name: LocalName::unpack_from(&buf[1..])?,
+        })
+    }
+}
+/// Events that only carry a `Connection_Handle`.
+macro_rules! handle_event {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+        pub struct $name {
+            pub handle: ConnectionHandle,
+        }
+        impl Event for $name {
+            const EVENT_CODE: EventCode = EventCode::$name;
+
+            fn event_byte_len(&self) -> usize {
+                ConnectionHandle::BYTE_LEN
+            }
+
+            fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+            where
+                Self: Sized,
+            {
+                PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+                Ok($name {
+                    handle: ConnectionHandle::unpack_from(buf, 0)?,
+                })
+            }
+
+            fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+                PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+                buf.copy_from_slice(&u16::from(self.handle).to_le_bytes());
+                Ok(())
+            }
+        }
+    };
+}
+handle_event!(
+    /// Packets waiting on `handle` were dropped, by [`Flush`] or because the automatic flush
+    /// timeout expired.
+    FlushOccurred
+);
+handle_event!(
+    /// [`EnhancedFlush`] finished dropping packets on `handle`.
+    EnhancedFlushComplete
+);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::le::advertisement_structures::tx_power_level;
+    use alloc::string::String;
+    #[test]
+    fn test_local_name() {
+        // 247 ASCII bytes then a 2 byte character that doesn't fit.
+        let mut long = String::from("a").repeat(247);
+        long.push('é');
+        assert!(LocalName::new_checked(&long).is_none());
+        let name = LocalName::truncated(&long);
+        assert_eq!(name.as_bytes().len(), 247);
+        assert_eq!(LocalName::truncated("gateway").as_str(), "gateway");
+        // An embedded NUL would end the name early on the wire.
+        assert!(LocalName::new_checked("gate\0way").is_none());
+        assert!(LocalName::new_checked("\0").is_none());
+        assert_eq!(LocalName::truncated("gate\0way").as_str(), "gate");
+        // A controller cutting the character in half.
+        let mut buf = [0_u8; LocalNameReturn::BYTE_LEN];
+        buf[1..].copy_from_slice(&long.as_bytes()[..LocalName::MAX_LEN]);
+        assert_eq!(LocalNameReturn::unpack_from(&buf).unwrap().name, name);
+        // A partial character before the end of the name is corruption, not truncation.
+        buf[100] = 0;
+        assert!(LocalNameReturn::unpack_from(&buf).is_ok());
+        buf[99] = 0xC3;
+        assert!(LocalNameReturn::unpack_from(&buf).is_err());
+        buf[1] = 0xFF;
+        assert!(LocalNameReturn::unpack_from(&buf).is_err());
+    }
+    #[test]
+    fn test_transmit_power_level() {
+        let command = ReadTransmitPowerLevel {
+            handle: ConnectionHandle::new(0x0001),
+            level_type: TransmitPowerLevelType::Maximum,
+        };
+        let mut buf = [0_u8; ReadTransmitPowerLevel::BYTE_LEN];
+        command.pack_into(&mut buf).unwrap();
+        assert_eq!(buf, [0x01, 0x00, 0x01]);
+        assert_eq!(ReadTransmitPowerLevel::unpack_from(&buf), Ok(command));
+        let r = TransmitPowerLevelReturn::unpack_from(&[0x00, 0x01, 0x00, 0xF8]).unwrap();
+        assert_eq!(r.power_level, TxPowerLevel::new(-8));
+        // Reused for the TX Power Level AD structure.
+        let ad = tx_power_level::TxPowerLevel::from(r.power_level);
+        assert_eq!(ad.dbm, -8);
+    }
+}
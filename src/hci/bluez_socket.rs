@@ -1,10 +1,11 @@
 //! BlueZ socket layer. Interacts with the BlueZ driver over socket AF_BLUETOOTH.
 use crate::hci::stream::{Filter, HCIFilterable, HCIReader, HCIWriter, FILTER_LEN};
-use crate::BTAddress;
+use crate::{BTAddress, PackError, BT_ADDRESS_LEN};
 use core::convert::TryFrom;
 use core::fmt::{Display, Formatter};
 use core::ops::Deref;
 use core::pin::Pin;
+use core::time::Duration;
 use std::os::unix::{
     io::{AsRawFd, FromRawFd, RawFd},
     net::UnixStream,
@@ -24,8 +25,13 @@ mod ioctl {
     // HCIGETDEVLIST =	_IOR('H', 210, int)
     nix::ioctl_read!(hci_get_dev_list, b'H', 210, super::HCIDevListReq);
 
-    // HCIGETDEVINFO =	_IOR('H', 211, int)
-    nix::ioctl_read!(hci_get_dev_info, b'H', 211, super::HCIDevInfo);
+    // HCIGETDEVINFO =	_IOR('H', 211, int). The size in the request is `int` but the kernel
+    // reads/writes a whole `struct hci_dev_info`.
+    nix::ioctl_read_bad!(
+        hci_get_dev_info,
+        nix::request_code_read!(b'H', 211, core::mem::size_of::<libc::c_int>()),
+        [u8; super::HCIDevInfo::BYTE_LEN]
+    );
 }
 #[repr(i32)]
 enum BTProtocol {
@@ -77,6 +83,15 @@ impl From<HCIChannel> for u16 {
         channel as u16
     }
 }
+/// Native endian `u32` at `index`. The kernel fills `hci_dev_info` in host byte order.
+fn u32_at(buf: &[u8], index: usize) -> u32 {
+    u32::from_ne_bytes([buf[index], buf[index + 1], buf[index + 2], buf[index + 3]])
+}
+fn u16_at(buf: &[u8], index: usize) -> u16 {
+    u16::from_ne_bytes([buf[index], buf[index + 1]])
+}
+/// Traffic counters the kernel keeps for an adapter (`struct hci_dev_stats`). They count from
+/// when the adapter was registered and wrap around.
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
 pub struct HCIDevStats {
     pub err_rx: u32,
@@ -90,16 +105,82 @@ pub struct HCIDevStats {
     pub byte_rx: u32,
     pub byte_tx: u32,
 }
+impl HCIDevStats {
+    pub const BYTE_LEN: usize = 10 * 4;
+    pub fn unpack_from(buf: &[u8]) -> Result<HCIDevStats, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(HCIDevStats {
+            err_rx: u32_at(buf, 0),
+            err_tx: u32_at(buf, 4),
+            cmd_tx: u32_at(buf, 8),
+            evt_rx: u32_at(buf, 12),
+            acl_tx: u32_at(buf, 16),
+            acl_rx: u32_at(buf, 20),
+            sco_tx: u32_at(buf, 24),
+            sco_rx: u32_at(buf, 28),
+            byte_rx: u32_at(buf, 32),
+            byte_tx: u32_at(buf, 36),
+        })
+    }
+    /// Counts since `earlier` (an older snapshot of the same adapter). Handles counters that
+    /// wrapped in between.
+    pub fn since(&self, earlier: &HCIDevStats) -> HCIDevStats {
+        HCIDevStats {
+            err_rx: self.err_rx.wrapping_sub(earlier.err_rx),
+            err_tx: self.err_tx.wrapping_sub(earlier.err_tx),
+            cmd_tx: self.cmd_tx.wrapping_sub(earlier.cmd_tx),
+            evt_rx: self.evt_rx.wrapping_sub(earlier.evt_rx),
+            acl_tx: self.acl_tx.wrapping_sub(earlier.acl_tx),
+            acl_rx: self.acl_rx.wrapping_sub(earlier.acl_rx),
+            sco_tx: self.sco_tx.wrapping_sub(earlier.sco_tx),
+            sco_rx: self.sco_rx.wrapping_sub(earlier.sco_rx),
+            byte_rx: self.byte_rx.wrapping_sub(earlier.byte_rx),
+            byte_tx: self.byte_tx.wrapping_sub(earlier.byte_tx),
+        }
+    }
+    pub fn errors(&self) -> u32 {
+        self.err_rx.saturating_add(self.err_tx)
+    }
+}
 pub struct HCIDevReq {
     pub dev_id: u16,
     pub dev_opt: u32,
 }
+/// Adapter state bits of [`HCIDevInfo::flags`] (`HCI_UP`, etc).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum HCIDevFlag {
+    Up = 0,
+    Init = 1,
+    Running = 2,
+    PageScan = 3,
+    InquiryScan = 4,
+    Auth = 5,
+    Encrypt = 6,
+    Inquiry = 7,
+    Raw = 8,
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct HCIDevFlags(pub u32);
+impl HCIDevFlags {
+    pub fn contains(self, flag: HCIDevFlag) -> bool {
+        self.0 & (1_u32 << flag as u8) != 0
+    }
+    pub fn is_up(self) -> bool {
+        self.contains(HCIDevFlag::Up)
+    }
+}
+/// Adapter information from the kernel (`struct hci_dev_info`), see [`Manager::device_info`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
 pub struct HCIDevInfo {
     pub dev_id: u16,
+    /// Interface name (`hci0`, etc), zero padded. See [`HCIDevInfo::name`].
     pub name: [u8; 8],
     pub address: BTAddress,
-    pub flags: u32,
+    pub flags: HCIDevFlags,
+    /// Bus (low nibble) and primary/AMP type.
     pub dev_type: u8,
+    /// LMP features (page 0).
     pub features: [u8; 8],
     pub pkt_type: u32,
     pub link_policy: u32,
@@ -110,6 +191,45 @@ pub struct HCIDevInfo {
     pub sco_pkts: u16,
     pub stats: HCIDevStats,
 }
+impl HCIDevInfo {
+    /// Size of `struct hci_dev_info` including the C padding after `features`.
+    pub const BYTE_LEN: usize = 52 + HCIDevStats::BYTE_LEN;
+    fn trimmed_name(name: &[u8; 8]) -> Result<&str, core::str::Utf8Error> {
+        let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        core::str::from_utf8(&name[..len])
+    }
+    /// Interface name without the padding.
+    pub fn name(&self) -> &str {
+        Self::trimmed_name(&self.name).unwrap_or("")
+    }
+    pub fn is_up(&self) -> bool {
+        self.flags.is_up()
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<HCIDevInfo, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let mut name = [0_u8; 8];
+        name.copy_from_slice(&buf[2..10]);
+        let mut features = [0_u8; 8];
+        features.copy_from_slice(&buf[21..29]);
+        Self::trimmed_name(&name).map_err(|_| PackError::bad_index(2))?;
+        Ok(HCIDevInfo {
+            dev_id: u16_at(buf, 0),
+            name,
+            address: BTAddress::unpack_from(&buf[10..10 + BT_ADDRESS_LEN])?,
+            flags: HCIDevFlags(u32_at(buf, 16)),
+            dev_type: buf[20],
+            features,
+            pkt_type: u32_at(buf, 32),
+            link_policy: u32_at(buf, 36),
+            link_mode: u32_at(buf, 40),
+            acl_mtu: u16_at(buf, 44),
+            acl_pkts: u16_at(buf, 46),
+            sco_mtu: u16_at(buf, 48),
+            sco_pkts: u16_at(buf, 50),
+            stats: HCIDevStats::unpack_from(&buf[52..])?,
+        })
+    }
+}
 pub struct HCIDevListReq {}
 #[derive(Copy, Clone, PartialOrd, PartialEq, Ord, Eq, Hash, Debug)]
 pub struct AdapterID(pub u16);
@@ -264,6 +384,55 @@ impl Manager {
         let control_fd = *control_lock.deref();
         Self::raw_device_down(control_fd, adapter_id)
     }
+    /// Reset the adapter (`hciconfig hciX reset`). It stays up.
+    pub fn reset(&self, adapter_id: AdapterID) -> Result<(), IOError> {
+        let control_lock = self
+            .control_fd
+            .lock()
+            .expect("mutexs only fail when poisoned");
+        let control_fd = *control_lock.deref();
+        unsafe {
+            ioctl::hci_device_reset(
+                control_fd,
+                adapter_id.0 as nix::sys::ioctl::ioctl_param_type,
+            )
+            .map_err(hci_to_socket_error)?;
+        }
+        Ok(())
+    }
+    /// Bring the adapter up or down (`hciconfig hciX up/down`). Does nothing if it already is.
+    pub fn set_powered(&self, adapter_id: AdapterID, is_powered: bool) -> Result<(), IOError> {
+        if self.device_info(adapter_id)?.is_up() == is_powered {
+            return Ok(());
+        }
+        if is_powered {
+            self.device_up(adapter_id)
+        } else {
+            self.device_down(adapter_id)
+        }
+    }
+    /// Take the adapter away from BlueZ. The adapter is brought down and a
+    /// [`HCIChannel::User`] socket is bound to it. Dropping the returned guard closes the socket
+    /// and brings the adapter back up if it was up before.
+    pub fn take_exclusive(&self, adapter_id: AdapterID) -> Result<ExclusiveAdapter<'_>, IOError> {
+        let was_up = self.device_info(adapter_id)?.is_up();
+        self.set_powered(adapter_id, false)?;
+        match HCISocket::new_channel(adapter_id, HCIChannel::User) {
+            Ok(socket) => Ok(ExclusiveAdapter {
+                manager: self,
+                adapter_id,
+                socket: Some(socket),
+                was_up,
+            }),
+            Err(e) => {
+                if was_up {
+                    // Best effort, the bind error is more useful.
+                    let _ = self.device_up(adapter_id);
+                }
+                Err(e)
+            }
+        }
+    }
     fn raw_device_down(ctl_fd: i32, adapter_id: AdapterID) -> Result<(), IOError> {
         unsafe {
             ioctl::hci_device_down(ctl_fd, adapter_id.0 as nix::sys::ioctl::ioctl_param_type)
@@ -278,6 +447,20 @@ impl Manager {
         }
         Ok(())
     }
+    /// Name, address, state and traffic counters of the adapter.
+    pub fn device_info(&self, adapter_id: AdapterID) -> Result<HCIDevInfo, IOError> {
+        let control_lock = self
+            .control_fd
+            .lock()
+            .expect("mutexs only fail when poisoned");
+        let control_fd = *control_lock.deref();
+        let mut buf = [0_u8; HCIDevInfo::BYTE_LEN];
+        buf[..2].copy_from_slice(&adapter_id.0.to_ne_bytes());
+        unsafe {
+            ioctl::hci_get_dev_info(control_fd, &mut buf).map_err(hci_to_socket_error)?;
+        }
+        HCIDevInfo::unpack_from(&buf).map_err(|_| IOError::InvalidData)
+    }
     pub fn get_adapter_socket(&self, adapter_id: AdapterID) -> Result<HCISocket, IOError> {
         let control_lock = self
             .control_fd
@@ -290,6 +473,90 @@ impl Manager {
     }
 }
 
+/// Exclusive access to an adapter from [`Manager::take_exclusive`]. The prior power state is
+/// restored on drop. Clones of the socket have to be dropped first, the adapter can't come back
+/// up while the user channel is open.
+#[derive(Debug)]
+pub struct ExclusiveAdapter<'m> {
+    manager: &'m Manager,
+    adapter_id: AdapterID,
+    socket: Option<HCISocket>,
+    was_up: bool,
+}
+impl<'m> ExclusiveAdapter<'m> {
+    pub fn adapter_id(&self) -> AdapterID {
+        self.adapter_id
+    }
+    pub fn socket(&self) -> &HCISocket {
+        self.socket.as_ref().expect("only taken on drop")
+    }
+    /// Another handle to the user channel socket (to turn into an [`AsyncHCISocket`]).
+    pub fn try_clone_socket(&self) -> Result<HCISocket, IOError> {
+        let stream = self.socket().0.try_clone().map_err(IOError::from)?;
+        Ok(HCISocket(stream))
+    }
+}
+impl<'m> Drop for ExclusiveAdapter<'m> {
+    fn drop(&mut self) {
+        self.socket = None;
+        if self.was_up {
+            // Nothing to report the error to.
+            let _ = self.manager.device_up(self.adapter_id);
+        }
+    }
+}
+/// Called with the traffic of an adapter since the last poll.
+pub type StatsHook = fn(AdapterID, &HCIDevStats);
+/// Reads [`HCIDevStats`] of an adapter every `interval` and hands the change since the last
+/// read to `on_stats`. Time is passed in by the caller (call [`StatsPoller::poll`] from any
+/// timer) so no clock is needed.
+#[derive(Copy, Clone, Debug)]
+pub struct StatsPoller {
+    pub adapter_id: AdapterID,
+    pub interval: Duration,
+    pub on_stats: Option<StatsHook>,
+    last: Option<HCIDevStats>,
+    next_poll: Duration,
+}
+impl StatsPoller {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+    pub fn new(adapter_id: AdapterID, interval: Duration) -> StatsPoller {
+        StatsPoller {
+            adapter_id,
+            interval,
+            on_stats: None,
+            last: None,
+            next_poll: Duration::from_secs(0),
+        }
+    }
+    /// Counters read by the last poll.
+    pub fn last(&self) -> Option<HCIDevStats> {
+        self.last
+    }
+    /// Read the counters if `interval` has passed since the last read (`now` is any monotonic
+    /// time). Returns the change since the last read, `None` if it isn't time yet or this was
+    /// the first read.
+    pub fn poll(
+        &mut self,
+        manager: &Manager,
+        now: Duration,
+    ) -> Result<Option<HCIDevStats>, IOError> {
+        if now < self.next_poll {
+            return Ok(None);
+        }
+        let stats = manager.device_info(self.adapter_id)?.stats;
+        Ok(self.update(stats, now))
+    }
+    fn update(&mut self, stats: HCIDevStats, now: Duration) -> Option<HCIDevStats> {
+        self.next_poll = now + self.interval;
+        let delta = self.last.map(|last| stats.since(&last));
+        self.last = Some(stats);
+        if let (Some(delta), Some(hook)) = (delta.as_ref(), self.on_stats) {
+            hook(self.adapter_id, delta)
+        }
+        delta
+    }
+}
 impl TryFrom<HCISocket> for AsyncHCISocket {
     type Error = std::io::Error;
 
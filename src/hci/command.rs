@@ -1,6 +1,7 @@
 //! HCI Command and command utilities.
 use crate::bytes::Storage;
 use crate::hci::event::{Event, EventPacket, ReturnEvent};
+use crate::hci::informational::CoreVersion;
 use crate::hci::packet::{PacketType, RawPacket};
 use crate::hci::{Opcode, OPCODE_LEN};
 use crate::PackError;
@@ -62,6 +63,9 @@ pub struct CommandHeader {
 /// HCI Command trait for structs that are HCI commands.
 pub trait Command {
     type Return: ReturnEvent;
+    /// Oldest Bluetooth Core version that defines this command. Adapters can refuse to send it
+    /// to older controllers (see [`crate::hci::adapters::VersionPolicy`]).
+    const MIN_VERSION: CoreVersion = CoreVersion::V1_0B;
     fn opcode() -> Opcode;
     fn full_len(&self) -> usize {
         self.byte_len() + OPCODE_LEN + 1
@@ -126,3 +130,16 @@ pub trait Command {
         Ok(None)
     }
 }
+/// Command answered by a [`crate::hci::event::CommandStatus`] that only finishes when a later
+/// `Completion` event arrives (connecting, inquiry, etc). `Cancel` stops it early.
+pub trait LongCommand: Command<Return = crate::hci::event::CommandStatus> {
+    type Completion: Event;
+    type Cancel: Command<
+        Return = crate::hci::event::CommandComplete<crate::hci::event::StatusReturn>,
+    >;
+    fn cancel_command(&self) -> Self::Cancel;
+    /// If `completion` finishes this command (and not another command of the same type).
+    fn is_completion(&self, _completion: &Self::Completion) -> bool {
+        true
+    }
+}
@@ -0,0 +1,193 @@
+//! Transport agnostic HCI packet decoding. Live streams, btsnoop replays
+//! ([`crate::hci::snoop`]) and the BlueZ monitor channel ([`crate::hci::monitor`]) all frame and
+//! validate packets through here so they can't disagree about what a packet looks like.
+//!
+//! [`decode`] checks a packet whose type is already known (monitor frames, un-encapsulated
+//! snoop records), [`decode_h4`] checks a complete H4 frame (packet type byte first) and
+//! [`Decoder`] reassembles H4 frames from a byte stream that may split or join packets (UART).
+use crate::hci::anomaly::{self, Anomaly, AnomalyHook};
+use crate::hci::packet::{PacketType, RawPacket};
+use crate::hci::{StreamError, MAX_FRAME_SIZE};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Length of the header (the part holding the parameter/data length) of each packet type.
+pub fn header_len(packet_type: PacketType) -> Result<usize, StreamError> {
+    match packet_type {
+        PacketType::Command => Ok(3),
+        PacketType::ACLData => Ok(4),
+        PacketType::SCOData => Ok(3),
+        PacketType::Event => Ok(2),
+        PacketType::ISOData => Ok(4),
+        PacketType::Vendor => Err(StreamError::UnsupportedPacketType(packet_type.into())),
+    }
+}
+/// Length of the parameters/data following `header`. `header` must be (at least)
+/// `header_len(packet_type)` bytes.
+pub fn payload_len(packet_type: PacketType, header: &[u8]) -> Result<usize, StreamError> {
+    let len = header_len(packet_type)?;
+    if header.len() < len {
+        return Err(StreamError::BadPacketCode);
+    }
+    Ok(match packet_type {
+        PacketType::Command | PacketType::SCOData => usize::from(header[2]),
+        PacketType::Event => usize::from(header[1]),
+        PacketType::ACLData => usize::from(u16::from_le_bytes([header[2], header[3]])),
+        PacketType::ISOData => usize::from(u16::from_le_bytes([header[2], header[3]]) & 0x3FFF),
+        PacketType::Vendor => unreachable!("rejected by header_len"),
+    })
+}
+/// Check that `buf` (packet without the type byte) is exactly one `packet_type` packet.
+pub fn decode(packet_type: PacketType, buf: &[u8]) -> Result<RawPacket<&[u8]>, StreamError> {
+    let header_len = header_len(packet_type)?;
+    if buf.len() < header_len || buf.len() != header_len + payload_len(packet_type, buf)? {
+        return Err(StreamError::BadPacketCode);
+    }
+    Ok(RawPacket { packet_type, buf })
+}
+/// Check that `buf` is exactly one H4 frame (packet type byte then the packet).
+pub fn decode_h4(buf: &[u8]) -> Result<RawPacket<&[u8]>, StreamError> {
+    let (&packet_type, rest) = buf.split_first().ok_or(StreamError::BadPacketCode)?;
+    let packet_type = PacketType::try_from(packet_type)
+        .map_err(|_| StreamError::UnsupportedPacketType(packet_type))?;
+    decode(packet_type, rest)
+}
+/// Incremental H4 frame decoder for byte streams. Bytes are pushed in as they arrive and each
+/// complete packet is available from [`Decoder::packet`] until more bytes are pushed.
+#[derive(Clone, Debug)]
+pub struct Decoder {
+    frame: Vec<u8>,
+    /// Bytes still needed to finish the current part of the frame (type, header or payload).
+    needed: usize,
+    /// Whether the header has been read (and `needed` is the payload length).
+    has_header: bool,
+    max_packet_len: usize,
+    /// Called with [`Anomaly::Resync`] when a partial packet is dropped.
+    pub on_anomaly: Option<AnomalyHook>,
+}
+impl Decoder {
+    /// `max_packet_len` is the largest packet (without the type byte) accepted.
+    pub fn new(max_packet_len: usize) -> Decoder {
+        Decoder {
+            frame: Vec::new(),
+            needed: 1,
+            has_header: false,
+            max_packet_len,
+            on_anomaly: None,
+        }
+    }
+    /// Drop any partially decoded packet.
+    pub fn reset(&mut self) {
+        self.frame.clear();
+        self.needed = 1;
+        self.has_header = false;
+    }
+    /// Whether a complete packet is ready.
+    pub fn is_complete(&self) -> bool {
+        self.has_header && self.needed == 0
+    }
+    /// Bytes still needed to finish the current part of the frame. Transports that can read an
+    /// exact number of bytes (USB) can read this many without reading into the next packet.
+    pub fn remaining(&self) -> usize {
+        if self.is_complete() {
+            1
+        } else {
+            self.needed
+        }
+    }
+    /// The packet decoded by the last [`Decoder::push`] (if it completed one).
+    pub fn packet(&self) -> Option<RawPacket<&[u8]>> {
+        if self.is_complete() {
+            Some(RawPacket {
+                packet_type: PacketType::try_from(self.frame[0]).expect("checked when pushed"),
+                buf: &self.frame[1..],
+            })
+        } else {
+            None
+        }
+    }
+    /// Push bytes into the decoder. Stops at the end of a packet so returns how many bytes were
+    /// used. The rest should be pushed after the packet is handled. On error the partial packet
+    /// is dropped.
+    pub fn push(&mut self, mut bytes: &[u8]) -> Result<usize, StreamError> {
+        if self.is_complete() {
+            self.reset();
+        }
+        let start_len = bytes.len();
+        while self.needed > 0 && !bytes.is_empty() {
+            let take = self.needed.min(bytes.len());
+            self.frame.extend_from_slice(&bytes[..take]);
+            bytes = &bytes[take..];
+            self.needed -= take;
+            if self.needed > 0 {
+                break;
+            }
+            if let Err(e) = self.advance() {
+                self.reset();
+                anomaly::report(self.on_anomaly, Anomaly::Resync(e));
+                return Err(e);
+            }
+        }
+        Ok(start_len - bytes.len())
+    }
+    /// Move on to the next part of the frame once the current one is filled.
+    fn advance(&mut self) -> Result<(), StreamError> {
+        let packet_type = PacketType::try_from(self.frame[0])
+            .map_err(|_| StreamError::UnsupportedPacketType(self.frame[0]))?;
+        let header_len = header_len(packet_type)?;
+        if self.frame.len() == 1 {
+            self.needed = header_len;
+        } else if !self.has_header {
+            let payload_len = payload_len(packet_type, &self.frame[1..])?;
+            if header_len + payload_len > self.max_packet_len {
+                return Err(StreamError::BadPacketCode);
+            }
+            self.has_header = true;
+            self.needed = payload_len;
+        }
+        Ok(())
+    }
+}
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder::new(MAX_FRAME_SIZE)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_decoder() {
+        // Command Complete for Reset then a split ACL packet with no data then a 2 byte one.
+        let stream = [
+            0x04, 0x0E, 0x04, 0x01, 0x03, 0x0C, 0x00, 0x02, 0x40, 0x00, 0x00, 0x00, 0x02, 0x40,
+            0x20, 0x02, 0x00, 0xAA, 0xBB,
+        ];
+        let mut decoder = Decoder::default();
+        let mut packets = Vec::new();
+        for chunk in stream.chunks(5) {
+            let mut chunk = chunk;
+            while !chunk.is_empty() {
+                let used = decoder.push(chunk).unwrap();
+                chunk = &chunk[used..];
+                if let Some(packet) = decoder.packet() {
+                    packets.push(packet.clone_buf::<Vec<u8>>());
+                }
+            }
+        }
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0].packet_type, PacketType::Event);
+        assert_eq!(packets[0].buf, &stream[1..7]);
+        assert_eq!(packets[1].buf, &[0x40, 0x00, 0x00, 0x00]);
+        assert_eq!(packets[2].buf, &stream[13..]);
+        assert!(decode_h4(&stream[..7]).is_ok());
+        assert!(decode_h4(&stream[..6]).is_err());
+        assert!(decode_h4(&[]).is_err());
+        assert_eq!(
+            Decoder::default().push(&[0x09]),
+            Err(StreamError::UnsupportedPacketType(0x09))
+        );
+        let mut small = Decoder::new(8);
+        assert!(small.push(&[0x02, 0x40, 0x00, 0x10, 0x00]).is_err());
+    }
+}
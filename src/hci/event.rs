@@ -74,6 +74,8 @@ pub enum EventCode {
     AMPTestEnd = 0x4A,
     AMPReceiverReport = 0x4B,
     LEMeta = 0x3E,
+    /// Vendor specific events. The parameters are up to the controller vendor.
+    VendorSpecific = 0xFF,
 }
 impl From<EventCode> for u8 {
     fn from(code: EventCode) -> Self {
@@ -154,6 +156,7 @@ impl TryFrom<u8> for EventCode {
             0x4A => Ok(EventCode::AMPTestEnd),
             0x4B => Ok(EventCode::AMPReceiverReport),
             0x3E => Ok(EventCode::LEMeta),
+            0xFF => Ok(EventCode::VendorSpecific),
             _ => Err(ConversionError(())),
         }
     }
@@ -238,6 +241,42 @@ impl<Storage: AsRef<[u8]>> EventPacket<Storage> {
     pub fn event_code(&self) -> EventCode {
         self.event_code
     }
+    /// Opcode of the command this event returns for (if it's a Command Complete or Command
+    /// Status event).
+    pub fn return_opcode(&self) -> Option<Opcode> {
+        match self.event_code {
+            EventCode::CommandComplete => {
+                CommandComplete::<StatusReturn>::guess_command_opcode(self.parameters.as_ref())
+            }
+            EventCode::CommandStatus => {
+                CommandStatus::guess_command_opcode(self.parameters.as_ref())
+            }
+            _ => None,
+        }
+    }
+    /// `Num_HCI_Command_Packets` of a Command Complete or Command Status event. The number of
+    /// commands the controller can accept right now.
+    pub fn return_num_command_packets(&self) -> Option<u8> {
+        let index = match self.event_code {
+            EventCode::CommandComplete => 0,
+            EventCode::CommandStatus => 1,
+            _ => return None,
+        };
+        self.parameters.as_ref().get(index).copied()
+    }
+    /// Status of a Command Complete or Command Status event. Command Complete return parameters
+    /// start with the status for almost every command.
+    pub fn return_status(&self) -> Option<ErrorCode> {
+        let index = match self.event_code {
+            EventCode::CommandComplete => COMMAND_COMPLETE_HEADER_LEN,
+            EventCode::CommandStatus => 0,
+            _ => return None,
+        };
+        self.parameters
+            .as_ref()
+            .get(index)
+            .and_then(|b| ErrorCode::try_from(*b).ok())
+    }
     pub fn parameters(&self) -> &[u8] {
         self.parameters.as_ref()
     }
@@ -312,6 +351,7 @@ pub trait ReturnParameters {
     where
         Self: Sized;
 }
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
 pub struct StatusReturn {
     pub status: ErrorCode,
 }
@@ -380,12 +420,17 @@ impl<Params: ReturnParameters> Event for CommandComplete<Params> {
 }
 pub trait ReturnEvent: Event {
     fn command_opcode(&self) -> Opcode;
+    /// `Num_HCI_Command_Packets`. The number of commands the controller can accept right now.
+    fn num_command_packets(&self) -> u8;
     fn guess_command_opcode(buf: &[u8]) -> Option<Opcode>;
 }
 impl<Params: ReturnParameters> ReturnEvent for CommandComplete<Params> {
     fn command_opcode(&self) -> Opcode {
         self.opcode
     }
+    fn num_command_packets(&self) -> u8 {
+        self.num_command_packets
+    }
     fn guess_command_opcode(buf: &[u8]) -> Option<Opcode> {
         if buf.len() >= 3 {
             Opcode::unpack(&buf[1..3]).ok()
@@ -436,6 +481,9 @@ impl ReturnEvent for CommandStatus {
     fn command_opcode(&self) -> Opcode {
         self.opcode
     }
+    fn num_command_packets(&self) -> u8 {
+        self.num_command_packets
+    }
     fn guess_command_opcode(buf: &[u8]) -> Option<Opcode> {
         if buf.len() >= COMMAND_STATUS_LEN {
             Opcode::unpack(&buf[2..4]).ok()
@@ -0,0 +1,711 @@
+//! Host to controller ACL data flow control. Controllers either count free buffers in packets
+//! (`Number Of Completed Packets` events, the default) or in fixed size data blocks (`Number Of
+//! Completed Data Blocks` events, picked with [`WriteFlowControlMode`]). [`ACLCredits`] tracks
+//! both so the ACL sender only has to ask if a packet fits. [`ControllerBufferInfo`] collects the
+//! buffer sizes the credits start from.
+use crate::hci::baseband::ControllerBasebandOpcode;
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, Event, EventCode, ReturnParameters, StatusReturn};
+use crate::hci::informational::{CoreVersion, InformationalOpcode};
+use crate::hci::le::connection::{BufferSizeV1, BufferSizeV2};
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::connection::ConnectionHandle;
+use crate::{ConversionError, PackError};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum FlowControlMode {
+    PacketBased = 0x00,
+    DataBlockBased = 0x01,
+}
+impl FlowControlMode {
+    pub const BYTE_LEN: usize = 1;
+}
+impl Default for FlowControlMode {
+    fn default() -> Self {
+        FlowControlMode::PacketBased
+    }
+}
+impl From<FlowControlMode> for u8 {
+    fn from(mode: FlowControlMode) -> Self {
+        mode as u8
+    }
+}
+impl TryFrom<u8> for FlowControlMode {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(FlowControlMode::PacketBased),
+            0x01 => Ok(FlowControlMode::DataBlockBased),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// `HCI_Read_Flow_Control_Mode`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadFlowControlMode;
+impl ReadFlowControlMode {
+    pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::ReadFlowControlMode;
+}
+impl Command for ReadFlowControlMode {
+    type Return = CommandComplete<FlowControlModeReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V3_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadFlowControlMode)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct FlowControlModeReturn {
+    pub status: ErrorCode,
+    pub mode: FlowControlMode,
+}
+impl FlowControlModeReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + FlowControlMode::BYTE_LEN;
+}
+impl ReturnParameters for FlowControlModeReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1] = self.mode.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(FlowControlModeReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            mode: FlowControlMode::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?,
+        })
+    }
+}
+/// `HCI_Write_Flow_Control_Mode`. Only allowed while there are no connections.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct WriteFlowControlMode {
+    pub mode: FlowControlMode,
+}
+impl WriteFlowControlMode {
+    pub const OPCODE: ControllerBasebandOpcode = ControllerBasebandOpcode::WriteFlowControlMode;
+}
+impl Command for WriteFlowControlMode {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V3_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        FlowControlMode::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(FlowControlMode::BYTE_LEN, buf)?;
+        buf[0] = self.mode.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(FlowControlMode::BYTE_LEN, buf)?;
+        Ok(WriteFlowControlMode {
+            mode: FlowControlMode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+        })
+    }
+}
+/// `HCI_Read_Data_Block_Size`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadDataBlockSize;
+impl ReadDataBlockSize {
+    pub const OPCODE: InformationalOpcode = InformationalOpcode::ReadDataBlockSize;
+}
+impl Command for ReadDataBlockSize {
+    type Return = CommandComplete<DataBlockSize>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V3_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadDataBlockSize)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct DataBlockSize {
+    pub status: ErrorCode,
+    /// Largest ACL Data packet payload the controller accepts.
+    pub max_acl_data_packet_len: u16,
+    pub data_block_len: u16,
+    pub total_num_data_blocks: u16,
+}
+impl DataBlockSize {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + 6;
+}
+impl ReturnParameters for DataBlockSize {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&self.max_acl_data_packet_len.to_le_bytes());
+        buf[3..5].copy_from_slice(&self.data_block_len.to_le_bytes());
+        buf[5..7].copy_from_slice(&self.total_num_data_blocks.to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(DataBlockSize {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            max_acl_data_packet_len: u16::from_le_bytes([buf[1], buf[2]]),
+            data_block_len: u16::from_le_bytes([buf[3], buf[4]]),
+            total_num_data_blocks: u16::from_le_bytes([buf[5], buf[6]]),
+        })
+    }
+}
+/// `HCI_Read_Buffer_Size`. The BR/EDR ACL and synchronous (SCO/eSCO) buffers. LE shares the ACL
+/// buffers when `HCI_LE_Read_Buffer_Size` reports none of its own.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadBufferSize;
+impl ReadBufferSize {
+    pub const OPCODE: InformationalOpcode = InformationalOpcode::ReadBufferSize;
+}
+impl Command for ReadBufferSize {
+    type Return = CommandComplete<BufferSize>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadBufferSize)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BufferSize {
+    pub status: ErrorCode,
+    pub acl_data_packet_len: u16,
+    pub synchronous_data_packet_len: u8,
+    pub total_num_acl_data_packets: u16,
+    pub total_num_synchronous_data_packets: u16,
+}
+impl BufferSize {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + 7;
+}
+impl ReturnParameters for BufferSize {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&self.acl_data_packet_len.to_le_bytes());
+        buf[3] = self.synchronous_data_packet_len;
+        buf[4..6].copy_from_slice(&self.total_num_acl_data_packets.to_le_bytes());
+        buf[6..8].copy_from_slice(&self.total_num_synchronous_data_packets.to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(BufferSize {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            acl_data_packet_len: u16::from_le_bytes([buf[1], buf[2]]),
+            synchronous_data_packet_len: buf[3],
+            total_num_acl_data_packets: u16::from_le_bytes([buf[4], buf[5]]),
+            total_num_synchronous_data_packets: u16::from_le_bytes([buf[6], buf[7]]),
+        })
+    }
+}
+/// One pool of controller data buffers.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BufferPool {
+    /// Largest data packet payload one buffer holds.
+    pub packet_len: u16,
+    pub total_packets: u16,
+}
+impl BufferPool {
+    /// Returns `None` if either is `0` (the controller has no such buffers).
+    pub fn new_checked(packet_len: u16, total_packets: u16) -> Option<BufferPool> {
+        if packet_len == 0 || total_packets == 0 {
+            None
+        } else {
+            Some(BufferPool {
+                packet_len,
+                total_packets,
+            })
+        }
+    }
+}
+/// Every data buffer pool of the controller, from the Read Buffer Size commands. `None` if the
+/// controller has no (separate) buffers of the kind or they haven't been read.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ControllerBufferInfo {
+    /// BR/EDR ACL buffers (`HCI_Read_Buffer_Size`).
+    pub acl: Option<BufferPool>,
+    /// SCO/eSCO buffers (`HCI_Read_Buffer_Size`).
+    pub synchronous: Option<BufferPool>,
+    /// LE ACL buffers (`HCI_LE_Read_Buffer_Size`). Shares [`ControllerBufferInfo::acl`] if `None`.
+    pub le_acl: Option<BufferPool>,
+    /// ISO buffers (`HCI_LE_Read_Buffer_Size` V2).
+    pub iso: Option<BufferPool>,
+}
+impl ControllerBufferInfo {
+    pub const NONE: ControllerBufferInfo = ControllerBufferInfo {
+        acl: None,
+        synchronous: None,
+        le_acl: None,
+        iso: None,
+    };
+    pub fn set_buffer_size(&mut self, size: &BufferSize) {
+        self.acl =
+            BufferPool::new_checked(size.acl_data_packet_len, size.total_num_acl_data_packets);
+        self.synchronous = BufferPool::new_checked(
+            size.synchronous_data_packet_len.into(),
+            size.total_num_synchronous_data_packets,
+        );
+    }
+    pub fn set_le_buffer_size_v1(&mut self, size: &BufferSizeV1) {
+        self.le_acl = BufferPool::new_checked(
+            size.le_acl_data_packet_len,
+            size.total_num_le_acl_data_packets.into(),
+        );
+    }
+    pub fn set_le_buffer_size_v2(&mut self, size: &BufferSizeV2) {
+        self.le_acl = BufferPool::new_checked(
+            size.le_acl_data_packet_len,
+            size.total_num_le_acl_data_packets.into(),
+        );
+        self.iso = BufferPool::new_checked(
+            size.iso_data_packet_len,
+            size.total_num_iso_data_packets.into(),
+        );
+    }
+    /// Buffers LE ACL data goes into. The LE ones or, if there aren't any, the shared BR/EDR
+    /// ones.
+    pub fn le_acl_buffers(&self) -> Option<BufferPool> {
+        self.le_acl.or(self.acl)
+    }
+    /// Returns if LE ACL data has to share buffers (and credits) with BR/EDR.
+    pub fn is_le_acl_shared(&self) -> bool {
+        self.le_acl.is_none() && self.acl.is_some()
+    }
+}
+/// Packets sent on `handle` that the controller has finished with.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CompletedPackets {
+    pub handle: ConnectionHandle,
+    pub num_completed_packets: u16,
+}
+impl CompletedPackets {
+    pub const BYTE_LEN: usize = 4;
+}
+/// `HCI_Number_Of_Completed_Packets` event. Packet-based flow control.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct NumberOfCompletedPackets {
+    pub handles: Vec<CompletedPackets>,
+}
+impl Event for NumberOfCompletedPackets {
+    const EVENT_CODE: EventCode = EventCode::NumberOfCompletedPackets;
+
+    fn event_byte_len(&self) -> usize {
+        1 + self.handles.len() * CompletedPackets::BYTE_LEN
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.is_empty() {
+            return Err(PackError::BadLength {
+                expected: 1,
+                got: 0,
+            });
+        }
+        let count = usize::from(buf[0]);
+        PackError::expect_length(1 + count * CompletedPackets::BYTE_LEN, buf)?;
+        let mut handles = Vec::with_capacity(count);
+        for (i, b) in buf[1..]
+            .chunks_exact(CompletedPackets::BYTE_LEN)
+            .enumerate()
+        {
+            handles.push(CompletedPackets {
+                handle: ConnectionHandle::unpack_from(b, 0)
+                    .map_err(|_| PackError::bad_index(1 + i * CompletedPackets::BYTE_LEN))?,
+                num_completed_packets: u16::from_le_bytes([b[2], b[3]]),
+            });
+        }
+        Ok(NumberOfCompletedPackets { handles })
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.event_byte_len(), buf)?;
+        buf[0] = u8::try_from(self.handles.len()).map_err(|_| PackError::InvalidFields)?;
+        for (h, b) in self
+            .handles
+            .iter()
+            .zip(buf[1..].chunks_exact_mut(CompletedPackets::BYTE_LEN))
+        {
+            b[..2].copy_from_slice(&u16::from(h.handle).to_le_bytes());
+            b[2..].copy_from_slice(&h.num_completed_packets.to_le_bytes());
+        }
+        Ok(())
+    }
+}
+/// Packets and data blocks sent on `handle` that the controller has finished with.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CompletedDataBlocks {
+    pub handle: ConnectionHandle,
+    pub num_completed_packets: u16,
+    pub num_completed_blocks: u16,
+}
+impl CompletedDataBlocks {
+    pub const BYTE_LEN: usize = 6;
+}
+/// `HCI_Number_Of_Completed_Data_Blocks` event. Data block-based flow control.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct NumberOfCompletedDataBlocks {
+    /// Size of the controller's block pool now. `0` means it may have changed and should be read
+    /// again with [`ReadDataBlockSize`].
+    pub total_num_data_blocks: u16,
+    pub handles: Vec<CompletedDataBlocks>,
+}
+impl NumberOfCompletedDataBlocks {
+    pub const HEADER_LEN: usize = 3;
+}
+impl Event for NumberOfCompletedDataBlocks {
+    const EVENT_CODE: EventCode = EventCode::NumberOfCompletedDataBlocks;
+
+    fn event_byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.handles.len() * CompletedDataBlocks::BYTE_LEN
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        let count = usize::from(buf[2]);
+        PackError::expect_length(
+            Self::HEADER_LEN + count * CompletedDataBlocks::BYTE_LEN,
+            buf,
+        )?;
+        let mut handles = Vec::with_capacity(count);
+        for (i, b) in buf[Self::HEADER_LEN..]
+            .chunks_exact(CompletedDataBlocks::BYTE_LEN)
+            .enumerate()
+        {
+            handles.push(CompletedDataBlocks {
+                handle: ConnectionHandle::unpack_from(b, 0).map_err(|_| {
+                    PackError::bad_index(Self::HEADER_LEN + i * CompletedDataBlocks::BYTE_LEN)
+                })?,
+                num_completed_packets: u16::from_le_bytes([b[2], b[3]]),
+                num_completed_blocks: u16::from_le_bytes([b[4], b[5]]),
+            });
+        }
+        Ok(NumberOfCompletedDataBlocks {
+            total_num_data_blocks: u16::from_le_bytes([buf[0], buf[1]]),
+            handles,
+        })
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.event_byte_len(), buf)?;
+        buf[..2].copy_from_slice(&self.total_num_data_blocks.to_le_bytes());
+        buf[2] = u8::try_from(self.handles.len()).map_err(|_| PackError::InvalidFields)?;
+        for (h, b) in self
+            .handles
+            .iter()
+            .zip(buf[Self::HEADER_LEN..].chunks_exact_mut(CompletedDataBlocks::BYTE_LEN))
+        {
+            b[..2].copy_from_slice(&u16::from(h.handle).to_le_bytes());
+            b[2..4].copy_from_slice(&h.num_completed_packets.to_le_bytes());
+            b[4..].copy_from_slice(&h.num_completed_blocks.to_le_bytes());
+        }
+        Ok(())
+    }
+}
+/// Free controller ACL buffers, counted in packets or data blocks depending on the
+/// [`FlowControlMode`]. Take credits with [`ACLCredits::try_send`] before writing each ACL Data
+/// packet and give them back from the completed packets/blocks events.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ACLCredits {
+    mode: FlowControlMode,
+    /// Only used in [`FlowControlMode::DataBlockBased`].
+    block_len: u16,
+    total: u16,
+    available: u16,
+    /// Credits in use by each connection, returned if it disconnects.
+    in_use: Vec<(ConnectionHandle, u16)>,
+    needs_block_size: bool,
+}
+impl ACLCredits {
+    /// `total_packets` is the controller's `Total_Num_ACL_Data_Packets`.
+    pub fn packet_based(total_packets: u16) -> ACLCredits {
+        ACLCredits {
+            mode: FlowControlMode::PacketBased,
+            block_len: 0,
+            total: total_packets,
+            available: total_packets,
+            in_use: Vec::new(),
+            needs_block_size: false,
+        }
+    }
+    /// Returns `None` if the block length is `0`.
+    pub fn block_based(size: DataBlockSize) -> Option<ACLCredits> {
+        if size.data_block_len == 0 {
+            None
+        } else {
+            Some(ACLCredits {
+                mode: FlowControlMode::DataBlockBased,
+                block_len: size.data_block_len,
+                total: size.total_num_data_blocks,
+                available: size.total_num_data_blocks,
+                in_use: Vec::new(),
+                needs_block_size: false,
+            })
+        }
+    }
+    pub fn mode(&self) -> FlowControlMode {
+        self.mode
+    }
+    pub fn total(&self) -> u16 {
+        self.total
+    }
+    pub fn available(&self) -> u16 {
+        self.available
+    }
+    /// The controller said its block pool may have changed. Read it again with
+    /// [`ReadDataBlockSize`] and pass it to [`ACLCredits::update_block_size`].
+    pub fn needs_block_size(&self) -> bool {
+        self.needs_block_size
+    }
+    /// Credits needed to send an ACL Data packet with `data_len` bytes of data.
+    pub fn cost(&self, data_len: usize) -> u16 {
+        match self.mode {
+            FlowControlMode::PacketBased => 1,
+            FlowControlMode::DataBlockBased => {
+                let block_len = usize::from(self.block_len);
+                u16::try_from(data_len.max(1).div_ceil(block_len)).unwrap_or(u16::MAX)
+            }
+        }
+    }
+    /// Take the credits to send `data_len` bytes on `handle`. Returns `false` (and takes nothing)
+    /// if there aren't enough free.
+    pub fn try_send(&mut self, handle: ConnectionHandle, data_len: usize) -> bool {
+        let cost = self.cost(data_len);
+        if cost > self.available {
+            return false;
+        }
+        self.available -= cost;
+        match self.in_use.iter_mut().find(|(h, _)| *h == handle) {
+            Some((_, used)) => *used = used.saturating_add(cost),
+            None => self.in_use.push((handle, cost)),
+        }
+        true
+    }
+    fn release(&mut self, handle: ConnectionHandle, credits: u16) {
+        // Ignore credits for handles with nothing in flight (sent before a disconnect, etc).
+        let index = match self.in_use.iter().position(|(h, _)| *h == handle) {
+            Some(index) => index,
+            None => return,
+        };
+        let used = &mut self.in_use[index].1;
+        let credits = credits.min(*used);
+        *used -= credits;
+        if *used == 0 {
+            self.in_use.swap_remove(index);
+        }
+        self.available = self.available.saturating_add(credits).min(self.total);
+    }
+    /// Give back packet credits. Ignored in [`FlowControlMode::DataBlockBased`].
+    pub fn completed_packets(&mut self, event: &NumberOfCompletedPackets) {
+        if self.mode != FlowControlMode::PacketBased {
+            return;
+        }
+        for h in &event.handles {
+            self.release(h.handle, h.num_completed_packets);
+        }
+    }
+    /// Give back block credits and pick up a resized block pool. Ignored in
+    /// [`FlowControlMode::PacketBased`].
+    pub fn completed_data_blocks(&mut self, event: &NumberOfCompletedDataBlocks) {
+        if self.mode != FlowControlMode::DataBlockBased {
+            return;
+        }
+        for h in &event.handles {
+            self.release(h.handle, h.num_completed_blocks);
+        }
+        if event.total_num_data_blocks == 0 {
+            self.needs_block_size = true;
+        } else {
+            self.resize(event.total_num_data_blocks);
+        }
+    }
+    /// New block pool from [`ReadDataBlockSize`].
+    pub fn update_block_size(&mut self, size: DataBlockSize) {
+        if self.mode == FlowControlMode::DataBlockBased && size.data_block_len != 0 {
+            self.block_len = size.data_block_len;
+            self.resize(size.total_num_data_blocks);
+            self.needs_block_size = false;
+        }
+    }
+    fn resize(&mut self, total: u16) {
+        let in_use = self.total - self.available;
+        self.total = total;
+        self.available = total.saturating_sub(in_use);
+    }
+    /// The controller frees the buffers of a connection when it disconnects.
+    pub fn disconnected(&mut self, handle: ConnectionHandle) {
+        if let Some(index) = self.in_use.iter().position(|(h, _)| *h == handle) {
+            let (_, used) = self.in_use.swap_remove(index);
+            self.available = self.available.saturating_add(used).min(self.total);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_block_credits() {
+        let mut credits = ACLCredits::block_based(DataBlockSize {
+            status: ErrorCode::Ok,
+            max_acl_data_packet_len: 1021,
+            data_block_len: 100,
+            total_num_data_blocks: 5,
+        })
+        .unwrap();
+        let handle = ConnectionHandle::new(0x0001);
+        assert_eq!(credits.cost(250), 3);
+        assert!(credits.try_send(handle, 250));
+        assert!(!credits.try_send(handle, 250));
+        assert!(credits.try_send(handle, 200));
+        assert_eq!(credits.available(), 0);
+
+        let event = NumberOfCompletedDataBlocks {
+            total_num_data_blocks: 8,
+            handles: alloc::vec![CompletedDataBlocks {
+                handle,
+                num_completed_packets: 1,
+                num_completed_blocks: 3,
+            }],
+        };
+        let packet = event.event_pack_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(
+            packet.parameters.as_ref(),
+            &[8, 0, 1, 0x01, 0x00, 1, 0, 3, 0]
+        );
+        let event = NumberOfCompletedDataBlocks::unpack_event_packet(&packet).unwrap();
+        credits.completed_data_blocks(&event);
+        // 3 blocks back and 3 more in the pool.
+        assert_eq!(credits.available(), 6);
+        credits.completed_data_blocks(&NumberOfCompletedDataBlocks::default());
+        assert!(credits.needs_block_size());
+        credits.disconnected(handle);
+        assert_eq!(credits.available(), credits.total());
+
+        let mut credits = ACLCredits::packet_based(2);
+        assert!(credits.try_send(handle, 1000));
+        credits.completed_packets(&NumberOfCompletedPackets {
+            handles: alloc::vec![CompletedPackets {
+                handle,
+                num_completed_packets: 4,
+            }],
+        });
+        assert_eq!(credits.available(), 2);
+    }
+    #[test]
+    fn test_buffer_info() {
+        let size =
+            BufferSize::unpack_from(&[0x00, 0xFD, 0x03, 0x40, 0x08, 0x00, 0x00, 0x00]).unwrap();
+        let mut info = ControllerBufferInfo::NONE;
+        info.set_buffer_size(&size);
+        assert_eq!(info.acl, BufferPool::new_checked(1021, 8));
+        assert!(info.synchronous.is_none());
+        // No separate LE buffers.
+        info.set_le_buffer_size_v2(&BufferSizeV2 {
+            status: ErrorCode::Ok,
+            le_acl_data_packet_len: 0,
+            total_num_le_acl_data_packets: 0,
+            iso_data_packet_len: 251,
+            total_num_iso_data_packets: 4,
+        });
+        assert!(info.is_le_acl_shared());
+        assert_eq!(info.le_acl_buffers(), info.acl);
+        assert_eq!(info.iso, BufferPool::new_checked(251, 4));
+        info.set_le_buffer_size_v1(&BufferSizeV1 {
+            status: ErrorCode::Ok,
+            le_acl_data_packet_len: 251,
+            total_num_le_acl_data_packets: 12,
+        });
+        assert_eq!(info.le_acl_buffers(), BufferPool::new_checked(251, 12));
+    }
+}
@@ -0,0 +1,238 @@
+//! Linux adapter hotplug. [`HotplugWatcher`] listens for kernel uevents (the same netlink
+//! messages udev gets) and reports `hciN` controllers appearing and disappearing, so a USB dongle
+//! being unplugged and plugged back in doesn't need the application restarted. [`AdapterSet`]
+//! keeps one open adapter per [`AdapterID`] up to date with those events.
+use crate::error::IOError;
+use crate::hci::bluez_socket::{handle_libc_error, AdapterID};
+use alloc::vec::Vec;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::net::UnixStream;
+
+/// Where the kernel lists the Bluetooth controllers.
+pub const SYSFS_BLUETOOTH: &str = "/sys/class/bluetooth";
+/// Kernel uevent multicast group (udev rebroadcasts on group 2).
+const UEVENT_KERNEL_GROUP: u32 = 1;
+/// Big enough for any uevent message.
+pub const MAX_UEVENT_LEN: usize = 8192;
+#[derive(Copy, Clone, PartialOrd, PartialEq, Ord, Eq, Hash, Debug)]
+pub enum HotplugEvent {
+    AdapterAdded(AdapterID),
+    AdapterRemoved(AdapterID),
+}
+impl HotplugEvent {
+    pub fn adapter_id(self) -> AdapterID {
+        match self {
+            HotplugEvent::AdapterAdded(id) | HotplugEvent::AdapterRemoved(id) => id,
+        }
+    }
+    /// Parse a kernel uevent (`action@devpath` followed by NUL separated `KEY=value` pairs).
+    /// Returns `None` for anything other than a Bluetooth controller being added or removed.
+    pub fn from_uevent(message: &[u8]) -> Option<HotplugEvent> {
+        let mut action = None;
+        let mut dev_path = None;
+        let mut is_bluetooth = false;
+        let mut is_host = false;
+        for field in message.split(|b| *b == 0) {
+            let field = core::str::from_utf8(field).ok()?;
+            let mut parts = field.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("ACTION"), Some(value)) => action = Some(value),
+                (Some("DEVPATH"), Some(value)) => dev_path = Some(value),
+                (Some("SUBSYSTEM"), Some(value)) => is_bluetooth = value == "bluetooth",
+                // Connections (`hci0:12`) are `link`s in the same subsystem.
+                (Some("DEVTYPE"), Some(value)) => is_host = value == "host",
+                _ => (),
+            }
+        }
+        if !is_bluetooth || !is_host {
+            return None;
+        }
+        let id = parse_adapter_name(dev_path?.rsplit('/').next()?)?;
+        match action? {
+            "add" => Some(HotplugEvent::AdapterAdded(id)),
+            "remove" => Some(HotplugEvent::AdapterRemoved(id)),
+            _ => None,
+        }
+    }
+}
+/// `hci3` -> `AdapterID(3)`.
+pub fn parse_adapter_name(name: &str) -> Option<AdapterID> {
+    let index = name.strip_prefix("hci")?;
+    if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    index.parse().ok().map(AdapterID)
+}
+/// Controllers currently registered with the kernel (powered or not).
+pub fn present_adapters() -> Result<Vec<AdapterID>, IOError> {
+    let mut out = Vec::new();
+    for entry in std::fs::read_dir(SYSFS_BLUETOOTH)? {
+        let entry = entry?;
+        if let Some(id) = entry.file_name().to_str().and_then(parse_adapter_name) {
+            out.push(id);
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct SockaddrNetlink {
+    family: libc::sa_family_t,
+    pad: u16,
+    pid: u32,
+    groups: u32,
+}
+/// Netlink socket subscribed to kernel uevents.
+#[derive(Debug)]
+pub struct HotplugWatcher(tokio::net::UnixStream);
+impl HotplugWatcher {
+    /// Must be called inside a tokio runtime (the socket is registered with its reactor).
+    pub fn new() -> Result<HotplugWatcher, IOError> {
+        let fd = handle_libc_error(unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_KOBJECT_UEVENT,
+            )
+        })?;
+        // Wrap it straight away so the fd is closed if `bind` fails.
+        let stream = unsafe { UnixStream::from_raw_fd(fd) };
+        let address = SockaddrNetlink {
+            family: libc::AF_NETLINK as u16,
+            pad: 0,
+            pid: 0,
+            groups: UEVENT_KERNEL_GROUP,
+        };
+        handle_libc_error(unsafe {
+            libc::bind(
+                fd,
+                &address as *const SockaddrNetlink as *const libc::sockaddr,
+                std::mem::size_of::<SockaddrNetlink>() as u32,
+            )
+        })?;
+        Ok(HotplugWatcher(tokio::net::UnixStream::from_std(stream)?))
+    }
+    /// Wait for the next controller to be added or removed. Other uevents are skipped.
+    pub async fn next_event(&mut self) -> Result<HotplugEvent, IOError> {
+        use tokio::io::AsyncReadExt;
+        let mut buf = [0_u8; MAX_UEVENT_LEN];
+        loop {
+            // Each read returns exactly one datagram.
+            let len = self.0.read(&mut buf[..]).await?;
+            if len == 0 {
+                return Err(IOError::Closed);
+            }
+            if let Some(event) = HotplugEvent::from_uevent(&buf[..len]) {
+                return Ok(event);
+            }
+        }
+    }
+}
+/// Open adapters keyed by [`AdapterID`]. Feed it [`HotplugEvent`]s and it opens adapters as they
+/// appear (with the `open` function passed in) and drops them when they're unplugged.
+pub struct AdapterSet<T> {
+    adapters: Vec<(AdapterID, T)>,
+}
+impl<T> AdapterSet<T> {
+    pub fn new() -> Self {
+        Self {
+            adapters: Vec::new(),
+        }
+    }
+    /// Open every adapter that's already plugged in. Adapters that fail to open are returned with
+    /// their error instead of failing the whole set.
+    pub fn open_present(
+        &mut self,
+        mut open: impl FnMut(AdapterID) -> Result<T, IOError>,
+    ) -> Result<Vec<(AdapterID, IOError)>, IOError> {
+        let mut failed = Vec::new();
+        for id in present_adapters()? {
+            if let Err(e) = self.add_with(id, &mut open) {
+                failed.push((id, e));
+            }
+        }
+        Ok(failed)
+    }
+    fn add_with(
+        &mut self,
+        id: AdapterID,
+        open: &mut impl FnMut(AdapterID) -> Result<T, IOError>,
+    ) -> Result<(), IOError> {
+        if self.get(id).is_none() {
+            let adapter = open(id)?;
+            self.adapters.push((id, adapter));
+        }
+        Ok(())
+    }
+    /// Open the adapter on [`HotplugEvent::AdapterAdded`] (if it isn't already) or remove and
+    /// return it on [`HotplugEvent::AdapterRemoved`].
+    pub fn handle_event(
+        &mut self,
+        event: HotplugEvent,
+        mut open: impl FnMut(AdapterID) -> Result<T, IOError>,
+    ) -> Result<Option<T>, IOError> {
+        match event {
+            HotplugEvent::AdapterAdded(id) => {
+                self.add_with(id, &mut open)?;
+                Ok(None)
+            }
+            HotplugEvent::AdapterRemoved(id) => Ok(self.remove(id)),
+        }
+    }
+    pub fn remove(&mut self, id: AdapterID) -> Option<T> {
+        let index = self.adapters.iter().position(|(i, _)| *i == id)?;
+        Some(self.adapters.remove(index).1)
+    }
+    pub fn get(&self, id: AdapterID) -> Option<&T> {
+        self.adapters.iter().find(|(i, _)| *i == id).map(|(_, a)| a)
+    }
+    pub fn get_mut(&mut self, id: AdapterID) -> Option<&mut T> {
+        self.adapters
+            .iter_mut()
+            .find(|(i, _)| *i == id)
+            .map(|(_, a)| a)
+    }
+    pub fn ids(&self) -> impl Iterator<Item = AdapterID> + '_ {
+        self.adapters.iter().map(|(id, _)| *id)
+    }
+    pub fn len(&self) -> usize {
+        self.adapters.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.adapters.is_empty()
+    }
+}
+impl<T> Default for AdapterSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_uevent() {
+        let added = b"add@/devices/pci0000:00/0000:00:14.0/usb1/1-2/1-2:1.0/bluetooth/hci1\0\
+            ACTION=add\0DEVPATH=/devices/pci0000:00/0000:00:14.0/usb1/1-2/1-2:1.0/bluetooth/hci1\0\
+            SUBSYSTEM=bluetooth\0DEVTYPE=host\0SEQNUM=4242\0";
+        assert_eq!(
+            HotplugEvent::from_uevent(&added[..]),
+            Some(HotplugEvent::AdapterAdded(AdapterID(1)))
+        );
+        let link = b"remove@/devices/virtual/bluetooth/hci0/hci0:12\0ACTION=remove\0\
+            DEVPATH=/devices/virtual/bluetooth/hci0/hci0:12\0SUBSYSTEM=bluetooth\0DEVTYPE=link\0";
+        assert_eq!(HotplugEvent::from_uevent(&link[..]), None);
+        assert_eq!(parse_adapter_name("hci"), None);
+        let mut set = AdapterSet::new();
+        set.handle_event(HotplugEvent::AdapterAdded(AdapterID(1)), |id| Ok(id.0))
+            .unwrap();
+        assert_eq!(
+            set.handle_event(HotplugEvent::AdapterRemoved(AdapterID(1)), |_| {
+                Err(IOError::NotFound)
+            }),
+            Ok(Some(1))
+        );
+        assert!(set.is_empty());
+    }
+}
@@ -0,0 +1,636 @@
+//! HCI Informational Parameters commands (`OGF::InformationalParameters`). Fixed controller
+//! information like the supported Bluetooth Core version, commands ([`SupportedCommands`]) and
+//! LMP features ([`LMPFeatures`]).
+use crate::hci::baseband::ControllerBasebandOpcode;
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, ReturnParameters};
+use crate::hci::le::LEControllerOpcode;
+use crate::hci::link_control::{AddressReturn, LinkControlOpcode};
+use crate::hci::status::StatusOpcode;
+use crate::hci::{ErrorCode, Opcode, OCF, OGF};
+use crate::{ConversionError, PackError};
+use core::convert::{TryFrom, TryInto};
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u16)]
+pub enum InformationalOpcode {
+    ReadLocalVersionInformation = 0x0001,
+    ReadLocalSupportedCommands = 0x0002,
+    ReadLocalSupportedFeatures = 0x0003,
+    ReadLocalExtendedFeatures = 0x0004,
+    ReadBufferSize = 0x0005,
+    ReadBDADDR = 0x0009,
+    ReadDataBlockSize = 0x000A,
+    ReadLocalSupportedCodecs = 0x000B,
+}
+impl From<InformationalOpcode> for OCF {
+    fn from(opcode: InformationalOpcode) -> Self {
+        OCF::new(opcode as u16)
+    }
+}
+impl From<InformationalOpcode> for Opcode {
+    fn from(opcode: InformationalOpcode) -> Self {
+        Opcode(OGF::InformationalParameters, opcode.into())
+    }
+}
+/// Bluetooth Core Specification version (`HCI_Version`/`LMP_Version` assigned numbers).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum CoreVersion {
+    V1_0B = 0x00,
+    V1_1 = 0x01,
+    V1_2 = 0x02,
+    V2_0 = 0x03,
+    V2_1 = 0x04,
+    V3_0 = 0x05,
+    V4_0 = 0x06,
+    V4_1 = 0x07,
+    V4_2 = 0x08,
+    V5_0 = 0x09,
+    V5_1 = 0x0A,
+    V5_2 = 0x0B,
+    V5_3 = 0x0C,
+    V5_4 = 0x0D,
+    V6_0 = 0x0E,
+}
+impl CoreVersion {
+    pub const BYTE_LEN: usize = 1;
+    /// Newest version this crate knows about.
+    pub const LATEST: CoreVersion = CoreVersion::V6_0;
+}
+impl From<CoreVersion> for u8 {
+    fn from(v: CoreVersion) -> Self {
+        v as u8
+    }
+}
+impl TryFrom<u8> for CoreVersion {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(CoreVersion::V1_0B),
+            0x01 => Ok(CoreVersion::V1_1),
+            0x02 => Ok(CoreVersion::V1_2),
+            0x03 => Ok(CoreVersion::V2_0),
+            0x04 => Ok(CoreVersion::V2_1),
+            0x05 => Ok(CoreVersion::V3_0),
+            0x06 => Ok(CoreVersion::V4_0),
+            0x07 => Ok(CoreVersion::V4_1),
+            0x08 => Ok(CoreVersion::V4_2),
+            0x09 => Ok(CoreVersion::V5_0),
+            0x0A => Ok(CoreVersion::V5_1),
+            0x0B => Ok(CoreVersion::V5_2),
+            0x0C => Ok(CoreVersion::V5_3),
+            0x0D => Ok(CoreVersion::V5_4),
+            0x0E => Ok(CoreVersion::V6_0),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+impl core::fmt::Display for CoreVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let s = match self {
+            CoreVersion::V1_0B => "1.0b",
+            CoreVersion::V1_1 => "1.1",
+            CoreVersion::V1_2 => "1.2",
+            CoreVersion::V2_0 => "2.0",
+            CoreVersion::V2_1 => "2.1",
+            CoreVersion::V3_0 => "3.0",
+            CoreVersion::V4_0 => "4.0",
+            CoreVersion::V4_1 => "4.1",
+            CoreVersion::V4_2 => "4.2",
+            CoreVersion::V5_0 => "5.0",
+            CoreVersion::V5_1 => "5.1",
+            CoreVersion::V5_2 => "5.2",
+            CoreVersion::V5_3 => "5.3",
+            CoreVersion::V5_4 => "5.4",
+            CoreVersion::V6_0 => "6.0",
+        };
+        f.write_str(s)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadLocalVersionInformation;
+impl ReadLocalVersionInformation {
+    pub const OPCODE: InformationalOpcode = InformationalOpcode::ReadLocalVersionInformation;
+}
+impl Command for ReadLocalVersionInformation {
+    type Return = CommandComplete<LocalVersionInformation>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadLocalVersionInformation)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct LocalVersionInformation {
+    pub status: ErrorCode,
+    /// Raw `HCI_Version`. Use [`LocalVersionInformation::core_version`] for the decoded version.
+    pub hci_version: u8,
+    pub hci_subversion: u16,
+    pub lmp_version: u8,
+    /// Company identifier of the controller manufacturer.
+    pub company_identifier: u16,
+    pub lmp_subversion: u16,
+}
+impl LocalVersionInformation {
+    pub const BYTE_LEN: usize = 9;
+    /// Core version of the HCI. Versions newer than this crate knows about are reported as
+    /// [`CoreVersion::LATEST`].
+    pub fn core_version(&self) -> CoreVersion {
+        CoreVersion::try_from(self.hci_version).unwrap_or(CoreVersion::LATEST)
+    }
+}
+impl ReturnParameters for LocalVersionInformation {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1] = self.hci_version;
+        buf[2..4].copy_from_slice(&self.hci_subversion.to_le_bytes());
+        buf[4] = self.lmp_version;
+        buf[5..7].copy_from_slice(&self.company_identifier.to_le_bytes());
+        buf[7..9].copy_from_slice(&self.lmp_subversion.to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LocalVersionInformation {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            hci_version: buf[1],
+            hci_subversion: u16::from_le_bytes([buf[2], buf[3]]),
+            lmp_version: buf[4],
+            company_identifier: u16::from_le_bytes([buf[5], buf[6]]),
+            lmp_subversion: u16::from_le_bytes([buf[7], buf[8]]),
+        })
+    }
+}
+/// `HCI_Read_Local_Supported_Commands`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadLocalSupportedCommands;
+impl ReadLocalSupportedCommands {
+    pub const OPCODE: InformationalOpcode = InformationalOpcode::ReadLocalSupportedCommands;
+}
+impl Command for ReadLocalSupportedCommands {
+    type Return = CommandComplete<SupportedCommandsReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V1_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadLocalSupportedCommands)
+    }
+}
+/// Supported commands bit mask (octet `n`, bit `m` of the Core spec table). Use the named
+/// accessors, [`SupportedCommands::supports`] or [`SupportedCommands::is_bit_set`] for commands
+/// without one.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SupportedCommands(pub [u8; SupportedCommands::BYTE_LEN]);
+impl SupportedCommands {
+    pub const BYTE_LEN: usize = 64;
+    pub const ZEROED: SupportedCommands = SupportedCommands([0_u8; Self::BYTE_LEN]);
+    pub fn is_bit_set(&self, octet: usize, bit: u8) -> bool {
+        octet < Self::BYTE_LEN && bit < 8 && self.0[octet] & (1_u8 << bit) != 0
+    }
+    /// Returns if the controller supports `Cmd` or `None` if its bit isn't known to this crate.
+    pub fn supports<Cmd: Command>(&self) -> Option<bool> {
+        self.supports_opcode(Cmd::opcode())
+    }
+    pub fn supports_opcode(&self, opcode: Opcode) -> Option<bool> {
+        Self::position(opcode).map(|(octet, bit)| self.is_bit_set(octet, bit))
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&self.0);
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<SupportedCommands, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(SupportedCommands(
+            buf.try_into().expect("length checked above"),
+        ))
+    }
+}
+impl Default for SupportedCommands {
+    fn default() -> Self {
+        SupportedCommands::ZEROED
+    }
+}
+/// Named accessors and [`SupportedCommands::position`] from one `name => (opcode, octet, bit)`
+/// table.
+macro_rules! supported_commands {
+    ($($name:ident => ($opcode:expr, $octet:expr, $bit:expr),)*) => {
+        impl SupportedCommands {
+            $(
+                pub fn $name(&self) -> bool {
+                    self.is_bit_set($octet, $bit)
+                }
+            )*
+            /// Octet and bit of `opcode` in the mask or `None` if it isn't known.
+            pub fn position(opcode: Opcode) -> Option<(usize, u8)> {
+                $(
+                    if opcode == Opcode::from($opcode) {
+                        return Some(($octet, $bit));
+                    }
+                )*
+                None
+            }
+        }
+    };
+}
+supported_commands! {
+    inquiry => (LinkControlOpcode::Inquiry, 0, 0),
+    inquiry_cancel => (LinkControlOpcode::InquiryCancel, 0, 1),
+    create_connection => (LinkControlOpcode::CreateConnection, 0, 4),
+    disconnect => (LinkControlOpcode::Disconnect, 0, 5),
+    read_remote_version_information => (LinkControlOpcode::ReadRemoteVersionInformation, 2, 7),
+    set_event_mask => (ControllerBasebandOpcode::SetEventMask, 5, 6),
+    reset => (ControllerBasebandOpcode::Reset, 5, 7),
+    write_local_name => (ControllerBasebandOpcode::WriteLocalName, 7, 0),
+    write_scan_enable => (ControllerBasebandOpcode::WriteScanEnable, 7, 7),
+    write_page_scan_activity => (ControllerBasebandOpcode::WritePageScanActivity, 8, 1),
+    write_inquiry_scan_activity => (ControllerBasebandOpcode::WriteInquiryScanActivity, 8, 3),
+    write_class_of_device => (ControllerBasebandOpcode::WriteClassOfDevice, 9, 1),
+    read_transmit_power_level => (ControllerBasebandOpcode::ReadTransmitPowerLevel, 10, 2),
+    read_local_version_information => (InformationalOpcode::ReadLocalVersionInformation, 14, 3),
+    read_local_supported_features => (InformationalOpcode::ReadLocalSupportedFeatures, 14, 5),
+    read_local_extended_features => (InformationalOpcode::ReadLocalExtendedFeatures, 14, 6),
+    read_buffer_size => (InformationalOpcode::ReadBufferSize, 14, 7),
+    read_bd_addr => (InformationalOpcode::ReadBDADDR, 15, 1),
+    read_rssi => (StatusOpcode::ReadRSSI, 15, 5),
+    write_simple_pairing_mode => (ControllerBasebandOpcode::WriteSimplePairingMode, 32, 3),
+    le_set_event_mask => (LEControllerOpcode::SetEventMask, 25, 0),
+    le_read_buffer_size_v1 => (LEControllerOpcode::ReadBufferSizeV1, 25, 1),
+    le_read_local_supported_features => (LEControllerOpcode::ReadLocalSupportedFeatures, 25, 2),
+    le_set_random_address => (LEControllerOpcode::SetRandomAddress, 25, 4),
+    le_set_advertising_parameters => (LEControllerOpcode::SetAdvertisingParameters, 25, 5),
+    le_read_advertising_channel_tx_power =>
+        (LEControllerOpcode::ReadAdvertisingChannelTxPower, 25, 6),
+    le_set_advertising_data => (LEControllerOpcode::SetAdvertisingData, 25, 7),
+    le_set_scan_response_data => (LEControllerOpcode::SetScanResponseData, 26, 0),
+    le_set_advertising_enable => (LEControllerOpcode::SetAdvertisingEnable, 26, 1),
+    le_set_scan_parameters => (LEControllerOpcode::SetScanParameters, 26, 2),
+    le_set_scan_enable => (LEControllerOpcode::SetScanEnable, 26, 3),
+    le_create_connection => (LEControllerOpcode::CreateConnection, 26, 4),
+    le_create_connection_cancel => (LEControllerOpcode::CreateConnectionCancel, 26, 5),
+    le_read_whitelist_size => (LEControllerOpcode::ReadWhitelistSize, 26, 6),
+    le_clear_whitelist => (LEControllerOpcode::ClearWhitelist, 26, 7),
+    le_add_device_to_whitelist => (LEControllerOpcode::AddDeviceToWhitelist, 27, 0),
+    le_remove_device_from_whitelist => (LEControllerOpcode::RemoveDeviceFromWhitelist, 27, 1),
+    le_connection_update => (LEControllerOpcode::ConnectionUpdate, 27, 2),
+    le_set_host_channel_classification =>
+        (LEControllerOpcode::SetHostChannelClassification, 27, 3),
+    le_read_channel_map => (LEControllerOpcode::ReadChannelMap, 27, 4),
+    le_read_remote_features => (LEControllerOpcode::ReadRemoteUsedFeatures, 27, 5),
+    le_encrypt => (LEControllerOpcode::Encrypt, 27, 6),
+    le_rand => (LEControllerOpcode::Rand, 27, 7),
+    le_start_encryption => (LEControllerOpcode::StartEncryption, 28, 0),
+    le_long_term_key_request_reply => (LEControllerOpcode::LongTermKeyRequestReply, 28, 1),
+    le_long_term_key_request_negative_reply =>
+        (LEControllerOpcode::LongTermKeyRequestNegativeReply, 28, 2),
+    le_read_supported_states => (LEControllerOpcode::ReadSupportedState, 28, 3),
+    le_receiver_test => (LEControllerOpcode::ReceiverTest, 28, 4),
+    le_transmitter_test => (LEControllerOpcode::TransmitterTest, 28, 5),
+    le_test_end => (LEControllerOpcode::TestEnd, 28, 6),
+    le_set_data_length => (LEControllerOpcode::SetDataLength, 33, 6),
+    le_read_suggested_default_data_length =>
+        (LEControllerOpcode::ReadSuggestedDefaultDataLength, 33, 7),
+    le_write_suggested_default_data_length =>
+        (LEControllerOpcode::WriteSuggestedDefaultDataLength, 34, 0),
+    le_add_device_to_resolving_list => (LEControllerOpcode::AddDeviceToResolvingList, 34, 3),
+    le_remove_device_from_resolving_list =>
+        (LEControllerOpcode::RemoveDeviceFromResolvingList, 34, 4),
+    le_clear_resolving_list => (LEControllerOpcode::ClearResolvingList, 34, 5),
+    le_read_resolving_list_size => (LEControllerOpcode::ReadResolvingListSize, 34, 6),
+    le_set_address_resolution_enable => (LEControllerOpcode::SetAddressResolutionEnable, 35, 1),
+    le_set_rpa_timeout => (LEControllerOpcode::SetResolvablePrivateAddressTimeout, 35, 2),
+    le_read_maximum_data_length => (LEControllerOpcode::ReadMaximumDataLength, 35, 3),
+    le_read_phy => (LEControllerOpcode::ReadPHY, 35, 4),
+    le_set_default_phy => (LEControllerOpcode::SetDefaultPHY, 35, 5),
+    le_set_phy => (LEControllerOpcode::SetPHY, 35, 6),
+    le_receiver_test_v2 => (LEControllerOpcode::ReceiverTestV2, 35, 7),
+    le_transmitter_test_v2 => (LEControllerOpcode::TransmitterTestV2, 36, 0),
+    le_set_extended_advertising_parameters =>
+        (LEControllerOpcode::SetExtendedAdvertisingParameters, 36, 2),
+    le_set_extended_advertising_data => (LEControllerOpcode::SetExtendedAdvertisingData, 36, 3),
+    le_set_extended_scan_response_data =>
+        (LEControllerOpcode::SetExtendedScanResponseData, 36, 4),
+    le_set_extended_advertising_enable =>
+        (LEControllerOpcode::SetExtendedAdvertisingEnable, 36, 5),
+    le_read_maximum_advertising_data_length =>
+        (LEControllerOpcode::ReadMaximumAdvertisingDataLength, 36, 6),
+    le_read_number_of_supported_advertising_sets =>
+        (LEControllerOpcode::ReadNumberOfSupportedAdvertisingSets, 36, 7),
+    le_remove_advertising_set => (LEControllerOpcode::RemoveAdvertisingSet, 37, 0),
+    le_clear_advertising_sets => (LEControllerOpcode::ClearAdvertisingSets, 37, 1),
+    le_set_periodic_advertising_parameters =>
+        (LEControllerOpcode::SetPeriodicAdvertisingParameters, 37, 2),
+    le_set_periodic_advertising_data => (LEControllerOpcode::SetPeriodicAdvertisingData, 37, 3),
+    le_set_periodic_advertising_enable =>
+        (LEControllerOpcode::SetPeriodicAdvertisingEnable, 37, 4),
+    le_set_extended_scan_parameters => (LEControllerOpcode::SetExtendedScanParameters, 37, 5),
+    le_set_extended_scan_enable => (LEControllerOpcode::SetExtendedScanEnable, 37, 6),
+    le_extended_create_connection => (LEControllerOpcode::ExtendedCreateConnection, 37, 7),
+    le_periodic_advertising_create_sync =>
+        (LEControllerOpcode::PeriodicAdvertisingCreateSync, 38, 0),
+    le_periodic_advertising_create_sync_cancel =>
+        (LEControllerOpcode::PeriodicAdvertisingCreateSyncCancel, 38, 1),
+    le_periodic_advertising_terminate_sync =>
+        (LEControllerOpcode::PeriodicAdvertisingTerminateSync, 38, 2),
+    le_add_device_to_periodic_advertiser_list =>
+        (LEControllerOpcode::AddDeviceToPeriodicAdvertiserList, 38, 3),
+    le_remove_device_from_periodic_advertiser_list =>
+        (LEControllerOpcode::RemoveDeviceFromPeriodicAdvertiserList, 38, 4),
+    le_clear_periodic_advertiser_list => (LEControllerOpcode::ClearPeriodicAdvertiserList, 38, 5),
+    le_set_privacy_mode => (LEControllerOpcode::SetPrivacyMode, 39, 2),
+    le_receiver_test_v3 => (LEControllerOpcode::ReceiverTestV3, 39, 3),
+    le_transmitter_test_v3 => (LEControllerOpcode::TransmitterTestV3, 39, 4),
+    le_read_buffer_size_v2 => (LEControllerOpcode::ReadBufferSizeV2, 41, 5),
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SupportedCommandsReturn {
+    pub status: ErrorCode,
+    pub commands: SupportedCommands,
+}
+impl SupportedCommandsReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + SupportedCommands::BYTE_LEN;
+}
+impl ReturnParameters for SupportedCommandsReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        self.commands.pack_into(&mut buf[1..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(SupportedCommandsReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            commands: SupportedCommands::unpack_from(&buf[1..])?,
+        })
+    }
+}
+/// Bits of the LMP feature mask (page 0).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum LMPFeature {
+    ThreeSlotPackets = 0,
+    FiveSlotPackets = 1,
+    Encryption = 2,
+    SlotOffset = 3,
+    TimingAccuracy = 4,
+    RoleSwitch = 5,
+    HoldMode = 6,
+    SniffMode = 7,
+    PowerControlRequests = 9,
+    ChannelQualityDrivenDataRate = 10,
+    SCOLink = 11,
+    HV2Packets = 12,
+    HV3Packets = 13,
+    MuLawLogSynchronousData = 14,
+    ALawLogSynchronousData = 15,
+    CVSDSynchronousData = 16,
+    PagingParameterNegotiation = 17,
+    PowerControl = 18,
+    TransparentSynchronousData = 19,
+    BroadcastEncryption = 23,
+    EnhancedDataRateACL2M = 25,
+    EnhancedDataRateACL3M = 26,
+    EnhancedInquiryScan = 27,
+    InterlacedInquiryScan = 28,
+    InterlacedPageScan = 29,
+    RSSIWithInquiryResults = 30,
+    ExtendedSCOLink = 31,
+    EV4Packets = 32,
+    EV5Packets = 33,
+    AFHCapablePeripheral = 35,
+    AFHClassificationPeripheral = 36,
+    BREDRNotSupported = 37,
+    LESupportedController = 38,
+    ThreeSlotEnhancedDataRateACL = 39,
+    FiveSlotEnhancedDataRateACL = 40,
+    SniffSubrating = 41,
+    PauseEncryption = 42,
+    AFHCapableCentral = 43,
+    AFHClassificationCentral = 44,
+    EnhancedDataRateESCO2M = 45,
+    EnhancedDataRateESCO3M = 46,
+    ThreeSlotEnhancedDataRateESCO = 47,
+    ExtendedInquiryResponse = 48,
+    SimultaneousLEAndBREDR = 49,
+    SecureSimplePairingController = 51,
+    EncapsulatedPDU = 52,
+    ErroneousDataReporting = 53,
+    NonFlushablePacketBoundaryFlag = 54,
+    LinkSupervisionTimeoutChangedEvent = 56,
+    VariableInquiryTxPowerLevel = 57,
+    EnhancedPowerControl = 58,
+    ExtendedFeatures = 63,
+}
+impl From<LMPFeature> for u8 {
+    fn from(feature: LMPFeature) -> Self {
+        feature as u8
+    }
+}
+/// LMP feature mask (page 0). Bits without an [`LMPFeature`] are kept and can be checked with
+/// [`LMPFeatures::is_bit_set`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default)]
+pub struct LMPFeatures(pub u64);
+impl LMPFeatures {
+    pub const BYTE_LEN: usize = 8;
+    pub const ZEROED: LMPFeatures = LMPFeatures(0);
+    pub fn contains(self, feature: LMPFeature) -> bool {
+        self.is_bit_set(feature.into())
+    }
+    pub fn is_bit_set(self, bit: u8) -> bool {
+        bit < 64 && self.0 & (1_u64 << bit) != 0
+    }
+    pub fn enable(&mut self, feature: LMPFeature) {
+        self.0 |= 1_u64 << u8::from(feature)
+    }
+    /// Returns if the controller supports LE at all.
+    pub fn is_le_supported(self) -> bool {
+        self.contains(LMPFeature::LESupportedController)
+    }
+    /// Returns if the controller supports BR/EDR (Classic).
+    pub fn is_br_edr_supported(self) -> bool {
+        !self.contains(LMPFeature::BREDRNotSupported)
+    }
+    pub fn pack_into(self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&self.0.to_le_bytes());
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<LMPFeatures, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LMPFeatures(u64::from_le_bytes(
+            buf.try_into().expect("length checked above"),
+        )))
+    }
+}
+/// `HCI_Read_Local_Supported_Features`. The LMP features (page 0). See
+/// [`crate::hci::le::features::ReadLocalFeatures`] for the LE features.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadLocalSupportedFeatures;
+impl ReadLocalSupportedFeatures {
+    pub const OPCODE: InformationalOpcode = InformationalOpcode::ReadLocalSupportedFeatures;
+}
+impl Command for ReadLocalSupportedFeatures {
+    type Return = CommandComplete<LMPFeaturesReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadLocalSupportedFeatures)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct LMPFeaturesReturn {
+    pub status: ErrorCode,
+    pub features: LMPFeatures,
+}
+impl LMPFeaturesReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + LMPFeatures::BYTE_LEN;
+}
+impl ReturnParameters for LMPFeaturesReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        self.features.pack_into(&mut buf[1..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LMPFeaturesReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            features: LMPFeatures::unpack_from(&buf[1..])?,
+        })
+    }
+}
+/// `HCI_Read_BD_ADDR`. The controller's public address (all zeros if it doesn't have one).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadBDADDR;
+impl ReadBDADDR {
+    pub const OPCODE: InformationalOpcode = InformationalOpcode::ReadBDADDR;
+}
+impl Command for ReadBDADDR {
+    type Return = CommandComplete<AddressReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadBDADDR)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hci::le::features::ReadLocalFeatures;
+    use crate::hci::le::test_mode::TestEnd;
+    use crate::BTAddress;
+    #[test]
+    fn test_supported_commands() {
+        let mut buf = [0_u8; SupportedCommandsReturn::BYTE_LEN];
+        // Reset, LE Read Local Supported Features and LE Set Extended Advertising Data.
+        buf[1 + 5] = 0x80;
+        buf[1 + 25] = 0x04;
+        buf[1 + 36] = 0x08;
+        let commands = SupportedCommandsReturn::unpack_from(&buf).unwrap().commands;
+        assert!(commands.reset() && !commands.set_event_mask());
+        assert!(commands.le_set_extended_advertising_data());
+        assert_eq!(commands.supports::<ReadLocalFeatures>(), Some(true));
+        assert_eq!(commands.supports::<TestEnd>(), Some(false));
+        assert_eq!(commands.supports::<ReadLocalSupportedCommands>(), None);
+        assert!(!commands.is_bit_set(64, 0));
+
+        let features = LMPFeaturesReturn::unpack_from(&[0, 0xFF, 0, 0, 0, 0x60, 0, 0, 0])
+            .unwrap()
+            .features;
+        assert!(features.contains(LMPFeature::ThreeSlotPackets));
+        assert!(features.is_le_supported() && !features.is_br_edr_supported());
+
+        assert_eq!(commands.supports::<ReadBDADDR>(), Some(false));
+        let address = AddressReturn::unpack_from(&[0, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01])
+            .unwrap()
+            .address;
+        assert_eq!(address, BTAddress([6, 5, 4, 3, 2, 1]));
+    }
+}
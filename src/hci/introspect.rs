@@ -0,0 +1,1493 @@
+//! Machine readable descriptions of the HCI commands and events this crate implements (opcode or
+//! event code, name, minimum Core version and parameter layout). Tooling that doesn't know a
+//! packet can still look it up ([`find_command`], [`find_event`]) and render its parameters
+//! field by field ([`MessageDescription::decode`]), and a support matrix can be dumped for the
+//! Core version a controller reports ([`support_matrix`]).
+//!
+//! Variable length parts (report lists, fragment data, etc) are described as one
+//! [`FieldKind::Rest`] field. The experimental channel sounding commands aren't described.
+use crate::hci::baseband::{
+    EnhancedFlush, Flush, ReadAutomaticFlushTimeout, ReadLocalName, ReadTransmitPowerLevel, Reset,
+    SetEventMask, WriteAutomaticFlushTimeout, WriteLocalName,
+};
+use crate::hci::command::Command;
+use crate::hci::event::EventCode;
+use crate::hci::flow_control::{
+    ReadBufferSize, ReadDataBlockSize, ReadFlowControlMode, WriteFlowControlMode,
+};
+use crate::hci::informational::{
+    CoreVersion, ReadBDADDR, ReadLocalSupportedCommands, ReadLocalSupportedFeatures,
+    ReadLocalVersionInformation,
+};
+use crate::hci::le::{self, MetaEventCode};
+use crate::hci::link_control::{
+    AuthenticationRequested, Disconnect, LinkKeyRequestNegativeReply, LinkKeyRequestReply,
+    PINCodeRequestNegativeReply, PINCodeRequestReply, ReadRemoteVersionInformation,
+};
+use crate::hci::status::ReadRSSI;
+use crate::hci::testing::{EnableDeviceUnderTestMode, ReadLoopbackMode, WriteLoopbackMode};
+use crate::hci::Opcode;
+use crate::{BTAddress, PackError, BT_ADDRESS_LEN};
+use alloc::vec::Vec;
+use core::fmt::Formatter;
+
+/// Layout of one parameter. All integers are little endian.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum FieldKind {
+    U8,
+    I8,
+    U16,
+    U24,
+    U32,
+    U64,
+    Address,
+    /// Fixed number of bytes (keys, padded data, etc).
+    Bytes(usize),
+    /// Everything left. Only used for the last field.
+    Rest,
+}
+impl FieldKind {
+    /// `None` for [`FieldKind::Rest`].
+    pub fn byte_len(self) -> Option<usize> {
+        match self {
+            FieldKind::U8 | FieldKind::I8 => Some(1),
+            FieldKind::U16 => Some(2),
+            FieldKind::U24 => Some(3),
+            FieldKind::U32 => Some(4),
+            FieldKind::U64 => Some(8),
+            FieldKind::Address => Some(BT_ADDRESS_LEN),
+            FieldKind::Bytes(len) => Some(len),
+            FieldKind::Rest => None,
+        }
+    }
+}
+/// One named parameter.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct Field {
+    /// Spec name (`Connection_Handle`, etc).
+    pub name: &'static str,
+    pub kind: FieldKind,
+}
+impl Field {
+    pub const fn new(name: &'static str, kind: FieldKind) -> Field {
+        Field { name, kind }
+    }
+}
+/// Decoded value of one [`Field`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum FieldValue<'a> {
+    Unsigned(u64),
+    Signed(i8),
+    Address(BTAddress),
+    Bytes(&'a [u8]),
+}
+impl<'a> core::fmt::Display for FieldValue<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FieldValue::Unsigned(v) => write!(f, "0x{:X}", v),
+            FieldValue::Signed(v) => write!(f, "{}", v),
+            FieldValue::Address(a) => write!(f, "{}", a),
+            FieldValue::Bytes(bytes) => {
+                for b in bytes.iter() {
+                    write!(f, "{:02X}", b)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+/// What a [`MessageDescription`] describes.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum MessageId {
+    Command(Opcode),
+    Event(EventCode),
+    /// LE Meta event. The parameters start after the subevent code.
+    MetaEvent(MetaEventCode),
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct MessageDescription {
+    pub id: MessageId,
+    /// Spec name without the `HCI_`/`HCI_LE_` prefix.
+    pub name: &'static str,
+    /// Oldest Core version with the message. Only known for commands.
+    pub min_version: Option<CoreVersion>,
+    pub parameters: &'static [Field],
+    /// Return parameters in the Command Complete (after `Num_HCI_Command_Packets` and the
+    /// opcode). Empty for commands answered with a Command Status and for events.
+    pub return_parameters: &'static [Field],
+}
+impl MessageDescription {
+    /// Describe command `C`. The opcode and minimum version come from its [`Command`] impl.
+    pub fn command<C: Command>(
+        name: &'static str,
+        parameters: &'static [Field],
+        return_parameters: &'static [Field],
+    ) -> MessageDescription {
+        MessageDescription {
+            id: MessageId::Command(C::opcode()),
+            name,
+            min_version: Some(C::MIN_VERSION),
+            parameters,
+            return_parameters,
+        }
+    }
+    pub const fn event(
+        code: EventCode,
+        name: &'static str,
+        parameters: &'static [Field],
+    ) -> MessageDescription {
+        MessageDescription {
+            id: MessageId::Event(code),
+            name,
+            min_version: None,
+            parameters,
+            return_parameters: &[],
+        }
+    }
+    pub const fn meta_event(
+        code: MetaEventCode,
+        name: &'static str,
+        parameters: &'static [Field],
+    ) -> MessageDescription {
+        MessageDescription {
+            id: MessageId::MetaEvent(code),
+            name,
+            min_version: None,
+            parameters,
+            return_parameters: &[],
+        }
+    }
+    /// Fixed length of the parameters or `None` if the last one is [`FieldKind::Rest`].
+    pub fn parameters_len(&self) -> Option<usize> {
+        self.parameters.iter().map(|f| f.kind.byte_len()).sum()
+    }
+    /// Whether a controller of `version` should know the message (always `true` for events).
+    pub fn is_supported_by(&self, version: CoreVersion) -> bool {
+        self.min_version.map_or(true, |min| version >= min)
+    }
+    /// Split `parameters` into [`MessageDescription::parameters`] fields.
+    pub fn decode<'a>(
+        &self,
+        parameters: &'a [u8],
+    ) -> Result<Vec<(Field, FieldValue<'a>)>, PackError> {
+        decode_fields(self.parameters, parameters)
+    }
+    /// Split the return parameters of a Command Complete into
+    /// [`MessageDescription::return_parameters`] fields.
+    pub fn decode_return<'a>(
+        &self,
+        return_parameters: &'a [u8],
+    ) -> Result<Vec<(Field, FieldValue<'a>)>, PackError> {
+        decode_fields(self.return_parameters, return_parameters)
+    }
+}
+/// Split `buf` into `fields`. Fails if `buf` is too short or, unless the last field is
+/// [`FieldKind::Rest`], too long.
+pub fn decode_fields<'a>(
+    fields: &[Field],
+    buf: &'a [u8],
+) -> Result<Vec<(Field, FieldValue<'a>)>, PackError> {
+    let mut out = Vec::with_capacity(fields.len());
+    let mut index = 0;
+    for field in fields {
+        let len = field
+            .kind
+            .byte_len()
+            .unwrap_or(buf.len() - index.min(buf.len()));
+        if index + len > buf.len() {
+            return Err(PackError::BadLength {
+                expected: index + len,
+                got: buf.len(),
+            });
+        }
+        let bytes = &buf[index..index + len];
+        let unsigned = || {
+            bytes
+                .iter()
+                .rev()
+                .fold(0_u64, |value, b| value << 8 | u64::from(*b))
+        };
+        let value = match field.kind {
+            FieldKind::U8 | FieldKind::U16 | FieldKind::U24 | FieldKind::U32 | FieldKind::U64 => {
+                FieldValue::Unsigned(unsigned())
+            }
+            FieldKind::I8 => FieldValue::Signed(bytes[0] as i8),
+            FieldKind::Address => FieldValue::Address(BTAddress::new(bytes)),
+            FieldKind::Bytes(_) | FieldKind::Rest => FieldValue::Bytes(bytes),
+        };
+        out.push((*field, value));
+        index += len;
+    }
+    PackError::expect_length(index, buf)?;
+    Ok(out)
+}
+/// [`Field`] literal. Unlike `Field::new` calls, these are promoted to `'static` in the field
+/// lists below.
+macro_rules! field {
+    ($name:literal, $kind:expr) => {
+        Field {
+            name: $name,
+            kind: $kind,
+        }
+    };
+}
+const STATUS: Field = field!("Status", FieldKind::U8);
+const CONNECTION_HANDLE: Field = field!("Connection_Handle", FieldKind::U16);
+const BD_ADDR: Field = field!("BD_ADDR", FieldKind::Address);
+const ADVERTISING_HANDLE: Field = field!("Advertising_Handle", FieldKind::U8);
+const SYNC_HANDLE: Field = field!("Sync_Handle", FieldKind::U16);
+const STATUS_ONLY: &[Field] = &[STATUS];
+const HANDLE_RETURN: &[Field] = &[STATUS, CONNECTION_HANDLE];
+const ADDRESS_RETURN: &[Field] = &[STATUS, BD_ADDR];
+const SCAN_DATA: &[Field] = &[
+    field!("Data_Length", FieldKind::U8),
+    field!("Data", FieldKind::Bytes(31)),
+];
+const EXTENDED_DATA: &[Field] = &[
+    ADVERTISING_HANDLE,
+    field!("Operation", FieldKind::U8),
+    field!("Fragment_Preference", FieldKind::U8),
+    field!("Data_Length", FieldKind::U8),
+    field!("Data", FieldKind::Rest),
+];
+const PERIODIC_ADVERTISER: &[Field] = &[
+    field!("Advertiser_Address_Type", FieldKind::U8),
+    field!("Advertiser_Address", FieldKind::Address),
+    field!("Advertising_SID", FieldKind::U8),
+];
+/// Every described command and event.
+pub fn descriptions() -> Vec<MessageDescription> {
+    use FieldKind::*;
+    use MessageDescription as D;
+    #[allow(unused_mut)]
+    let mut out = alloc::vec![
+        // Controller & Baseband, Informational and Testing.
+        D::command::<Reset>("Reset", &[], STATUS_ONLY),
+        D::command::<SetEventMask>("Set_Event_Mask", &[field!("Event_Mask", U64)], STATUS_ONLY),
+        D::command::<Flush>("Flush", &[CONNECTION_HANDLE], HANDLE_RETURN),
+        D::command::<ReadLocalName>(
+            "Read_Local_Name",
+            &[],
+            &[STATUS, field!("Local_Name", Bytes(248))],
+        ),
+        D::command::<WriteLocalName>(
+            "Write_Local_Name",
+            &[field!("Local_Name", Bytes(248))],
+            STATUS_ONLY,
+        ),
+        D::command::<EnhancedFlush>(
+            "Enhanced_Flush",
+            &[CONNECTION_HANDLE, field!("Packet_Type", U8)],
+            &[],
+        ),
+        D::command::<ReadAutomaticFlushTimeout>(
+            "Read_Automatic_Flush_Timeout",
+            &[CONNECTION_HANDLE],
+            &[STATUS, CONNECTION_HANDLE, field!("Flush_Timeout", U16)],
+        ),
+        D::command::<WriteAutomaticFlushTimeout>(
+            "Write_Automatic_Flush_Timeout",
+            &[CONNECTION_HANDLE, field!("Flush_Timeout", U16)],
+            HANDLE_RETURN,
+        ),
+        D::command::<ReadTransmitPowerLevel>(
+            "Read_Transmit_Power_Level",
+            &[CONNECTION_HANDLE, field!("Type", U8)],
+            &[STATUS, CONNECTION_HANDLE, field!("TX_Power_Level", I8)],
+        ),
+        D::command::<ReadFlowControlMode>(
+            "Read_Flow_Control_Mode",
+            &[],
+            &[STATUS, field!("Flow_Control_Mode", U8)],
+        ),
+        D::command::<WriteFlowControlMode>(
+            "Write_Flow_Control_Mode",
+            &[field!("Flow_Control_Mode", U8)],
+            STATUS_ONLY,
+        ),
+        D::command::<ReadBDADDR>("Read_BD_ADDR", &[], ADDRESS_RETURN),
+        D::command::<ReadLocalSupportedCommands>(
+            "Read_Local_Supported_Commands",
+            &[],
+            &[STATUS, field!("Supported_Commands", Bytes(64))],
+        ),
+        D::command::<ReadLocalSupportedFeatures>(
+            "Read_Local_Supported_Features",
+            &[],
+            &[STATUS, field!("LMP_Features", U64)],
+        ),
+        D::command::<ReadLocalVersionInformation>(
+            "Read_Local_Version_Information",
+            &[],
+            &[
+                STATUS,
+                field!("HCI_Version", U8),
+                field!("HCI_Subversion", U16),
+                field!("LMP_Version", U8),
+                field!("Company_Identifier", U16),
+                field!("LMP_Subversion", U16),
+            ],
+        ),
+        D::command::<ReadBufferSize>(
+            "Read_Buffer_Size",
+            &[],
+            &[
+                STATUS,
+                field!("ACL_Data_Packet_Length", U16),
+                field!("Synchronous_Data_Packet_Length", U8),
+                field!("Total_Num_ACL_Data_Packets", U16),
+                field!("Total_Num_Synchronous_Data_Packets", U16),
+            ],
+        ),
+        D::command::<ReadDataBlockSize>(
+            "Read_Data_Block_Size",
+            &[],
+            &[
+                STATUS,
+                field!("Max_ACL_Data_Packet_Length", U16),
+                field!("Data_Block_Length", U16),
+                field!("Total_Num_Data_Blocks", U16),
+            ],
+        ),
+        D::command::<ReadRSSI>(
+            "Read_RSSI",
+            &[CONNECTION_HANDLE],
+            &[STATUS, CONNECTION_HANDLE, field!("RSSI", I8)],
+        ),
+        D::command::<ReadLoopbackMode>(
+            "Read_Loopback_Mode",
+            &[],
+            &[STATUS, field!("Loopback_Mode", U8)],
+        ),
+        D::command::<WriteLoopbackMode>(
+            "Write_Loopback_Mode",
+            &[field!("Loopback_Mode", U8)],
+            STATUS_ONLY,
+        ),
+        D::command::<EnableDeviceUnderTestMode>("Enable_Device_Under_Test_Mode", &[], STATUS_ONLY),
+        // Link Control.
+        D::command::<LinkKeyRequestReply>(
+            "Link_Key_Request_Reply",
+            &[BD_ADDR, field!("Link_Key", Bytes(16))],
+            ADDRESS_RETURN,
+        ),
+        D::command::<LinkKeyRequestNegativeReply>(
+            "Link_Key_Request_Negative_Reply",
+            &[BD_ADDR],
+            ADDRESS_RETURN,
+        ),
+        D::command::<PINCodeRequestReply>(
+            "PIN_Code_Request_Reply",
+            &[
+                BD_ADDR,
+                field!("PIN_Code_Length", U8),
+                field!("PIN_Code", Bytes(16)),
+            ],
+            ADDRESS_RETURN,
+        ),
+        D::command::<PINCodeRequestNegativeReply>(
+            "PIN_Code_Request_Negative_Reply",
+            &[BD_ADDR],
+            ADDRESS_RETURN,
+        ),
+        D::command::<AuthenticationRequested>(
+            "Authentication_Requested",
+            &[CONNECTION_HANDLE],
+            &[],
+        ),
+        D::command::<Disconnect>(
+            "Disconnect",
+            &[CONNECTION_HANDLE, field!("Reason", U8)],
+            &[],
+        ),
+        D::command::<ReadRemoteVersionInformation>(
+            "Read_Remote_Version_Information",
+            &[CONNECTION_HANDLE],
+            &[],
+        ),
+        // LE Controller.
+        D::command::<le::commands::SetMetaEventMask>(
+            "LE_Set_Event_Mask",
+            &[field!("LE_Event_Mask", U64)],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ReadBufferSizeV1>(
+            "LE_Read_Buffer_Size",
+            &[],
+            &[
+                STATUS,
+                field!("LE_ACL_Data_Packet_Length", U16),
+                field!("Total_Num_LE_ACL_Data_Packets", U8),
+            ],
+        ),
+        D::command::<le::commands::ReadBufferSizeV2>(
+            "LE_Read_Buffer_Size_V2",
+            &[],
+            &[
+                STATUS,
+                field!("LE_ACL_Data_Packet_Length", U16),
+                field!("Total_Num_LE_ACL_Data_Packets", U8),
+                field!("ISO_Data_Packet_Length", U16),
+                field!("Total_Num_ISO_Data_Packets", U8),
+            ],
+        ),
+        D::command::<le::commands::Rand>(
+            "LE_Rand",
+            &[],
+            &[STATUS, field!("Random_Number", Bytes(8))],
+        ),
+        D::command::<le::commands::ReadLocalFeatures>(
+            "LE_Read_Local_Supported_Features",
+            &[],
+            &[STATUS, field!("LE_Features", U64)],
+        ),
+        D::command::<le::commands::ReadRemoteFeatures>(
+            "LE_Read_Remote_Features",
+            &[CONNECTION_HANDLE],
+            &[],
+        ),
+        D::command::<le::commands::Encrypt>(
+            "LE_Encrypt",
+            &[
+                field!("Key", Bytes(16)),
+                field!("Plaintext_Data", Bytes(16)),
+            ],
+            &[STATUS, field!("Encrypted_Data", Bytes(16))],
+        ),
+        D::command::<le::commands::StartEncryption>(
+            "LE_Enable_Encryption",
+            &[
+                CONNECTION_HANDLE,
+                field!("Random_Number", U64),
+                field!("Encrypted_Diversifier", U16),
+                field!("Long_Term_Key", Bytes(16)),
+            ],
+            &[],
+        ),
+        D::command::<le::commands::LongTermKeyRequestReply>(
+            "LE_Long_Term_Key_Request_Reply",
+            &[CONNECTION_HANDLE, field!("Long_Term_Key", Bytes(16))],
+            HANDLE_RETURN,
+        ),
+        D::command::<le::commands::LongTermKeyRequestNegativeReply>(
+            "LE_Long_Term_Key_Request_Negative_Reply",
+            &[CONNECTION_HANDLE],
+            HANDLE_RETURN,
+        ),
+        D::command::<le::commands::SetAdvertisingParameters>(
+            "LE_Set_Advertising_Parameters",
+            &[
+                field!("Advertising_Interval_Min", U16),
+                field!("Advertising_Interval_Max", U16),
+                field!("Advertising_Type", U8),
+                field!("Own_Address_Type", U8),
+                field!("Peer_Address_Type", U8),
+                field!("Peer_Address", Address),
+                field!("Advertising_Channel_Map", U8),
+                field!("Advertising_Filter_Policy", U8),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ReadAdvertisingChannelTxPower>(
+            "LE_Read_Advertising_Physical_Channel_Tx_Power",
+            &[],
+            &[STATUS, field!("TX_Power_Level", I8)],
+        ),
+        D::command::<le::commands::SetAdvertisingData>(
+            "LE_Set_Advertising_Data",
+            SCAN_DATA,
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetScanResponseData>(
+            "LE_Set_Scan_Response_Data",
+            SCAN_DATA,
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetAdvertisingEnable>(
+            "LE_Set_Advertising_Enable",
+            &[field!("Advertising_Enable", U8)],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetScanParameters>(
+            "LE_Set_Scan_Parameters",
+            &[
+                field!("LE_Scan_Type", U8),
+                field!("LE_Scan_Interval", U16),
+                field!("LE_Scan_Window", U16),
+                field!("Own_Address_Type", U8),
+                field!("Scanning_Filter_Policy", U8),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetScanEnable>(
+            "LE_Set_Scan_Enable",
+            &[
+                field!("LE_Scan_Enable", U8),
+                field!("Filter_Duplicates", U8),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::connection::CreateConnection>(
+            "LE_Create_Connection",
+            &[
+                field!("LE_Scan_Interval", U16),
+                field!("LE_Scan_Window", U16),
+                field!("Initiator_Filter_Policy", U8),
+                field!("Peer_Address_Type", U8),
+                field!("Peer_Address", Address),
+                field!("Own_Address_Type", U8),
+                field!("Connection_Interval_Min", U16),
+                field!("Connection_Interval_Max", U16),
+                field!("Max_Latency", U16),
+                field!("Supervision_Timeout", U16),
+                field!("Min_CE_Length", U16),
+                field!("Max_CE_Length", U16),
+            ],
+            &[],
+        ),
+        D::command::<le::connection::CreateConnectionCancel>(
+            "LE_Create_Connection_Cancel",
+            &[],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ConnectionUpdate>(
+            "LE_Connection_Update",
+            &[
+                CONNECTION_HANDLE,
+                field!("Connection_Interval_Min", U16),
+                field!("Connection_Interval_Max", U16),
+                field!("Max_Latency", U16),
+                field!("Supervision_Timeout", U16),
+                field!("Min_CE_Length", U16),
+                field!("Max_CE_Length", U16),
+            ],
+            &[],
+        ),
+        D::command::<le::commands::SetHostChannelClassification>(
+            "LE_Set_Host_Channel_Classification",
+            &[field!("Channel_Map", Bytes(5))],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ReadChannelMap>(
+            "LE_Read_Channel_Map",
+            &[CONNECTION_HANDLE],
+            &[STATUS, CONNECTION_HANDLE, field!("Channel_Map", Bytes(5))],
+        ),
+        D::command::<le::commands::ClearWhitelist>("LE_Clear_Filter_Accept_List", &[], STATUS_ONLY),
+        D::command::<le::commands::ReadWhitelistSize>(
+            "LE_Read_Filter_Accept_List_Size",
+            &[],
+            &[STATUS, field!("Filter_Accept_List_Size", U8)],
+        ),
+        D::command::<le::commands::AddDeviceToWhitelist>(
+            "LE_Add_Device_To_Filter_Accept_List",
+            &[field!("Address_Type", U8), field!("Address", Address)],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::RemoveDeviceFromWhitelist>(
+            "LE_Remove_Device_From_Filter_Accept_List",
+            &[field!("Address_Type", U8), field!("Address", Address)],
+            STATUS_ONLY,
+        ),
+        D::command::<le::extended_advertise::SetExtendedAdvertisingParameters>(
+            "LE_Set_Extended_Advertising_Parameters",
+            &[
+                ADVERTISING_HANDLE,
+                field!("Advertising_Event_Properties", U16),
+                field!("Primary_Advertising_Interval_Min", U24),
+                field!("Primary_Advertising_Interval_Max", U24),
+                field!("Primary_Advertising_Channel_Map", U8),
+                field!("Own_Address_Type", U8),
+                field!("Peer_Address_Type", U8),
+                field!("Peer_Address", Address),
+                field!("Advertising_Filter_Policy", U8),
+                field!("Advertising_TX_Power", I8),
+                field!("Primary_Advertising_PHY", U8),
+                field!("Secondary_Advertising_Max_Skip", U8),
+                field!("Secondary_Advertising_PHY", U8),
+                field!("Advertising_SID", U8),
+                field!("Scan_Request_Notification_Enable", U8),
+            ],
+            &[STATUS, field!("Selected_TX_Power", I8)],
+        ),
+        D::command::<le::extended_advertise::SetExtendedAdvertisingData>(
+            "LE_Set_Extended_Advertising_Data",
+            EXTENDED_DATA,
+            STATUS_ONLY,
+        ),
+        D::command::<le::extended_advertise::SetExtendedScanResponseData>(
+            "LE_Set_Extended_Scan_Response_Data",
+            EXTENDED_DATA,
+            STATUS_ONLY,
+        ),
+        D::command::<le::extended_advertise::SetExtendedAdvertisingEnable>(
+            "LE_Set_Extended_Advertising_Enable",
+            &[
+                field!("Enable", U8),
+                field!("Num_Sets", U8),
+                field!("Sets", Rest),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::extended_advertise::ReadMaximumAdvertisingDataLength>(
+            "LE_Read_Maximum_Advertising_Data_Length",
+            &[],
+            &[STATUS, field!("Max_Advertising_Data_Length", U16)],
+        ),
+        D::command::<le::extended_advertise::ReadNumberOfSupportedAdvertisingSets>(
+            "LE_Read_Number_of_Supported_Advertising_Sets",
+            &[],
+            &[STATUS, field!("Num_Supported_Advertising_Sets", U8)],
+        ),
+        D::command::<le::extended_advertise::RemoveAdvertisingSet>(
+            "LE_Remove_Advertising_Set",
+            &[ADVERTISING_HANDLE],
+            STATUS_ONLY,
+        ),
+        D::command::<le::extended_advertise::ClearAdvertisingSets>(
+            "LE_Clear_Advertising_Sets",
+            &[],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetPeriodicAdvertisingParameters>(
+            "LE_Set_Periodic_Advertising_Parameters",
+            &[
+                ADVERTISING_HANDLE,
+                field!("Periodic_Advertising_Interval_Min", U16),
+                field!("Periodic_Advertising_Interval_Max", U16),
+                field!("Periodic_Advertising_Properties", U16),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetPeriodicAdvertisingData>(
+            "LE_Set_Periodic_Advertising_Data",
+            &[
+                ADVERTISING_HANDLE,
+                field!("Operation", U8),
+                field!("Advertising_Data_Length", U8),
+                field!("Advertising_Data", Rest),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetPeriodicAdvertisingEnable>(
+            "LE_Set_Periodic_Advertising_Enable",
+            &[field!("Enable", U8), ADVERTISING_HANDLE],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetExtendedScanParameters>(
+            "LE_Set_Extended_Scan_Parameters",
+            &[
+                field!("Own_Address_Type", U8),
+                field!("Scanning_Filter_Policy", U8),
+                field!("Scanning_PHYs", U8),
+                field!("PHY_Parameters", Rest),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetExtendedScanEnable>(
+            "LE_Set_Extended_Scan_Enable",
+            &[
+                field!("Enable", U8),
+                field!("Filter_Duplicates", U8),
+                field!("Duration", U16),
+                field!("Period", U16),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ExtendedCreateConnection>(
+            "LE_Extended_Create_Connection",
+            &[
+                field!("Initiator_Filter_Policy", U8),
+                field!("Own_Address_Type", U8),
+                field!("Peer_Address_Type", U8),
+                field!("Peer_Address", Address),
+                field!("Initiating_PHYs", U8),
+                field!("PHY_Parameters", Rest),
+            ],
+            &[],
+        ),
+        D::command::<le::commands::PeriodicAdvertisingCreateSync>(
+            "LE_Periodic_Advertising_Create_Sync",
+            &[
+                field!("Options", U8),
+                field!("Advertising_SID", U8),
+                field!("Advertiser_Address_Type", U8),
+                field!("Advertiser_Address", Address),
+                field!("Skip", U16),
+                field!("Sync_Timeout", U16),
+                field!("Sync_CTE_Type", U8),
+            ],
+            &[],
+        ),
+        D::command::<le::commands::PeriodicAdvertisingCreateSyncCancel>(
+            "LE_Periodic_Advertising_Create_Sync_Cancel",
+            &[],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::PeriodicAdvertisingTerminateSync>(
+            "LE_Periodic_Advertising_Terminate_Sync",
+            &[SYNC_HANDLE],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::AddDeviceToPeriodicAdvertiserList>(
+            "LE_Add_Device_To_Periodic_Advertiser_List",
+            PERIODIC_ADVERTISER,
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::RemoveDeviceFromPeriodicAdvertiserList>(
+            "LE_Remove_Device_From_Periodic_Advertiser_List",
+            PERIODIC_ADVERTISER,
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ClearPeriodicAdvertiserList>(
+            "LE_Clear_Periodic_Advertiser_List",
+            &[],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetDataLength>(
+            "LE_Set_Data_Length",
+            &[
+                CONNECTION_HANDLE,
+                field!("TX_Octets", U16),
+                field!("TX_Time", U16),
+            ],
+            HANDLE_RETURN,
+        ),
+        D::command::<le::commands::ReadSuggestedDefaultDataLength>(
+            "LE_Read_Suggested_Default_Data_Length",
+            &[],
+            &[
+                STATUS,
+                field!("Suggested_Max_TX_Octets", U16),
+                field!("Suggested_Max_TX_Time", U16),
+            ],
+        ),
+        D::command::<le::commands::WriteSuggestedDefaultDataLength>(
+            "LE_Write_Suggested_Default_Data_Length",
+            &[
+                field!("Suggested_Max_TX_Octets", U16),
+                field!("Suggested_Max_TX_Time", U16),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::AddDeviceToResolvingList>(
+            "LE_Add_Device_To_Resolving_List",
+            &[
+                field!("Peer_Identity_Address_Type", U8),
+                field!("Peer_Identity_Address", Address),
+                field!("Peer_IRK", Bytes(16)),
+                field!("Local_IRK", Bytes(16)),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::RemoveDeviceFromResolvingList>(
+            "LE_Remove_Device_From_Resolving_List",
+            &[
+                field!("Peer_Identity_Address_Type", U8),
+                field!("Peer_Identity_Address", Address),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ClearResolvingList>(
+            "LE_Clear_Resolving_List",
+            &[],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ReadResolvingListSize>(
+            "LE_Read_Resolving_List_Size",
+            &[],
+            &[STATUS, field!("Resolving_List_Size", U8)],
+        ),
+        D::command::<le::commands::SetAddressResolutionEnable>(
+            "LE_Set_Address_Resolution_Enable",
+            &[field!("Address_Resolution_Enable", U8)],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetResolvablePrivateAddressTimeout>(
+            "LE_Set_Resolvable_Private_Address_Timeout",
+            &[field!("RPA_Timeout", U16)],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ReadMaximumDataLength>(
+            "LE_Read_Maximum_Data_Length",
+            &[],
+            &[
+                STATUS,
+                field!("Supported_Max_TX_Octets", U16),
+                field!("Supported_Max_TX_Time", U16),
+                field!("Supported_Max_RX_Octets", U16),
+                field!("Supported_Max_RX_Time", U16),
+            ],
+        ),
+        D::command::<le::commands::ReadPHY>(
+            "LE_Read_PHY",
+            &[CONNECTION_HANDLE],
+            &[
+                STATUS,
+                CONNECTION_HANDLE,
+                field!("TX_PHY", U8),
+                field!("RX_PHY", U8),
+            ],
+        ),
+        D::command::<le::commands::SetDefaultPHY>(
+            "LE_Set_Default_PHY",
+            &[
+                field!("All_PHYs", U8),
+                field!("TX_PHYs", U8),
+                field!("RX_PHYs", U8),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::SetPHY>(
+            "LE_Set_PHY",
+            &[
+                CONNECTION_HANDLE,
+                field!("All_PHYs", U8),
+                field!("TX_PHYs", U8),
+                field!("RX_PHYs", U8),
+                field!("PHY_Options", U16),
+            ],
+            &[],
+        ),
+        D::command::<le::commands::SetPrivacyMode>(
+            "LE_Set_Privacy_Mode",
+            &[
+                field!("Peer_Identity_Address_Type", U8),
+                field!("Peer_Identity_Address", Address),
+                field!("Privacy_Mode", U8),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ReceiverTest>(
+            "LE_Receiver_Test",
+            &[field!("RX_Channel", U8)],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::TransmitterTest>(
+            "LE_Transmitter_Test",
+            &[
+                field!("TX_Channel", U8),
+                field!("Test_Data_Length", U8),
+                field!("Packet_Payload", U8),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ReceiverTestV2>(
+            "LE_Receiver_Test_V2",
+            &[
+                field!("RX_Channel", U8),
+                field!("PHY", U8),
+                field!("Modulation_Index", U8),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::TransmitterTestV2>(
+            "LE_Transmitter_Test_V2",
+            &[
+                field!("TX_Channel", U8),
+                field!("Test_Data_Length", U8),
+                field!("Packet_Payload", U8),
+                field!("PHY", U8),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::ReceiverTestV3>(
+            "LE_Receiver_Test_V3",
+            &[
+                field!("RX_Channel", U8),
+                field!("PHY", U8),
+                field!("Modulation_Index", U8),
+                field!("Expected_CTE_Length", U8),
+                field!("Expected_CTE_Type", U8),
+                field!("Slot_Durations", U8),
+                field!("Switching_Pattern_Length", U8),
+                field!("Antenna_IDs", Rest),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::TransmitterTestV3>(
+            "LE_Transmitter_Test_V3",
+            &[
+                field!("TX_Channel", U8),
+                field!("Test_Data_Length", U8),
+                field!("Packet_Payload", U8),
+                field!("PHY", U8),
+                field!("CTE_Length", U8),
+                field!("CTE_Type", U8),
+                field!("Switching_Pattern_Length", U8),
+                field!("Antenna_IDs", Rest),
+            ],
+            STATUS_ONLY,
+        ),
+        D::command::<le::commands::TestEnd>(
+            "LE_Test_End",
+            &[],
+            &[STATUS, field!("Num_Packets", U16)],
+        ),
+        D::command::<le::commands::PeriodicAdvertisingSyncTransfer>(
+            "LE_Periodic_Advertising_Sync_Transfer",
+            &[CONNECTION_HANDLE, field!("Service_Data", U16), SYNC_HANDLE],
+            HANDLE_RETURN,
+        ),
+        D::command::<le::commands::SetPeriodicAdvertisingSyncTransferParameters>(
+            "LE_Set_Periodic_Advertising_Sync_Transfer_Parameters",
+            &[
+                CONNECTION_HANDLE,
+                field!("Mode", U8),
+                field!("Skip", U16),
+                field!("Sync_Timeout", U16),
+                field!("CTE_Type", U8),
+            ],
+            HANDLE_RETURN,
+        ),
+        D::command::<le::commands::SetPathLossReportingParameters>(
+            "LE_Set_Path_Loss_Reporting_Parameters",
+            &[
+                CONNECTION_HANDLE,
+                field!("High_Threshold", U8),
+                field!("High_Hysteresis", U8),
+                field!("Low_Threshold", U8),
+                field!("Low_Hysteresis", U8),
+                field!("Min_Time_Spent", U16),
+            ],
+            HANDLE_RETURN,
+        ),
+        D::command::<le::commands::SetPathLossReportingEnable>(
+            "LE_Set_Path_Loss_Reporting_Enable",
+            &[CONNECTION_HANDLE, field!("Enable", U8)],
+            HANDLE_RETURN,
+        ),
+        D::command::<le::commands::BIGCreateSync>(
+            "LE_BIG_Create_Sync",
+            &[
+                field!("BIG_Handle", U8),
+                field!("Sync_Handle", U16),
+                field!("Encryption", U8),
+                field!("Broadcast_Code", Bytes(16)),
+                field!("MSE", U8),
+                field!("BIG_Sync_Timeout", U16),
+                field!("Num_BIS", U8),
+                field!("BIS", Rest),
+            ],
+            &[],
+        ),
+        D::command::<le::commands::BIGTerminateSync>(
+            "LE_BIG_Terminate_Sync",
+            &[field!("BIG_Handle", U8)],
+            &[STATUS, field!("BIG_Handle", U8)],
+        ),
+        D::command::<le::host_feature::SetHostFeature>(
+            "LE_Set_Host_Feature",
+            &[field!("Bit_Number", U8), field!("Bit_Value", U8)],
+            STATUS_ONLY,
+        ),
+        // Events.
+        D::event(
+            EventCode::CommandComplete,
+            "Command_Complete",
+            &[
+                field!("Num_HCI_Command_Packets", U8),
+                field!("Command_Opcode", U16),
+                field!("Return_Parameters", Rest),
+            ],
+        ),
+        D::event(
+            EventCode::CommandStatus,
+            "Command_Status",
+            &[
+                STATUS,
+                field!("Num_HCI_Command_Packets", U8),
+                field!("Command_Opcode", U16),
+            ],
+        ),
+        D::event(
+            EventCode::DisconnectionComplete,
+            "Disconnection_Complete",
+            &[STATUS, CONNECTION_HANDLE, field!("Reason", U8)],
+        ),
+        D::event(
+            EventCode::AuthenticationComplete,
+            "Authentication_Complete",
+            HANDLE_RETURN,
+        ),
+        D::event(
+            EventCode::ReadRemoteVersionInformationComplete,
+            "Read_Remote_Version_Information_Complete",
+            &[
+                STATUS,
+                CONNECTION_HANDLE,
+                field!("Version", U8),
+                field!("Company_Identifier", U16),
+                field!("Subversion", U16),
+            ],
+        ),
+        D::event(
+            EventCode::EncryptionChange,
+            "Encryption_Change",
+            &[STATUS, CONNECTION_HANDLE, field!("Encryption_Enabled", U8)],
+        ),
+        D::event(
+            EventCode::EncryptionKeyRefreshComplete,
+            "Encryption_Key_Refresh_Complete",
+            HANDLE_RETURN,
+        ),
+        D::event(
+            EventCode::FlushOccurred,
+            "Flush_Occurred",
+            &[CONNECTION_HANDLE],
+        ),
+        D::event(
+            EventCode::NumberOfCompletedPackets,
+            "Number_Of_Completed_Packets",
+            &[field!("Num_Handles", U8), field!("Handles", Rest)],
+        ),
+        D::event(EventCode::PINCodeRequest, "PIN_Code_Request", &[BD_ADDR]),
+        D::event(EventCode::LinkKeyRequest, "Link_Key_Request", &[BD_ADDR]),
+        D::event(
+            EventCode::LinkKeyNotification,
+            "Link_Key_Notification",
+            &[
+                BD_ADDR,
+                field!("Link_Key", Bytes(16)),
+                field!("Key_Type", U8),
+            ],
+        ),
+        D::event(
+            EventCode::EnhancedFlushComplete,
+            "Enhanced_Flush_Complete",
+            &[CONNECTION_HANDLE],
+        ),
+        D::event(
+            EventCode::NumberOfCompletedDataBlocks,
+            "Number_Of_Completed_Data_Blocks",
+            &[
+                field!("Total_Num_Data_Blocks", U16),
+                field!("Num_Handles", U8),
+                field!("Handles", Rest),
+            ],
+        ),
+        // LE Meta events.
+        D::meta_event(
+            MetaEventCode::ConnectionComplete,
+            "LE_Connection_Complete",
+            &[
+                STATUS,
+                CONNECTION_HANDLE,
+                field!("Role", U8),
+                field!("Peer_Address_Type", U8),
+                field!("Peer_Address", Address),
+                field!("Connection_Interval", U16),
+                field!("Peripheral_Latency", U16),
+                field!("Supervision_Timeout", U16),
+                field!("Central_Clock_Accuracy", U8),
+            ],
+        ),
+        D::meta_event(
+            MetaEventCode::ConnectionUpdateComplete,
+            "LE_Connection_Update_Complete",
+            &[
+                STATUS,
+                CONNECTION_HANDLE,
+                field!("Connection_Interval", U16),
+                field!("Peripheral_Latency", U16),
+                field!("Supervision_Timeout", U16),
+            ],
+        ),
+        D::meta_event(
+            MetaEventCode::AdvertisingReport,
+            "LE_Advertising_Report",
+            &[field!("Num_Reports", U8), field!("Reports", Rest)],
+        ),
+        D::meta_event(
+            MetaEventCode::ReadRemoteFeatures,
+            "LE_Read_Remote_Features_Complete",
+            &[STATUS, CONNECTION_HANDLE, field!("LE_Features", U64)],
+        ),
+        D::meta_event(
+            MetaEventCode::LongTermKeyRequest,
+            "LE_Long_Term_Key_Request",
+            &[
+                CONNECTION_HANDLE,
+                field!("Random_Number", U64),
+                field!("Encrypted_Diversifier", U16),
+            ],
+        ),
+        D::meta_event(
+            MetaEventCode::DataLengthChange,
+            "LE_Data_Length_Change",
+            &[
+                CONNECTION_HANDLE,
+                field!("Max_TX_Octets", U16),
+                field!("Max_TX_Time", U16),
+                field!("Max_RX_Octets", U16),
+                field!("Max_RX_Time", U16),
+            ],
+        ),
+        D::meta_event(
+            MetaEventCode::EnhancedConnectionComplete,
+            "LE_Enhanced_Connection_Complete",
+            &[
+                STATUS,
+                CONNECTION_HANDLE,
+                field!("Role", U8),
+                field!("Peer_Address_Type", U8),
+                field!("Peer_Address", Address),
+                field!("Local_Resolvable_Private_Address", Address),
+                field!("Peer_Resolvable_Private_Address", Address),
+                field!("Connection_Interval", U16),
+                field!("Peripheral_Latency", U16),
+                field!("Supervision_Timeout", U16),
+                field!("Central_Clock_Accuracy", U8),
+            ],
+        ),
+        D::meta_event(
+            MetaEventCode::PHYUpdateCompleteEvent,
+            "LE_PHY_Update_Complete",
+            &[
+                STATUS,
+                CONNECTION_HANDLE,
+                field!("TX_PHY", U8),
+                field!("RX_PHY", U8),
+            ],
+        ),
+        D::meta_event(
+            MetaEventCode::ExtendedAdvertisingReport,
+            "LE_Extended_Advertising_Report",
+            &[field!("Num_Reports", U8), field!("Reports", Rest)],
+        ),
+        D::meta_event(
+            MetaEventCode::PeriodicAdvertisingSyncEstablished,
+            "LE_Periodic_Advertising_Sync_Established",
+            &[
+                STATUS,
+                SYNC_HANDLE,
+                field!("Advertising_SID", U8),
+                field!("Advertiser_Address_Type", U8),
+                field!("Advertiser_Address", Address),
+                field!("Advertiser_PHY", U8),
+                field!("Periodic_Advertising_Interval", U16),
+                field!("Advertiser_Clock_Accuracy", U8),
+            ],
+        ),
+        D::meta_event(
+            MetaEventCode::PeriodicAdvertisingSyncLost,
+            "LE_Periodic_Advertising_Sync_Lost",
+            &[SYNC_HANDLE],
+        ),
+        D::meta_event(
+            MetaEventCode::PeriodicAdvertisingSyncTransferReceived,
+            "LE_Periodic_Advertising_Sync_Transfer_Received",
+            &[
+                STATUS,
+                CONNECTION_HANDLE,
+                field!("Service_Data", U16),
+                SYNC_HANDLE,
+                field!("Advertising_SID", U8),
+                field!("Advertiser_Address_Type", U8),
+                field!("Advertiser_Address", Address),
+                field!("Advertiser_PHY", U8),
+                field!("Periodic_Advertising_Interval", U16),
+                field!("Advertiser_Clock_Accuracy", U8),
+            ],
+        ),
+        D::meta_event(
+            MetaEventCode::PathLossThreshold,
+            "LE_Path_Loss_Threshold",
+            &[
+                CONNECTION_HANDLE,
+                field!("Current_Path_Loss", U8),
+                field!("Zone_Entered", U8),
+            ],
+        ),
+        D::meta_event(
+            MetaEventCode::BIGSyncEstablished,
+            "LE_BIG_Sync_Established",
+            &[
+                STATUS,
+                field!("BIG_Handle", U8),
+                field!("Transport_Latency_BIG", U24),
+                field!("NSE", U8),
+                field!("BN", U8),
+                field!("PTO", U8),
+                field!("IRC", U8),
+                field!("Max_PDU", U16),
+                field!("ISO_Interval", U16),
+                field!("Num_BIS", U8),
+                field!("Connection_Handles", Rest),
+            ],
+        ),
+    ];
+    #[cfg(feature = "classic")]
+    {
+        use crate::classic::inquiry::{Inquiry, InquiryCancel};
+        use crate::classic::settings::{
+            WriteClassOfDevice, WriteInquiryScanActivity, WritePageScanActivity, WriteScanEnable,
+            WriteSimplePairingMode,
+        };
+        out.push(D::command::<WriteScanEnable>(
+            "Write_Scan_Enable",
+            &[field!("Scan_Enable", U8)],
+            STATUS_ONLY,
+        ));
+        out.push(D::command::<WritePageScanActivity>(
+            "Write_Page_Scan_Activity",
+            &[
+                field!("Page_Scan_Interval", U16),
+                field!("Page_Scan_Window", U16),
+            ],
+            STATUS_ONLY,
+        ));
+        out.push(D::command::<WriteInquiryScanActivity>(
+            "Write_Inquiry_Scan_Activity",
+            &[
+                field!("Inquiry_Scan_Interval", U16),
+                field!("Inquiry_Scan_Window", U16),
+            ],
+            STATUS_ONLY,
+        ));
+        out.push(D::command::<WriteClassOfDevice>(
+            "Write_Class_Of_Device",
+            &[field!("Class_Of_Device", U24)],
+            STATUS_ONLY,
+        ));
+        out.push(D::command::<WriteSimplePairingMode>(
+            "Write_Simple_Pairing_Mode",
+            &[field!("Simple_Pairing_Mode", U8)],
+            STATUS_ONLY,
+        ));
+        out.push(D::command::<Inquiry>(
+            "Inquiry",
+            &[
+                field!("LAP", U24),
+                field!("Inquiry_Length", U8),
+                field!("Num_Responses", U8),
+            ],
+            &[],
+        ));
+        out.push(D::command::<InquiryCancel>(
+            "Inquiry_Cancel",
+            &[],
+            STATUS_ONLY,
+        ));
+        out.push(D::event(
+            EventCode::InquiryComplete,
+            "Inquiry_Complete",
+            STATUS_ONLY,
+        ));
+    }
+    out
+}
+/// Description of the command with `opcode`.
+pub fn find_command(opcode: Opcode) -> Option<MessageDescription> {
+    descriptions()
+        .into_iter()
+        .find(|d| d.id == MessageId::Command(opcode))
+}
+/// Description of the event with `event_code`. LE Meta events are looked up by the subevent code
+/// (first byte of `parameters`); decode them from `parameters[1..]`.
+pub fn find_event(event_code: EventCode, parameters: &[u8]) -> Option<MessageDescription> {
+    let id = if event_code == EventCode::LEMeta {
+        use core::convert::TryFrom;
+        MessageId::MetaEvent(MetaEventCode::try_from(*parameters.first()?).ok()?)
+    } else {
+        MessageId::Event(event_code)
+    };
+    descriptions().into_iter().find(|d| d.id == id)
+}
+/// Every described command and event and whether a controller of `version` should support it.
+pub fn support_matrix(version: CoreVersion) -> Vec<(MessageDescription, bool)> {
+    descriptions()
+        .into_iter()
+        .map(|d| (d, d.is_supported_by(version)))
+        .collect()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use alloc::string::ToString;
+    #[test]
+    fn test_descriptions() {
+        let descriptions = descriptions();
+        for (i, d) in descriptions.iter().enumerate() {
+            assert!(
+                descriptions[i + 1..].iter().all(|other| other.id != d.id),
+                "{} described twice",
+                d.name
+            );
+            // Only the last field can be `Rest`.
+            for fields in [d.parameters, d.return_parameters].iter() {
+                let rest = fields.iter().position(|f| f.kind == FieldKind::Rest);
+                assert!(rest.map_or(true, |i| i + 1 == fields.len()), "{}", d.name);
+            }
+        }
+
+        let parameters = crate::le::scan::ScanParameters::DEFAULT;
+        let packet = le::commands::SetScanParameters(parameters)
+            .pack_command_packet::<Box<[u8]>>()
+            .unwrap();
+        let d = find_command(packet.opcode).unwrap();
+        assert_eq!(d.name, "LE_Set_Scan_Parameters");
+        assert_eq!(d.parameters_len(), Some(packet.parameters.len()));
+        let fields = d.decode(&packet.parameters).unwrap();
+        assert_eq!(fields[1].0.name, "LE_Scan_Interval");
+        assert_eq!(
+            fields[1].1,
+            FieldValue::Unsigned(u16::from(parameters.scan_interval).into())
+        );
+        assert!(d.decode(&packet.parameters[1..]).is_err());
+
+        let event = [0x0A, 0x00, 0x40, 0x00, 0x01, 0x00, 0x11, 0x22];
+        let d = find_event(EventCode::LEMeta, &event).unwrap();
+        assert_eq!(d.name, "LE_Enhanced_Connection_Complete");
+        let complete = [0x01, 0x03, 0x0C, 0x00];
+        let d = find_event(EventCode::CommandComplete, &complete).unwrap();
+        let fields = d.decode(&complete).unwrap();
+        assert_eq!(fields[2].1.to_string(), "00");
+
+        let matrix = support_matrix(CoreVersion::V4_2);
+        let (periodic, supported) = matrix
+            .iter()
+            .find(|(d, _)| d.name == "LE_Set_Periodic_Advertising_Enable")
+            .unwrap();
+        assert_eq!(periodic.min_version, Some(CoreVersion::V5_0));
+        assert!(!supported);
+    }
+    #[test]
+    fn test_min_versions() {
+        use CoreVersion::*;
+        let expected = [
+            (0x0C03, V1_0B), // Reset
+            (0x0C01, V1_0B), // Set_Event_Mask
+            (0x0C08, V1_0B), // Flush
+            (0x0C14, V1_0B), // Read_Local_Name
+            (0x0C13, V1_0B), // Write_Local_Name
+            (0x0C5F, V2_1),  // Enhanced_Flush
+            (0x0C27, V1_0B), // Read_Automatic_Flush_Timeout
+            (0x0C28, V1_0B), // Write_Automatic_Flush_Timeout
+            (0x0C2D, V1_0B), // Read_Transmit_Power_Level
+            (0x0C66, V3_0),  // Read_Flow_Control_Mode
+            (0x0C67, V3_0),  // Write_Flow_Control_Mode
+            (0x1009, V1_0B), // Read_BD_ADDR
+            (0x1002, V1_2),  // Read_Local_Supported_Commands
+            (0x1003, V1_0B), // Read_Local_Supported_Features
+            (0x1001, V1_0B), // Read_Local_Version_Information
+            (0x1005, V1_0B), // Read_Buffer_Size
+            (0x100A, V3_0),  // Read_Data_Block_Size
+            (0x1405, V1_0B), // Read_RSSI
+            (0x1801, V1_0B), // Read_Loopback_Mode
+            (0x1802, V1_0B), // Write_Loopback_Mode
+            (0x1803, V1_0B), // Enable_Device_Under_Test_Mode
+            (0x040B, V1_0B), // Link_Key_Request_Reply
+            (0x040C, V1_0B), // Link_Key_Request_Negative_Reply
+            (0x040D, V1_0B), // PIN_Code_Request_Reply
+            (0x040E, V1_0B), // PIN_Code_Request_Negative_Reply
+            (0x0411, V1_0B), // Authentication_Requested
+            (0x0406, V1_0B), // Disconnect
+            (0x041D, V1_0B), // Read_Remote_Version_Information
+            (0x2001, V4_0),  // LE_Set_Event_Mask
+            (0x2002, V4_0),  // LE_Read_Buffer_Size
+            (0x2060, V5_2),  // LE_Read_Buffer_Size_V2
+            (0x2018, V4_0),  // LE_Rand
+            (0x2003, V4_0),  // LE_Read_Local_Supported_Features
+            (0x2016, V4_0),  // LE_Read_Remote_Features
+            (0x2017, V4_0),  // LE_Encrypt
+            (0x2019, V4_0),  // LE_Enable_Encryption
+            (0x201A, V4_0),  // LE_Long_Term_Key_Request_Reply
+            (0x201B, V4_0),  // LE_Long_Term_Key_Request_Negative_Reply
+            (0x2006, V4_0),  // LE_Set_Advertising_Parameters
+            (0x2007, V4_0),  // LE_Read_Advertising_Physical_Channel_Tx_Power
+            (0x2008, V4_0),  // LE_Set_Advertising_Data
+            (0x2009, V4_0),  // LE_Set_Scan_Response_Data
+            (0x200A, V4_0),  // LE_Set_Advertising_Enable
+            (0x200B, V4_0),  // LE_Set_Scan_Parameters
+            (0x200C, V4_0),  // LE_Set_Scan_Enable
+            (0x200D, V4_0),  // LE_Create_Connection
+            (0x200E, V4_0),  // LE_Create_Connection_Cancel
+            (0x2013, V4_0),  // LE_Connection_Update
+            (0x2014, V4_0),  // LE_Set_Host_Channel_Classification
+            (0x2015, V4_0),  // LE_Read_Channel_Map
+            (0x2010, V4_0),  // LE_Clear_Filter_Accept_List
+            (0x200F, V4_0),  // LE_Read_Filter_Accept_List_Size
+            (0x2011, V4_0),  // LE_Add_Device_To_Filter_Accept_List
+            (0x2012, V4_0),  // LE_Remove_Device_From_Filter_Accept_List
+            (0x2036, V5_0),  // LE_Set_Extended_Advertising_Parameters
+            (0x2037, V5_0),  // LE_Set_Extended_Advertising_Data
+            (0x2038, V5_0),  // LE_Set_Extended_Scan_Response_Data
+            (0x2039, V5_0),  // LE_Set_Extended_Advertising_Enable
+            (0x203A, V5_0),  // LE_Read_Maximum_Advertising_Data_Length
+            (0x203B, V5_0),  // LE_Read_Number_of_Supported_Advertising_Sets
+            (0x203C, V5_0),  // LE_Remove_Advertising_Set
+            (0x203D, V5_0),  // LE_Clear_Advertising_Sets
+            (0x203E, V5_0),  // LE_Set_Periodic_Advertising_Parameters
+            (0x203F, V5_0),  // LE_Set_Periodic_Advertising_Data
+            (0x2040, V5_0),  // LE_Set_Periodic_Advertising_Enable
+            (0x2041, V5_0),  // LE_Set_Extended_Scan_Parameters
+            (0x2042, V5_0),  // LE_Set_Extended_Scan_Enable
+            (0x2043, V5_0),  // LE_Extended_Create_Connection
+            (0x2044, V5_0),  // LE_Periodic_Advertising_Create_Sync
+            (0x2045, V5_0),  // LE_Periodic_Advertising_Create_Sync_Cancel
+            (0x2046, V5_0),  // LE_Periodic_Advertising_Terminate_Sync
+            (0x2047, V5_0),  // LE_Add_Device_To_Periodic_Advertiser_List
+            (0x2048, V5_0),  // LE_Remove_Device_From_Periodic_Advertiser_List
+            (0x2049, V5_0),  // LE_Clear_Periodic_Advertiser_List
+            (0x2022, V4_2),  // LE_Set_Data_Length
+            (0x2023, V4_2),  // LE_Read_Suggested_Default_Data_Length
+            (0x2024, V4_2),  // LE_Write_Suggested_Default_Data_Length
+            (0x2027, V4_2),  // LE_Add_Device_To_Resolving_List
+            (0x2028, V4_2),  // LE_Remove_Device_From_Resolving_List
+            (0x2029, V4_2),  // LE_Clear_Resolving_List
+            (0x202A, V4_2),  // LE_Read_Resolving_List_Size
+            (0x202D, V4_2),  // LE_Set_Address_Resolution_Enable
+            (0x202E, V4_2),  // LE_Set_Resolvable_Private_Address_Timeout
+            (0x202F, V4_2),  // LE_Read_Maximum_Data_Length
+            (0x2030, V5_0),  // LE_Read_PHY
+            (0x2031, V5_0),  // LE_Set_Default_PHY
+            (0x2032, V5_0),  // LE_Set_PHY
+            (0x204E, V5_0),  // LE_Set_Privacy_Mode
+            (0x201D, V4_0),  // LE_Receiver_Test
+            (0x201E, V4_0),  // LE_Transmitter_Test
+            (0x2033, V5_0),  // LE_Receiver_Test_V2
+            (0x2034, V5_0),  // LE_Transmitter_Test_V2
+            (0x204F, V5_1),  // LE_Receiver_Test_V3
+            (0x2050, V5_1),  // LE_Transmitter_Test_V3
+            (0x201F, V4_0),  // LE_Test_End
+            (0x205A, V5_1),  // LE_Periodic_Advertising_Sync_Transfer
+            (0x205C, V5_1),  // LE_Set_Periodic_Advertising_Sync_Transfer_Parameters
+            (0x2078, V5_2),  // LE_Set_Path_Loss_Reporting_Parameters
+            (0x2079, V5_2),  // LE_Set_Path_Loss_Reporting_Enable
+            (0x206B, V5_2),  // LE_BIG_Create_Sync
+            (0x206C, V5_2),  // LE_BIG_Terminate_Sync
+            (0x2074, V5_2),  // LE_Set_Host_Feature
+        ];
+        #[cfg(feature = "classic")]
+        let expected = [
+            &expected[..],
+            &[
+                (0x0C1A, V1_0B), // Write_Scan_Enable
+                (0x0C1C, V1_0B), // Write_Page_Scan_Activity
+                (0x0C1E, V1_0B), // Write_Inquiry_Scan_Activity
+                (0x0C24, V1_0B), // Write_Class_Of_Device
+                (0x0C56, V2_1),  // Write_Simple_Pairing_Mode
+                (0x0401, V1_0B), // Inquiry
+                (0x0402, V1_0B), // Inquiry_Cancel
+            ],
+        ]
+        .concat();
+        let commands: Vec<_> = descriptions()
+            .into_iter()
+            .filter_map(|d| match d.id {
+                MessageId::Command(opcode) => Some((u16::from(opcode), d)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(commands.len(), expected.len());
+        for (opcode, d) in commands {
+            let version = expected
+                .iter()
+                .find(|(o, _)| *o == opcode)
+                .map(|(_, version)| *version);
+            assert_eq!(d.min_version, version, "{}", d.name);
+        }
+    }
+}
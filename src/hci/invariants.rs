@@ -0,0 +1,507 @@
+//! Pack/unpack invariants for HCI messages. Every message type must satisfy
+//! `unpack(pack(x)) == x`, reject buffers of the wrong length and pack back to the same bytes it
+//! was unpacked from (after one normalizing round trip). [`fuzz_message`] runs the byte level
+//! checks against every known message type and is what the `fuzz/` targets call.
+#[cfg(feature = "classic")]
+use crate::classic::{inquiry, settings};
+use crate::hci::command::Command;
+use crate::hci::event::{Event, ReturnParameters};
+#[cfg(feature = "channel_sounding")]
+use crate::hci::le::cs;
+use crate::hci::le::{
+    advertise, big, channel_map, connection, data_length, encryption, extended_advertise,
+    extended_connection, extended_scan, features, host_feature, mask, path_loss,
+    periodic_advertise, periodic_sync, phy, random, resolving_list, scan, sync_transfer, test_mode,
+    whitelist, MetaEvent,
+};
+use crate::hci::packet::Packet;
+use crate::hci::{
+    acl, baseband, event, flow_control, informational, iso, link_control, status, testing,
+};
+use crate::PackError;
+use alloc::vec::Vec;
+
+/// A broken pack/unpack invariant.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum InvariantError {
+    /// Packing a value failed.
+    Pack(PackError),
+    /// Unpacking bytes that were just packed failed.
+    Unpack(PackError),
+    /// Packing into a buffer of `len` bytes succeeded but `byte_len()` is `expected`.
+    AcceptedBadLength { expected: usize, len: usize },
+    /// `unpack(pack(x)) != x`.
+    ValueMismatch,
+    /// Repacking an unpacked value gave different bytes.
+    BytesMismatch,
+}
+impl crate::error::Error for InvariantError {}
+/// Pack/unpack functions of one message type. Lets commands, return parameters, meta events and
+/// packets share the same checks.
+pub struct Codec<T> {
+    pub byte_len: fn(&T) -> usize,
+    pub pack_into: fn(&T, &mut [u8]) -> Result<(), PackError>,
+    pub unpack_from: fn(&[u8]) -> Result<T, PackError>,
+}
+impl<T> Clone for Codec<T> {
+    fn clone(&self) -> Self {
+        Codec {
+            byte_len: self.byte_len,
+            pack_into: self.pack_into,
+            unpack_from: self.unpack_from,
+        }
+    }
+}
+impl<T> Copy for Codec<T> {}
+impl<T: Command> Codec<T> {
+    pub fn command() -> Self {
+        Codec {
+            byte_len: T::byte_len,
+            pack_into: T::pack_into,
+            unpack_from: T::unpack_from,
+        }
+    }
+}
+impl<T: ReturnParameters> Codec<T> {
+    pub fn return_parameters() -> Self {
+        Codec {
+            byte_len: T::byte_len,
+            pack_into: T::pack_into,
+            unpack_from: T::unpack_from,
+        }
+    }
+}
+impl<T: Event> Codec<T> {
+    pub fn event() -> Self {
+        Codec {
+            byte_len: T::event_byte_len,
+            pack_into: T::event_pack_into,
+            unpack_from: T::event_unpack_from,
+        }
+    }
+}
+impl<T: MetaEvent> Codec<T> {
+    pub fn meta_event() -> Self {
+        Codec {
+            byte_len: T::meta_byte_len,
+            pack_into: T::meta_pack_into,
+            unpack_from: T::meta_unpack_from,
+        }
+    }
+}
+impl<T: Packet> Codec<T> {
+    pub fn packet() -> Self {
+        Codec {
+            byte_len: T::packet_byte_len,
+            pack_into: T::packet_pack_into,
+            unpack_from: T::packet_unpack_from,
+        }
+    }
+}
+impl<T> Codec<T> {
+    /// Pack `value`, checking that `byte_len()` bytes (and only `byte_len()` bytes) are accepted.
+    pub fn pack(self, value: &T) -> Result<Vec<u8>, InvariantError> {
+        let len = (self.byte_len)(value);
+        let mut buf = Vec::with_capacity(len + 1);
+        buf.resize(len + 1, 0_u8);
+        if (self.pack_into)(value, &mut buf[..]).is_ok() {
+            return Err(InvariantError::AcceptedBadLength {
+                expected: len,
+                len: len + 1,
+            });
+        }
+        if len > 0 && (self.pack_into)(value, &mut buf[..len - 1]).is_ok() {
+            return Err(InvariantError::AcceptedBadLength {
+                expected: len,
+                len: len - 1,
+            });
+        }
+        buf.truncate(len);
+        (self.pack_into)(value, &mut buf[..]).map_err(InvariantError::Pack)?;
+        Ok(buf)
+    }
+    /// Check `unpack(pack(value)) == value`. Returns the packed bytes.
+    pub fn round_trip(self, value: &T) -> Result<Vec<u8>, InvariantError>
+    where
+        T: PartialEq,
+    {
+        let bytes = self.pack(value)?;
+        let unpacked = (self.unpack_from)(&bytes[..]).map_err(InvariantError::Unpack)?;
+        if &unpacked != value {
+            return Err(InvariantError::ValueMismatch);
+        }
+        if !bytes.is_empty() && (self.unpack_from)(&bytes[..bytes.len() - 1]).is_ok() {
+            return Err(InvariantError::AcceptedBadLength {
+                expected: bytes.len(),
+                len: bytes.len() - 1,
+            });
+        }
+        Ok(bytes)
+    }
+    /// Byte level check for arbitrary (fuzzed) input. Bytes that don't unpack are ignored.
+    /// Otherwise the unpacked value must pack, and unpacking and repacking those bytes must give
+    /// the same bytes again.
+    pub fn check_bytes(self, buf: &[u8]) -> Result<(), InvariantError> {
+        let value = match (self.unpack_from)(buf) {
+            Ok(value) => value,
+            Err(_) => return Ok(()),
+        };
+        let bytes = self.pack(&value)?;
+        let again = (self.unpack_from)(&bytes[..]).map_err(InvariantError::Unpack)?;
+        if self.pack(&again)? != bytes {
+            return Err(InvariantError::BytesMismatch);
+        }
+        Ok(())
+    }
+}
+/// `[Codec::<$ty>::$codec().check_bytes, ..]` without repeating the closure for every type.
+macro_rules! fuzz_checks {
+    ($($(#[$cfg:meta])* $codec:ident $ty:ty,)*) => {
+        &[$($(#[$cfg])* |buf| Codec::<$ty>::$codec().check_bytes(buf),)*]
+    };
+}
+/// [`Codec::check_bytes`] of every message type, indexed by the [`fuzz_message`] selector. New
+/// types go at the end (before the feature gated ones) so existing fuzz corpora keep selecting
+/// the same types.
+const FUZZ_CHECKS: &[fn(&[u8]) -> Result<(), InvariantError>] = fuzz_checks! {
+    command baseband::Reset,
+    command baseband::SetEventMask,
+    command advertise::SetAdvertisingEnable,
+    command advertise::SetAdvertisingData,
+    command advertise::SetAdvertisingParameters,
+    command advertise::ReadAdvertisingChannelTxPower,
+    command scan::SetScanEnable,
+    command scan::SetScanParameters,
+    command scan::SetScanResponseData,
+    command mask::SetMetaEventMask,
+    command random::Rand,
+    command connection::ReadBufferSizeV1,
+    command connection::ReadBufferSizeV2,
+    command big::BIGCreateSync,
+    command big::BIGTerminateSync,
+    return_parameters event::StatusReturn,
+    return_parameters advertise::TxPowerLevelReturn,
+    return_parameters random::RandReturn,
+    return_parameters connection::BufferSizeV1,
+    return_parameters connection::BufferSizeV2,
+    return_parameters big::BIGTerminateSyncReturn,
+    meta_event big::BIGSyncEstablished,
+    packet iso::ISODataPacket<Vec<u8>>,
+    command informational::ReadLocalVersionInformation,
+    return_parameters informational::LocalVersionInformation,
+    command baseband::Flush,
+    command baseband::EnhancedFlush,
+    command baseband::ReadAutomaticFlushTimeout,
+    command baseband::WriteAutomaticFlushTimeout,
+    command baseband::ReadTransmitPowerLevel,
+    command baseband::WriteLocalName,
+    command baseband::ReadLocalName,
+    command testing::ReadLoopbackMode,
+    command testing::WriteLoopbackMode,
+    command testing::EnableDeviceUnderTestMode,
+    command informational::ReadLocalSupportedCommands,
+    command informational::ReadLocalSupportedFeatures,
+    command informational::ReadBDADDR,
+    command extended_advertise::SetExtendedAdvertisingParameters,
+    command extended_advertise::SetExtendedAdvertisingData,
+    command extended_advertise::SetExtendedScanResponseData,
+    command extended_advertise::SetExtendedAdvertisingEnable,
+    command extended_advertise::ReadMaximumAdvertisingDataLength,
+    command extended_advertise::ReadNumberOfSupportedAdvertisingSets,
+    command extended_advertise::RemoveAdvertisingSet,
+    command extended_advertise::ClearAdvertisingSets,
+    command encryption::StartEncryption,
+    command encryption::LongTermKeyRequestReply,
+    command encryption::LongTermKeyRequestNegativeReply,
+    command data_length::SetDataLength,
+    command data_length::ReadSuggestedDefaultDataLength,
+    command data_length::WriteSuggestedDefaultDataLength,
+    command data_length::ReadMaximumDataLength,
+    command features::ReadLocalFeatures,
+    command features::ReadRemoteFeatures,
+    command whitelist::ClearWhitelist,
+    command whitelist::AddDeviceToWhitelist,
+    command whitelist::RemoveDeviceFromWhitelist,
+    command whitelist::ReadWhitelistSize,
+    command phy::ReadPHY,
+    command phy::SetDefaultPHY,
+    command phy::SetPHY,
+    command extended_connection::ExtendedCreateConnection,
+    command channel_map::SetHostChannelClassification,
+    command channel_map::ReadChannelMap,
+    command test_mode::ReceiverTest,
+    command test_mode::TransmitterTest,
+    command test_mode::ReceiverTestV2,
+    command test_mode::TransmitterTestV2,
+    command test_mode::ReceiverTestV3,
+    command test_mode::TransmitterTestV3,
+    command test_mode::TestEnd,
+    command periodic_sync::PeriodicAdvertisingCreateSync,
+    command periodic_sync::PeriodicAdvertisingCreateSyncCancel,
+    command periodic_sync::PeriodicAdvertisingTerminateSync,
+    command periodic_sync::AddDeviceToPeriodicAdvertiserList,
+    command periodic_sync::RemoveDeviceFromPeriodicAdvertiserList,
+    command periodic_sync::ClearPeriodicAdvertiserList,
+    command path_loss::SetPathLossReportingParameters,
+    command path_loss::SetPathLossReportingEnable,
+    command connection::CreateConnection,
+    command connection::CreateConnectionCancel,
+    command connection::ConnectionUpdate,
+    command periodic_advertise::SetPeriodicAdvertisingParameters,
+    command periodic_advertise::SetPeriodicAdvertisingData,
+    command periodic_advertise::SetPeriodicAdvertisingEnable,
+    command host_feature::SetHostFeature,
+    command extended_scan::SetExtendedScanParameters,
+    command extended_scan::SetExtendedScanEnable,
+    command sync_transfer::PeriodicAdvertisingSyncTransfer,
+    command sync_transfer::SetPeriodicAdvertisingSyncTransferParameters,
+    command resolving_list::AddDeviceToResolvingList,
+    command resolving_list::RemoveDeviceFromResolvingList,
+    command resolving_list::ClearResolvingList,
+    command resolving_list::ReadResolvingListSize,
+    command resolving_list::SetAddressResolutionEnable,
+    command resolving_list::SetResolvablePrivateAddressTimeout,
+    command resolving_list::SetPrivacyMode,
+    command random::Encrypt,
+    command link_control::LinkKeyRequestReply,
+    command link_control::LinkKeyRequestNegativeReply,
+    command link_control::PINCodeRequestReply,
+    command link_control::PINCodeRequestNegativeReply,
+    command link_control::AuthenticationRequested,
+    command link_control::Disconnect,
+    command link_control::ReadRemoteVersionInformation,
+    command status::ReadRSSI,
+    command flow_control::ReadFlowControlMode,
+    command flow_control::WriteFlowControlMode,
+    command flow_control::ReadDataBlockSize,
+    command flow_control::ReadBufferSize,
+    return_parameters baseband::HandleReturn,
+    return_parameters baseband::FlushTimeoutReturn,
+    return_parameters baseband::TransmitPowerLevelReturn,
+    return_parameters baseband::LocalNameReturn,
+    return_parameters testing::LoopbackModeReturn,
+    return_parameters informational::SupportedCommandsReturn,
+    return_parameters informational::LMPFeaturesReturn,
+    return_parameters extended_advertise::MaximumAdvertisingDataLengthReturn,
+    return_parameters extended_advertise::NumberOfSupportedAdvertisingSetsReturn,
+    return_parameters data_length::SuggestedDataLengthReturn,
+    return_parameters data_length::MaximumDataLengthReturn,
+    return_parameters features::LocalFeaturesReturn,
+    return_parameters whitelist::WhitelistSizeReturn,
+    return_parameters phy::ConnectionPHYs,
+    return_parameters channel_map::ChannelMapReturn,
+    return_parameters test_mode::TestEndReturn,
+    return_parameters sync_transfer::SyncTransferReturn,
+    return_parameters resolving_list::ResolvingListSizeReturn,
+    return_parameters random::EncryptReturn,
+    return_parameters link_control::AddressReturn,
+    return_parameters status::RSSIReturn,
+    return_parameters flow_control::FlowControlModeReturn,
+    return_parameters flow_control::DataBlockSize,
+    return_parameters flow_control::BufferSize,
+    event event::CommandStatus,
+    event encryption::EncryptionChange,
+    event encryption::EncryptionKeyRefreshComplete,
+    event link_control::LinkKeyNotification,
+    event link_control::AuthenticationComplete,
+    event link_control::DisconnectionComplete,
+    event link_control::ReadRemoteVersionInformationComplete,
+    event flow_control::NumberOfCompletedPackets,
+    event flow_control::NumberOfCompletedDataBlocks,
+    event baseband::FlushOccurred,
+    event baseband::EnhancedFlushComplete,
+    event link_control::LinkKeyRequest,
+    event link_control::PINCodeRequest,
+    meta_event encryption::LongTermKeyRequest,
+    meta_event data_length::DataLengthChange,
+    meta_event features::ReadRemoteFeaturesComplete,
+    meta_event phy::PHYUpdateComplete,
+    meta_event extended_connection::EnhancedConnectionComplete,
+    meta_event periodic_sync::PeriodicAdvertisingSyncEstablished,
+    meta_event periodic_sync::PeriodicAdvertisingSyncLost,
+    meta_event path_loss::PathLossThreshold,
+    meta_event connection::ConnectionCompleteEvent,
+    meta_event connection::ConnectionUpdateComplete,
+    meta_event sync_transfer::PeriodicAdvertisingSyncTransferReceived,
+    packet acl::ACLDataPacket<Vec<u8>>,
+    #[cfg(feature = "classic")]
+    command inquiry::Inquiry,
+    #[cfg(feature = "classic")]
+    command inquiry::InquiryCancel,
+    #[cfg(feature = "classic")]
+    command settings::WriteScanEnable,
+    #[cfg(feature = "classic")]
+    command settings::WriteClassOfDevice,
+    #[cfg(feature = "classic")]
+    command settings::WriteSimplePairingMode,
+    #[cfg(feature = "channel_sounding")]
+    command cs::CSReadLocalSupportedCapabilities,
+    #[cfg(feature = "channel_sounding")]
+    command cs::CSReadRemoteSupportedCapabilities,
+    #[cfg(feature = "channel_sounding")]
+    command cs::CSCreateConfig,
+    #[cfg(feature = "channel_sounding")]
+    command cs::CSRemoveConfig,
+    #[cfg(feature = "channel_sounding")]
+    command cs::CSSetProcedureParameters,
+    #[cfg(feature = "channel_sounding")]
+    command cs::CSProcedureEnable,
+    #[cfg(feature = "classic")]
+    command settings::WritePageScanActivity,
+    #[cfg(feature = "classic")]
+    command settings::WriteInquiryScanActivity,
+    #[cfg(feature = "channel_sounding")]
+    return_parameters cs::CSLocalCapabilitiesReturn,
+    #[cfg(feature = "channel_sounding")]
+    return_parameters cs::CSConnectionReturn,
+    #[cfg(feature = "classic")]
+    event inquiry::InquiryComplete,
+    #[cfg(feature = "channel_sounding")]
+    meta_event cs::CSReadRemoteSupportedCapabilitiesComplete,
+    #[cfg(feature = "channel_sounding")]
+    meta_event cs::CSConfigComplete,
+    #[cfg(feature = "channel_sounding")]
+    meta_event cs::CSProcedureEnableComplete,
+    #[cfg(feature = "channel_sounding")]
+    meta_event cs::CSSubeventResult,
+    #[cfg(feature = "channel_sounding")]
+    meta_event cs::CSSubeventResultContinue,
+};
+/// Number of message types [`fuzz_message`] can select.
+pub const FUZZ_MESSAGE_COUNT: usize = FUZZ_CHECKS.len();
+/// Run [`Codec::check_bytes`] for the message type selected by the first byte of `data` against
+/// the rest of `data`.
+pub fn fuzz_message(data: &[u8]) -> Result<(), InvariantError> {
+    match data.split_first() {
+        Some((selector, buf)) => FUZZ_CHECKS[usize::from(*selector) % FUZZ_MESSAGE_COUNT](buf),
+        None => Ok(()),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hci::baseband::{Reset, SetEventMask};
+    use crate::hci::le::advertise::{
+        SetAdvertisingData, SetAdvertisingEnable, SetAdvertisingParameters,
+    };
+    use crate::hci::le::big::{BIGCreateSync, BIGSyncEstablished};
+    use crate::hci::le::big::{BIGHandle, SyncHandle};
+    use crate::hci::le::connection::{BufferSizeV2, CreateConnection};
+    use crate::hci::le::mask::SetMetaEventMask;
+    use crate::hci::le::scan::{SetScanParameters, SetScanResponseData};
+    use crate::hci::ErrorCode;
+    use crate::le::advertiser::AdvertisingParameters;
+    use crate::le::advertiser::PeerAddressType;
+    use crate::le::connection::{
+        CELength, ConnectionHandle, ConnectionInterval, ConnectionLatency, InitiatorFilterPolicy,
+        SupervisionTimeout,
+    };
+    use crate::le::scan::{OwnAddressType, ScanInterval, ScanParameters, ScanWindow};
+    use crate::BTAddress;
+    #[test]
+    fn test_round_trips() {
+        Codec::command().round_trip(&Reset).unwrap();
+        Codec::command()
+            .round_trip(&SetEventMask::default())
+            .unwrap();
+        Codec::command()
+            .round_trip(&SetAdvertisingEnable { is_enabled: true })
+            .unwrap();
+        Codec::command()
+            .round_trip(&SetAdvertisingData::new(&[0x02, 0x01, 0x06]))
+            .unwrap();
+        Codec::command()
+            .round_trip(&SetAdvertisingParameters(AdvertisingParameters::DEFAULT))
+            .unwrap();
+        Codec::command()
+            .round_trip(&SetScanParameters(ScanParameters::DEFAULT))
+            .unwrap();
+        Codec::command()
+            .round_trip(&SetScanResponseData::from_slice(&[0x02, 0x0A, 0x00]).unwrap())
+            .unwrap();
+        Codec::command()
+            .round_trip(&SetMetaEventMask::default())
+            .unwrap();
+        let create_sync = BIGCreateSync {
+            big_handle: BIGHandle::new(1),
+            sync_handle: SyncHandle(0x0102),
+            broadcast_code: Some([0xAA; 16]),
+            mse: 0,
+            big_sync_timeout: 0x00C8,
+            bis: vec![1, 2],
+        };
+        Codec::command()
+            .round_trip(&CreateConnection {
+                le_scan_interval: ScanInterval::DEFAULT,
+                le_scan_window: ScanWindow::DEFAULT,
+                initiator_filter_policy: InitiatorFilterPolicy::WhiteList,
+                peer_address_type: PeerAddressType::Random,
+                peer_address: BTAddress([1, 2, 3, 4, 5, 6]),
+                own_address_type: OwnAddressType::Random,
+                connection_interval_min: ConnectionInterval::MIN,
+                connection_interval_max: ConnectionInterval::MAX,
+                connection_latency: ConnectionLatency::MIN,
+                supervision_timeout: SupervisionTimeout::MAX,
+                min_ce_len: CELength(1),
+                max_ce_len: CELength(2),
+            })
+            .unwrap();
+        let bytes = Codec::command().round_trip(&create_sync).unwrap();
+        assert_eq!(&bytes[1..3], &[0x02, 0x01]);
+        Codec::return_parameters()
+            .round_trip(&BufferSizeV2 {
+                status: ErrorCode::Ok,
+                le_acl_data_packet_len: 0x00FB,
+                total_num_le_acl_data_packets: 4,
+                iso_data_packet_len: 0x0100,
+                total_num_iso_data_packets: 8,
+            })
+            .unwrap();
+        Codec::meta_event()
+            .round_trip(&BIGSyncEstablished {
+                status: ErrorCode::Ok,
+                big_handle: BIGHandle::new(1),
+                transport_latency_big: 0x01_2345,
+                nse: 4,
+                bn: 1,
+                pto: 0,
+                irc: 4,
+                max_pdu: 120,
+                iso_interval: 8,
+                connection_handles: vec![ConnectionHandle::new(0x60), ConnectionHandle::new(0x61)],
+            })
+            .unwrap();
+    }
+    #[test]
+    fn test_fuzz_messages() {
+        // xorshift so the inputs are the same every run.
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        // Long enough for the largest fixed length messages (e.g. 248 byte local names).
+        let mut buf = [0_u8; 300];
+        for i in 0..FUZZ_MESSAGE_COUNT * 2_000 {
+            let len = 1 + (next() % (buf.len() - 1) as u64) as usize;
+            buf.iter_mut().for_each(|b| *b = next() as u8);
+            buf[0] = (i % FUZZ_MESSAGE_COUNT) as u8;
+            // Bias some bytes (most length and count fields are near the start) towards small
+            // values so more inputs unpack.
+            if (i / FUZZ_MESSAGE_COUNT) & 1 == 0 {
+                for b in &mut buf[1..4] {
+                    *b %= 8;
+                }
+            }
+            for _ in 0..next() % 6 {
+                let at = 1 + (next() % len as u64) as usize;
+                buf[at] %= 3;
+            }
+            if let Err(e) = fuzz_message(&buf[..len]) {
+                panic!("{:?} for {:02X?}", e, &buf[..len]);
+            }
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! HCI ISO Data packets. Carry isochronous SDUs (or SDU fragments) between the host and
+//! controller for CIS/BIS connections.
+use crate::bytes::Storage;
+use crate::hci::packet::{Packet, PacketType};
+use crate::le::connection::ConnectionHandle;
+use crate::{ConversionError, PackError};
+use alloc::vec::Vec;
+use core::convert::{TryFrom, TryInto};
+
+/// `PB_Flag`. Where the packet data is in the SDU.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum PacketBoundary {
+    FirstFragment = 0b00,
+    ContinuationFragment = 0b01,
+    CompleteSDU = 0b10,
+    LastFragment = 0b11,
+}
+impl PacketBoundary {
+    /// Whether the packet starts a new SDU (and so contains the ISO Data Load header).
+    pub fn is_start(self) -> bool {
+        match self {
+            PacketBoundary::FirstFragment | PacketBoundary::CompleteSDU => true,
+            PacketBoundary::ContinuationFragment | PacketBoundary::LastFragment => false,
+        }
+    }
+    /// Whether the packet is the end of an SDU.
+    pub fn is_end(self) -> bool {
+        match self {
+            PacketBoundary::CompleteSDU | PacketBoundary::LastFragment => true,
+            PacketBoundary::FirstFragment | PacketBoundary::ContinuationFragment => false,
+        }
+    }
+}
+impl From<PacketBoundary> for u8 {
+    fn from(b: PacketBoundary) -> Self {
+        b as u8
+    }
+}
+impl TryFrom<u8> for PacketBoundary {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(PacketBoundary::FirstFragment),
+            0b01 => Ok(PacketBoundary::ContinuationFragment),
+            0b10 => Ok(PacketBoundary::CompleteSDU),
+            0b11 => Ok(PacketBoundary::LastFragment),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// `Packet_Status_Flag` of a received SDU.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum PacketStatus {
+    Valid = 0b00,
+    PossiblyInvalid = 0b01,
+    LostData = 0b10,
+}
+impl Default for PacketStatus {
+    fn default() -> Self {
+        PacketStatus::Valid
+    }
+}
+impl From<PacketStatus> for u8 {
+    fn from(s: PacketStatus) -> Self {
+        s as u8
+    }
+}
+impl TryFrom<u8> for PacketStatus {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(PacketStatus::Valid),
+            0b01 => Ok(PacketStatus::PossiblyInvalid),
+            0b10 => Ok(PacketStatus::LostData),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// ISO Data Load header. Only present in the first fragment (or complete SDU) packet.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct DataLoadHeader {
+    /// Time stamp in microseconds.
+    pub time_stamp: Option<u32>,
+    pub sequence_number: u16,
+    pub sdu_len: u16,
+    pub status: PacketStatus,
+}
+impl DataLoadHeader {
+    pub const MAX_SDU_LEN: u16 = 0x0FFF;
+    pub fn byte_len(&self) -> usize {
+        if self.time_stamp.is_some() {
+            8
+        } else {
+            4
+        }
+    }
+}
+/// HCI ISO Data packet.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ISODataPacket<Buf> {
+    pub handle: ConnectionHandle,
+    pub boundary: PacketBoundary,
+    pub load_header: Option<DataLoadHeader>,
+    pub data: Buf,
+}
+impl<Buf> ISODataPacket<Buf> {
+    pub const HEADER_LEN: usize = 4;
+    pub const MAX_DATA_LOAD_LEN: usize = 0x3FFF;
+}
+impl<Buf: AsRef<[u8]>> ISODataPacket<Buf> {
+    pub fn data_load_len(&self) -> usize {
+        self.load_header.map_or(0, |h| h.byte_len()) + self.data.as_ref().len()
+    }
+}
+impl<Buf: Storage<u8>> Packet for ISODataPacket<Buf> {
+    const PACKET_TYPE: PacketType = PacketType::ISOData;
+
+    fn packet_byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.data_load_len()
+    }
+
+    fn packet_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.packet_byte_len(), buf)?;
+        if self.boundary.is_start() != self.load_header.is_some() {
+            return Err(PackError::InvalidFields);
+        }
+        let ts_flag = self.load_header.map_or(false, |h| h.time_stamp.is_some());
+        let handle = u16::from(self.handle)
+            | (u16::from(u8::from(self.boundary)) << 12)
+            | (u16::from(ts_flag) << 14);
+        let data_load_len =
+            u16::try_from(self.data_load_len()).map_err(|_| PackError::InvalidFields)?;
+        if usize::from(data_load_len) > Self::MAX_DATA_LOAD_LEN {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0..2].copy_from_slice(&handle.to_le_bytes());
+        buf[2..4].copy_from_slice(&data_load_len.to_le_bytes());
+        let mut index = Self::HEADER_LEN;
+        if let Some(header) = self.load_header {
+            if let Some(time_stamp) = header.time_stamp {
+                buf[index..index + 4].copy_from_slice(&time_stamp.to_le_bytes());
+                index += 4;
+            }
+            if header.sdu_len > DataLoadHeader::MAX_SDU_LEN {
+                return Err(PackError::InvalidFields);
+            }
+            buf[index..index + 2].copy_from_slice(&header.sequence_number.to_le_bytes());
+            let sdu_len = header.sdu_len | (u16::from(u8::from(header.status)) << 14);
+            buf[index + 2..index + 4].copy_from_slice(&sdu_len.to_le_bytes());
+            index += 4;
+        }
+        buf[index..].copy_from_slice(self.data.as_ref());
+        Ok(())
+    }
+
+    fn packet_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        let handle = u16::from_le_bytes(buf[0..2].try_into().expect("length checked above"));
+        let data_load_len =
+            u16::from_le_bytes(buf[2..4].try_into().expect("length checked above")) & 0x3FFF;
+        PackError::expect_length(Self::HEADER_LEN + usize::from(data_load_len), buf)?;
+        let boundary = PacketBoundary::try_from(((handle >> 12) & 0b11) as u8)
+            .expect("masked to 2 bits");
+        let ts_flag = handle & (1 << 14) != 0;
+        let handle = ConnectionHandle::new_checked(handle & 0x0FFF).ok_or(PackError::bad_index(0))?;
+        let mut index = Self::HEADER_LEN;
+        let load_header = if boundary.is_start() {
+            let time_stamp = if ts_flag {
+                let ts = buf
+                    .get(index..index + 4)
+                    .ok_or(PackError::bad_index(index))?;
+                index += 4;
+                Some(u32::from_le_bytes(ts.try_into().expect("4 bytes")))
+            } else {
+                None
+            };
+            let header = buf
+                .get(index..index + 4)
+                .ok_or(PackError::bad_index(index))?;
+            let sdu_len = u16::from_le_bytes([header[2], header[3]]);
+            index += 4;
+            Some(DataLoadHeader {
+                time_stamp,
+                sequence_number: u16::from_le_bytes([header[0], header[1]]),
+                sdu_len: sdu_len & DataLoadHeader::MAX_SDU_LEN,
+                status: PacketStatus::try_from((sdu_len >> 14) as u8)
+                    .map_err(|_| PackError::bad_index(index - 1))?,
+            })
+        } else {
+            None
+        };
+        Ok(ISODataPacket {
+            handle,
+            boundary,
+            load_header,
+            data: Buf::from_slice(&buf[index..]),
+        })
+    }
+}
+/// Splits an SDU into ISO Data packets no longer than `max_data_len` (the controller's
+/// `ISO_Data_Packet_Length`).
+pub fn fragment_sdu(
+    handle: ConnectionHandle,
+    header: DataLoadHeader,
+    sdu: &[u8],
+    max_data_len: usize,
+) -> Result<Vec<ISODataPacket<Vec<u8>>>, PackError> {
+    let first_len = max_data_len
+        .checked_sub(header.byte_len())
+        .filter(|&l| l > 0)
+        .ok_or(PackError::InvalidFields)?;
+    if sdu.len() > usize::from(DataLoadHeader::MAX_SDU_LEN) {
+        return Err(PackError::InvalidFields);
+    }
+    let header = DataLoadHeader {
+        sdu_len: u16::try_from(sdu.len()).map_err(|_| PackError::InvalidFields)?,
+        ..header
+    };
+    let mut out = Vec::new();
+    let (first, mut rest) = sdu.split_at(core::cmp::min(first_len, sdu.len()));
+    out.push(ISODataPacket {
+        handle,
+        boundary: if rest.is_empty() {
+            PacketBoundary::CompleteSDU
+        } else {
+            PacketBoundary::FirstFragment
+        },
+        load_header: Some(header),
+        data: first.to_vec(),
+    });
+    while !rest.is_empty() {
+        let (fragment, next) = rest.split_at(core::cmp::min(max_data_len, rest.len()));
+        rest = next;
+        out.push(ISODataPacket {
+            handle,
+            boundary: if rest.is_empty() {
+                PacketBoundary::LastFragment
+            } else {
+                PacketBoundary::ContinuationFragment
+            },
+            load_header: None,
+            data: fragment.to_vec(),
+        });
+    }
+    Ok(out)
+}
@@ -1,6 +1,7 @@
 //! LE [`SetAdvertisingEnable`], [`SetAdvertisingData`] and other advertising types.
 use crate::bytes::ToFromBytesEndian;
 use crate::hci::command::Command;
+use crate::hci::informational::CoreVersion;
 use crate::hci::event::{CommandComplete, ReturnParameters, StatusReturn};
 use crate::hci::le::LEControllerOpcode;
 use crate::hci::{ErrorCode, Opcode};
@@ -19,6 +20,7 @@ pub struct SetAdvertisingEnable {
 const SET_ADVERTISING_ENABLE_LEN: usize = 1;
 impl Command for SetAdvertisingEnable {
     type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
 
     fn opcode() -> Opcode {
         LEControllerOpcode::SetAdvertisingEnable.into()
@@ -55,6 +57,7 @@ pub struct SetAdvertisingData {
 
 impl Command for SetAdvertisingData {
     type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
 
     fn opcode() -> Opcode {
         LEControllerOpcode::SetAdvertisingData.into()
@@ -74,11 +77,17 @@ impl Command for SetAdvertisingData {
         Ok(())
     }
 
-    fn unpack_from(_buf: &[u8]) -> Result<Self, PackError>
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
     where
         Self: Sized,
     {
-        unimplemented!()
+        PackError::expect_length(Self::COMMAND_BYTE_LEN, buf)?;
+        let len = buf[0];
+        if usize::from(len) > ADVERTISING_DATA_MAX_LEN {
+            return Err(PackError::bad_index(0));
+        }
+        // Bytes past `len` are padding and are dropped (`pack_into` zeros them).
+        Ok(SetAdvertisingData::new(&buf[1..][..usize::from(len)]))
     }
 }
 impl SetAdvertisingData {
@@ -93,10 +102,11 @@ impl SetAdvertisingData {
         }
     }
 }
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
 pub struct SetAdvertisingParameters(pub AdvertisingParameters);
 impl Command for SetAdvertisingParameters {
     type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
 
     fn opcode() -> Opcode {
         LEControllerOpcode::SetAdvertisingParameters.into()
@@ -160,9 +170,11 @@ impl Command for SetAdvertisingParameters {
     }
 }
 
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
 pub struct ReadAdvertisingChannelTxPower {}
 impl Command for ReadAdvertisingChannelTxPower {
     type Return = CommandComplete<TxPowerLevelReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
 
     fn opcode() -> Opcode {
         LEControllerOpcode::ReadAdvertisingChannelTxPower.into()
@@ -246,6 +258,7 @@ impl From<RSSI> for TxPowerLevel {
         TxPowerLevel(rssi.0)
     }
 }
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
 pub struct TxPowerLevelReturn {
     pub status: ErrorCode,
     pub power_level: TxPowerLevel,
@@ -0,0 +1,289 @@
+//! LE Broadcast Isochronous Group (BIG) synchronization commands and events. Used to receive the
+//! BISes of a broadcast (like an Auracast broadcast) after syncing to its periodic advertising.
+use crate::hci::command::Command;
+use crate::hci::informational::CoreVersion;
+use crate::hci::event::{CommandComplete, CommandStatus, ReturnParameters};
+use crate::hci::le::{LEControllerOpcode, MetaEvent, MetaEventCode};
+pub use crate::hci::le::periodic_sync::SyncHandle;
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::connection::ConnectionHandle;
+use crate::PackError;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Host assigned identifier of a BIG.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct BIGHandle(u8);
+impl BIGHandle {
+    pub const BYTE_LEN: usize = 1;
+    pub const MIN_U8: u8 = 0x00;
+    pub const MAX_U8: u8 = 0xEF;
+    pub const MIN: BIGHandle = BIGHandle(Self::MIN_U8);
+    pub const MAX: BIGHandle = BIGHandle(Self::MAX_U8);
+    pub fn new(value: u8) -> Self {
+        match Self::new_checked(value) {
+            Some(s) => s,
+            None => panic!("big handle out of range (`{}`)", value),
+        }
+    }
+    pub fn new_checked(value: u8) -> Option<Self> {
+        if value > Self::MAX_U8 {
+            None
+        } else {
+            Some(Self(value))
+        }
+    }
+}
+impl From<BIGHandle> for u8 {
+    fn from(h: BIGHandle) -> Self {
+        h.0
+    }
+}
+impl TryFrom<u8> for BIGHandle {
+    type Error = crate::ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Self::new_checked(value).ok_or(crate::ConversionError(()))
+    }
+}
+/// `HCI_LE_BIG_Create_Sync`. Synchronize to the BISes listed in `bis` (BIS indices, `1..=0x1F`).
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BIGCreateSync {
+    pub big_handle: BIGHandle,
+    pub sync_handle: SyncHandle,
+    /// `Some(broadcast_code)` if the BIG is encrypted.
+    pub broadcast_code: Option<[u8; 16]>,
+    /// Maximum number of subevents to use per BIS ISO event. `0` lets the controller decide.
+    pub mse: u8,
+    /// Sync timeout in units of 10ms (`0x000A` to `0x4000`).
+    pub big_sync_timeout: u16,
+    pub bis: Vec<u8>,
+}
+impl BIGCreateSync {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::BIGCreateSync;
+    pub const HEADER_LEN: usize = 1 + 2 + 1 + 16 + 1 + 2 + 1;
+    pub const MAX_BIS: usize = 0x1F;
+}
+impl Command for BIGCreateSync {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.bis.len()
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        if self.bis.is_empty() || self.bis.len() > Self::MAX_BIS {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = self.big_handle.into();
+        buf[1..3].copy_from_slice(&self.sync_handle.0.to_le_bytes());
+        buf[3] = self.broadcast_code.is_some().into();
+        buf[4..20].copy_from_slice(&self.broadcast_code.unwrap_or_default());
+        buf[20] = self.mse;
+        buf[21..23].copy_from_slice(&self.big_sync_timeout.to_le_bytes());
+        buf[23] = u8::try_from(self.bis.len()).map_err(|_| PackError::InvalidFields)?;
+        buf[Self::HEADER_LEN..].copy_from_slice(&self.bis);
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        let num_bis = usize::from(buf[23]);
+        if num_bis == 0 || num_bis > Self::MAX_BIS {
+            return Err(PackError::bad_index(23));
+        }
+        PackError::expect_length(Self::HEADER_LEN + num_bis, buf)?;
+        let mut broadcast_code = [0_u8; 16];
+        broadcast_code.copy_from_slice(&buf[4..20]);
+        Ok(BIGCreateSync {
+            big_handle: BIGHandle::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            sync_handle: SyncHandle(u16::from_le_bytes([buf[1], buf[2]])),
+            broadcast_code: match buf[3] {
+                0 => None,
+                1 => Some(broadcast_code),
+                _ => return Err(PackError::bad_index(3)),
+            },
+            mse: buf[20],
+            big_sync_timeout: u16::from_le_bytes([buf[21], buf[22]]),
+            bis: buf[Self::HEADER_LEN..].to_vec(),
+        })
+    }
+}
+/// `HCI_LE_BIG_Terminate_Sync`. Stop synchronizing (or cancel a pending `BIGCreateSync`).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BIGTerminateSync {
+    pub big_handle: BIGHandle,
+}
+impl BIGTerminateSync {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::BIGTerminateSync;
+    pub const BYTE_LEN: usize = BIGHandle::BYTE_LEN;
+}
+impl Command for BIGTerminateSync {
+    type Return = CommandComplete<BIGTerminateSyncReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.big_handle.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(BIGTerminateSync {
+            big_handle: BIGHandle::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+        })
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BIGTerminateSyncReturn {
+    pub status: ErrorCode,
+    pub big_handle: BIGHandle,
+}
+impl BIGTerminateSyncReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + BIGHandle::BYTE_LEN;
+}
+impl ReturnParameters for BIGTerminateSyncReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1] = self.big_handle.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(BIGTerminateSyncReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            big_handle: BIGHandle::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?,
+        })
+    }
+}
+/// `HCI_LE_BIG_Sync_Established` event. Decoded BIS parameters and one connection handle per
+/// synchronized BIS (in the same order as `BIGCreateSync::bis`).
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BIGSyncEstablished {
+    pub status: ErrorCode,
+    pub big_handle: BIGHandle,
+    /// Maximum transport latency in microseconds (24-bit).
+    pub transport_latency_big: u32,
+    /// Number of subevents per BIS in each BIG event.
+    pub nse: u8,
+    /// Burst number.
+    pub bn: u8,
+    /// Pre-transmission offset.
+    pub pto: u8,
+    /// Immediate repetition count.
+    pub irc: u8,
+    /// Maximum PDU size in bytes.
+    pub max_pdu: u16,
+    /// ISO interval in units of 1.25ms.
+    pub iso_interval: u16,
+    pub connection_handles: Vec<ConnectionHandle>,
+}
+impl BIGSyncEstablished {
+    pub const HEADER_LEN: usize = 1 + 1 + 3 + 1 + 1 + 1 + 1 + 2 + 2 + 1;
+    /// ISO interval in microseconds.
+    pub fn iso_interval_us(&self) -> u32 {
+        u32::from(self.iso_interval) * 1250
+    }
+}
+impl MetaEvent for BIGSyncEstablished {
+    const META_CODE: MetaEventCode = MetaEventCode::BIGSyncEstablished;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.connection_handles.len() * ConnectionHandle::BYTE_LEN
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        let num_bis = usize::from(buf[Self::HEADER_LEN - 1]);
+        PackError::expect_length(Self::HEADER_LEN + num_bis * ConnectionHandle::BYTE_LEN, buf)?;
+        let connection_handles = buf[Self::HEADER_LEN..]
+            .chunks_exact(ConnectionHandle::BYTE_LEN)
+            .enumerate()
+            .map(|(i, h)| {
+                ConnectionHandle::new_checked(u16::from_le_bytes([h[0], h[1]]))
+                    .ok_or_else(|| PackError::bad_index(Self::HEADER_LEN + i * 2))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(BIGSyncEstablished {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            big_handle: BIGHandle::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?,
+            transport_latency_big: u32::from_le_bytes([buf[2], buf[3], buf[4], 0]),
+            nse: buf[5],
+            bn: buf[6],
+            pto: buf[7],
+            irc: buf[8],
+            max_pdu: u16::from_le_bytes([buf[9], buf[10]]),
+            iso_interval: u16::from_le_bytes([buf[11], buf[12]]),
+            connection_handles,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.meta_byte_len(), buf)?;
+        if self.transport_latency_big > 0x00FF_FFFF {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = self.status.into();
+        buf[1] = self.big_handle.into();
+        buf[2..5].copy_from_slice(&self.transport_latency_big.to_le_bytes()[..3]);
+        buf[5] = self.nse;
+        buf[6] = self.bn;
+        buf[7] = self.pto;
+        buf[8] = self.irc;
+        buf[9..11].copy_from_slice(&self.max_pdu.to_le_bytes());
+        buf[11..13].copy_from_slice(&self.iso_interval.to_le_bytes());
+        buf[13] =
+            u8::try_from(self.connection_handles.len()).map_err(|_| PackError::InvalidFields)?;
+        for (handle, out) in self
+            .connection_handles
+            .iter()
+            .zip(buf[Self::HEADER_LEN..].chunks_exact_mut(ConnectionHandle::BYTE_LEN))
+        {
+            out.copy_from_slice(&u16::from(*handle).to_le_bytes());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+//! LE data channel maps. [`SetHostChannelClassification`] tells the controller which of the 37
+//! data channels the host knows are bad (WiFi, other interference) so adaptive frequency hopping
+//! avoids them, and [`ReadChannelMap`] reads the map a connection currently hops over.
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, ReturnParameters, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::LEControllerOpcode;
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::connection::ConnectionHandle;
+use crate::PackError;
+use core::convert::TryFrom;
+
+/// Bitmap of the 37 LE data channels (bit `n` is channel `n`). A set bit means the channel is
+/// used (or, for the host classification, not known to be bad).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct DataChannelMap(u64);
+impl DataChannelMap {
+    pub const BYTE_LEN: usize = 5;
+    pub const CHANNELS: u8 = 37;
+    pub const ZEROED: DataChannelMap = DataChannelMap(0);
+    pub const ALL: DataChannelMap = DataChannelMap((1_u64 << Self::CHANNELS) - 1);
+    /// Fewest channels a map may leave enabled.
+    pub const MIN_USED: u32 = 2;
+    /// Returns `None` if a bit above channel 36 is set.
+    pub fn new_checked(map: u64) -> Option<DataChannelMap> {
+        if map & !Self::ALL.0 == 0 {
+            Some(DataChannelMap(map))
+        } else {
+            None
+        }
+    }
+    pub fn is_used(self, channel: u8) -> bool {
+        channel < Self::CHANNELS && self.0 & (1_u64 << channel) != 0
+    }
+    /// Channels past 36 are ignored.
+    pub fn enable(&mut self, channel: u8) {
+        if channel < Self::CHANNELS {
+            self.0 |= 1_u64 << channel
+        }
+    }
+    pub fn disable(&mut self, channel: u8) {
+        if channel < Self::CHANNELS {
+            self.0 &= !(1_u64 << channel)
+        }
+    }
+    /// Number of used channels.
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+    /// Channels used by both maps.
+    pub fn intersection(self, other: DataChannelMap) -> DataChannelMap {
+        DataChannelMap(self.0 & other.0)
+    }
+    pub fn pack_into(self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&self.0.to_le_bytes()[..Self::BYTE_LEN]);
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<DataChannelMap, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let mut bytes = [0_u8; 8];
+        bytes[..Self::BYTE_LEN].copy_from_slice(buf);
+        Self::new_checked(u64::from_le_bytes(bytes)).ok_or(PackError::bad_index(4))
+    }
+}
+impl Default for DataChannelMap {
+    fn default() -> Self {
+        DataChannelMap::ALL
+    }
+}
+impl From<DataChannelMap> for u64 {
+    fn from(map: DataChannelMap) -> Self {
+        map.0
+    }
+}
+/// `HCI_LE_Set_Host_Channel_Classification`. Channels cleared in `channel_map` are known bad and
+/// won't be used by any connection. At least [`DataChannelMap::MIN_USED`] must stay set.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct SetHostChannelClassification {
+    pub channel_map: DataChannelMap,
+}
+impl SetHostChannelClassification {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetHostChannelClassification;
+}
+impl Command for SetHostChannelClassification {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        DataChannelMap::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        if self.channel_map.count() < DataChannelMap::MIN_USED {
+            return Err(PackError::InvalidFields);
+        }
+        self.channel_map.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(SetHostChannelClassification {
+            channel_map: DataChannelMap::unpack_from(buf)?,
+        })
+    }
+}
+/// `HCI_LE_Read_Channel_Map`. Channels the connection currently hops over.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReadChannelMap {
+    pub connection_handle: ConnectionHandle,
+}
+impl ReadChannelMap {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReadChannelMap;
+}
+impl Command for ReadChannelMap {
+    type Return = CommandComplete<ChannelMapReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ConnectionHandle::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        Ok(ReadChannelMap {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+        })
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ChannelMapReturn {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+    pub channel_map: DataChannelMap,
+}
+impl ChannelMapReturn {
+    pub const BYTE_LEN: usize =
+        ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN + DataChannelMap::BYTE_LEN;
+}
+impl ReturnParameters for ChannelMapReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        self.channel_map.pack_into(&mut buf[3..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(ChannelMapReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            connection_handle: ConnectionHandle::unpack_from(buf, 1)?,
+            channel_map: DataChannelMap::unpack_from(&buf[3..])?,
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_channel_map() {
+        assert_eq!(DataChannelMap::ALL.count(), 37);
+        assert!(DataChannelMap::new_checked(1 << 37).is_none());
+        // Avoid WiFi channel 1 (LE data channels 0-8).
+        let mut map = DataChannelMap::ALL;
+        for channel in 0..9 {
+            map.disable(channel);
+        }
+        assert!(!map.is_used(8) && map.is_used(9) && !map.is_used(37));
+        let set = SetHostChannelClassification { channel_map: map };
+        let packet = set.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(packet.parameters.as_ref(), &[0x00, 0xFE, 0xFF, 0xFF, 0x1F]);
+        assert_eq!(
+            SetHostChannelClassification::unpack_command_packet(&packet),
+            Ok(set)
+        );
+        let mut one = DataChannelMap::ZEROED;
+        one.enable(20);
+        assert_eq!(
+            SetHostChannelClassification { channel_map: one }
+                .pack_command_packet::<Box<[u8]>>()
+                .err(),
+            Some(PackError::InvalidFields)
+        );
+        let returned =
+            ChannelMapReturn::unpack_from(&[0x00, 0x40, 0x00, 0x00, 0xFE, 0xFF, 0xFF, 0x1F])
+                .unwrap();
+        assert_eq!(returned.channel_map, map);
+        assert!(ChannelMapReturn::unpack_from(&[0x00, 0x40, 0x00, 0, 0, 0, 0, 0x20]).is_err());
+    }
+}
@@ -1,14 +1,16 @@
-use crate::hci::command::Command;
-use crate::hci::event::{CommandComplete, CommandStatus, ReturnParameters};
-use crate::hci::le::{LEControllerOpcode, MetaEventCode};
+use crate::hci::command::{Command, LongCommand};
+use crate::hci::informational::CoreVersion;
+use crate::hci::event::{CommandComplete, CommandStatus, ReturnParameters, StatusReturn};
+use crate::hci::le::{LEControllerOpcode, MetaEvent, MetaEventCode};
 use crate::hci::{ErrorCode, Opcode};
-use crate::le::advertiser::PeerAddressType;
+use crate::le::advertiser::{DirectedPeer, DirectedPeerError, PeerAddressType};
+use crate::le::connection::qos::ConnectionParameters;
 use crate::le::connection::{
     CELength, ConnectionHandle, ConnectionInterval, ConnectionLatency, InitiatorFilterPolicy,
     MasterClockAccuracy, Role, SupervisionTimeout,
 };
 use crate::le::scan::{OwnAddressType, ScanInterval, ScanWindow};
-use crate::{BTAddress, PackError, BT_ADDRESS_LEN};
+use crate::{BTAddress, DeviceAddress, PackError, BT_ADDRESS_LEN};
 use core::convert::{TryFrom, TryInto};
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
@@ -27,6 +29,7 @@ impl BufferSizeV1 {
 }
 impl Command for ReadBufferSizeV1 {
     type Return = CommandComplete<BufferSizeV1>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
 
     fn opcode() -> Opcode {
         Self::OPCODE.into()
@@ -96,6 +99,7 @@ impl BufferSizeV2 {
 
 impl Command for ReadBufferSizeV2 {
     type Return = CommandComplete<BufferSizeV2>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_2;
 
     fn opcode() -> Opcode {
         Self::OPCODE.into()
@@ -175,13 +179,48 @@ impl CreateConnection {
         + InitiatorFilterPolicy::BYTE_LEN
         + PeerAddressType::BYTE_LEN
         + BT_ADDRESS_LEN
-        + ScanInterval::BYTE_LEN * 2
+        + 1
+        + ConnectionInterval::BYTE_LEN * 2
         + ConnectionLatency::BYTE_LEN
         + SupervisionTimeout::BYTE_LEN
         + CELength::BYTE_LEN * 2;
+    pub fn peer(&self) -> DirectedPeer {
+        DirectedPeer::new(self.peer_address_type, self.peer_address)
+    }
+    /// Connect to `peer` (with [`InitiatorFilterPolicy::PeerAddress`]).
+    pub fn set_peer(&mut self, peer: DeviceAddress) {
+        self.initiator_filter_policy = InitiatorFilterPolicy::PeerAddress;
+        self.peer_address_type = peer.kind.into();
+        self.peer_address = peer.addr;
+    }
+    /// Check the peer against the own address type. The peer is ignored when connecting to the
+    /// white list.
+    pub fn check_peer(&self) -> Result<(), DirectedPeerError> {
+        match self.initiator_filter_policy {
+            InitiatorFilterPolicy::PeerAddress => self.peer().check(self.own_address_type.into()),
+            InitiatorFilterPolicy::WhiteList => Ok(()),
+        }
+    }
+    pub fn parameters(&self) -> ConnectionParameters {
+        ConnectionParameters {
+            interval_min: self.connection_interval_min,
+            interval_max: self.connection_interval_max,
+            latency: self.connection_latency,
+            supervision_timeout: self.supervision_timeout,
+        }
+    }
+    /// Use `parameters` (e.g. from a [`ConnectionQos`](crate::le::connection::qos::ConnectionQos)
+    /// preset) for the new connection.
+    pub fn set_parameters(&mut self, parameters: ConnectionParameters) {
+        self.connection_interval_min = parameters.interval_min;
+        self.connection_interval_max = parameters.interval_max;
+        self.connection_latency = parameters.latency;
+        self.supervision_timeout = parameters.supervision_timeout;
+    }
 }
 impl Command for CreateConnection {
     type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
 
     fn opcode() -> Opcode {
         Self::OPCODE.into()
@@ -221,7 +260,29 @@ impl Command for CreateConnection {
         Self: Sized,
     {
         PackError::expect_length(Self::BYTE_LEN, buf)?;
-        todo!("implement unpack from for CreateConnection")
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        Ok(CreateConnection {
+            le_scan_interval: ScanInterval::new_checked(u16_at(0))
+                .ok_or(PackError::bad_index(0))?,
+            le_scan_window: ScanWindow::new_checked(u16_at(2)).ok_or(PackError::bad_index(2))?,
+            initiator_filter_policy: InitiatorFilterPolicy::try_from(buf[4])
+                .map_err(|_| PackError::bad_index(4))?,
+            peer_address_type: PeerAddressType::try_from(buf[5])
+                .map_err(|_| PackError::bad_index(5))?,
+            peer_address: BTAddress::new(&buf[6..12]),
+            own_address_type: OwnAddressType::try_from(buf[12])
+                .map_err(|_| PackError::bad_index(12))?,
+            connection_interval_min: ConnectionInterval::new_checked(u16_at(13))
+                .ok_or(PackError::bad_index(13))?,
+            connection_interval_max: ConnectionInterval::new_checked(u16_at(15))
+                .ok_or(PackError::bad_index(15))?,
+            connection_latency: ConnectionLatency::new_checked(u16_at(17))
+                .ok_or(PackError::bad_index(17))?,
+            supervision_timeout: SupervisionTimeout::new_checked(u16_at(19))
+                .ok_or(PackError::bad_index(19))?,
+            min_ce_len: CELength(u16_at(21)),
+            max_ce_len: CELength(u16_at(23)),
+        })
     }
 }
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
@@ -247,4 +308,341 @@ impl ConnectionCompleteEvent {
         + ConnectionLatency::BYTE_LEN
         + SupervisionTimeout::BYTE_LEN
         + MasterClockAccuracy::BYTE_LEN;
+    pub fn peer(&self) -> DeviceAddress {
+        DirectedPeer::new(self.peer_address_type, self.peer_address).into()
+    }
+}
+impl MetaEvent for ConnectionCompleteEvent {
+    const META_CODE: MetaEventCode = Self::CODE;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    /// The connection parameters of a failed connection aren't valid. Out of range values are
+    /// replaced by the minimum instead of being an error.
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let status = ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?;
+        let is_ok = status == ErrorCode::Ok;
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        Ok(ConnectionCompleteEvent {
+            status,
+            connection_handle: or_min(
+                ConnectionHandle::new_checked(u16_at(1)),
+                ConnectionHandle::MIN,
+                is_ok,
+                1,
+            )?,
+            role: or_min(Role::try_from(buf[3]).ok(), Role::Master, is_ok, 3)?,
+            peer_address_type: or_min(
+                PeerAddressType::try_from(buf[4]).ok(),
+                PeerAddressType::Public,
+                is_ok,
+                4,
+            )?,
+            peer_address: BTAddress::new(&buf[5..11]),
+            connection_interval: or_min(
+                ConnectionInterval::new_checked(u16_at(11)),
+                ConnectionInterval::MIN,
+                is_ok,
+                11,
+            )?,
+            connection_latency: or_min(
+                ConnectionLatency::new_checked(u16_at(13)),
+                ConnectionLatency::MIN,
+                is_ok,
+                13,
+            )?,
+            supervision_timeout: or_min(
+                SupervisionTimeout::new_checked(u16_at(15)),
+                SupervisionTimeout::MIN,
+                is_ok,
+                15,
+            )?,
+            master_clock_accuracy: or_min(
+                MasterClockAccuracy::try_from(buf[17]).ok(),
+                MasterClockAccuracy::PPM500,
+                is_ok,
+                17,
+            )?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[3] = self.role.into();
+        buf[4] = self.peer_address_type.into();
+        self.peer_address.pack_into(&mut buf[5..11])?;
+        buf[11..13].copy_from_slice(&u16::from(self.connection_interval).to_le_bytes());
+        buf[13..15].copy_from_slice(&u16::from(self.connection_latency).to_le_bytes());
+        buf[15..17].copy_from_slice(&u16::from(self.supervision_timeout).to_le_bytes());
+        buf[17] = self.master_clock_accuracy.into();
+        Ok(())
+    }
+}
+/// `value` or, for failed connections, `min` if `value` is invalid.
+pub(crate) fn or_min<T>(value: Option<T>, min: T, is_ok: bool, index: usize) -> Result<T, PackError> {
+    match value {
+        Some(value) => Ok(value),
+        None if !is_ok => Ok(min),
+        None => Err(PackError::bad_index(index)),
+    }
+}
+impl LongCommand for CreateConnection {
+    type Completion = ConnectionCompleteEvent;
+    type Cancel = CreateConnectionCancel;
+
+    fn cancel_command(&self) -> CreateConnectionCancel {
+        CreateConnectionCancel
+    }
+
+    /// Connecting to the white list can complete with any address.
+    fn is_completion(&self, completion: &ConnectionCompleteEvent) -> bool {
+        match self.initiator_filter_policy {
+            InitiatorFilterPolicy::PeerAddress => completion.peer_address == self.peer_address,
+            InitiatorFilterPolicy::WhiteList => true,
+        }
+    }
+}
+/// `HCI_LE_Create_Connection_Cancel`. Stop a pending [`CreateConnection`]. The controller then
+/// sends a [`ConnectionCompleteEvent`] with `ErrorCode::NoConnection` (Unknown Connection Identifier).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct CreateConnectionCancel;
+impl CreateConnectionCancel {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::CreateConnectionCancel;
+}
+impl Command for CreateConnectionCancel {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(CreateConnectionCancel)
+    }
+}
+/// `HCI_LE_Connection_Update`. Change the connection parameters of an established connection.
+/// Completes with a [`ConnectionUpdateComplete`] (possibly with parameters the peer picked within
+/// the requested range).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ConnectionUpdate {
+    pub connection_handle: ConnectionHandle,
+    pub connection_interval_min: ConnectionInterval,
+    pub connection_interval_max: ConnectionInterval,
+    pub connection_latency: ConnectionLatency,
+    pub supervision_timeout: SupervisionTimeout,
+    pub min_ce_len: CELength,
+    pub max_ce_len: CELength,
+}
+impl ConnectionUpdate {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ConnectionUpdate;
+    pub const BYTE_LEN: usize =
+        ConnectionHandle::BYTE_LEN + ConnectionParameters::BYTE_LEN + CELength::BYTE_LEN * 2;
+    /// Update `connection_handle` to `parameters` (e.g. from a
+    /// [`ConnectionQos`](crate::le::connection::qos::ConnectionQos) preset).
+    pub fn new(
+        connection_handle: ConnectionHandle,
+        parameters: ConnectionParameters,
+    ) -> ConnectionUpdate {
+        ConnectionUpdate {
+            connection_handle,
+            connection_interval_min: parameters.interval_min,
+            connection_interval_max: parameters.interval_max,
+            connection_latency: parameters.latency,
+            supervision_timeout: parameters.supervision_timeout,
+            min_ce_len: CELength::MIN,
+            max_ce_len: CELength::MIN,
+        }
+    }
+    pub fn parameters(&self) -> ConnectionParameters {
+        ConnectionParameters {
+            interval_min: self.connection_interval_min,
+            interval_max: self.connection_interval_max,
+            latency: self.connection_latency,
+            supervision_timeout: self.supervision_timeout,
+        }
+    }
+}
+impl Command for ConnectionUpdate {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        self.parameters().pack_into(&mut buf[2..10])?;
+        buf[10..12].copy_from_slice(&self.min_ce_len.0.to_le_bytes());
+        buf[12..14].copy_from_slice(&self.max_ce_len.0.to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let mut update = ConnectionUpdate::new(
+            ConnectionHandle::unpack_from(buf, 0)?,
+            ConnectionParameters::unpack_from(&buf[2..10])?,
+        );
+        update.min_ce_len = CELength(u16::from_le_bytes([buf[10], buf[11]]));
+        update.max_ce_len = CELength(u16::from_le_bytes([buf[12], buf[13]]));
+        Ok(update)
+    }
+}
+/// LE Connection Update Complete event. The parameters the connection uses now. Also sent when the
+/// peer changes them.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ConnectionUpdateComplete {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+    pub connection_interval: ConnectionInterval,
+    pub connection_latency: ConnectionLatency,
+    pub supervision_timeout: SupervisionTimeout,
+}
+impl ConnectionUpdateComplete {
+    pub const CODE: MetaEventCode = MetaEventCode::ConnectionUpdateComplete;
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN
+        + ConnectionHandle::BYTE_LEN
+        + ConnectionInterval::BYTE_LEN
+        + ConnectionLatency::BYTE_LEN
+        + SupervisionTimeout::BYTE_LEN;
+}
+impl MetaEvent for ConnectionUpdateComplete {
+    const META_CODE: MetaEventCode = Self::CODE;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    /// Like [`ConnectionCompleteEvent`], invalid parameters of a failed update are replaced by
+    /// the minimum.
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let status = ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?;
+        let is_ok = status == ErrorCode::Ok;
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        Ok(ConnectionUpdateComplete {
+            status,
+            connection_handle: or_min(
+                ConnectionHandle::new_checked(u16_at(1)),
+                ConnectionHandle::MIN,
+                is_ok,
+                1,
+            )?,
+            connection_interval: or_min(
+                ConnectionInterval::new_checked(u16_at(3)),
+                ConnectionInterval::MIN,
+                is_ok,
+                3,
+            )?,
+            connection_latency: or_min(
+                ConnectionLatency::new_checked(u16_at(5)),
+                ConnectionLatency::MIN,
+                is_ok,
+                5,
+            )?,
+            supervision_timeout: or_min(
+                SupervisionTimeout::new_checked(u16_at(7)),
+                SupervisionTimeout::MIN,
+                is_ok,
+                7,
+            )?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[3..5].copy_from_slice(&u16::from(self.connection_interval).to_le_bytes());
+        buf[5..7].copy_from_slice(&u16::from(self.connection_latency).to_le_bytes());
+        buf[7..9].copy_from_slice(&u16::from(self.supervision_timeout).to_le_bytes());
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_cancelled_connection_complete() {
+        // Connection Complete sent after `CreateConnectionCancel`. Everything but the status and
+        // address is zeroed.
+        let mut buf = [0_u8; ConnectionCompleteEvent::BYTE_LEN];
+        buf[0] = ErrorCode::NoConnection.into();
+        buf[5..11].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+        let event = ConnectionCompleteEvent::meta_unpack_from(&buf).unwrap();
+        assert_eq!(event.status, ErrorCode::NoConnection);
+        assert_eq!(event.connection_interval, ConnectionInterval::MIN);
+        let create = CreateConnection {
+            le_scan_interval: ScanInterval::DEFAULT,
+            le_scan_window: ScanWindow::DEFAULT,
+            initiator_filter_policy: InitiatorFilterPolicy::PeerAddress,
+            peer_address_type: PeerAddressType::Public,
+            peer_address: BTAddress([1, 2, 3, 4, 5, 6]),
+            own_address_type: OwnAddressType::Public,
+            connection_interval_min: ConnectionInterval::MIN,
+            connection_interval_max: ConnectionInterval::MIN,
+            connection_latency: ConnectionLatency::MIN,
+            supervision_timeout: SupervisionTimeout::MIN,
+            min_ce_len: CELength::MIN,
+            max_ce_len: CELength::MIN,
+        };
+        assert!(create.is_completion(&event));
+
+        // Successful connections must be valid.
+        buf[0] = ErrorCode::Ok.into();
+        assert!(ConnectionCompleteEvent::meta_unpack_from(&buf).is_err());
+    }
+    #[test]
+    fn test_connection_update() {
+        use crate::le::connection::qos::ConnectionQos;
+        use alloc::boxed::Box;
+        let update = ConnectionUpdate::new(
+            ConnectionHandle::new(0x0040),
+            ConnectionQos::DEFAULT.parameters(),
+        );
+        let packet = update.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(&packet.parameters.as_ref()[..2], &[0x40, 0x00]);
+        assert_eq!(ConnectionUpdate::unpack_command_packet(&packet), Ok(update));
+
+        let buf = [0x00, 0x40, 0x00, 0x18, 0x00, 0x04, 0x00, 0x90, 0x01];
+        let complete = ConnectionUpdateComplete::meta_unpack_from(&buf).unwrap();
+        assert_eq!(complete.connection_interval, ConnectionInterval::new(24));
+        assert_eq!(complete.connection_latency, ConnectionLatency::new(4));
+        let mut packed = [0_u8; ConnectionUpdateComplete::BYTE_LEN];
+        complete.meta_pack_into(&mut packed).unwrap();
+        assert_eq!(packed, buf);
+    }
 }
@@ -0,0 +1,1017 @@
+//! LE Channel Sounding (Bluetooth 6.0) commands and events. Experimental: only compiled with the
+//! `channel_sounding` feature and the parameter layouts may still change as silicon ships.
+//!
+//! A ranging session reads the peer's capabilities, creates a config
+//! ([`CSCreateConfig`] then [`CSConfigComplete`]), sets the procedure parameters and enables
+//! procedures. Results then arrive as [`CSSubeventResult`] (and [`CSSubeventResultContinue`])
+//! events.
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, CommandStatus, ReturnParameters};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::{LEControllerOpcode, MetaEvent, MetaEventCode};
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::connection::ConnectionHandle;
+use crate::{ConversionError, PackError};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+fn unpack_status(buf: &[u8], index: usize) -> Result<ErrorCode, PackError> {
+    ErrorCode::try_from(buf[index]).map_err(|_| PackError::bad_index(index))
+}
+fn u16_at(buf: &[u8], index: usize) -> u16 {
+    u16::from_le_bytes([buf[index], buf[index + 1]])
+}
+fn u24_at(buf: &[u8], index: usize) -> u32 {
+    u32::from_le_bytes([buf[index], buf[index + 1], buf[index + 2], 0])
+}
+/// CS config identifier (`0..=3`).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct CSConfigID(u8);
+impl CSConfigID {
+    pub const BYTE_LEN: usize = 1;
+    pub const MAX_U8: u8 = 3;
+    pub fn new(value: u8) -> Self {
+        match Self::new_checked(value) {
+            Some(s) => s,
+            None => panic!("cs config id out of range (`{}`)", value),
+        }
+    }
+    pub fn new_checked(value: u8) -> Option<Self> {
+        if value > Self::MAX_U8 {
+            None
+        } else {
+            Some(Self(value))
+        }
+    }
+}
+impl From<CSConfigID> for u8 {
+    fn from(id: CSConfigID) -> Self {
+        id.0
+    }
+}
+impl TryFrom<u8> for CSConfigID {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Self::new_checked(value).ok_or(ConversionError(()))
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum CSRole {
+    Initiator = 0x00,
+    Reflector = 0x01,
+}
+impl From<CSRole> for u8 {
+    fn from(role: CSRole) -> Self {
+        role as u8
+    }
+}
+impl TryFrom<u8> for CSRole {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(CSRole::Initiator),
+            0x01 => Ok(CSRole::Reflector),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// CS capabilities of a controller (local or remote).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct CSCapabilities {
+    pub num_config_supported: u8,
+    pub max_consecutive_procedures_supported: u16,
+    pub num_antennas_supported: u8,
+    pub max_antenna_paths_supported: u8,
+    /// Bit 0: initiator. Bit 1: reflector.
+    pub roles_supported: u8,
+    /// Bit 0: mode 3 supported.
+    pub modes_supported: u8,
+    pub rtt_capability: u8,
+    pub rtt_aa_only_n: u8,
+    pub rtt_sounding_n: u8,
+    pub rtt_random_payload_n: u8,
+    pub nadm_sounding_capability: u16,
+    pub nadm_random_capability: u16,
+    pub cs_sync_phys_supported: u8,
+    pub subfeatures_supported: u16,
+    pub t_ip1_times_supported: u16,
+    pub t_ip2_times_supported: u16,
+    pub t_fcs_times_supported: u16,
+    pub t_pm_times_supported: u16,
+    pub t_sw_time_supported: u8,
+    pub tx_snr_capability: u8,
+}
+impl CSCapabilities {
+    pub const BYTE_LEN: usize = 28;
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.num_config_supported;
+        buf[1..3].copy_from_slice(&self.max_consecutive_procedures_supported.to_le_bytes());
+        buf[3] = self.num_antennas_supported;
+        buf[4] = self.max_antenna_paths_supported;
+        buf[5] = self.roles_supported;
+        buf[6] = self.modes_supported;
+        buf[7] = self.rtt_capability;
+        buf[8] = self.rtt_aa_only_n;
+        buf[9] = self.rtt_sounding_n;
+        buf[10] = self.rtt_random_payload_n;
+        buf[11..13].copy_from_slice(&self.nadm_sounding_capability.to_le_bytes());
+        buf[13..15].copy_from_slice(&self.nadm_random_capability.to_le_bytes());
+        buf[15] = self.cs_sync_phys_supported;
+        buf[16..18].copy_from_slice(&self.subfeatures_supported.to_le_bytes());
+        buf[18..20].copy_from_slice(&self.t_ip1_times_supported.to_le_bytes());
+        buf[20..22].copy_from_slice(&self.t_ip2_times_supported.to_le_bytes());
+        buf[22..24].copy_from_slice(&self.t_fcs_times_supported.to_le_bytes());
+        buf[24..26].copy_from_slice(&self.t_pm_times_supported.to_le_bytes());
+        buf[26] = self.t_sw_time_supported;
+        buf[27] = self.tx_snr_capability;
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<Self, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CSCapabilities {
+            num_config_supported: buf[0],
+            max_consecutive_procedures_supported: u16_at(buf, 1),
+            num_antennas_supported: buf[3],
+            max_antenna_paths_supported: buf[4],
+            roles_supported: buf[5],
+            modes_supported: buf[6],
+            rtt_capability: buf[7],
+            rtt_aa_only_n: buf[8],
+            rtt_sounding_n: buf[9],
+            rtt_random_payload_n: buf[10],
+            nadm_sounding_capability: u16_at(buf, 11),
+            nadm_random_capability: u16_at(buf, 13),
+            cs_sync_phys_supported: buf[15],
+            subfeatures_supported: u16_at(buf, 16),
+            t_ip1_times_supported: u16_at(buf, 18),
+            t_ip2_times_supported: u16_at(buf, 20),
+            t_fcs_times_supported: u16_at(buf, 22),
+            t_pm_times_supported: u16_at(buf, 24),
+            t_sw_time_supported: buf[26],
+            tx_snr_capability: buf[27],
+        })
+    }
+}
+/// `HCI_LE_CS_Read_Local_Supported_Capabilities`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct CSReadLocalSupportedCapabilities;
+impl CSReadLocalSupportedCapabilities {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::CSReadLocalSupportedCapabilities;
+}
+impl Command for CSReadLocalSupportedCapabilities {
+    type Return = CommandComplete<CSLocalCapabilitiesReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V6_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(CSReadLocalSupportedCapabilities)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSLocalCapabilitiesReturn {
+    pub status: ErrorCode,
+    pub capabilities: CSCapabilities,
+}
+impl CSLocalCapabilitiesReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + CSCapabilities::BYTE_LEN;
+}
+impl ReturnParameters for CSLocalCapabilitiesReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        self.capabilities.pack_into(&mut buf[1..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CSLocalCapabilitiesReturn {
+            status: unpack_status(buf, 0)?,
+            capabilities: CSCapabilities::unpack_from(&buf[1..])?,
+        })
+    }
+}
+/// `HCI_LE_CS_Read_Remote_Supported_Capabilities`. Answered by
+/// [`CSReadRemoteSupportedCapabilitiesComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSReadRemoteSupportedCapabilities {
+    pub connection_handle: ConnectionHandle,
+}
+impl CSReadRemoteSupportedCapabilities {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::CSReadRemoteSupportedCapabilities;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN;
+}
+impl Command for CSReadRemoteSupportedCapabilities {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V6_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CSReadRemoteSupportedCapabilities {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+        })
+    }
+}
+/// `HCI_LE_CS_Read_Remote_Supported_Capabilities_Complete` event.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSReadRemoteSupportedCapabilitiesComplete {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+    pub capabilities: CSCapabilities,
+}
+impl CSReadRemoteSupportedCapabilitiesComplete {
+    pub const BYTE_LEN: usize =
+        ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN + CSCapabilities::BYTE_LEN;
+}
+impl MetaEvent for CSReadRemoteSupportedCapabilitiesComplete {
+    const META_CODE: MetaEventCode = MetaEventCode::CSReadRemoteSupportedCapabilitiesComplete;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CSReadRemoteSupportedCapabilitiesComplete {
+            status: unpack_status(buf, 0)?,
+            connection_handle: ConnectionHandle::unpack_from(buf, 1)?,
+            capabilities: CSCapabilities::unpack_from(&buf[3..])?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        self.capabilities.pack_into(&mut buf[3..])
+    }
+}
+/// Parameters shared by [`CSCreateConfig`] and [`CSConfigComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSConfigParameters {
+    pub main_mode_type: u8,
+    pub sub_mode_type: u8,
+    pub min_main_mode_steps: u8,
+    pub max_main_mode_steps: u8,
+    pub main_mode_repetition: u8,
+    pub mode_0_steps: u8,
+    pub role: CSRole,
+    pub rtt_type: u8,
+    pub cs_sync_phy: u8,
+    /// Bitmap of the channels to use (channel `n` is bit `n`).
+    pub channel_map: [u8; 10],
+    pub channel_map_repetition: u8,
+    pub channel_selection_type: u8,
+    pub ch3c_shape: u8,
+    pub ch3c_jump: u8,
+}
+impl CSConfigParameters {
+    /// Including the trailing reserved byte.
+    pub const BYTE_LEN: usize = 24;
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.main_mode_type;
+        buf[1] = self.sub_mode_type;
+        buf[2] = self.min_main_mode_steps;
+        buf[3] = self.max_main_mode_steps;
+        buf[4] = self.main_mode_repetition;
+        buf[5] = self.mode_0_steps;
+        buf[6] = self.role.into();
+        buf[7] = self.rtt_type;
+        buf[8] = self.cs_sync_phy;
+        buf[9..19].copy_from_slice(&self.channel_map);
+        buf[19] = self.channel_map_repetition;
+        buf[20] = self.channel_selection_type;
+        buf[21] = self.ch3c_shape;
+        buf[22] = self.ch3c_jump;
+        buf[23] = 0;
+        Ok(())
+    }
+    /// `offset` is only used for error indices.
+    fn unpack_from(buf: &[u8], offset: usize) -> Result<Self, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let mut channel_map = [0_u8; 10];
+        channel_map.copy_from_slice(&buf[9..19]);
+        Ok(CSConfigParameters {
+            main_mode_type: buf[0],
+            sub_mode_type: buf[1],
+            min_main_mode_steps: buf[2],
+            max_main_mode_steps: buf[3],
+            main_mode_repetition: buf[4],
+            mode_0_steps: buf[5],
+            role: CSRole::try_from(buf[6]).map_err(|_| PackError::bad_index(offset + 6))?,
+            rtt_type: buf[7],
+            cs_sync_phy: buf[8],
+            channel_map,
+            channel_map_repetition: buf[19],
+            channel_selection_type: buf[20],
+            ch3c_shape: buf[21],
+            ch3c_jump: buf[22],
+        })
+    }
+}
+/// `HCI_LE_CS_Create_Config`. Answered by [`CSConfigComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSCreateConfig {
+    pub connection_handle: ConnectionHandle,
+    pub config_id: CSConfigID,
+    /// Also create the config on the remote controller.
+    pub create_context: bool,
+    pub parameters: CSConfigParameters,
+}
+impl CSCreateConfig {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::CSCreateConfig;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + 1 + 1 + CSConfigParameters::BYTE_LEN;
+}
+impl Command for CSCreateConfig {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V6_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2] = self.config_id.into();
+        buf[3] = self.create_context.into();
+        self.parameters.pack_into(&mut buf[4..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CSCreateConfig {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            config_id: CSConfigID::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+            create_context: match buf[3] {
+                0 => false,
+                1 => true,
+                _ => return Err(PackError::bad_index(3)),
+            },
+            parameters: CSConfigParameters::unpack_from(&buf[4..], 4)?,
+        })
+    }
+}
+/// `HCI_LE_CS_Remove_Config`. Answered by [`CSConfigComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSRemoveConfig {
+    pub connection_handle: ConnectionHandle,
+    pub config_id: CSConfigID,
+}
+impl CSRemoveConfig {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::CSRemoveConfig;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + CSConfigID::BYTE_LEN;
+}
+impl Command for CSRemoveConfig {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V6_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2] = self.config_id.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CSRemoveConfig {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            config_id: CSConfigID::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+        })
+    }
+}
+/// `HCI_LE_CS_Config_Complete` event. Sent after a config is created or removed.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSConfigComplete {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+    pub config_id: CSConfigID,
+    /// `0x00` removed, `0x01` created.
+    pub action: u8,
+    pub parameters: CSConfigParameters,
+    pub t_ip1_time: u8,
+    pub t_ip2_time: u8,
+    pub t_fcs_time: u8,
+    pub t_pm_time: u8,
+}
+impl CSConfigComplete {
+    pub const BYTE_LEN: usize = 1 + 2 + 1 + 1 + CSConfigParameters::BYTE_LEN + 4;
+}
+impl MetaEvent for CSConfigComplete {
+    const META_CODE: MetaEventCode = MetaEventCode::CSConfigComplete;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CSConfigComplete {
+            status: unpack_status(buf, 0)?,
+            connection_handle: ConnectionHandle::unpack_from(buf, 1)?,
+            config_id: CSConfigID::try_from(buf[3]).map_err(|_| PackError::bad_index(3))?,
+            action: buf[4],
+            parameters: CSConfigParameters::unpack_from(&buf[5..29], 5)?,
+            t_ip1_time: buf[29],
+            t_ip2_time: buf[30],
+            t_fcs_time: buf[31],
+            t_pm_time: buf[32],
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[3] = self.config_id.into();
+        buf[4] = self.action;
+        self.parameters.pack_into(&mut buf[5..29])?;
+        buf[29] = self.t_ip1_time;
+        buf[30] = self.t_ip2_time;
+        buf[31] = self.t_fcs_time;
+        buf[32] = self.t_pm_time;
+        Ok(())
+    }
+}
+/// `HCI_LE_CS_Set_Procedure_Parameters`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSSetProcedureParameters {
+    pub connection_handle: ConnectionHandle,
+    pub config_id: CSConfigID,
+    /// Units of 0.625ms.
+    pub max_procedure_len: u16,
+    /// Units of connection events.
+    pub min_procedure_interval: u16,
+    pub max_procedure_interval: u16,
+    /// `0` means procedures repeat until disabled.
+    pub max_procedure_count: u16,
+    /// Microseconds (24-bit).
+    pub min_subevent_len: u32,
+    pub max_subevent_len: u32,
+    pub tone_antenna_config_selection: u8,
+    pub phy: u8,
+    pub tx_power_delta: u8,
+    pub preferred_peer_antenna: u8,
+    pub snr_control_initiator: u8,
+    pub snr_control_reflector: u8,
+}
+impl CSSetProcedureParameters {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::CSSetProcedureParameters;
+    pub const BYTE_LEN: usize = 2 + 1 + 2 + 2 + 2 + 2 + 3 + 3 + 1 + 1 + 1 + 1 + 1 + 1;
+}
+impl Command for CSSetProcedureParameters {
+    type Return = CommandComplete<CSConnectionReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V6_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        if self.min_subevent_len > 0x00FF_FFFF || self.max_subevent_len > 0x00FF_FFFF {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2] = self.config_id.into();
+        buf[3..5].copy_from_slice(&self.max_procedure_len.to_le_bytes());
+        buf[5..7].copy_from_slice(&self.min_procedure_interval.to_le_bytes());
+        buf[7..9].copy_from_slice(&self.max_procedure_interval.to_le_bytes());
+        buf[9..11].copy_from_slice(&self.max_procedure_count.to_le_bytes());
+        buf[11..14].copy_from_slice(&self.min_subevent_len.to_le_bytes()[..3]);
+        buf[14..17].copy_from_slice(&self.max_subevent_len.to_le_bytes()[..3]);
+        buf[17] = self.tone_antenna_config_selection;
+        buf[18] = self.phy;
+        buf[19] = self.tx_power_delta;
+        buf[20] = self.preferred_peer_antenna;
+        buf[21] = self.snr_control_initiator;
+        buf[22] = self.snr_control_reflector;
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CSSetProcedureParameters {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            config_id: CSConfigID::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+            max_procedure_len: u16_at(buf, 3),
+            min_procedure_interval: u16_at(buf, 5),
+            max_procedure_interval: u16_at(buf, 7),
+            max_procedure_count: u16_at(buf, 9),
+            min_subevent_len: u24_at(buf, 11),
+            max_subevent_len: u24_at(buf, 14),
+            tone_antenna_config_selection: buf[17],
+            phy: buf[18],
+            tx_power_delta: buf[19],
+            preferred_peer_antenna: buf[20],
+            snr_control_initiator: buf[21],
+            snr_control_reflector: buf[22],
+        })
+    }
+}
+/// Status and connection handle returned by CS commands on a connection.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSConnectionReturn {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+}
+impl CSConnectionReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN;
+}
+impl ReturnParameters for CSConnectionReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CSConnectionReturn {
+            status: unpack_status(buf, 0)?,
+            connection_handle: ConnectionHandle::unpack_from(buf, 1)?,
+        })
+    }
+}
+/// `HCI_LE_CS_Procedure_Enable`. Answered by [`CSProcedureEnableComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSProcedureEnable {
+    pub connection_handle: ConnectionHandle,
+    pub config_id: CSConfigID,
+    pub is_enabled: bool,
+}
+impl CSProcedureEnable {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::CSProcedureEnable;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + CSConfigID::BYTE_LEN + 1;
+}
+impl Command for CSProcedureEnable {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V6_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2] = self.config_id.into();
+        buf[3] = self.is_enabled.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CSProcedureEnable {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            config_id: CSConfigID::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+            is_enabled: match buf[3] {
+                0 => false,
+                1 => true,
+                _ => return Err(PackError::bad_index(3)),
+            },
+        })
+    }
+}
+/// `HCI_LE_CS_Procedure_Enable_Complete` event. Timing the controller picked for the procedures.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSProcedureEnableComplete {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+    pub config_id: CSConfigID,
+    pub is_enabled: bool,
+    pub tone_antenna_config_selection: u8,
+    pub selected_tx_power: i8,
+    /// Microseconds (24-bit).
+    pub subevent_len: u32,
+    pub subevents_per_event: u8,
+    pub subevent_interval: u16,
+    pub event_interval: u16,
+    pub procedure_interval: u16,
+    pub procedure_count: u16,
+    pub max_procedure_len: u16,
+}
+impl CSProcedureEnableComplete {
+    pub const BYTE_LEN: usize = 1 + 2 + 1 + 1 + 1 + 1 + 3 + 1 + 2 + 2 + 2 + 2 + 2;
+}
+impl MetaEvent for CSProcedureEnableComplete {
+    const META_CODE: MetaEventCode = MetaEventCode::CSProcedureEnableComplete;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CSProcedureEnableComplete {
+            status: unpack_status(buf, 0)?,
+            connection_handle: ConnectionHandle::unpack_from(buf, 1)?,
+            config_id: CSConfigID::try_from(buf[3]).map_err(|_| PackError::bad_index(3))?,
+            is_enabled: match buf[4] {
+                0 => false,
+                1 => true,
+                _ => return Err(PackError::bad_index(4)),
+            },
+            tone_antenna_config_selection: buf[5],
+            selected_tx_power: buf[6] as i8,
+            subevent_len: u24_at(buf, 7),
+            subevents_per_event: buf[10],
+            subevent_interval: u16_at(buf, 11),
+            event_interval: u16_at(buf, 13),
+            procedure_interval: u16_at(buf, 15),
+            procedure_count: u16_at(buf, 17),
+            max_procedure_len: u16_at(buf, 19),
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        if self.subevent_len > 0x00FF_FFFF {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[3] = self.config_id.into();
+        buf[4] = self.is_enabled.into();
+        buf[5] = self.tone_antenna_config_selection;
+        buf[6] = self.selected_tx_power as u8;
+        buf[7..10].copy_from_slice(&self.subevent_len.to_le_bytes()[..3]);
+        buf[10] = self.subevents_per_event;
+        buf[11..13].copy_from_slice(&self.subevent_interval.to_le_bytes());
+        buf[13..15].copy_from_slice(&self.event_interval.to_le_bytes());
+        buf[15..17].copy_from_slice(&self.procedure_interval.to_le_bytes());
+        buf[17..19].copy_from_slice(&self.procedure_count.to_le_bytes());
+        buf[19..21].copy_from_slice(&self.max_procedure_len.to_le_bytes());
+        Ok(())
+    }
+}
+/// One CS step of a subevent result. `data` is mode specific (phase/RTT measurements) and left
+/// undecoded.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSStep {
+    pub mode: u8,
+    pub channel: u8,
+    pub data: Vec<u8>,
+}
+impl CSStep {
+    pub const HEADER_LEN: usize = 3;
+    pub fn byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.data.len()
+    }
+}
+fn steps_byte_len(steps: &[CSStep]) -> usize {
+    steps.iter().map(CSStep::byte_len).sum()
+}
+/// Unpack `num_steps` steps filling all of `buf`. `offset` is only used for error indices.
+fn unpack_steps(buf: &[u8], num_steps: usize, offset: usize) -> Result<Vec<CSStep>, PackError> {
+    let mut steps = Vec::with_capacity(num_steps);
+    let mut i = 0;
+    for _ in 0..num_steps {
+        if buf.len() < i + CSStep::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: offset + i + CSStep::HEADER_LEN,
+                got: offset + buf.len(),
+            });
+        }
+        let end = i + CSStep::HEADER_LEN + usize::from(buf[i + 2]);
+        if buf.len() < end {
+            return Err(PackError::BadLength {
+                expected: offset + end,
+                got: offset + buf.len(),
+            });
+        }
+        steps.push(CSStep {
+            mode: buf[i],
+            channel: buf[i + 1],
+            data: buf[i + CSStep::HEADER_LEN..end].to_vec(),
+        });
+        i = end;
+    }
+    PackError::expect_length(i, buf)?;
+    Ok(steps)
+}
+fn pack_steps(steps: &[CSStep], buf: &mut [u8]) -> Result<(), PackError> {
+    let mut i = 0;
+    for step in steps {
+        buf[i] = step.mode;
+        buf[i + 1] = step.channel;
+        buf[i + 2] = u8::try_from(step.data.len()).map_err(|_| PackError::InvalidFields)?;
+        buf[i + CSStep::HEADER_LEN..i + step.byte_len()].copy_from_slice(&step.data);
+        i += step.byte_len();
+    }
+    Ok(())
+}
+/// Status fields shared by [`CSSubeventResult`] and [`CSSubeventResultContinue`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSResultStatus {
+    /// `0x0` all results complete, `0x1` partial results (more to follow), `0xF` aborted.
+    pub procedure_done_status: u8,
+    pub subevent_done_status: u8,
+    pub abort_reason: u8,
+    pub num_antenna_paths: u8,
+}
+/// `HCI_LE_CS_Subevent_Result` event. Results of one CS subevent (more may follow in
+/// [`CSSubeventResultContinue`] events).
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSSubeventResult {
+    pub connection_handle: ConnectionHandle,
+    pub config_id: CSConfigID,
+    pub start_acl_connection_event_counter: u16,
+    pub procedure_counter: u16,
+    pub frequency_compensation: u16,
+    pub reference_power_level: i8,
+    pub status: CSResultStatus,
+    pub steps: Vec<CSStep>,
+}
+impl CSSubeventResult {
+    pub const HEADER_LEN: usize = 2 + 1 + 2 + 2 + 2 + 1 + 1 + 1 + 1 + 1 + 1;
+}
+impl MetaEvent for CSSubeventResult {
+    const META_CODE: MetaEventCode = MetaEventCode::CSSubeventResult;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::HEADER_LEN + steps_byte_len(&self.steps)
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        Ok(CSSubeventResult {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            config_id: CSConfigID::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+            start_acl_connection_event_counter: u16_at(buf, 3),
+            procedure_counter: u16_at(buf, 5),
+            frequency_compensation: u16_at(buf, 7),
+            reference_power_level: buf[9] as i8,
+            status: CSResultStatus {
+                procedure_done_status: buf[10],
+                subevent_done_status: buf[11],
+                abort_reason: buf[12],
+                num_antenna_paths: buf[13],
+            },
+            steps: unpack_steps(
+                &buf[Self::HEADER_LEN..],
+                usize::from(buf[14]),
+                Self::HEADER_LEN,
+            )?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.meta_byte_len(), buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2] = self.config_id.into();
+        buf[3..5].copy_from_slice(&self.start_acl_connection_event_counter.to_le_bytes());
+        buf[5..7].copy_from_slice(&self.procedure_counter.to_le_bytes());
+        buf[7..9].copy_from_slice(&self.frequency_compensation.to_le_bytes());
+        buf[9] = self.reference_power_level as u8;
+        buf[10] = self.status.procedure_done_status;
+        buf[11] = self.status.subevent_done_status;
+        buf[12] = self.status.abort_reason;
+        buf[13] = self.status.num_antenna_paths;
+        buf[14] = u8::try_from(self.steps.len()).map_err(|_| PackError::InvalidFields)?;
+        pack_steps(&self.steps, &mut buf[Self::HEADER_LEN..])
+    }
+}
+/// `HCI_LE_CS_Subevent_Result_Continue` event. More steps for the last [`CSSubeventResult`].
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CSSubeventResultContinue {
+    pub connection_handle: ConnectionHandle,
+    pub config_id: CSConfigID,
+    pub status: CSResultStatus,
+    pub steps: Vec<CSStep>,
+}
+impl CSSubeventResultContinue {
+    pub const HEADER_LEN: usize = 2 + 1 + 1 + 1 + 1 + 1 + 1;
+}
+impl MetaEvent for CSSubeventResultContinue {
+    const META_CODE: MetaEventCode = MetaEventCode::CSSubeventResultContinue;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::HEADER_LEN + steps_byte_len(&self.steps)
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        Ok(CSSubeventResultContinue {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            config_id: CSConfigID::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+            status: CSResultStatus {
+                procedure_done_status: buf[3],
+                subevent_done_status: buf[4],
+                abort_reason: buf[5],
+                num_antenna_paths: buf[6],
+            },
+            steps: unpack_steps(
+                &buf[Self::HEADER_LEN..],
+                usize::from(buf[7]),
+                Self::HEADER_LEN,
+            )?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.meta_byte_len(), buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2] = self.config_id.into();
+        buf[3] = self.status.procedure_done_status;
+        buf[4] = self.status.subevent_done_status;
+        buf[5] = self.status.abort_reason;
+        buf[6] = self.status.num_antenna_paths;
+        buf[7] = u8::try_from(self.steps.len()).map_err(|_| PackError::InvalidFields)?;
+        pack_steps(&self.steps, &mut buf[Self::HEADER_LEN..])
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hci::invariants::Codec;
+    #[test]
+    fn test_round_trips() {
+        let handle = ConnectionHandle::new(0x40);
+        let parameters = CSConfigParameters {
+            main_mode_type: 2,
+            sub_mode_type: 0xFF,
+            min_main_mode_steps: 2,
+            max_main_mode_steps: 5,
+            main_mode_repetition: 0,
+            mode_0_steps: 3,
+            role: CSRole::Initiator,
+            rtt_type: 0,
+            cs_sync_phy: 1,
+            channel_map: [0xFC, 0xFF, 0x7F, 0xFC, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x1F],
+            channel_map_repetition: 1,
+            channel_selection_type: 0,
+            ch3c_shape: 0,
+            ch3c_jump: 2,
+        };
+        let bytes = Codec::command()
+            .round_trip(&CSCreateConfig {
+                connection_handle: handle,
+                config_id: CSConfigID::new(1),
+                create_context: true,
+                parameters,
+            })
+            .unwrap();
+        assert_eq!(bytes.len(), 28);
+        Codec::meta_event()
+            .round_trip(&CSConfigComplete {
+                status: ErrorCode::Ok,
+                connection_handle: handle,
+                config_id: CSConfigID::new(1),
+                action: 1,
+                parameters,
+                t_ip1_time: 145,
+                t_ip2_time: 145,
+                t_fcs_time: 150,
+                t_pm_time: 40,
+            })
+            .unwrap();
+        Codec::meta_event()
+            .round_trip(&CSSubeventResult {
+                connection_handle: handle,
+                config_id: CSConfigID::new(1),
+                start_acl_connection_event_counter: 10,
+                procedure_counter: 1,
+                frequency_compensation: 0xC000,
+                reference_power_level: -20,
+                status: CSResultStatus {
+                    procedure_done_status: 1,
+                    subevent_done_status: 0,
+                    abort_reason: 0,
+                    num_antenna_paths: 1,
+                },
+                steps: vec![
+                    CSStep {
+                        mode: 0,
+                        channel: 10,
+                        data: vec![1, 2, 3, 4, 5],
+                    },
+                    CSStep {
+                        mode: 2,
+                        channel: 40,
+                        data: vec![],
+                    },
+                ],
+            })
+            .unwrap();
+        Codec::return_parameters()
+            .round_trip(&CSLocalCapabilitiesReturn {
+                status: ErrorCode::Ok,
+                capabilities: CSCapabilities {
+                    num_config_supported: 4,
+                    roles_supported: 0b11,
+                    ..CSCapabilities::default()
+                },
+            })
+            .unwrap();
+    }
+}
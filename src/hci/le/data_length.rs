@@ -0,0 +1,292 @@
+//! LE Data Length Extension (Core 4.2+). Without it link layer payloads are limited to 27
+//! octets. [`SetDataLength`] asks for longer ones on a connection, the suggested default is used
+//! for new connections and [`DataLengthChange`] reports what the link ended up with.
+use crate::hci::baseband::HandleReturn;
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, ReturnParameters, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::connection::or_min;
+use crate::hci::le::{LEControllerOpcode, MetaEvent, MetaEventCode};
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::connection::qos::DataLength;
+use crate::le::connection::ConnectionHandle;
+use crate::PackError;
+use core::convert::TryFrom;
+
+/// The data length returned by a failed command isn't valid and is replaced by
+/// [`DataLength::MIN`].
+fn unpack_data_length(buf: &[u8], index: usize, is_ok: bool) -> Result<DataLength, PackError> {
+    or_min(
+        DataLength::unpack_from(&buf[index..index + DataLength::BYTE_LEN]).ok(),
+        DataLength::MIN,
+        is_ok,
+        index,
+    )
+}
+/// `HCI_LE_Set_Data_Length`. Suggest the longest TX payload for a connection. The controller
+/// negotiates with the peer and sends [`DataLengthChange`] if the link changes.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetDataLength {
+    pub connection_handle: ConnectionHandle,
+    pub tx: DataLength,
+}
+impl SetDataLength {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetDataLength;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + DataLength::BYTE_LEN;
+}
+impl Command for SetDataLength {
+    type Return = CommandComplete<HandleReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        self.tx.pack_into(&mut buf[2..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(SetDataLength {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            tx: DataLength::unpack_from(&buf[2..])?,
+        })
+    }
+}
+/// `HCI_LE_Read_Suggested_Default_Data_Length`. TX data length used for new connections.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadSuggestedDefaultDataLength {}
+impl ReadSuggestedDefaultDataLength {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReadSuggestedDefaultDataLength;
+}
+impl Command for ReadSuggestedDefaultDataLength {
+    type Return = CommandComplete<SuggestedDataLengthReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadSuggestedDefaultDataLength {})
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SuggestedDataLengthReturn {
+    pub status: ErrorCode,
+    pub tx: DataLength,
+}
+impl SuggestedDataLengthReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + DataLength::BYTE_LEN;
+}
+impl ReturnParameters for SuggestedDataLengthReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        self.tx.pack_into(&mut buf[1..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let status = ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?;
+        Ok(SuggestedDataLengthReturn {
+            status,
+            tx: unpack_data_length(buf, 1, status == ErrorCode::Ok)?,
+        })
+    }
+}
+/// `HCI_LE_Write_Suggested_Default_Data_Length`. TX data length to use for new connections.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct WriteSuggestedDefaultDataLength {
+    pub tx: DataLength,
+}
+impl WriteSuggestedDefaultDataLength {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::WriteSuggestedDefaultDataLength;
+}
+impl Command for WriteSuggestedDefaultDataLength {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        DataLength::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.tx.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(WriteSuggestedDefaultDataLength {
+            tx: DataLength::unpack_from(buf)?,
+        })
+    }
+}
+/// `HCI_LE_Read_Maximum_Data_Length`. Longest payloads the controller supports.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadMaximumDataLength {}
+impl ReadMaximumDataLength {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReadMaximumDataLength;
+}
+impl Command for ReadMaximumDataLength {
+    type Return = CommandComplete<MaximumDataLengthReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadMaximumDataLength {})
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct MaximumDataLengthReturn {
+    pub status: ErrorCode,
+    pub max_tx: DataLength,
+    pub max_rx: DataLength,
+}
+impl MaximumDataLengthReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + DataLength::BYTE_LEN * 2;
+}
+impl ReturnParameters for MaximumDataLengthReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        self.max_tx.pack_into(&mut buf[1..5])?;
+        self.max_rx.pack_into(&mut buf[5..9])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let status = ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?;
+        let is_ok = status == ErrorCode::Ok;
+        Ok(MaximumDataLengthReturn {
+            status,
+            max_tx: unpack_data_length(buf, 1, is_ok)?,
+            max_rx: unpack_data_length(buf, 5, is_ok)?,
+        })
+    }
+}
+/// `HCI_LE_Data_Length_Change` event. The longest payloads the link now uses in each direction.
+/// Feed `max_tx.max_octets` to [`crate::hci::acl::ACLFragmenter::set_max_tx_octets`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct DataLengthChange {
+    pub connection_handle: ConnectionHandle,
+    pub max_tx: DataLength,
+    pub max_rx: DataLength,
+}
+impl DataLengthChange {
+    pub const CODE: MetaEventCode = MetaEventCode::DataLengthChange;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + DataLength::BYTE_LEN * 2;
+}
+impl MetaEvent for DataLengthChange {
+    const META_CODE: MetaEventCode = Self::CODE;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(DataLengthChange {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            max_tx: DataLength::unpack_from(&buf[2..6])?,
+            max_rx: DataLength::unpack_from(&buf[6..10])?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        self.max_tx.pack_into(&mut buf[2..6])?;
+        self.max_rx.pack_into(&mut buf[6..10])
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_data_length() {
+        let set = SetDataLength {
+            connection_handle: ConnectionHandle::new(0x0040),
+            tx: DataLength::MAX_UNCODED,
+        };
+        let packet = set.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(
+            packet.parameters.as_ref(),
+            &[0x40, 0x00, 0xFB, 0x00, 0x48, 0x08]
+        );
+        assert_eq!(SetDataLength::unpack_command_packet(&packet), Ok(set));
+        // 26 octets is below the minimum.
+        assert!(WriteSuggestedDefaultDataLength::unpack_from(&[0x1A, 0x00, 0x48, 0x01]).is_err());
+
+        let max = [0x00, 0xFB, 0x00, 0x90, 0x42, 0xFB, 0x00, 0x90, 0x42];
+        let returned = MaximumDataLengthReturn::unpack_from(&max).unwrap();
+        assert_eq!(u16::from(returned.max_rx.max_time), 17040);
+        let mut buf = [0_u8; MaximumDataLengthReturn::BYTE_LEN];
+        returned.pack_into(&mut buf).unwrap();
+        assert_eq!(buf, max);
+        // A failed read doesn't return valid lengths.
+        let failed = MaximumDataLengthReturn::unpack_from(&[0x01, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        assert_eq!(failed.max_tx, DataLength::MIN);
+    }
+}
@@ -0,0 +1,336 @@
+//! LE link layer encryption. The central encrypts a link with [`StartEncryption`]. The
+//! peripheral's controller asks the host for the key with a [`LongTermKeyRequest`], answered by
+//! [`LongTermKeyRequestReply`] or [`LongTermKeyRequestNegativeReply`]. Both sides then get an
+//! [`EncryptionChange`] (or an [`EncryptionKeyRefreshComplete`] if the link was already
+//! encrypted).
+use crate::hci::baseband::HandleReturn;
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, CommandStatus, Event, EventCode};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::{LEControllerOpcode, MetaEvent, MetaEventCode};
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::connection::ConnectionHandle;
+use crate::le::smp::keys::LTK;
+use crate::{ConversionError, PackError};
+use core::convert::{TryFrom, TryInto};
+
+fn unpack_ltk(buf: &[u8]) -> LTK {
+    LTK(buf.try_into().expect("caller checked the length"))
+}
+/// `Random_Number` and `Encrypted_Diversifier` identifying a legacy pairing LTK. Both are zero
+/// for keys from LE Secure Connections.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct KeyIdentifier {
+    pub random_number: u64,
+    pub encrypted_diversifier: u16,
+}
+impl KeyIdentifier {
+    pub const BYTE_LEN: usize = 8 + 2;
+    pub const SECURE_CONNECTIONS: KeyIdentifier = KeyIdentifier {
+        random_number: 0,
+        encrypted_diversifier: 0,
+    };
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..8].copy_from_slice(&self.random_number.to_le_bytes());
+        buf[8..10].copy_from_slice(&self.encrypted_diversifier.to_le_bytes());
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<KeyIdentifier, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(KeyIdentifier {
+            random_number: u64::from_le_bytes(buf[0..8].try_into().expect("length checked above")),
+            encrypted_diversifier: u16::from_le_bytes([buf[8], buf[9]]),
+        })
+    }
+}
+/// `HCI_LE_Enable_Encryption` (`HCI_LE_Start_Encryption`). Central only. Completes with an
+/// [`EncryptionChange`] or [`EncryptionKeyRefreshComplete`] event.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct StartEncryption {
+    pub connection_handle: ConnectionHandle,
+    pub key_identifier: KeyIdentifier,
+    pub ltk: LTK,
+}
+impl StartEncryption {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::StartEncryption;
+    pub const BYTE_LEN: usize =
+        ConnectionHandle::BYTE_LEN + KeyIdentifier::BYTE_LEN + LTK::BYTE_LEN;
+}
+impl Command for StartEncryption {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        self.key_identifier.pack_into(&mut buf[2..12])?;
+        buf[12..28].copy_from_slice(&self.ltk.0);
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(StartEncryption {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            key_identifier: KeyIdentifier::unpack_from(&buf[2..12])?,
+            ltk: unpack_ltk(&buf[12..28]),
+        })
+    }
+}
+/// `HCI_LE_Long_Term_Key_Request_Reply`. Answers a [`LongTermKeyRequest`] with the key.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct LongTermKeyRequestReply {
+    pub connection_handle: ConnectionHandle,
+    pub ltk: LTK,
+}
+impl LongTermKeyRequestReply {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::LongTermKeyRequestReply;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + LTK::BYTE_LEN;
+}
+impl Command for LongTermKeyRequestReply {
+    type Return = CommandComplete<HandleReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2..18].copy_from_slice(&self.ltk.0);
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LongTermKeyRequestReply {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            ltk: unpack_ltk(&buf[2..18]),
+        })
+    }
+}
+/// `HCI_LE_Long_Term_Key_Request_Negative_Reply`. The host has no key for the link. The peer
+/// gets `ErrorCode::KeyMissing`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct LongTermKeyRequestNegativeReply {
+    pub connection_handle: ConnectionHandle,
+}
+impl LongTermKeyRequestNegativeReply {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::LongTermKeyRequestNegativeReply;
+}
+impl Command for LongTermKeyRequestNegativeReply {
+    type Return = CommandComplete<HandleReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ConnectionHandle::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        Ok(LongTermKeyRequestNegativeReply {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+        })
+    }
+}
+/// `HCI_LE_Long_Term_Key_Request` event. The central started encryption (peripheral only).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct LongTermKeyRequest {
+    pub connection_handle: ConnectionHandle,
+    pub key_identifier: KeyIdentifier,
+}
+impl LongTermKeyRequest {
+    pub const CODE: MetaEventCode = MetaEventCode::LongTermKeyRequest;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + KeyIdentifier::BYTE_LEN;
+}
+impl MetaEvent for LongTermKeyRequest {
+    const META_CODE: MetaEventCode = Self::CODE;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LongTermKeyRequest {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            key_identifier: KeyIdentifier::unpack_from(&buf[2..])?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        self.key_identifier.pack_into(&mut buf[2..])
+    }
+}
+/// `Encryption_Enabled` of an [`EncryptionChange`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum EncryptionMode {
+    Off = 0x00,
+    /// AES-CCM on LE links (E0 on BR/EDR links).
+    On = 0x01,
+    /// AES-CCM on BR/EDR links.
+    BREDRAESCCM = 0x02,
+}
+impl From<EncryptionMode> for u8 {
+    fn from(m: EncryptionMode) -> Self {
+        m as u8
+    }
+}
+impl TryFrom<u8> for EncryptionMode {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(EncryptionMode::Off),
+            0x01 => Ok(EncryptionMode::On),
+            0x02 => Ok(EncryptionMode::BREDRAESCCM),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// `HCI_Encryption_Change` event. Encryption of a link was turned on or off (or failed to be).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct EncryptionChange {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+    pub mode: EncryptionMode,
+}
+impl EncryptionChange {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN + 1;
+    pub fn is_encrypted(&self) -> bool {
+        self.status == ErrorCode::Ok && self.mode != EncryptionMode::Off
+    }
+}
+impl Event for EncryptionChange {
+    const EVENT_CODE: EventCode = EventCode::EncryptionChange;
+
+    fn event_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(EncryptionChange {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            connection_handle: ConnectionHandle::unpack_from(buf, 1)?,
+            mode: EncryptionMode::try_from(buf[3]).map_err(|_| PackError::bad_index(3))?,
+        })
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[3] = self.mode.into();
+        Ok(())
+    }
+}
+/// `HCI_Encryption_Key_Refresh_Complete` event. An already encrypted link switched keys.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct EncryptionKeyRefreshComplete {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+}
+impl EncryptionKeyRefreshComplete {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN;
+}
+impl Event for EncryptionKeyRefreshComplete {
+    const EVENT_CODE: EventCode = EventCode::EncryptionKeyRefreshComplete;
+
+    fn event_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(EncryptionKeyRefreshComplete {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            connection_handle: ConnectionHandle::unpack_from(buf, 1)?,
+        })
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_encryption() {
+        let start = StartEncryption {
+            connection_handle: ConnectionHandle::new(0x0040),
+            key_identifier: KeyIdentifier {
+                random_number: 0x0102_0304_0506_0708,
+                encrypted_diversifier: 0xABCD,
+            },
+            ltk: LTK([0x11; 16]),
+        };
+        let packet = start.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(
+            &packet.parameters[..12],
+            &[0x40, 0x00, 0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, 0xCD, 0xAB]
+        );
+        assert_eq!(StartEncryption::unpack_command_packet(&packet), Ok(start));
+
+        let mut buf = [0_u8; LongTermKeyRequest::BYTE_LEN];
+        let request = LongTermKeyRequest {
+            connection_handle: ConnectionHandle::new(0x0040),
+            key_identifier: KeyIdentifier::SECURE_CONNECTIONS,
+        };
+        request.meta_pack_into(&mut buf).unwrap();
+        assert_eq!(LongTermKeyRequest::meta_unpack_from(&buf), Ok(request));
+
+        let change = EncryptionChange::event_unpack_from(&[0x00, 0x40, 0x00, 0x01]).unwrap();
+        assert!(change.is_encrypted());
+        assert!(EncryptionChange::event_unpack_from(&[0x00, 0x40, 0x00, 0x03]).is_err());
+    }
+}
@@ -0,0 +1,837 @@
+//! LE Extended Advertising commands (Bluetooth 5.0). Advertising sets (see
+//! [`AdvertisingSetHandle`]) with their own parameters and up to 1650 bytes of data, sent in
+//! [`SetExtendedAdvertisingData`] fragments of up to [`ExtendedAdvertisingData::MAX_FRAGMENT_LEN`]
+//! bytes.
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, ReturnParameters, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::advertise::{TxPowerLevel, TxPowerLevelReturn};
+use crate::hci::le::LEControllerOpcode;
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::advertiser::{
+    AdvertisingInterval, AdvertisingSetHandle, ChannelMap, FilterPolicy, OwnAddressType,
+    PeerAddressType,
+};
+use crate::le::phy::Phy;
+use crate::le::report::AdvertisingSID;
+use crate::{BTAddress, ConversionError, PackError, BT_ADDRESS_LEN};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// `Advertising_Event_Properties` bit field.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct AdvertisingEventProperties(u16);
+impl AdvertisingEventProperties {
+    pub const BYTE_LEN: usize = 2;
+    pub const CONNECTABLE: u16 = 1 << 0;
+    pub const SCANNABLE: u16 = 1 << 1;
+    pub const DIRECTED: u16 = 1 << 2;
+    /// High duty cycle directed connectable (legacy) advertising.
+    pub const HIGH_DUTY_CYCLE: u16 = 1 << 3;
+    /// Use legacy advertising PDUs. Data is limited to 31 bytes.
+    pub const LEGACY: u16 = 1 << 4;
+    /// Leave the advertiser's address out of all PDUs.
+    pub const ANONYMOUS: u16 = 1 << 5;
+    /// Include the TX power in the extended header of at least one PDU.
+    pub const INCLUDE_TX_POWER: u16 = 1 << 6;
+    /// Mask of the defined bits. The rest are reserved.
+    pub const MASK: u16 = 0x7F;
+    /// Legacy `ADV_IND`.
+    pub const LEGACY_ADV_IND: AdvertisingEventProperties =
+        AdvertisingEventProperties(Self::LEGACY | Self::CONNECTABLE | Self::SCANNABLE);
+    /// Legacy `ADV_NONCONN_IND`.
+    pub const LEGACY_ADV_NONCONN_IND: AdvertisingEventProperties =
+        AdvertisingEventProperties(Self::LEGACY);
+    pub fn new(bits: u16) -> AdvertisingEventProperties {
+        Self::new_checked(bits).expect("invalid advertising event properties")
+    }
+    /// Returns `None` if reserved bits are set.
+    pub fn new_checked(bits: u16) -> Option<AdvertisingEventProperties> {
+        if bits & !Self::MASK != 0 {
+            None
+        } else {
+            Some(AdvertisingEventProperties(bits))
+        }
+    }
+    pub fn is_connectable(self) -> bool {
+        self.0 & Self::CONNECTABLE != 0
+    }
+    pub fn is_scannable(self) -> bool {
+        self.0 & Self::SCANNABLE != 0
+    }
+    pub fn is_directed(self) -> bool {
+        self.0 & Self::DIRECTED != 0
+    }
+    pub fn is_legacy(self) -> bool {
+        self.0 & Self::LEGACY != 0
+    }
+    pub fn is_anonymous(self) -> bool {
+        self.0 & Self::ANONYMOUS != 0
+    }
+    /// Extended (non legacy) advertising can't be both connectable and scannable.
+    pub fn is_valid(self) -> bool {
+        self.is_legacy() || !(self.is_connectable() && self.is_scannable())
+    }
+}
+impl From<AdvertisingEventProperties> for u16 {
+    fn from(p: AdvertisingEventProperties) -> Self {
+        p.0
+    }
+}
+impl TryFrom<u16> for AdvertisingEventProperties {
+    type Error = ConversionError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        AdvertisingEventProperties::new_checked(value).ok_or(ConversionError(()))
+    }
+}
+/// Primary advertising interval in units of 0.625 ms. Range `0x000020-0xFFFFFF` (24 bits,
+/// unlike the legacy [`AdvertisingInterval`]).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct ExtendedAdvertisingInterval(u32);
+impl ExtendedAdvertisingInterval {
+    pub const BYTE_LEN: usize = 3;
+    pub const MIN_U32: u32 = 0x00_0020;
+    pub const MAX_U32: u32 = 0xFF_FFFF;
+    pub const MIN: ExtendedAdvertisingInterval = ExtendedAdvertisingInterval(Self::MIN_U32);
+    pub const MAX: ExtendedAdvertisingInterval = ExtendedAdvertisingInterval(Self::MAX_U32);
+    pub fn new_checked(interval: u32) -> Option<ExtendedAdvertisingInterval> {
+        if (Self::MIN_U32..=Self::MAX_U32).contains(&interval) {
+            Some(ExtendedAdvertisingInterval(interval))
+        } else {
+            None
+        }
+    }
+    pub const fn as_microseconds(self) -> u64 {
+        self.0 as u64 * 625
+    }
+    pub const fn as_duration(self) -> core::time::Duration {
+        core::time::Duration::from_micros(self.as_microseconds())
+    }
+    fn pack_into(self, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.0.to_le_bytes()[..Self::BYTE_LEN]);
+    }
+    fn unpack_from(buf: &[u8]) -> Option<ExtendedAdvertisingInterval> {
+        Self::new_checked(u32::from_le_bytes([buf[0], buf[1], buf[2], 0]))
+    }
+}
+impl Default for ExtendedAdvertisingInterval {
+    fn default() -> Self {
+        AdvertisingInterval::DEFAULT.into()
+    }
+}
+impl From<AdvertisingInterval> for ExtendedAdvertisingInterval {
+    fn from(interval: AdvertisingInterval) -> Self {
+        ExtendedAdvertisingInterval(u16::from(interval).into())
+    }
+}
+impl From<ExtendedAdvertisingInterval> for u32 {
+    fn from(interval: ExtendedAdvertisingInterval) -> Self {
+        interval.0
+    }
+}
+impl TryFrom<u32> for ExtendedAdvertisingInterval {
+    type Error = ConversionError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        ExtendedAdvertisingInterval::new_checked(value).ok_or(ConversionError(()))
+    }
+}
+/// Parameters of one advertising set.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct ExtendedAdvertisingParameters {
+    pub handle: AdvertisingSetHandle,
+    pub properties: AdvertisingEventProperties,
+    pub interval_min: ExtendedAdvertisingInterval,
+    pub interval_max: ExtendedAdvertisingInterval,
+    pub channel_map: ChannelMap,
+    pub own_address_type: OwnAddressType,
+    pub peer_address_type: PeerAddressType,
+    pub peer_address: BTAddress,
+    pub filter_policy: FilterPolicy,
+    /// Maximum TX power or `None` to let the controller choose.
+    pub tx_power: Option<TxPowerLevel>,
+    /// LE 1M or LE Coded. LE 2M can't be used on the primary advertising channels.
+    pub primary_phy: Phy,
+    /// Auxiliary packets that can be skipped before sending one (`0` sends one every event).
+    pub secondary_max_skip: u8,
+    pub secondary_phy: Phy,
+    pub sid: AdvertisingSID,
+    /// Send LE Scan Request Received events.
+    pub scan_request_notification: bool,
+}
+impl ExtendedAdvertisingParameters {
+    pub const BYTE_LEN: usize = 25;
+    /// `Advertising_TX_Power` meaning no preference.
+    pub const NO_TX_POWER_PREFERENCE: u8 = 0x7F;
+    pub fn new(handle: AdvertisingSetHandle) -> ExtendedAdvertisingParameters {
+        ExtendedAdvertisingParameters {
+            handle,
+            properties: AdvertisingEventProperties::LEGACY_ADV_IND,
+            interval_min: ExtendedAdvertisingInterval::default(),
+            interval_max: ExtendedAdvertisingInterval::default(),
+            channel_map: ChannelMap::default(),
+            own_address_type: OwnAddressType::default(),
+            peer_address_type: PeerAddressType::default(),
+            peer_address: BTAddress::ZEROED,
+            filter_policy: FilterPolicy::default(),
+            tx_power: None,
+            primary_phy: Phy::LE1M,
+            secondary_max_skip: 0,
+            secondary_phy: Phy::LE1M,
+            sid: AdvertisingSID::default(),
+            scan_request_notification: false,
+        }
+    }
+}
+/// `HCI_LE_Set_Extended_Advertising_Parameters`. Returns the TX power the controller picked.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetExtendedAdvertisingParameters(pub ExtendedAdvertisingParameters);
+impl SetExtendedAdvertisingParameters {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetExtendedAdvertisingParameters;
+}
+impl Command for SetExtendedAdvertisingParameters {
+    type Return = CommandComplete<TxPowerLevelReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ExtendedAdvertisingParameters::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(ExtendedAdvertisingParameters::BYTE_LEN, buf)?;
+        let p = &self.0;
+        if p.interval_max < p.interval_min || p.primary_phy == Phy::LE2M || !p.properties.is_valid()
+        {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = p.handle.into();
+        buf[1..3].copy_from_slice(&u16::from(p.properties).to_le_bytes());
+        p.interval_min.pack_into(&mut buf[3..6]);
+        p.interval_max.pack_into(&mut buf[6..9]);
+        buf[9] = p.channel_map.into();
+        buf[10] = p.own_address_type.into();
+        buf[11] = p.peer_address_type.into();
+        p.peer_address
+            .pack_into(&mut buf[12..12 + BT_ADDRESS_LEN])?;
+        buf[18] = p.filter_policy.into();
+        buf[19] = p.tx_power.map_or(
+            ExtendedAdvertisingParameters::NO_TX_POWER_PREFERENCE,
+            u8::from,
+        );
+        buf[20] = p.primary_phy.into();
+        buf[21] = p.secondary_max_skip;
+        buf[22] = p.secondary_phy.into();
+        buf[23] = p.sid.into();
+        buf[24] = p.scan_request_notification.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(ExtendedAdvertisingParameters::BYTE_LEN, buf)?;
+        Ok(SetExtendedAdvertisingParameters(
+            ExtendedAdvertisingParameters {
+                handle: AdvertisingSetHandle::try_from(buf[0])
+                    .map_err(|_| PackError::bad_index(0))?,
+                properties: AdvertisingEventProperties::try_from(u16::from_le_bytes([
+                    buf[1], buf[2],
+                ]))
+                .map_err(|_| PackError::bad_index(1))?,
+                interval_min: ExtendedAdvertisingInterval::unpack_from(&buf[3..6])
+                    .ok_or(PackError::bad_index(3))?,
+                interval_max: ExtendedAdvertisingInterval::unpack_from(&buf[6..9])
+                    .ok_or(PackError::bad_index(6))?,
+                channel_map: ChannelMap::try_from(buf[9]).map_err(|_| PackError::bad_index(9))?,
+                own_address_type: OwnAddressType::try_from(buf[10])
+                    .map_err(|_| PackError::bad_index(10))?,
+                peer_address_type: PeerAddressType::try_from(buf[11])
+                    .map_err(|_| PackError::bad_index(11))?,
+                peer_address: BTAddress::unpack_from(&buf[12..12 + BT_ADDRESS_LEN])?,
+                filter_policy: FilterPolicy::try_from(buf[18])
+                    .map_err(|_| PackError::bad_index(18))?,
+                tx_power: match buf[19] {
+                    ExtendedAdvertisingParameters::NO_TX_POWER_PREFERENCE => None,
+                    p => Some(TxPowerLevel::try_from(p).map_err(|_| PackError::bad_index(19))?),
+                },
+                primary_phy: Phy::try_from(buf[20]).map_err(|_| PackError::bad_index(20))?,
+                secondary_max_skip: buf[21],
+                secondary_phy: Phy::try_from(buf[22]).map_err(|_| PackError::bad_index(22))?,
+                sid: AdvertisingSID::try_from(buf[23]).map_err(|_| PackError::bad_index(23))?,
+                scan_request_notification: match buf[24] {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(PackError::bad_index(24)),
+                },
+            },
+        ))
+    }
+}
+/// `Operation` of an extended advertising (or scan response) data fragment.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum DataOperation {
+    IntermediateFragment = 0x00,
+    FirstFragment = 0x01,
+    LastFragment = 0x02,
+    /// All the data in one command.
+    Complete = 0x03,
+    /// Keep the data but change the Advertising DID (advertising data only).
+    UnchangedData = 0x04,
+}
+impl From<DataOperation> for u8 {
+    fn from(op: DataOperation) -> Self {
+        op as u8
+    }
+}
+impl TryFrom<u8> for DataOperation {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(DataOperation::IntermediateFragment),
+            0x01 => Ok(DataOperation::FirstFragment),
+            0x02 => Ok(DataOperation::LastFragment),
+            0x03 => Ok(DataOperation::Complete),
+            0x04 => Ok(DataOperation::UnchangedData),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// One fragment of the data of an advertising set. Shared by [`SetExtendedAdvertisingData`] and
+/// [`SetExtendedScanResponseData`].
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ExtendedAdvertisingData {
+    pub handle: AdvertisingSetHandle,
+    pub operation: DataOperation,
+    /// Let the controller split the data over more PDUs than needed (`Fragment_Preference`).
+    pub controller_may_fragment: bool,
+    pub data: Vec<u8>,
+}
+impl ExtendedAdvertisingData {
+    pub const HEADER_LEN: usize = 4;
+    pub const MAX_FRAGMENT_LEN: usize = 251;
+    /// Maximum advertising data of one set. Controllers may support less (see
+    /// `HCI_LE_Read_Maximum_Advertising_Data_Length`).
+    pub const MAX_DATA_LEN: usize = 1650;
+    /// Split `data` into as many fragments as needed. Empty `data` is one empty
+    /// [`DataOperation::Complete`] fragment (clears the data).
+    pub fn fragments(
+        handle: AdvertisingSetHandle,
+        data: &[u8],
+    ) -> impl Iterator<Item = ExtendedAdvertisingData> + '_ {
+        let chunks: Vec<&[u8]> = if data.is_empty() {
+            alloc::vec![data]
+        } else {
+            data.chunks(Self::MAX_FRAGMENT_LEN).collect()
+        };
+        let count = chunks.len();
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(move |(i, chunk)| ExtendedAdvertisingData {
+                handle,
+                operation: match i {
+                    _ if count == 1 => DataOperation::Complete,
+                    0 => DataOperation::FirstFragment,
+                    i if i + 1 == count => DataOperation::LastFragment,
+                    _ => DataOperation::IntermediateFragment,
+                },
+                controller_may_fragment: true,
+                data: chunk.to_vec(),
+            })
+    }
+    fn byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.data.len()
+    }
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        if self.data.len() > Self::MAX_FRAGMENT_LEN {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = self.handle.into();
+        buf[1] = self.operation.into();
+        buf[2] = (!self.controller_may_fragment).into();
+        buf[3] = u8::try_from(self.data.len()).map_err(|_| PackError::InvalidFields)?;
+        buf[Self::HEADER_LEN..].copy_from_slice(&self.data);
+        Ok(())
+    }
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError> {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        let data_len = usize::from(buf[3]);
+        if data_len > Self::MAX_FRAGMENT_LEN {
+            return Err(PackError::bad_index(3));
+        }
+        PackError::expect_length(Self::HEADER_LEN + data_len, buf)?;
+        Ok(ExtendedAdvertisingData {
+            handle: AdvertisingSetHandle::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            operation: DataOperation::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?,
+            controller_may_fragment: match buf[2] {
+                0 => true,
+                1 => false,
+                _ => return Err(PackError::bad_index(2)),
+            },
+            data: buf[Self::HEADER_LEN..].to_vec(),
+        })
+    }
+}
+/// `HCI_LE_Set_Extended_Advertising_Data`.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetExtendedAdvertisingData(pub ExtendedAdvertisingData);
+impl SetExtendedAdvertisingData {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetExtendedAdvertisingData;
+}
+impl Command for SetExtendedAdvertisingData {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        self.0.byte_len()
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.0.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(SetExtendedAdvertisingData(
+            ExtendedAdvertisingData::unpack_from(buf)?,
+        ))
+    }
+}
+/// `HCI_LE_Set_Extended_Scan_Response_Data`. [`DataOperation::UnchangedData`] isn't allowed.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetExtendedScanResponseData(pub ExtendedAdvertisingData);
+impl SetExtendedScanResponseData {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetExtendedScanResponseData;
+}
+impl Command for SetExtendedScanResponseData {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        self.0.byte_len()
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        if self.0.operation == DataOperation::UnchangedData {
+            return Err(PackError::InvalidFields);
+        }
+        self.0.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        let data = ExtendedAdvertisingData::unpack_from(buf)?;
+        if data.operation == DataOperation::UnchangedData {
+            return Err(PackError::bad_index(1));
+        }
+        Ok(SetExtendedScanResponseData(data))
+    }
+}
+/// One set of [`SetExtendedAdvertisingEnable`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct AdvertisingSetEnable {
+    pub handle: AdvertisingSetHandle,
+    /// How long to advertise in units of 10 ms. `0` advertises until disabled.
+    pub duration: u16,
+    /// Extended advertising events to send before stopping. `0` means no limit.
+    pub max_extended_advertising_events: u8,
+}
+impl AdvertisingSetEnable {
+    pub const BYTE_LEN: usize = 4;
+    /// Advertise until disabled.
+    pub fn new(handle: AdvertisingSetHandle) -> AdvertisingSetEnable {
+        AdvertisingSetEnable {
+            handle,
+            duration: 0,
+            max_extended_advertising_events: 0,
+        }
+    }
+}
+/// `HCI_LE_Set_Extended_Advertising_Enable`. Disabling with no `sets` disables every set.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetExtendedAdvertisingEnable {
+    pub is_enabled: bool,
+    pub sets: Vec<AdvertisingSetEnable>,
+}
+impl SetExtendedAdvertisingEnable {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetExtendedAdvertisingEnable;
+    pub const HEADER_LEN: usize = 2;
+    pub const MAX_SETS: usize = 0x3F;
+}
+impl Command for SetExtendedAdvertisingEnable {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.sets.len() * AdvertisingSetEnable::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        if self.sets.len() > Self::MAX_SETS || (self.is_enabled && self.sets.is_empty()) {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = self.is_enabled.into();
+        buf[1] = u8::try_from(self.sets.len()).map_err(|_| PackError::InvalidFields)?;
+        for (set, out) in self
+            .sets
+            .iter()
+            .zip(buf[Self::HEADER_LEN..].chunks_exact_mut(AdvertisingSetEnable::BYTE_LEN))
+        {
+            out[0] = set.handle.into();
+            out[1..3].copy_from_slice(&set.duration.to_le_bytes());
+            out[3] = set.max_extended_advertising_events;
+        }
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        let num_sets = usize::from(buf[1]);
+        if num_sets > Self::MAX_SETS {
+            return Err(PackError::bad_index(1));
+        }
+        PackError::expect_length(
+            Self::HEADER_LEN + num_sets * AdvertisingSetEnable::BYTE_LEN,
+            buf,
+        )?;
+        let mut sets = Vec::with_capacity(num_sets);
+        for (i, set) in buf[Self::HEADER_LEN..]
+            .chunks_exact(AdvertisingSetEnable::BYTE_LEN)
+            .enumerate()
+        {
+            let index = Self::HEADER_LEN + i * AdvertisingSetEnable::BYTE_LEN;
+            sets.push(AdvertisingSetEnable {
+                handle: AdvertisingSetHandle::try_from(set[0])
+                    .map_err(|_| PackError::bad_index(index))?,
+                duration: u16::from_le_bytes([set[1], set[2]]),
+                max_extended_advertising_events: set[3],
+            });
+        }
+        let is_enabled = match buf[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(PackError::bad_index(0)),
+        };
+        // Only disabling can apply to every set.
+        if is_enabled && sets.is_empty() {
+            return Err(PackError::bad_index(1));
+        }
+        Ok(SetExtendedAdvertisingEnable { is_enabled, sets })
+    }
+}
+/// `HCI_LE_Read_Maximum_Advertising_Data_Length`. How much advertising (or scan response) data
+/// one set can hold, up to [`ExtendedAdvertisingData::MAX_DATA_LEN`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadMaximumAdvertisingDataLength {}
+impl ReadMaximumAdvertisingDataLength {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReadMaximumAdvertisingDataLength;
+}
+impl Command for ReadMaximumAdvertisingDataLength {
+    type Return = CommandComplete<MaximumAdvertisingDataLengthReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadMaximumAdvertisingDataLength {})
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct MaximumAdvertisingDataLengthReturn {
+    pub status: ErrorCode,
+    pub max_data_len: u16,
+}
+impl MaximumAdvertisingDataLengthReturn {
+    pub const BYTE_LEN: usize = 3;
+}
+impl ReturnParameters for MaximumAdvertisingDataLengthReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&self.max_data_len.to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(MaximumAdvertisingDataLengthReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            max_data_len: u16::from_le_bytes([buf[1], buf[2]]),
+        })
+    }
+}
+/// `HCI_LE_Read_Number_of_Supported_Advertising_Sets`. Handles from `0` to `num_sets - 1` can be
+/// used at the same time.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadNumberOfSupportedAdvertisingSets {}
+impl ReadNumberOfSupportedAdvertisingSets {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReadNumberOfSupportedAdvertisingSets;
+}
+impl Command for ReadNumberOfSupportedAdvertisingSets {
+    type Return = CommandComplete<NumberOfSupportedAdvertisingSetsReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadNumberOfSupportedAdvertisingSets {})
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct NumberOfSupportedAdvertisingSetsReturn {
+    pub status: ErrorCode,
+    pub num_sets: u8,
+}
+impl NumberOfSupportedAdvertisingSetsReturn {
+    pub const BYTE_LEN: usize = 2;
+}
+impl ReturnParameters for NumberOfSupportedAdvertisingSetsReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1] = self.num_sets;
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(NumberOfSupportedAdvertisingSetsReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            num_sets: buf[1],
+        })
+    }
+}
+/// `HCI_LE_Remove_Advertising_Set`. The controller rejects removing a set that is still enabled
+/// with `CommandDisallowed`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct RemoveAdvertisingSet {
+    pub handle: AdvertisingSetHandle,
+}
+impl RemoveAdvertisingSet {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::RemoveAdvertisingSet;
+}
+impl Command for RemoveAdvertisingSet {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        AdvertisingSetHandle::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(AdvertisingSetHandle::BYTE_LEN, buf)?;
+        buf[0] = self.handle.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(AdvertisingSetHandle::BYTE_LEN, buf)?;
+        Ok(RemoveAdvertisingSet {
+            handle: AdvertisingSetHandle::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+        })
+    }
+}
+/// `HCI_LE_Clear_Advertising_Sets`. Removes every set. Disallowed while any set is enabled.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ClearAdvertisingSets {}
+impl ClearAdvertisingSets {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ClearAdvertisingSets;
+}
+impl Command for ClearAdvertisingSets {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ClearAdvertisingSets {})
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_extended_advertising_commands() {
+        let mut parameters = ExtendedAdvertisingParameters::new(AdvertisingSetHandle::new(1));
+        parameters.properties = AdvertisingEventProperties::new(
+            AdvertisingEventProperties::CONNECTABLE | AdvertisingEventProperties::INCLUDE_TX_POWER,
+        );
+        parameters.interval_max = ExtendedAdvertisingInterval::new_checked(0x01_0000).unwrap();
+        parameters.secondary_phy = Phy::LE2M;
+        parameters.tx_power = Some(TxPowerLevel::new(-4));
+        let command = SetExtendedAdvertisingParameters(parameters);
+        let packet = command.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(&packet.parameters[..9], &[1, 0x41, 0, 0, 8, 0, 0, 0, 1]);
+        assert_eq!(
+            SetExtendedAdvertisingParameters::unpack_command_packet(&packet),
+            Ok(command)
+        );
+        parameters.primary_phy = Phy::LE2M;
+        assert_eq!(
+            SetExtendedAdvertisingParameters(parameters)
+                .pack_command_packet::<Box<[u8]>>()
+                .err(),
+            Some(PackError::InvalidFields)
+        );
+
+        let data = [0xAB_u8; 600];
+        let fragments: Vec<_> =
+            ExtendedAdvertisingData::fragments(AdvertisingSetHandle::new(1), &data).collect();
+        let operations: Vec<_> = fragments.iter().map(|f| f.operation).collect();
+        assert_eq!(
+            operations,
+            [
+                DataOperation::FirstFragment,
+                DataOperation::IntermediateFragment,
+                DataOperation::LastFragment
+            ]
+        );
+        assert_eq!(fragments[2].data.len(), 600 - 2 * 251);
+        let command = SetExtendedScanResponseData(fragments[0].clone());
+        let packet = command.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(packet.parameters.len(), 4 + 251);
+        assert_eq!(
+            SetExtendedScanResponseData::unpack_command_packet(&packet),
+            Ok(command)
+        );
+        let empty: Vec<_> =
+            ExtendedAdvertisingData::fragments(AdvertisingSetHandle::new(1), &[]).collect();
+        assert_eq!(empty.len(), 1);
+        assert_eq!(empty[0].operation, DataOperation::Complete);
+
+        let enable = SetExtendedAdvertisingEnable {
+            is_enabled: true,
+            sets: alloc::vec![AdvertisingSetEnable {
+                handle: AdvertisingSetHandle::new(1),
+                duration: 500,
+                max_extended_advertising_events: 0,
+            }],
+        };
+        let packet = enable.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(&*packet.parameters, &[1, 1, 1, 0xF4, 0x01, 0]);
+        assert_eq!(
+            SetExtendedAdvertisingEnable::unpack_command_packet(&packet),
+            Ok(enable)
+        );
+
+        let remove = RemoveAdvertisingSet {
+            handle: AdvertisingSetHandle::new(3),
+        };
+        let packet = remove.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(&*packet.parameters, &[3]);
+        assert_eq!(
+            RemoveAdvertisingSet::unpack_command_packet(&packet),
+            Ok(remove)
+        );
+        assert!(RemoveAdvertisingSet::unpack_from(&[0xF0]).is_err());
+        let r = MaximumAdvertisingDataLengthReturn::unpack_from(&[0x00, 0x72, 0x06]).unwrap();
+        assert_eq!(
+            usize::from(r.max_data_len),
+            ExtendedAdvertisingData::MAX_DATA_LEN
+        );
+        let r = NumberOfSupportedAdvertisingSetsReturn::unpack_from(&[0x00, 0x04]).unwrap();
+        assert_eq!(r.num_sets, 4);
+    }
+}
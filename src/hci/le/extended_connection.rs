@@ -0,0 +1,393 @@
+//! LE [`ExtendedCreateConnection`] (Bluetooth 5.0). Connects over LE 1M, LE 2M and/or LE Coded
+//! with separate initiating parameters for each PHY and completes with an
+//! [`EnhancedConnectionComplete`] event.
+use crate::hci::command::{Command, LongCommand};
+use crate::hci::event::CommandStatus;
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::connection::{or_min, CreateConnectionCancel};
+use crate::hci::le::{LEControllerOpcode, MetaEvent, MetaEventCode};
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::advertiser::{DirectedPeer, DirectedPeerError, PeerAddressType};
+use crate::le::connection::{
+    CELength, ConnectionHandle, ConnectionInterval, ConnectionLatency, InitiatorFilterPolicy,
+    MasterClockAccuracy, Role, SupervisionTimeout,
+};
+use crate::le::phy::Phy;
+use crate::le::report::AddressType;
+use crate::le::scan::{OwnAddressType, ScanInterval, ScanWindow};
+use crate::{BTAddress, DeviceAddress, PackError, BT_ADDRESS_LEN};
+use core::convert::TryFrom;
+
+/// Scan and connection parameters used when connecting on one PHY. The scan interval and window
+/// are ignored for LE 2M (it isn't scanned, only used for the connection).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct InitiatingPhyParameters {
+    pub scan_interval: ScanInterval,
+    pub scan_window: ScanWindow,
+    pub connection_interval_min: ConnectionInterval,
+    pub connection_interval_max: ConnectionInterval,
+    pub connection_latency: ConnectionLatency,
+    pub supervision_timeout: SupervisionTimeout,
+    pub min_ce_len: CELength,
+    pub max_ce_len: CELength,
+}
+impl InitiatingPhyParameters {
+    pub const BYTE_LEN: usize = 16;
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        if u16::from(self.scan_window) > u16::from(self.scan_interval)
+            || self.connection_interval_max < self.connection_interval_min
+        {
+            return Err(PackError::InvalidFields);
+        }
+        let fields = [
+            u16::from(self.scan_interval),
+            u16::from(self.scan_window),
+            u16::from(self.connection_interval_min),
+            u16::from(self.connection_interval_max),
+            u16::from(self.connection_latency),
+            u16::from(self.supervision_timeout),
+            self.min_ce_len.0,
+            self.max_ce_len.0,
+        ];
+        for (field, b) in fields.iter().zip(buf.chunks_exact_mut(2)) {
+            b.copy_from_slice(&field.to_le_bytes());
+        }
+        Ok(())
+    }
+    /// `index` is only for errors.
+    fn unpack_from(buf: &[u8], index: usize) -> Result<InitiatingPhyParameters, PackError> {
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        Ok(InitiatingPhyParameters {
+            scan_interval: ScanInterval::new_checked(u16_at(0))
+                .ok_or(PackError::bad_index(index))?,
+            scan_window: ScanWindow::new_checked(u16_at(2))
+                .ok_or(PackError::bad_index(index + 2))?,
+            connection_interval_min: ConnectionInterval::new_checked(u16_at(4))
+                .ok_or(PackError::bad_index(index + 4))?,
+            connection_interval_max: ConnectionInterval::new_checked(u16_at(6))
+                .ok_or(PackError::bad_index(index + 6))?,
+            connection_latency: ConnectionLatency::new_checked(u16_at(8))
+                .ok_or(PackError::bad_index(index + 8))?,
+            supervision_timeout: SupervisionTimeout::new_checked(u16_at(10))
+                .ok_or(PackError::bad_index(index + 10))?,
+            min_ce_len: CELength(u16_at(12)),
+            max_ce_len: CELength(u16_at(14)),
+        })
+    }
+}
+/// `HCI_LE_Extended_Create_Connection`. PHYs set to `None` aren't used. LE 1M or LE Coded has to
+/// be set (LE 2M can't be scanned for advertisements).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ExtendedCreateConnection {
+    pub initiator_filter_policy: InitiatorFilterPolicy,
+    pub own_address_type: OwnAddressType,
+    pub peer_address_type: PeerAddressType,
+    pub peer_address: BTAddress,
+    pub le_1m: Option<InitiatingPhyParameters>,
+    pub le_2m: Option<InitiatingPhyParameters>,
+    pub le_coded: Option<InitiatingPhyParameters>,
+}
+impl ExtendedCreateConnection {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ExtendedCreateConnection;
+    pub const HEADER_LEN: usize = 10;
+    /// Connect to `peer` on LE 1M only.
+    pub fn new(peer: DeviceAddress, parameters: InitiatingPhyParameters) -> Self {
+        ExtendedCreateConnection {
+            initiator_filter_policy: InitiatorFilterPolicy::PeerAddress,
+            own_address_type: OwnAddressType::Public,
+            peer_address_type: peer.kind.into(),
+            peer_address: peer.addr,
+            le_1m: Some(parameters),
+            le_2m: None,
+            le_coded: None,
+        }
+    }
+    pub fn phy(&self, phy: Phy) -> Option<InitiatingPhyParameters> {
+        match phy {
+            Phy::LE1M => self.le_1m,
+            Phy::LE2M => self.le_2m,
+            Phy::LECoded => self.le_coded,
+        }
+    }
+    pub fn set_phy(&mut self, phy: Phy, parameters: Option<InitiatingPhyParameters>) {
+        match phy {
+            Phy::LE1M => self.le_1m = parameters,
+            Phy::LE2M => self.le_2m = parameters,
+            Phy::LECoded => self.le_coded = parameters,
+        }
+    }
+    /// `Initiating_PHYs` bit field.
+    pub fn initiating_phys(&self) -> u8 {
+        [Phy::LE1M, Phy::LE2M, Phy::LECoded]
+            .iter()
+            .filter(|phy| self.phy(**phy).is_some())
+            .fold(0, |phys, phy| phys | Self::phy_bit(*phy))
+    }
+    fn phy_bit(phy: Phy) -> u8 {
+        1 << (u8::from(phy) - 1)
+    }
+    pub fn peer(&self) -> DirectedPeer {
+        DirectedPeer::new(self.peer_address_type, self.peer_address)
+    }
+    /// Check the peer against the own address type. The peer is ignored when connecting to the
+    /// white list.
+    pub fn check_peer(&self) -> Result<(), DirectedPeerError> {
+        match self.initiator_filter_policy {
+            InitiatorFilterPolicy::PeerAddress => self.peer().check(self.own_address_type.into()),
+            InitiatorFilterPolicy::WhiteList => Ok(()),
+        }
+    }
+}
+impl Command for ExtendedCreateConnection {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::HEADER_LEN
+            + InitiatingPhyParameters::BYTE_LEN * self.initiating_phys().count_ones() as usize
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        if self.le_1m.is_none() && self.le_coded.is_none() {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = self.initiator_filter_policy.into();
+        buf[1] = self.own_address_type.into();
+        buf[2] = self.peer_address_type.into();
+        self.peer_address
+            .pack_into(&mut buf[3..3 + BT_ADDRESS_LEN])?;
+        buf[9] = self.initiating_phys();
+        // PHY parameters are in bit order.
+        let phys = self.le_1m.iter().chain(&self.le_2m).chain(&self.le_coded);
+        for (phy, b) in
+            phys.zip(buf[Self::HEADER_LEN..].chunks_exact_mut(InitiatingPhyParameters::BYTE_LEN))
+        {
+            phy.pack_into(b)?;
+        }
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        let phys = buf[9];
+        if phys & !0b111 != 0 || phys & 0b101 == 0 {
+            return Err(PackError::bad_index(9));
+        }
+        PackError::expect_length(
+            Self::HEADER_LEN + InitiatingPhyParameters::BYTE_LEN * phys.count_ones() as usize,
+            buf,
+        )?;
+        let mut out = ExtendedCreateConnection {
+            initiator_filter_policy: InitiatorFilterPolicy::try_from(buf[0])
+                .map_err(|_| PackError::bad_index(0))?,
+            own_address_type: OwnAddressType::try_from(buf[1])
+                .map_err(|_| PackError::bad_index(1))?,
+            peer_address_type: PeerAddressType::try_from(buf[2])
+                .map_err(|_| PackError::bad_index(2))?,
+            peer_address: BTAddress::new(&buf[3..3 + BT_ADDRESS_LEN]),
+            le_1m: None,
+            le_2m: None,
+            le_coded: None,
+        };
+        let mut index = Self::HEADER_LEN;
+        for phy in [Phy::LE1M, Phy::LE2M, Phy::LECoded].iter() {
+            if phys & Self::phy_bit(*phy) != 0 {
+                let parameters = InitiatingPhyParameters::unpack_from(
+                    &buf[index..index + InitiatingPhyParameters::BYTE_LEN],
+                    index,
+                )?;
+                out.set_phy(*phy, Some(parameters));
+                index += InitiatingPhyParameters::BYTE_LEN;
+            }
+        }
+        Ok(out)
+    }
+}
+impl LongCommand for ExtendedCreateConnection {
+    type Completion = EnhancedConnectionComplete;
+    type Cancel = CreateConnectionCancel;
+
+    fn cancel_command(&self) -> CreateConnectionCancel {
+        CreateConnectionCancel
+    }
+
+    /// Connecting to the white list can complete with any address.
+    fn is_completion(&self, completion: &EnhancedConnectionComplete) -> bool {
+        match self.initiator_filter_policy {
+            InitiatorFilterPolicy::PeerAddress => completion.peer_address == self.peer_address,
+            InitiatorFilterPolicy::WhiteList => true,
+        }
+    }
+}
+/// LE Enhanced Connection Complete event. Sent instead of
+/// [`crate::hci::le::connection::ConnectionCompleteEvent`] when it's unmasked, and always after
+/// an [`ExtendedCreateConnection`]. Also has the resolvable private addresses used when the
+/// controller does address resolution.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct EnhancedConnectionComplete {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+    pub role: Role,
+    pub peer_address_type: AddressType,
+    /// Identity address if the controller resolved the peer's address.
+    pub peer_address: BTAddress,
+    /// Our resolvable private address or zeroed if not used.
+    pub local_resolvable_private_address: BTAddress,
+    /// The peer's resolvable private address or zeroed if not used.
+    pub peer_resolvable_private_address: BTAddress,
+    pub connection_interval: ConnectionInterval,
+    pub connection_latency: ConnectionLatency,
+    pub supervision_timeout: SupervisionTimeout,
+    pub master_clock_accuracy: MasterClockAccuracy,
+}
+impl EnhancedConnectionComplete {
+    pub const CODE: MetaEventCode = MetaEventCode::EnhancedConnectionComplete;
+    pub const BYTE_LEN: usize = 30;
+    pub fn peer(&self) -> DeviceAddress {
+        self.peer_address_type.device_address(self.peer_address)
+    }
+    pub fn local_resolvable_private_address(&self) -> Option<BTAddress> {
+        Some(self.local_resolvable_private_address).filter(|a| *a != BTAddress::ZEROED)
+    }
+    pub fn peer_resolvable_private_address(&self) -> Option<BTAddress> {
+        Some(self.peer_resolvable_private_address).filter(|a| *a != BTAddress::ZEROED)
+    }
+}
+impl MetaEvent for EnhancedConnectionComplete {
+    const META_CODE: MetaEventCode = Self::CODE;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    /// Like [`crate::hci::le::connection::ConnectionCompleteEvent`], invalid fields of a failed
+    /// connection are replaced by the minimum.
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let status = ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?;
+        let is_ok = status == ErrorCode::Ok;
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        Ok(EnhancedConnectionComplete {
+            status,
+            connection_handle: or_min(
+                ConnectionHandle::new_checked(u16_at(1)),
+                ConnectionHandle::MIN,
+                is_ok,
+                1,
+            )?,
+            role: or_min(Role::try_from(buf[3]).ok(), Role::Master, is_ok, 3)?,
+            peer_address_type: or_min(
+                AddressType::try_from(buf[4]).ok(),
+                AddressType::PublicDevice,
+                is_ok,
+                4,
+            )?,
+            peer_address: BTAddress::new(&buf[5..11]),
+            local_resolvable_private_address: BTAddress::new(&buf[11..17]),
+            peer_resolvable_private_address: BTAddress::new(&buf[17..23]),
+            connection_interval: or_min(
+                ConnectionInterval::new_checked(u16_at(23)),
+                ConnectionInterval::MIN,
+                is_ok,
+                23,
+            )?,
+            connection_latency: or_min(
+                ConnectionLatency::new_checked(u16_at(25)),
+                ConnectionLatency::MIN,
+                is_ok,
+                25,
+            )?,
+            supervision_timeout: or_min(
+                SupervisionTimeout::new_checked(u16_at(27)),
+                SupervisionTimeout::MIN,
+                is_ok,
+                27,
+            )?,
+            master_clock_accuracy: or_min(
+                MasterClockAccuracy::try_from(buf[29]).ok(),
+                MasterClockAccuracy::PPM500,
+                is_ok,
+                29,
+            )?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[3] = self.role.into();
+        buf[4] = self.peer_address_type.into();
+        self.peer_address.pack_into(&mut buf[5..11])?;
+        self.local_resolvable_private_address
+            .pack_into(&mut buf[11..17])?;
+        self.peer_resolvable_private_address
+            .pack_into(&mut buf[17..23])?;
+        buf[23..25].copy_from_slice(&u16::from(self.connection_interval).to_le_bytes());
+        buf[25..27].copy_from_slice(&u16::from(self.connection_latency).to_le_bytes());
+        buf[27..29].copy_from_slice(&u16::from(self.supervision_timeout).to_le_bytes());
+        buf[29] = self.master_clock_accuracy.into();
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_extended_create_connection() {
+        let parameters = InitiatingPhyParameters {
+            scan_interval: ScanInterval::DEFAULT,
+            scan_window: ScanWindow::DEFAULT,
+            connection_interval_min: ConnectionInterval::MIN,
+            connection_interval_max: ConnectionInterval::MIN,
+            connection_latency: ConnectionLatency::MIN,
+            supervision_timeout: SupervisionTimeout::MIN,
+            min_ce_len: CELength::MIN,
+            max_ce_len: CELength::MIN,
+        };
+        let peer = DeviceAddress::public(BTAddress([1, 2, 3, 4, 5, 6]));
+        let mut create = ExtendedCreateConnection::new(peer, parameters);
+        create.set_phy(Phy::LECoded, Some(parameters));
+        assert_eq!(create.initiating_phys(), 0b101);
+        let packet = create.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(packet.parameters.len(), 10 + 2 * 16);
+        assert_eq!(
+            ExtendedCreateConnection::unpack_from(&packet.parameters),
+            Ok(create)
+        );
+        create.le_1m = None;
+        create.le_coded = None;
+        create.le_2m = Some(parameters);
+        assert!(create.pack_command_packet::<Box<[u8]>>().is_err());
+
+        let mut buf = [0_u8; EnhancedConnectionComplete::BYTE_LEN];
+        buf[0] = ErrorCode::NoConnection.into();
+        buf[4] = AddressType::RandomIdentity.into();
+        buf[5..11].copy_from_slice(&[1, 2, 3, 4, 5, 0xC6]);
+        buf[23] = 6;
+        buf[27] = 10;
+        let event = EnhancedConnectionComplete::meta_unpack_from(&buf).unwrap();
+        assert_eq!(event.peer(), DeviceAddress::random(event.peer_address));
+        assert_eq!(event.peer_resolvable_private_address(), None);
+        let mut packed = [0_u8; EnhancedConnectionComplete::BYTE_LEN];
+        event.meta_pack_into(&mut packed).unwrap();
+        assert_eq!(packed, buf);
+    }
+}
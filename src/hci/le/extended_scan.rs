@@ -0,0 +1,449 @@
+//! LE Extended Scanning commands (Bluetooth 5.0). Unlike [`crate::hci::le::scan`] each primary
+//! PHY (LE 1M and LE Coded) gets its own scan interval and window so both can be scanned at once,
+//! and scanning can stop by itself after a [`ScanDuration`] (or repeat every [`ScanPeriod`]).
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::LEControllerOpcode;
+use crate::hci::Opcode;
+use crate::le::phy::Phy;
+use crate::le::scan::{
+    OwnAddressType, ScanInterval, ScanParameters, ScanType, ScanWindow, ScanningFilterPolicy,
+};
+use crate::{ConversionError, PackError};
+use core::convert::TryFrom;
+use core::time::Duration;
+
+/// Scan type, interval and window of one primary PHY.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PhyScanParameters {
+    pub scan_type: ScanType,
+    pub scan_interval: ScanInterval,
+    pub scan_window: ScanWindow,
+}
+impl PhyScanParameters {
+    pub const BYTE_LEN: usize = 5;
+    pub const DEFAULT: PhyScanParameters = PhyScanParameters {
+        scan_type: ScanType::Passive,
+        scan_interval: ScanInterval::DEFAULT,
+        scan_window: ScanWindow::DEFAULT,
+    };
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        let window = u16::from(self.scan_window);
+        let interval = u16::from(self.scan_interval);
+        if window > interval {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = self.scan_type.into();
+        buf[1..3].copy_from_slice(&interval.to_le_bytes());
+        buf[3..5].copy_from_slice(&window.to_le_bytes());
+        Ok(())
+    }
+    /// `index` is only for errors.
+    fn unpack_from(buf: &[u8], index: usize) -> Result<PhyScanParameters, PackError> {
+        let scan_interval = ScanInterval::new_checked(u16::from_le_bytes([buf[1], buf[2]]))
+            .ok_or(PackError::bad_index(index + 1))?;
+        let scan_window = ScanWindow::new_checked(u16::from_le_bytes([buf[3], buf[4]]))
+            .ok_or(PackError::bad_index(index + 3))?;
+        if u16::from(scan_window) > u16::from(scan_interval) {
+            return Err(PackError::bad_index(index + 3));
+        }
+        Ok(PhyScanParameters {
+            scan_type: ScanType::try_from(buf[0]).map_err(|_| PackError::bad_index(index))?,
+            scan_interval,
+            scan_window,
+        })
+    }
+}
+impl Default for PhyScanParameters {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+/// Parameters of [`SetExtendedScanParameters`]. PHYs set to `None` aren't scanned. At least one
+/// PHY has to be scanned.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ExtendedScanParameters {
+    pub own_address_type: OwnAddressType,
+    pub scanning_filter_policy: ScanningFilterPolicy,
+    pub le_1m: Option<PhyScanParameters>,
+    pub le_coded: Option<PhyScanParameters>,
+}
+impl ExtendedScanParameters {
+    pub const HEADER_LEN: usize = 3;
+    pub const DEFAULT: ExtendedScanParameters = ExtendedScanParameters {
+        own_address_type: OwnAddressType::Public,
+        scanning_filter_policy: ScanningFilterPolicy::All,
+        le_1m: Some(PhyScanParameters::DEFAULT),
+        le_coded: None,
+    };
+    /// `Scanning_PHYs` bit of LE 1M.
+    pub const LE_1M_BIT: u8 = 1 << 0;
+    /// `Scanning_PHYs` bit of LE Coded.
+    pub const LE_CODED_BIT: u8 = 1 << 2;
+    /// Scan LE 1M and LE Coded with the same `phy_parameters`. The controller splits the scan
+    /// window between them.
+    pub fn both_phys(
+        phy_parameters: PhyScanParameters,
+        own_address_type: OwnAddressType,
+        scanning_filter_policy: ScanningFilterPolicy,
+    ) -> ExtendedScanParameters {
+        ExtendedScanParameters {
+            own_address_type,
+            scanning_filter_policy,
+            le_1m: Some(phy_parameters),
+            le_coded: Some(phy_parameters),
+        }
+    }
+    pub fn phy(&self, phy: Phy) -> Option<PhyScanParameters> {
+        match phy {
+            Phy::LE1M => self.le_1m,
+            Phy::LECoded => self.le_coded,
+            // LE 2M isn't a primary advertising PHY.
+            Phy::LE2M => None,
+        }
+    }
+    pub fn scanning_phys(&self) -> u8 {
+        let mut phys = 0;
+        if self.le_1m.is_some() {
+            phys |= Self::LE_1M_BIT;
+        }
+        if self.le_coded.is_some() {
+            phys |= Self::LE_CODED_BIT;
+        }
+        phys
+    }
+    pub fn byte_len(&self) -> usize {
+        Self::HEADER_LEN
+            + PhyScanParameters::BYTE_LEN
+                * (usize::from(self.le_1m.is_some()) + usize::from(self.le_coded.is_some()))
+    }
+}
+impl Default for ExtendedScanParameters {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+impl From<ScanParameters> for ExtendedScanParameters {
+    /// Legacy scan parameters on LE 1M only.
+    fn from(p: ScanParameters) -> Self {
+        ExtendedScanParameters {
+            own_address_type: p.own_address_type,
+            scanning_filter_policy: p.scanning_filter_policy,
+            le_1m: Some(PhyScanParameters {
+                scan_type: p.scan_type,
+                scan_interval: p.scan_interval,
+                scan_window: p.scan_window,
+            }),
+            le_coded: None,
+        }
+    }
+}
+/// `HCI_LE_Set_Extended_Scan_Parameters`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct SetExtendedScanParameters(pub ExtendedScanParameters);
+impl SetExtendedScanParameters {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetExtendedScanParameters;
+}
+impl Command for SetExtendedScanParameters {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        self.0.byte_len()
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.0.byte_len(), buf)?;
+        let p = &self.0;
+        if p.scanning_phys() == 0 {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = p.own_address_type.into();
+        buf[1] = p.scanning_filter_policy.into();
+        buf[2] = p.scanning_phys();
+        let mut phy_bufs =
+            buf[ExtendedScanParameters::HEADER_LEN..].chunks_exact_mut(PhyScanParameters::BYTE_LEN);
+        // PHY parameters are in bit order.
+        for phy in p.le_1m.iter().chain(p.le_coded.iter()) {
+            phy.pack_into(phy_bufs.next().ok_or(PackError::InvalidFields)?)?;
+        }
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < ExtendedScanParameters::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: ExtendedScanParameters::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        let phys = buf[2];
+        if phys == 0
+            || phys & !(ExtendedScanParameters::LE_1M_BIT | ExtendedScanParameters::LE_CODED_BIT)
+                != 0
+        {
+            return Err(PackError::bad_index(2));
+        }
+        let phy_count = phys.count_ones() as usize;
+        PackError::expect_length(
+            ExtendedScanParameters::HEADER_LEN + phy_count * PhyScanParameters::BYTE_LEN,
+            buf,
+        )?;
+        let mut index = ExtendedScanParameters::HEADER_LEN;
+        let mut next_phy = |bit: u8| -> Result<Option<PhyScanParameters>, PackError> {
+            if phys & bit == 0 {
+                return Ok(None);
+            }
+            let phy = PhyScanParameters::unpack_from(
+                &buf[index..index + PhyScanParameters::BYTE_LEN],
+                index,
+            )?;
+            index += PhyScanParameters::BYTE_LEN;
+            Ok(Some(phy))
+        };
+        let le_1m = next_phy(ExtendedScanParameters::LE_1M_BIT)?;
+        let le_coded = next_phy(ExtendedScanParameters::LE_CODED_BIT)?;
+        Ok(SetExtendedScanParameters(ExtendedScanParameters {
+            own_address_type: OwnAddressType::try_from(buf[0])
+                .map_err(|_| PackError::bad_index(0))?,
+            scanning_filter_policy: ScanningFilterPolicy::try_from(buf[1])
+                .map_err(|_| PackError::bad_index(1))?,
+            le_1m,
+            le_coded,
+        }))
+    }
+}
+/// Duplicate advertisement filtering of [`SetExtendedScanEnable`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum FilterDuplicates {
+    Disabled = 0x00,
+    Enabled = 0x01,
+    /// Filter duplicates until the next [`ScanPeriod`] starts.
+    ResetEachPeriod = 0x02,
+}
+impl Default for FilterDuplicates {
+    fn default() -> Self {
+        FilterDuplicates::Disabled
+    }
+}
+impl From<bool> for FilterDuplicates {
+    fn from(filter_duplicates: bool) -> Self {
+        if filter_duplicates {
+            FilterDuplicates::Enabled
+        } else {
+            FilterDuplicates::Disabled
+        }
+    }
+}
+impl From<FilterDuplicates> for u8 {
+    fn from(f: FilterDuplicates) -> Self {
+        f as u8
+    }
+}
+impl TryFrom<u8> for FilterDuplicates {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(FilterDuplicates::Disabled),
+            0x01 => Ok(FilterDuplicates::Enabled),
+            0x02 => Ok(FilterDuplicates::ResetEachPeriod),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// How long to scan for in units of 10 ms. `0` scans until disabled.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ScanDuration(pub u16);
+impl ScanDuration {
+    pub const CONTINUOUS: ScanDuration = ScanDuration(0);
+    pub const UNIT: Duration = Duration::from_millis(10);
+    /// Rounds down to 10 ms. Returns `None` if `duration` is over 655.35 s.
+    pub fn from_duration(duration: Duration) -> Option<ScanDuration> {
+        u16::try_from(duration.as_millis() / Self::UNIT.as_millis())
+            .ok()
+            .map(ScanDuration)
+    }
+    pub fn is_continuous(self) -> bool {
+        self.0 == 0
+    }
+    pub fn as_duration(self) -> Duration {
+        Self::UNIT * u32::from(self.0)
+    }
+}
+/// Time from the start of one scan to the start of the next in units of 1.28 s. `0` scans only
+/// once.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ScanPeriod(pub u16);
+impl ScanPeriod {
+    pub const ONCE: ScanPeriod = ScanPeriod(0);
+    pub const UNIT: Duration = Duration::from_millis(1280);
+    /// Rounds down to 1.28 s. Returns `None` if `period` is over 83884.8 s.
+    pub fn from_duration(period: Duration) -> Option<ScanPeriod> {
+        u16::try_from(period.as_millis() / Self::UNIT.as_millis())
+            .ok()
+            .map(ScanPeriod)
+    }
+    pub fn is_once(self) -> bool {
+        self.0 == 0
+    }
+    pub fn as_duration(self) -> Duration {
+        Self::UNIT * u32::from(self.0)
+    }
+}
+/// `HCI_LE_Set_Extended_Scan_Enable`. A scan with a [`ScanDuration`] ends with an LE Scan Timeout
+/// event. A [`ScanPeriod`] needs a duration shorter than it.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct SetExtendedScanEnable {
+    pub is_enabled: bool,
+    pub filter_duplicates: FilterDuplicates,
+    pub duration: ScanDuration,
+    pub period: ScanPeriod,
+}
+impl SetExtendedScanEnable {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetExtendedScanEnable;
+    pub const BYTE_LEN: usize = 6;
+    pub const DISABLE: SetExtendedScanEnable = SetExtendedScanEnable {
+        is_enabled: false,
+        filter_duplicates: FilterDuplicates::Disabled,
+        duration: ScanDuration::CONTINUOUS,
+        period: ScanPeriod::ONCE,
+    };
+    /// Scan until disabled.
+    pub fn enable(filter_duplicates: impl Into<FilterDuplicates>) -> SetExtendedScanEnable {
+        SetExtendedScanEnable {
+            is_enabled: true,
+            filter_duplicates: filter_duplicates.into(),
+            ..Self::DISABLE
+        }
+    }
+    /// Stop scanning after `duration`.
+    pub fn for_duration(mut self, duration: ScanDuration) -> SetExtendedScanEnable {
+        self.duration = duration;
+        self
+    }
+    /// Scan for the duration every `period`.
+    pub fn every(mut self, period: ScanPeriod) -> SetExtendedScanEnable {
+        self.period = period;
+        self
+    }
+    pub fn is_valid(&self) -> bool {
+        self.period.is_once()
+            || (!self.duration.is_continuous()
+                && self.duration.as_duration() < self.period.as_duration())
+    }
+}
+impl Command for SetExtendedScanEnable {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        if !self.is_valid() {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = self.is_enabled.into();
+        buf[1] = self.filter_duplicates.into();
+        buf[2..4].copy_from_slice(&self.duration.0.to_le_bytes());
+        buf[4..6].copy_from_slice(&self.period.0.to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let out = SetExtendedScanEnable {
+            is_enabled: match buf[0] {
+                0 => false,
+                1 => true,
+                _ => return Err(PackError::bad_index(0)),
+            },
+            filter_duplicates: FilterDuplicates::try_from(buf[1])
+                .map_err(|_| PackError::bad_index(1))?,
+            duration: ScanDuration(u16::from_le_bytes([buf[2], buf[3]])),
+            period: ScanPeriod(u16::from_le_bytes([buf[4], buf[5]])),
+        };
+        if out.is_valid() {
+            Ok(out)
+        } else {
+            Err(PackError::InvalidFields)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_extended_scan_parameters() {
+        let parameters = ExtendedScanParameters {
+            le_coded: Some(PhyScanParameters {
+                scan_type: ScanType::Active,
+                scan_interval: ScanInterval::new(0x0030),
+                scan_window: ScanWindow::new(0x0020),
+            }),
+            ..ExtendedScanParameters::DEFAULT
+        };
+        let packet = SetExtendedScanParameters(parameters)
+            .pack_command_packet::<Box<[u8]>>()
+            .unwrap();
+        assert_eq!(
+            packet.parameters.as_ref(),
+            &[0x00, 0x00, 0x05, 0x00, 0x10, 0x00, 0x10, 0x00, 0x01, 0x30, 0x00, 0x20, 0x00]
+        );
+        assert_eq!(
+            SetExtendedScanParameters::unpack_from(&packet.parameters),
+            Ok(SetExtendedScanParameters(parameters))
+        );
+        let no_phys = ExtendedScanParameters {
+            le_1m: None,
+            ..parameters
+        };
+        assert_eq!(
+            SetExtendedScanParameters::unpack_from(&[
+                0x00, 0x00, 0x04, 0x01, 0x30, 0x00, 0x20, 0x00
+            ]),
+            Ok(SetExtendedScanParameters(no_phys))
+        );
+        assert_eq!(
+            SetExtendedScanParameters::unpack_from(&[0x00, 0x00, 0x02]),
+            Err(PackError::bad_index(2))
+        );
+    }
+    #[test]
+    fn test_extended_scan_enable() {
+        let enable = SetExtendedScanEnable::enable(FilterDuplicates::ResetEachPeriod)
+            .for_duration(ScanDuration::from_duration(Duration::from_millis(500)).unwrap())
+            .every(ScanPeriod::from_duration(Duration::from_secs(3)).unwrap());
+        assert_eq!(enable.duration, ScanDuration(50));
+        assert_eq!(enable.period, ScanPeriod(2));
+        assert!(enable.is_valid());
+        assert!(!SetExtendedScanEnable::enable(true)
+            .every(ScanPeriod(1))
+            .is_valid());
+        let packet = enable.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(packet.parameters.as_ref(), &[1, 2, 50, 0, 2, 0]);
+        assert_eq!(
+            SetExtendedScanEnable::unpack_from(&packet.parameters),
+            Ok(enable)
+        );
+    }
+}
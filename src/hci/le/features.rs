@@ -0,0 +1,266 @@
+//! LE feature bits ([`LEFeatures`]), [`ReadLocalFeatures`] for reading the controller's and
+//! [`ReadRemoteFeatures`] for reading a peer's. Check a peer supports a feature (LE 2M PHY, Data
+//! Length Extension, etc) before asking for it.
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, CommandStatus, ReturnParameters};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::{LEControllerOpcode, MetaEvent, MetaEventCode};
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::connection::ConnectionHandle;
+use crate::PackError;
+use core::convert::{TryFrom, TryInto};
+
+/// Bits of the LE feature mask (page 0).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum LEFeature {
+    LEEncryption = 0,
+    ConnectionParametersRequest = 1,
+    ExtendedRejectIndication = 2,
+    PeripheralInitiatedFeaturesExchange = 3,
+    LEPing = 4,
+    LEDataPacketLengthExtension = 5,
+    LLPrivacy = 6,
+    ExtendedScannerFilterPolicies = 7,
+    LE2MPHY = 8,
+    StableModulationIndexTransmitter = 9,
+    StableModulationIndexReceiver = 10,
+    LECodedPHY = 11,
+    LEExtendedAdvertising = 12,
+    LEPeriodicAdvertising = 13,
+    ChannelSelectionAlgorithm2 = 14,
+    LEPowerClass1 = 15,
+    MinimumNumberOfUsedChannels = 16,
+    ConnectionCTERequest = 17,
+    ConnectionCTEResponse = 18,
+    ConnectionlessCTETransmitter = 19,
+    ConnectionlessCTEReceiver = 20,
+    AntennaSwitchingDuringCTETransmission = 21,
+    AntennaSwitchingDuringCTEReception = 22,
+    ReceivingConstantToneExtensions = 23,
+    PeriodicAdvertisingSyncTransferSender = 24,
+    PeriodicAdvertisingSyncTransferRecipient = 25,
+    SleepClockAccuracyUpdates = 26,
+    RemotePublicKeyValidation = 27,
+    ConnectedIsochronousStreamCentral = 28,
+    ConnectedIsochronousStreamPeripheral = 29,
+    IsochronousBroadcaster = 30,
+    SynchronizedReceiver = 31,
+    ConnectedIsochronousStreamHostSupport = 32,
+    LEPowerControlRequest = 33,
+    LEPowerChangeIndication = 34,
+    LEPathLossMonitoring = 35,
+    PeriodicAdvertisingADISupport = 36,
+    ConnectionSubrating = 37,
+    ConnectionSubratingHostSupport = 38,
+    ChannelClassification = 39,
+    ChannelSounding = 46,
+    ChannelSoundingHostSupport = 47,
+}
+impl From<LEFeature> for u8 {
+    fn from(feature: LEFeature) -> Self {
+        feature as u8
+    }
+}
+/// LE feature mask. Bits without an [`LEFeature`] are kept and can be checked with
+/// [`LEFeatures::is_bit_set`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default)]
+pub struct LEFeatures(pub u64);
+impl LEFeatures {
+    pub const BYTE_LEN: usize = 8;
+    pub const ZEROED: LEFeatures = LEFeatures(0);
+    pub fn contains(self, feature: LEFeature) -> bool {
+        self.is_bit_set(feature.into())
+    }
+    pub fn is_bit_set(self, bit: u8) -> bool {
+        bit < 64 && self.0 & (1_u64 << bit) != 0
+    }
+    pub fn enable(&mut self, feature: LEFeature) {
+        self.0 |= 1_u64 << u8::from(feature)
+    }
+    pub fn disable(&mut self, feature: LEFeature) {
+        self.0 &= !(1_u64 << u8::from(feature))
+    }
+    /// Features supported by both `self` and `other` (the local controller and a peer).
+    pub fn common(self, other: LEFeatures) -> LEFeatures {
+        LEFeatures(self.0 & other.0)
+    }
+    pub fn pack_into(self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&self.0.to_le_bytes());
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<LEFeatures, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LEFeatures(u64::from_le_bytes(
+            buf.try_into().expect("length checked above"),
+        )))
+    }
+}
+impl From<LEFeature> for LEFeatures {
+    fn from(feature: LEFeature) -> Self {
+        let mut features = LEFeatures::ZEROED;
+        features.enable(feature);
+        features
+    }
+}
+impl From<LEFeatures> for u64 {
+    fn from(features: LEFeatures) -> Self {
+        features.0
+    }
+}
+/// `HCI_LE_Read_Local_Supported_Features` (page 0).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadLocalFeatures;
+impl ReadLocalFeatures {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReadLocalSupportedFeatures;
+}
+impl Command for ReadLocalFeatures {
+    type Return = CommandComplete<LocalFeaturesReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadLocalFeatures)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct LocalFeaturesReturn {
+    pub status: ErrorCode,
+    pub features: LEFeatures,
+}
+impl LocalFeaturesReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + LEFeatures::BYTE_LEN;
+}
+impl ReturnParameters for LocalFeaturesReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        self.features.pack_into(&mut buf[1..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LocalFeaturesReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            features: LEFeatures::unpack_from(&buf[1..])?,
+        })
+    }
+}
+/// `HCI_LE_Read_Remote_Features` (page 0). Completes with a [`ReadRemoteFeaturesComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReadRemoteFeatures {
+    pub connection_handle: ConnectionHandle,
+}
+impl ReadRemoteFeatures {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReadRemoteUsedFeatures;
+}
+impl Command for ReadRemoteFeatures {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ConnectionHandle::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        Ok(ReadRemoteFeatures {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+        })
+    }
+}
+/// LE Read Remote Features Complete event. Also sent unprompted by some controllers once a
+/// connection is established.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReadRemoteFeaturesComplete {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+    pub features: LEFeatures,
+}
+impl ReadRemoteFeaturesComplete {
+    pub const CODE: MetaEventCode = MetaEventCode::ReadRemoteFeatures;
+    pub const BYTE_LEN: usize =
+        ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN + LEFeatures::BYTE_LEN;
+}
+impl MetaEvent for ReadRemoteFeaturesComplete {
+    const META_CODE: MetaEventCode = Self::CODE;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(ReadRemoteFeaturesComplete {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            connection_handle: ConnectionHandle::unpack_from(buf, 1)?,
+            features: LEFeatures::unpack_from(&buf[3..])?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        self.features.pack_into(&mut buf[3..])
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_remote_features() {
+        let buf = [0x00, 0x40, 0x00, 0x21, 0x01, 0, 0, 0, 0, 0, 0];
+        let complete = ReadRemoteFeaturesComplete::meta_unpack_from(&buf).unwrap();
+        assert_eq!(complete.connection_handle, ConnectionHandle::new(0x0040));
+        assert!(complete.features.contains(LEFeature::LEEncryption));
+        assert!(complete
+            .features
+            .contains(LEFeature::LEDataPacketLengthExtension));
+        assert!(complete.features.contains(LEFeature::LE2MPHY));
+        assert!(!complete.features.contains(LEFeature::LECodedPHY));
+        assert!(!complete.features.is_bit_set(64));
+        let mut packed = [0_u8; ReadRemoteFeaturesComplete::BYTE_LEN];
+        complete.meta_pack_into(&mut packed).unwrap();
+        assert_eq!(packed, buf);
+    }
+}
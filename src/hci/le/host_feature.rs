@@ -0,0 +1,76 @@
+//! LE [`SetHostFeature`]. Tells the controller which optional features the host supports
+//! (Connected Isochronous Channels, etc) so it can advertise them to peers.
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::LEControllerOpcode;
+use crate::hci::Opcode;
+use crate::PackError;
+
+/// Host controlled bits of the LE feature mask.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum HostFeatureBit {
+    /// Connected Isochronous Stream (Host Support).
+    ConnectedIsochronousStream = 32,
+    /// Connection Subrating (Host Support).
+    ConnectionSubrating = 38,
+    /// Channel Sounding (Host Support).
+    ChannelSounding = 47,
+}
+impl From<HostFeatureBit> for u8 {
+    fn from(bit: HostFeatureBit) -> Self {
+        bit as u8
+    }
+}
+/// `HCI_LE_Set_Host_Feature`. `bit_number` is a raw bit so newer features can be set without
+/// a [`HostFeatureBit`] for them.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetHostFeature {
+    pub bit_number: u8,
+    pub bit_value: bool,
+}
+impl SetHostFeature {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetHostFeature;
+    pub const BYTE_LEN: usize = 2;
+    pub fn new(bit: HostFeatureBit, bit_value: bool) -> SetHostFeature {
+        SetHostFeature {
+            bit_number: bit.into(),
+            bit_value,
+        }
+    }
+}
+impl Command for SetHostFeature {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.bit_number;
+        buf[1] = self.bit_value.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(SetHostFeature {
+            bit_number: buf[0],
+            bit_value: match buf[1] {
+                0 => false,
+                1 => true,
+                _ => return Err(PackError::bad_index(1)),
+            },
+        })
+    }
+}
@@ -1,6 +1,9 @@
-//! LE [`SetEventMask`] and [`EventMask`] for dealing with LE event masks.
+//! LE [`SetMetaEventMask`] and [`MetaEventMask`] for dealing with LE event masks. LE events are
+//! only sent if both their bit here and the LE Meta event in the
+//! [`EventMask`](crate::hci::baseband::EventMask) are set.
 use crate::bytes::ToFromBytesEndian;
 use crate::hci::command::Command;
+use crate::hci::informational::CoreVersion;
 use crate::hci::event::{CommandComplete, StatusReturn};
 use crate::hci::le::{LEControllerOpcode, MetaEventCode};
 use crate::hci::Opcode;
@@ -8,12 +11,22 @@ use crate::ConversionError;
 use crate::PackError;
 use core::convert::TryFrom;
 
+/// `LE_Event_Mask`. Built like the [`EventMask`](crate::hci::baseband::EventMask):
+/// ```
+/// use btle::hci::le::mask::MetaEventMask;
+/// use btle::hci::le::MetaEventCode;
+/// let mask = MetaEventMask::ZEROED
+///     .enable(MetaEventCode::AdvertisingReport)
+///     .enable(MetaEventCode::ConnectionComplete);
+/// assert_eq!(u64::from(mask), 0b11);
+/// ```
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub struct MetaEventMask(u64);
 impl MetaEventMask {
-    const DEFAULT: MetaEventMask = MetaEventMask(0x1F);
-    const ZEROED: MetaEventMask = MetaEventMask(0);
-    const BYTE_LEN: usize = 8;
+    /// The controller's default after a reset (the Bluetooth 4.0 events).
+    pub const DEFAULT: MetaEventMask = MetaEventMask(0x1F);
+    pub const ZEROED: MetaEventMask = MetaEventMask(0);
+    pub const BYTE_LEN: usize = 8;
     pub const fn zeroed() -> MetaEventMask {
         Self::ZEROED
     }
@@ -32,6 +45,19 @@ impl MetaEventMask {
     pub fn get_event(&self, event: MetaEventCode) -> bool {
         self.0 & (1u64 << Self::event_pos(event)) != 0
     }
+    #[must_use]
+    pub fn enable(mut self, event: MetaEventCode) -> MetaEventMask {
+        self.enable_event(event);
+        self
+    }
+    #[must_use]
+    pub fn disable(mut self, event: MetaEventCode) -> MetaEventMask {
+        self.disable_event(event);
+        self
+    }
+    pub fn is_enabled(self, event: MetaEventCode) -> bool {
+        self.get_event(event)
+    }
 }
 impl From<MetaEventMask> for u64 {
     fn from(m: MetaEventMask) -> Self {
@@ -57,6 +83,7 @@ pub struct SetMetaEventMask(pub MetaEventMask);
 
 impl Command for SetMetaEventMask {
     type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
 
     fn opcode() -> Opcode {
         LEControllerOpcode::SetEventMask.into()
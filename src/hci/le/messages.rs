@@ -6,12 +6,70 @@ pub mod commands {
             ReadAdvertisingChannelTxPower, SetAdvertisingData, SetAdvertisingEnable,
             SetAdvertisingParameters,
         },
-        connection::{ReadBufferSizeV1, ReadBufferSizeV2},
+        big::{BIGCreateSync, BIGTerminateSync},
+        channel_map::{ReadChannelMap, SetHostChannelClassification},
+        connection::{ConnectionUpdate, ReadBufferSizeV1, ReadBufferSizeV2},
+        data_length::{
+            ReadMaximumDataLength, ReadSuggestedDefaultDataLength, SetDataLength,
+            WriteSuggestedDefaultDataLength,
+        },
+        encryption::{LongTermKeyRequestNegativeReply, LongTermKeyRequestReply, StartEncryption},
+        extended_connection::ExtendedCreateConnection,
+        extended_scan::{SetExtendedScanEnable, SetExtendedScanParameters},
+        features::{ReadLocalFeatures, ReadRemoteFeatures},
         mask::SetMetaEventMask,
-        random::Rand,
+        path_loss::{SetPathLossReportingEnable, SetPathLossReportingParameters},
+        periodic_advertise::{
+            SetPeriodicAdvertisingData, SetPeriodicAdvertisingEnable,
+            SetPeriodicAdvertisingParameters,
+        },
+        periodic_sync::{
+            AddDeviceToPeriodicAdvertiserList, ClearPeriodicAdvertiserList,
+            PeriodicAdvertisingCreateSync, PeriodicAdvertisingCreateSyncCancel,
+            PeriodicAdvertisingTerminateSync, RemoveDeviceFromPeriodicAdvertiserList,
+        },
+        phy::{ReadPHY, SetDefaultPHY, SetPHY},
+        random::{Encrypt, Rand},
+        resolving_list::{
+            AddDeviceToResolvingList, ClearResolvingList, ReadResolvingListSize,
+            RemoveDeviceFromResolvingList, SetAddressResolutionEnable, SetPrivacyMode,
+            SetResolvablePrivateAddressTimeout,
+        },
         scan::{SetScanEnable, SetScanParameters, SetScanResponseData},
+        sync_transfer::{
+            PeriodicAdvertisingSyncTransfer, SetPeriodicAdvertisingSyncTransferParameters,
+        },
+        test_mode::{
+            ReceiverTest, ReceiverTestV2, ReceiverTestV3, TestEnd, TransmitterTest,
+            TransmitterTestV2, TransmitterTestV3,
+        },
+        whitelist::{
+            AddDeviceToWhitelist, ClearWhitelist, ReadWhitelistSize, RemoveDeviceFromWhitelist,
+        },
+    };
+    #[cfg(feature = "channel_sounding")]
+    pub use super::cs::{
+        CSCreateConfig, CSProcedureEnable, CSReadLocalSupportedCapabilities,
+        CSReadRemoteSupportedCapabilities, CSRemoveConfig, CSSetProcedureParameters,
     };
 }
 pub mod events {
-    pub use super::report::AdvertisingReport;
+    pub use super::{
+        big::BIGSyncEstablished,
+        connection::ConnectionUpdateComplete,
+        data_length::DataLengthChange,
+        encryption::LongTermKeyRequest,
+        extended_connection::EnhancedConnectionComplete,
+        features::ReadRemoteFeaturesComplete,
+        path_loss::PathLossThreshold,
+        periodic_sync::{PeriodicAdvertisingSyncEstablished, PeriodicAdvertisingSyncLost},
+        phy::PHYUpdateComplete,
+        report::{AdvertisingReport, ExtendedAdvertisingReport},
+        sync_transfer::PeriodicAdvertisingSyncTransferReceived,
+    };
+    #[cfg(feature = "channel_sounding")]
+    pub use super::cs::{
+        CSConfigComplete, CSProcedureEnableComplete, CSReadRemoteSupportedCapabilitiesComplete,
+        CSSubeventResult, CSSubeventResultContinue,
+    };
 }
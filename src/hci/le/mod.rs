@@ -1,12 +1,31 @@
 //! HCI LE Layer. Handles everything from advertising, scanning, LE links, etc.
 pub mod advertise;
+pub mod big;
+pub mod channel_map;
+pub mod data_length;
+pub mod encryption;
+pub mod extended_advertise;
+pub mod extended_connection;
+pub mod extended_scan;
+pub mod features;
+pub mod host_feature;
 pub mod mask;
 pub mod messages;
+pub mod path_loss;
+pub mod periodic_advertise;
+pub mod phy;
+pub mod periodic_sync;
 pub mod report;
+pub mod resolving_list;
 pub use messages::*;
 pub mod connection;
+#[cfg(feature = "channel_sounding")]
+pub mod cs;
 pub mod random;
 pub mod scan;
+pub mod sync_transfer;
+pub mod test_mode;
+pub mod whitelist;
 use crate::bytes::Storage;
 use crate::hci::event::{Event, EventCode, EventPacket};
 use crate::hci::{Opcode, OCF, OGF};
@@ -49,6 +68,57 @@ pub enum LEControllerOpcode {
     ReceiverTest = 0x001D,
     TransmitterTest = 0x001E,
     TestEnd = 0x001F,
+    SetDataLength = 0x0022,
+    ReadSuggestedDefaultDataLength = 0x0023,
+    WriteSuggestedDefaultDataLength = 0x0024,
+    AddDeviceToResolvingList = 0x0027,
+    RemoveDeviceFromResolvingList = 0x0028,
+    ClearResolvingList = 0x0029,
+    ReadResolvingListSize = 0x002A,
+    SetAddressResolutionEnable = 0x002D,
+    SetResolvablePrivateAddressTimeout = 0x002E,
+    ReadMaximumDataLength = 0x002F,
+    ReadPHY = 0x0030,
+    SetDefaultPHY = 0x0031,
+    SetPHY = 0x0032,
+    ReceiverTestV2 = 0x0033,
+    TransmitterTestV2 = 0x0034,
+    SetExtendedAdvertisingParameters = 0x0036,
+    SetExtendedAdvertisingData = 0x0037,
+    SetExtendedScanResponseData = 0x0038,
+    SetExtendedAdvertisingEnable = 0x0039,
+    ReadMaximumAdvertisingDataLength = 0x003A,
+    ReadNumberOfSupportedAdvertisingSets = 0x003B,
+    RemoveAdvertisingSet = 0x003C,
+    ClearAdvertisingSets = 0x003D,
+    SetPeriodicAdvertisingParameters = 0x003E,
+    SetPeriodicAdvertisingData = 0x003F,
+    SetPeriodicAdvertisingEnable = 0x0040,
+    SetExtendedScanParameters = 0x0041,
+    SetExtendedScanEnable = 0x0042,
+    ExtendedCreateConnection = 0x0043,
+    PeriodicAdvertisingCreateSync = 0x0044,
+    PeriodicAdvertisingCreateSyncCancel = 0x0045,
+    PeriodicAdvertisingTerminateSync = 0x0046,
+    AddDeviceToPeriodicAdvertiserList = 0x0047,
+    RemoveDeviceFromPeriodicAdvertiserList = 0x0048,
+    ClearPeriodicAdvertiserList = 0x0049,
+    SetPrivacyMode = 0x004E,
+    ReceiverTestV3 = 0x004F,
+    TransmitterTestV3 = 0x0050,
+    PeriodicAdvertisingSyncTransfer = 0x005A,
+    SetPeriodicAdvertisingSyncTransferParameters = 0x005C,
+    SetHostFeature = 0x0074,
+    SetPathLossReportingParameters = 0x0078,
+    SetPathLossReportingEnable = 0x0079,
+    BIGCreateSync = 0x006B,
+    BIGTerminateSync = 0x006C,
+    CSReadLocalSupportedCapabilities = 0x0089,
+    CSReadRemoteSupportedCapabilities = 0x008A,
+    CSCreateConfig = 0x0090,
+    CSRemoveConfig = 0x0091,
+    CSSetProcedureParameters = 0x0093,
+    CSProcedureEnable = 0x0094,
 }
 impl TryFrom<OCF> for LEControllerOpcode {
     type Error = ConversionError;
@@ -56,8 +126,8 @@ impl TryFrom<OCF> for LEControllerOpcode {
     fn try_from(ocf: OCF) -> Result<Self, Self::Error> {
         match u16::from(ocf) {
             0x0001 => Ok(LEControllerOpcode::SetEventMask),
-            0x0060 => Ok(LEControllerOpcode::ReadBufferSizeV1),
-            0x0002 => Ok(LEControllerOpcode::ReadBufferSizeV2),
+            0x0002 => Ok(LEControllerOpcode::ReadBufferSizeV1),
+            0x0060 => Ok(LEControllerOpcode::ReadBufferSizeV2),
             0x0003 => Ok(LEControllerOpcode::ReadLocalSupportedFeatures),
             0x0005 => Ok(LEControllerOpcode::SetRandomAddress),
             0x0006 => Ok(LEControllerOpcode::SetAdvertisingParameters),
@@ -86,6 +156,57 @@ impl TryFrom<OCF> for LEControllerOpcode {
             0x001D => Ok(LEControllerOpcode::ReceiverTest),
             0x001E => Ok(LEControllerOpcode::TransmitterTest),
             0x001F => Ok(LEControllerOpcode::TestEnd),
+            0x0022 => Ok(LEControllerOpcode::SetDataLength),
+            0x0023 => Ok(LEControllerOpcode::ReadSuggestedDefaultDataLength),
+            0x0024 => Ok(LEControllerOpcode::WriteSuggestedDefaultDataLength),
+            0x0027 => Ok(LEControllerOpcode::AddDeviceToResolvingList),
+            0x0028 => Ok(LEControllerOpcode::RemoveDeviceFromResolvingList),
+            0x0029 => Ok(LEControllerOpcode::ClearResolvingList),
+            0x002A => Ok(LEControllerOpcode::ReadResolvingListSize),
+            0x002D => Ok(LEControllerOpcode::SetAddressResolutionEnable),
+            0x002E => Ok(LEControllerOpcode::SetResolvablePrivateAddressTimeout),
+            0x002F => Ok(LEControllerOpcode::ReadMaximumDataLength),
+            0x0030 => Ok(LEControllerOpcode::ReadPHY),
+            0x0031 => Ok(LEControllerOpcode::SetDefaultPHY),
+            0x0032 => Ok(LEControllerOpcode::SetPHY),
+            0x0033 => Ok(LEControllerOpcode::ReceiverTestV2),
+            0x0034 => Ok(LEControllerOpcode::TransmitterTestV2),
+            0x0036 => Ok(LEControllerOpcode::SetExtendedAdvertisingParameters),
+            0x0037 => Ok(LEControllerOpcode::SetExtendedAdvertisingData),
+            0x0038 => Ok(LEControllerOpcode::SetExtendedScanResponseData),
+            0x0039 => Ok(LEControllerOpcode::SetExtendedAdvertisingEnable),
+            0x003A => Ok(LEControllerOpcode::ReadMaximumAdvertisingDataLength),
+            0x003B => Ok(LEControllerOpcode::ReadNumberOfSupportedAdvertisingSets),
+            0x003C => Ok(LEControllerOpcode::RemoveAdvertisingSet),
+            0x003D => Ok(LEControllerOpcode::ClearAdvertisingSets),
+            0x003E => Ok(LEControllerOpcode::SetPeriodicAdvertisingParameters),
+            0x003F => Ok(LEControllerOpcode::SetPeriodicAdvertisingData),
+            0x0040 => Ok(LEControllerOpcode::SetPeriodicAdvertisingEnable),
+            0x0041 => Ok(LEControllerOpcode::SetExtendedScanParameters),
+            0x0042 => Ok(LEControllerOpcode::SetExtendedScanEnable),
+            0x0043 => Ok(LEControllerOpcode::ExtendedCreateConnection),
+            0x0044 => Ok(LEControllerOpcode::PeriodicAdvertisingCreateSync),
+            0x0045 => Ok(LEControllerOpcode::PeriodicAdvertisingCreateSyncCancel),
+            0x0046 => Ok(LEControllerOpcode::PeriodicAdvertisingTerminateSync),
+            0x0047 => Ok(LEControllerOpcode::AddDeviceToPeriodicAdvertiserList),
+            0x0048 => Ok(LEControllerOpcode::RemoveDeviceFromPeriodicAdvertiserList),
+            0x0049 => Ok(LEControllerOpcode::ClearPeriodicAdvertiserList),
+            0x004E => Ok(LEControllerOpcode::SetPrivacyMode),
+            0x004F => Ok(LEControllerOpcode::ReceiverTestV3),
+            0x0050 => Ok(LEControllerOpcode::TransmitterTestV3),
+            0x005A => Ok(LEControllerOpcode::PeriodicAdvertisingSyncTransfer),
+            0x005C => Ok(LEControllerOpcode::SetPeriodicAdvertisingSyncTransferParameters),
+            0x0074 => Ok(LEControllerOpcode::SetHostFeature),
+            0x0078 => Ok(LEControllerOpcode::SetPathLossReportingParameters),
+            0x0079 => Ok(LEControllerOpcode::SetPathLossReportingEnable),
+            0x006B => Ok(LEControllerOpcode::BIGCreateSync),
+            0x006C => Ok(LEControllerOpcode::BIGTerminateSync),
+            0x0089 => Ok(LEControllerOpcode::CSReadLocalSupportedCapabilities),
+            0x008A => Ok(LEControllerOpcode::CSReadRemoteSupportedCapabilities),
+            0x0090 => Ok(LEControllerOpcode::CSCreateConfig),
+            0x0091 => Ok(LEControllerOpcode::CSRemoveConfig),
+            0x0093 => Ok(LEControllerOpcode::CSSetProcedureParameters),
+            0x0094 => Ok(LEControllerOpcode::CSProcedureEnable),
             _ => Err(ConversionError(())),
         }
     }
@@ -142,10 +263,18 @@ pub enum MetaEventCode {
     PathLossThreshold = 0x20,
     TransmitPowerReporting = 0x21,
     BIGInfoAdvertisingReport = 0x22,
+    CSReadRemoteSupportedCapabilitiesComplete = 0x2C,
+    CSReadRemoteFAETableComplete = 0x2D,
+    CSSecurityEnableComplete = 0x2E,
+    CSConfigComplete = 0x2F,
+    CSProcedureEnableComplete = 0x30,
+    CSSubeventResult = 0x31,
+    CSSubeventResultContinue = 0x32,
+    CSTestEndComplete = 0x33,
 }
 impl MetaEventCode {
     /// The `MetaEventCode` with the highest value.
-    pub const MAX_CODE: MetaEventCode = MetaEventCode::BIGInfoAdvertisingReport;
+    pub const MAX_CODE: MetaEventCode = MetaEventCode::CSTestEndComplete;
 }
 impl From<MetaEventCode> for u8 {
     fn from(c: MetaEventCode) -> Self {
@@ -191,6 +320,14 @@ impl TryFrom<u8> for MetaEventCode {
             0x20 => Ok(MetaEventCode::PathLossThreshold),
             0x21 => Ok(MetaEventCode::TransmitPowerReporting),
             0x22 => Ok(MetaEventCode::BIGInfoAdvertisingReport),
+            0x2C => Ok(MetaEventCode::CSReadRemoteSupportedCapabilitiesComplete),
+            0x2D => Ok(MetaEventCode::CSReadRemoteFAETableComplete),
+            0x2E => Ok(MetaEventCode::CSSecurityEnableComplete),
+            0x2F => Ok(MetaEventCode::CSConfigComplete),
+            0x30 => Ok(MetaEventCode::CSProcedureEnableComplete),
+            0x31 => Ok(MetaEventCode::CSSubeventResult),
+            0x32 => Ok(MetaEventCode::CSSubeventResultContinue),
+            0x33 => Ok(MetaEventCode::CSTestEndComplete),
             _ => Err(ConversionError(())),
         }
     }
@@ -0,0 +1,245 @@
+//! LE Path Loss Monitoring (Core 5.2+). The controller compares the path loss (peer TX power
+//! minus RSSI) of a connection against a low and a high threshold and sends a
+//! [`PathLossThreshold`] event whenever the link moves into another [`PathLossZone`].
+use crate::hci::baseband::HandleReturn;
+use crate::hci::command::Command;
+use crate::hci::event::CommandComplete;
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::{LEControllerOpcode, MetaEvent, MetaEventCode};
+use crate::hci::Opcode;
+use crate::le::connection::ConnectionHandle;
+use crate::{ConversionError, PackError};
+use core::convert::TryFrom;
+
+/// Path loss zone. Low path loss means the peer is close.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum PathLossZone {
+    Low = 0x00,
+    Middle = 0x01,
+    High = 0x02,
+}
+impl From<PathLossZone> for u8 {
+    fn from(z: PathLossZone) -> Self {
+        z as u8
+    }
+}
+impl TryFrom<u8> for PathLossZone {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(PathLossZone::Low),
+            0x01 => Ok(PathLossZone::Middle),
+            0x02 => Ok(PathLossZone::High),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// `HCI_LE_Set_Path_Loss_Reporting_Parameters`. Thresholds and hysteresis are in dB. The zone
+/// changes to High when the path loss rises above `high_threshold + high_hysteresis` and back when
+/// it falls below `high_threshold - high_hysteresis` (the same for the low threshold).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetPathLossReportingParameters {
+    pub connection_handle: ConnectionHandle,
+    /// [`SetPathLossReportingParameters::UNUSED_HIGH_THRESHOLD`] disables the High zone.
+    pub high_threshold: u8,
+    pub high_hysteresis: u8,
+    pub low_threshold: u8,
+    pub low_hysteresis: u8,
+    /// Connection events the path loss has to stay past a threshold before the zone changes.
+    pub min_time_spent: u16,
+}
+impl SetPathLossReportingParameters {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetPathLossReportingParameters;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + 4 + 2;
+    pub const UNUSED_HIGH_THRESHOLD: u8 = 0xFF;
+    /// The thresholds (with their hysteresis) must not overlap.
+    pub fn is_valid(&self) -> bool {
+        self.high_threshold >= self.low_threshold
+            && self.high_threshold >= self.high_hysteresis
+            && self.low_threshold >= self.low_hysteresis
+            && u16::from(self.high_threshold - self.high_hysteresis)
+                >= u16::from(self.low_threshold) + u16::from(self.low_hysteresis)
+    }
+}
+impl Command for SetPathLossReportingParameters {
+    type Return = CommandComplete<HandleReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        if !self.is_valid() {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2] = self.high_threshold;
+        buf[3] = self.high_hysteresis;
+        buf[4] = self.low_threshold;
+        buf[5] = self.low_hysteresis;
+        buf[6..8].copy_from_slice(&self.min_time_spent.to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let out = SetPathLossReportingParameters {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            high_threshold: buf[2],
+            high_hysteresis: buf[3],
+            low_threshold: buf[4],
+            low_hysteresis: buf[5],
+            min_time_spent: u16::from_le_bytes([buf[6], buf[7]]),
+        };
+        if out.is_valid() {
+            Ok(out)
+        } else {
+            Err(PackError::InvalidFields)
+        }
+    }
+}
+/// `HCI_LE_Set_Path_Loss_Reporting_Enable`. Set the parameters first with
+/// [`SetPathLossReportingParameters`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetPathLossReportingEnable {
+    pub connection_handle: ConnectionHandle,
+    pub is_enabled: bool,
+}
+impl SetPathLossReportingEnable {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetPathLossReportingEnable;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + 1;
+}
+impl Command for SetPathLossReportingEnable {
+    type Return = CommandComplete<HandleReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2] = self.is_enabled.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(SetPathLossReportingEnable {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            is_enabled: match buf[2] {
+                0 => false,
+                1 => true,
+                _ => return Err(PackError::bad_index(2)),
+            },
+        })
+    }
+}
+/// `HCI_LE_Path_Loss_Threshold` event. The connection moved into `zone_entered`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PathLossThreshold {
+    pub connection_handle: ConnectionHandle,
+    /// Path loss in dB. `None` if the controller couldn't measure it.
+    pub current_path_loss: Option<u8>,
+    pub zone_entered: PathLossZone,
+}
+impl PathLossThreshold {
+    pub const CODE: MetaEventCode = MetaEventCode::PathLossThreshold;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + 1 + 1;
+    pub const UNAVAILABLE: u8 = 0xFF;
+}
+impl MetaEvent for PathLossThreshold {
+    const META_CODE: MetaEventCode = Self::CODE;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(PathLossThreshold {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            current_path_loss: match buf[2] {
+                Self::UNAVAILABLE => None,
+                path_loss => Some(path_loss),
+            },
+            zone_entered: PathLossZone::try_from(buf[3]).map_err(|_| PackError::bad_index(3))?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2] = self.current_path_loss.unwrap_or(Self::UNAVAILABLE);
+        buf[3] = self.zone_entered.into();
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_path_loss() {
+        let parameters = SetPathLossReportingParameters {
+            connection_handle: ConnectionHandle::new(0x0040),
+            high_threshold: 70,
+            high_hysteresis: 5,
+            low_threshold: 50,
+            low_hysteresis: 5,
+            min_time_spent: 8,
+        };
+        let packet = parameters.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(
+            packet.parameters.as_ref(),
+            &[0x40, 0x00, 70, 5, 50, 5, 8, 0]
+        );
+        assert_eq!(
+            SetPathLossReportingParameters::unpack_command_packet(&packet),
+            Ok(parameters)
+        );
+        // Overlapping hysteresis.
+        assert_eq!(
+            SetPathLossReportingParameters {
+                high_hysteresis: 11,
+                low_hysteresis: 10,
+                ..parameters
+            }
+            .pack_command_packet::<Box<[u8]>>()
+            .err(),
+            Some(PackError::InvalidFields)
+        );
+
+        let mut buf = [0_u8; PathLossThreshold::BYTE_LEN];
+        let event = PathLossThreshold {
+            connection_handle: ConnectionHandle::new(0x0040),
+            current_path_loss: None,
+            zone_entered: PathLossZone::High,
+        };
+        event.meta_pack_into(&mut buf).unwrap();
+        assert_eq!(buf, [0x40, 0x00, 0xFF, 0x02]);
+        assert_eq!(PathLossThreshold::meta_unpack_from(&buf), Ok(event));
+        assert!(PathLossThreshold::meta_unpack_from(&[0x40, 0x00, 60, 0x03]).is_err());
+    }
+}
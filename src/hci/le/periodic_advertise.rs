@@ -0,0 +1,388 @@
+//! LE Periodic Advertising commands (Bluetooth 5.0). Periodic advertising trains run on top of an
+//! extended advertising set (see [`crate::hci::le::extended_advertise`]) that is neither
+//! connectable nor scannable. Set the periodic parameters and data of the set, enable periodic
+//! advertising then enable the set itself so observers can find the train and sync to it.
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::extended_advertise::DataOperation;
+use crate::hci::le::LEControllerOpcode;
+use crate::hci::Opcode;
+use crate::le::advertiser::AdvertisingSetHandle;
+use crate::{ConversionError, PackError};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::time::Duration;
+
+/// Periodic advertising interval in units of 1.25 ms. Range `0x0006-0xFFFF` (7.5 ms to 81.9 s).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PeriodicAdvertisingInterval(u16);
+impl PeriodicAdvertisingInterval {
+    pub const BYTE_LEN: usize = 2;
+    pub const MIN_U16: u16 = 0x0006;
+    pub const MIN: PeriodicAdvertisingInterval = PeriodicAdvertisingInterval(Self::MIN_U16);
+    pub const MAX: PeriodicAdvertisingInterval = PeriodicAdvertisingInterval(u16::MAX);
+    /// 100 ms.
+    pub const DEFAULT: PeriodicAdvertisingInterval = PeriodicAdvertisingInterval(0x0050);
+    pub const UNIT_MICROS: u32 = 1250;
+    /// Creates a new `PeriodicAdvertisingInterval`.
+    /// # Panics
+    /// Panics if `interval < PeriodicAdvertisingInterval::MIN_U16`.
+    pub fn new(interval: u16) -> PeriodicAdvertisingInterval {
+        Self::new_checked(interval)
+            .unwrap_or_else(|| panic!("invalid periodic advertising interval '{}'", interval))
+    }
+    pub fn new_checked(interval: u16) -> Option<PeriodicAdvertisingInterval> {
+        if interval >= Self::MIN_U16 {
+            Some(PeriodicAdvertisingInterval(interval))
+        } else {
+            None
+        }
+    }
+    pub const fn as_microseconds(self) -> u32 {
+        self.0 as u32 * Self::UNIT_MICROS
+    }
+    pub const fn as_duration(self) -> Duration {
+        Duration::from_micros(self.as_microseconds() as u64)
+    }
+    /// Rounds down to the nearest 1.25 ms. Returns `None` if out of range.
+    pub fn from_duration(duration: Duration) -> Option<PeriodicAdvertisingInterval> {
+        let units = duration.as_micros() / u128::from(Self::UNIT_MICROS);
+        Self::new_checked(u16::try_from(units).ok()?)
+    }
+}
+impl Default for PeriodicAdvertisingInterval {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+impl From<PeriodicAdvertisingInterval> for u16 {
+    fn from(i: PeriodicAdvertisingInterval) -> Self {
+        i.0
+    }
+}
+impl TryFrom<u16> for PeriodicAdvertisingInterval {
+    type Error = ConversionError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Self::new_checked(value).ok_or(ConversionError(()))
+    }
+}
+impl TryFrom<Duration> for PeriodicAdvertisingInterval {
+    type Error = ConversionError;
+
+    fn try_from(value: Duration) -> Result<Self, Self::Error> {
+        Self::from_duration(value).ok_or(ConversionError(()))
+    }
+}
+/// Periodic advertising parameters of one advertising set.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PeriodicAdvertisingParameters {
+    pub handle: AdvertisingSetHandle,
+    pub interval_min: PeriodicAdvertisingInterval,
+    pub interval_max: PeriodicAdvertisingInterval,
+    /// Include the TX power in the `AUX_SYNC_IND` PDUs.
+    pub include_tx_power: bool,
+}
+impl PeriodicAdvertisingParameters {
+    pub const BYTE_LEN: usize = 7;
+    /// Bit 6 of `Periodic_Advertising_Properties`. The rest are reserved.
+    pub const INCLUDE_TX_POWER: u16 = 1 << 6;
+    pub fn new(handle: AdvertisingSetHandle) -> PeriodicAdvertisingParameters {
+        PeriodicAdvertisingParameters {
+            handle,
+            interval_min: PeriodicAdvertisingInterval::DEFAULT,
+            interval_max: PeriodicAdvertisingInterval::DEFAULT,
+            include_tx_power: false,
+        }
+    }
+    pub fn with_interval(
+        self,
+        interval_min: PeriodicAdvertisingInterval,
+        interval_max: PeriodicAdvertisingInterval,
+    ) -> PeriodicAdvertisingParameters {
+        PeriodicAdvertisingParameters {
+            interval_min,
+            interval_max,
+            ..self
+        }
+    }
+    /// `interval_min` can't be more than `interval_max`.
+    pub fn is_valid(&self) -> bool {
+        self.interval_min <= self.interval_max
+    }
+}
+/// `HCI_LE_Set_Periodic_Advertising_Parameters`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetPeriodicAdvertisingParameters(pub PeriodicAdvertisingParameters);
+impl SetPeriodicAdvertisingParameters {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetPeriodicAdvertisingParameters;
+}
+impl Command for SetPeriodicAdvertisingParameters {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        PeriodicAdvertisingParameters::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(PeriodicAdvertisingParameters::BYTE_LEN, buf)?;
+        let parameters = &self.0;
+        if !parameters.is_valid() {
+            return Err(PackError::InvalidFields);
+        }
+        let properties = if parameters.include_tx_power {
+            PeriodicAdvertisingParameters::INCLUDE_TX_POWER
+        } else {
+            0
+        };
+        buf[0] = parameters.handle.into();
+        buf[1..3].copy_from_slice(&u16::from(parameters.interval_min).to_le_bytes());
+        buf[3..5].copy_from_slice(&u16::from(parameters.interval_max).to_le_bytes());
+        buf[5..7].copy_from_slice(&properties.to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(PeriodicAdvertisingParameters::BYTE_LEN, buf)?;
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        let properties = u16_at(5);
+        if properties & !PeriodicAdvertisingParameters::INCLUDE_TX_POWER != 0 {
+            return Err(PackError::bad_index(5));
+        }
+        let parameters = PeriodicAdvertisingParameters {
+            handle: AdvertisingSetHandle::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            interval_min: PeriodicAdvertisingInterval::new_checked(u16_at(1))
+                .ok_or(PackError::bad_index(1))?,
+            interval_max: PeriodicAdvertisingInterval::new_checked(u16_at(3))
+                .ok_or(PackError::bad_index(3))?,
+            include_tx_power: properties != 0,
+        };
+        if !parameters.is_valid() {
+            return Err(PackError::InvalidFields);
+        }
+        Ok(SetPeriodicAdvertisingParameters(parameters))
+    }
+}
+/// `HCI_LE_Set_Periodic_Advertising_Data`. One fragment of the periodic advertising data of an
+/// advertising set. [`DataOperation::UnchangedData`] (only updates the Advertising DID) needs
+/// Bluetooth 5.3.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetPeriodicAdvertisingData {
+    pub handle: AdvertisingSetHandle,
+    pub operation: DataOperation,
+    pub data: Vec<u8>,
+}
+impl SetPeriodicAdvertisingData {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetPeriodicAdvertisingData;
+    pub const HEADER_LEN: usize = 3;
+    pub const MAX_FRAGMENT_LEN: usize = 252;
+    /// Maximum periodic advertising data of one set. Controllers may support less.
+    pub const MAX_DATA_LEN: usize = 1650;
+    /// Split `data` into as many fragments as needed. Empty `data` is one empty
+    /// [`DataOperation::Complete`] fragment (clears the data).
+    pub fn fragments(
+        handle: AdvertisingSetHandle,
+        data: &[u8],
+    ) -> impl Iterator<Item = SetPeriodicAdvertisingData> + '_ {
+        let chunks: Vec<&[u8]> = if data.is_empty() {
+            alloc::vec![data]
+        } else {
+            data.chunks(Self::MAX_FRAGMENT_LEN).collect()
+        };
+        let count = chunks.len();
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(move |(i, chunk)| SetPeriodicAdvertisingData {
+                handle,
+                operation: match i {
+                    _ if count == 1 => DataOperation::Complete,
+                    0 => DataOperation::FirstFragment,
+                    i if i + 1 == count => DataOperation::LastFragment,
+                    _ => DataOperation::IntermediateFragment,
+                },
+                data: chunk.to_vec(),
+            })
+    }
+}
+impl Command for SetPeriodicAdvertisingData {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.data.len()
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        if self.data.len() > Self::MAX_FRAGMENT_LEN
+            || (self.operation == DataOperation::UnchangedData && !self.data.is_empty())
+        {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = self.handle.into();
+        buf[1] = self.operation.into();
+        buf[2] = u8::try_from(self.data.len()).map_err(|_| PackError::InvalidFields)?;
+        buf[Self::HEADER_LEN..].copy_from_slice(&self.data);
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        let data_len = usize::from(buf[2]);
+        if data_len > Self::MAX_FRAGMENT_LEN {
+            return Err(PackError::bad_index(2));
+        }
+        PackError::expect_length(Self::HEADER_LEN + data_len, buf)?;
+        let operation = DataOperation::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?;
+        if operation == DataOperation::UnchangedData && data_len != 0 {
+            return Err(PackError::bad_index(2));
+        }
+        Ok(SetPeriodicAdvertisingData {
+            handle: AdvertisingSetHandle::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            operation,
+            data: buf[Self::HEADER_LEN..].to_vec(),
+        })
+    }
+}
+/// `HCI_LE_Set_Periodic_Advertising_Enable`. Periodic advertising only starts once the
+/// advertising set itself is enabled too.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetPeriodicAdvertisingEnable {
+    pub is_enabled: bool,
+    /// Include the ADI field in `AUX_SYNC_IND` PDUs (Bluetooth 5.3).
+    pub include_adi: bool,
+    pub handle: AdvertisingSetHandle,
+}
+impl SetPeriodicAdvertisingEnable {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetPeriodicAdvertisingEnable;
+    pub const BYTE_LEN: usize = 2;
+    pub const ENABLE_BIT: u8 = 1 << 0;
+    pub const INCLUDE_ADI_BIT: u8 = 1 << 1;
+    pub fn new(handle: AdvertisingSetHandle, is_enabled: bool) -> SetPeriodicAdvertisingEnable {
+        SetPeriodicAdvertisingEnable {
+            is_enabled,
+            include_adi: false,
+            handle,
+        }
+    }
+}
+impl Command for SetPeriodicAdvertisingEnable {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let mut enable = 0;
+        if self.is_enabled {
+            enable |= Self::ENABLE_BIT;
+        }
+        if self.include_adi {
+            enable |= Self::INCLUDE_ADI_BIT;
+        }
+        buf[0] = enable;
+        buf[1] = self.handle.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        if buf[0] & !(Self::ENABLE_BIT | Self::INCLUDE_ADI_BIT) != 0 {
+            return Err(PackError::bad_index(0));
+        }
+        Ok(SetPeriodicAdvertisingEnable {
+            is_enabled: buf[0] & Self::ENABLE_BIT != 0,
+            include_adi: buf[0] & Self::INCLUDE_ADI_BIT != 0,
+            handle: AdvertisingSetHandle::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?,
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_periodic_advertising() {
+        assert_eq!(PeriodicAdvertisingInterval::new_checked(5), None);
+        assert_eq!(
+            PeriodicAdvertisingInterval::from_duration(Duration::from_millis(100)),
+            Some(PeriodicAdvertisingInterval::DEFAULT)
+        );
+        assert_eq!(
+            PeriodicAdvertisingInterval::MIN.as_duration(),
+            Duration::from_micros(7500)
+        );
+
+        let handle = AdvertisingSetHandle::new(1);
+        let mut parameters = PeriodicAdvertisingParameters::new(handle);
+        parameters.include_tx_power = true;
+        let set_parameters = SetPeriodicAdvertisingParameters(parameters);
+        let packet = set_parameters.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(&packet.parameters[..], &[1, 0x50, 0, 0x50, 0, 0x40, 0]);
+        assert_eq!(
+            SetPeriodicAdvertisingParameters::unpack_from(&packet.parameters),
+            Ok(set_parameters)
+        );
+        let backwards = parameters.with_interval(
+            PeriodicAdvertisingInterval::MAX,
+            PeriodicAdvertisingInterval::MIN,
+        );
+        assert!(SetPeriodicAdvertisingParameters(backwards)
+            .pack_command_packet::<Box<[u8]>>()
+            .is_err());
+
+        let data = [0xAA_u8; 300];
+        let fragments: Vec<_> = SetPeriodicAdvertisingData::fragments(handle, &data).collect();
+        assert_eq!(fragments.len(), 2);
+        assert_eq!(fragments[0].operation, DataOperation::FirstFragment);
+        assert_eq!(fragments[1].data.len(), 300 - 252);
+        let packet = fragments[0].pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(
+            SetPeriodicAdvertisingData::unpack_from(&packet.parameters).as_ref(),
+            Ok(&fragments[0])
+        );
+
+        let enable = SetPeriodicAdvertisingEnable {
+            include_adi: true,
+            ..SetPeriodicAdvertisingEnable::new(handle, true)
+        };
+        let packet = enable.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(&packet.parameters[..], &[0b11, 1]);
+        assert_eq!(
+            SetPeriodicAdvertisingEnable::unpack_from(&packet.parameters),
+            Ok(enable)
+        );
+    }
+}
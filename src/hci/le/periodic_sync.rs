@@ -0,0 +1,564 @@
+//! LE Periodic Advertising synchronization commands and events. An observer syncs to the periodic
+//! advertising train of one advertising set (or of any set in the Periodic Advertiser List) and
+//! then receives its reports until it terminates the sync or the sync is lost.
+use crate::hci::command::{Command, LongCommand};
+use crate::hci::event::{CommandComplete, CommandStatus, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::connection::or_min;
+use crate::hci::le::periodic_advertise::PeriodicAdvertisingInterval;
+use crate::hci::le::{LEControllerOpcode, MetaEvent, MetaEventCode};
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::advertiser::PeerAddressType;
+use crate::le::connection::MasterClockAccuracy;
+use crate::le::phy::Phy;
+use crate::le::report::{AddressType, AdvertisingSID};
+use crate::{BTAddress, ConversionError, DeviceAddress, PackError, BT_ADDRESS_LEN};
+use core::convert::TryFrom;
+
+/// Handle of a periodic advertising train returned by the periodic advertising sync established
+/// event.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct SyncHandle(pub u16);
+impl SyncHandle {
+    pub const BYTE_LEN: usize = 2;
+    pub const MAX_U16: u16 = 0x0EFF;
+    pub const MIN: SyncHandle = SyncHandle(0);
+    pub const MAX: SyncHandle = SyncHandle(Self::MAX_U16);
+    pub fn new_checked(value: u16) -> Option<SyncHandle> {
+        if value > Self::MAX_U16 {
+            None
+        } else {
+            Some(SyncHandle(value))
+        }
+    }
+}
+impl From<SyncHandle> for u16 {
+    fn from(h: SyncHandle) -> Self {
+        h.0
+    }
+}
+impl TryFrom<u16> for SyncHandle {
+    type Error = ConversionError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        SyncHandle::new_checked(value).ok_or(ConversionError(()))
+    }
+}
+/// One advertising set of an advertiser. Used by [`PeriodicAdvertisingCreateSync`] and as an entry
+/// of the Periodic Advertiser List.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PeriodicAdvertiserListEntry {
+    /// Public and Random also match the Public and Random Identity addresses.
+    pub address_type: PeerAddressType,
+    pub address: BTAddress,
+    pub sid: AdvertisingSID,
+}
+impl PeriodicAdvertiserListEntry {
+    pub const BYTE_LEN: usize = 1 + BT_ADDRESS_LEN + AdvertisingSID::BYTE_LEN;
+    pub fn new(address: DeviceAddress, sid: AdvertisingSID) -> PeriodicAdvertiserListEntry {
+        PeriodicAdvertiserListEntry {
+            address_type: address.kind.into(),
+            address: address.addr,
+            sid,
+        }
+    }
+    pub fn device_address(&self) -> DeviceAddress {
+        match self.address_type {
+            PeerAddressType::Public => DeviceAddress::public(self.address),
+            PeerAddressType::Random => DeviceAddress::random(self.address),
+        }
+    }
+    /// Packed in the Periodic Advertiser List command order (address type, address, SID).
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.address_type.into();
+        self.address.pack_into(&mut buf[1..7])?;
+        buf[7] = self.sid.into();
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<PeriodicAdvertiserListEntry, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(PeriodicAdvertiserListEntry {
+            address_type: PeerAddressType::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            address: BTAddress::unpack_from(&buf[1..7])?,
+            sid: AdvertisingSID::try_from(buf[7]).map_err(|_| PackError::bad_index(7))?,
+        })
+    }
+}
+/// `HCI_LE_Periodic_Advertising_Create_Sync`. Start looking for a periodic advertising train.
+/// Completes with [`PeriodicAdvertisingSyncEstablished`] once synced or after `sync_timeout`
+/// without finding it. Only one can be pending at a time.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PeriodicAdvertisingCreateSync {
+    /// Sync to any advertiser in the Periodic Advertiser List instead of `advertiser`.
+    pub use_periodic_advertiser_list: bool,
+    /// Don't send reports until reporting is enabled (Core 5.1+).
+    pub is_reporting_disabled: bool,
+    /// Ignored if `use_periodic_advertiser_list` is set.
+    pub advertiser: PeriodicAdvertiserListEntry,
+    /// Number of periodic advertising events that can be skipped after a successful receive
+    /// (`0x0000` to `0x01F3`).
+    pub skip: u16,
+    /// Sync timeout in units of 10ms (`0x000A` to `0x4000`).
+    pub sync_timeout: u16,
+    /// Bit mask of the Constant Tone Extension types not to sync to. `0` syncs to any.
+    pub sync_cte_type: u8,
+}
+impl PeriodicAdvertisingCreateSync {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::PeriodicAdvertisingCreateSync;
+    pub const BYTE_LEN: usize = 1 + PeriodicAdvertiserListEntry::BYTE_LEN + 2 + 2 + 1;
+    pub const USE_LIST_BIT: u8 = 1 << 0;
+    pub const REPORTING_DISABLED_BIT: u8 = 1 << 1;
+    pub const MAX_SKIP: u16 = 0x01F3;
+    pub const MIN_SYNC_TIMEOUT: u16 = 0x000A;
+    pub const MAX_SYNC_TIMEOUT: u16 = 0x4000;
+    /// Sync to `advertiser` with no skipping.
+    pub fn new(advertiser: PeriodicAdvertiserListEntry, sync_timeout: u16) -> Self {
+        PeriodicAdvertisingCreateSync {
+            use_periodic_advertiser_list: false,
+            is_reporting_disabled: false,
+            advertiser,
+            skip: 0,
+            sync_timeout,
+            sync_cte_type: 0,
+        }
+    }
+    /// Sync to the first advertiser found from the Periodic Advertiser List.
+    pub fn from_list(sync_timeout: u16) -> Self {
+        PeriodicAdvertisingCreateSync {
+            use_periodic_advertiser_list: true,
+            ..Self::new(
+                PeriodicAdvertiserListEntry {
+                    address_type: PeerAddressType::Public,
+                    address: BTAddress::ZEROED,
+                    sid: AdvertisingSID::default(),
+                },
+                sync_timeout,
+            )
+        }
+    }
+    pub fn is_valid(&self) -> bool {
+        self.skip <= Self::MAX_SKIP
+            && (Self::MIN_SYNC_TIMEOUT..=Self::MAX_SYNC_TIMEOUT).contains(&self.sync_timeout)
+    }
+    fn options(&self) -> u8 {
+        let mut options = 0;
+        if self.use_periodic_advertiser_list {
+            options |= Self::USE_LIST_BIT;
+        }
+        if self.is_reporting_disabled {
+            options |= Self::REPORTING_DISABLED_BIT;
+        }
+        options
+    }
+}
+impl Command for PeriodicAdvertisingCreateSync {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        if !self.is_valid() {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0] = self.options();
+        // The SID comes first here, unlike in the Periodic Advertiser List commands.
+        buf[1] = self.advertiser.sid.into();
+        buf[2] = self.advertiser.address_type.into();
+        self.advertiser.address.pack_into(&mut buf[3..9])?;
+        buf[9..11].copy_from_slice(&self.skip.to_le_bytes());
+        buf[11..13].copy_from_slice(&self.sync_timeout.to_le_bytes());
+        buf[13] = self.sync_cte_type;
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        if buf[0] & !(Self::USE_LIST_BIT | Self::REPORTING_DISABLED_BIT) != 0 {
+            return Err(PackError::bad_index(0));
+        }
+        let out = PeriodicAdvertisingCreateSync {
+            use_periodic_advertiser_list: buf[0] & Self::USE_LIST_BIT != 0,
+            is_reporting_disabled: buf[0] & Self::REPORTING_DISABLED_BIT != 0,
+            advertiser: PeriodicAdvertiserListEntry {
+                address_type: PeerAddressType::try_from(buf[2])
+                    .map_err(|_| PackError::bad_index(2))?,
+                address: BTAddress::unpack_from(&buf[3..9])?,
+                sid: AdvertisingSID::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?,
+            },
+            skip: u16::from_le_bytes([buf[9], buf[10]]),
+            sync_timeout: u16::from_le_bytes([buf[11], buf[12]]),
+            sync_cte_type: buf[13],
+        };
+        if out.is_valid() {
+            Ok(out)
+        } else {
+            Err(PackError::InvalidFields)
+        }
+    }
+}
+impl LongCommand for PeriodicAdvertisingCreateSync {
+    type Completion = PeriodicAdvertisingSyncEstablished;
+    type Cancel = PeriodicAdvertisingCreateSyncCancel;
+
+    fn cancel_command(&self) -> PeriodicAdvertisingCreateSyncCancel {
+        PeriodicAdvertisingCreateSyncCancel
+    }
+
+    /// Syncing from the Periodic Advertiser List can complete with any advertiser.
+    fn is_completion(&self, completion: &PeriodicAdvertisingSyncEstablished) -> bool {
+        self.use_periodic_advertiser_list
+            || completion.status != ErrorCode::Ok
+            || (completion.sid == self.advertiser.sid
+                && completion.advertiser_address == self.advertiser.address)
+    }
+}
+/// `HCI_LE_Periodic_Advertising_Create_Sync_Cancel`. Stop a pending
+/// [`PeriodicAdvertisingCreateSync`]. The controller then sends a
+/// [`PeriodicAdvertisingSyncEstablished`] with `ErrorCode::OperationCancelledByHost`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct PeriodicAdvertisingCreateSyncCancel;
+impl PeriodicAdvertisingCreateSyncCancel {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::PeriodicAdvertisingCreateSyncCancel;
+}
+impl Command for PeriodicAdvertisingCreateSyncCancel {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(PeriodicAdvertisingCreateSyncCancel)
+    }
+}
+/// `HCI_LE_Periodic_Advertising_Terminate_Sync`. Stop receiving the train of `sync_handle`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct PeriodicAdvertisingTerminateSync {
+    pub sync_handle: SyncHandle,
+}
+impl PeriodicAdvertisingTerminateSync {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::PeriodicAdvertisingTerminateSync;
+    pub const BYTE_LEN: usize = SyncHandle::BYTE_LEN;
+}
+impl Command for PeriodicAdvertisingTerminateSync {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&self.sync_handle.0.to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(PeriodicAdvertisingTerminateSync {
+            sync_handle: SyncHandle::try_from(u16::from_le_bytes([buf[0], buf[1]]))
+                .map_err(|_| PackError::bad_index(0))?,
+        })
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct AddDeviceToPeriodicAdvertiserList(pub PeriodicAdvertiserListEntry);
+impl AddDeviceToPeriodicAdvertiserList {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::AddDeviceToPeriodicAdvertiserList;
+}
+impl Command for AddDeviceToPeriodicAdvertiserList {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        PeriodicAdvertiserListEntry::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.0.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(AddDeviceToPeriodicAdvertiserList(
+            PeriodicAdvertiserListEntry::unpack_from(buf)?,
+        ))
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct RemoveDeviceFromPeriodicAdvertiserList(pub PeriodicAdvertiserListEntry);
+impl RemoveDeviceFromPeriodicAdvertiserList {
+    pub const OPCODE: LEControllerOpcode =
+        LEControllerOpcode::RemoveDeviceFromPeriodicAdvertiserList;
+}
+impl Command for RemoveDeviceFromPeriodicAdvertiserList {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        PeriodicAdvertiserListEntry::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.0.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(RemoveDeviceFromPeriodicAdvertiserList(
+            PeriodicAdvertiserListEntry::unpack_from(buf)?,
+        ))
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ClearPeriodicAdvertiserList {}
+impl ClearPeriodicAdvertiserList {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ClearPeriodicAdvertiserList;
+}
+impl Command for ClearPeriodicAdvertiserList {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ClearPeriodicAdvertiserList {})
+    }
+}
+/// `HCI_LE_Periodic_Advertising_Sync_Established` event. Completes a
+/// [`PeriodicAdvertisingCreateSync`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PeriodicAdvertisingSyncEstablished {
+    pub status: ErrorCode,
+    pub sync_handle: SyncHandle,
+    pub sid: AdvertisingSID,
+    pub advertiser_address_type: AddressType,
+    pub advertiser_address: BTAddress,
+    pub advertiser_phy: Phy,
+    pub periodic_advertising_interval: PeriodicAdvertisingInterval,
+    pub advertiser_clock_accuracy: MasterClockAccuracy,
+}
+impl PeriodicAdvertisingSyncEstablished {
+    pub const CODE: MetaEventCode = MetaEventCode::PeriodicAdvertisingSyncEstablished;
+    pub const BYTE_LEN: usize = 15;
+    pub fn advertiser(&self) -> DeviceAddress {
+        self.advertiser_address_type
+            .device_address(self.advertiser_address)
+    }
+}
+impl MetaEvent for PeriodicAdvertisingSyncEstablished {
+    const META_CODE: MetaEventCode = Self::CODE;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    /// Like [`crate::hci::le::connection::ConnectionCompleteEvent`], invalid fields of a failed
+    /// sync are replaced by the minimum.
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let status = ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?;
+        let is_ok = status == ErrorCode::Ok;
+        Ok(PeriodicAdvertisingSyncEstablished {
+            status,
+            sync_handle: or_min(
+                SyncHandle::new_checked(u16::from_le_bytes([buf[1], buf[2]])),
+                SyncHandle::MIN,
+                is_ok,
+                1,
+            )?,
+            sid: or_min(
+                AdvertisingSID::new_checked(buf[3]),
+                AdvertisingSID::default(),
+                is_ok,
+                3,
+            )?,
+            advertiser_address_type: or_min(
+                AddressType::try_from(buf[4]).ok(),
+                AddressType::PublicDevice,
+                is_ok,
+                4,
+            )?,
+            advertiser_address: BTAddress::new(&buf[5..11]),
+            advertiser_phy: or_min(Phy::try_from(buf[11]).ok(), Phy::LE1M, is_ok, 11)?,
+            periodic_advertising_interval: or_min(
+                PeriodicAdvertisingInterval::new_checked(u16::from_le_bytes([buf[12], buf[13]])),
+                PeriodicAdvertisingInterval::MIN,
+                is_ok,
+                12,
+            )?,
+            advertiser_clock_accuracy: or_min(
+                MasterClockAccuracy::try_from(buf[14]).ok(),
+                MasterClockAccuracy::PPM500,
+                is_ok,
+                14,
+            )?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&self.sync_handle.0.to_le_bytes());
+        buf[3] = self.sid.into();
+        buf[4] = self.advertiser_address_type.into();
+        self.advertiser_address.pack_into(&mut buf[5..11])?;
+        buf[11] = self.advertiser_phy.into();
+        buf[12..14].copy_from_slice(&u16::from(self.periodic_advertising_interval).to_le_bytes());
+        buf[14] = self.advertiser_clock_accuracy.into();
+        Ok(())
+    }
+}
+/// `HCI_LE_Periodic_Advertising_Sync_Lost` event. The train of `sync_handle` wasn't received
+/// within the sync timeout. The handle is no longer valid.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct PeriodicAdvertisingSyncLost {
+    pub sync_handle: SyncHandle,
+}
+impl PeriodicAdvertisingSyncLost {
+    pub const BYTE_LEN: usize = SyncHandle::BYTE_LEN;
+}
+impl MetaEvent for PeriodicAdvertisingSyncLost {
+    const META_CODE: MetaEventCode = MetaEventCode::PeriodicAdvertisingSyncLost;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(PeriodicAdvertisingSyncLost {
+            sync_handle: SyncHandle::try_from(u16::from_le_bytes([buf[0], buf[1]]))
+                .map_err(|_| PackError::bad_index(0))?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&self.sync_handle.0.to_le_bytes());
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_create_sync() {
+        let advertiser = PeriodicAdvertiserListEntry::new(
+            DeviceAddress::random(BTAddress([1, 2, 3, 4, 5, 0xC6])),
+            AdvertisingSID::new(3),
+        );
+        let cmd = PeriodicAdvertisingCreateSync::new(advertiser, 0x0100);
+        let packet = cmd.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(
+            packet.parameters.as_ref(),
+            &[0x00, 0x03, 0x01, 1, 2, 3, 4, 5, 0xC6, 0x00, 0x00, 0x00, 0x01, 0x00]
+        );
+        assert_eq!(
+            PeriodicAdvertisingCreateSync::unpack_from(packet.parameters.as_ref()),
+            Ok(cmd)
+        );
+        assert_eq!(
+            PeriodicAdvertisingCreateSync::from_list(0x0005)
+                .pack_command_packet::<Box<[u8]>>()
+                .err(),
+            Some(PackError::InvalidFields)
+        );
+        let mut list_entry = [0_u8; PeriodicAdvertiserListEntry::BYTE_LEN];
+        advertiser.pack_into(&mut list_entry).unwrap();
+        assert_eq!(list_entry, [0x01, 1, 2, 3, 4, 5, 0xC6, 0x03]);
+
+        let established = PeriodicAdvertisingSyncEstablished {
+            status: ErrorCode::Ok,
+            sync_handle: SyncHandle(0x0001),
+            sid: advertiser.sid,
+            advertiser_address_type: AddressType::RandomDevice,
+            advertiser_address: advertiser.address,
+            advertiser_phy: Phy::LE2M,
+            periodic_advertising_interval: PeriodicAdvertisingInterval::DEFAULT,
+            advertiser_clock_accuracy: MasterClockAccuracy::PPM50,
+        };
+        let mut buf = [0_u8; PeriodicAdvertisingSyncEstablished::BYTE_LEN];
+        established.meta_pack_into(&mut buf).unwrap();
+        assert_eq!(
+            PeriodicAdvertisingSyncEstablished::meta_unpack_from(&buf),
+            Ok(established)
+        );
+        assert!(cmd.is_completion(&established));
+        assert!(!cmd.is_completion(&PeriodicAdvertisingSyncEstablished {
+            sid: AdvertisingSID::new(4),
+            ..established
+        }));
+        // A failed sync has garbage in the rest of the fields.
+        let mut failed = [0xFF_u8; PeriodicAdvertisingSyncEstablished::BYTE_LEN];
+        failed[0] = ErrorCode::OperationCancelledByHost.into();
+        let failed = PeriodicAdvertisingSyncEstablished::meta_unpack_from(&failed).unwrap();
+        assert_eq!(failed.sync_handle, SyncHandle::MIN);
+        assert!(cmd.is_completion(&failed));
+    }
+}
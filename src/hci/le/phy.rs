@@ -0,0 +1,343 @@
+//! LE PHY management (Core 5.0+). Read the PHYs of a connection, set the preferred PHYs for new
+//! connections and switch a connection to LE 2M (throughput) or LE Coded (range).
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, CommandStatus, ReturnParameters, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::connection::or_min;
+use crate::hci::le::{LEControllerOpcode, MetaEvent, MetaEventCode};
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::connection::ConnectionHandle;
+use crate::le::phy::{Phy, PhyMask};
+use crate::{ConversionError, PackError};
+use core::convert::TryFrom;
+
+/// `All_PHYs` bits for no transmitter/receiver preference.
+const NO_TX_PREFERENCE: u8 = 0x01;
+const NO_RX_PREFERENCE: u8 = 0x02;
+/// A preference can't be an empty set.
+fn is_valid_preference(tx_phys: Option<PhyMask>, rx_phys: Option<PhyMask>) -> bool {
+    tx_phys != Some(PhyMask::ZEROED) && rx_phys != Some(PhyMask::ZEROED)
+}
+/// Packs `All_PHYs`, `TX_PHYs` and `RX_PHYs`. `None` is no preference.
+fn pack_preferred_phys(
+    tx_phys: Option<PhyMask>,
+    rx_phys: Option<PhyMask>,
+    buf: &mut [u8],
+) -> Result<(), PackError> {
+    if !is_valid_preference(tx_phys, rx_phys) {
+        return Err(PackError::InvalidFields);
+    }
+    buf[0] = if tx_phys.is_none() {
+        NO_TX_PREFERENCE
+    } else {
+        0
+    } | if rx_phys.is_none() {
+        NO_RX_PREFERENCE
+    } else {
+        0
+    };
+    buf[1] = tx_phys.unwrap_or_default().into();
+    buf[2] = rx_phys.unwrap_or_default().into();
+    Ok(())
+}
+fn unpack_preferred_phys(
+    buf: &[u8],
+    index: usize,
+) -> Result<(Option<PhyMask>, Option<PhyMask>), PackError> {
+    let mask = |i: usize| PhyMask::try_from(buf[i]).map_err(|_| PackError::bad_index(i));
+    if buf[index] & !(NO_TX_PREFERENCE | NO_RX_PREFERENCE) != 0 {
+        return Err(PackError::bad_index(index));
+    }
+    let tx_phys = mask(index + 1)?;
+    let rx_phys = mask(index + 2)?;
+    let tx_phys = if buf[index] & NO_TX_PREFERENCE == 0 {
+        Some(tx_phys)
+    } else {
+        None
+    };
+    let rx_phys = if buf[index] & NO_RX_PREFERENCE == 0 {
+        Some(rx_phys)
+    } else {
+        None
+    };
+    if !is_valid_preference(tx_phys, rx_phys) {
+        return Err(PackError::InvalidFields);
+    }
+    Ok((tx_phys, rx_phys))
+}
+/// `HCI_LE_Read_PHY`. The PHYs a connection is using.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReadPHY {
+    pub connection_handle: ConnectionHandle,
+}
+impl ReadPHY {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReadPHY;
+}
+impl Command for ReadPHY {
+    type Return = CommandComplete<ConnectionPHYs>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ConnectionHandle::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        Ok(ReadPHY {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+        })
+    }
+}
+/// PHYs of a connection. Returned by [`ReadPHY`] and sent in [`PHYUpdateComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ConnectionPHYs {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+    pub tx_phy: Phy,
+    pub rx_phy: Phy,
+}
+impl ConnectionPHYs {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN + 2;
+}
+impl ReturnParameters for ConnectionPHYs {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[3] = self.tx_phy.into();
+        buf[4] = self.rx_phy.into();
+        Ok(())
+    }
+
+    /// The PHYs of a failed read/update aren't valid and are replaced by LE 1M.
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let status = ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?;
+        let is_ok = status == ErrorCode::Ok;
+        Ok(ConnectionPHYs {
+            status,
+            connection_handle: ConnectionHandle::unpack_from(buf, 1)?,
+            tx_phy: or_min(Phy::try_from(buf[3]).ok(), Phy::LE1M, is_ok, 3)?,
+            rx_phy: or_min(Phy::try_from(buf[4]).ok(), Phy::LE1M, is_ok, 4)?,
+        })
+    }
+}
+/// `HCI_LE_Set_Default_PHY`. PHYs preferred for new connections. `None` leaves the choice to
+/// the controller.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct SetDefaultPHY {
+    pub tx_phys: Option<PhyMask>,
+    pub rx_phys: Option<PhyMask>,
+}
+impl SetDefaultPHY {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetDefaultPHY;
+    pub const BYTE_LEN: usize = 3;
+    pub fn is_valid(&self) -> bool {
+        is_valid_preference(self.tx_phys, self.rx_phys)
+    }
+}
+impl Command for SetDefaultPHY {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        pack_preferred_phys(self.tx_phys, self.rx_phys, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let (tx_phys, rx_phys) = unpack_preferred_phys(buf, 0)?;
+        Ok(SetDefaultPHY { tx_phys, rx_phys })
+    }
+}
+/// Preferred coding when LE Coded is used.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum CodedPHYOptions {
+    NoPreference = 0x0000,
+    /// S=2 (500 kb/s).
+    S2 = 0x0001,
+    /// S=8 (125 kb/s, longest range).
+    S8 = 0x0002,
+}
+impl CodedPHYOptions {
+    pub const DEFAULT: CodedPHYOptions = CodedPHYOptions::NoPreference;
+}
+impl Default for CodedPHYOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+impl From<CodedPHYOptions> for u16 {
+    fn from(o: CodedPHYOptions) -> Self {
+        o as u16
+    }
+}
+impl TryFrom<u16> for CodedPHYOptions {
+    type Error = ConversionError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0x0000 => Ok(CodedPHYOptions::NoPreference),
+            0x0001 => Ok(CodedPHYOptions::S2),
+            0x0002 => Ok(CodedPHYOptions::S8),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// `HCI_LE_Set_PHY`. Ask to switch `connection_handle` to one of the preferred PHYs. The result
+/// is sent in a [`PHYUpdateComplete`] (also when nothing changed).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetPHY {
+    pub connection_handle: ConnectionHandle,
+    pub tx_phys: Option<PhyMask>,
+    pub rx_phys: Option<PhyMask>,
+    pub phy_options: CodedPHYOptions,
+}
+impl SetPHY {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetPHY;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + 3 + 2;
+    /// Prefer `phy` in both directions.
+    pub fn new(connection_handle: ConnectionHandle, phy: Phy) -> SetPHY {
+        SetPHY {
+            connection_handle,
+            tx_phys: Some(phy.into()),
+            rx_phys: Some(phy.into()),
+            phy_options: CodedPHYOptions::DEFAULT,
+        }
+    }
+    pub fn is_valid(&self) -> bool {
+        is_valid_preference(self.tx_phys, self.rx_phys)
+    }
+}
+impl Command for SetPHY {
+    type Return = CommandStatus;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        pack_preferred_phys(self.tx_phys, self.rx_phys, &mut buf[2..5])?;
+        buf[5..7].copy_from_slice(&u16::from(self.phy_options).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let (tx_phys, rx_phys) = unpack_preferred_phys(buf, 2)?;
+        Ok(SetPHY {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            tx_phys,
+            rx_phys,
+            phy_options: CodedPHYOptions::try_from(u16::from_le_bytes([buf[5], buf[6]]))
+                .map_err(|_| PackError::bad_index(5))?,
+        })
+    }
+}
+/// LE PHY Update Complete event. The PHYs of a connection changed (or a [`SetPHY`] finished).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PHYUpdateComplete(pub ConnectionPHYs);
+impl PHYUpdateComplete {
+    pub const CODE: MetaEventCode = MetaEventCode::PHYUpdateCompleteEvent;
+}
+impl MetaEvent for PHYUpdateComplete {
+    const META_CODE: MetaEventCode = Self::CODE;
+
+    fn meta_byte_len(&self) -> usize {
+        ConnectionPHYs::BYTE_LEN
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(PHYUpdateComplete(ConnectionPHYs::unpack_from(buf)?))
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.0.pack_into(buf)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_set_phy() {
+        let mut tx_phys = PhyMask::from(Phy::LE2M);
+        tx_phys.enable(Phy::LE1M);
+        let set_phy = SetPHY {
+            tx_phys: Some(tx_phys),
+            rx_phys: None,
+            ..SetPHY::new(ConnectionHandle::new(0x0040), Phy::LE2M)
+        };
+        let packet = set_phy.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(
+            packet.parameters.as_ref(),
+            &[0x40, 0x00, 0x02, 0b011, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(SetPHY::unpack_command_packet(&packet), Ok(set_phy));
+        assert_eq!(
+            SetDefaultPHY {
+                tx_phys: Some(PhyMask::ZEROED),
+                rx_phys: None,
+            }
+            .pack_command_packet::<Box<[u8]>>()
+            .err(),
+            Some(PackError::InvalidFields)
+        );
+
+        let update = PHYUpdateComplete(ConnectionPHYs {
+            status: ErrorCode::Ok,
+            connection_handle: ConnectionHandle::new(0x0040),
+            tx_phy: Phy::LE2M,
+            rx_phy: Phy::LECoded,
+        });
+        let mut buf = [0_u8; ConnectionPHYs::BYTE_LEN];
+        update.meta_pack_into(&mut buf).unwrap();
+        assert_eq!(buf, [0x00, 0x40, 0x00, 0x02, 0x03]);
+        assert_eq!(PHYUpdateComplete::meta_unpack_from(&buf), Ok(update));
+    }
+}
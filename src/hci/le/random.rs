@@ -1,5 +1,7 @@
-//! LE [`Rand`] command and return parameters.
+//! Controller crypto. [`Rand`] returns random bytes and [`Encrypt`] AES-128 encrypts a block,
+//! both using the controller's hardware so the host doesn't need an RNG or AES of its own.
 use crate::hci::command::Command;
+use crate::hci::informational::CoreVersion;
 use crate::hci::event::{CommandComplete, ReturnParameters};
 use crate::hci::le::LEControllerOpcode;
 use crate::hci::{ErrorCode, Opcode};
@@ -10,6 +12,7 @@ use core::convert::{TryFrom, TryInto};
 pub struct Rand {}
 impl Command for Rand {
     type Return = CommandComplete<RandReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
 
     fn opcode() -> Opcode {
         LEControllerOpcode::Rand.into()
@@ -62,3 +65,104 @@ impl ReturnParameters for RandReturn {
         })
     }
 }
+/// Length of an AES-128 key or block.
+pub const AES_BLOCK_LEN: usize = 16;
+/// `HCI_LE_Encrypt`. AES-128 encrypts `plaintext` with `key`. Both (and the returned
+/// ciphertext) are in HCI octet order, least significant octet first, which is the reverse of the
+/// FIPS-197 notation.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Encrypt {
+    pub key: [u8; AES_BLOCK_LEN],
+    pub plaintext: [u8; AES_BLOCK_LEN],
+}
+impl Encrypt {
+    pub const BYTE_LEN: usize = AES_BLOCK_LEN * 2;
+}
+impl Command for Encrypt {
+    type Return = CommandComplete<EncryptReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        LEControllerOpcode::Encrypt.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[..AES_BLOCK_LEN].copy_from_slice(&self.key[..]);
+        buf[AES_BLOCK_LEN..].copy_from_slice(&self.plaintext[..]);
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(Encrypt {
+            key: (&buf[..AES_BLOCK_LEN])
+                .try_into()
+                .expect("length checked above"),
+            plaintext: (&buf[AES_BLOCK_LEN..])
+                .try_into()
+                .expect("length checked above"),
+        })
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct EncryptReturn {
+    pub status: ErrorCode,
+    pub encrypted_data: [u8; AES_BLOCK_LEN],
+}
+impl ReturnParameters for EncryptReturn {
+    fn byte_len(&self) -> usize {
+        AES_BLOCK_LEN + 1
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(AES_BLOCK_LEN + 1, buf)?;
+        buf[0] = self.status.into();
+        buf[1..].copy_from_slice(&self.encrypted_data[..]);
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(AES_BLOCK_LEN + 1, buf)?;
+        Ok(EncryptReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            encrypted_data: (&buf[1..]).try_into().expect("length checked above"),
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hci::event::Event;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_encrypt() {
+        let encrypt = Encrypt {
+            key: [0x01; AES_BLOCK_LEN],
+            plaintext: [0x02; AES_BLOCK_LEN],
+        };
+        let packet = encrypt.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(packet.parameters.len(), Encrypt::BYTE_LEN);
+        assert_eq!(Encrypt::unpack_command_packet(&packet), Ok(encrypt));
+        let complete = CommandComplete {
+            num_command_packets: 1,
+            opcode: Encrypt::opcode(),
+            params: EncryptReturn {
+                status: ErrorCode::Ok,
+                encrypted_data: [0xAA; AES_BLOCK_LEN],
+            },
+        };
+        let event = complete.event_pack_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(Encrypt::unpack_return(event.as_ref()), Ok(Some(complete)));
+    }
+}
@@ -2,7 +2,13 @@
 use crate::bytes::Storage;
 use crate::hci::le::{MetaEvent, MetaEventCode};
 use crate::le::advertisement::{RawAdvertisement, StaticAdvBuffer, MAX_ADV_LEN};
-use crate::le::report::{AddressType, EventType, NumReports, ReportInfo};
+use crate::hci::event::StaticHCIBuffer;
+use crate::hci::le::advertise::TxPowerLevel;
+use crate::le::phy::Phy;
+use crate::le::report::{
+    AddressType, AdvertisingSID, EventType, ExtendedEventType, ExtendedReportInfo, NumReports,
+    ReportInfo, ReportOrigin,
+};
 use crate::{BTAddress, PackError, BT_ADDRESS_LEN, RSSI};
 use core::convert::TryFrom;
 
@@ -28,10 +34,11 @@ impl<T: AsRef<[ReportInfo<B>]>, B: AsRef<[u8]>> AdvertisingReport<T, B> {
         }
     }
     pub fn byte_len(&self) -> usize {
+        // num_reports (1) + each report with its data_len (1) byte.
         self.reports
             .as_ref()
             .iter()
-            .fold(0usize, |size, report| size + report.byte_len())
+            .fold(0usize, |size, report| size + report.byte_len() + 1)
             + 1
     }
 }
@@ -56,94 +63,228 @@ impl<T: Storage<ReportInfo<B>>, B: Storage<u8> + Default + Copy> MetaEvent
         .map_err(|_| PackError::bad_index(0))?;
         let reports_len = usize::from(u8::from(num_reports));
         let mut out = AdvertisingReport::new(T::with_size(reports_len));
-        let mut total_data_len = 0usize;
+        // Each report is packed one after another:
+        // event_type (1) | address_type (1) | address (6) | data_len (1) | data | rssi (1)
+        let mut index = 1;
         for i in 0..reports_len {
-            let event_type_index = i + 1;
-            let address_type_index = event_type_index + reports_len;
-            let address_index = address_type_index + reports_len;
-            let data_len_index = address_index + BT_ADDRESS_LEN * reports_len;
-            let data_index = data_len_index + total_data_len;
-            let event_type = match buf.get(event_type_index).map(|e| EventType::try_from(*e)) {
+            let event_type = match buf.get(index).map(|e| EventType::try_from(*e)) {
                 Some(Ok(t)) => t,
-                _ => return Err(PackError::bad_index(event_type_index)),
+                _ => return Err(PackError::bad_index(index)),
             };
-            let address_type = match buf
-                .get(address_type_index)
-                .map(|e| AddressType::try_from(*e))
-            {
+            let address_type = match buf.get(index + 1).map(|e| AddressType::try_from(*e)) {
                 Some(Ok(t)) => t,
-                _ => return Err(PackError::bad_index(address_type_index)),
+                _ => return Err(PackError::bad_index(index + 1)),
             };
-
-            let address =
-                BTAddress::unpack_from(&buf[address_index..address_index + BT_ADDRESS_LEN])?;
-            let data_len = buf
-                .get(data_len_index)
-                .map(|e| *e)
-                .ok_or(PackError::bad_index(data_len_index))?;
-            let data_index_end = data_index + 1 + usize::from(data_len);
-            if usize::from(data_len) > MAX_ADV_LEN {
+            let address_index = index + 2;
+            let data_len_index = address_index + BT_ADDRESS_LEN;
+            let data_len = usize::from(
+                *buf.get(data_len_index)
+                    .ok_or(PackError::bad_index(data_len_index))?,
+            );
+            if data_len > MAX_ADV_LEN {
                 return Err(PackError::bad_index(data_len_index));
             }
-            let data = &buf[data_index + 1..data_index_end];
-            PackError::expect_length(data_len.into(), data)?;
+            let data_index = data_len_index + 1;
+            let rssi_index = data_index + data_len;
+            let rssi = match buf.get(rssi_index).map(|val| RSSI::maybe_rssi(*val as i8)) {
+                Some(Ok(maybe_rssi)) => maybe_rssi,
+                _ => return Err(PackError::bad_index(rssi_index)),
+            };
             out.reports.as_mut()[i] = ReportInfo {
                 event_type,
                 address_type,
-                address,
-                data: RawAdvertisement(B::from_slice(data)),
-                rssi: None,
+                address: BTAddress::unpack_from(&buf[address_index..data_len_index])?,
+                data: RawAdvertisement(B::from_slice(&buf[data_index..rssi_index])),
+                rssi,
+                origin: ReportOrigin::LEGACY,
             };
-            total_data_len += usize::from(data_len);
-        }
-        for i in 0..reports_len {
-            let rssi_index = 1 + (1 + 1 + 1 + BT_ADDRESS_LEN) * reports_len + total_data_len + i;
-            out.reports.as_mut()[i].rssi =
-                match buf.get(rssi_index).map(|val| RSSI::maybe_rssi(*val as i8)) {
-                    Some(Ok(maybe_rssi)) => maybe_rssi,
-                    _ => return Err(PackError::bad_index(rssi_index)),
-                }
+            index = rssi_index + 1;
         }
         Ok(out)
     }
 
     fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
         let reports = self.reports.as_ref();
-        let reports_len = reports.len();
         let num_reports =
-            NumReports::try_from(reports_len).map_err(|_| PackError::InvalidFields)?;
+            NumReports::try_from(reports.len()).map_err(|_| PackError::InvalidFields)?;
         let full = self.byte_len();
         PackError::expect_length(full, buf)?;
-        let mut total_data_len = 0usize;
-        for i in 0..reports_len {
-            let report = &reports[i];
+        let mut index = 1;
+        for report in reports {
             let data = report.data.as_ref();
             let data_len = data.len();
             if data_len > MAX_ADV_LEN {
                 return Err(PackError::InvalidFields);
             }
-            let event_type_index = i + 1;
-            let address_type_index = event_type_index + reports_len;
-            let address_index = address_type_index + reports_len;
-            let data_len_index = address_index + BT_ADDRESS_LEN * reports_len;
-            let data_index = data_len_index + total_data_len;
-            let data_index_end = data_index + usize::from(data_len);
-            buf[event_type_index] = report.event_type.into();
-            buf[address_type_index] = report.address_type.into();
+            let address_index = index + 2;
+            let data_len_index = address_index + BT_ADDRESS_LEN;
+            let data_index = data_len_index + 1;
+            let rssi_index = data_index + data_len;
+            buf[index] = report.event_type.into();
+            buf[index + 1] = report.address_type.into();
             report
                 .address
-                .pack_into(&mut buf[address_type_index..address_type_index + BT_ADDRESS_LEN])?;
-            buf[data_index..data_index_end].copy_from_slice(data);
-            total_data_len += data_len;
+                .pack_into(&mut buf[address_index..data_len_index])?;
+            buf[data_len_index] = data_len as u8;
+            buf[data_index..rssi_index].copy_from_slice(data);
+            buf[rssi_index] = report.rssi.map(i8::from).unwrap_or(RSSI::UNSUPPORTED_RSSI) as u8;
+            index = rssi_index + 1;
         }
+        buf[0] = num_reports.into();
+        Ok(())
+    }
+}
+
+/// LE Extended Advertising Report event. Unlike the legacy [`AdvertisingReport`] each report is
+/// packed one after another. Long advertising data is split over several reports (and events),
+/// see [`crate::le::report::ExtendedReportAssembler`].
+#[derive(Copy, Clone, Debug)]
+pub struct ExtendedAdvertisingReport<
+    T: AsRef<[ExtendedReportInfo<B>]>,
+    B: AsRef<[u8]> = StaticHCIBuffer,
+> {
+    pub reports: T,
+    _marker: core::marker::PhantomData<B>,
+}
+impl<T: AsRef<[ExtendedReportInfo<B>]>, B: AsRef<[u8]>> ExtendedAdvertisingReport<T, B> {
+    pub const SUBEVENT_CODE: MetaEventCode = MetaEventCode::ExtendedAdvertisingReport;
+    pub const MAX_NUM_REPORTS: usize = 0x0A;
+    pub fn new(reports: T) -> Self {
+        Self {
+            reports,
+            _marker: core::marker::PhantomData,
+        }
+    }
+    pub fn byte_len(&self) -> usize {
+        self.reports
+            .as_ref()
+            .iter()
+            .fold(1usize, |size, report| size + report.byte_len())
+    }
+}
+impl<T: Storage<ExtendedReportInfo<B>>, B: Storage<u8> + Default + Copy> MetaEvent
+    for ExtendedAdvertisingReport<T, B>
+{
+    const META_CODE: MetaEventCode = Self::SUBEVENT_CODE;
+
+    fn meta_byte_len(&self) -> usize {
+        ExtendedAdvertisingReport::byte_len(self)
+    }
+
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        let reports_len = usize::from(*buf.get(0).ok_or(PackError::BadLength {
+            expected: 1,
+            got: 0,
+        })?);
+        if reports_len == 0 || reports_len > Self::MAX_NUM_REPORTS {
+            return Err(PackError::bad_index(0));
+        }
+        let header_len = ExtendedReportInfo::<B>::HEADER_LEN;
+        let mut out = Self::new(T::with_size(reports_len));
+        let mut index = 1;
         for i in 0..reports_len {
-            let rssi_index = 1 + (1 + 1 + 1 + BT_ADDRESS_LEN) * reports_len + total_data_len + i;
-            buf[rssi_index] = reports[i]
+            if buf.len() < index + header_len {
+                return Err(PackError::BadLength {
+                    expected: index + header_len,
+                    got: buf.len(),
+                });
+            }
+            let r = &buf[index..index + header_len];
+            let data_len = usize::from(r[header_len - 1]);
+            let end = index + header_len + data_len;
+            if buf.len() < end {
+                return Err(PackError::BadLength {
+                    expected: end,
+                    got: buf.len(),
+                });
+            }
+            if data_len > B::max_len() {
+                return Err(PackError::bad_index(index + header_len - 1));
+            }
+            let periodic_interval = u16::from_le_bytes([r[14], r[15]]);
+            out.reports.as_mut()[i] = ExtendedReportInfo {
+                event_type: ExtendedEventType::try_from(u16::from_le_bytes([r[0], r[1]]))
+                    .map_err(|_| PackError::bad_index(index))?,
+                address_type: match r[2] {
+                    0xFF => None,
+                    t => Some(
+                        AddressType::try_from(t).map_err(|_| PackError::bad_index(index + 2))?,
+                    ),
+                },
+                address: BTAddress::new(&r[3..9]),
+                primary_phy: Phy::try_from(r[9]).map_err(|_| PackError::bad_index(index + 9))?,
+                secondary_phy: Phy::maybe_phy(r[10])
+                    .map_err(|_| PackError::bad_index(index + 10))?,
+                sid: AdvertisingSID::maybe_sid(r[11])
+                    .map_err(|_| PackError::bad_index(index + 11))?,
+                tx_power: match r[12] {
+                    0x7F => None,
+                    p => Some(
+                        TxPowerLevel::try_from(p).map_err(|_| PackError::bad_index(index + 12))?,
+                    ),
+                },
+                rssi: RSSI::maybe_rssi(r[13] as i8)
+                    .map_err(|_| PackError::bad_index(index + 13))?,
+                periodic_advertising_interval: if periodic_interval == 0 {
+                    None
+                } else {
+                    Some(periodic_interval)
+                },
+                direct_address_type: r[16],
+                direct_address: BTAddress::new(&r[17..23]),
+                data: RawAdvertisement(B::from_slice(&buf[index + header_len..end])),
+            };
+            index = end;
+        }
+        if index != buf.len() {
+            return Err(PackError::BadLength {
+                expected: index,
+                got: buf.len(),
+            });
+        }
+        Ok(out)
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        let reports = self.reports.as_ref();
+        if reports.is_empty() || reports.len() > Self::MAX_NUM_REPORTS {
+            return Err(PackError::InvalidFields);
+        }
+        PackError::expect_length(self.byte_len(), buf)?;
+        buf[0] = reports.len() as u8;
+        let mut index = 1;
+        for report in reports {
+            let data = report.data.as_ref();
+            if data.len() > usize::from(u8::MAX) {
+                return Err(PackError::InvalidFields);
+            }
+            let r = &mut buf[index..index + report.byte_len()];
+            r[0..2].copy_from_slice(&u16::from(report.event_type).to_le_bytes());
+            r[2] = report.address_type.map(u8::from).unwrap_or(0xFF);
+            report.address.pack_into(&mut r[3..9])?;
+            r[9] = report.primary_phy.into();
+            r[10] = report.secondary_phy.map(u8::from).unwrap_or(0x00);
+            r[11] = report.sid.map(u8::from).unwrap_or(0xFF);
+            r[12] = report.tx_power.map(u8::from).unwrap_or(0x7F);
+            r[13] = report
                 .rssi
                 .map(i8::from)
                 .unwrap_or(RSSI::UNSUPPORTED_RSSI) as u8;
+            r[14..16].copy_from_slice(
+                &report
+                    .periodic_advertising_interval
+                    .unwrap_or(0)
+                    .to_le_bytes(),
+            );
+            r[16] = report.direct_address_type;
+            report.direct_address.pack_into(&mut r[17..23])?;
+            r[23] = data.len() as u8;
+            r[24..].copy_from_slice(data);
+            index += report.byte_len();
         }
-        buf[0] = num_reports.into();
         Ok(())
     }
 }
@@ -175,3 +316,86 @@ impl<Buf: AsRef<[ReportInfo<ReportBuf>]>, ReportBuf: AsRef<[u8]> + Clone> Iterat
         Some(report.clone())
     }
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    #[test]
+    fn test_multiple_reports() {
+        let report = |i: u8, data: &[u8], rssi| ReportInfo {
+            event_type: EventType::AdvInd,
+            address_type: AddressType::RandomDevice,
+            address: BTAddress([i, 2, 3, 4, 5, 6]),
+            data: RawAdvertisement(StaticAdvBuffer::from_slice(data)),
+            rssi,
+            origin: ReportOrigin::LEGACY,
+        };
+        let reports: AdvertisingReport<Vec<ReportInfo>> = AdvertisingReport::new(alloc::vec![
+            report(1, &[0x02, 0x01, 0x06], Some(RSSI::new(-40))),
+            report(2, &[], None),
+            report(3, &[0x03, 0xFF, 0x59, 0x00], Some(RSSI::new(-90))),
+        ]);
+        let mut buf = alloc::vec![0_u8; reports.meta_byte_len()];
+        reports.meta_pack_into(&mut buf).unwrap();
+        // Each report is packed in full before the next one.
+        assert_eq!(&buf[1..10], &[0x00, 0x01, 1, 2, 3, 4, 5, 6, 3]);
+        assert_eq!(&buf[10..14], &[0x02, 0x01, 0x06, -40_i8 as u8]);
+        assert_eq!(&buf[14..24], &[0x00, 0x01, 2, 2, 3, 4, 5, 6, 0, 0x7F]);
+        let unpacked: AdvertisingReport<Vec<ReportInfo>> =
+            AdvertisingReport::meta_unpack_from(&buf).unwrap();
+        for (a, b) in unpacked.reports.iter().zip(reports.reports.iter()) {
+            assert_eq!(a.address, b.address);
+            assert_eq!(a.data.as_ref(), b.data.as_ref());
+            assert_eq!(a.rssi, b.rssi);
+        }
+        assert!(AdvertisingReport::<Vec<ReportInfo>>::meta_unpack_from(&buf[..buf.len() - 1])
+            .is_err());
+    }
+    #[test]
+    fn test_extended_fragments() {
+        use crate::le::report::{DataStatus, ExtendedReportAssembler};
+        use core::time::Duration;
+        type Report = ExtendedAdvertisingReport<Vec<ExtendedReportInfo<StaticHCIBuffer>>>;
+        let fragment = |status, data: &[u8]| ExtendedReportInfo {
+            event_type: ExtendedEventType::new(ExtendedEventType::CONNECTABLE)
+                .with_data_status(status),
+            address_type: Some(AddressType::RandomDevice),
+            address: BTAddress([1, 2, 3, 4, 5, 0xC6]),
+            primary_phy: Phy::LECoded,
+            secondary_phy: Some(Phy::LE2M),
+            sid: Some(AdvertisingSID::new(3)),
+            rssi: Some(RSSI::new(-70)),
+            data: RawAdvertisement(StaticHCIBuffer::from_slice(data)),
+            ..ExtendedReportInfo::default()
+        };
+        let first = [0xAA; 200];
+        let events = [
+            Report::new(alloc::vec![fragment(DataStatus::Incomplete, &first)]),
+            Report::new(alloc::vec![fragment(DataStatus::Complete, &[0xBB; 50])]),
+        ];
+        let mut assembler = ExtendedReportAssembler::default();
+        let mut out = Vec::new();
+        for (i, event) in events.iter().enumerate() {
+            let mut buf = alloc::vec![0_u8; event.meta_byte_len()];
+            event.meta_pack_into(&mut buf).unwrap();
+            let unpacked = Report::meta_unpack_from(&buf).unwrap();
+            assert_eq!(unpacked.reports[0].sid, Some(AdvertisingSID::new(3)));
+            assert!(Report::meta_unpack_from(&buf[..buf.len() - 1]).is_err());
+            let now = Duration::from_millis(i as u64);
+            out.extend(assembler.push(&unpacked.reports[0], now));
+        }
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].data.as_ref().len(), 250);
+        assert_eq!(out[0].event_type.data_status(), DataStatus::Complete);
+        assert_eq!(out[0].origin().data_phy(), Phy::LE2M);
+        // A fragment that never finishes is abandoned after the timeout.
+        assert!(assembler
+            .push(&fragment(DataStatus::Incomplete, &first), Duration::from_secs(1))
+            .is_none());
+        assert!(assembler.expire(Duration::from_millis(1200)).is_empty());
+        let expired = assembler.expire(Duration::from_secs(2));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].event_type.data_status(), DataStatus::Truncated);
+        assert_eq!(assembler.pending_len(), 0);
+    }
+}
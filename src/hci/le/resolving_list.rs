@@ -0,0 +1,477 @@
+//! LE Resolving List and controller address resolution (Core 4.2+). With the IRKs of bonded peers
+//! in the Resolving List the controller resolves their resolvable private addresses (RPAs) to
+//! identity addresses itself and generates our own RPAs. [`SetPrivacyMode`] picks how strict
+//! it is about each peer.
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, ReturnParameters, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::LEControllerOpcode;
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::advertiser::PeerAddressType;
+use crate::le::smp::keys::IRK;
+use crate::{BTAddress, ConversionError, DeviceAddress, PackError, BT_ADDRESS_LEN};
+use core::convert::TryFrom;
+use core::time::Duration;
+
+/// Identity address of a peer in the Resolving List.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PeerIdentity {
+    /// Public or Random (static) Identity address.
+    pub address_type: PeerAddressType,
+    pub address: BTAddress,
+}
+impl PeerIdentity {
+    pub const BYTE_LEN: usize = 1 + BT_ADDRESS_LEN;
+    pub fn device_address(&self) -> DeviceAddress {
+        match self.address_type {
+            PeerAddressType::Public => DeviceAddress::public(self.address),
+            PeerAddressType::Random => DeviceAddress::random(self.address),
+        }
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.address_type.into();
+        self.address.pack_into(&mut buf[1..])
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<PeerIdentity, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(PeerIdentity {
+            address_type: PeerAddressType::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            address: BTAddress::unpack_from(&buf[1..])?,
+        })
+    }
+}
+impl From<DeviceAddress> for PeerIdentity {
+    fn from(address: DeviceAddress) -> Self {
+        PeerIdentity {
+            address_type: address.kind.into(),
+            address: address.addr,
+        }
+    }
+}
+/// One peer in the Resolving List.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct ResolvingListEntry {
+    pub peer: PeerIdentity,
+    /// IRK the peer distributed. Its RPAs are resolved with it.
+    pub peer_irk: IRK,
+    /// IRK we distributed to the peer. Our RPAs for the peer are generated with it.
+    /// [`IRK::ZEROED`] uses our identity address instead.
+    pub local_irk: IRK,
+}
+impl ResolvingListEntry {
+    pub const BYTE_LEN: usize = PeerIdentity::BYTE_LEN + IRK::BYTE_LEN * 2;
+    pub fn new(peer: DeviceAddress, peer_irk: IRK, local_irk: IRK) -> ResolvingListEntry {
+        ResolvingListEntry {
+            peer: peer.into(),
+            peer_irk,
+            local_irk,
+        }
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        self.peer.pack_into(&mut buf[..PeerIdentity::BYTE_LEN])?;
+        buf[7..23].copy_from_slice(&self.peer_irk.0);
+        buf[23..39].copy_from_slice(&self.local_irk.0);
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<ResolvingListEntry, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let mut peer_irk = IRK::ZEROED;
+        peer_irk.0.copy_from_slice(&buf[7..23]);
+        let mut local_irk = IRK::ZEROED;
+        local_irk.0.copy_from_slice(&buf[23..39]);
+        Ok(ResolvingListEntry {
+            peer: PeerIdentity::unpack_from(&buf[..PeerIdentity::BYTE_LEN])?,
+            peer_irk,
+            local_irk,
+        })
+    }
+}
+/// `HCI_LE_Add_Device_To_Resolving_List`. Can't be used while address resolution is enabled
+/// and advertising, scanning or connecting.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct AddDeviceToResolvingList(pub ResolvingListEntry);
+impl AddDeviceToResolvingList {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::AddDeviceToResolvingList;
+}
+impl Command for AddDeviceToResolvingList {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ResolvingListEntry::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.0.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(AddDeviceToResolvingList(ResolvingListEntry::unpack_from(
+            buf,
+        )?))
+    }
+}
+/// `HCI_LE_Remove_Device_From_Resolving_List`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct RemoveDeviceFromResolvingList(pub PeerIdentity);
+impl RemoveDeviceFromResolvingList {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::RemoveDeviceFromResolvingList;
+}
+impl Command for RemoveDeviceFromResolvingList {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        PeerIdentity::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.0.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(RemoveDeviceFromResolvingList(PeerIdentity::unpack_from(
+            buf,
+        )?))
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ClearResolvingList {}
+impl ClearResolvingList {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ClearResolvingList;
+}
+impl Command for ClearResolvingList {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ClearResolvingList {})
+    }
+}
+/// Read the total number of Resolving List entries the controller can hold.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadResolvingListSize {}
+impl ReadResolvingListSize {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReadResolvingListSize;
+}
+impl Command for ReadResolvingListSize {
+    type Return = CommandComplete<ResolvingListSizeReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadResolvingListSize {})
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ResolvingListSizeReturn {
+    pub status: ErrorCode,
+    pub size: u8,
+}
+impl ResolvingListSizeReturn {
+    pub const BYTE_LEN: usize = 2;
+}
+impl ReturnParameters for ResolvingListSizeReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1] = self.size;
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(ResolvingListSizeReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            size: buf[1],
+        })
+    }
+}
+/// `HCI_LE_Set_Address_Resolution_Enable`. Resolve peer RPAs with the Resolving List (and
+/// generate our own). Off after a reset.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct SetAddressResolutionEnable {
+    pub is_enabled: bool,
+}
+impl SetAddressResolutionEnable {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetAddressResolutionEnable;
+    pub const BYTE_LEN: usize = 1;
+}
+impl Command for SetAddressResolutionEnable {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.is_enabled.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        match buf[0] {
+            0 => Ok(SetAddressResolutionEnable { is_enabled: false }),
+            1 => Ok(SetAddressResolutionEnable { is_enabled: true }),
+            _ => Err(PackError::bad_index(0)),
+        }
+    }
+}
+/// How long the controller uses a RPA before generating a new one, in seconds.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct RPATimeout(u16);
+impl RPATimeout {
+    pub const BYTE_LEN: usize = 2;
+    pub const MIN_U16: u16 = 0x0001;
+    pub const MIN: RPATimeout = RPATimeout(Self::MIN_U16);
+    /// 11.5 hours.
+    pub const MAX_U16: u16 = 0xA1B8;
+    pub const MAX: RPATimeout = RPATimeout(Self::MAX_U16);
+    /// 15 minutes.
+    pub const DEFAULT: RPATimeout = RPATimeout(900);
+    pub fn new(seconds: u16) -> Self {
+        match Self::new_checked(seconds) {
+            Some(t) => t,
+            None => panic!("RPA timeout out of range (`{}`)", seconds),
+        }
+    }
+    pub fn new_checked(seconds: u16) -> Option<Self> {
+        if (Self::MIN_U16..=Self::MAX_U16).contains(&seconds) {
+            Some(RPATimeout(seconds))
+        } else {
+            None
+        }
+    }
+    pub fn as_duration(self) -> Duration {
+        Duration::from_secs(self.0.into())
+    }
+}
+impl Default for RPATimeout {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+impl From<RPATimeout> for u16 {
+    fn from(t: RPATimeout) -> Self {
+        t.0
+    }
+}
+/// `HCI_LE_Set_Resolvable_Private_Address_Timeout`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct SetResolvablePrivateAddressTimeout {
+    pub timeout: RPATimeout,
+}
+impl SetResolvablePrivateAddressTimeout {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetResolvablePrivateAddressTimeout;
+}
+impl Command for SetResolvablePrivateAddressTimeout {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_2;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        RPATimeout::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(RPATimeout::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&u16::from(self.timeout).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(RPATimeout::BYTE_LEN, buf)?;
+        Ok(SetResolvablePrivateAddressTimeout {
+            timeout: RPATimeout::new_checked(u16::from_le_bytes([buf[0], buf[1]]))
+                .ok_or_else(|| PackError::bad_index(0))?,
+        })
+    }
+}
+/// How strictly the controller treats a peer in the Resolving List.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum PrivacyMode {
+    /// Only accept the peer's RPAs, not its identity address.
+    Network = 0x00,
+    /// Also accept the peer's identity address (for peers that don't use RPAs while
+    /// advertising).
+    Device = 0x01,
+}
+impl PrivacyMode {
+    pub const DEFAULT: PrivacyMode = PrivacyMode::Network;
+}
+impl Default for PrivacyMode {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+impl From<PrivacyMode> for u8 {
+    fn from(m: PrivacyMode) -> Self {
+        m as u8
+    }
+}
+impl TryFrom<u8> for PrivacyMode {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(PrivacyMode::Network),
+            0x01 => Ok(PrivacyMode::Device),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// `HCI_LE_Set_Privacy_Mode`. `peer` has to be in the Resolving List already. Has the same
+/// restrictions as changing the Resolving List.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetPrivacyMode {
+    pub peer: PeerIdentity,
+    pub mode: PrivacyMode,
+}
+impl SetPrivacyMode {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::SetPrivacyMode;
+    pub const BYTE_LEN: usize = PeerIdentity::BYTE_LEN + 1;
+}
+impl Command for SetPrivacyMode {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        self.peer.pack_into(&mut buf[..PeerIdentity::BYTE_LEN])?;
+        buf[PeerIdentity::BYTE_LEN] = self.mode.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(SetPrivacyMode {
+            peer: PeerIdentity::unpack_from(&buf[..PeerIdentity::BYTE_LEN])?,
+            mode: PrivacyMode::try_from(buf[PeerIdentity::BYTE_LEN])
+                .map_err(|_| PackError::bad_index(PeerIdentity::BYTE_LEN))?,
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_resolving_list() {
+        let entry = ResolvingListEntry::new(
+            DeviceAddress::public(BTAddress([1, 2, 3, 4, 5, 6])),
+            IRK([0x11; 16]),
+            IRK::ZEROED,
+        );
+        let packet = AddDeviceToResolvingList(entry)
+            .pack_command_packet::<Box<[u8]>>()
+            .unwrap();
+        assert_eq!(packet.parameters.len(), ResolvingListEntry::BYTE_LEN);
+        assert_eq!(
+            &packet.parameters.as_ref()[..8],
+            &[0, 1, 2, 3, 4, 5, 6, 0x11]
+        );
+        assert_eq!(
+            AddDeviceToResolvingList::unpack_command_packet(&packet),
+            Ok(AddDeviceToResolvingList(entry))
+        );
+
+        let packet = SetResolvablePrivateAddressTimeout::default()
+            .pack_command_packet::<Box<[u8]>>()
+            .unwrap();
+        assert_eq!(packet.parameters.as_ref(), &[0x84, 0x03]);
+        assert_eq!(RPATimeout::new_checked(0), None);
+        assert_eq!(RPATimeout::new_checked(0xA1B9), None);
+
+        let privacy_mode = SetPrivacyMode {
+            peer: entry.peer,
+            mode: PrivacyMode::Device,
+        };
+        let packet = privacy_mode.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(packet.parameters.as_ref(), &[0, 1, 2, 3, 4, 5, 6, 1]);
+        assert_eq!(
+            SetPrivacyMode::unpack_command_packet(&packet),
+            Ok(privacy_mode)
+        );
+    }
+}
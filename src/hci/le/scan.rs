@@ -1,10 +1,13 @@
 //! LE [`SetScanEnable`], [`SetScanParameters`], and other primitive scan types.
 use crate::bytes::ToFromBytesEndian;
 use crate::hci::command::Command;
+use crate::hci::informational::CoreVersion;
 use crate::hci::event::{CommandComplete, StatusReturn};
 use crate::hci::le::LEControllerOpcode;
 use crate::hci::Opcode;
-use crate::le::scan::ScanParameters;
+use crate::le::scan::{
+    OwnAddressType, ScanInterval, ScanParameters, ScanType, ScanWindow, ScanningFilterPolicy,
+};
 use crate::PackError;
 use core::convert::{TryFrom, TryInto};
 
@@ -18,6 +21,7 @@ impl SetScanEnable {
 }
 impl Command for SetScanEnable {
     type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
 
     fn opcode() -> Opcode {
         LEControllerOpcode::SetScanEnable.into()
@@ -67,6 +71,7 @@ impl SetScanParameters {
 pub const SET_SCAN_PARAMETERS_LEN: usize = 7;
 impl Command for SetScanParameters {
     type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
 
     fn opcode() -> Opcode {
         LEControllerOpcode::SetScanParameters.into()
@@ -93,11 +98,29 @@ impl Command for SetScanParameters {
         Ok(())
     }
 
-    fn unpack_from(_buf: &[u8]) -> Result<Self, PackError>
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
     where
         Self: Sized,
     {
-        unimplemented!()
+        PackError::expect_length(SET_SCAN_PARAMETERS_LEN, buf)?;
+        let scan_interval =
+            ScanInterval::new_checked(u16::from_bytes_le(&buf[1..3]).expect("length checked above"))
+                .ok_or(PackError::bad_index(1))?;
+        let scan_window =
+            ScanWindow::new_checked(u16::from_bytes_le(&buf[3..5]).expect("length checked above"))
+                .ok_or(PackError::bad_index(3))?;
+        if u16::from(scan_window) > u16::from(scan_interval) {
+            return Err(PackError::bad_index(3));
+        }
+        Ok(SetScanParameters(ScanParameters {
+            scan_type: ScanType::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            scan_interval,
+            scan_window,
+            own_address_type: OwnAddressType::try_from(buf[5])
+                .map_err(|_| PackError::bad_index(5))?,
+            scanning_filter_policy: ScanningFilterPolicy::try_from(buf[6])
+                .map_err(|_| PackError::bad_index(6))?,
+        }))
     }
 }
 pub const MAX_RESPONSE_DATA_LEN: usize = 31;
@@ -150,6 +173,7 @@ impl AsMut<[u8]> for SetScanResponseData {
 }
 impl Command for SetScanResponseData {
     type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
 
     fn opcode() -> Opcode {
         Self::OPCODE.into()
@@ -172,6 +196,9 @@ impl Command for SetScanResponseData {
     {
         PackError::expect_length(Self::BYTE_LEN, buf)?;
         let len = buf[0];
+        if usize::from(len) > MAX_RESPONSE_DATA_LEN {
+            return Err(PackError::bad_index(0));
+        }
         let mut data = [0_u8; MAX_RESPONSE_DATA_LEN];
         data.copy_from_slice(&buf[1..]);
         Ok(SetScanResponseData { len, data })
@@ -0,0 +1,369 @@
+//! LE Periodic Advertising Sync Transfer (PAST). Hands the sync info of a periodic advertising
+//! train to a connected peer so it can sync to the train without scanning for it (and receives it
+//! from a peer the same way).
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, ReturnParameters};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::connection::or_min;
+use crate::hci::le::periodic_advertise::PeriodicAdvertisingInterval;
+use crate::hci::le::periodic_sync::SyncHandle;
+use crate::hci::le::{LEControllerOpcode, MetaEvent, MetaEventCode};
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::connection::{ConnectionHandle, MasterClockAccuracy};
+use crate::le::phy::Phy;
+use crate::le::report::{AddressType, AdvertisingSID};
+use crate::{BTAddress, ConversionError, DeviceAddress, PackError};
+use core::convert::TryFrom;
+
+/// `HCI_LE_Periodic_Advertising_Sync_Transfer`. Send the sync info of `sync_handle` to the peer
+/// of `connection_handle`. `service_data` is passed to the peer's host as is (in its
+/// [`PeriodicAdvertisingSyncTransferReceived`]).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PeriodicAdvertisingSyncTransfer {
+    pub connection_handle: ConnectionHandle,
+    pub service_data: u16,
+    pub sync_handle: SyncHandle,
+}
+impl PeriodicAdvertisingSyncTransfer {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::PeriodicAdvertisingSyncTransfer;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + 2 + SyncHandle::BYTE_LEN;
+}
+impl Command for PeriodicAdvertisingSyncTransfer {
+    type Return = CommandComplete<SyncTransferReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_1;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2..4].copy_from_slice(&self.service_data.to_le_bytes());
+        buf[4..6].copy_from_slice(&self.sync_handle.0.to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(PeriodicAdvertisingSyncTransfer {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            service_data: u16::from_le_bytes([buf[2], buf[3]]),
+            sync_handle: SyncHandle::new_checked(u16::from_le_bytes([buf[4], buf[5]]))
+                .ok_or_else(|| PackError::bad_index(4))?,
+        })
+    }
+}
+/// What the controller does with sync info received from a peer.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum SyncTransferMode {
+    /// Ignore it (no [`PeriodicAdvertisingSyncTransferReceived`] either).
+    NoSync = 0x00,
+    /// Sync to the train without sending its reports.
+    ReportsDisabled = 0x01,
+    /// Sync to the train and send its reports.
+    ReportsEnabled = 0x02,
+    /// Like `ReportsEnabled` but drop duplicate reports (Core 5.3+).
+    ReportsEnabledFilterDuplicates = 0x03,
+}
+impl SyncTransferMode {
+    pub const DEFAULT: SyncTransferMode = SyncTransferMode::NoSync;
+}
+impl Default for SyncTransferMode {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+impl From<SyncTransferMode> for u8 {
+    fn from(m: SyncTransferMode) -> Self {
+        m as u8
+    }
+}
+impl TryFrom<u8> for SyncTransferMode {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(SyncTransferMode::NoSync),
+            0x01 => Ok(SyncTransferMode::ReportsDisabled),
+            0x02 => Ok(SyncTransferMode::ReportsEnabled),
+            0x03 => Ok(SyncTransferMode::ReportsEnabledFilterDuplicates),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// `HCI_LE_Set_Periodic_Advertising_Sync_Transfer_Parameters`. How to handle sync info received
+/// from the peer of `connection_handle`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SetPeriodicAdvertisingSyncTransferParameters {
+    pub connection_handle: ConnectionHandle,
+    pub mode: SyncTransferMode,
+    /// Number of periodic advertising events that can be skipped after a successful receive
+    /// (`0x0000` to `0x01F3`).
+    pub skip: u16,
+    /// Sync timeout in units of 10ms (`0x000A` to `0x4000`).
+    pub sync_timeout: u16,
+    /// Bit mask of the Constant Tone Extension types not to sync to. `0` syncs to any.
+    pub cte_type: u8,
+}
+impl SetPeriodicAdvertisingSyncTransferParameters {
+    pub const OPCODE: LEControllerOpcode =
+        LEControllerOpcode::SetPeriodicAdvertisingSyncTransferParameters;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + 1 + 2 + 2 + 1;
+    pub const MAX_SKIP: u16 = 0x01F3;
+    pub const MIN_SYNC_TIMEOUT: u16 = 0x000A;
+    pub const MAX_SYNC_TIMEOUT: u16 = 0x4000;
+    /// Accept sync info from the peer of `connection_handle` in `mode` with no skipping.
+    pub fn new(
+        connection_handle: ConnectionHandle,
+        mode: SyncTransferMode,
+        sync_timeout: u16,
+    ) -> Self {
+        SetPeriodicAdvertisingSyncTransferParameters {
+            connection_handle,
+            mode,
+            skip: 0,
+            sync_timeout,
+            cte_type: 0,
+        }
+    }
+    pub fn is_valid(&self) -> bool {
+        self.skip <= Self::MAX_SKIP
+            && (Self::MIN_SYNC_TIMEOUT..=Self::MAX_SYNC_TIMEOUT).contains(&self.sync_timeout)
+    }
+}
+impl Command for SetPeriodicAdvertisingSyncTransferParameters {
+    type Return = CommandComplete<SyncTransferReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_1;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        if !self.is_valid() {
+            return Err(PackError::InvalidFields);
+        }
+        buf[0..2].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[2] = self.mode.into();
+        buf[3..5].copy_from_slice(&self.skip.to_le_bytes());
+        buf[5..7].copy_from_slice(&self.sync_timeout.to_le_bytes());
+        buf[7] = self.cte_type;
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let out = SetPeriodicAdvertisingSyncTransferParameters {
+            connection_handle: ConnectionHandle::unpack_from(buf, 0)?,
+            mode: SyncTransferMode::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+            skip: u16::from_le_bytes([buf[3], buf[4]]),
+            sync_timeout: u16::from_le_bytes([buf[5], buf[6]]),
+            cte_type: buf[7],
+        };
+        if out.is_valid() {
+            Ok(out)
+        } else {
+            Err(PackError::InvalidFields)
+        }
+    }
+}
+/// Status and connection handle returned by the PAST commands.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SyncTransferReturn {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+}
+impl SyncTransferReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN;
+}
+impl ReturnParameters for SyncTransferReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(SyncTransferReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            connection_handle: ConnectionHandle::unpack_from(buf, 1)?,
+        })
+    }
+}
+/// `HCI_LE_Periodic_Advertising_Sync_Transfer_Received` event. Sync info received from the peer
+/// of `connection_handle` and the result of syncing to it. Like
+/// [`crate::hci::le::periodic_sync::PeriodicAdvertisingSyncEstablished`] but the train was found
+/// through the peer instead of scanning.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PeriodicAdvertisingSyncTransferReceived {
+    pub status: ErrorCode,
+    pub connection_handle: ConnectionHandle,
+    pub service_data: u16,
+    pub sync_handle: SyncHandle,
+    pub sid: AdvertisingSID,
+    pub advertiser_address_type: AddressType,
+    pub advertiser_address: BTAddress,
+    pub advertiser_phy: Phy,
+    pub periodic_advertising_interval: PeriodicAdvertisingInterval,
+    pub advertiser_clock_accuracy: MasterClockAccuracy,
+}
+impl PeriodicAdvertisingSyncTransferReceived {
+    pub const CODE: MetaEventCode = MetaEventCode::PeriodicAdvertisingSyncTransferReceived;
+    pub const BYTE_LEN: usize = 19;
+    pub fn advertiser(&self) -> DeviceAddress {
+        self.advertiser_address_type
+            .device_address(self.advertiser_address)
+    }
+}
+impl MetaEvent for PeriodicAdvertisingSyncTransferReceived {
+    const META_CODE: MetaEventCode = Self::CODE;
+
+    fn meta_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    /// Invalid fields of a failed sync are replaced by the minimum. The connection handle is
+    /// always checked.
+    fn meta_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let status = ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?;
+        let is_ok = status == ErrorCode::Ok;
+        Ok(PeriodicAdvertisingSyncTransferReceived {
+            status,
+            connection_handle: ConnectionHandle::unpack_from(buf, 1)?,
+            service_data: u16::from_le_bytes([buf[3], buf[4]]),
+            sync_handle: or_min(
+                SyncHandle::new_checked(u16::from_le_bytes([buf[5], buf[6]])),
+                SyncHandle::MIN,
+                is_ok,
+                5,
+            )?,
+            sid: or_min(
+                AdvertisingSID::new_checked(buf[7]),
+                AdvertisingSID::default(),
+                is_ok,
+                7,
+            )?,
+            advertiser_address_type: or_min(
+                AddressType::try_from(buf[8]).ok(),
+                AddressType::PublicDevice,
+                is_ok,
+                8,
+            )?,
+            advertiser_address: BTAddress::new(&buf[9..15]),
+            advertiser_phy: or_min(Phy::try_from(buf[15]).ok(), Phy::LE1M, is_ok, 15)?,
+            periodic_advertising_interval: or_min(
+                PeriodicAdvertisingInterval::new_checked(u16::from_le_bytes([buf[16], buf[17]])),
+                PeriodicAdvertisingInterval::MIN,
+                is_ok,
+                16,
+            )?,
+            advertiser_clock_accuracy: or_min(
+                MasterClockAccuracy::try_from(buf[18]).ok(),
+                MasterClockAccuracy::PPM500,
+                is_ok,
+                18,
+            )?,
+        })
+    }
+
+    fn meta_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.connection_handle).to_le_bytes());
+        buf[3..5].copy_from_slice(&self.service_data.to_le_bytes());
+        buf[5..7].copy_from_slice(&self.sync_handle.0.to_le_bytes());
+        buf[7] = self.sid.into();
+        buf[8] = self.advertiser_address_type.into();
+        self.advertiser_address.pack_into(&mut buf[9..15])?;
+        buf[15] = self.advertiser_phy.into();
+        buf[16..18].copy_from_slice(&u16::from(self.periodic_advertising_interval).to_le_bytes());
+        buf[18] = self.advertiser_clock_accuracy.into();
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_sync_transfer() {
+        let transfer = PeriodicAdvertisingSyncTransfer {
+            connection_handle: ConnectionHandle::new(0x0040),
+            service_data: 0xABCD,
+            sync_handle: SyncHandle(0x0002),
+        };
+        let packet = transfer.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(
+            packet.parameters.as_ref(),
+            &[0x40, 0x00, 0xCD, 0xAB, 0x02, 0x00]
+        );
+        let parameters = SetPeriodicAdvertisingSyncTransferParameters::new(
+            ConnectionHandle::new(0x0040),
+            SyncTransferMode::ReportsEnabled,
+            0x0200,
+        );
+        let packet = parameters.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(
+            SetPeriodicAdvertisingSyncTransferParameters::unpack_command_packet(&packet),
+            Ok(parameters)
+        );
+        assert_eq!(
+            SetPeriodicAdvertisingSyncTransferParameters {
+                skip: 0x01F4,
+                ..parameters
+            }
+            .pack_command_packet::<Box<[u8]>>()
+            .err(),
+            Some(PackError::InvalidFields)
+        );
+
+        let received = PeriodicAdvertisingSyncTransferReceived {
+            status: ErrorCode::Ok,
+            connection_handle: ConnectionHandle::new(0x0040),
+            service_data: 0xABCD,
+            sync_handle: SyncHandle(0x0003),
+            sid: AdvertisingSID::new(1),
+            advertiser_address_type: AddressType::PublicDevice,
+            advertiser_address: BTAddress([1, 2, 3, 4, 5, 6]),
+            advertiser_phy: Phy::LECoded,
+            periodic_advertising_interval: PeriodicAdvertisingInterval::MIN,
+            advertiser_clock_accuracy: MasterClockAccuracy::PPM20,
+        };
+        let mut buf = [0_u8; PeriodicAdvertisingSyncTransferReceived::BYTE_LEN];
+        received.meta_pack_into(&mut buf).unwrap();
+        assert_eq!(&buf[..7], &[0x00, 0x40, 0x00, 0xCD, 0xAB, 0x03, 0x00]);
+        assert_eq!(
+            PeriodicAdvertisingSyncTransferReceived::meta_unpack_from(&buf),
+            Ok(received)
+        );
+    }
+}
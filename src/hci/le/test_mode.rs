@@ -0,0 +1,536 @@
+//! LE Direct Test Mode (DTM) for RF certification and factory testing. The controller either
+//! transmits test packets on one channel ([`TransmitterTest`] and the V2/V3 variants) or counts
+//! the test packets it receives ([`ReceiverTest`], etc) until [`TestEnd`], which returns the
+//! number of packets received.
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, ReturnParameters, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::LEControllerOpcode;
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::phy::Phy;
+use crate::{ConversionError, PackError};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// RF channel `0..=39` (frequency `2402 + 2 * channel` MHz). Not the link layer channel index.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct TestChannel(u8);
+impl TestChannel {
+    pub const BYTE_LEN: usize = 1;
+    pub const MAX_U8: u8 = 39;
+    pub fn new(channel: u8) -> TestChannel {
+        match Self::new_checked(channel) {
+            Some(c) => c,
+            None => panic!("test channel out of range (`{}`)", channel),
+        }
+    }
+    pub fn new_checked(channel: u8) -> Option<TestChannel> {
+        if channel <= Self::MAX_U8 {
+            Some(TestChannel(channel))
+        } else {
+            None
+        }
+    }
+    pub fn frequency_mhz(self) -> u16 {
+        2402 + 2 * u16::from(self.0)
+    }
+}
+impl From<TestChannel> for u8 {
+    fn from(channel: TestChannel) -> Self {
+        channel.0
+    }
+}
+fn unpack_channel(buf: &[u8], index: usize) -> Result<TestChannel, PackError> {
+    TestChannel::new_checked(buf[index]).ok_or(PackError::bad_index(index))
+}
+/// Bit pattern of the test packet payload.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum TestPayload {
+    PRBS9 = 0x00,
+    Repeated11110000 = 0x01,
+    Repeated10101010 = 0x02,
+    PRBS15 = 0x03,
+    AllOnes = 0x04,
+    AllZeros = 0x05,
+    Repeated00001111 = 0x06,
+    Repeated01010101 = 0x07,
+}
+impl From<TestPayload> for u8 {
+    fn from(p: TestPayload) -> Self {
+        p as u8
+    }
+}
+impl TryFrom<u8> for TestPayload {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(TestPayload::PRBS9),
+            0x01 => Ok(TestPayload::Repeated11110000),
+            0x02 => Ok(TestPayload::Repeated10101010),
+            0x03 => Ok(TestPayload::PRBS15),
+            0x04 => Ok(TestPayload::AllOnes),
+            0x05 => Ok(TestPayload::AllZeros),
+            0x06 => Ok(TestPayload::Repeated00001111),
+            0x07 => Ok(TestPayload::Repeated01010101),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// PHY of [`TransmitterTestV2`]. The Coded PHY needs the coding picked.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum TransmitterPhy {
+    LE1M = 0x01,
+    LE2M = 0x02,
+    LECodedS8 = 0x03,
+    LECodedS2 = 0x04,
+}
+impl From<TransmitterPhy> for u8 {
+    fn from(p: TransmitterPhy) -> Self {
+        p as u8
+    }
+}
+impl TryFrom<u8> for TransmitterPhy {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(TransmitterPhy::LE1M),
+            0x02 => Ok(TransmitterPhy::LE2M),
+            0x03 => Ok(TransmitterPhy::LECodedS8),
+            0x04 => Ok(TransmitterPhy::LECodedS2),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// Modulation index the receiver should assume.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum ModulationIndex {
+    Standard = 0x00,
+    Stable = 0x01,
+}
+impl From<ModulationIndex> for u8 {
+    fn from(m: ModulationIndex) -> Self {
+        m as u8
+    }
+}
+impl TryFrom<u8> for ModulationIndex {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(ModulationIndex::Standard),
+            0x01 => Ok(ModulationIndex::Stable),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// Most antenna IDs in a V3 command's switching pattern.
+pub const MAX_SWITCHING_PATTERN_LEN: usize = 0x4B;
+fn pack_antenna_ids(antenna_ids: &[u8], buf: &mut [u8]) -> Result<(), PackError> {
+    if antenna_ids.len() > MAX_SWITCHING_PATTERN_LEN {
+        return Err(PackError::InvalidFields);
+    }
+    buf[0] = antenna_ids.len() as u8;
+    buf[1..].copy_from_slice(antenna_ids);
+    Ok(())
+}
+fn unpack_antenna_ids(buf: &[u8], index: usize) -> Result<Vec<u8>, PackError> {
+    let len = usize::from(buf[index]);
+    if len > MAX_SWITCHING_PATTERN_LEN {
+        return Err(PackError::bad_index(index));
+    }
+    PackError::expect_length(index + 1 + len, buf)?;
+    Ok(buf[index + 1..].to_vec())
+}
+/// `HCI_LE_Receiver_Test` (V1). Receive on the LE 1M PHY.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReceiverTest {
+    pub rx_channel: TestChannel,
+}
+impl ReceiverTest {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReceiverTest;
+}
+impl Command for ReceiverTest {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        TestChannel::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(TestChannel::BYTE_LEN, buf)?;
+        buf[0] = self.rx_channel.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(TestChannel::BYTE_LEN, buf)?;
+        Ok(ReceiverTest {
+            rx_channel: unpack_channel(buf, 0)?,
+        })
+    }
+}
+/// `HCI_LE_Transmitter_Test` (V1). Transmit on the LE 1M PHY.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct TransmitterTest {
+    pub tx_channel: TestChannel,
+    /// Payload length in bytes.
+    pub test_data_length: u8,
+    pub packet_payload: TestPayload,
+}
+impl TransmitterTest {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::TransmitterTest;
+    pub const BYTE_LEN: usize = 3;
+}
+impl Command for TransmitterTest {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.tx_channel.into();
+        buf[1] = self.test_data_length;
+        buf[2] = self.packet_payload.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(TransmitterTest {
+            tx_channel: unpack_channel(buf, 0)?,
+            test_data_length: buf[1],
+            packet_payload: TestPayload::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+        })
+    }
+}
+/// `HCI_LE_Receiver_Test` (V2). Receive on any PHY.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReceiverTestV2 {
+    pub rx_channel: TestChannel,
+    pub phy: Phy,
+    pub modulation_index: ModulationIndex,
+}
+impl ReceiverTestV2 {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReceiverTestV2;
+    pub const BYTE_LEN: usize = 3;
+}
+impl Command for ReceiverTestV2 {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.rx_channel.into();
+        buf[1] = self.phy.into();
+        buf[2] = self.modulation_index.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(ReceiverTestV2 {
+            rx_channel: unpack_channel(buf, 0)?,
+            phy: Phy::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?,
+            modulation_index: ModulationIndex::try_from(buf[2])
+                .map_err(|_| PackError::bad_index(2))?,
+        })
+    }
+}
+/// `HCI_LE_Transmitter_Test` (V2). Transmit on any PHY.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct TransmitterTestV2 {
+    pub tx_channel: TestChannel,
+    pub test_data_length: u8,
+    pub packet_payload: TestPayload,
+    pub phy: TransmitterPhy,
+}
+impl TransmitterTestV2 {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::TransmitterTestV2;
+    pub const BYTE_LEN: usize = 4;
+}
+impl Command for TransmitterTestV2 {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.tx_channel.into();
+        buf[1] = self.test_data_length;
+        buf[2] = self.packet_payload.into();
+        buf[3] = self.phy.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(TransmitterTestV2 {
+            tx_channel: unpack_channel(buf, 0)?,
+            test_data_length: buf[1],
+            packet_payload: TestPayload::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+            phy: TransmitterPhy::try_from(buf[3]).map_err(|_| PackError::bad_index(3))?,
+        })
+    }
+}
+/// `HCI_LE_Receiver_Test` (V3). Also samples a Constant Tone Extension (CTE) for direction
+/// finding. `expected_cte_length` is in 8µs units (`0` for no CTE).
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReceiverTestV3 {
+    pub rx_channel: TestChannel,
+    pub phy: Phy,
+    pub modulation_index: ModulationIndex,
+    pub expected_cte_length: u8,
+    pub expected_cte_type: u8,
+    /// `0x01` for 1µs or `0x02` for 2µs switching and sampling slots.
+    pub slot_durations: u8,
+    pub antenna_ids: Vec<u8>,
+}
+impl ReceiverTestV3 {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReceiverTestV3;
+    pub const HEADER_LEN: usize = 7;
+}
+impl Command for ReceiverTestV3 {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_1;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.antenna_ids.len()
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        buf[0] = self.rx_channel.into();
+        buf[1] = self.phy.into();
+        buf[2] = self.modulation_index.into();
+        buf[3] = self.expected_cte_length;
+        buf[4] = self.expected_cte_type;
+        buf[5] = self.slot_durations;
+        pack_antenna_ids(&self.antenna_ids, &mut buf[6..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        Ok(ReceiverTestV3 {
+            rx_channel: unpack_channel(buf, 0)?,
+            phy: Phy::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?,
+            modulation_index: ModulationIndex::try_from(buf[2])
+                .map_err(|_| PackError::bad_index(2))?,
+            expected_cte_length: buf[3],
+            expected_cte_type: buf[4],
+            slot_durations: buf[5],
+            antenna_ids: unpack_antenna_ids(buf, 6)?,
+        })
+    }
+}
+/// `HCI_LE_Transmitter_Test` (V3). Also appends a Constant Tone Extension (CTE) of
+/// `cte_length` 8µs units (`0` for no CTE).
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct TransmitterTestV3 {
+    pub tx_channel: TestChannel,
+    pub test_data_length: u8,
+    pub packet_payload: TestPayload,
+    pub phy: TransmitterPhy,
+    pub cte_length: u8,
+    pub cte_type: u8,
+    pub antenna_ids: Vec<u8>,
+}
+impl TransmitterTestV3 {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::TransmitterTestV3;
+    pub const HEADER_LEN: usize = 7;
+}
+impl Command for TransmitterTestV3 {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V5_1;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.antenna_ids.len()
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        buf[0] = self.tx_channel.into();
+        buf[1] = self.test_data_length;
+        buf[2] = self.packet_payload.into();
+        buf[3] = self.phy.into();
+        buf[4] = self.cte_length;
+        buf[5] = self.cte_type;
+        pack_antenna_ids(&self.antenna_ids, &mut buf[6..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        Ok(TransmitterTestV3 {
+            tx_channel: unpack_channel(buf, 0)?,
+            test_data_length: buf[1],
+            packet_payload: TestPayload::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+            phy: TransmitterPhy::try_from(buf[3]).map_err(|_| PackError::bad_index(3))?,
+            cte_length: buf[4],
+            cte_type: buf[5],
+            antenna_ids: unpack_antenna_ids(buf, 6)?,
+        })
+    }
+}
+/// `HCI_LE_Test_End`. Stop the running test.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct TestEnd;
+impl TestEnd {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::TestEnd;
+}
+impl Command for TestEnd {
+    type Return = CommandComplete<TestEndReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(TestEnd)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct TestEndReturn {
+    pub status: ErrorCode,
+    /// Packets received by a receiver test (`0` after a transmitter test).
+    pub num_packets: u16,
+}
+impl TestEndReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + 2;
+}
+impl ReturnParameters for TestEndReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&self.num_packets.to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(TestEndReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            num_packets: u16::from_le_bytes([buf[1], buf[2]]),
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use alloc::vec;
+    #[test]
+    fn test_direct_test_mode() {
+        assert_eq!(TestChannel::new(19).frequency_mhz(), 2440);
+        assert!(TestChannel::new_checked(40).is_none());
+        let tx = TransmitterTestV2 {
+            tx_channel: TestChannel::new(0),
+            test_data_length: 37,
+            packet_payload: TestPayload::PRBS9,
+            phy: TransmitterPhy::LECodedS2,
+        };
+        let packet = tx.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(packet.parameters.as_ref(), &[0x00, 0x25, 0x00, 0x04]);
+        assert_eq!(TransmitterTestV2::unpack_command_packet(&packet), Ok(tx));
+
+        let rx = ReceiverTestV3 {
+            rx_channel: TestChannel::new(39),
+            phy: Phy::LE1M,
+            modulation_index: ModulationIndex::Standard,
+            expected_cte_length: 20,
+            expected_cte_type: 0x01,
+            slot_durations: 0x01,
+            antenna_ids: vec![0, 1, 2, 3],
+        };
+        let packet = rx.pack_command_packet::<Box<[u8]>>().unwrap();
+        assert_eq!(packet.parameters.as_ref()[6], 4);
+        assert_eq!(ReceiverTestV3::unpack_command_packet(&packet), Ok(rx));
+        assert!(ReceiverTestV3::unpack_from(&[39, 1, 0, 20, 1, 1, 4, 0, 1]).is_err());
+
+        let end = TestEndReturn::unpack_from(&[0x00, 0xE8, 0x03]).unwrap();
+        assert_eq!(end.num_packets, 1000);
+    }
+}
@@ -0,0 +1,240 @@
+//! LE White List commands. Scanning, advertising and connecting can be limited to the devices in
+//! the White List with their filter policies.
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, ReturnParameters, StatusReturn};
+use crate::hci::informational::CoreVersion;
+use crate::hci::le::LEControllerOpcode;
+use crate::hci::{ErrorCode, Opcode};
+use crate::le::advertiser::PeerAddressType;
+use crate::{BTAddress, ConversionError, DeviceAddress, PackError, BT_ADDRESS_LEN};
+use core::convert::TryFrom;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum WhitelistAddressType {
+    Public = 0x00,
+    Random = 0x01,
+    /// Advertisements without an address (extended advertising).
+    Anonymous = 0xFF,
+}
+impl From<PeerAddressType> for WhitelistAddressType {
+    fn from(a: PeerAddressType) -> Self {
+        match a {
+            PeerAddressType::Public => WhitelistAddressType::Public,
+            PeerAddressType::Random => WhitelistAddressType::Random,
+        }
+    }
+}
+impl From<WhitelistAddressType> for u8 {
+    fn from(a: WhitelistAddressType) -> Self {
+        a as u8
+    }
+}
+impl TryFrom<u8> for WhitelistAddressType {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(WhitelistAddressType::Public),
+            0x01 => Ok(WhitelistAddressType::Random),
+            0xFF => Ok(WhitelistAddressType::Anonymous),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// One device in the White List. `address` is ignored for [`WhitelistAddressType::Anonymous`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct WhitelistEntry {
+    pub address_type: WhitelistAddressType,
+    pub address: BTAddress,
+}
+impl WhitelistEntry {
+    pub const BYTE_LEN: usize = 1 + BT_ADDRESS_LEN;
+    pub const fn public(address: BTAddress) -> WhitelistEntry {
+        WhitelistEntry {
+            address_type: WhitelistAddressType::Public,
+            address,
+        }
+    }
+    pub const fn random(address: BTAddress) -> WhitelistEntry {
+        WhitelistEntry {
+            address_type: WhitelistAddressType::Random,
+            address,
+        }
+    }
+    /// `None` for [`WhitelistAddressType::Anonymous`].
+    pub fn device_address(&self) -> Option<DeviceAddress> {
+        match self.address_type {
+            WhitelistAddressType::Public => Some(DeviceAddress::public(self.address)),
+            WhitelistAddressType::Random => Some(DeviceAddress::random(self.address)),
+            WhitelistAddressType::Anonymous => None,
+        }
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.address_type.into();
+        self.address.pack_into(&mut buf[1..])
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<WhitelistEntry, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(WhitelistEntry {
+            address_type: WhitelistAddressType::try_from(buf[0])
+                .map_err(|_| PackError::bad_index(0))?,
+            address: BTAddress::unpack_from(&buf[1..])?,
+        })
+    }
+}
+impl From<DeviceAddress> for WhitelistEntry {
+    fn from(address: DeviceAddress) -> Self {
+        WhitelistEntry {
+            address_type: PeerAddressType::from(address.kind).into(),
+            address: address.addr,
+        }
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ClearWhitelist {}
+impl ClearWhitelist {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ClearWhitelist;
+}
+impl Command for ClearWhitelist {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ClearWhitelist {})
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct AddDeviceToWhitelist(pub WhitelistEntry);
+impl AddDeviceToWhitelist {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::AddDeviceToWhitelist;
+}
+impl Command for AddDeviceToWhitelist {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        WhitelistEntry::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.0.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(AddDeviceToWhitelist(WhitelistEntry::unpack_from(buf)?))
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct RemoveDeviceFromWhitelist(pub WhitelistEntry);
+impl RemoveDeviceFromWhitelist {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::RemoveDeviceFromWhitelist;
+}
+impl Command for RemoveDeviceFromWhitelist {
+    type Return = CommandComplete<StatusReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        WhitelistEntry::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.0.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(RemoveDeviceFromWhitelist(WhitelistEntry::unpack_from(buf)?))
+    }
+}
+/// Read the total number of White List entries the controller can hold. Some of them can be
+/// taken by devices added before, this is the capacity not the free space.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadWhitelistSize {}
+impl ReadWhitelistSize {
+    pub const OPCODE: LEControllerOpcode = LEControllerOpcode::ReadWhitelistSize;
+}
+impl Command for ReadWhitelistSize {
+    type Return = CommandComplete<WhitelistSizeReturn>;
+    const MIN_VERSION: CoreVersion = CoreVersion::V4_0;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadWhitelistSize {})
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct WhitelistSizeReturn {
+    pub status: ErrorCode,
+    pub size: u8,
+}
+impl WhitelistSizeReturn {
+    pub const BYTE_LEN: usize = 2;
+}
+impl ReturnParameters for WhitelistSizeReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1] = self.size;
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(WhitelistSizeReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            size: buf[1],
+        })
+    }
+}
@@ -1,5 +1,14 @@
-//! Link Controller module (WIP).
-use crate::hci::{Opcode, OCF, OGF};
+//! Link Controller module (WIP). Commands and events for authenticating classic connections with
+//! stored link keys (see [`crate::key_store`]) or legacy PIN pairing, and reading the version of
+//! the remote controller on a connection.
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, CommandStatus, Event, EventCode, ReturnParameters};
+use crate::hci::informational::CoreVersion;
+use crate::hci::{ErrorCode, Opcode, OCF, OGF};
+use crate::key_store::{LinkKey, LinkKeyType, StoredLinkKey};
+use crate::le::connection::ConnectionHandle;
+use crate::{BTAddress, CompanyID, PackError, BT_ADDRESS_LEN};
+use core::convert::TryFrom;
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
 #[repr(u16)]
@@ -42,3 +51,604 @@ impl From<LinkControlOpcode> for Opcode {
         Self(OGF::LinkControl, opcode.into())
     }
 }
+/// `Status` and `BD_ADDR` returned by the link key and PIN code request replies (and
+/// [`ReadBDADDR`](crate::hci::informational::ReadBDADDR)).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct AddressReturn {
+    pub status: ErrorCode,
+    pub address: BTAddress,
+}
+impl AddressReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + BT_ADDRESS_LEN;
+}
+impl ReturnParameters for AddressReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        self.address.pack_into(&mut buf[1..])
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(AddressReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            address: BTAddress::unpack_from(&buf[1..])?,
+        })
+    }
+}
+/// `HCI_Link_Key_Request_Reply`. Answers a [`LinkKeyRequest`] with the stored key for `address`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct LinkKeyRequestReply {
+    pub address: BTAddress,
+    pub link_key: LinkKey,
+}
+impl LinkKeyRequestReply {
+    pub const OPCODE: LinkControlOpcode = LinkControlOpcode::LinkKeyRequestReply;
+    pub const BYTE_LEN: usize = BT_ADDRESS_LEN + LinkKey::BYTE_LEN;
+}
+impl Command for LinkKeyRequestReply {
+    type Return = CommandComplete<AddressReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        self.address.pack_into(&mut buf[..BT_ADDRESS_LEN])?;
+        buf[BT_ADDRESS_LEN..].copy_from_slice(&self.link_key.0);
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LinkKeyRequestReply {
+            address: BTAddress::unpack_from(&buf[..BT_ADDRESS_LEN])?,
+            link_key: LinkKey::unpack_from(&buf[BT_ADDRESS_LEN..])?,
+        })
+    }
+}
+/// `HCI_Link_Key_Request_Negative_Reply`. No key is stored for `address` so the controller
+/// falls back to pairing.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct LinkKeyRequestNegativeReply {
+    pub address: BTAddress,
+}
+impl LinkKeyRequestNegativeReply {
+    pub const OPCODE: LinkControlOpcode = LinkControlOpcode::LinkKeyRequestNegativeReply;
+}
+impl Command for LinkKeyRequestNegativeReply {
+    type Return = CommandComplete<AddressReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        BT_ADDRESS_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.address.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(LinkKeyRequestNegativeReply {
+            address: BTAddress::unpack_from(buf)?,
+        })
+    }
+}
+/// Legacy pairing PIN (1 to 16 bytes, usually ASCII digits).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PINCode {
+    len: u8,
+    code: [u8; PINCode::MAX_LEN],
+}
+impl PINCode {
+    pub const MAX_LEN: usize = 16;
+    /// Returns `None` if `code` is empty or longer than [`PINCode::MAX_LEN`].
+    pub fn new(code: &[u8]) -> Option<PINCode> {
+        if code.is_empty() || code.len() > Self::MAX_LEN {
+            return None;
+        }
+        let mut out = PINCode {
+            len: code.len() as u8,
+            code: [0_u8; Self::MAX_LEN],
+        };
+        out.code[..code.len()].copy_from_slice(code);
+        Some(out)
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.code[..usize::from(self.len)]
+    }
+}
+/// `HCI_PIN_Code_Request_Reply`. Answers a [`PINCodeRequest`] (legacy pairing).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PINCodeRequestReply {
+    pub address: BTAddress,
+    pub pin_code: PINCode,
+}
+impl PINCodeRequestReply {
+    pub const OPCODE: LinkControlOpcode = LinkControlOpcode::PINCodeRequestReply;
+    pub const BYTE_LEN: usize = BT_ADDRESS_LEN + 1 + PINCode::MAX_LEN;
+}
+impl Command for PINCodeRequestReply {
+    type Return = CommandComplete<AddressReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        self.address.pack_into(&mut buf[..BT_ADDRESS_LEN])?;
+        buf[BT_ADDRESS_LEN] = self.pin_code.len;
+        buf[BT_ADDRESS_LEN + 1..].copy_from_slice(&self.pin_code.code);
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let len = usize::from(buf[BT_ADDRESS_LEN]);
+        let code = buf[BT_ADDRESS_LEN + 1..]
+            .get(..len)
+            .ok_or(PackError::bad_index(BT_ADDRESS_LEN))?;
+        Ok(PINCodeRequestReply {
+            address: BTAddress::unpack_from(&buf[..BT_ADDRESS_LEN])?,
+            pin_code: PINCode::new(code).ok_or(PackError::bad_index(BT_ADDRESS_LEN))?,
+        })
+    }
+}
+/// `HCI_PIN_Code_Request_Negative_Reply`. Refuse legacy pairing with `address`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PINCodeRequestNegativeReply {
+    pub address: BTAddress,
+}
+impl PINCodeRequestNegativeReply {
+    pub const OPCODE: LinkControlOpcode = LinkControlOpcode::PINCodeRequestNegativeReply;
+}
+impl Command for PINCodeRequestNegativeReply {
+    type Return = CommandComplete<AddressReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        BT_ADDRESS_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        self.address.pack_into(buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        Ok(PINCodeRequestNegativeReply {
+            address: BTAddress::unpack_from(buf)?,
+        })
+    }
+}
+/// `HCI_Authentication_Requested`. Authenticate the connection (with the stored link key or by
+/// pairing). Finishes with [`AuthenticationComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct AuthenticationRequested {
+    pub handle: ConnectionHandle,
+}
+impl AuthenticationRequested {
+    pub const OPCODE: LinkControlOpcode = LinkControlOpcode::AuthenticationRequested;
+}
+impl Command for AuthenticationRequested {
+    type Return = CommandStatus;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ConnectionHandle::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        Ok(AuthenticationRequested {
+            handle: ConnectionHandle::unpack_from(buf, 0)?,
+        })
+    }
+}
+/// `HCI_Disconnect`. Works on LE and BR/EDR connections. `reason` is sent to the peer and has to
+/// be `AuthenticationFailure`, one of the `OtherEndTerminatedConnection*` codes,
+/// `UnsupportedRemoteFeature` or `PairingWithUnitKeyNotSupported`. Finishes with
+/// [`DisconnectionComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct Disconnect {
+    pub handle: ConnectionHandle,
+    pub reason: ErrorCode,
+}
+impl Disconnect {
+    pub const OPCODE: LinkControlOpcode = LinkControlOpcode::Disconnect;
+    pub const BYTE_LEN: usize = ConnectionHandle::BYTE_LEN + ErrorCode::BYTE_LEN;
+}
+impl Command for Disconnect {
+    type Return = CommandStatus;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[..2].copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        buf[2] = self.reason.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(Disconnect {
+            handle: ConnectionHandle::unpack_from(buf, 0)?,
+            reason: ErrorCode::try_from(buf[2]).map_err(|_| PackError::bad_index(2))?,
+        })
+    }
+}
+/// `HCI_Read_Remote_Version_Information`. Works on LE and BR/EDR connections. Finishes with
+/// [`ReadRemoteVersionInformationComplete`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReadRemoteVersionInformation {
+    pub handle: ConnectionHandle,
+}
+impl ReadRemoteVersionInformation {
+    pub const OPCODE: LinkControlOpcode = LinkControlOpcode::ReadRemoteVersionInformation;
+}
+impl Command for ReadRemoteVersionInformation {
+    type Return = CommandStatus;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ConnectionHandle::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        Ok(ReadRemoteVersionInformation {
+            handle: ConnectionHandle::unpack_from(buf, 0)?,
+        })
+    }
+}
+/// Events that only carry a `BD_ADDR`.
+macro_rules! address_event {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+        pub struct $name {
+            pub address: BTAddress,
+        }
+        impl Event for $name {
+            const EVENT_CODE: EventCode = EventCode::$name;
+
+            fn event_byte_len(&self) -> usize {
+                BT_ADDRESS_LEN
+            }
+
+            fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+            where
+                Self: Sized,
+            {
+                Ok($name {
+                    address: BTAddress::unpack_from(buf)?,
+                })
+            }
+
+            fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+                self.address.pack_into(buf)
+            }
+        }
+    };
+}
+address_event!(
+    /// The controller needs the link key for `address`. Answer with [`LinkKeyRequestReply`] or
+    /// [`LinkKeyRequestNegativeReply`].
+    LinkKeyRequest
+);
+address_event!(
+    /// Legacy pairing with `address` needs a PIN. Answer with [`PINCodeRequestReply`] or
+    /// [`PINCodeRequestNegativeReply`].
+    PINCodeRequest
+);
+/// A new link key was created for `address`. Should be stored to bond with the device.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct LinkKeyNotification {
+    pub address: BTAddress,
+    pub link_key: LinkKey,
+    pub key_type: LinkKeyType,
+}
+impl LinkKeyNotification {
+    pub const BYTE_LEN: usize = BT_ADDRESS_LEN + LinkKey::BYTE_LEN + 1;
+    pub fn stored_key(&self) -> StoredLinkKey {
+        StoredLinkKey {
+            key: self.link_key,
+            key_type: self.key_type,
+        }
+    }
+}
+impl Event for LinkKeyNotification {
+    const EVENT_CODE: EventCode = EventCode::LinkKeyNotification;
+
+    fn event_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LinkKeyNotification {
+            address: BTAddress::unpack_from(&buf[..BT_ADDRESS_LEN])?,
+            link_key: LinkKey::unpack_from(&buf[BT_ADDRESS_LEN..Self::BYTE_LEN - 1])?,
+            key_type: LinkKeyType::try_from(buf[Self::BYTE_LEN - 1])
+                .map_err(|_| PackError::bad_index(Self::BYTE_LEN - 1))?,
+        })
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        self.address.pack_into(&mut buf[..BT_ADDRESS_LEN])?;
+        buf[BT_ADDRESS_LEN..Self::BYTE_LEN - 1].copy_from_slice(&self.link_key.0);
+        buf[Self::BYTE_LEN - 1] = self.key_type.into();
+        Ok(())
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct AuthenticationComplete {
+    pub status: ErrorCode,
+    pub handle: ConnectionHandle,
+}
+impl AuthenticationComplete {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN;
+}
+impl Event for AuthenticationComplete {
+    const EVENT_CODE: EventCode = EventCode::AuthenticationComplete;
+
+    fn event_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(AuthenticationComplete {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            handle: ConnectionHandle::unpack_from(buf, 1)?,
+        })
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..].copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        Ok(())
+    }
+}
+/// A connection was closed (by either side or by a timeout). `reason` says why.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct DisconnectionComplete {
+    pub status: ErrorCode,
+    pub handle: ConnectionHandle,
+    pub reason: ErrorCode,
+}
+impl DisconnectionComplete {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN * 2 + ConnectionHandle::BYTE_LEN;
+}
+impl Event for DisconnectionComplete {
+    const EVENT_CODE: EventCode = EventCode::DisconnectionComplete;
+
+    fn event_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(DisconnectionComplete {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            handle: ConnectionHandle::unpack_from(buf, 1)?,
+            reason: ErrorCode::try_from(buf[3]).map_err(|_| PackError::bad_index(3))?,
+        })
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        buf[3] = self.reason.into();
+        Ok(())
+    }
+}
+/// LMP version, manufacturer and subversion of a remote controller. Together they usually
+/// identify the chipset and firmware of the peer.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct RemoteVersion {
+    /// Versions newer than this crate knows about are reported as [`CoreVersion::LATEST`].
+    pub lmp_version: CoreVersion,
+    pub manufacturer: CompanyID,
+    /// Manufacturer specific, usually the firmware build.
+    pub lmp_subversion: u16,
+}
+impl RemoteVersion {
+    pub const BYTE_LEN: usize = CoreVersion::BYTE_LEN + 2 + 2;
+    pub fn pack_into(self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.lmp_version.into();
+        buf[1..3].copy_from_slice(&self.manufacturer.0.to_le_bytes());
+        buf[3..5].copy_from_slice(&self.lmp_subversion.to_le_bytes());
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<RemoteVersion, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(RemoteVersion {
+            lmp_version: CoreVersion::try_from(buf[0]).unwrap_or(CoreVersion::LATEST),
+            manufacturer: CompanyID(u16::from_le_bytes([buf[1], buf[2]])),
+            lmp_subversion: u16::from_le_bytes([buf[3], buf[4]]),
+        })
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReadRemoteVersionInformationComplete {
+    pub status: ErrorCode,
+    pub handle: ConnectionHandle,
+    pub version: RemoteVersion,
+}
+impl ReadRemoteVersionInformationComplete {
+    pub const BYTE_LEN: usize =
+        ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN + RemoteVersion::BYTE_LEN;
+}
+impl Event for ReadRemoteVersionInformationComplete {
+    const EVENT_CODE: EventCode = EventCode::ReadRemoteVersionInformationComplete;
+
+    fn event_byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(ReadRemoteVersionInformationComplete {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            handle: ConnectionHandle::unpack_from(buf, 1)?,
+            version: RemoteVersion::unpack_from(&buf[3..])?,
+        })
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        self.version.pack_into(&mut buf[3..])
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_store::{KeyStore, MemoryKeyStore};
+    use alloc::vec::Vec;
+    #[test]
+    fn test_link_key_flow() {
+        let address = BTAddress([1, 2, 3, 4, 5, 6]);
+        let notification = LinkKeyNotification {
+            address,
+            link_key: LinkKey([0xAB; 16]),
+            key_type: LinkKeyType::AuthenticatedCombinationP256,
+        };
+        let packet = notification.event_pack_packet::<Vec<u8>>().unwrap();
+        let unpacked = LinkKeyNotification::unpack_event_packet(&packet).unwrap();
+        assert_eq!(unpacked, notification);
+        assert!(LinkKeyRequest::unpack_event_packet(&packet).is_err());
+
+        let mut store = MemoryKeyStore::new();
+        assert!(store.link_key(address).is_none());
+        store.store_link_key(address, unpacked.stored_key());
+        let reply = LinkKeyRequestReply {
+            address,
+            link_key: store.link_key(address).unwrap().key,
+        };
+        let mut buf = [0_u8; LinkKeyRequestReply::BYTE_LEN];
+        reply.pack_into(&mut buf).unwrap();
+        assert_eq!(&buf[..6], &address.0);
+        assert_eq!(LinkKeyRequestReply::unpack_from(&buf).unwrap(), reply);
+        assert!(store.remove_bond(address).is_some());
+        assert!(store.bonds().is_empty());
+
+        let pin = PINCode::new(b"0000").unwrap();
+        let reply = PINCodeRequestReply {
+            address,
+            pin_code: pin,
+        };
+        let mut buf = [0_u8; PINCodeRequestReply::BYTE_LEN];
+        reply.pack_into(&mut buf).unwrap();
+        assert_eq!(buf[6], 4);
+        assert_eq!(PINCodeRequestReply::unpack_from(&buf).unwrap(), reply);
+        assert!(PINCode::new(&[]).is_none());
+        assert!(PINCode::new(&[b'1'; 17]).is_none());
+    }
+    #[test]
+    fn test_remote_version() {
+        // Version 5.2, Apple (0x004C), subversion 0x1234 on handle 0x0040.
+        let buf = [0x00, 0x40, 0x00, 0x0B, 0x4C, 0x00, 0x34, 0x12];
+        let complete = ReadRemoteVersionInformationComplete::event_unpack_from(&buf).unwrap();
+        assert_eq!(complete.handle, ConnectionHandle::new(0x0040));
+        assert_eq!(
+            complete.version,
+            RemoteVersion {
+                lmp_version: CoreVersion::V5_2,
+                manufacturer: CompanyID(0x004C),
+                lmp_subversion: 0x1234,
+            }
+        );
+        let mut packed = [0_u8; ReadRemoteVersionInformationComplete::BYTE_LEN];
+        complete.event_pack_into(&mut packed).unwrap();
+        assert_eq!(packed, buf);
+        let newer = RemoteVersion::unpack_from(&[0x7F, 0, 0, 0, 0]).unwrap();
+        assert_eq!(newer.lmp_version, CoreVersion::LATEST);
+    }
+}
@@ -1,19 +1,34 @@
 //! HCI Layer (where most the magic happens). Implements a Bluetooth Adapter for any controller
 //! supporting HCI streams.
 //! (HCI Layer is Little Endian).
+pub mod acl;
 pub mod adapter;
 pub mod adapters;
+pub mod anomaly;
 pub mod baseband;
 #[cfg(all(unix, feature = "bluez_socket"))]
 pub mod bluez_socket;
 pub mod command;
+pub mod decoder;
 pub mod event;
+pub mod flow_control;
+#[cfg(all(target_os = "linux", feature = "hotplug"))]
+pub mod hotplug;
+pub mod informational;
+pub mod introspect;
+pub mod invariants;
+pub mod iso;
 pub mod le;
 pub mod link_control;
+pub mod monitor;
 pub mod packet;
 #[cfg(feature = "remote")]
 pub mod remote;
+pub mod render;
+pub mod snoop;
+pub mod status;
 pub mod stream;
+pub mod testing;
 #[cfg(feature = "hci_usb")]
 pub mod usb;
 
@@ -163,6 +178,8 @@ pub enum ErrorCode {
     EIRTooLarge = 0x36,
     SimplePairingNotSupported = 0x37,
     HostBusyPairing = 0x38,
+    ControllerBusy = 0x3A,
+    OperationCancelledByHost = 0x44,
 }
 impl ErrorCode {
     pub const BYTE_LEN: usize = 1;
@@ -242,6 +259,8 @@ impl ErrorCode {
             ErrorCode::EIRTooLarge => "EIRTooLarge",
             ErrorCode::SimplePairingNotSupported => "SimplePairingNotSupported",
             ErrorCode::HostBusyPairing => "HostBusyPairing",
+            ErrorCode::ControllerBusy => "ControllerBusy",
+            ErrorCode::OperationCancelledByHost => "OperationCancelledByHost",
         }
     }
 }
@@ -302,6 +321,20 @@ impl TryFrom<u8> for ErrorCode {
             0x27 => Ok(ErrorCode::QoSNotSupported),
             0x28 => Ok(ErrorCode::InstantPassed),
             0x29 => Ok(ErrorCode::PairingWithUnitKeyNotSupported),
+            0x2A => Ok(ErrorCode::TransactionCollision),
+            0x2C => Ok(ErrorCode::QOSUnacceptableParameter),
+            0x2D => Ok(ErrorCode::QOSRejected),
+            0x2E => Ok(ErrorCode::ClassificationNotSupported),
+            0x2F => Ok(ErrorCode::InsufficientSecurity),
+            0x30 => Ok(ErrorCode::ParameterOutOfRange),
+            0x32 => Ok(ErrorCode::RoleSwitchPending),
+            0x34 => Ok(ErrorCode::SlotViolation),
+            0x35 => Ok(ErrorCode::RoleSwitchFailed),
+            0x36 => Ok(ErrorCode::EIRTooLarge),
+            0x37 => Ok(ErrorCode::SimplePairingNotSupported),
+            0x38 => Ok(ErrorCode::HostBusyPairing),
+            0x3A => Ok(ErrorCode::ControllerBusy),
+            0x44 => Ok(ErrorCode::OperationCancelledByHost),
             _ => Err(ConversionError(())),
         }
     }
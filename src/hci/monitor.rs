@@ -0,0 +1,139 @@
+//! BlueZ monitor channel (`HCI_CHANNEL_MONITOR`) frames. The monitor channel carries a copy of
+//! every packet on every controller, each prefixed by a small header. Packets are decoded with
+//! [`crate::hci::decoder`] like a live stream.
+use crate::hci::decoder;
+use crate::hci::packet::{PacketType, RawPacket};
+use crate::hci::snoop::Direction;
+use crate::hci::StreamError;
+use crate::ConversionError;
+use core::convert::TryFrom;
+
+pub const MONITOR_HEADER_LEN: usize = 6;
+/// `HCI_DEV_NONE`. Index of frames not tied to a controller (system notes, logging).
+pub const MONITOR_INDEX_NONE: u16 = 0xFFFF;
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u16)]
+pub enum MonitorOpcode {
+    NewIndex = 0,
+    DeleteIndex = 1,
+    CommandPacket = 2,
+    EventPacket = 3,
+    ACLTxPacket = 4,
+    ACLRxPacket = 5,
+    SCOTxPacket = 6,
+    SCORxPacket = 7,
+    OpenIndex = 8,
+    CloseIndex = 9,
+    IndexInfo = 10,
+    VendorDiagnostic = 11,
+    SystemNote = 12,
+    UserLogging = 13,
+    ControlOpen = 14,
+    ControlClose = 15,
+    ControlCommand = 16,
+    ControlEvent = 17,
+    ISOTxPacket = 18,
+    ISORxPacket = 19,
+}
+impl MonitorOpcode {
+    /// Packet type and direction of opcodes carrying HCI packets.
+    pub fn packet(self) -> Option<(PacketType, Direction)> {
+        match self {
+            MonitorOpcode::CommandPacket => Some((PacketType::Command, Direction::Sent)),
+            MonitorOpcode::EventPacket => Some((PacketType::Event, Direction::Received)),
+            MonitorOpcode::ACLTxPacket => Some((PacketType::ACLData, Direction::Sent)),
+            MonitorOpcode::ACLRxPacket => Some((PacketType::ACLData, Direction::Received)),
+            MonitorOpcode::SCOTxPacket => Some((PacketType::SCOData, Direction::Sent)),
+            MonitorOpcode::SCORxPacket => Some((PacketType::SCOData, Direction::Received)),
+            MonitorOpcode::ISOTxPacket => Some((PacketType::ISOData, Direction::Sent)),
+            MonitorOpcode::ISORxPacket => Some((PacketType::ISOData, Direction::Received)),
+            _ => None,
+        }
+    }
+}
+impl From<MonitorOpcode> for u16 {
+    fn from(opcode: MonitorOpcode) -> Self {
+        opcode as u16
+    }
+}
+impl TryFrom<u16> for MonitorOpcode {
+    type Error = ConversionError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(MonitorOpcode::NewIndex),
+            1 => Ok(MonitorOpcode::DeleteIndex),
+            2 => Ok(MonitorOpcode::CommandPacket),
+            3 => Ok(MonitorOpcode::EventPacket),
+            4 => Ok(MonitorOpcode::ACLTxPacket),
+            5 => Ok(MonitorOpcode::ACLRxPacket),
+            6 => Ok(MonitorOpcode::SCOTxPacket),
+            7 => Ok(MonitorOpcode::SCORxPacket),
+            8 => Ok(MonitorOpcode::OpenIndex),
+            9 => Ok(MonitorOpcode::CloseIndex),
+            10 => Ok(MonitorOpcode::IndexInfo),
+            11 => Ok(MonitorOpcode::VendorDiagnostic),
+            12 => Ok(MonitorOpcode::SystemNote),
+            13 => Ok(MonitorOpcode::UserLogging),
+            14 => Ok(MonitorOpcode::ControlOpen),
+            15 => Ok(MonitorOpcode::ControlClose),
+            16 => Ok(MonitorOpcode::ControlCommand),
+            17 => Ok(MonitorOpcode::ControlEvent),
+            18 => Ok(MonitorOpcode::ISOTxPacket),
+            19 => Ok(MonitorOpcode::ISORxPacket),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// One monitor channel frame.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct MonitorFrame<'a> {
+    pub opcode: MonitorOpcode,
+    /// Controller index (`hciN`) or [`MONITOR_INDEX_NONE`].
+    pub index: u16,
+    pub payload: &'a [u8],
+}
+impl<'a> MonitorFrame<'a> {
+    /// Unpack a frame read from the monitor socket (one frame per read).
+    pub fn unpack_from(buf: &'a [u8]) -> Result<MonitorFrame<'a>, StreamError> {
+        if buf.len() < MONITOR_HEADER_LEN {
+            return Err(StreamError::BadPacketCode);
+        }
+        let opcode = u16::from_le_bytes([buf[0], buf[1]]);
+        let index = u16::from_le_bytes([buf[2], buf[3]]);
+        let len = usize::from(u16::from_le_bytes([buf[4], buf[5]]));
+        let payload = &buf[MONITOR_HEADER_LEN..];
+        if payload.len() != len {
+            return Err(StreamError::BadPacketCode);
+        }
+        Ok(MonitorFrame {
+            opcode: MonitorOpcode::try_from(opcode).map_err(|_| StreamError::BadOpcode)?,
+            index,
+            payload,
+        })
+    }
+    /// The HCI packet carried by the frame. `None` for frames that don't carry one (index
+    /// changes, notes, etc).
+    pub fn packet(&self) -> Option<Result<(Direction, RawPacket<&'a [u8]>), StreamError>> {
+        let (packet_type, direction) = self.opcode.packet()?;
+        Some(decoder::decode(packet_type, self.payload).map(|packet| (direction, packet)))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_frames() {
+        let frame = [
+            0x03, 0x00, 0x00, 0x00, 0x06, 0x00, 0x0E, 0x04, 0x01, 0x03, 0x0C, 0x00,
+        ];
+        let frame = MonitorFrame::unpack_from(&frame).unwrap();
+        assert_eq!(frame.opcode, MonitorOpcode::EventPacket);
+        let (direction, packet) = frame.packet().unwrap().unwrap();
+        assert_eq!(direction, Direction::Received);
+        assert_eq!(packet.packet_type, PacketType::Event);
+        let note = [0x0C, 0x00, 0xFF, 0xFF, 0x02, 0x00, b'h', b'i'];
+        assert!(MonitorFrame::unpack_from(&note).unwrap().packet().is_none());
+        assert!(MonitorFrame::unpack_from(&note[..7]).is_err());
+    }
+}
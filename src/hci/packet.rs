@@ -11,6 +11,7 @@ pub enum PacketType {
     ACLData = 0x02,
     SCOData = 0x03,
     Event = 0x04,
+    ISOData = 0x05,
     Vendor = 0xFF,
 }
 impl From<PacketType> for u8 {
@@ -32,6 +33,7 @@ impl TryFrom<u8> for PacketType {
             0x02 => Ok(PacketType::ACLData),
             0x03 => Ok(PacketType::SCOData),
             0x04 => Ok(PacketType::Event),
+            0x05 => Ok(PacketType::ISOData),
             0xFF => Ok(PacketType::Vendor),
             _ => Err(ConversionError(())),
         }
@@ -1,4 +1,9 @@
 //! Remote HCI Controller (WIP).
+use crate::error::IOError;
+use crate::hci::adapter::Error;
+use crate::hci::stream::{HCIReader, HCIWriter};
+use core::pin::Pin;
+use core::task::{Context, Poll};
 use std::{io, net};
 pub struct Client(pub net::TcpStream);
 impl io::Write for Client {
@@ -20,6 +25,65 @@ impl Client {
         Self(stream)
     }
 }
+/// H4 framed HCI over TCP (controller proxies, emulators, etc). Opened by the built-in `tcp`
+/// scheme of [`crate::hci::adapters::registry`] as `tcp:host:port`.
+#[derive(Debug)]
+pub struct TcpTransport(pub tokio::net::TcpStream);
+impl TcpTransport {
+    /// Must be awaited on a tokio runtime with IO enabled. `address` is resolved with
+    /// [`std::net::ToSocketAddrs`] (which may block) and the first address that connects is used.
+    pub async fn connect(address: &str) -> Result<TcpTransport, Error> {
+        use std::net::ToSocketAddrs;
+        let mut last_error = IOError::NotFound;
+        let addresses = address
+            .to_socket_addrs()
+            .map_err(|e| Error::IOError(e.into()))?;
+        for address in addresses {
+            match tokio::net::TcpStream::connect(address).await {
+                Ok(stream) => return TcpTransport::from_stream(stream),
+                Err(e) => last_error = e.into(),
+            }
+        }
+        Err(Error::IOError(last_error))
+    }
+    fn from_stream(stream: tokio::net::TcpStream) -> Result<TcpTransport, Error> {
+        stream
+            .set_nodelay(true)
+            .map_err(|e| Error::IOError(e.into()))?;
+        Ok(TcpTransport(stream))
+    }
+}
+impl HCIReader for TcpTransport {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, Error>> {
+        use tokio::io::AsyncRead;
+        Pin::new(&mut self.0)
+            .poll_read(cx, buf)
+            .map_err(|e| Error::IOError(e.into()))
+    }
+}
+impl HCIWriter for TcpTransport {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Error>> {
+        use tokio::io::AsyncWrite;
+        Pin::new(&mut self.0)
+            .poll_write(cx, buf)
+            .map_err(|e| Error::IOError(e.into()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        use tokio::io::AsyncWrite;
+        Pin::new(&mut self.0)
+            .poll_flush(cx)
+            .map_err(|e| Error::IOError(e.into()))
+    }
+}
 #[cfg(feature = "remote_async")]
 pub mod remote_async {
     use core::pin::Pin;
@@ -0,0 +1,438 @@
+//! btmon style rendering of HCI packets ([`render_packet`]) and advertising reports
+//! ([`render_report`]), either as indented text or as one JSON object per packet (for support
+//! bundles and log pipelines). Parameters are split with the [`introspect`] descriptions, so
+//! packets without one still render (as hex).
+//!
+//! Everything is written to a [`core::fmt::Write`] (a `String`, a `Formatter`, etc).
+use crate::bytes::HexSlice;
+use crate::hci::event::EventCode;
+use crate::hci::introspect::{self, Field, FieldValue, MessageDescription, MessageId};
+use crate::hci::packet::{PacketType, RawPacket};
+use crate::hci::snoop::Direction;
+use crate::hci::Opcode;
+use crate::le::report::ReportInfo;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt::Write;
+
+/// Indentation of each nesting level in [`Format::Text`].
+const INDENT: usize = 8;
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum Format {
+    /// Human readable, like `btmon`. Each packet takes one or more lines (all ending with a
+    /// newline).
+    Text,
+    /// One JSON object per packet, without a trailing newline. Numbers are decimal, addresses
+    /// are strings and byte fields are uppercase hex strings.
+    Json,
+}
+impl Format {
+    pub const DEFAULT: Format = Format::Text;
+}
+impl Default for Format {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+fn direction_str(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Sent => "sent",
+        Direction::Received => "received",
+    }
+}
+fn direction_arrow(direction: Direction) -> char {
+    match direction {
+        Direction::Sent => '<',
+        Direction::Received => '>',
+    }
+}
+/// `btmon` writes opcodes as `(OGF|OCF)`.
+fn write_opcode<W: Write>(out: &mut W, opcode: u16) -> core::fmt::Result {
+    write!(out, "(0x{:02x}|0x{:04x})", opcode >> 10, opcode & 0x03FF)
+}
+fn find_command(opcode: u16) -> Option<MessageDescription> {
+    Opcode::try_from(opcode)
+        .ok()
+        .and_then(introspect::find_command)
+}
+fn write_value<W: Write>(out: &mut W, format: Format, value: FieldValue<'_>) -> core::fmt::Result {
+    match (format, value) {
+        (Format::Text, value) => write!(out, " {}", value),
+        (Format::Json, FieldValue::Unsigned(v)) => write!(out, "{}", v),
+        (Format::Json, FieldValue::Signed(v)) => write!(out, "{}", v),
+        (Format::Json, value) => write!(out, "\"{}\"", value),
+    }
+}
+/// Write decoded `fields` (or `raw` as hex if they couldn't be decoded). Text lines are indented
+/// by `indent`, JSON is written as an object (or a hex string).
+fn write_fields<W: Write>(
+    out: &mut W,
+    format: Format,
+    fields: Option<Vec<(Field, FieldValue<'_>)>>,
+    raw: &[u8],
+    indent: usize,
+) -> core::fmt::Result {
+    let fields = match fields {
+        Some(fields) => fields,
+        None => {
+            return match format {
+                Format::Text if raw.is_empty() => Ok(()),
+                Format::Text => writeln!(
+                    out,
+                    "{:indent$}Data: {}",
+                    "",
+                    HexSlice(raw),
+                    indent = indent
+                ),
+                Format::Json => write!(out, "\"{}\"", HexSlice(raw)),
+            }
+        }
+    };
+    // The return parameters of a Command Complete are decoded with the command's description.
+    let returned_by = fields.iter().find_map(|(field, value)| match value {
+        FieldValue::Unsigned(opcode) if field.name == "Command_Opcode" => {
+            find_command(*opcode as u16)
+        }
+        _ => None,
+    });
+    if format == Format::Json {
+        out.write_char('{')?;
+    }
+    for (i, (field, value)) in fields.iter().enumerate() {
+        match format {
+            Format::Text => write!(out, "{:indent$}{}:", "", field.name, indent = indent)?,
+            Format::Json if i == 0 => write!(out, "\"{}\":", field.name)?,
+            Format::Json => write!(out, ",\"{}\":", field.name)?,
+        }
+        match (field.name, *value) {
+            ("Command_Opcode", FieldValue::Unsigned(opcode)) if format == Format::Text => {
+                let opcode = opcode as u16;
+                match find_command(opcode) {
+                    Some(command) => write!(out, " {} ", command.name)?,
+                    None => write!(out, " Unknown ")?,
+                }
+                write_opcode(out, opcode)?;
+                out.write_char('\n')?;
+            }
+            ("Return_Parameters", FieldValue::Bytes(bytes)) if returned_by.is_some() => {
+                let command = returned_by.expect("checked above");
+                if format == Format::Text {
+                    out.write_char('\n')?;
+                }
+                write_fields(
+                    out,
+                    format,
+                    command.decode_return(bytes).ok(),
+                    bytes,
+                    indent + INDENT,
+                )?;
+            }
+            (_, value) => {
+                write_value(out, format, value)?;
+                if format == Format::Text {
+                    out.write_char('\n')?;
+                }
+            }
+        }
+    }
+    if format == Format::Json {
+        out.write_char('}')?;
+    }
+    Ok(())
+}
+fn write_message<W: Write>(
+    out: &mut W,
+    format: Format,
+    description: Option<MessageDescription>,
+    parameters: &[u8],
+) -> core::fmt::Result {
+    if format == Format::Json {
+        match description {
+            Some(d) => write!(out, ",\"name\":\"{}\",\"parameters\":", d.name)?,
+            None => write!(out, ",\"name\":null,\"parameters\":")?,
+        }
+    }
+    let fields = description.and_then(|d| d.decode(parameters).ok());
+    write_fields(out, format, fields, parameters, INDENT)
+}
+/// Render `packet` sent or received by the host. Malformed packets are rendered as hex.
+pub fn render_packet<W: Write>(
+    out: &mut W,
+    direction: Direction,
+    packet: RawPacket<&[u8]>,
+    format: Format,
+) -> core::fmt::Result {
+    let buf = packet.buf;
+    let arrow = direction_arrow(direction);
+    if format == Format::Json {
+        write!(out, "{{\"direction\":\"{}\"", direction_str(direction))?;
+    }
+    match packet.packet_type {
+        PacketType::Command if buf.len() >= 3 => {
+            let opcode = u16::from_le_bytes([buf[0], buf[1]]);
+            let parameters = &buf[3..];
+            let description = find_command(opcode);
+            match format {
+                Format::Text => {
+                    write!(
+                        out,
+                        "{} HCI Command: {} ",
+                        arrow,
+                        description.map_or("Unknown", |d| d.name)
+                    )?;
+                    write_opcode(out, opcode)?;
+                    writeln!(out, " plen {}", parameters.len())?;
+                }
+                Format::Json => write!(out, ",\"type\":\"command\",\"opcode\":{}", opcode)?,
+            }
+            write_message(out, format, description, parameters)?;
+        }
+        PacketType::Event if buf.len() >= 2 => {
+            let code = buf[0];
+            let parameters = &buf[2..];
+            let event_code = EventCode::try_from(code).ok();
+            let description =
+                event_code.and_then(|event_code| introspect::find_event(event_code, parameters));
+            let meta = match description.map(|d| d.id) {
+                Some(MessageId::MetaEvent(_)) => Some(parameters[0]),
+                _ => None,
+            };
+            match format {
+                Format::Text => {
+                    let name = match (meta, description) {
+                        (Some(_), _) => "LE_Meta",
+                        (None, Some(d)) => d.name,
+                        (None, None) => "Unknown",
+                    };
+                    writeln!(
+                        out,
+                        "{} HCI Event: {} (0x{:02x}) plen {}",
+                        arrow,
+                        name,
+                        code,
+                        parameters.len()
+                    )?;
+                    if let (Some(subevent), Some(d)) = (meta, description) {
+                        writeln!(
+                            out,
+                            "{:indent$}{} (0x{:02x})",
+                            "",
+                            d.name,
+                            subevent,
+                            indent = INDENT
+                        )?;
+                    }
+                }
+                Format::Json => {
+                    write!(out, ",\"type\":\"event\",\"event_code\":{}", code)?;
+                    if let Some(subevent) = meta {
+                        write!(out, ",\"subevent_code\":{}", subevent)?;
+                    }
+                }
+            }
+            let parameters = if meta.is_some() {
+                &parameters[1..]
+            } else {
+                parameters
+            };
+            write_message(out, format, description, parameters)?;
+        }
+        PacketType::ACLData if buf.len() >= 4 => {
+            let header = u16::from_le_bytes([buf[0], buf[1]]);
+            let handle = header & 0x0FFF;
+            let flags = header >> 12;
+            let data = &buf[4..];
+            match format {
+                Format::Text => {
+                    let tx_rx = match direction {
+                        Direction::Sent => "TX",
+                        Direction::Received => "RX",
+                    };
+                    writeln!(
+                        out,
+                        "{} ACL Data {}: Handle {} flags 0x{:02x} dlen {}",
+                        arrow,
+                        tx_rx,
+                        handle,
+                        flags,
+                        data.len()
+                    )?;
+                }
+                Format::Json => write!(
+                    out,
+                    ",\"type\":\"acl\",\"handle\":{},\"flags\":{},\"data\":",
+                    handle, flags
+                )?,
+            }
+            write_fields(out, format, None, data, INDENT)?;
+        }
+        packet_type => {
+            match format {
+                Format::Text => writeln!(out, "{} {:?}: len {}", arrow, packet_type, buf.len())?,
+                Format::Json => write!(out, ",\"type\":\"{:?}\",\"data\":", packet_type)?,
+            }
+            write_fields(out, format, None, buf, INDENT)?;
+        }
+    }
+    if format == Format::Json {
+        out.write_char('}')?;
+    }
+    Ok(())
+}
+/// Render an advertising report with its AD structures.
+pub fn render_report<W: Write, T: AsRef<[u8]>>(
+    out: &mut W,
+    report: &ReportInfo<T>,
+    format: Format,
+) -> core::fmt::Result {
+    let data = report.data.as_ref();
+    let origin = &report.origin;
+    match format {
+        Format::Text => {
+            writeln!(out, "> LE Advertising Report: {}", report.event_type)?;
+            writeln!(
+                out,
+                "{:indent$}Address: {} ({:?})",
+                "",
+                report.address,
+                report.address_type,
+                indent = INDENT
+            )?;
+            match report.rssi {
+                Some(rssi) => writeln!(
+                    out,
+                    "{:indent$}RSSI: {} dBm",
+                    "",
+                    i8::from(rssi),
+                    indent = INDENT
+                )?,
+                None => writeln!(out, "{:indent$}RSSI: unavailable", "", indent = INDENT)?,
+            }
+            write!(
+                out,
+                "{:indent$}PHY: {}",
+                "",
+                origin.primary_phy,
+                indent = INDENT
+            )?;
+            if let Some(secondary_phy) = origin.secondary_phy {
+                write!(out, " / {}", secondary_phy)?;
+            }
+            out.write_char('\n')?;
+            if let Some(sid) = origin.sid {
+                writeln!(out, "{:indent$}SID: {}", "", u8::from(sid), indent = INDENT)?;
+            }
+            if let Some(adapter) = origin.adapter {
+                writeln!(out, "{:indent$}Adapter: {}", "", adapter, indent = INDENT)?;
+            }
+            writeln!(
+                out,
+                "{:indent$}Data: {}",
+                "",
+                HexSlice(data),
+                indent = INDENT
+            )?;
+            for ad in report.data.iter() {
+                writeln!(
+                    out,
+                    "{:indent$}{:?} (0x{:02x}): {}",
+                    "",
+                    ad.ad_type,
+                    u8::from(ad.ad_type),
+                    HexSlice(ad.buf.as_ref()),
+                    indent = INDENT * 2
+                )?;
+            }
+        }
+        Format::Json => {
+            write!(
+                out,
+                "{{\"type\":\"report\",\"event_type\":\"{}\",\"address\":\"{}\",\"address_type\":\"{:?}\",\"rssi\":",
+                report.event_type, report.address, report.address_type
+            )?;
+            match report.rssi {
+                Some(rssi) => write!(out, "{}", i8::from(rssi))?,
+                None => out.write_str("null")?,
+            }
+            write!(
+                out,
+                ",\"primary_phy\":\"{}\",\"secondary_phy\":",
+                origin.primary_phy
+            )?;
+            match origin.secondary_phy {
+                Some(phy) => write!(out, "\"{}\"", phy)?,
+                None => out.write_str("null")?,
+            }
+            out.write_str(",\"sid\":")?;
+            match origin.sid {
+                Some(sid) => write!(out, "{}", u8::from(sid))?,
+                None => out.write_str("null")?,
+            }
+            out.write_str(",\"adapter\":")?;
+            match origin.adapter {
+                Some(adapter) => write!(out, "{}", adapter.0)?,
+                None => out.write_str("null")?,
+            }
+            write!(out, ",\"data\":\"{}\",\"ad_structures\":[", HexSlice(data))?;
+            for (i, ad) in report.data.iter().enumerate() {
+                if i != 0 {
+                    out.write_char(',')?;
+                }
+                write!(
+                    out,
+                    "{{\"ad_type\":{},\"data\":\"{}\"}}",
+                    u8::from(ad.ad_type),
+                    HexSlice(ad.buf.as_ref())
+                )?;
+            }
+            out.write_str("]}")?;
+        }
+    }
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hci::command::Command;
+    use crate::hci::le::scan::SetScanEnable;
+    use alloc::string::String;
+    #[test]
+    fn test_render_packet() {
+        let mut buf = [0_u8; 16];
+        let len = SetScanEnable {
+            is_enabled: true,
+            filter_duplicates: false,
+        }
+        .pack_full(&mut buf)
+        .unwrap();
+        let packet = RawPacket {
+            packet_type: PacketType::Command,
+            buf: &buf[..len],
+        };
+        let mut text = String::new();
+        render_packet(&mut text, Direction::Sent, packet, Format::Text).unwrap();
+        assert_eq!(
+            text,
+            "< HCI Command: LE_Set_Scan_Enable (0x08|0x000c) plen 2\n        \
+             LE_Scan_Enable: 0x1\n        Filter_Duplicates: 0x0\n"
+        );
+
+        // Command Complete for the command above.
+        let complete = RawPacket {
+            packet_type: PacketType::Event,
+            buf: &[0x0E, 0x04, 0x01, 0x0C, 0x20, 0x00][..],
+        };
+        let mut json = String::new();
+        render_packet(&mut json, Direction::Received, complete, Format::Json).unwrap();
+        assert_eq!(
+            json,
+            "{\"direction\":\"received\",\"type\":\"event\",\"event_code\":14,\
+             \"name\":\"Command_Complete\",\"parameters\":{\"Num_HCI_Command_Packets\":1,\
+             \"Command_Opcode\":8204,\"Return_Parameters\":{\"Status\":0}}}"
+        );
+        let mut text = String::new();
+        render_packet(&mut text, Direction::Received, complete, Format::Text).unwrap();
+        assert!(text.contains("Command_Opcode: LE_Set_Scan_Enable (0x08|0x000c)\n"));
+        assert!(text.ends_with("Return_Parameters:\n                Status: 0x0\n"));
+    }
+}
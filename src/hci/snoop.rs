@@ -0,0 +1,173 @@
+//! btsnoop capture replay (the format written by Android's HCI snoop log and `btmon -w`).
+//! Records are decoded with [`crate::hci::decoder`] like a live stream.
+use crate::hci::decoder;
+use crate::hci::packet::{PacketType, RawPacket};
+use crate::hci::StreamError;
+use core::convert::TryInto;
+
+pub const SNOOP_MAGIC: [u8; 8] = *b"btsnoop\0";
+pub const SNOOP_VERSION: u32 = 1;
+pub const SNOOP_HEADER_LEN: usize = 16;
+pub const SNOOP_RECORD_HEADER_LEN: usize = 24;
+/// Microseconds between 0 AD (the btsnoop epoch) and the Unix epoch.
+pub const SNOOP_UNIX_EPOCH_OFFSET: u64 = 0x00E0_3AB4_4A67_6000;
+/// How the packets in a capture are encapsulated.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum Datalink {
+    /// Packets without the type byte. The record flags say if it's a command/event or ACL data.
+    HCIUnencapsulated,
+    /// H4 frames (type byte first).
+    HCIUART,
+}
+impl Datalink {
+    pub fn from_u32(value: u32) -> Option<Datalink> {
+        match value {
+            1001 => Some(Datalink::HCIUnencapsulated),
+            1002 => Some(Datalink::HCIUART),
+            _ => None,
+        }
+    }
+    pub fn to_u32(self) -> u32 {
+        match self {
+            Datalink::HCIUnencapsulated => 1001,
+            Datalink::HCIUART => 1002,
+        }
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum Direction {
+    /// Host to controller.
+    Sent,
+    /// Controller to host.
+    Received,
+}
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct SnoopRecord<'a> {
+    pub direction: Direction,
+    /// Microseconds since 0 AD.
+    pub timestamp: u64,
+    /// Packets lost between the previous record and this one.
+    pub cumulative_drops: u32,
+    pub packet: RawPacket<&'a [u8]>,
+}
+impl<'a> SnoopRecord<'a> {
+    /// Microseconds since the Unix epoch.
+    pub fn unix_micros(&self) -> u64 {
+        self.timestamp.saturating_sub(SNOOP_UNIX_EPOCH_OFFSET)
+    }
+}
+/// Iterates over the records of an in-memory btsnoop capture.
+#[derive(Clone, Debug)]
+pub struct SnoopReader<'a> {
+    datalink: Datalink,
+    rest: &'a [u8],
+}
+impl<'a> SnoopReader<'a> {
+    /// Check the file header. Returns `StreamError::BadPacketCode` if `capture` isn't a version 1
+    /// btsnoop capture of a supported datalink.
+    pub fn new(capture: &'a [u8]) -> Result<SnoopReader<'a>, StreamError> {
+        if capture.len() < SNOOP_HEADER_LEN || capture[..8] != SNOOP_MAGIC {
+            return Err(StreamError::BadPacketCode);
+        }
+        let version = u32::from_be_bytes(capture[8..12].try_into().expect("length checked"));
+        let datalink = u32::from_be_bytes(capture[12..16].try_into().expect("length checked"));
+        if version != SNOOP_VERSION {
+            return Err(StreamError::BadPacketCode);
+        }
+        Ok(SnoopReader {
+            datalink: Datalink::from_u32(datalink).ok_or(StreamError::BadPacketCode)?,
+            rest: &capture[SNOOP_HEADER_LEN..],
+        })
+    }
+    pub fn datalink(&self) -> Datalink {
+        self.datalink
+    }
+    fn next_record(&mut self) -> Result<SnoopRecord<'a>, StreamError> {
+        if self.rest.len() < SNOOP_RECORD_HEADER_LEN {
+            return Err(StreamError::StreamClosed);
+        }
+        let header = &self.rest[..SNOOP_RECORD_HEADER_LEN];
+        let u32_at = |i: usize| u32::from_be_bytes(header[i..i + 4].try_into().expect("4 bytes"));
+        let included_len = u32_at(4) as usize;
+        let flags = u32_at(8);
+        let cumulative_drops = u32_at(12);
+        let timestamp = u64::from_be_bytes(header[16..24].try_into().expect("8 bytes"));
+        let end = SNOOP_RECORD_HEADER_LEN + included_len;
+        if self.rest.len() < end {
+            return Err(StreamError::StreamClosed);
+        }
+        let data = &self.rest[SNOOP_RECORD_HEADER_LEN..end];
+        self.rest = &self.rest[end..];
+        let packet = match self.datalink {
+            Datalink::HCIUART => decoder::decode_h4(data)?,
+            Datalink::HCIUnencapsulated => {
+                let packet_type = match (flags & 0b10 != 0, flags & 0b01 != 0) {
+                    (true, false) => PacketType::Command,
+                    (true, true) => PacketType::Event,
+                    (false, _) => PacketType::ACLData,
+                };
+                decoder::decode(packet_type, data)?
+            }
+        };
+        Ok(SnoopRecord {
+            direction: if flags & 0b01 == 0 {
+                Direction::Sent
+            } else {
+                Direction::Received
+            },
+            timestamp,
+            cumulative_drops,
+            packet,
+        })
+    }
+}
+impl<'a> Iterator for SnoopReader<'a> {
+    type Item = Result<SnoopRecord<'a>, StreamError>;
+
+    /// Truncated trailing records (capture cut off mid-write) end the iteration. Malformed
+    /// packets are returned as errors and skipped.
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Err(StreamError::StreamClosed) => None,
+            r => Some(r),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    fn record(flags: u32, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(&flags.to_be_bytes());
+        out.extend_from_slice(&0_u32.to_be_bytes());
+        out.extend_from_slice(&(SNOOP_UNIX_EPOCH_OFFSET + 5).to_be_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+    #[test]
+    fn test_replay() {
+        let mut capture = Vec::new();
+        capture.extend_from_slice(&SNOOP_MAGIC);
+        capture.extend_from_slice(&SNOOP_VERSION.to_be_bytes());
+        capture.extend_from_slice(&Datalink::HCIUART.to_u32().to_be_bytes());
+        capture.extend(record(0b10, &[0x01, 0x03, 0x0C, 0x00]));
+        capture.extend(record(0b11, &[0x04, 0x0E, 0x04, 0x01, 0x03, 0x0C, 0x00]));
+        // Bad event length.
+        capture.extend(record(0b11, &[0x04, 0x0E, 0x04, 0x01]));
+        capture.extend_from_slice(&[0, 0, 0]);
+        let records: Vec<_> = SnoopReader::new(&capture).unwrap().collect();
+        assert_eq!(records.len(), 3);
+        let reset = records[0].unwrap();
+        assert_eq!(reset.direction, Direction::Sent);
+        assert_eq!(reset.packet.packet_type, PacketType::Command);
+        assert_eq!(reset.unix_micros(), 5);
+        let complete = records[1].unwrap();
+        assert_eq!(complete.direction, Direction::Received);
+        assert_eq!(complete.packet.buf, &[0x0E, 0x04, 0x01, 0x03, 0x0C, 0x00]);
+        assert!(records[2].is_err());
+        assert!(SnoopReader::new(&capture[..10]).is_err());
+    }
+}
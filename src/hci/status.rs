@@ -0,0 +1,125 @@
+//! HCI Status Parameters commands (`OGF::StatusParameters`). Values the controller measures on
+//! a connection, like the RSSI ([`ReadRSSI`]).
+use crate::hci::command::Command;
+use crate::hci::event::{CommandComplete, ReturnParameters};
+use crate::hci::{ErrorCode, Opcode, OCF, OGF};
+use crate::le::connection::ConnectionHandle;
+use crate::{PackError, RSSI};
+use core::convert::TryFrom;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u16)]
+pub enum StatusOpcode {
+    ReadRSSI = 0x0005,
+}
+impl From<StatusOpcode> for OCF {
+    fn from(opcode: StatusOpcode) -> Self {
+        OCF::new(opcode as u16)
+    }
+}
+impl From<StatusOpcode> for Opcode {
+    fn from(opcode: StatusOpcode) -> Self {
+        Opcode(OGF::StatusParameters, opcode.into())
+    }
+}
+/// `HCI_Read_RSSI`. The RSSI of the last packets received on the connection `handle`, so a
+/// connected peer's signal can be followed without scanning for its advertisements.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReadRSSI {
+    pub handle: ConnectionHandle,
+}
+impl ReadRSSI {
+    pub const OPCODE: StatusOpcode = StatusOpcode::ReadRSSI;
+}
+impl Command for ReadRSSI {
+    type Return = CommandComplete<RSSIReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        ConnectionHandle::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(ConnectionHandle::BYTE_LEN, buf)?;
+        Ok(ReadRSSI {
+            handle: ConnectionHandle::unpack_from(buf, 0)?,
+        })
+    }
+}
+/// Return parameters of [`ReadRSSI`]. On LE connections the RSSI is absolute (dBm). BR/EDR
+/// controllers return the distance from their golden receive power range instead, which doesn't
+/// always fit [`RSSI`] and fails to unpack.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct RSSIReturn {
+    pub status: ErrorCode,
+    pub handle: ConnectionHandle,
+    pub rssi: RSSI,
+}
+impl RSSIReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + ConnectionHandle::BYTE_LEN + 1;
+}
+impl ReturnParameters for RSSIReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1..3].copy_from_slice(&u16::from(self.handle).to_le_bytes());
+        buf[3] = self.rssi.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(RSSIReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            handle: ConnectionHandle::unpack_from(buf, 1)?,
+            rssi: RSSI::try_from(i8::from_le_bytes([buf[3]]))
+                .map_err(|_| PackError::bad_index(3))?,
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_read_rssi() {
+        let command = ReadRSSI {
+            handle: ConnectionHandle::new(0x0040),
+        };
+        let mut buf = [0_u8; 2];
+        command.pack_into(&mut buf).unwrap();
+        assert_eq!(buf, [0x40, 0x00]);
+        assert_eq!(ReadRSSI::unpack_from(&buf), Ok(command));
+        assert_eq!(
+            Opcode::from(ReadRSSI::OPCODE),
+            Opcode(OGF::StatusParameters, OCF::new(0x0005))
+        );
+
+        let r = RSSIReturn::unpack_from(&[0x00, 0x40, 0x00, 0xC4]).unwrap();
+        assert_eq!(r.handle, command.handle);
+        assert_eq!(r.rssi, RSSI::new(-60));
+        let mut packed = [0_u8; RSSIReturn::BYTE_LEN];
+        r.pack_into(&mut packed).unwrap();
+        assert_eq!(packed, [0x00, 0x40, 0x00, 0xC4]);
+        // +127 dBm isn't a valid RSSI.
+        assert!(RSSIReturn::unpack_from(&[0x00, 0x40, 0x00, 0x7F]).is_err());
+    }
+}
@@ -2,18 +2,22 @@
 //! commands.
 use crate::bytes::Storage;
 use crate::error;
+use crate::hci::baseband::EventMask;
 use crate::hci::command::CommandPacket;
+use crate::hci::decoder::Decoder;
 use crate::hci::event::{EventCode, EventPacket, StaticHCIBuffer, MAX_HCI_PACKET_SIZE};
-use crate::hci::packet::{PacketType, RawPacket};
+use crate::hci::packet::PacketType;
 use crate::hci::{adapter, Opcode, StreamError};
 use crate::PackError;
 use core::convert::{TryFrom, TryInto};
 use core::ops::Deref;
 use core::ops::DerefMut;
+use core::ops::Range;
 use core::pin::Pin;
 use core::task::{Context, Poll};
 use core::u32;
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 use futures_util::future::{poll_fn, LocalBoxFuture};
 
 impl From<PackError> for StreamError {
@@ -66,6 +70,22 @@ impl Filter {
             opcode: Opcode::nop(),
         }
     }
+    /// Filter passing the events `mask` lets the controller send (and Command Complete, Command
+    /// Status, etc which it always sends). Use it instead of [`Filter::all_events`] to only wake
+    /// up for the events an application asked for.
+    pub fn for_event_mask(mask: EventMask) -> Filter {
+        let mut filter = Filter {
+            type_mask: 1 << 4,
+            event_mask: [0, 0],
+            opcode: Opcode::nop(),
+        };
+        for event in (1..=u8::MAX).filter_map(|code| EventCode::try_from(code).ok()) {
+            if mask.is_enabled(event) {
+                filter.enable_event(event);
+            }
+        }
+        filter
+    }
     pub fn enable_event(&mut self, event: EventCode) {
         let event = u32::from(event);
         assert!(event < 64);
@@ -145,14 +165,26 @@ pub trait HCIReader: Unpin {
 }
 /// HCI Stream. Wraps the `poll_read` and `poll_write` methods of [`HCIReader`] and [`HCIWriter`]
 /// to provide the [`Stream::read_packet`] and [`Stream::send_command`] functions.
+///
+/// Packets are framed by a [`Decoder`] so reads that split or join packets (UART) are handled.
+/// Bytes read past the end of a packet are kept for the next [`Stream::read_event`].
 #[derive(Clone, Debug)]
 pub struct Stream<S: HCIReader, B: Deref<Target = S>> {
     pub stream: Pin<B>,
+    pub decoder: Decoder,
+    buf: Vec<u8>,
+    /// Bytes in `buf` read but not yet pushed into `decoder`.
+    pending: Range<usize>,
 }
 pub const HCI_EVENT_READ_TRIES: usize = 50;
 impl<S: HCIReader, B: Deref<Target = S> + DerefMut> Stream<S, B> {
     pub fn new(stream: Pin<B>) -> Self {
-        Self { stream }
+        Self {
+            stream,
+            decoder: Decoder::default(),
+            buf: alloc::vec![0_u8; MAX_HCI_PACKET_SIZE],
+            pending: 0..0,
+        }
     }
     pub fn stream_pinned(&mut self) -> Pin<&mut S> {
         self.stream.as_mut()
@@ -173,12 +205,30 @@ impl<S: HCIReader, B: Deref<Target = S> + DerefMut> Stream<S, B> {
     pub async fn read_event<Buf: Storage<u8>>(
         &mut self,
     ) -> Result<EventPacket<Buf>, adapter::Error> {
-        let mut event_buf = StaticHCIBuffer::with_size(MAX_HCI_PACKET_SIZE);
-        let len = self.read_bytes(event_buf.as_mut()).await?;
-        let packet = RawPacket::try_from(&event_buf.as_ref()[..len])
-            .map_err(|_| StreamError::BadPacketCode)?;
-        let event_packet = EventPacket::try_from(packet).map_err(StreamError::EventError)?;
-        Ok(event_packet.to_new_storage())
+        loop {
+            if self.pending.is_empty() {
+                let Self { stream, buf, .. } = self;
+                let len = poll_fn(|cx| stream.as_mut().poll_read(cx, buf)).await?;
+                if len == 0 {
+                    return Err(StreamError::BadPacketCode.into());
+                }
+                self.pending = 0..len;
+            }
+            let used = match self.decoder.push(&self.buf[self.pending.clone()]) {
+                Ok(used) => used,
+                Err(e) => {
+                    // The rest of the read can't be framed without the dropped packet.
+                    self.pending = 0..0;
+                    return Err(e.into());
+                }
+            };
+            self.pending.start += used;
+            if let Some(packet) = self.decoder.packet() {
+                let event_packet =
+                    EventPacket::try_from(packet).map_err(StreamError::EventError)?;
+                return Ok(event_packet.to_new_storage());
+            }
+        }
     }
     pub async fn send_command_packet(
         &mut self,
@@ -205,3 +255,42 @@ impl<S: HCIWriter + HCIReader, B: Deref<Target = S> + DerefMut> adapter::Adapter
         Box::pin(self.read_event())
     }
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::VecDeque;
+    use futures_util::FutureExt;
+    struct Chunks(VecDeque<Vec<u8>>);
+    impl HCIReader for Chunks {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize, adapter::Error>> {
+            match self.0.pop_front() {
+                Some(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Poll::Ready(Ok(chunk.len()))
+                }
+                None => Poll::Pending,
+            }
+        }
+    }
+    #[test]
+    fn test_read_split_events() {
+        // A Command Complete split over two reads then joined with a Command Status.
+        let chunks = alloc::vec![
+            alloc::vec![0x04, 0x0E],
+            alloc::vec![0x04, 0x01, 0x03, 0x0C, 0x00, 0x04, 0x0F, 0x04],
+            alloc::vec![0x00, 0x01, 0x05, 0x04],
+        ];
+        let mut stream = Stream::new(Box::pin(Chunks(chunks.into())));
+        let first: EventPacket<Vec<u8>> = stream.read_event().now_or_never().unwrap().unwrap();
+        assert_eq!(first.event_code, EventCode::CommandComplete);
+        assert_eq!(first.parameters, [0x01, 0x03, 0x0C, 0x00]);
+        let second: EventPacket<Vec<u8>> = stream.read_event().now_or_never().unwrap().unwrap();
+        assert_eq!(second.event_code, EventCode::CommandStatus);
+        assert_eq!(second.parameters, [0x00, 0x01, 0x05, 0x04]);
+        assert!(stream.read_event::<Vec<u8>>().now_or_never().is_none());
+    }
+}
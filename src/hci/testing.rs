@@ -0,0 +1,291 @@
+//! HCI Testing commands (`OGF::Testing`). Loopback and Device Under Test modes for bringing up
+//! controllers and transports. In local loopback mode the controller sends most commands back as
+//! [`LoopbackCommand`] events (and ACL/SCO data back as data) instead of running them, so the
+//! transport can be checked end to end without a radio.
+use crate::bytes::Storage;
+use crate::hci::command::{Command, CommandPacket};
+use crate::hci::event::{CommandComplete, Event, EventCode, ReturnParameters, StatusReturn};
+use crate::hci::{ErrorCode, Opcode, OCF, OGF, OPCODE_LEN};
+use crate::{ConversionError, PackError};
+use core::convert::{TryFrom, TryInto};
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u16)]
+pub enum TestingOpcode {
+    ReadLoopbackMode = 0x0001,
+    WriteLoopbackMode = 0x0002,
+    EnableDeviceUnderTestMode = 0x0003,
+}
+impl From<TestingOpcode> for OCF {
+    fn from(opcode: TestingOpcode) -> Self {
+        OCF::new(opcode as u16)
+    }
+}
+impl From<TestingOpcode> for Opcode {
+    fn from(opcode: TestingOpcode) -> Self {
+        Opcode(OGF::Testing, opcode.into())
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum LoopbackMode {
+    NoLoopback = 0x00,
+    /// Commands and data are sent back by the controller itself.
+    LocalLoopback = 0x01,
+    /// Data received from the remote device is sent back to it.
+    RemoteLoopback = 0x02,
+}
+impl LoopbackMode {
+    pub const BYTE_LEN: usize = 1;
+}
+impl Default for LoopbackMode {
+    fn default() -> Self {
+        LoopbackMode::NoLoopback
+    }
+}
+impl From<LoopbackMode> for u8 {
+    fn from(mode: LoopbackMode) -> Self {
+        mode as u8
+    }
+}
+impl TryFrom<u8> for LoopbackMode {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(LoopbackMode::NoLoopback),
+            0x01 => Ok(LoopbackMode::LocalLoopback),
+            0x02 => Ok(LoopbackMode::RemoteLoopback),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// `HCI_Read_Loopback_Mode`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReadLoopbackMode;
+impl ReadLoopbackMode {
+    pub const OPCODE: TestingOpcode = TestingOpcode::ReadLoopbackMode;
+}
+impl Command for ReadLoopbackMode {
+    type Return = CommandComplete<LoopbackModeReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(ReadLoopbackMode)
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct LoopbackModeReturn {
+    pub status: ErrorCode,
+    pub mode: LoopbackMode,
+}
+impl LoopbackModeReturn {
+    pub const BYTE_LEN: usize = ErrorCode::BYTE_LEN + LoopbackMode::BYTE_LEN;
+}
+impl ReturnParameters for LoopbackModeReturn {
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.status.into();
+        buf[1] = self.mode.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LoopbackModeReturn {
+            status: ErrorCode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            mode: LoopbackMode::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?,
+        })
+    }
+}
+/// `HCI_Write_Loopback_Mode`. Entering local loopback disconnects every connection.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct WriteLoopbackMode {
+    pub mode: LoopbackMode,
+}
+impl WriteLoopbackMode {
+    pub const OPCODE: TestingOpcode = TestingOpcode::WriteLoopbackMode;
+}
+impl Command for WriteLoopbackMode {
+    type Return = CommandComplete<StatusReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        LoopbackMode::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(LoopbackMode::BYTE_LEN, buf)?;
+        buf[0] = self.mode.into();
+        Ok(())
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(LoopbackMode::BYTE_LEN, buf)?;
+        Ok(WriteLoopbackMode {
+            mode: LoopbackMode::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+        })
+    }
+}
+/// `HCI_Enable_Device_Under_Test_Mode`. Lets a tester take control of the controller over the
+/// air. Only a [`crate::hci::baseband::Reset`] leaves it.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct EnableDeviceUnderTestMode;
+impl EnableDeviceUnderTestMode {
+    pub const OPCODE: TestingOpcode = TestingOpcode::EnableDeviceUnderTestMode;
+}
+impl Command for EnableDeviceUnderTestMode {
+    type Return = CommandComplete<StatusReturn>;
+
+    fn opcode() -> Opcode {
+        Self::OPCODE.into()
+    }
+
+    fn byte_len(&self) -> usize {
+        0
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(0, buf)
+    }
+
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        PackError::expect_length(0, buf)?;
+        Ok(EnableDeviceUnderTestMode)
+    }
+}
+/// Command sent back by a controller in [`LoopbackMode::LocalLoopback`]. Commands that would
+/// make the event longer than 255 bytes come back truncated.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct LoopbackCommand<Buf> {
+    pub opcode: Opcode,
+    /// `Parameter_Total_Length` of the original command.
+    pub parameter_len: u8,
+    pub parameters: Buf,
+}
+impl<Buf: AsRef<[u8]>> LoopbackCommand<Buf> {
+    /// Longest command parameters that fit in the event untruncated.
+    pub const MAX_PARAMETERS_LEN: usize = 255 - OPCODE_LEN - 1;
+    pub fn is_truncated(&self) -> bool {
+        usize::from(self.parameter_len) > self.parameters.as_ref().len()
+    }
+    /// Whether this is an exact echo of `packet`.
+    pub fn matches(&self, packet: &CommandPacket<&[u8]>) -> bool {
+        self.opcode == packet.opcode
+            && usize::from(self.parameter_len) == packet.parameters.len()
+            && self.parameters.as_ref() == packet.parameters
+    }
+}
+impl<Buf: Storage<u8>> Event for LoopbackCommand<Buf> {
+    const EVENT_CODE: EventCode = EventCode::LoopbackCommand;
+
+    fn event_byte_len(&self) -> usize {
+        OPCODE_LEN + 1 + self.parameters.len()
+    }
+
+    fn event_unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < OPCODE_LEN + 1 {
+            return Err(PackError::BadLength {
+                expected: OPCODE_LEN + 1,
+                got: buf.len(),
+            });
+        }
+        let parameters = &buf[OPCODE_LEN + 1..];
+        let parameter_len = buf[OPCODE_LEN];
+        if usize::from(parameter_len) < parameters.len() {
+            return Err(PackError::bad_index(OPCODE_LEN));
+        }
+        Ok(LoopbackCommand {
+            opcode: Opcode::unpack(&buf[..OPCODE_LEN])?,
+            parameter_len,
+            parameters: Buf::from_slice(parameters),
+        })
+    }
+
+    fn event_pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.event_byte_len(), buf)?;
+        self.opcode.pack(&mut buf[..OPCODE_LEN])?;
+        buf[OPCODE_LEN] = self.parameter_len;
+        buf[OPCODE_LEN + 1..].copy_from_slice(self.parameters.as_ref());
+        Ok(())
+    }
+}
+/// Parameters of the `len` byte command sent by
+/// [`crate::hci::adapters::Adapter::loopback_echo_test`]. Byte `i` is `len + i` so shifted or
+/// repeated bytes don't echo back the same.
+pub fn echo_pattern(len: u8, buf: &mut [u8]) {
+    for (i, b) in buf.iter_mut().take(len.into()).enumerate() {
+        *b = len.wrapping_add(i.try_into().unwrap_or(0));
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_loopback_command() {
+        let mut parameters = [0_u8; 4];
+        echo_pattern(4, &mut parameters);
+        assert_eq!(parameters, [4, 5, 6, 7]);
+        let packet = CommandPacket {
+            opcode: Opcode(OGF::VendorSpecific, OCF::new(0)),
+            parameters: &parameters[..],
+        };
+        let event = LoopbackCommand::<Box<[u8]>> {
+            opcode: packet.opcode,
+            parameter_len: 4,
+            parameters: Box::from(&parameters[..]),
+        }
+        .event_pack_packet::<Box<[u8]>>()
+        .unwrap();
+        assert_eq!(event.parameters.as_ref(), &[0x00, 0xFC, 4, 4, 5, 6, 7]);
+        let echo = LoopbackCommand::<Box<[u8]>>::unpack_event_packet(&event).unwrap();
+        assert!(echo.matches(&packet));
+        assert!(!echo.is_truncated());
+        let truncated =
+            LoopbackCommand::<Box<[u8]>>::event_unpack_from(&[0x00, 0xFC, 5, 4, 5, 6, 7]).unwrap();
+        assert!(truncated.is_truncated());
+        assert!(!truncated.matches(&packet));
+        assert_eq!(
+            LoopbackModeReturn::unpack_from(&[0x00, 0x01]),
+            Ok(LoopbackModeReturn {
+                status: ErrorCode::Ok,
+                mode: LoopbackMode::LocalLoopback,
+            })
+        );
+    }
+}
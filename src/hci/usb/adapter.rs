@@ -2,7 +2,9 @@ use crate::bytes::Storage;
 use crate::error::IOError;
 use crate::hci;
 use crate::hci::command::CommandPacket;
-use crate::hci::event::{EventCode, EventPacket, StaticHCIBuffer};
+use crate::hci::decoder::Decoder;
+use crate::hci::event::{EventPacket, StaticHCIBuffer, MAX_HCI_PACKET_SIZE};
+use crate::hci::packet::PacketType;
 use crate::hci::usb::device::has_bluetooth_interface;
 use crate::hci::usb::Error;
 use core::convert::TryFrom;
@@ -12,32 +14,7 @@ use usbw::device::DeviceIdentifier;
 use usbw::libusb::async_device::{AsyncDevice, SingleTransferDevice};
 use usbw::libusb::device_descriptor::DeviceDescriptor;
 
-/// Yield the task back to the executor. Just returns `Poll::Pending` once and calls
-/// `.waker_by_ref()` to put the task back onto the queue. Workaround for blocking futures
-pub async fn yield_now() {
-    struct YieldNow {
-        yielded: bool,
-    }
-
-    impl core::future::Future for YieldNow {
-        type Output = ();
-
-        fn poll(
-            mut self: core::pin::Pin<&mut Self>,
-            cx: &mut core::task::Context<'_>,
-        ) -> core::task::Poll<()> {
-            if self.yielded {
-                return core::task::Poll::Ready(());
-            }
-
-            self.yielded = true;
-            cx.waker().wake_by_ref();
-            core::task::Poll::Pending
-        }
-    }
-
-    YieldNow { yielded: false }.await
-}
+pub use crate::hci::adapters::yield_now;
 
 pub const HCI_COMMAND_ENDPOINT: u8 = 0x01;
 pub const ACL_DATA_OUT_ENDPOINT: u8 = 0x02;
@@ -50,6 +27,7 @@ pub const INTERFACE_NUM: u8 = 0x00;
 pub struct Adapter {
     handle: SingleTransferDevice,
     device_descriptor: DeviceDescriptor,
+    decoder: Decoder,
     _private: (),
 }
 impl core::fmt::Debug for Adapter {
@@ -81,6 +59,7 @@ impl Adapter {
             handle: SingleTransferDevice::new(handle),
             _private: (),
             device_descriptor,
+            decoder: Decoder::default(),
         }
     }
     pub fn device_identifier(&self) -> DeviceIdentifier {
@@ -203,18 +182,19 @@ impl Adapter {
     pub async fn read_event_packet<Buf: Storage<u8>>(
         &mut self,
     ) -> Result<EventPacket<Buf>, hci::adapter::Error> {
-        let mut header = [0u8; 2];
-        self.read_event_bytes(&mut header[..]).await?;
-        let len = header[1];
-        let mut buf = Buf::with_size(len.into());
-        // Even if the event code is wrong, still read so we don't leave data in buffer
-        self.read_event_bytes(buf.as_mut()).await?;
-        let event_code =
-            EventCode::try_from(header[0]).map_err(|_| hci::StreamError::BadEventCode)?;
-        Ok(EventPacket {
-            event_code,
-            parameters: buf,
-        })
+        // The event endpoint only carries events so there is no packet type byte to read.
+        self.decoder.reset();
+        self.decoder.push(&[PacketType::Event.into()])?;
+        let mut buf = [0_u8; MAX_HCI_PACKET_SIZE];
+        while !self.decoder.is_complete() {
+            let len = self.decoder.remaining();
+            // Even if the event code is wrong, still read so we don't leave data in buffer
+            self.read_event_bytes(&mut buf[..len]).await?;
+            self.decoder.push(&buf[..len])?;
+        }
+        let packet = self.decoder.packet().expect("decoder is complete");
+        let event_packet = EventPacket::try_from(packet).map_err(hci::StreamError::EventError)?;
+        Ok(event_packet.to_new_storage())
     }
     pub fn reset(&mut self) -> Result<(), Error> {
         self.handle.device().handle_ref().reset()?;
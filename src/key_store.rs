@@ -0,0 +1,206 @@
+//! Keys of bonded devices. [`KeyStore`] is shared by everything that needs to remember a peer
+//! between connections (classic link keys, LE signing keys, etc). [`MemoryKeyStore`] keeps them in
+//! memory; implement the trait to persist them somewhere else.
+use crate::le::att::authentication::CSRK;
+use crate::le::smp::keys::IRK;
+use crate::{BTAddress, ConversionError, PackError};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// 128-bit BR/EDR link key (in HCI byte order).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct LinkKey(pub [u8; 16]);
+impl LinkKey {
+    pub const BYTE_LEN: usize = 16;
+    pub fn unpack_from(buf: &[u8]) -> Result<LinkKey, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let mut key = [0_u8; Self::BYTE_LEN];
+        key.copy_from_slice(buf);
+        Ok(LinkKey(key))
+    }
+}
+impl core::fmt::Debug for LinkKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Don't leak keys into logs.
+        write!(f, "LinkKey(..)")
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum LinkKeyType {
+    Combination = 0x00,
+    DebugCombination = 0x03,
+    UnauthenticatedCombinationP192 = 0x04,
+    AuthenticatedCombinationP192 = 0x05,
+    ChangedCombination = 0x06,
+    UnauthenticatedCombinationP256 = 0x07,
+    AuthenticatedCombinationP256 = 0x08,
+}
+impl LinkKeyType {
+    /// Created with MITM protection (numeric comparison, passkey or OOB).
+    pub fn is_authenticated(self) -> bool {
+        match self {
+            LinkKeyType::AuthenticatedCombinationP192
+            | LinkKeyType::AuthenticatedCombinationP256 => true,
+            _ => false,
+        }
+    }
+    /// Created from the Secure Simple Pairing debug keys. Anyone can compute these.
+    pub fn is_debug(self) -> bool {
+        self == LinkKeyType::DebugCombination
+    }
+}
+impl From<LinkKeyType> for u8 {
+    fn from(t: LinkKeyType) -> Self {
+        t as u8
+    }
+}
+impl TryFrom<u8> for LinkKeyType {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(LinkKeyType::Combination),
+            0x03 => Ok(LinkKeyType::DebugCombination),
+            0x04 => Ok(LinkKeyType::UnauthenticatedCombinationP192),
+            0x05 => Ok(LinkKeyType::AuthenticatedCombinationP192),
+            0x06 => Ok(LinkKeyType::ChangedCombination),
+            0x07 => Ok(LinkKeyType::UnauthenticatedCombinationP256),
+            0x08 => Ok(LinkKeyType::AuthenticatedCombinationP256),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct StoredLinkKey {
+    pub key: LinkKey,
+    pub key_type: LinkKeyType,
+}
+/// CSRK used for signed writes and its `SignCounter`. The counter must persist with the key or
+/// peers reject (or we accept) replayed writes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct SigningKey {
+    pub csrk: CSRK,
+    /// Next counter to send (local key) or the lowest counter accepted next (peer key).
+    pub sign_counter: u32,
+}
+impl SigningKey {
+    pub fn new(csrk: CSRK) -> SigningKey {
+        SigningKey {
+            csrk,
+            sign_counter: 0,
+        }
+    }
+}
+/// Everything stored about one bonded device.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct Bond {
+    pub address: BTAddress,
+    pub link_key: Option<StoredLinkKey>,
+    /// Our CSRK, distributed to the peer. Signs our writes.
+    pub local_signing_key: Option<SigningKey>,
+    /// The peer's CSRK. Verifies its writes.
+    pub peer_signing_key: Option<SigningKey>,
+    /// The peer's IRK. Resolves the private addresses it uses.
+    pub peer_irk: Option<IRK>,
+}
+impl Bond {
+    /// Bond with no keys yet.
+    pub fn new(address: BTAddress) -> Bond {
+        Bond {
+            address,
+            link_key: None,
+            local_signing_key: None,
+            peer_signing_key: None,
+            peer_irk: None,
+        }
+    }
+}
+/// Storage for [`Bond`]s, keyed by address.
+pub trait KeyStore {
+    fn bond(&self, address: BTAddress) -> Option<Bond>;
+    /// Add `bond` or replace the bond with the same address.
+    fn store_bond(&mut self, bond: Bond);
+    fn remove_bond(&mut self, address: BTAddress) -> Option<Bond>;
+    fn link_key(&self, address: BTAddress) -> Option<StoredLinkKey> {
+        self.bond(address)?.link_key
+    }
+    /// Set the link key of `address`, creating the bond if needed.
+    fn store_link_key(&mut self, address: BTAddress, link_key: StoredLinkKey) {
+        let mut bond = self.bond(address).unwrap_or_else(|| Bond::new(address));
+        bond.link_key = Some(link_key);
+        self.store_bond(bond);
+    }
+    /// Set our CSRK for `address` (resetting its counter), creating the bond if needed.
+    fn store_local_csrk(&mut self, address: BTAddress, csrk: CSRK) {
+        let mut bond = self.bond(address).unwrap_or_else(|| Bond::new(address));
+        bond.local_signing_key = Some(SigningKey::new(csrk));
+        self.store_bond(bond);
+    }
+    /// Set the CSRK `address` distributed (resetting its counter), creating the bond if needed.
+    fn store_peer_csrk(&mut self, address: BTAddress, csrk: CSRK) {
+        let mut bond = self.bond(address).unwrap_or_else(|| Bond::new(address));
+        bond.peer_signing_key = Some(SigningKey::new(csrk));
+        self.store_bond(bond);
+    }
+    /// Set the IRK `address` distributed, creating the bond if needed. `address` is the identity
+    /// address from the same key distribution.
+    fn store_peer_irk(&mut self, address: BTAddress, irk: IRK) {
+        let mut bond = self.bond(address).unwrap_or_else(|| Bond::new(address));
+        bond.peer_irk = Some(irk);
+        self.store_bond(bond);
+    }
+    /// Our CSRK for `address` and the `SignCounter` to sign the next write with. The stored
+    /// counter is advanced. Returns `None` if there's no key or the counter is used up.
+    fn next_local_signature(&mut self, address: BTAddress) -> Option<(CSRK, u32)> {
+        let mut bond = self.bond(address)?;
+        let key = bond.local_signing_key?;
+        bond.local_signing_key = Some(SigningKey {
+            sign_counter: key.sign_counter.checked_add(1)?,
+            ..key
+        });
+        self.store_bond(bond);
+        Some((key.csrk, key.sign_counter))
+    }
+    /// Only accept counters from `address` after `sign_counter` from now on.
+    fn accept_peer_sign_counter(&mut self, address: BTAddress, sign_counter: u32) {
+        if let Some(mut bond) = self.bond(address) {
+            if let Some(key) = bond.peer_signing_key.as_mut() {
+                key.sign_counter = sign_counter.saturating_add(1);
+                self.store_bond(bond);
+            }
+        }
+    }
+}
+#[derive(Clone, Debug, Default)]
+pub struct MemoryKeyStore {
+    bonds: Vec<Bond>,
+}
+impl MemoryKeyStore {
+    pub fn new() -> MemoryKeyStore {
+        MemoryKeyStore { bonds: Vec::new() }
+    }
+    pub fn bonds(&self) -> &[Bond] {
+        &self.bonds
+    }
+    pub fn clear(&mut self) {
+        self.bonds.clear();
+    }
+}
+impl KeyStore for MemoryKeyStore {
+    fn bond(&self, address: BTAddress) -> Option<Bond> {
+        self.bonds.iter().find(|b| b.address == address).copied()
+    }
+
+    fn store_bond(&mut self, bond: Bond) {
+        match self.bonds.iter_mut().find(|b| b.address == bond.address) {
+            Some(stored) => *stored = bond,
+            None => self.bonds.push(bond),
+        }
+    }
+
+    fn remove_bond(&mut self, address: BTAddress) -> Option<Bond> {
+        let index = self.bonds.iter().position(|b| b.address == address)?;
+        Some(self.bonds.remove(index))
+    }
+}
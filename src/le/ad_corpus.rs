@@ -0,0 +1,326 @@
+//! Advertisement payload corpora for hardening AD parsers. [`AdCorpus`] generates spec-valid
+//! payloads and payloads with one deliberate [`Malformation`] from a seed (same seed, same
+//! corpus). [`payload_from_bytes`] does the same driven by fuzzer input so `cargo fuzz` targets
+//! explore AD shaped inputs instead of random bytes.
+//!
+//! ```
+//! use btle::le::ad_corpus::AdCorpus;
+//! use btle::le::advertisement::{RawAdvertisement, MAX_ADV_LEN};
+//!
+//! for entry in AdCorpus::new(1).take(100) {
+//!     assert!(entry.malformation.is_some() || entry.payload.len() <= MAX_ADV_LEN);
+//!     // A parser must never panic, valid or not.
+//!     let _ = RawAdvertisement(entry.payload.as_slice()).iter().count();
+//! }
+//! ```
+use crate::le::advertisement::{AdType, MAX_ADV_LEN};
+use crate::le::advertisement_structures::flags::Flags;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Small deterministic xorshift generator. Not for anything security related.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CorpusRng(u64);
+impl CorpusRng {
+    pub fn new(seed: u64) -> CorpusRng {
+        // xorshift is stuck at zero.
+        CorpusRng(seed ^ 0x2545_F491_4F6C_DD1D)
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    pub fn next_u8(&mut self) -> u8 {
+        self.next_u64() as u8
+    }
+    /// `0..bound`. `bound` must not be `0`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+    pub fn fill(&mut self, buf: &mut [u8]) {
+        buf.iter_mut().for_each(|b| *b = self.next_u8());
+    }
+}
+/// Ways [`AdCorpus`] breaks a valid payload.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum Malformation {
+    /// The last structure's length runs past the end of the payload.
+    LengthOverrun,
+    /// A zero length byte in the middle (ends the significant part early).
+    ZeroLength,
+    /// A structure with a length of `1` (AD type but no data) where data is required.
+    EmptyData,
+    /// An AD type not in [`AdType`].
+    UnknownAdType,
+    /// Longer than [`MAX_ADV_LEN`].
+    Oversized,
+    /// A lone length byte at the end.
+    TruncatedHeader,
+    /// Fixed size data of the wrong size (2 byte Flags, 3 byte Appearance, etc).
+    WrongFixedLen,
+    /// A local name that isn't UTF-8.
+    InvalidUtf8Name,
+    /// A UUID list that isn't a multiple of the UUID size.
+    PartialUuid,
+}
+impl Malformation {
+    pub const ALL: [Malformation; 9] = [
+        Malformation::LengthOverrun,
+        Malformation::ZeroLength,
+        Malformation::EmptyData,
+        Malformation::UnknownAdType,
+        Malformation::Oversized,
+        Malformation::TruncatedHeader,
+        Malformation::WrongFixedLen,
+        Malformation::InvalidUtf8Name,
+        Malformation::PartialUuid,
+    ];
+}
+/// One payload of a corpus.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CorpusEntry {
+    pub payload: Vec<u8>,
+    /// `None` for spec-valid payloads.
+    pub malformation: Option<Malformation>,
+}
+/// Append one `len, ad_type, data` structure.
+fn push_structure(out: &mut Vec<u8>, ad_type: u8, data: &[u8]) {
+    out.push((data.len() + 1) as u8);
+    out.push(ad_type);
+    out.extend_from_slice(data);
+}
+/// Append a random valid structure with at most `space` bytes (header included). Returns `false`
+/// if nothing fits.
+fn push_valid_structure(rng: &mut CorpusRng, out: &mut Vec<u8>, space: usize) -> bool {
+    if space < 3 {
+        return false;
+    }
+    let max_data = space - 2;
+    let mut data = [0_u8; MAX_ADV_LEN];
+    let (ad_type, len) = match rng.below(7) {
+        0 => {
+            data[0] = rng.next_u8() % (Flags::FLAGS_MAX + 1);
+            (AdType::Flags, 1)
+        }
+        1 => {
+            data[0] = rng.next_u8();
+            (AdType::TxPowerLevel, 1)
+        }
+        2 if max_data >= 2 => {
+            rng.fill(&mut data[..2]);
+            (AdType::Appearance, 2)
+        }
+        3 => {
+            let len = 1 + rng.below(max_data);
+            for b in &mut data[..len] {
+                *b = b'a' + rng.next_u8() % 26;
+            }
+            let ad_type = if rng.below(2) == 0 {
+                AdType::CompleteLocalName
+            } else {
+                AdType::ShortenLocalName
+            };
+            (ad_type, len)
+        }
+        4 if max_data >= 2 => {
+            let len = 2 * (1 + rng.below(max_data / 2));
+            rng.fill(&mut data[..len]);
+            (AdType::CompleteList16bitUUID, len)
+        }
+        5 if max_data >= 2 => {
+            let len = 2 + rng.below(max_data - 1);
+            rng.fill(&mut data[..len]);
+            (AdType::ServiceData, len)
+        }
+        _ if max_data >= 2 => {
+            let len = 2 + rng.below(max_data - 1);
+            rng.fill(&mut data[..len]);
+            (AdType::ManufacturerData, len)
+        }
+        _ => {
+            data[0] = rng.next_u8();
+            (AdType::TxPowerLevel, 1)
+        }
+    };
+    push_structure(out, ad_type.into(), &data[..len]);
+    true
+}
+/// A spec-valid payload of at most [`MAX_ADV_LEN`] bytes. Can be empty.
+pub fn valid_payload(rng: &mut CorpusRng) -> Vec<u8> {
+    let mut out = Vec::with_capacity(MAX_ADV_LEN);
+    for _ in 0..rng.below(5) {
+        let space = MAX_ADV_LEN - out.len();
+        if !push_valid_structure(rng, &mut out, space) {
+            break;
+        }
+    }
+    out
+}
+/// A payload with `malformation`. Everything else in it is valid.
+pub fn malformed_payload(rng: &mut CorpusRng, malformation: Malformation) -> Vec<u8> {
+    let mut out = valid_payload(rng);
+    // Room for the broken structure.
+    out.truncate(MAX_ADV_LEN / 2);
+    if !out.is_empty() {
+        // Keep whole structures only.
+        let mut end = 0;
+        while end < out.len() && end + 1 + usize::from(out[end]) <= out.len() {
+            end += 1 + usize::from(out[end]);
+        }
+        out.truncate(end);
+    }
+    match malformation {
+        Malformation::LengthOverrun => {
+            let extra = 1 + rng.below(10) as u8;
+            push_structure(&mut out, AdType::ManufacturerData.into(), &[0x59, 0x00]);
+            let len_index = out.len() - 4;
+            out[len_index] += extra;
+        }
+        Malformation::ZeroLength => {
+            let at = if out.is_empty() { 0 } else { out.len() / 2 };
+            out.insert(at, 0);
+            push_structure(&mut out, AdType::TxPowerLevel.into(), &[0]);
+        }
+        Malformation::EmptyData => {
+            let ad_type =
+                [AdType::Flags, AdType::ManufacturerData, AdType::ServiceData][rng.below(3)];
+            push_structure(&mut out, ad_type.into(), &[]);
+        }
+        Malformation::UnknownAdType => {
+            let unknown = loop {
+                let t = rng.next_u8();
+                if t != 0 && AdType::try_from(t).is_err() {
+                    break t;
+                }
+            };
+            push_structure(&mut out, unknown, &[rng.next_u8()]);
+        }
+        Malformation::Oversized => {
+            while out.len() <= MAX_ADV_LEN {
+                push_structure(&mut out, AdType::TxPowerLevel.into(), &[rng.next_u8()]);
+            }
+        }
+        Malformation::TruncatedHeader => out.push(2 + rng.below(10) as u8),
+        Malformation::WrongFixedLen => match rng.below(3) {
+            0 => push_structure(&mut out, AdType::Flags.into(), &[0x06, 0x00]),
+            1 => push_structure(&mut out, AdType::Appearance.into(), &[0x00, 0x00, 0x00]),
+            _ => push_structure(&mut out, AdType::TxPowerLevel.into(), &[0x00, 0x00]),
+        },
+        Malformation::InvalidUtf8Name => push_structure(
+            &mut out,
+            AdType::CompleteLocalName.into(),
+            &[b'b', 0xC3, 0x28],
+        ),
+        Malformation::PartialUuid => push_structure(
+            &mut out,
+            AdType::CompleteList16bitUUID.into(),
+            &[0x0F, 0x18, 0x0A],
+        ),
+    }
+    out
+}
+/// Endless seeded corpus. Every `malformed_every`th entry (4th by default) is malformed, cycling
+/// through [`Malformation::ALL`].
+#[derive(Clone, Debug)]
+pub struct AdCorpus {
+    rng: CorpusRng,
+    index: usize,
+    pub malformed_every: usize,
+}
+impl AdCorpus {
+    pub fn new(seed: u64) -> AdCorpus {
+        AdCorpus {
+            rng: CorpusRng::new(seed),
+            index: 0,
+            malformed_every: 4,
+        }
+    }
+    /// Only valid payloads.
+    pub fn valid_only(seed: u64) -> AdCorpus {
+        AdCorpus {
+            malformed_every: 0,
+            ..AdCorpus::new(seed)
+        }
+    }
+}
+impl Iterator for AdCorpus {
+    type Item = CorpusEntry;
+
+    fn next(&mut self) -> Option<CorpusEntry> {
+        self.index += 1;
+        let malformation = if self.malformed_every != 0 && self.index % self.malformed_every == 0 {
+            let i = self.index / self.malformed_every;
+            Some(Malformation::ALL[i % Malformation::ALL.len()])
+        } else {
+            None
+        };
+        let payload = match malformation {
+            Some(m) => malformed_payload(&mut self.rng, m),
+            None => valid_payload(&mut self.rng),
+        };
+        Some(CorpusEntry {
+            payload,
+            malformation,
+        })
+    }
+}
+/// Build a corpus entry from fuzzer input. The first byte picks valid (`0`) or a
+/// [`Malformation`], the rest seeds the generator.
+pub fn payload_from_bytes(data: &[u8]) -> CorpusEntry {
+    let (selector, rest) = match data.split_first() {
+        Some((selector, rest)) => (usize::from(*selector), rest),
+        None => (0, data),
+    };
+    let seed = rest
+        .iter()
+        .take(8)
+        .fold(0_u64, |seed, b| seed << 8 | u64::from(*b));
+    let mut rng = CorpusRng::new(seed);
+    let malformation = match selector % (Malformation::ALL.len() + 1) {
+        0 => None,
+        i => Some(Malformation::ALL[i - 1]),
+    };
+    let payload = match malformation {
+        Some(m) => malformed_payload(&mut rng, m),
+        None => valid_payload(&mut rng),
+    };
+    CorpusEntry {
+        payload,
+        malformation,
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::le::advertisement::RawAdvertisement;
+    #[test]
+    fn test_corpus() {
+        for entry in AdCorpus::new(7).take(2_000) {
+            let structures = RawAdvertisement(entry.payload.as_slice()).iter().count();
+            match entry.malformation {
+                None => {
+                    assert!(entry.payload.len() <= MAX_ADV_LEN);
+                    // Every byte belongs to a known structure.
+                    let parsed: usize = RawAdvertisement(entry.payload.as_slice())
+                        .iter()
+                        .map(|s| s.buf.as_ref().len() + 2)
+                        .sum();
+                    assert_eq!(parsed, entry.payload.len(), "{:02X?}", entry.payload);
+                    assert!(structures <= 5);
+                }
+                Some(Malformation::Oversized) => assert!(entry.payload.len() > MAX_ADV_LEN),
+                Some(_) => (),
+            }
+        }
+        assert_eq!(
+            AdCorpus::new(3).take(10).collect::<Vec<_>>(),
+            AdCorpus::new(3).take(10).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            payload_from_bytes(&[1, 2, 3]).malformation,
+            Some(Malformation::LengthOverrun)
+        );
+    }
+}
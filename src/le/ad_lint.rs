@@ -0,0 +1,324 @@
+//! Advertising payload compliance checks. [`lint`] walks the advertising and scan response data
+//! the way a scanner would and reports everything the Core Specification Supplement (Part A)
+//! rules out or that scanners commonly trip on: missing or misplaced Flags, repeated
+//! structures, service data for services that aren't listed, bad structure lengths, etc. Run it
+//! on payloads before they are sent to the controller (in tests or debug builds) to catch
+//! mistakes that controllers happily accept but phones ignore.
+use crate::le::advertisement::{AdType, MAX_ADV_LEN};
+use crate::le::advertiser::AdvertisingType;
+use crate::le::profile::ServiceUUID;
+use crate::BT_ADDRESS_LEN;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Which payload a warning is about.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum Payload {
+    AdvertisingData,
+    ScanResponseData,
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum AdLint {
+    /// The payload is `len` bytes but only `max_len` can be sent.
+    TooLong { len: usize, max_len: usize },
+    /// The structure's length byte runs past the end of the payload.
+    Truncated,
+    /// Non-zero bytes after a zero length byte (which ends the significant part).
+    TrailingData,
+    /// `ad_type` data isn't a valid length for the type.
+    BadLength { ad_type: AdType, len: usize },
+    /// Connectable advertising data without a Flags structure (or with no discoverable mode
+    /// set).
+    MissingFlags,
+    /// Flags in the scan response. Flags may only be advertised.
+    FlagsInScanResponse,
+    /// Both LE Limited and LE General Discoverable Mode are set.
+    ConflictingFlags,
+    /// `ad_type` appears more than once but may only appear once (in both payloads together).
+    Duplicate { ad_type: AdType },
+    /// More than one local name (shortened or complete).
+    MultipleNames,
+    /// The local name isn't UTF-8.
+    InvalidName,
+    /// Service data for a service not in any service UUID list.
+    UnlistedServiceData { uuid: ServiceUUID },
+}
+/// An [`AdLint`] and where it was found. `offset` is the start of the structure (its length
+/// byte) or `0` for the whole payload.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct AdWarning {
+    pub payload: Payload,
+    pub offset: usize,
+    pub lint: AdLint,
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct AdLintOptions {
+    /// Whether the advertising PDU is connectable (Flags are required).
+    pub is_connectable: bool,
+    /// Longest payload that can be sent ([`MAX_ADV_LEN`] for legacy advertising).
+    pub max_len: usize,
+}
+impl AdLintOptions {
+    /// Connectable legacy advertising.
+    pub const DEFAULT: AdLintOptions = AdLintOptions {
+        is_connectable: true,
+        max_len: MAX_ADV_LEN,
+    };
+    /// Legacy advertising with `advertising_type`.
+    pub fn legacy(advertising_type: AdvertisingType) -> AdLintOptions {
+        AdLintOptions {
+            is_connectable: advertising_type.is_connectable(),
+            max_len: MAX_ADV_LEN,
+        }
+    }
+}
+impl Default for AdLintOptions {
+    fn default() -> Self {
+        AdLintOptions::DEFAULT
+    }
+}
+/// AD types that may only appear once across the advertising data and scan response.
+const SINGLE_TYPES: [AdType; 7] = [
+    AdType::Flags,
+    AdType::TxPowerLevel,
+    AdType::Appearance,
+    AdType::AdvertisingInterval,
+    AdType::LEDeviceAddress,
+    AdType::LERole,
+    AdType::ClassOfDevice,
+];
+/// Returns if `len` bytes of data is valid for `ad_type`. Types without a fixed layout are
+/// always valid.
+fn is_valid_len(ad_type: AdType, len: usize) -> bool {
+    match ad_type {
+        AdType::Flags | AdType::TxPowerLevel | AdType::LERole => len == 1,
+        AdType::Appearance | AdType::AdvertisingInterval => len == 2,
+        AdType::ClassOfDevice => len == 3,
+        AdType::LEDeviceAddress => len == BT_ADDRESS_LEN + 1,
+        AdType::PublicTargetAddress | AdType::RandomTargetAddress => {
+            len > 0 && len.is_multiple_of(BT_ADDRESS_LEN)
+        }
+        AdType::SlaveConnectionIntervalRange => len == 4,
+        AdType::ManufacturerData => len >= 2,
+        ad_type => match (list_uuid_len(ad_type), service_data_uuid_len(ad_type)) {
+            (Some(uuid_len), _) => len.is_multiple_of(uuid_len),
+            (_, Some(uuid_len)) => len >= uuid_len,
+            (None, None) => true,
+        },
+    }
+}
+fn list_uuid_len(ad_type: AdType) -> Option<usize> {
+    match ad_type {
+        AdType::IncompleteList16bitUUID | AdType::CompleteList16bitUUID => Some(2),
+        AdType::IncompleteList32bitUUID | AdType::CompleteList32bitUUID => Some(4),
+        AdType::IncompleteList128bitUUID | AdType::CompleteList128bitUUID => Some(16),
+        _ => None,
+    }
+}
+fn service_data_uuid_len(ad_type: AdType) -> Option<usize> {
+    match ad_type {
+        AdType::ServiceData => Some(2),
+        AdType::ServiceData32bitUUID => Some(4),
+        AdType::ServiceData128bitUUID => Some(16),
+        _ => None,
+    }
+}
+/// One AD structure found by [`structures`].
+struct Structure<'a> {
+    payload: Payload,
+    offset: usize,
+    /// `None` for types this crate doesn't know.
+    ad_type: Option<AdType>,
+    data: &'a [u8],
+}
+/// Split `buf` into structures, pushing framing problems to `warnings`.
+fn structures<'a>(
+    payload: Payload,
+    buf: &'a [u8],
+    max_len: usize,
+    found: &mut Vec<Structure<'a>>,
+    warnings: &mut Vec<AdWarning>,
+) {
+    let mut warn = |offset, lint| {
+        warnings.push(AdWarning {
+            payload,
+            offset,
+            lint,
+        })
+    };
+    if buf.len() > max_len {
+        warn(
+            0,
+            AdLint::TooLong {
+                len: buf.len(),
+                max_len,
+            },
+        );
+    }
+    let mut offset = 0;
+    while offset < buf.len() {
+        let len = usize::from(buf[offset]);
+        if len == 0 {
+            if buf[offset..].iter().any(|b| *b != 0) {
+                warn(offset, AdLint::TrailingData);
+            }
+            return;
+        }
+        if offset + len >= buf.len() {
+            warn(offset, AdLint::Truncated);
+            return;
+        }
+        found.push(Structure {
+            payload,
+            offset,
+            ad_type: AdType::try_from(buf[offset + 1]).ok(),
+            data: &buf[offset + 2..offset + 1 + len],
+        });
+        offset += len + 1;
+    }
+}
+/// Check `advertising_data` and `scan_response_data` (empty if there's no scan response).
+/// Framing problems (lengths) come first, then the structures' warnings in payload order.
+pub fn lint(
+    advertising_data: &[u8],
+    scan_response_data: &[u8],
+    options: &AdLintOptions,
+) -> Vec<AdWarning> {
+    let mut warnings = Vec::new();
+    let mut found = Vec::new();
+    for (payload, buf) in [
+        (Payload::AdvertisingData, advertising_data),
+        (Payload::ScanResponseData, scan_response_data),
+    ] {
+        structures(payload, buf, options.max_len, &mut found, &mut warnings);
+    }
+    let listed: Vec<ServiceUUID> = found
+        .iter()
+        .filter_map(|s| Some((s.data, list_uuid_len(s.ad_type?)?)))
+        .flat_map(|(data, uuid_len)| data.chunks_exact(uuid_len))
+        .filter_map(ServiceUUID::from_le_bytes)
+        .collect();
+    let mut has_name = false;
+    let mut is_discoverable = false;
+    for (i, structure) in found.iter().enumerate() {
+        let ad_type = match structure.ad_type {
+            Some(ad_type) => ad_type,
+            None => continue,
+        };
+        let mut warn = |lint| {
+            warnings.push(AdWarning {
+                payload: structure.payload,
+                offset: structure.offset,
+                lint,
+            })
+        };
+        let data = structure.data;
+        if !is_valid_len(ad_type, data.len()) {
+            warn(AdLint::BadLength {
+                ad_type,
+                len: data.len(),
+            });
+            continue;
+        }
+        if SINGLE_TYPES.contains(&ad_type) && found[..i].iter().any(|s| s.ad_type == Some(ad_type))
+        {
+            warn(AdLint::Duplicate { ad_type });
+        }
+        match ad_type {
+            AdType::Flags => {
+                if structure.payload == Payload::ScanResponseData {
+                    warn(AdLint::FlagsInScanResponse);
+                } else {
+                    if data[0] & 0x03 == 0x03 {
+                        warn(AdLint::ConflictingFlags);
+                    }
+                    is_discoverable = data[0] & 0x03 != 0;
+                }
+            }
+            AdType::ShortenLocalName | AdType::CompleteLocalName => {
+                if has_name {
+                    warn(AdLint::MultipleNames);
+                }
+                has_name = true;
+                if core::str::from_utf8(data).is_err() {
+                    warn(AdLint::InvalidName);
+                }
+            }
+            ad_type => {
+                if let Some(uuid_len) = service_data_uuid_len(ad_type) {
+                    match ServiceUUID::from_le_bytes(&data[..uuid_len]) {
+                        Some(uuid) if !listed.contains(&uuid) => {
+                            warn(AdLint::UnlistedServiceData { uuid })
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+    if options.is_connectable && !advertising_data.is_empty() && !is_discoverable {
+        warnings.push(AdWarning {
+            payload: Payload::AdvertisingData,
+            offset: 0,
+            lint: AdLint::MissingFlags,
+        });
+    }
+    warnings
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uuid::UUID16;
+    #[test]
+    fn test_lint() {
+        // Flags, battery service UUID, battery service data and a name.
+        let adv = [
+            0x02, 0x01, 0x06, 0x03, 0x03, 0x0F, 0x18, 0x04, 0x16, 0x0F, 0x18, 0x64, 0x03, 0x09,
+            b'a', b'b',
+        ];
+        assert_eq!(lint(&adv, &[], &AdLintOptions::DEFAULT), Vec::new());
+        // Flags and a second name in the scan response, unlisted service data.
+        let scan_response = [0x02, 0x01, 0x06, 0x02, 0x08, b'a', 0x03, 0x16, 0x0A, 0x18];
+        let warnings = lint(&adv, &scan_response, &AdLintOptions::DEFAULT);
+        let lints: Vec<AdLint> = warnings.iter().map(|w| w.lint).collect();
+        assert_eq!(
+            lints,
+            [
+                AdLint::Duplicate {
+                    ad_type: AdType::Flags
+                },
+                AdLint::FlagsInScanResponse,
+                AdLint::MultipleNames,
+                AdLint::UnlistedServiceData {
+                    uuid: ServiceUUID::UUID16(UUID16(0x180A))
+                },
+            ]
+        );
+        assert_eq!(warnings[2].payload, Payload::ScanResponseData);
+        assert_eq!(warnings[2].offset, 3);
+        // Connectable without Flags, a truncated structure and too much data.
+        let mut long = alloc::vec![0x02, 0x19, 0x00];
+        long.resize(33, 0x1F);
+        let lints: Vec<AdLint> = lint(&long, &[], &AdLintOptions::DEFAULT)
+            .iter()
+            .map(|w| w.lint)
+            .collect();
+        assert_eq!(
+            lints,
+            [
+                AdLint::TooLong {
+                    len: 33,
+                    max_len: 31
+                },
+                AdLint::Truncated,
+                AdLint::BadLength {
+                    ad_type: AdType::Appearance,
+                    len: 1
+                },
+                AdLint::MissingFlags,
+            ]
+        );
+        let non_connectable = AdLintOptions::legacy(AdvertisingType::AdvNonnConnInd);
+        assert_eq!(lint(&[0x02, 0x0A, 0x00], &[], &non_connectable), Vec::new());
+    }
+}
@@ -0,0 +1,256 @@
+//! Advertising payload templates. An [`AdTemplate`] is laid out once with fixed AD structures and
+//! [`AdSlot`]s for the fields that change (counters, sensor values, battery level, etc). Updating a
+//! slot writes straight into the packed payload instead of rebuilding the whole advertisement.
+use crate::le::advertisement::{
+    AdStructureType, AdType, RawAdvertisement, StaticAdvBuffer, MAX_ADV_LEN,
+};
+use crate::{CompanyID, PackError};
+use core::convert::TryFrom;
+
+/// Offset of the power byte of the first TX Power Level structure in a raw advertising
+/// `payload`, or `None` if it doesn't have one.
+pub fn tx_power_level_offset(payload: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    while offset + 2 <= payload.len() {
+        let len = usize::from(payload[offset]);
+        if len == 0 || offset + len >= payload.len() {
+            return None;
+        }
+        if payload[offset + 1] == u8::from(AdType::TxPowerLevel) && len == 2 {
+            return Some(offset + 2);
+        }
+        offset += len + 1;
+    }
+    None
+}
+/// Write `dbm` into the TX Power Level structure of `payload`. Returns `false` if it doesn't
+/// have one.
+pub fn fill_tx_power_level(payload: &mut [u8], dbm: i8) -> bool {
+    match tx_power_level_offset(payload) {
+        Some(offset) => {
+            payload[offset] = dbm as u8;
+            true
+        }
+        None => false,
+    }
+}
+/// Location of a changeable field inside an [`AdTemplate`] payload.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct AdSlot {
+    offset: u8,
+    len: u8,
+}
+impl AdSlot {
+    /// Offset of the slot in the packed advertisement.
+    pub fn offset(self) -> usize {
+        usize::from(self.offset)
+    }
+    pub fn len(self) -> usize {
+        usize::from(self.len)
+    }
+    pub fn is_empty(self) -> bool {
+        self.len == 0
+    }
+}
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Default)]
+pub struct AdTemplate {
+    adv: RawAdvertisement<StaticAdvBuffer>,
+    dirty: bool,
+}
+impl AdTemplate {
+    pub fn new() -> AdTemplate {
+        AdTemplate {
+            adv: RawAdvertisement::new(),
+            dirty: true,
+        }
+    }
+    /// Append a fixed AD structure.
+    pub fn push<AdStruct: AdStructureType>(
+        &mut self,
+        ad_struct: &AdStruct,
+    ) -> Result<(), PackError> {
+        self.adv.insert(ad_struct)?;
+        self.dirty = true;
+        Ok(())
+    }
+    /// Append an AD structure of `ad_type` starting with the fixed `prefix` followed by a
+    /// `len` byte slot (initially zeroed).
+    pub fn slot(
+        &mut self,
+        ad_type: AdType,
+        prefix: &[u8],
+        len: usize,
+    ) -> Result<AdSlot, PackError> {
+        let start = self.adv.as_ref().len();
+        let data_len = prefix.len() + len;
+        let total_len = start + 2 + data_len;
+        if total_len > StaticAdvBuffer::max_size() {
+            return Err(PackError::BadLength {
+                expected: total_len,
+                got: StaticAdvBuffer::max_size(),
+            });
+        }
+        let buf = &mut self.adv.0;
+        buf.resize(total_len);
+        let bytes = buf.as_mut();
+        bytes[start] = u8::try_from(data_len + 1).map_err(|_| PackError::InvalidFields)?;
+        bytes[start + 1] = ad_type.into();
+        bytes[start + 2..start + 2 + prefix.len()].copy_from_slice(prefix);
+        bytes[start + 2 + prefix.len()..]
+            .iter_mut()
+            .for_each(|b| *b = 0);
+        self.dirty = true;
+        Ok(AdSlot {
+            offset: u8::try_from(start + 2 + prefix.len()).map_err(|_| PackError::InvalidFields)?,
+            len: u8::try_from(len).map_err(|_| PackError::InvalidFields)?,
+        })
+    }
+    /// Append Manufacturer Specific Data for `company_id` with a `len` byte slot after `prefix`.
+    pub fn manufacturer_slot(
+        &mut self,
+        company_id: CompanyID,
+        prefix: &[u8],
+        len: usize,
+    ) -> Result<AdSlot, PackError> {
+        let mut full_prefix = [0_u8; MAX_ADV_LEN];
+        let prefix_len = CompanyID::byte_len() + prefix.len();
+        if prefix_len > full_prefix.len() {
+            return Err(PackError::BadLength {
+                expected: prefix_len,
+                got: full_prefix.len(),
+            });
+        }
+        full_prefix[..2].copy_from_slice(&company_id.0.to_le_bytes());
+        full_prefix[2..prefix_len].copy_from_slice(prefix);
+        self.slot(AdType::ManufacturerData, &full_prefix[..prefix_len], len)
+    }
+    /// Append a TX Power Level structure to be filled in later with [`AdTemplate::fill_tx_power`]
+    /// (or set through the returned slot).
+    pub fn tx_power_slot(&mut self) -> Result<AdSlot, PackError> {
+        self.slot(AdType::TxPowerLevel, &[], 1)
+    }
+    /// Write `dbm` into the TX Power Level structure (if there is one). Returns if the template
+    /// has one.
+    pub fn fill_tx_power(&mut self, dbm: i8) -> bool {
+        let offset = match tx_power_level_offset(self.adv.as_ref()) {
+            Some(offset) => offset,
+            None => return false,
+        };
+        let byte = &mut self.adv.0.as_mut()[offset];
+        if *byte != dbm as u8 {
+            *byte = dbm as u8;
+            self.dirty = true;
+        }
+        true
+    }
+    /// Overwrite `slot` with `value`. `value` must be exactly `slot.len()` bytes.
+    pub fn set(&mut self, slot: AdSlot, value: &[u8]) -> Result<(), PackError> {
+        let bytes = self.slot_bytes_mut(slot)?;
+        PackError::expect_length(bytes.len(), value)?;
+        if bytes != value {
+            bytes.copy_from_slice(value);
+            self.dirty = true;
+        }
+        Ok(())
+    }
+    pub fn set_u8(&mut self, slot: AdSlot, value: u8) -> Result<(), PackError> {
+        self.set(slot, &[value])
+    }
+    pub fn set_u16_le(&mut self, slot: AdSlot, value: u16) -> Result<(), PackError> {
+        self.set(slot, &value.to_le_bytes())
+    }
+    pub fn set_u32_le(&mut self, slot: AdSlot, value: u32) -> Result<(), PackError> {
+        self.set(slot, &value.to_le_bytes())
+    }
+    /// Treat `slot` as a little endian counter and add 1 to it (wrapping).
+    pub fn increment(&mut self, slot: AdSlot) -> Result<(), PackError> {
+        let bytes = self.slot_bytes_mut(slot)?;
+        for b in bytes.iter_mut() {
+            let (next, carry) = b.overflowing_add(1);
+            *b = next;
+            if !carry {
+                break;
+            }
+        }
+        self.dirty = true;
+        Ok(())
+    }
+    pub fn get(&self, slot: AdSlot) -> Option<&[u8]> {
+        self.adv
+            .as_ref()
+            .get(slot.offset()..slot.offset() + slot.len())
+    }
+    fn slot_bytes_mut(&mut self, slot: AdSlot) -> Result<&mut [u8], PackError> {
+        let end = slot.offset() + slot.len();
+        let buf = self.adv.0.as_mut();
+        if end > buf.len() {
+            return Err(PackError::bad_index(end));
+        }
+        Ok(&mut buf[slot.offset()..end])
+    }
+    /// The rendered advertisement.
+    pub fn advertisement(&self) -> &RawAdvertisement<StaticAdvBuffer> {
+        &self.adv
+    }
+    /// Returns if the payload changed since the last [`AdTemplate::take_dirty`].
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+    /// Returns if the payload changed and clears the flag. Lets callers skip sending
+    /// `SetAdvertisingData` when nothing changed.
+    pub fn take_dirty(&mut self) -> bool {
+        core::mem::replace(&mut self.dirty, false)
+    }
+}
+impl AsRef<[u8]> for AdTemplate {
+    fn as_ref(&self) -> &[u8] {
+        self.adv.as_ref()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::le::advertisement_structures::flags::Flags;
+    use core::convert::TryFrom;
+    #[test]
+    fn test_slots() {
+        let mut template = AdTemplate::new();
+        template.push(&Flags::try_from(0x06).unwrap()).unwrap();
+        let counter = template
+            .manufacturer_slot(CompanyID(0x0059), &[0xAB], 2)
+            .unwrap();
+        let battery = template
+            .slot(AdType::ServiceData, &[0x0F, 0x18], 1)
+            .unwrap();
+        assert!(template.take_dirty());
+        template.set_u16_le(counter, 0x01FF).unwrap();
+        template.increment(counter).unwrap();
+        template.set_u8(battery, 87).unwrap();
+        assert!(template.take_dirty());
+        template.set_u8(battery, 87).unwrap();
+        assert!(!template.take_dirty());
+        assert_eq!(template.get(counter), Some(&[0x00, 0x02][..]));
+        let structs: alloc::vec::Vec<_> = template.advertisement().iter().collect();
+        assert_eq!(structs.len(), 3);
+        assert_eq!(structs[1].ad_type, AdType::ManufacturerData);
+        assert_eq!(structs[1].buf.as_ref(), &[0x59, 0x00, 0xAB, 0x00, 0x02]);
+        assert_eq!(structs[2].buf.as_ref(), &[0x0F, 0x18, 87]);
+        assert!(template.set(battery, &[1, 2]).is_err());
+        assert!(template.slot(AdType::ServiceData, &[], 30).is_err());
+    }
+    #[test]
+    fn test_tx_power_fill() {
+        let mut template = AdTemplate::new();
+        template.push(&Flags::try_from(0x06).unwrap()).unwrap();
+        assert!(!template.fill_tx_power(-4));
+        let slot = template.tx_power_slot().unwrap();
+        template.take_dirty();
+        assert!(template.fill_tx_power(-4));
+        assert!(template.take_dirty());
+        assert_eq!(template.get(slot), Some(&[0xFC][..]));
+        let mut payload = [0x02, 0x01, 0x06, 0x02, 0x0A, 0x00, 0x01, 0x01];
+        assert!(fill_tx_power_level(&mut payload, 8));
+        assert_eq!(payload[5], 8);
+        assert!(!fill_tx_power_level(&mut payload[..5], 8));
+    }
+}
@@ -0,0 +1,271 @@
+//! Writing AD structures into an advertising payload. [`AdWriter`] prefixes every structure with
+//! its length and type and, when one doesn't fit, reports which one and by how much
+//! ([`AdWriteError::Overflow`]) instead of a generic [`PackError`].
+use crate::bytes::truncate_name;
+use crate::le::advertisement::{AdStructureType, AdType};
+use crate::PackError;
+use core::convert::TryFrom;
+
+/// Length and type bytes in front of every AD structure.
+pub const AD_HEADER_LEN: usize = 2;
+/// Longest AD structure data. The length byte also counts the type byte.
+pub const MAX_AD_DATA_LEN: usize = u8::MAX as usize - 1;
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum AdWriteError {
+    /// The `ad_type` structure needs `required` bytes (header included) but only `available`
+    /// are left in the payload.
+    Overflow {
+        ad_type: AdType,
+        required: usize,
+        available: usize,
+    },
+    /// The structure data is longer than [`MAX_AD_DATA_LEN`] so its length doesn't fit in the
+    /// length byte.
+    TooLong { ad_type: AdType, len: usize },
+    /// The structure failed to pack itself.
+    Pack(PackError),
+}
+impl From<PackError> for AdWriteError {
+    fn from(e: PackError) -> Self {
+        AdWriteError::Pack(e)
+    }
+}
+impl From<AdWriteError> for PackError {
+    fn from(e: AdWriteError) -> Self {
+        match e {
+            AdWriteError::Overflow {
+                required,
+                available,
+                ..
+            } => PackError::BadLength {
+                expected: required,
+                got: available,
+            },
+            AdWriteError::TooLong { .. } => PackError::InvalidFields,
+            AdWriteError::Pack(e) => e,
+        }
+    }
+}
+impl crate::error::Error for AdWriteError {}
+/// What [`AdWriter::write_shortened_name`] does with a name that doesn't fit.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum NameTruncation {
+    /// Return [`AdWriteError::Overflow`].
+    Error,
+    /// Cut the name to the space left (on a UTF-8 character boundary). Only an overflow if not
+    /// even `min_len` bytes fit.
+    Truncate { min_len: usize },
+}
+impl Default for NameTruncation {
+    fn default() -> Self {
+        NameTruncation::Error
+    }
+}
+/// How [`AdWriter::write_local_name`] wrote a name.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum NameDecision<'n> {
+    /// Fit as a Complete Local Name.
+    Complete(&'n str),
+    /// Didn't fit so only this prefix was written, as a Shortened Local Name.
+    Shortened(&'n str),
+}
+impl<'n> NameDecision<'n> {
+    /// The name that was written.
+    pub fn name(self) -> &'n str {
+        match self {
+            NameDecision::Complete(name) | NameDecision::Shortened(name) => name,
+        }
+    }
+    pub fn is_shortened(self) -> bool {
+        match self {
+            NameDecision::Complete(_) => false,
+            NameDecision::Shortened(_) => true,
+        }
+    }
+}
+/// Appends length prefixed AD structures to `buf`. Works for legacy (31 byte) and extended
+/// advertising payloads.
+pub struct AdWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    pub name_truncation: NameTruncation,
+    /// Bytes [`AdWriter::write_local_name`] leaves free when it has to shorten the name (for
+    /// structures written after it).
+    pub name_reserve: usize,
+}
+impl<'a> AdWriter<'a> {
+    pub fn new(buf: &'a mut [u8]) -> AdWriter<'a> {
+        AdWriter {
+            buf,
+            len: 0,
+            name_truncation: NameTruncation::default(),
+            name_reserve: 0,
+        }
+    }
+    /// Bytes written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// Bytes left in the payload.
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.len
+    }
+    /// Data bytes left for one more structure (after its header).
+    pub fn remaining_data(&self) -> usize {
+        self.remaining()
+            .saturating_sub(AD_HEADER_LEN)
+            .min(MAX_AD_DATA_LEN)
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+    /// Make room for an `ad_type` structure with `data_len` bytes and write its header. Returns
+    /// the data bytes.
+    fn reserve(&mut self, ad_type: AdType, data_len: usize) -> Result<&mut [u8], AdWriteError> {
+        let len_u8 = u8::try_from(data_len + 1).map_err(|_| AdWriteError::TooLong {
+            ad_type,
+            len: data_len,
+        })?;
+        let required = AD_HEADER_LEN + data_len;
+        if required > self.remaining() {
+            return Err(AdWriteError::Overflow {
+                ad_type,
+                required,
+                available: self.remaining(),
+            });
+        }
+        let start = self.len;
+        self.buf[start] = len_u8;
+        self.buf[start + 1] = ad_type.into();
+        Ok(&mut self.buf[start + AD_HEADER_LEN..start + required])
+    }
+    pub fn write<AdStruct: AdStructureType>(
+        &mut self,
+        ad_struct: &AdStruct,
+    ) -> Result<(), AdWriteError> {
+        let data_len = ad_struct.byte_len();
+        ad_struct.pack_into(self.reserve(ad_struct.ad_type(), data_len)?)?;
+        self.len += AD_HEADER_LEN + data_len;
+        Ok(())
+    }
+    /// Write an `ad_type` structure containing `data` as is.
+    pub fn write_raw(&mut self, ad_type: AdType, data: &[u8]) -> Result<(), AdWriteError> {
+        self.reserve(ad_type, data.len())?.copy_from_slice(data);
+        self.len += AD_HEADER_LEN + data.len();
+        Ok(())
+    }
+    /// Write a Shortened Local Name, truncated if allowed by `name_truncation`. Returns the name
+    /// that was written.
+    pub fn write_shortened_name<'n>(&mut self, name: &'n str) -> Result<&'n str, AdWriteError> {
+        self.write_shortened_name_within(name, self.remaining_data())
+    }
+    fn write_shortened_name_within<'n>(
+        &mut self,
+        name: &'n str,
+        max_len: usize,
+    ) -> Result<&'n str, AdWriteError> {
+        let name = match self.name_truncation {
+            NameTruncation::Error => name,
+            NameTruncation::Truncate { min_len } => {
+                let truncated = truncate_name(name, max_len);
+                if truncated.len() < min_len.min(name.len()) {
+                    return Err(AdWriteError::Overflow {
+                        ad_type: AdType::ShortenLocalName,
+                        required: AD_HEADER_LEN + min_len.min(name.len()),
+                        available: self.remaining(),
+                    });
+                }
+                truncated
+            }
+        };
+        self.write_raw(AdType::ShortenLocalName, name.as_bytes())?;
+        Ok(name)
+    }
+    /// Write a Complete Local Name or, if it doesn't fit and `name_truncation` allows it, fall
+    /// back to a Shortened Local Name leaving `name_reserve` bytes free. Most stacks do the
+    /// same; the returned [`NameDecision`] says which one was written.
+    pub fn write_local_name<'n>(
+        &mut self,
+        name: &'n str,
+    ) -> Result<NameDecision<'n>, AdWriteError> {
+        let overflow = match self.write_raw(AdType::CompleteLocalName, name.as_bytes()) {
+            Ok(()) => return Ok(NameDecision::Complete(name)),
+            Err(overflow @ AdWriteError::Overflow { .. }) => overflow,
+            Err(e) => return Err(e),
+        };
+        if self.name_truncation == NameTruncation::Error {
+            return Err(overflow);
+        }
+        let max_len = self
+            .remaining()
+            .saturating_sub(self.name_reserve)
+            .saturating_sub(AD_HEADER_LEN)
+            .min(MAX_AD_DATA_LEN);
+        Ok(NameDecision::Shortened(
+            self.write_shortened_name_within(name, max_len)?,
+        ))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::le::advertisement::MAX_ADV_LEN;
+    use crate::le::advertisement_structures::flags::Flags;
+    #[test]
+    fn test_overflow() {
+        let mut buf = [0_u8; MAX_ADV_LEN];
+        let mut writer = AdWriter::new(&mut buf);
+        writer.write(&Flags::try_from(0x06).unwrap()).unwrap();
+        writer
+            .write_raw(AdType::ManufacturerData, &[0x59, 0x00, 1, 2, 3])
+            .unwrap();
+        assert_eq!(writer.remaining(), 21);
+        assert_eq!(
+            writer.write_raw(AdType::ServiceData, &[0; 20]),
+            Err(AdWriteError::Overflow {
+                ad_type: AdType::ServiceData,
+                required: 22,
+                available: 21,
+            })
+        );
+        // Nothing is written on failure.
+        assert_eq!(writer.len(), 10);
+        assert_eq!(
+            writer.write_shortened_name("a very long device name"),
+            Err(AdWriteError::Overflow {
+                ad_type: AdType::ShortenLocalName,
+                required: 25,
+                available: 21,
+            })
+        );
+        writer.name_truncation = NameTruncation::Truncate { min_len: 4 };
+        assert_eq!(
+            writer.write_shortened_name("a very long device ñame"),
+            Ok("a very long device ")
+        );
+        assert_eq!(writer.remaining(), 0);
+        assert_eq!(&writer.as_bytes()[..3], &[0x02, 0x01, 0x06]);
+    }
+    #[test]
+    fn test_local_name_fallback() {
+        let mut buf = [0_u8; MAX_ADV_LEN];
+        let mut writer = AdWriter::new(&mut buf);
+        assert_eq!(
+            writer.write_local_name("sensor"),
+            Ok(NameDecision::Complete("sensor"))
+        );
+        let name = "kitchen temperature sensor";
+        assert!(writer.write_local_name(name).is_err());
+        writer.name_truncation = NameTruncation::Truncate { min_len: 4 };
+        writer.name_reserve = 5;
+        let decision = writer.write_local_name(name).unwrap();
+        assert!(decision.is_shortened());
+        assert_eq!(decision.name(), "kitchen temperat");
+        assert_eq!(writer.remaining(), 5);
+        // Too little space left for `min_len`.
+        assert!(writer.write_local_name(name).is_err());
+    }
+}
@@ -1 +1,103 @@
+//! What a backend (HCI controller, `bluetoothd`, WinRT) can do. Cross-platform applications can
+//! check [`Backend::capabilities`] and adapt (hide the advertising UI, skip passive scanning,
+//! etc) instead of finding the limits through runtime errors.
+use crate::hci::le::extended_advertise::ExtendedAdvertisingData;
+use crate::hci::le::features::{LEFeature, LEFeatures};
+use crate::le::advertisement::MAX_ADV_LEN;
 
+/// Capability matrix of one backend.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct Capabilities {
+    pub supports_advertising: bool,
+    /// Extended advertising (advertising sets, data past [`MAX_ADV_LEN`]).
+    pub supports_extended_advertising: bool,
+    pub supports_periodic_advertising: bool,
+    pub supports_active_scan: bool,
+    pub supports_passive_scan: bool,
+    /// Connections can be made (central) or accepted (peripheral).
+    pub supports_connections: bool,
+    /// The White List can be set (see [`crate::le::advertiser::Advertiser::set_whitelist`]).
+    pub supports_whitelist: bool,
+    /// Longest advertising data (and scan response) that can be set. `0` if advertising isn't
+    /// supported.
+    pub max_advertising_data_len: usize,
+}
+impl Capabilities {
+    /// Supports nothing.
+    pub const NONE: Capabilities = Capabilities {
+        supports_advertising: false,
+        supports_extended_advertising: false,
+        supports_periodic_advertising: false,
+        supports_active_scan: false,
+        supports_passive_scan: false,
+        supports_connections: false,
+        supports_whitelist: false,
+        max_advertising_data_len: 0,
+    };
+    /// Any Bluetooth 4.0 controller over HCI (legacy advertising, both scan types, connections
+    /// and the White List).
+    pub const HCI_LEGACY: Capabilities = Capabilities {
+        supports_advertising: true,
+        supports_extended_advertising: false,
+        supports_periodic_advertising: false,
+        supports_active_scan: true,
+        supports_passive_scan: true,
+        supports_connections: true,
+        supports_whitelist: true,
+        max_advertising_data_len: MAX_ADV_LEN,
+    };
+    /// HCI controller with the local LE `features`. Only legacy advertising is assumed if the
+    /// features haven't been read.
+    pub fn from_le_features(features: Option<LEFeatures>) -> Capabilities {
+        let features = match features {
+            Some(features) => features,
+            None => return Self::HCI_LEGACY,
+        };
+        let is_extended = features.contains(LEFeature::LEExtendedAdvertising);
+        Capabilities {
+            supports_extended_advertising: is_extended,
+            supports_periodic_advertising: is_extended
+                && features.contains(LEFeature::LEPeriodicAdvertising),
+            max_advertising_data_len: if is_extended {
+                ExtendedAdvertisingData::MAX_DATA_LEN
+            } else {
+                MAX_ADV_LEN
+            },
+            ..Self::HCI_LEGACY
+        }
+    }
+    /// Returns if advertising data `len` bytes long can be set.
+    pub fn fits_advertising_data(&self, len: usize) -> bool {
+        self.supports_advertising && len <= self.max_advertising_data_len
+    }
+}
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities::NONE
+    }
+}
+/// A BLE backend that can report its [`Capabilities`].
+pub trait Backend {
+    fn capabilities(&self) -> Capabilities;
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_capabilities() {
+        assert_eq!(
+            Capabilities::from_le_features(None),
+            Capabilities::HCI_LEGACY
+        );
+        let mut features = LEFeatures::from(LEFeature::LEPeriodicAdvertising);
+        // Periodic advertising needs extended advertising.
+        let legacy = Capabilities::from_le_features(Some(features));
+        assert!(!legacy.supports_periodic_advertising);
+        assert!(legacy.fits_advertising_data(31) && !legacy.fits_advertising_data(32));
+        features.enable(LEFeature::LEExtendedAdvertising);
+        let extended = Capabilities::from_le_features(Some(features));
+        assert!(extended.supports_extended_advertising && extended.supports_periodic_advertising);
+        assert!(extended.fits_advertising_data(251));
+        assert!(!Capabilities::NONE.fits_advertising_data(0));
+    }
+}
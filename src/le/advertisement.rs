@@ -202,7 +202,8 @@ impl RawAdvertisement<StaticAdvBuffer> {
         // The AdStruct byte len should always be less than MAX_AD_LEN (30) and so it should always
         // be able to fit in a u8. If the usize -> u8 conversion fails, then theres something really
         // wrong with the ad structure.
-        let len_u8 = u8::try_from(len).map_err(|_| PackError::InvalidFields)?;
+        // The length byte also counts the AdType byte.
+        let len_u8 = u8::try_from(len + 1).map_err(|_| PackError::InvalidFields)?;
         ad_struct.pack_into(&mut self.0.as_mut()[current_len + 2..])?;
         self.0.as_mut()[current_len] = len_u8;
         self.0.as_mut()[current_len + 1] = ad_struct.ad_type().into();
@@ -238,7 +239,9 @@ impl<'a> Iterator for AdStructureIterator<'a> {
         }
         let d = mem::replace(&mut self.data, &[]);
         let len = usize::from(d[0]);
-        if len + 1 > d.len() {
+        // A zero length ends the significant part (the rest is padding, as in EIR data).
+        // Structures longer than `MAX_AD_LEN` only fit in EIR data and aren't supported.
+        if len == 0 || len + 1 > d.len() || len - 1 > MAX_AD_LEN {
             return None;
         }
         let (data, rest) = d.split_at(len + 1);
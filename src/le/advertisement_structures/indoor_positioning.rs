@@ -0,0 +1,224 @@
+//! Indoor Positioning AD structure (`0x25`) from the Indoor Positioning Service.
+use crate::le::advertisement::{
+    AdStructureType, AdType, ConstAdStructType, UnpackableAdStructType,
+};
+use crate::PackError;
+
+/// Position coordinates. WGS84 in 1e-7 degrees or local in decimeters.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum Coordinates {
+    WGS84 { latitude: i32, longitude: i32 },
+    Local { north: i16, east: i16 },
+}
+impl Coordinates {
+    pub fn byte_len(self) -> usize {
+        match self {
+            Coordinates::WGS84 { .. } => 8,
+            Coordinates::Local { .. } => 4,
+        }
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct IndoorPositioning {
+    pub coordinates: Option<Coordinates>,
+    /// TX power in dBm.
+    pub tx_power: Option<i8>,
+    /// Floor number (offset by 20, so `20` is the ground floor).
+    pub floor_number: Option<u8>,
+    /// Altitude in decimeters (offset by -1000).
+    pub altitude: Option<u16>,
+    /// Raw uncertainty field (stationary bit, update time and precision).
+    pub uncertainty: Option<u8>,
+    /// Location name is available from the GATT database.
+    pub location_name_available: bool,
+}
+impl IndoorPositioning {
+    pub const AD_TYPE: AdType = AdType::IndoorPositioning;
+    pub const COORDINATES_PRESENT: u8 = 1 << 0;
+    pub const LOCAL_COORDINATES: u8 = 1 << 1;
+    pub const TX_POWER_PRESENT: u8 = 1 << 2;
+    pub const ALTITUDE_PRESENT: u8 = 1 << 3;
+    pub const FLOOR_NUMBER_PRESENT: u8 = 1 << 4;
+    pub const UNCERTAINTY_PRESENT: u8 = 1 << 5;
+    pub const LOCATION_NAME_AVAILABLE: u8 = 1 << 6;
+    pub fn flags(&self) -> u8 {
+        let mut flags = 0;
+        match self.coordinates {
+            Some(Coordinates::WGS84 { .. }) => flags |= Self::COORDINATES_PRESENT,
+            Some(Coordinates::Local { .. }) => {
+                flags |= Self::COORDINATES_PRESENT | Self::LOCAL_COORDINATES
+            }
+            None => (),
+        }
+        if self.tx_power.is_some() {
+            flags |= Self::TX_POWER_PRESENT;
+        }
+        if self.altitude.is_some() {
+            flags |= Self::ALTITUDE_PRESENT;
+        }
+        if self.floor_number.is_some() {
+            flags |= Self::FLOOR_NUMBER_PRESENT;
+        }
+        if self.uncertainty.is_some() {
+            flags |= Self::UNCERTAINTY_PRESENT;
+        }
+        if self.location_name_available {
+            flags |= Self::LOCATION_NAME_AVAILABLE;
+        }
+        flags
+    }
+}
+impl AdStructureType for IndoorPositioning {
+    fn ad_type(&self) -> AdType {
+        Self::AD_TYPE
+    }
+
+    fn byte_len(&self) -> usize {
+        1 + self.coordinates.map_or(0, Coordinates::byte_len)
+            + self.tx_power.map_or(0, |_| 1)
+            + self.floor_number.map_or(0, |_| 1)
+            + self.altitude.map_or(0, |_| 2)
+            + self.uncertainty.map_or(0, |_| 1)
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        buf[0] = self.flags();
+        let mut i = 1;
+        match self.coordinates {
+            Some(Coordinates::WGS84 {
+                latitude,
+                longitude,
+            }) => {
+                buf[i..i + 4].copy_from_slice(&latitude.to_le_bytes());
+                buf[i + 4..i + 8].copy_from_slice(&longitude.to_le_bytes());
+                i += 8;
+            }
+            Some(Coordinates::Local { north, east }) => {
+                buf[i..i + 2].copy_from_slice(&north.to_le_bytes());
+                buf[i + 2..i + 4].copy_from_slice(&east.to_le_bytes());
+                i += 4;
+            }
+            None => (),
+        }
+        if let Some(tx_power) = self.tx_power {
+            buf[i] = tx_power as u8;
+            i += 1;
+        }
+        if let Some(floor_number) = self.floor_number {
+            buf[i] = floor_number;
+            i += 1;
+        }
+        if let Some(altitude) = self.altitude {
+            buf[i..i + 2].copy_from_slice(&altitude.to_le_bytes());
+            i += 2;
+        }
+        if let Some(uncertainty) = self.uncertainty {
+            buf[i] = uncertainty;
+        }
+        Ok(())
+    }
+}
+impl UnpackableAdStructType for IndoorPositioning {
+    fn unpack_from(ad_type: AdType, buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if ad_type != Self::AD_TYPE {
+            return Err(PackError::InvalidFields);
+        }
+        // An empty Indoor Positioning AD means all information is in the GATT database.
+        let flags = match buf.first() {
+            Some(flags) => *flags,
+            None => return Ok(IndoorPositioning::default()),
+        };
+        let mut i = 1;
+        let mut take = |len: usize| -> Result<&[u8], PackError> {
+            let field = buf.get(i..i + len).ok_or(PackError::BadLength {
+                expected: i + len,
+                got: buf.len(),
+            })?;
+            i += len;
+            Ok(field)
+        };
+        let coordinates = if flags & Self::COORDINATES_PRESENT == 0 {
+            None
+        } else if flags & Self::LOCAL_COORDINATES == 0 {
+            let b = take(8)?;
+            Some(Coordinates::WGS84 {
+                latitude: i32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                longitude: i32::from_le_bytes([b[4], b[5], b[6], b[7]]),
+            })
+        } else {
+            let b = take(4)?;
+            Some(Coordinates::Local {
+                north: i16::from_le_bytes([b[0], b[1]]),
+                east: i16::from_le_bytes([b[2], b[3]]),
+            })
+        };
+        let tx_power = if flags & Self::TX_POWER_PRESENT == 0 {
+            None
+        } else {
+            Some(take(1)?[0] as i8)
+        };
+        let floor_number = if flags & Self::FLOOR_NUMBER_PRESENT == 0 {
+            None
+        } else {
+            Some(take(1)?[0])
+        };
+        let altitude = if flags & Self::ALTITUDE_PRESENT == 0 {
+            None
+        } else {
+            let b = take(2)?;
+            Some(u16::from_le_bytes([b[0], b[1]]))
+        };
+        let uncertainty = if flags & Self::UNCERTAINTY_PRESENT == 0 {
+            None
+        } else {
+            Some(take(1)?[0])
+        };
+        if i != buf.len() {
+            return Err(PackError::BadLength {
+                expected: i,
+                got: buf.len(),
+            });
+        }
+        Ok(IndoorPositioning {
+            coordinates,
+            tx_power,
+            floor_number,
+            altitude,
+            uncertainty,
+            location_name_available: flags & Self::LOCATION_NAME_AVAILABLE != 0,
+        })
+    }
+}
+impl ConstAdStructType for IndoorPositioning {
+    const AD_TYPE: AdType = AdType::IndoorPositioning;
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_indoor_positioning() {
+        let ips = IndoorPositioning {
+            coordinates: Some(Coordinates::Local {
+                north: -12,
+                east: 300,
+            }),
+            tx_power: Some(-4),
+            floor_number: Some(21),
+            altitude: None,
+            uncertainty: Some(0x41),
+            location_name_available: true,
+        };
+        let mut buf = [0_u8; 8];
+        ips.pack_into(&mut buf).unwrap();
+        assert_eq!(buf[0], 0b0111_0111);
+        assert_eq!(
+            IndoorPositioning::unpack_from(AdType::IndoorPositioning, &buf),
+            Ok(ips)
+        );
+        assert!(IndoorPositioning::unpack_from(AdType::IndoorPositioning, &buf[..7]).is_err());
+    }
+}
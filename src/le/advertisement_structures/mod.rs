@@ -3,15 +3,21 @@ use crate::le::advertisement::{AdStructureType, AdType, UnpackableAdStructType};
 use crate::PackError;
 
 pub mod flags;
+pub mod indoor_positioning;
 pub mod local_name;
 pub mod manufacturer_data;
+pub mod transport_discovery;
 pub mod tx_power_level;
+pub mod uri;
 
 pub enum Structs<Buf> {
     Flags(flags::Flags),
     LocalName(local_name::LocalName<Buf>),
     ManufacturerData(manufacturer_data::ManufacturerSpecificData<Buf>),
     TxPowerLevel(tx_power_level::TxPowerLevel),
+    URI(uri::URI<Buf>),
+    IndoorPositioning(indoor_positioning::IndoorPositioning),
+    TransportDiscoveryData(transport_discovery::TransportDiscoveryData<Buf>),
 }
 impl<Buf: AsRef<[u8]>> AdStructureType for Structs<Buf> {
     fn ad_type(&self) -> AdType {
@@ -22,6 +28,11 @@ impl<Buf: AsRef<[u8]>> AdStructureType for Structs<Buf> {
                 manufacturer_data::ManufacturerSpecificData::<Buf>::AD_TYPE
             }
             Structs::TxPowerLevel(_) => tx_power_level::TxPowerLevel::AD_TYPE,
+            Structs::URI(_) => uri::URI::<Buf>::AD_TYPE,
+            Structs::IndoorPositioning(_) => indoor_positioning::IndoorPositioning::AD_TYPE,
+            Structs::TransportDiscoveryData(_) => {
+                transport_discovery::TransportDiscoveryData::<Buf>::AD_TYPE
+            }
         }
     }
 
@@ -31,6 +42,9 @@ impl<Buf: AsRef<[u8]>> AdStructureType for Structs<Buf> {
             Structs::LocalName(l) => l.byte_len(),
             Structs::ManufacturerData(d) => d.byte_len(),
             Structs::TxPowerLevel(t) => t.byte_len(),
+            Structs::URI(u) => u.byte_len(),
+            Structs::IndoorPositioning(i) => i.byte_len(),
+            Structs::TransportDiscoveryData(t) => t.byte_len(),
         }
     }
 
@@ -40,6 +54,9 @@ impl<Buf: AsRef<[u8]>> AdStructureType for Structs<Buf> {
             Structs::LocalName(l) => l.pack_into(buf),
             Structs::ManufacturerData(d) => d.pack_into(buf),
             Structs::TxPowerLevel(t) => t.pack_into(buf),
+            Structs::URI(u) => u.pack_into(buf),
+            Structs::IndoorPositioning(i) => i.pack_into(buf),
+            Structs::TransportDiscoveryData(t) => t.pack_into(buf),
         }
     }
 }
@@ -59,6 +76,13 @@ impl<Buf: Storage<u8>> UnpackableAdStructType for Structs<Buf> {
             AdType::TxPowerLevel => Ok(Structs::TxPowerLevel(
                 tx_power_level::TxPowerLevel::unpack_from(ad_type, buf)?,
             )),
+            AdType::URI => Ok(Structs::URI(uri::URI::unpack_from(ad_type, buf)?)),
+            AdType::IndoorPositioning => Ok(Structs::IndoorPositioning(
+                indoor_positioning::IndoorPositioning::unpack_from(ad_type, buf)?,
+            )),
+            AdType::TransportDiscoveryData => Ok(Structs::TransportDiscoveryData(
+                transport_discovery::TransportDiscoveryData::unpack_from(ad_type, buf)?,
+            )),
             _ => Err(PackError::BadOpcode),
         }
     }
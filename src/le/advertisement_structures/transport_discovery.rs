@@ -0,0 +1,249 @@
+//! Transport Discovery Data AD structure (`0x26`) from the Transport Discovery Service. Made of
+//! Transport Blocks each describing one transport an organization (Wi-Fi Alliance, etc) offers.
+use crate::bytes::Storage;
+use crate::le::advertisement::{
+    AdStructureType, AdType, ConstAdStructType, UnpackableAdStructType,
+};
+use crate::{ConversionError, PackError};
+use core::convert::TryFrom;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum TransportRole {
+    NotSpecified = 0b00,
+    SeekerOnly = 0b01,
+    ProviderOnly = 0b10,
+    SeekerAndProvider = 0b11,
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum TransportState {
+    Off = 0b00,
+    On = 0b01,
+    TemporarilyUnavailable = 0b10,
+}
+impl TryFrom<u8> for TransportState {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(TransportState::Off),
+            0b01 => Ok(TransportState::On),
+            0b10 => Ok(TransportState::TemporarilyUnavailable),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct TDSFlags {
+    pub role: TransportRole,
+    /// Transport Data doesn't fit and must be read from the TDS GATT service.
+    pub data_incomplete: bool,
+    pub state: TransportState,
+}
+impl From<TDSFlags> for u8 {
+    fn from(flags: TDSFlags) -> Self {
+        flags.role as u8 | (u8::from(flags.data_incomplete) << 2) | ((flags.state as u8) << 3)
+    }
+}
+impl TryFrom<u8> for TDSFlags {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value & 0xE0 != 0 {
+            return Err(ConversionError(()));
+        }
+        Ok(TDSFlags {
+            role: match value & 0b11 {
+                0b00 => TransportRole::NotSpecified,
+                0b01 => TransportRole::SeekerOnly,
+                0b10 => TransportRole::ProviderOnly,
+                _ => TransportRole::SeekerAndProvider,
+            },
+            data_incomplete: value & (1 << 2) != 0,
+            state: TransportState::try_from((value >> 3) & 0b11)?,
+        })
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct TransportBlock<'a> {
+    /// Organization ID (assigned numbers). `0x02` is the Wi-Fi Alliance.
+    pub organization_id: u8,
+    pub flags: TDSFlags,
+    pub data: &'a [u8],
+}
+impl<'a> TransportBlock<'a> {
+    pub const HEADER_LEN: usize = 3;
+    pub fn byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.data.len()
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        buf[0] = self.organization_id;
+        buf[1] = self.flags.into();
+        buf[2] = u8::try_from(self.data.len()).map_err(|_| PackError::InvalidFields)?;
+        buf[Self::HEADER_LEN..].copy_from_slice(self.data);
+        Ok(())
+    }
+    /// Unpack the block at the start of `buf`. Returns the block and the rest of `buf`.
+    pub fn unpack_from(buf: &'a [u8]) -> Result<(TransportBlock<'a>, &'a [u8]), PackError> {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(PackError::BadLength {
+                expected: Self::HEADER_LEN,
+                got: buf.len(),
+            });
+        }
+        let len = Self::HEADER_LEN + usize::from(buf[2]);
+        if buf.len() < len {
+            return Err(PackError::BadLength {
+                expected: len,
+                got: buf.len(),
+            });
+        }
+        Ok((
+            TransportBlock {
+                organization_id: buf[0],
+                flags: TDSFlags::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?,
+                data: &buf[Self::HEADER_LEN..len],
+            },
+            &buf[len..],
+        ))
+    }
+}
+pub struct TransportBlocks<'a> {
+    buf: &'a [u8],
+}
+impl<'a> Iterator for TransportBlocks<'a> {
+    type Item = Result<TransportBlock<'a>, PackError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        match TransportBlock::unpack_from(self.buf) {
+            Ok((block, rest)) => {
+                self.buf = rest;
+                Some(Ok(block))
+            }
+            Err(e) => {
+                self.buf = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+#[derive(Copy, Clone, Debug)]
+pub struct TransportDiscoveryData<Buf> {
+    pub data: Buf,
+}
+impl<Buf> TransportDiscoveryData<Buf> {
+    pub const AD_TYPE: AdType = AdType::TransportDiscoveryData;
+    pub fn new(data: Buf) -> Self {
+        TransportDiscoveryData { data }
+    }
+}
+impl<Buf: AsRef<[u8]>> TransportDiscoveryData<Buf> {
+    pub fn blocks(&self) -> TransportBlocks<'_> {
+        TransportBlocks {
+            buf: self.data.as_ref(),
+        }
+    }
+}
+impl<Buf: Storage<u8>> TransportDiscoveryData<Buf> {
+    pub fn from_blocks(blocks: &[TransportBlock<'_>]) -> Result<Self, PackError> {
+        let len = blocks.iter().map(TransportBlock::byte_len).sum();
+        if len > Buf::max_len() {
+            return Err(PackError::BadLength {
+                expected: Buf::max_len(),
+                got: len,
+            });
+        }
+        let mut buf = Buf::with_size(len);
+        let mut i = 0;
+        for block in blocks {
+            block.pack_into(&mut buf.as_mut()[i..i + block.byte_len()])?;
+            i += block.byte_len();
+        }
+        Ok(Self::new(buf))
+    }
+}
+impl<Buf: AsRef<[u8]>> AdStructureType for TransportDiscoveryData<Buf> {
+    fn ad_type(&self) -> AdType {
+        Self::AD_TYPE
+    }
+
+    fn byte_len(&self) -> usize {
+        self.data.as_ref().len()
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        buf.copy_from_slice(self.data.as_ref());
+        Ok(())
+    }
+}
+impl<Buf: Storage<u8>> UnpackableAdStructType for TransportDiscoveryData<Buf> {
+    fn unpack_from(ad_type: AdType, buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if ad_type != Self::AD_TYPE {
+            return Err(PackError::InvalidFields);
+        }
+        let max_len = Buf::max_len();
+        if buf.len() > max_len {
+            return Err(PackError::BadLength {
+                expected: max_len,
+                got: buf.len(),
+            });
+        }
+        let tdd = Self::new(Buf::from_slice(buf));
+        for block in tdd.blocks() {
+            block?;
+        }
+        Ok(tdd)
+    }
+}
+impl<Buf: Storage<u8>> ConstAdStructType for TransportDiscoveryData<Buf> {
+    const AD_TYPE: AdType = AdType::TransportDiscoveryData;
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::le::advertisement::StaticAdvStructBuf;
+    #[test]
+    fn test_transport_blocks() {
+        let flags = TDSFlags {
+            role: TransportRole::ProviderOnly,
+            data_incomplete: false,
+            state: TransportState::On,
+        };
+        assert_eq!(u8::from(flags), 0b0000_1010);
+        let blocks = [
+            TransportBlock {
+                organization_id: 0x02,
+                flags,
+                data: &[1, 2, 3],
+            },
+            TransportBlock {
+                organization_id: 0x01,
+                flags,
+                data: &[],
+            },
+        ];
+        let tdd = TransportDiscoveryData::<StaticAdvStructBuf>::from_blocks(&blocks).unwrap();
+        assert_eq!(tdd.byte_len(), 9);
+        let unpacked = TransportDiscoveryData::<StaticAdvStructBuf>::unpack_from(
+            AdType::TransportDiscoveryData,
+            tdd.data.as_ref(),
+        )
+        .unwrap();
+        let parsed: Result<alloc::vec::Vec<_>, _> = unpacked.blocks().collect();
+        assert_eq!(parsed.unwrap(), blocks);
+        assert!(TransportDiscoveryData::<StaticAdvStructBuf>::unpack_from(
+            AdType::TransportDiscoveryData,
+            &[0x02, 0x0A, 0x05, 1],
+        )
+        .is_err());
+    }
+}
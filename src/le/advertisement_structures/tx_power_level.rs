@@ -22,6 +22,13 @@ impl TxPowerLevel {
     }
 }
 
+impl From<crate::hci::le::advertise::TxPowerLevel> for TxPowerLevel {
+    /// TX Power Level structure advertising the level the controller reported (see
+    /// [`crate::hci::adapters::le::LEAdapter::get_advertising_tx_power`]).
+    fn from(level: crate::hci::le::advertise::TxPowerLevel) -> Self {
+        TxPowerLevel::new(level.into())
+    }
+}
 impl AdStructureType for TxPowerLevel {
     fn ad_type(&self) -> AdType {
         Self::AD_TYPE
@@ -0,0 +1,77 @@
+use crate::bytes::Storage;
+use crate::le::advertisement::{
+    AdStructureType, AdType, ConstAdStructType, UnpackableAdStructType,
+};
+use crate::uri::EncodedURI;
+use crate::PackError;
+
+/// URI AD structure. Holds the URI in its encoded (scheme compressed) form.
+#[derive(Copy, Clone, Debug)]
+pub struct URI<Buf> {
+    pub encoded: Buf,
+}
+impl<Buf> URI<Buf> {
+    pub const AD_TYPE: AdType = AdType::URI;
+    pub fn new(encoded: Buf) -> Self {
+        URI { encoded }
+    }
+}
+impl<Buf: Storage<u8>> URI<Buf> {
+    /// Encode `uri` (like `https://www.bluetooth.com`).
+    pub fn from_uri(uri: &str) -> Result<Self, PackError> {
+        let encoded = EncodedURI::from_uri(uri);
+        let len = encoded.byte_len();
+        if len > Buf::max_len() {
+            return Err(PackError::BadLength {
+                expected: Buf::max_len(),
+                got: len,
+            });
+        }
+        let mut buf = Buf::with_size(len);
+        encoded.pack_into(buf.as_mut())?;
+        Ok(URI::new(buf))
+    }
+}
+impl<Buf: AsRef<[u8]>> URI<Buf> {
+    pub fn decode(&self) -> Result<EncodedURI<'_>, PackError> {
+        EncodedURI::unpack_from(self.encoded.as_ref())
+    }
+}
+impl<Buf: AsRef<[u8]>> AdStructureType for URI<Buf> {
+    fn ad_type(&self) -> AdType {
+        Self::AD_TYPE
+    }
+
+    fn byte_len(&self) -> usize {
+        self.encoded.as_ref().len()
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        buf.copy_from_slice(self.encoded.as_ref());
+        Ok(())
+    }
+}
+impl<Buf: Storage<u8>> UnpackableAdStructType for URI<Buf> {
+    fn unpack_from(ad_type: AdType, buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        if ad_type != Self::AD_TYPE {
+            return Err(PackError::InvalidFields);
+        }
+        let max_len = Buf::max_len();
+        if buf.len() > max_len {
+            return Err(PackError::BadLength {
+                expected: max_len,
+                got: buf.len(),
+            });
+        }
+        // Check it decodes before accepting it.
+        EncodedURI::unpack_from(buf)?;
+        Ok(Self::new(Buf::from_slice(buf)))
+    }
+}
+impl<Buf: Storage<u8>> ConstAdStructType for URI<Buf> {
+    const AD_TYPE: AdType = AdType::URI;
+}
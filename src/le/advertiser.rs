@@ -1,11 +1,21 @@
 //! Generic BLE Advertiser (WIP)
 use crate::hci::adapter;
-use crate::BTAddress;
+use crate::hci::adapters::retry::DelayFn;
+use crate::hci::le::advertise::TxPowerLevel;
+use crate::hci::le::periodic_advertise::PeriodicAdvertisingParameters;
+use crate::hci::le::whitelist::WhitelistEntry;
+use crate::le::ad_template::{fill_tx_power_level, tx_power_level_offset};
+use crate::le::scan;
+use crate::{AddressKind, AddressType, BTAddress, DeviceAddress};
 use crate::ConversionError;
+use crate::Stream;
 use core::convert::TryFrom;
 use futures_util::future::LocalBoxFuture;
 use core::convert::TryInto;
+use core::pin::Pin;
+use core::task::Poll;
 use core::time::Duration;
+use futures_util::{FutureExt, StreamExt};
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub struct AdvertisingInterval(u16);
@@ -89,6 +99,23 @@ pub enum AdvertisingType {
 impl AdvertisingType {
     pub const BYTE_LEN: usize = 1;
     pub const DEFAULT: AdvertisingType = AdvertisingType::AdvInd;
+    /// Whether the advertisement is addressed to one peer (`ADV_DIRECT_IND`).
+    pub fn is_directed(self) -> bool {
+        match self {
+            AdvertisingType::AdvDirectIndHighDutyCycle
+            | AdvertisingType::AdvDirectIndLowDutyCycle => true,
+            _ => false,
+        }
+    }
+    /// Whether peers can connect (`ADV_IND` or `ADV_DIRECT_IND`).
+    pub fn is_connectable(self) -> bool {
+        match self {
+            AdvertisingType::AdvInd
+            | AdvertisingType::AdvDirectIndHighDutyCycle
+            | AdvertisingType::AdvDirectIndLowDutyCycle => true,
+            AdvertisingType::AdvScanInd | AdvertisingType::AdvNonnConnInd => false,
+        }
+    }
 }
 impl Default for AdvertisingType {
     fn default() -> Self {
@@ -144,6 +171,14 @@ impl TryFrom<u8> for PeerAddressType {
         }
     }
 }
+impl From<AddressKind> for PeerAddressType {
+    fn from(kind: AddressKind) -> Self {
+        match kind {
+            AddressKind::Public => PeerAddressType::Public,
+            AddressKind::Random(_) => PeerAddressType::Random,
+        }
+    }
+}
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub enum OwnAddressType {
     PublicDevice = 0x00,
@@ -177,6 +212,122 @@ impl TryFrom<u8> for OwnAddressType {
         }
     }
 }
+impl From<scan::OwnAddressType> for OwnAddressType {
+    fn from(t: scan::OwnAddressType) -> Self {
+        match t {
+            scan::OwnAddressType::Public => OwnAddressType::PublicDevice,
+            scan::OwnAddressType::Random => OwnAddressType::RandomDevice,
+            scan::OwnAddressType::PrivateOrPublic => OwnAddressType::PrivateOrPublic,
+            scan::OwnAddressType::PrivateOrRandom => OwnAddressType::PrivateOrRandom,
+        }
+    }
+}
+impl OwnAddressType {
+    /// Whether the controller generates a RPA from the resolving list. The resolving list is
+    /// keyed by the peer's identity address so the peer address has to be an identity address.
+    pub fn uses_resolving_list(self) -> bool {
+        match self {
+            OwnAddressType::PrivateOrPublic | OwnAddressType::PrivateOrRandom => true,
+            OwnAddressType::PublicDevice | OwnAddressType::RandomDevice => false,
+        }
+    }
+}
+/// Reasons a [`DirectedPeer`] can't be used with an own address type.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum DirectedPeerError {
+    /// Random address with the reserved (`0b10`) type bits.
+    ReservedAddressType,
+    /// Random address with the random part all `0`s or all `1`s.
+    InvalidRandomAddress,
+    /// RPA given as the peer but the own address type looks the peer up in the resolving list
+    /// (which needs the peer's identity address).
+    ResolvablePeerWithResolvingList,
+    /// Non-resolvable private address given as the peer but the own address type looks the
+    /// peer up in the resolving list.
+    NonResolvablePeerWithResolvingList,
+}
+impl core::fmt::Display for DirectedPeerError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid directed peer {:?}", self)
+    }
+}
+impl crate::error::Error for DirectedPeerError {}
+/// Peer address and address type of directed advertising and connection initiation.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct DirectedPeer {
+    pub address_type: PeerAddressType,
+    pub address: BTAddress,
+}
+impl DirectedPeer {
+    pub const fn new(address_type: PeerAddressType, address: BTAddress) -> DirectedPeer {
+        DirectedPeer {
+            address_type,
+            address,
+        }
+    }
+    pub const fn public(address: BTAddress) -> DirectedPeer {
+        DirectedPeer::new(PeerAddressType::Public, address)
+    }
+    pub const fn random(address: BTAddress) -> DirectedPeer {
+        DirectedPeer::new(PeerAddressType::Random, address)
+    }
+    pub fn device_address(self) -> DeviceAddress {
+        DeviceAddress::new(self.address_type == PeerAddressType::Random, self.address)
+    }
+    /// Type of the random address. `None` for public addresses.
+    pub fn random_address_type(self) -> Option<AddressType> {
+        match self.address_type {
+            PeerAddressType::Public => None,
+            PeerAddressType::Random => Some(self.address.address_type()),
+        }
+    }
+    /// Whether the peer is an identity address (public or random static).
+    pub fn is_identity(self) -> bool {
+        match self.random_address_type() {
+            None | Some(AddressType::StaticDevice) => true,
+            Some(_) => false,
+        }
+    }
+    /// Check the peer address is well formed and can be used with `own_address_type`.
+    pub fn check(self, own_address_type: OwnAddressType) -> Result<(), DirectedPeerError> {
+        let random_type = match self.random_address_type() {
+            None => return Ok(()),
+            Some(t) => t,
+        };
+        if random_type == AddressType::RFU {
+            return Err(DirectedPeerError::ReservedAddressType);
+        }
+        // The random part is every bit but the 2 type bits.
+        let random_part = self.address.to_u64() & 0x3FFF_FFFF_FFFF;
+        if random_type != AddressType::ResolvablePrivateAddress
+            && (random_part == 0 || random_part == 0x3FFF_FFFF_FFFF)
+        {
+            return Err(DirectedPeerError::InvalidRandomAddress);
+        }
+        if own_address_type.uses_resolving_list() {
+            match random_type {
+                AddressType::ResolvablePrivateAddress => {
+                    return Err(DirectedPeerError::ResolvablePeerWithResolvingList)
+                }
+                AddressType::NonResolvablePrivate => {
+                    return Err(DirectedPeerError::NonResolvablePeerWithResolvingList)
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+impl From<DeviceAddress> for DirectedPeer {
+    fn from(address: DeviceAddress) -> Self {
+        DirectedPeer::new(address.kind.into(), address.addr)
+    }
+}
+impl From<DirectedPeer> for DeviceAddress {
+    fn from(peer: DirectedPeer) -> Self {
+        peer.device_address()
+    }
+}
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub enum Channels {
     Channel37 = 0x00,
@@ -249,6 +400,17 @@ pub enum FilterPolicy {
 }
 impl FilterPolicy {
     pub const DEFAULT: FilterPolicy = FilterPolicy::All;
+    /// Same connection filtering as `self` but scan requests only from the White List.
+    pub fn with_scan_whitelist(self) -> FilterPolicy {
+        match self {
+            FilterPolicy::All | FilterPolicy::ConnectionAllScanWhitelist => {
+                FilterPolicy::ConnectionAllScanWhitelist
+            }
+            FilterPolicy::ScanAllConnectionWhitelist | FilterPolicy::Whitelist => {
+                FilterPolicy::Whitelist
+            }
+        }
+    }
 }
 impl Default for FilterPolicy {
     fn default() -> Self {
@@ -313,6 +475,32 @@ impl AdvertisingParameters {
             filter_policy: self.filter_policy,
         }
     }
+    /// Creates a new `AdvertisingParameters` from `self` with the peer address and address type
+    /// set to `peer`.
+    pub const fn with_peer(self, peer: DirectedPeer) -> AdvertisingParameters {
+        AdvertisingParameters {
+            interval_min: self.interval_min,
+            interval_max: self.interval_max,
+            advertising_type: self.advertising_type,
+            own_address_type: self.own_address_type,
+            peer_address_type: peer.address_type,
+            peer_address: peer.address,
+            channel_map: self.channel_map,
+            filter_policy: self.filter_policy,
+        }
+    }
+    pub fn peer(&self) -> DirectedPeer {
+        DirectedPeer::new(self.peer_address_type, self.peer_address)
+    }
+    /// Check the peer against the own address type. The peer is only used by directed
+    /// advertising and for the resolving list lookup so other parameters always pass.
+    pub fn check_peer(&self) -> Result<(), DirectedPeerError> {
+        if self.advertising_type.is_directed() || self.own_address_type.uses_resolving_list() {
+            self.peer().check(self.own_address_type)
+        } else {
+            Ok(())
+        }
+    }
     /// Creates a new `AdvertisingParameters` from `self` with `self.interval_min` and
     /// `self.interval_max` set to the `interval_min` and `interval_max` parameter respectively.
     pub const fn with_interval(
@@ -350,4 +538,302 @@ pub trait Advertiser {
         &'a mut self,
         data: &'d [u8],
     ) -> LocalBoxFuture<'d, Result<(), adapter::Error>>;
+    fn set_scan_response_data<'d, 'a: 'd>(
+        &'a mut self,
+        data: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>>;
+    /// Replace the White List with `entries`. Fails while advertising, scanning or connecting
+    /// with a filter policy that uses it.
+    fn set_whitelist<'d, 'a: 'd>(
+        &'a mut self,
+        entries: &'d [WhitelistEntry],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>>;
+    /// Answer scan requests with `scan_response` only for `scanners`. Loads them into the White
+    /// List, sets `parameters` with a filter policy limiting scan requests to it (connection
+    /// requests are still filtered as `parameters.filter_policy` says) and sets the scan
+    /// response data. Call with advertising disabled.
+    fn set_whitelisted_scan_response<'d, 'a: 'd>(
+        &'a mut self,
+        parameters: AdvertisingParameters,
+        scanners: &'d [WhitelistEntry],
+        scan_response: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            self.set_whitelist(scanners).await?;
+            let mut parameters = parameters;
+            parameters.filter_policy = parameters.filter_policy.with_scan_whitelist();
+            self.set_advertising_parameters(parameters).await?;
+            self.set_scan_response_data(scan_response).await
+        })
+    }
+    /// Set the advertising data to each payload of `payloads` (sensor readings, etc), at most
+    /// once every `min_interval`. Payloads arriving in between are coalesced: once the interval is
+    /// up only the newest one is set. `delay` waits out the interval (usually the async runtime's
+    /// sleep). Returns once `payloads` ends (after its last payload is set) or setting fails.
+    fn update_data_stream<'a, S>(
+        &'a mut self,
+        payloads: S,
+        min_interval: Duration,
+        delay: DelayFn,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>>
+    where
+        Self: Sized,
+        S: Stream + Unpin + 'a,
+        S::Item: AsRef<[u8]>,
+    {
+        Box::pin(async move {
+            let mut payloads = payloads;
+            let mut next = payloads.next().await;
+            while let Some(payload) = next.take() {
+                self.set_advertising_data(payload.as_ref()).await?;
+                let mut timer = delay(min_interval);
+                let mut is_done = false;
+                futures_util::future::poll_fn(|cx| {
+                    while !is_done {
+                        match Pin::new(&mut payloads).poll_next(cx) {
+                            Poll::Ready(Some(payload)) => next = Some(payload),
+                            Poll::Ready(None) => is_done = true,
+                            Poll::Pending => break,
+                        }
+                    }
+                    if is_done && next.is_none() {
+                        // Nothing left to set so there's no need to wait.
+                        Poll::Ready(())
+                    } else {
+                        timer.poll_unpin(cx)
+                    }
+                })
+                .await;
+                if next.is_none() && !is_done {
+                    next = payloads.next().await;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+/// Identifies one advertising set. Range `0x00-0xEF`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct AdvertisingSetHandle(u8);
+impl AdvertisingSetHandle {
+    pub const BYTE_LEN: usize = 1;
+    pub const MAX_U8: u8 = 0xEF;
+    pub const MAX: AdvertisingSetHandle = AdvertisingSetHandle(Self::MAX_U8);
+    /// Creates a new `AdvertisingSetHandle`.
+    /// # Panics
+    /// Panics if `handle > AdvertisingSetHandle::MAX_U8`.
+    pub fn new(handle: u8) -> AdvertisingSetHandle {
+        assert!(
+            handle <= Self::MAX_U8,
+            "invalid advertising set handle '{}'",
+            handle
+        );
+        AdvertisingSetHandle(handle)
+    }
+    pub fn new_checked(handle: u8) -> Option<AdvertisingSetHandle> {
+        if handle <= Self::MAX_U8 {
+            Some(AdvertisingSetHandle(handle))
+        } else {
+            None
+        }
+    }
+}
+impl From<AdvertisingSetHandle> for u8 {
+    fn from(handle: AdvertisingSetHandle) -> Self {
+        handle.0
+    }
+}
+impl TryFrom<u8> for AdvertisingSetHandle {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        AdvertisingSetHandle::new_checked(value).ok_or(ConversionError(()))
+    }
+}
+/// Advertiser that can run several advertisements (advertising sets) at once. Unknown handles
+/// return `adapter::Error::BadParameter`.
+pub trait AdvertisingSets {
+    /// Add a new advertising set. It starts advertising once sets are enabled.
+    fn add_set<'d, 'a: 'd>(
+        &'a mut self,
+        advertising_parameters: AdvertisingParameters,
+        data: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<AdvertisingSetHandle, adapter::Error>>;
+    fn set_set_data<'d, 'a: 'd>(
+        &'a mut self,
+        handle: AdvertisingSetHandle,
+        data: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>>;
+    fn remove_set<'a>(
+        &'a mut self,
+        handle: AdvertisingSetHandle,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>>;
+    /// Enable or disable all sets.
+    fn set_sets_enable<'a>(
+        &'a mut self,
+        is_enabled: bool,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>>;
+}
+/// Advertiser that can also broadcast periodic advertising trains (Bluetooth 5.0). A train runs
+/// on an advertising set that is neither connectable nor scannable and only starts once both
+/// periodic advertising and the set itself are enabled.
+pub trait PeriodicAdvertiser: Advertiser {
+    /// Fails with `adapter::Error::BadParameter` if `parameters.interval_min` is more than
+    /// `parameters.interval_max`.
+    fn set_periodic_advertising_parameters<'a>(
+        &'a mut self,
+        parameters: PeriodicAdvertisingParameters,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>>;
+    /// Replace the periodic advertising data of set `handle`, fragmented if needed.
+    fn set_periodic_advertising_data<'d, 'a: 'd>(
+        &'a mut self,
+        handle: AdvertisingSetHandle,
+        data: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>>;
+    fn set_periodic_advertising_enable<'a>(
+        &'a mut self,
+        handle: AdvertisingSetHandle,
+        is_enabled: bool,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>>;
+    /// Set the parameters and data of a periodic advertising train then enable it.
+    fn start_periodic_advertising<'d, 'a: 'd>(
+        &'a mut self,
+        parameters: PeriodicAdvertisingParameters,
+        data: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            self.set_periodic_advertising_parameters(parameters).await?;
+            self.set_periodic_advertising_data(parameters.handle, data)
+                .await?;
+            self.set_periodic_advertising_enable(parameters.handle, true)
+                .await
+        })
+    }
+}
+/// Reads the TX power an advertiser actually transmits at, for filling in TX Power Level AD
+/// structures (see [`fill_tx_power`]).
+pub trait AdvertisingTxPower {
+    /// TX power of advertising set `set`, or of the (only) legacy advertising instance if `None`.
+    fn advertising_tx_power<'a>(
+        &'a mut self,
+        set: Option<AdvertisingSetHandle>,
+    ) -> LocalBoxFuture<'a, Result<TxPowerLevel, adapter::Error>>;
+}
+/// If `payload` has a TX Power Level structure, ask `advertiser` for the TX power of `set` and
+/// write it in. Returns if the payload has one (the controller isn't asked if not).
+pub async fn fill_tx_power<A: AdvertisingTxPower>(
+    advertiser: &mut A,
+    set: Option<AdvertisingSetHandle>,
+    payload: &mut [u8],
+) -> Result<bool, adapter::Error> {
+    if tx_power_level_offset(payload).is_none() {
+        return Ok(false);
+    }
+    let level = advertiser.advertising_tx_power(set).await?;
+    Ok(fill_tx_power_level(payload, level.into()))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_directed_peer() {
+        let rpa = DirectedPeer::random(BTAddress([0x01, 0x02, 0x03, 0x04, 0x05, 0x46]));
+        let static_random = DirectedPeer::random(BTAddress([0x01, 0x02, 0x03, 0x04, 0x05, 0xC6]));
+        let public = DirectedPeer::public(BTAddress::ZEROED);
+        assert!(rpa.check(OwnAddressType::RandomDevice).is_ok());
+        assert_eq!(
+            rpa.check(OwnAddressType::PrivateOrRandom),
+            Err(DirectedPeerError::ResolvablePeerWithResolvingList)
+        );
+        assert!(static_random.check(OwnAddressType::PrivateOrPublic).is_ok());
+        assert!(public.check(OwnAddressType::PrivateOrPublic).is_ok());
+        assert_eq!(
+            DirectedPeer::random(BTAddress::ZEROED).check(OwnAddressType::PublicDevice),
+            Err(DirectedPeerError::InvalidRandomAddress)
+        );
+        assert_eq!(
+            DirectedPeer::random(BTAddress([0, 0, 0, 0, 0, 0x80])).check(OwnAddressType::DEFAULT),
+            Err(DirectedPeerError::ReservedAddressType)
+        );
+        let params = AdvertisingParameters {
+            advertising_type: AdvertisingType::AdvDirectIndLowDutyCycle,
+            own_address_type: OwnAddressType::PrivateOrPublic,
+            ..AdvertisingParameters::DEFAULT
+        }
+        .with_peer(rpa);
+        assert!(params.check_peer().is_err());
+        assert!(params.with_peer(static_random).check_peer().is_ok());
+        assert!(AdvertisingParameters::DEFAULT.with_peer(rpa).check_peer().is_ok());
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_update_data_stream() {
+        use crate::clock::sim::{delay, SimTime};
+        use crate::clock::{Clock, MockClock};
+        use alloc::vec::Vec;
+        use futures_util::stream;
+        struct RecordingAdvertiser {
+            clock: MockClock,
+            data: Vec<(Duration, Vec<u8>)>,
+        }
+        impl Advertiser for RecordingAdvertiser {
+            fn set_advertising_enable<'a>(
+                &'a mut self,
+                _is_enabled: bool,
+            ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+                Box::pin(async { Ok(()) })
+            }
+
+            fn set_advertising_parameters<'a>(
+                &'a mut self,
+                _advertising_parameters: AdvertisingParameters,
+            ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+                Box::pin(async { Ok(()) })
+            }
+
+            fn set_advertising_data<'d, 'a: 'd>(
+                &'a mut self,
+                data: &'d [u8],
+            ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+                self.data.push((self.clock.now(), data.to_vec()));
+                Box::pin(async { Ok(()) })
+            }
+
+            fn set_scan_response_data<'d, 'a: 'd>(
+                &'a mut self,
+                _data: &'d [u8],
+            ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+                Box::pin(async { Ok(()) })
+            }
+
+            fn set_whitelist<'d, 'a: 'd>(
+                &'a mut self,
+                _entries: &'d [WhitelistEntry],
+            ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+                Box::pin(async { Ok(()) })
+            }
+        }
+        let sim = SimTime::new();
+        let mut advertiser = RecordingAdvertiser {
+            clock: sim.clock(),
+            data: Vec::new(),
+        };
+        // (wait before the payload, payload)
+        let readings = [(0, 1_u8), (10, 2), (20, 3), (150, 4)];
+        let payloads = Box::pin(
+            stream::iter(readings.iter().copied())
+                .then(|(wait, reading)| delay(Duration::from_millis(wait)).map(move |_| [reading])),
+        );
+        sim.block_on(advertiser.update_data_stream(payloads, Duration::from_millis(100), delay))
+            .unwrap();
+        let ms = Duration::from_millis;
+        assert_eq!(
+            advertiser.data,
+            [
+                (ms(0), alloc::vec![1]),
+                (ms(100), alloc::vec![3]),
+                (ms(200), alloc::vec![4])
+            ]
+        );
+    }
 }
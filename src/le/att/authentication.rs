@@ -1,9 +1,19 @@
+//! ATT data signing (Core Spec Vol 3, Part H, 2.4.5). A Signed Write Command carries a 12 byte
+//! Authentication Signature made with the sender's CSRK so it can be checked on an unencrypted
+//! link.
+use crate::le::smp::crypto;
+use alloc::vec::Vec;
+
 pub const SIGNATURE_LEN: usize = 12;
+/// `SignCounter` (Little Endian) followed by the 64-bit MAC.
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
 pub struct Signature(pub [u8; SIGNATURE_LEN]);
 impl Signature {
     pub const ZEROED: Signature = Signature([0_u8; SIGNATURE_LEN]);
     pub const BYTE_LEN: usize = SIGNATURE_LEN;
+    pub fn sign_counter(&self) -> u32 {
+        u32::from_le_bytes([self.0[0], self.0[1], self.0[2], self.0[3]])
+    }
 }
 impl AsRef<[u8]> for Signature {
     fn as_ref(&self) -> &[u8] {
@@ -15,3 +25,39 @@ impl AsMut<[u8]> for Signature {
         self.0.as_mut()
     }
 }
+/// Connection Signature Resolving Key (Little Endian, the same as the Signing Information PDU).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct CSRK(pub [u8; 16]);
+impl CSRK {
+    pub const BYTE_LEN: usize = 16;
+}
+impl core::fmt::Debug for CSRK {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Don't leak keys into logs.
+        write!(f, "CSRK(..)")
+    }
+}
+/// Sign `message` (the PDU from the opcode up to the signature) with `csrk`.
+pub fn sign(csrk: &CSRK, message: &[u8], sign_counter: u32) -> Signature {
+    // The toolbox is MSB-first, the PDU and key are Little Endian.
+    let m: Vec<u8> = message
+        .iter()
+        .chain(sign_counter.to_le_bytes().iter())
+        .rev()
+        .copied()
+        .collect();
+    let mut key = csrk.0;
+    key.reverse();
+    let mac = crypto::aes_cmac(&key, &m);
+    let mut signature = [0_u8; SIGNATURE_LEN];
+    signature[..4].copy_from_slice(&sign_counter.to_le_bytes());
+    // The MAC is the 64 most significant bits of the CMAC.
+    for (out, b) in signature[4..].iter_mut().zip(mac[..8].iter().rev()) {
+        *out = *b;
+    }
+    Signature(signature)
+}
+/// Whether `signature` is `message` signed with `csrk`. Doesn't check the `SignCounter`.
+pub fn verify(csrk: &CSRK, message: &[u8], signature: &Signature) -> bool {
+    sign(csrk, message, signature.sign_counter()) == *signature
+}
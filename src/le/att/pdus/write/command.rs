@@ -0,0 +1,170 @@
+use crate::key_store::KeyStore;
+use crate::le::att::attribute::{Handle, Value};
+use crate::le::att::authentication::{self, Signature};
+use crate::le::att::pdus::{PackablePDU, UnpackablePDU};
+use crate::le::att::Opcode;
+use crate::{BTAddress, PackError};
+use alloc::vec::Vec;
+
+/// Reasons a [`SignedWriteCmd`] can't be signed or is rejected.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum SigningError {
+    /// No CSRK stored for the peer.
+    MissingKey,
+    /// Every `SignCounter` value has been used. The devices have to bond again.
+    CounterExhausted,
+    /// The `SignCounter` was already used (a replayed write).
+    StaleCounter,
+    /// The MAC doesn't match.
+    BadSignature,
+}
+impl core::fmt::Display for SigningError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "signed write {:?}", self)
+    }
+}
+impl crate::error::Error for SigningError {}
+/// `ATT_SIGNED_WRITE_CMD`. Write without response authenticated with the sender's CSRK. Only
+/// used on unencrypted links (encrypted links use a plain Write Command).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SignedWriteCmd<B> {
+    pub handle: Handle,
+    pub value: Value<B>,
+    pub signature: Signature,
+}
+impl<B: AsRef<[u8]>> SignedWriteCmd<B> {
+    pub const HEADER_LEN: usize = 2;
+    /// Sign a write to `peer` with our CSRK for it, advancing the stored `SignCounter`.
+    pub fn sign<K: KeyStore>(
+        keys: &mut K,
+        peer: BTAddress,
+        handle: Handle,
+        value: B,
+    ) -> Result<SignedWriteCmd<B>, SigningError> {
+        if keys.bond(peer).and_then(|bond| bond.local_signing_key).is_none() {
+            return Err(SigningError::MissingKey);
+        }
+        let (csrk, sign_counter) = keys
+            .next_local_signature(peer)
+            .ok_or(SigningError::CounterExhausted)?;
+        let mut out = SignedWriteCmd {
+            handle,
+            value: Value::new(value),
+            signature: Signature::ZEROED,
+        };
+        out.signature = authentication::sign(&csrk, &out.signed_bytes(), sign_counter);
+        Ok(out)
+    }
+    /// Check the signature against the CSRK `peer` distributed. Accepted writes advance the
+    /// stored `SignCounter` so they can't be replayed. A counter of `u32::MAX` is never accepted.
+    pub fn verify<K: KeyStore>(&self, keys: &mut K, peer: BTAddress) -> Result<(), SigningError> {
+        let key = keys
+            .bond(peer)
+            .and_then(|bond| bond.peer_signing_key)
+            .ok_or(SigningError::MissingKey)?;
+        let sign_counter = self.signature.sign_counter();
+        if sign_counter == u32::MAX {
+            return Err(SigningError::CounterExhausted);
+        }
+        if sign_counter < key.sign_counter {
+            return Err(SigningError::StaleCounter);
+        }
+        if !authentication::verify(&key.csrk, &self.signed_bytes(), &self.signature) {
+            return Err(SigningError::BadSignature);
+        }
+        keys.accept_peer_sign_counter(peer, sign_counter);
+        Ok(())
+    }
+    /// The PDU without the signature (what the signature covers).
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(Opcode::BYTE_LEN + Self::HEADER_LEN + self.value.len());
+        out.push(Opcode::SignedWriteCmd.into());
+        out.extend_from_slice(&self.handle.inner().to_le_bytes());
+        out.extend_from_slice(self.value.as_ref());
+        out
+    }
+}
+impl<B: AsRef<[u8]>> PackablePDU for SignedWriteCmd<B> {
+    const OPCODE: Opcode = Opcode::SignedWriteCmd;
+
+    fn byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.value.len() + Signature::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        let value_end = Self::HEADER_LEN + self.value.len();
+        buf[..2].copy_from_slice(&self.handle.inner().to_le_bytes());
+        buf[2..value_end].copy_from_slice(self.value.as_ref());
+        buf[value_end..].copy_from_slice(self.signature.as_ref());
+        Ok(())
+    }
+}
+impl UnpackablePDU for SignedWriteCmd<Vec<u8>> {
+    fn unpack_from(buf: &[u8]) -> Result<Self, PackError>
+    where
+        Self: Sized,
+    {
+        let min_len = Self::HEADER_LEN + Signature::BYTE_LEN;
+        if buf.len() < min_len {
+            return Err(PackError::BadLength {
+                expected: min_len,
+                got: buf.len(),
+            });
+        }
+        let value_end = buf.len() - Signature::BYTE_LEN;
+        let mut signature = Signature::ZEROED;
+        signature.as_mut().copy_from_slice(&buf[value_end..]);
+        Ok(SignedWriteCmd {
+            handle: Handle::new(u16::from_le_bytes([buf[0], buf[1]])),
+            value: Value::new(buf[2..value_end].to_vec()),
+            signature,
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_store::MemoryKeyStore;
+    use crate::le::att::authentication::CSRK;
+    #[test]
+    fn test_signed_write() {
+        let client_address = BTAddress([1, 2, 3, 4, 5, 6]);
+        let server_address = BTAddress([6, 5, 4, 3, 2, 1]);
+        let csrk = CSRK([0x5A; 16]);
+        let mut client = MemoryKeyStore::new();
+        let mut server = MemoryKeyStore::new();
+        client.store_local_csrk(server_address, csrk);
+        server.store_peer_csrk(client_address, csrk);
+
+        let write =
+            SignedWriteCmd::sign(&mut client, server_address, Handle::new(0x0010), [0xAB]).unwrap();
+        assert_eq!(write.signature.sign_counter(), 0);
+        let mut buf = alloc::vec![0_u8; write.byte_len()];
+        write.pack_into(&mut buf).unwrap();
+        assert_eq!(&buf[..3], &[0x10, 0x00, 0xAB]);
+        let received = SignedWriteCmd::unpack_from(&buf).unwrap();
+        assert_eq!(received.verify(&mut server, client_address), Ok(()));
+        // Replayed.
+        assert_eq!(
+            received.verify(&mut server, client_address),
+            Err(SigningError::StaleCounter)
+        );
+        let next =
+            SignedWriteCmd::sign(&mut client, server_address, Handle::new(0x0010), [0xCD]).unwrap();
+        assert_eq!(next.signature.sign_counter(), 1);
+        let tampered = SignedWriteCmd {
+            value: Value::new([0xCE]),
+            ..next
+        };
+        assert_eq!(
+            tampered.verify(&mut server, client_address),
+            Err(SigningError::BadSignature)
+        );
+        assert_eq!(next.verify(&mut server, client_address), Ok(()));
+        assert_eq!(
+            next.verify(&mut MemoryKeyStore::new(), client_address),
+            Err(SigningError::MissingKey)
+        );
+    }
+}
@@ -0,0 +1,530 @@
+//! LE Audio broadcast (Auracast) helpers. Finds Broadcast Audio Announcements in advertising
+//! data, parses the Broadcast Audio Source Endpoint (BASE) structure from the periodic advertising
+//! data and builds the `BIGCreateSync` command used to receive the broadcast's BISes.
+use crate::hci::le::big::{BIGCreateSync, BIGHandle, BIGSyncEstablished, SyncHandle};
+use crate::le::advertisement::AdType;
+use crate::le::connection::ConnectionHandle;
+use crate::le::report::ReportInfo;
+use crate::uuid::UUID16;
+use crate::{BTAddress, PackError, RSSI};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Broadcast Audio Announcement Service UUID. Service data is the 3-byte `Broadcast_ID`.
+pub const BROADCAST_AUDIO_ANNOUNCEMENT: UUID16 = UUID16(0x1852);
+/// Basic Audio Announcement Service UUID. Service data is the BASE.
+pub const BASIC_AUDIO_ANNOUNCEMENT: UUID16 = UUID16(0x1851);
+
+/// Find the service data for `uuid` in raw AD data. Unlike `RawAdvertisement::iter` this works
+/// on extended and periodic advertising data which can be longer than 31 bytes.
+pub fn find_service_data(data: &[u8], uuid: UUID16) -> Option<&[u8]> {
+    let mut rest = data;
+    while let Some((&len, tail)) = rest.split_first() {
+        let len = usize::from(len);
+        if len == 0 || len > tail.len() {
+            return None;
+        }
+        let (ad, next) = tail.split_at(len);
+        rest = next;
+        if ad[0] == u8::from(AdType::ServiceData)
+            && ad.len() >= 3
+            && u16::from_le_bytes([ad[1], ad[2]]) == u16::from(uuid)
+        {
+            return Some(&ad[3..]);
+        }
+    }
+    None
+}
+/// 24-bit Broadcast ID identifying a broadcast source.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct BroadcastID(u32);
+impl BroadcastID {
+    pub const BYTE_LEN: usize = 3;
+    pub const MAX_U32: u32 = 0x00FF_FFFF;
+    pub fn new(value: u32) -> Self {
+        match Self::new_checked(value) {
+            Some(s) => s,
+            None => panic!("broadcast id out of range (`{}`)", value),
+        }
+    }
+    pub fn new_checked(value: u32) -> Option<Self> {
+        if value > Self::MAX_U32 {
+            None
+        } else {
+            Some(Self(value))
+        }
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<Self, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(BroadcastID(u32::from_le_bytes([buf[0], buf[1], buf[2], 0])))
+    }
+    pub fn pack_into(self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf.copy_from_slice(&self.0.to_le_bytes()[..3]);
+        Ok(())
+    }
+}
+impl From<BroadcastID> for u32 {
+    fn from(id: BroadcastID) -> Self {
+        id.0
+    }
+}
+/// Broadcast source found while scanning.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BroadcastSource {
+    pub address: BTAddress,
+    pub broadcast_id: BroadcastID,
+    pub rssi: Option<RSSI>,
+}
+impl BroadcastSource {
+    /// Parse the Broadcast Audio Announcement from the advertising data of `address`.
+    pub fn from_data(address: BTAddress, data: &[u8], rssi: Option<RSSI>) -> Option<Self> {
+        let service_data = find_service_data(data, BROADCAST_AUDIO_ANNOUNCEMENT)?;
+        // Service data may have extra (future) fields after the Broadcast ID.
+        let broadcast_id =
+            BroadcastID::unpack_from(service_data.get(..BroadcastID::BYTE_LEN)?).ok()?;
+        Some(BroadcastSource {
+            address,
+            broadcast_id,
+            rssi,
+        })
+    }
+    pub fn from_report<T: AsRef<[u8]>>(report: &ReportInfo<T>) -> Option<Self> {
+        Self::from_data(report.address, report.data.as_ref(), report.rssi)
+    }
+}
+/// Collects the broadcast sources seen while scanning. Each source is only kept once (by
+/// `Broadcast_ID`) with the latest RSSI.
+#[derive(Clone, Debug, Default)]
+pub struct BroadcastScanner {
+    sources: Vec<BroadcastSource>,
+}
+impl BroadcastScanner {
+    pub fn new() -> BroadcastScanner {
+        BroadcastScanner::default()
+    }
+    /// Process advertising data. Returns the source if it's a newly found broadcast.
+    pub fn on_data(
+        &mut self,
+        address: BTAddress,
+        data: &[u8],
+        rssi: Option<RSSI>,
+    ) -> Option<BroadcastSource> {
+        let source = BroadcastSource::from_data(address, data, rssi)?;
+        match self
+            .sources
+            .iter_mut()
+            .find(|s| s.broadcast_id == source.broadcast_id)
+        {
+            Some(existing) => {
+                *existing = source;
+                None
+            }
+            None => {
+                self.sources.push(source);
+                Some(source)
+            }
+        }
+    }
+    pub fn on_report<T: AsRef<[u8]>>(&mut self, report: &ReportInfo<T>) -> Option<BroadcastSource> {
+        self.on_data(report.address, report.data.as_ref(), report.rssi)
+    }
+    pub fn sources(&self) -> &[BroadcastSource] {
+        &self.sources
+    }
+    pub fn clear(&mut self) {
+        self.sources.clear();
+    }
+}
+/// Codec ID. `coding_format` `0x06` is LC3.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct CodecID {
+    pub coding_format: u8,
+    pub company_id: u16,
+    pub vendor_codec_id: u16,
+}
+impl CodecID {
+    pub const BYTE_LEN: usize = 5;
+    pub const LC3: CodecID = CodecID {
+        coding_format: 0x06,
+        company_id: 0,
+        vendor_codec_id: 0,
+    };
+    pub fn unpack_from(buf: &[u8]) -> Result<Self, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(CodecID {
+            coding_format: buf[0],
+            company_id: u16::from_le_bytes([buf[1], buf[2]]),
+            vendor_codec_id: u16::from_le_bytes([buf[3], buf[4]]),
+        })
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.coding_format;
+        buf[1..3].copy_from_slice(&self.company_id.to_le_bytes());
+        buf[3..5].copy_from_slice(&self.vendor_codec_id.to_le_bytes());
+        Ok(())
+    }
+}
+/// LC3 Codec Specific Configuration decoded from its LTV structures. Fields missing from the
+/// LTVs are `None`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct CodecConfiguration {
+    /// Sampling frequency in Hz.
+    pub sampling_frequency: Option<u32>,
+    /// Frame duration in microseconds.
+    pub frame_duration_us: Option<u32>,
+    pub audio_channel_allocation: Option<u32>,
+    pub octets_per_codec_frame: Option<u16>,
+    pub codec_frames_per_sdu: Option<u8>,
+}
+impl CodecConfiguration {
+    pub const SAMPLING_FREQUENCY: u8 = 0x01;
+    pub const FRAME_DURATION: u8 = 0x02;
+    pub const AUDIO_CHANNEL_ALLOCATION: u8 = 0x03;
+    pub const OCTETS_PER_CODEC_FRAME: u8 = 0x04;
+    pub const CODEC_FRAMES_PER_SDU: u8 = 0x05;
+    /// Decode the LTV structures in `buf`. Unknown types are ignored.
+    pub fn decode(buf: &[u8]) -> Result<Self, PackError> {
+        let mut out = CodecConfiguration::default();
+        out.merge_ltvs(buf)?;
+        Ok(out)
+    }
+    /// Decode the LTV structures in `buf`, overwriting any fields they contain. Used to apply the
+    /// BIS level configuration on top of the subgroup level configuration.
+    pub fn merge_ltvs(&mut self, buf: &[u8]) -> Result<(), PackError> {
+        let mut index = 0;
+        while index < buf.len() {
+            let len = usize::from(buf[index]);
+            let ltv = buf
+                .get(index + 1..index + 1 + len)
+                .filter(|ltv| !ltv.is_empty())
+                .ok_or_else(|| PackError::bad_index(index))?;
+            let (kind, value) = (ltv[0], &ltv[1..]);
+            let bad_value = || PackError::bad_index(index + 2);
+            match kind {
+                Self::SAMPLING_FREQUENCY => {
+                    let hz = match value {
+                        [0x01] => 8_000,
+                        [0x02] => 11_025,
+                        [0x03] => 16_000,
+                        [0x04] => 22_050,
+                        [0x05] => 24_000,
+                        [0x06] => 32_000,
+                        [0x07] => 44_100,
+                        [0x08] => 48_000,
+                        [0x09] => 88_200,
+                        [0x0A] => 96_000,
+                        [0x0B] => 176_400,
+                        [0x0C] => 192_000,
+                        [0x0D] => 384_000,
+                        _ => return Err(bad_value()),
+                    };
+                    self.sampling_frequency = Some(hz);
+                }
+                Self::FRAME_DURATION => {
+                    self.frame_duration_us = Some(match value {
+                        [0x00] => 7_500,
+                        [0x01] => 10_000,
+                        _ => return Err(bad_value()),
+                    });
+                }
+                Self::AUDIO_CHANNEL_ALLOCATION => match value {
+                    [a, b, c, d] => {
+                        self.audio_channel_allocation = Some(u32::from_le_bytes([*a, *b, *c, *d]))
+                    }
+                    _ => return Err(bad_value()),
+                },
+                Self::OCTETS_PER_CODEC_FRAME => match value {
+                    [a, b] => self.octets_per_codec_frame = Some(u16::from_le_bytes([*a, *b])),
+                    _ => return Err(bad_value()),
+                },
+                Self::CODEC_FRAMES_PER_SDU => match value {
+                    [n] => self.codec_frames_per_sdu = Some(*n),
+                    _ => return Err(bad_value()),
+                },
+                _ => (),
+            }
+            index += 1 + len;
+        }
+        Ok(())
+    }
+}
+/// Level 3 of the BASE. One BIS in a subgroup.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BaseBIS {
+    /// BIS index (`1..=0x1F`) used in `BIGCreateSync`.
+    pub index: u8,
+    /// Codec Specific Configuration LTVs that override the subgroup's.
+    pub codec_specific_configuration: Vec<u8>,
+}
+/// Level 2 of the BASE. BISes sharing a codec and metadata.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BaseSubgroup {
+    pub codec_id: CodecID,
+    pub codec_specific_configuration: Vec<u8>,
+    pub metadata: Vec<u8>,
+    pub bis: Vec<BaseBIS>,
+}
+impl BaseSubgroup {
+    /// Codec configuration of `bis` (subgroup level with the BIS level applied on top).
+    pub fn bis_configuration(&self, bis: &BaseBIS) -> Result<CodecConfiguration, PackError> {
+        let mut config = CodecConfiguration::decode(&self.codec_specific_configuration)?;
+        config.merge_ltvs(&bis.codec_specific_configuration)?;
+        Ok(config)
+    }
+    fn byte_len(&self) -> usize {
+        1 + CodecID::BYTE_LEN
+            + 1
+            + self.codec_specific_configuration.len()
+            + 1
+            + self.metadata.len()
+            + self
+                .bis
+                .iter()
+                .map(|b| 2 + b.codec_specific_configuration.len())
+                .sum::<usize>()
+    }
+}
+/// Broadcast Audio Source Endpoint structure (the Basic Audio Announcement service data).
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct Base {
+    /// Presentation delay in microseconds (24-bit).
+    pub presentation_delay_us: u32,
+    pub subgroups: Vec<BaseSubgroup>,
+}
+/// Reads length prefixed fields out of a BASE.
+struct BaseReader<'a> {
+    buf: &'a [u8],
+    index: usize,
+}
+impl<'a> BaseReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PackError> {
+        let out = self
+            .buf
+            .get(self.index..self.index + len)
+            .ok_or_else(|| PackError::bad_index(self.index))?;
+        self.index += len;
+        Ok(out)
+    }
+    fn u8(&mut self) -> Result<u8, PackError> {
+        Ok(self.take(1)?[0])
+    }
+    fn prefixed(&mut self) -> Result<&'a [u8], PackError> {
+        let len = self.u8()?;
+        self.take(usize::from(len))
+    }
+}
+impl Base {
+    pub const MAX_PRESENTATION_DELAY: u32 = 0x00FF_FFFF;
+    /// Parse the BASE out of periodic advertising data.
+    pub fn from_data(data: &[u8]) -> Result<Base, PackError> {
+        Self::unpack_from(
+            find_service_data(data, BASIC_AUDIO_ANNOUNCEMENT).ok_or(PackError::InvalidFields)?,
+        )
+    }
+    /// Unpack the BASE from the Basic Audio Announcement service data (without the UUID).
+    pub fn unpack_from(buf: &[u8]) -> Result<Base, PackError> {
+        let mut reader = BaseReader { buf, index: 0 };
+        let delay = reader.take(3)?;
+        let presentation_delay_us = u32::from_le_bytes([delay[0], delay[1], delay[2], 0]);
+        let num_subgroups = reader.u8()?;
+        if num_subgroups == 0 {
+            return Err(PackError::bad_index(3));
+        }
+        let mut subgroups = Vec::with_capacity(usize::from(num_subgroups));
+        for _ in 0..num_subgroups {
+            let num_bis = reader.u8()?;
+            if num_bis == 0 {
+                return Err(PackError::bad_index(reader.index - 1));
+            }
+            let codec_id = CodecID::unpack_from(reader.take(CodecID::BYTE_LEN)?)?;
+            let codec_specific_configuration = reader.prefixed()?.to_vec();
+            let metadata = reader.prefixed()?.to_vec();
+            let mut bis = Vec::with_capacity(usize::from(num_bis));
+            for _ in 0..num_bis {
+                let index = reader.u8()?;
+                if index == 0 || usize::from(index) > BIGCreateSync::MAX_BIS {
+                    return Err(PackError::bad_index(reader.index - 1));
+                }
+                bis.push(BaseBIS {
+                    index,
+                    codec_specific_configuration: reader.prefixed()?.to_vec(),
+                });
+            }
+            subgroups.push(BaseSubgroup {
+                codec_id,
+                codec_specific_configuration,
+                metadata,
+                bis,
+            });
+        }
+        Ok(Base {
+            presentation_delay_us,
+            subgroups,
+        })
+    }
+    pub fn byte_len(&self) -> usize {
+        3 + 1
+            + self
+                .subgroups
+                .iter()
+                .map(BaseSubgroup::byte_len)
+                .sum::<usize>()
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        if self.presentation_delay_us > Self::MAX_PRESENTATION_DELAY {
+            return Err(PackError::InvalidFields);
+        }
+        fn put(buf: &mut [u8], index: &mut usize, data: &[u8]) {
+            buf[*index..*index + data.len()].copy_from_slice(data);
+            *index += data.len();
+        }
+        fn put_prefixed(buf: &mut [u8], index: &mut usize, data: &[u8]) -> Result<(), PackError> {
+            put(
+                buf,
+                index,
+                &[u8::try_from(data.len()).map_err(|_| PackError::InvalidFields)?],
+            );
+            put(buf, index, data);
+            Ok(())
+        }
+        let mut index = 0;
+        put(
+            buf,
+            &mut index,
+            &self.presentation_delay_us.to_le_bytes()[..3],
+        );
+        put(
+            buf,
+            &mut index,
+            &[u8::try_from(self.subgroups.len()).map_err(|_| PackError::InvalidFields)?],
+        );
+        for subgroup in &self.subgroups {
+            put(
+                buf,
+                &mut index,
+                &[u8::try_from(subgroup.bis.len()).map_err(|_| PackError::InvalidFields)?],
+            );
+            subgroup
+                .codec_id
+                .pack_into(&mut buf[index..index + CodecID::BYTE_LEN])?;
+            index += CodecID::BYTE_LEN;
+            put_prefixed(buf, &mut index, &subgroup.codec_specific_configuration)?;
+            put_prefixed(buf, &mut index, &subgroup.metadata)?;
+            for bis in &subgroup.bis {
+                put(buf, &mut index, &[bis.index]);
+                put_prefixed(buf, &mut index, &bis.codec_specific_configuration)?;
+            }
+        }
+        Ok(())
+    }
+    /// All BISes with their decoded codec configuration.
+    pub fn streams(&self) -> impl Iterator<Item = (&BaseSubgroup, &BaseBIS)> + '_ {
+        self.subgroups
+            .iter()
+            .flat_map(|s| s.bis.iter().map(move |b| (s, b)))
+    }
+    /// `BIGCreateSync` for the BISes in `bis` (all BISes if empty). `sync_handle` is the handle of
+    /// the periodic advertising train that carried the BASE.
+    pub fn create_sync(
+        &self,
+        big_handle: BIGHandle,
+        sync_handle: SyncHandle,
+        bis: &[u8],
+        broadcast_code: Option<[u8; 16]>,
+    ) -> BIGCreateSync {
+        let bis = if bis.is_empty() {
+            self.streams().map(|(_, b)| b.index).collect()
+        } else {
+            bis.to_vec()
+        };
+        BIGCreateSync {
+            big_handle,
+            sync_handle,
+            broadcast_code,
+            mse: 0,
+            big_sync_timeout: 100,
+            bis,
+        }
+    }
+}
+/// A synchronized BIS with its ISO connection handle and decoded codec configuration.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BroadcastStream {
+    pub handle: ConnectionHandle,
+    pub bis_index: u8,
+    pub codec_id: CodecID,
+    pub configuration: CodecConfiguration,
+    /// Maximum SDU size from `BIGSyncEstablished`.
+    pub max_pdu: u16,
+    /// ISO interval in microseconds from `BIGSyncEstablished`.
+    pub iso_interval_us: u32,
+}
+impl BroadcastStream {
+    /// Match the connection handles in `established` with the BISes requested by `create_sync`.
+    pub fn from_established(
+        base: &Base,
+        create_sync: &BIGCreateSync,
+        established: &BIGSyncEstablished,
+    ) -> Result<Vec<BroadcastStream>, PackError> {
+        create_sync
+            .bis
+            .iter()
+            .zip(established.connection_handles.iter())
+            .map(|(&bis_index, &handle)| {
+                let (subgroup, bis) = base
+                    .streams()
+                    .find(|(_, b)| b.index == bis_index)
+                    .ok_or(PackError::InvalidFields)?;
+                Ok(BroadcastStream {
+                    handle,
+                    bis_index,
+                    codec_id: subgroup.codec_id,
+                    configuration: subgroup.bis_configuration(bis)?,
+                    max_pdu: established.max_pdu,
+                    iso_interval_us: established.iso_interval_us(),
+                })
+            })
+            .collect()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_base() {
+        // Service data from a 48kHz stereo LC3 broadcast.
+        let periodic_data = [
+            0x2D, 0x16, 0x51, 0x18, // Basic Audio Announcement
+            0x40, 0x9C, 0x00, // 40ms presentation delay
+            0x01, // 1 subgroup
+            0x02, // 2 BISes
+            0x06, 0x00, 0x00, 0x00, 0x00, // LC3
+            0x0A, 0x02, 0x01, 0x08, 0x02, 0x02, 0x01, 0x03, 0x04, 0x78,
+            0x00, // 48kHz, 10ms, 120
+            0x04, 0x03, 0x02, 0x04, 0x00, // Media context
+            0x01, 0x06, 0x05, 0x03, 0x01, 0x00, 0x00, 0x00, // BIS 1: front left
+            0x02, 0x06, 0x05, 0x03, 0x02, 0x00, 0x00, 0x00, // BIS 2: front right
+        ];
+        let base = Base::from_data(&periodic_data[..]).unwrap();
+        assert_eq!(base.presentation_delay_us, 40_000);
+        let subgroup = &base.subgroups[0];
+        assert_eq!(subgroup.codec_id, CodecID::LC3);
+        let config = subgroup.bis_configuration(&subgroup.bis[1]).unwrap();
+        assert_eq!(config.sampling_frequency, Some(48_000));
+        assert_eq!(config.frame_duration_us, Some(10_000));
+        assert_eq!(config.octets_per_codec_frame, Some(120));
+        assert_eq!(config.audio_channel_allocation, Some(0x02));
+        let mut buf = vec![0_u8; base.byte_len()];
+        base.pack_into(&mut buf[..]).unwrap();
+        assert_eq!(&buf[..], &periodic_data[4..]);
+
+        let sync = base.create_sync(BIGHandle::new(0), SyncHandle(1), &[], None);
+        assert_eq!(sync.bis, vec![1, 2]);
+        let announcement = [0x06, 0x16, 0x52, 0x18, 0x56, 0x34, 0x12];
+        let source = BroadcastSource::from_data(BTAddress::ZEROED, &announcement[..], None);
+        assert_eq!(source.unwrap().broadcast_id, BroadcastID::new(0x12_3456));
+    }
+}
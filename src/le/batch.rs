@@ -0,0 +1,149 @@
+//! Batched report delivery. [`Batched`] collects the items of a report stream into `Vec`s sent
+//! every `max_reports` reports or `max_delay` after the first report of the batch, whichever comes
+//! first. High volume gateways wake up once per batch instead of once per report.
+use crate::hci::adapters::retry::DelayFn;
+use crate::LocalBoxFuture;
+use crate::Stream;
+use alloc::vec::Vec;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+use futures_util::FutureExt;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BatchDelivery {
+    /// Deliver once this many reports are waiting.
+    pub max_reports: usize,
+    /// Deliver once the oldest waiting report is this old.
+    pub max_delay: Duration,
+}
+impl BatchDelivery {
+    pub const DEFAULT: BatchDelivery = BatchDelivery {
+        max_reports: 64,
+        max_delay: Duration::from_millis(100),
+    };
+}
+impl Default for BatchDelivery {
+    fn default() -> Self {
+        BatchDelivery::DEFAULT
+    }
+}
+/// Stream of batches from a stream of `Result<T, E>`. An error is delivered on its own, after
+/// the reports received before it.
+pub struct Batched<S: Stream<Item = Result<T, E>> + Unpin, T, E> {
+    stream: S,
+    config: BatchDelivery,
+    delay: DelayFn,
+    batch: Vec<T>,
+    /// Started by the first report of the batch.
+    timer: Option<LocalBoxFuture<'static, ()>>,
+    error: Option<E>,
+    is_done: bool,
+}
+impl<S: Stream<Item = Result<T, E>> + Unpin, T, E> Batched<S, T, E> {
+    /// `delay` waits out `max_delay` (usually the async runtime's sleep).
+    pub fn new(stream: S, config: BatchDelivery, delay: DelayFn) -> Self {
+        Batched {
+            stream,
+            config,
+            delay,
+            batch: Vec::with_capacity(config.max_reports),
+            timer: None,
+            error: None,
+            is_done: false,
+        }
+    }
+    pub fn config(&self) -> BatchDelivery {
+        self.config
+    }
+    /// Reports waiting for the batch to be delivered.
+    pub fn pending(&self) -> usize {
+        self.batch.len()
+    }
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+    fn take_batch(&mut self) -> Vec<T> {
+        self.timer = None;
+        core::mem::replace(&mut self.batch, Vec::with_capacity(self.config.max_reports))
+    }
+}
+impl<S: Stream<Item = Result<T, E>> + Unpin, T, E> Unpin for Batched<S, T, E> {}
+impl<S: Stream<Item = Result<T, E>> + Unpin, T, E> Stream for Batched<S, T, E> {
+    type Item = Result<Vec<T>, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(e) = this.error.take() {
+            return Poll::Ready(Some(Err(e)));
+        }
+        while !this.is_done {
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(Ok(report))) => {
+                    if this.batch.is_empty() {
+                        this.timer = Some((this.delay)(this.config.max_delay));
+                    }
+                    this.batch.push(report);
+                    if this.batch.len() >= this.config.max_reports {
+                        return Poll::Ready(Some(Ok(this.take_batch())));
+                    }
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    if this.batch.is_empty() {
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                    this.error = Some(e);
+                    return Poll::Ready(Some(Ok(this.take_batch())));
+                }
+                Poll::Ready(None) => this.is_done = true,
+                Poll::Pending => {
+                    let is_due = match this.timer.as_mut() {
+                        Some(timer) => timer.poll_unpin(cx).is_ready(),
+                        None => false,
+                    };
+                    return if is_due {
+                        Poll::Ready(Some(Ok(this.take_batch())))
+                    } else {
+                        Poll::Pending
+                    };
+                }
+            }
+        }
+        if this.batch.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Ready(Some(Ok(this.take_batch())))
+        }
+    }
+}
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::clock::sim::{delay, SimTime};
+    use futures_util::stream::{self, StreamExt};
+    #[test]
+    fn test_batched() {
+        let config = BatchDelivery {
+            max_reports: 2,
+            max_delay: Duration::from_millis(100),
+        };
+        let sim = SimTime::new();
+        let reports = stream::iter(alloc::vec![Ok(1), Ok(2), Ok(3), Err(()), Ok(4)]);
+        let batches: Vec<Result<Vec<u8>, ()>> =
+            sim.block_on(Batched::new(reports, config, delay).collect());
+        assert_eq!(
+            batches,
+            alloc::vec![
+                Ok(alloc::vec![1, 2]),
+                Ok(alloc::vec![3]),
+                Err(()),
+                Ok(alloc::vec![4])
+            ]
+        );
+        // A lone report goes out once it's `max_delay` old.
+        let reports = stream::iter(alloc::vec![Ok::<u8, ()>(5)]).chain(stream::pending());
+        let mut batched = Batched::new(reports, config, delay);
+        assert_eq!(sim.block_on(batched.next()), Some(Ok(alloc::vec![5])));
+        assert_eq!(sim.now(), Duration::from_millis(100));
+    }
+}
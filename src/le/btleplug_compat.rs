@@ -0,0 +1,391 @@
+//! `btleplug` shaped central API on top of any [`Observer`] + [`Advertiser`] backend, so
+//! applications written against `btleplug` can move over by changing imports. [`Central`] has
+//! `btleplug`'s scanning calls (`start_scan`, `stop_scan`, `events`, `peripherals`, `peripheral`)
+//! and emits the same [`CentralEvent`]s, while [`Central::backend_mut`] still reaches the adapter
+//! for raw HCI features (custom advertising data, advertising parameters, etc).
+//!
+//! `btleplug`'s own `Central`/`Peripheral` traits can't be implemented here: their futures must
+//! be `Send` (this crate's adapter futures aren't) and `PeripheralId` can only be built by
+//! `btleplug`'s platform code. So the methods are inherent ones with the same names and
+//! meaning, with these differences:
+//! - Identifiers are [`PeripheralId`] (the device's [`DeviceAddress`]) and UUIDs are
+//!   [`crate::uuid::UUID`].
+//! - Peripheral data is already in memory so [`Central::peripherals`] and
+//!   [`Peripheral::properties`] aren't async.
+//! - Only the advertising side exists: [`Peripheral`] has no `connect` or GATT calls.
+//!
+//! ```ignore
+//! let mut central = Central::new(adapter);
+//! central.start_scan(ScanFilter::default()).await?;
+//! let mut events = central.events().await?;
+//! while let Some(CentralEvent::DeviceDiscovered(id)) = events.next().await.transpose()? {
+//!     println!("{}", id);
+//! }
+//! ```
+use crate::hci::adapter;
+use crate::le::advertisement::{AdType, RawAdvertisement};
+use crate::le::advertiser::Advertiser;
+use crate::le::profile::{DeviceProfile, ServiceUUID};
+use crate::le::report::{AddressType, ReportInfo};
+use crate::le::scan::{Observer, ScanParameters, ScanType};
+use crate::uuid::UUID;
+use crate::{BTAddress, DeviceAddress};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use futures_util::future::LocalBoxFuture;
+use futures_util::stream::{self, LocalBoxStream, StreamExt};
+
+/// Identifies a peripheral (`btleplug::platform::PeripheralId`).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PeripheralId(pub DeviceAddress);
+impl core::fmt::Display for PeripheralId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.0, f)
+    }
+}
+/// Only report peripherals advertising one of `services`. Empty reports every peripheral.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ScanFilter {
+    pub services: Vec<UUID>,
+}
+/// What the advertisements said about a peripheral (`btleplug::api::PeripheralProperties`).
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PeripheralProperties {
+    pub address: BTAddress,
+    pub address_type: Option<AddressType>,
+    pub local_name: Option<String>,
+    /// Advertised TX power (dBm).
+    pub tx_power_level: Option<i16>,
+    /// RSSI of the last report (dBm).
+    pub rssi: Option<i16>,
+    /// Latest manufacturer data by company ID.
+    pub manufacturer_data: BTreeMap<u16, Vec<u8>>,
+    /// Latest service data by service UUID.
+    pub service_data: BTreeMap<UUID, Vec<u8>>,
+    pub services: Vec<UUID>,
+}
+impl PeripheralProperties {
+    fn new(address: BTAddress) -> PeripheralProperties {
+        PeripheralProperties {
+            address,
+            address_type: None,
+            local_name: None,
+            tx_power_level: None,
+            rssi: None,
+            manufacturer_data: BTreeMap::new(),
+            service_data: BTreeMap::new(),
+            services: Vec::new(),
+        }
+    }
+}
+/// `btleplug::api::CentralEvent` (the advertising related ones).
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum CentralEvent {
+    /// First report from a peripheral (that passes the [`ScanFilter`]).
+    DeviceDiscovered(PeripheralId),
+    DeviceUpdated(PeripheralId),
+    /// The report had manufacturer data. `manufacturer_data` is all of it seen so far.
+    ManufacturerDataAdvertisement {
+        id: PeripheralId,
+        manufacturer_data: BTreeMap<u16, Vec<u8>>,
+    },
+    /// The report had service data. `service_data` is all of it seen so far.
+    ServiceDataAdvertisement {
+        id: PeripheralId,
+        service_data: BTreeMap<UUID, Vec<u8>>,
+    },
+    /// The report listed services. `services` is all of them seen so far.
+    ServicesAdvertisement {
+        id: PeripheralId,
+        services: Vec<UUID>,
+    },
+}
+/// Snapshot of a peripheral (`btleplug::api::Peripheral`) taken by [`Central::peripherals`].
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct Peripheral {
+    id: PeripheralId,
+    properties: PeripheralProperties,
+}
+impl Peripheral {
+    pub fn id(&self) -> PeripheralId {
+        self.id
+    }
+    pub fn address(&self) -> BTAddress {
+        self.properties.address
+    }
+    /// Always `Some` (`btleplug` returns `None` for peripherals it hasn't heard yet).
+    pub fn properties(&self) -> Option<PeripheralProperties> {
+        Some(self.properties.clone())
+    }
+    pub fn services(&self) -> &[UUID] {
+        &self.properties.services
+    }
+}
+/// Peripherals heard since the scan started, least recently heard first.
+#[derive(Clone, Debug)]
+struct Peripherals {
+    filter: ScanFilter,
+    entries: Vec<(PeripheralId, DeviceProfile, PeripheralProperties)>,
+    max_peripherals: usize,
+}
+impl Peripherals {
+    fn is_match(&self, properties: &PeripheralProperties) -> bool {
+        self.filter.services.is_empty()
+            || properties
+                .services
+                .iter()
+                .any(|uuid| self.filter.services.contains(uuid))
+    }
+    /// Merge `report` and return the events it causes.
+    fn update<T: AsRef<[u8]>>(&mut self, report: &ReportInfo<T>) -> Vec<CentralEvent> {
+        let id = PeripheralId(report.device_address());
+        let (mut entry, was_match) = match self.entries.iter().position(|(i, _, _)| *i == id) {
+            Some(i) => {
+                let entry = self.entries.remove(i);
+                let was_match = self.is_match(&entry.2);
+                (entry, was_match)
+            }
+            None => (
+                (
+                    id,
+                    DeviceProfile::default(),
+                    PeripheralProperties::new(report.address),
+                ),
+                false,
+            ),
+        };
+        let (_, profile, properties) = &mut entry;
+        properties.address_type = Some(report.address_type);
+        properties.rssi = report.rssi.map(|rssi| i16::from(i8::from(rssi)));
+        profile.merge(&report.data);
+        properties.local_name.clone_from(&profile.name);
+        properties.services = profile.services.iter().map(|uuid| uuid.to_uuid()).collect();
+        let carried = merge_data(properties, &report.data);
+        let mut events = Vec::new();
+        if self.is_match(properties) {
+            events.push(if was_match {
+                CentralEvent::DeviceUpdated(id)
+            } else {
+                CentralEvent::DeviceDiscovered(id)
+            });
+            if carried.manufacturer_data {
+                events.push(CentralEvent::ManufacturerDataAdvertisement {
+                    id,
+                    manufacturer_data: properties.manufacturer_data.clone(),
+                });
+            }
+            if carried.service_data {
+                events.push(CentralEvent::ServiceDataAdvertisement {
+                    id,
+                    service_data: properties.service_data.clone(),
+                });
+            }
+            if carried.services {
+                events.push(CentralEvent::ServicesAdvertisement {
+                    id,
+                    services: properties.services.clone(),
+                });
+            }
+        }
+        if self.max_peripherals > 0 && self.entries.len() >= self.max_peripherals {
+            self.entries.remove(0);
+        }
+        self.entries.push(entry);
+        events
+    }
+}
+/// Which kinds of data a report carried.
+#[derive(Copy, Clone, Default)]
+struct Carried {
+    manufacturer_data: bool,
+    service_data: bool,
+    services: bool,
+}
+/// Merge the TX power, manufacturer data and service data of `data` into `properties`.
+fn merge_data<T: AsRef<[u8]>>(
+    properties: &mut PeripheralProperties,
+    data: &RawAdvertisement<T>,
+) -> Carried {
+    let mut carried = Carried::default();
+    for structure in data.iter() {
+        let buf = structure.buf.as_ref();
+        let uuid_len = match structure.ad_type {
+            AdType::TxPowerLevel if buf.len() == 1 => {
+                properties.tx_power_level = Some(i16::from(i8::from_le_bytes([buf[0]])));
+                continue;
+            }
+            AdType::ManufacturerData if buf.len() >= 2 => {
+                let company_id = u16::from_le_bytes([buf[0], buf[1]]);
+                properties
+                    .manufacturer_data
+                    .insert(company_id, buf[2..].to_vec());
+                carried.manufacturer_data = true;
+                continue;
+            }
+            AdType::IncompleteList16bitUUID
+            | AdType::CompleteList16bitUUID
+            | AdType::IncompleteList32bitUUID
+            | AdType::CompleteList32bitUUID
+            | AdType::IncompleteList128bitUUID
+            | AdType::CompleteList128bitUUID => {
+                carried.services = true;
+                continue;
+            }
+            AdType::ServiceData => 2,
+            AdType::ServiceData32bitUUID => 4,
+            AdType::ServiceData128bitUUID => 16,
+            _ => continue,
+        };
+        if let Some(uuid) = buf.get(..uuid_len).and_then(ServiceUUID::from_le_bytes) {
+            properties
+                .service_data
+                .insert(uuid.to_uuid(), buf[uuid_len..].to_vec());
+            carried.service_data = true;
+        }
+    }
+    carried
+}
+/// `btleplug::api::Central` (an `Adapter`) over `backend`.
+pub struct Central<B> {
+    backend: B,
+    peripherals: Peripherals,
+}
+impl<B: Observer + Advertiser> Central<B> {
+    pub const DEFAULT_MAX_PERIPHERALS: usize = 256;
+    pub fn new(backend: B) -> Central<B> {
+        Self::with_max_peripherals(backend, Self::DEFAULT_MAX_PERIPHERALS)
+    }
+    /// Remembers at most `max_peripherals`, forgetting the one heard least recently when full.
+    pub fn with_max_peripherals(backend: B, max_peripherals: usize) -> Central<B> {
+        Central {
+            backend,
+            peripherals: Peripherals {
+                filter: ScanFilter::default(),
+                entries: Vec::new(),
+                max_peripherals,
+            },
+        }
+    }
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+    /// The adapter itself, for everything `btleplug` can't do.
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+    pub fn into_inner(self) -> B {
+        self.backend
+    }
+    /// Start active scanning (as `btleplug` does) and report only peripherals passing `filter`.
+    pub async fn start_scan(&mut self, filter: ScanFilter) -> Result<(), adapter::Error> {
+        self.peripherals.filter = filter;
+        self.backend.set_scan_enable(false, false).await?;
+        self.backend
+            .set_scan_parameters(ScanParameters {
+                scan_type: ScanType::Active,
+                ..ScanParameters::DEFAULT
+            })
+            .await?;
+        self.backend.set_scan_enable(true, false).await
+    }
+    pub async fn stop_scan(&mut self) -> Result<(), adapter::Error> {
+        self.backend.set_scan_enable(false, false).await
+    }
+    /// Events from the backend's advertisement stream. Peripherals are only updated while the
+    /// stream is polled.
+    pub fn events(
+        &mut self,
+    ) -> LocalBoxFuture<
+        '_,
+        Result<LocalBoxStream<'_, Result<CentralEvent, adapter::Error>>, adapter::Error>,
+    > {
+        Box::pin(async move {
+            let Central {
+                backend,
+                peripherals,
+            } = self;
+            let reports = backend.advertisement_stream().await?;
+            Ok(reports
+                .flat_map(move |report| {
+                    let events = match report {
+                        Ok(report) => peripherals.update(&report).into_iter().map(Ok).collect(),
+                        Err(e) => alloc::vec![Err(e)],
+                    };
+                    stream::iter(events)
+                })
+                .boxed_local())
+        })
+    }
+    /// Peripherals passing the [`ScanFilter`], least recently heard first.
+    pub fn peripherals(&self) -> Vec<Peripheral> {
+        self.peripherals
+            .entries
+            .iter()
+            .filter(|(_, _, properties)| self.peripherals.is_match(properties))
+            .map(|(id, _, properties)| Peripheral {
+                id: *id,
+                properties: properties.clone(),
+            })
+            .collect()
+    }
+    pub fn peripheral(&self, id: &PeripheralId) -> Option<Peripheral> {
+        self.peripherals().into_iter().find(|p| p.id == *id)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uuid::UUID16;
+    use crate::RSSI;
+    #[test]
+    fn test_central_events() {
+        let mut peripherals = Peripherals {
+            filter: ScanFilter {
+                services: alloc::vec![ServiceUUID::UUID16(UUID16(0x180F)).to_uuid()],
+            },
+            entries: Vec::new(),
+            max_peripherals: 4,
+        };
+        let mut report = ReportInfo::<Vec<u8>> {
+            address: BTAddress([1; 6]),
+            rssi: Some(RSSI::new(-40)),
+            ..ReportInfo::default()
+        };
+        // Manufacturer data only, not reported until the service shows up.
+        report.data = RawAdvertisement(alloc::vec![0x04, 0xFF, 0x59, 0x00, 0x01]);
+        assert_eq!(peripherals.update(&report), Vec::new());
+        // Battery service UUID and battery level service data.
+        report.data = RawAdvertisement(alloc::vec![
+            0x03, 0x03, 0x0F, 0x18, 0x04, 0x16, 0x0F, 0x18, 0x64
+        ]);
+        let id = PeripheralId(report.device_address());
+        let events = peripherals.update(&report);
+        let battery = ServiceUUID::UUID16(UUID16(0x180F)).to_uuid();
+        assert_eq!(
+            alloc::format!("{}", battery),
+            "0000180f-0000-1000-8000-00805f9b34fb"
+        );
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0], CentralEvent::DeviceDiscovered(id));
+        match &events[1] {
+            CentralEvent::ServiceDataAdvertisement { service_data, .. } => {
+                assert_eq!(service_data.get(&battery), Some(&alloc::vec![0x64]))
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        report.data = RawAdvertisement(alloc::vec![0x02, 0x0A, 0xF8]);
+        assert_eq!(
+            peripherals.update(&report),
+            [CentralEvent::DeviceUpdated(id)]
+        );
+        let (_, _, properties) = &peripherals.entries[0];
+        assert_eq!(properties.tx_power_level, Some(-8));
+        assert_eq!(properties.rssi, Some(-40));
+        assert_eq!(
+            properties.manufacturer_data.get(&0x0059),
+            Some(&alloc::vec![0x01])
+        );
+    }
+}
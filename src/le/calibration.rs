@@ -0,0 +1,146 @@
+//! Per adapter RSSI calibration. Dongles report dBm with large constant biases (and some with a
+//! non-linear response) so the same beacon at the same distance reads differently on each model.
+//! An [`RssiCalibration`] (usually loaded from config with the `serde-1` feature) corrects the RSSI
+//! of reports before they are handed to distance estimation, [`crate::le::proximity`], etc.
+use crate::le::report::{AdapterIndex, ReportInfo};
+use crate::RSSI;
+use alloc::vec::Vec;
+
+/// Reference measurement for a calibration curve: the adapter reported `measured` when the
+/// actual signal strength was `actual`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct CalibrationPoint {
+    pub measured: i8,
+    pub actual: i8,
+}
+/// Correction for one adapter model. The RSSI is mapped through `curve` (if it has any points)
+/// and then `offset` is added.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct RssiCalibration {
+    /// Constant correction in dB.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub offset: i8,
+    /// Points of a piecewise linear curve, sorted by `measured`. RSSI below the first or above
+    /// the last point is shifted by that point's correction.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub curve: Vec<CalibrationPoint>,
+}
+impl RssiCalibration {
+    /// Leaves the RSSI as is.
+    pub const IDENTITY: RssiCalibration = RssiCalibration::offset(0);
+    pub const fn offset(offset: i8) -> RssiCalibration {
+        RssiCalibration {
+            offset,
+            curve: Vec::new(),
+        }
+    }
+    /// Calibration from curve points in any order.
+    pub fn from_curve(mut curve: Vec<CalibrationPoint>, offset: i8) -> RssiCalibration {
+        curve.sort();
+        curve.dedup_by_key(|p| p.measured);
+        RssiCalibration { offset, curve }
+    }
+    pub fn is_identity(&self) -> bool {
+        self.offset == 0 && self.curve.iter().all(|p| p.measured == p.actual)
+    }
+    fn apply_curve(&self, measured: i32) -> i32 {
+        let after = self
+            .curve
+            .iter()
+            .position(|p| i32::from(p.measured) >= measured);
+        let shift = |p: &CalibrationPoint| measured + i32::from(p.actual) - i32::from(p.measured);
+        match after {
+            None => self.curve.last().map_or(measured, shift),
+            Some(0) => shift(&self.curve[0]),
+            Some(i) => {
+                let (low, high) = (self.curve[i - 1], self.curve[i]);
+                let (x0, y0) = (i32::from(low.measured), i32::from(low.actual));
+                let (x1, y1) = (i32::from(high.measured), i32::from(high.actual));
+                let num = (y1 - y0) * (measured - x0);
+                let den = x1 - x0;
+                // Rounded to the nearest dB.
+                y0 + (2 * num + den * num.signum()) / (2 * den)
+            }
+        }
+    }
+    /// Calibrated `rssi`, clamped to the valid RSSI range.
+    pub fn calibrate(&self, rssi: RSSI) -> RSSI {
+        let dbm = self.apply_curve(i8::from(rssi).into()) + i32::from(self.offset);
+        RSSI::new(dbm.clamp(RSSI::MIN_RSSI_I8.into(), RSSI::MAX_RSSI_I8.into()) as i8)
+    }
+    /// Calibrate the RSSI of `report` (if it has one).
+    pub fn calibrate_report<T>(&self, report: &mut ReportInfo<T>) {
+        report.rssi = report.rssi.map(|rssi| self.calibrate(rssi));
+    }
+}
+/// [`RssiCalibration`] for each adapter. Reports from adapters without an override (or without
+/// [`crate::le::report::ReportOrigin::adapter`] set) use `default`.
+#[derive(Clone, Debug, Default)]
+pub struct RssiCalibrations {
+    pub default: RssiCalibration,
+    overrides: Vec<(AdapterIndex, RssiCalibration)>,
+}
+impl RssiCalibrations {
+    pub fn new(default: RssiCalibration) -> RssiCalibrations {
+        RssiCalibrations {
+            default,
+            overrides: Vec::new(),
+        }
+    }
+    /// Use `calibration` for reports from `adapter`. Adapters of the same model can share a
+    /// profile by setting a clone of it on each.
+    pub fn set(&mut self, adapter: AdapterIndex, calibration: RssiCalibration) {
+        match self.overrides.iter_mut().find(|(a, _)| *a == adapter) {
+            Some((_, c)) => *c = calibration,
+            None => self.overrides.push((adapter, calibration)),
+        }
+    }
+    /// Go back to the `default` calibration for `adapter`.
+    pub fn clear(&mut self, adapter: AdapterIndex) {
+        self.overrides.retain(|(a, _)| *a != adapter);
+    }
+    pub fn calibration_for(&self, adapter: Option<AdapterIndex>) -> &RssiCalibration {
+        adapter
+            .and_then(|adapter| self.overrides.iter().find(|(a, _)| *a == adapter))
+            .map_or(&self.default, |(_, c)| c)
+    }
+    /// Calibrate the RSSI of `report` with the calibration for the adapter that received it.
+    pub fn calibrate_report<T>(&self, report: &mut ReportInfo<T>) {
+        self.calibration_for(report.origin.adapter)
+            .calibrate_report(report);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_calibration() {
+        let point = |measured, actual| CalibrationPoint { measured, actual };
+        let rssi = RSSI::new;
+        assert_eq!(RssiCalibration::offset(-8).calibrate(rssi(-60)), rssi(-68));
+        assert_eq!(RssiCalibration::offset(30).calibrate(rssi(0)), rssi(20));
+        let curve = RssiCalibration::from_curve(
+            alloc::vec![point(-40, -45), point(-90, -80), point(-60, -62)],
+            0,
+        );
+        assert_eq!(curve.calibrate(rssi(-60)), rssi(-62));
+        assert_eq!(curve.calibrate(rssi(-75)), rssi(-71));
+        assert_eq!(curve.calibrate(rssi(-50)), rssi(-53));
+        // Outside the curve keeps the end point's correction.
+        assert_eq!(curve.calibrate(rssi(-100)), rssi(-90));
+        assert_eq!(curve.calibrate(rssi(-30)), rssi(-35));
+        let mut calibrations = RssiCalibrations::default();
+        calibrations.set(AdapterIndex(1), RssiCalibration::offset(5));
+        let mut report = ReportInfo::<crate::le::advertisement::StaticAdvBuffer> {
+            rssi: Some(rssi(-70)),
+            ..ReportInfo::default()
+        };
+        calibrations.calibrate_report(&mut report);
+        assert_eq!(report.rssi, Some(rssi(-70)));
+        let mut report = report.with_adapter(AdapterIndex(1));
+        calibrations.calibrate_report(&mut report);
+        assert_eq!(report.rssi, Some(rssi(-65)));
+    }
+}
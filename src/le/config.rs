@@ -0,0 +1,453 @@
+//! Declarative adapter configuration. A [`ProfileConfig`] lists named [`AdapterProfile`]s (which
+//! adapter to open, how to scan, which reports to keep and what to advertise) so fleet
+//! deployments can ship BLE setup as data. With the `serde-1` feature the config deserializes
+//! from any serde format, every field but `name` can be left out.
+//!
+//! ```ignore
+//! let config: ProfileConfig = serde_json::from_str(&std::fs::read_to_string("ble.json")?)?;
+//! let profile = config.apply("gateway", &mut adapter).await?;
+//! let filter = profile.filter.compile()?;
+//! ```
+use crate::hci::adapter;
+use crate::le::advertisement::MAX_ADV_LEN;
+use crate::le::advertiser::{
+    Advertiser, AdvertisingInterval, AdvertisingParameters, AdvertisingType,
+};
+use crate::le::matcher::ManufacturerDataMatcher;
+use crate::le::report::ReportInfo;
+use crate::le::scan::{Observer, ScanInterval, ScanParameters, ScanType, ScanWindow};
+use crate::{CompanyID, RSSI};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum ConfigError {
+    /// No profile with this name.
+    UnknownProfile,
+    InvalidScanInterval,
+    /// The scan window is invalid or longer than the scan interval.
+    InvalidScanWindow,
+    InvalidAdvertisingInterval,
+    /// Advertising data or scan response longer than [`MAX_ADV_LEN`].
+    AdvertisingDataTooLong,
+    /// Legacy advertising only has one advertising set.
+    TooManyAdvertisingSets,
+    /// `min_rssi` is out of range or the manufacturer data pattern can't be matched.
+    InvalidFilter,
+    Adapter(adapter::Error),
+}
+impl From<adapter::Error> for ConfigError {
+    fn from(e: adapter::Error) -> Self {
+        ConfigError::Adapter(e)
+    }
+}
+impl crate::error::Error for ConfigError {}
+/// Converts milliseconds to 0.625 ms units.
+fn ms_to_units(ms: u32) -> Option<u16> {
+    u16::try_from(u64::from(ms) * 8 / 5).ok()
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-1", serde(default))]
+pub struct ScanConfig {
+    /// Send scan requests (active scanning) instead of only listening.
+    pub active: bool,
+    pub interval_ms: u32,
+    /// Listen time per interval. At most `interval_ms`.
+    pub window_ms: u32,
+    pub filter_duplicates: bool,
+}
+impl ScanConfig {
+    /// Passive scanning at [`ScanParameters::DEFAULT`] timing (10 ms every 10 ms).
+    pub const DEFAULT: ScanConfig = ScanConfig {
+        active: false,
+        interval_ms: 10,
+        window_ms: 10,
+        filter_duplicates: false,
+    };
+    pub fn parameters(&self) -> Result<ScanParameters, ConfigError> {
+        let scan_interval = ms_to_units(self.interval_ms)
+            .and_then(ScanInterval::new_checked)
+            .ok_or(ConfigError::InvalidScanInterval)?;
+        let scan_window = match ms_to_units(self.window_ms).and_then(ScanWindow::new_checked) {
+            Some(window) if u16::from(window) <= u16::from(scan_interval) => window,
+            _ => return Err(ConfigError::InvalidScanWindow),
+        };
+        Ok(ScanParameters {
+            scan_type: if self.active {
+                ScanType::Active
+            } else {
+                ScanType::Passive
+            },
+            scan_interval,
+            scan_window,
+            ..ScanParameters::DEFAULT
+        })
+    }
+}
+impl Default for ScanConfig {
+    fn default() -> Self {
+        ScanConfig::DEFAULT
+    }
+}
+/// Which advertising reports an application keeps. Applied on the host, see
+/// [`ReportFilterConfig::compile`].
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-1", serde(default))]
+pub struct ReportFilterConfig {
+    /// Drop reports weaker than this (dBm). Reports without RSSI are kept.
+    pub min_rssi: Option<i8>,
+    /// Only keep reports with manufacturer data from this company.
+    pub company_id: Option<u16>,
+    /// Manufacturer data (after the company ID) must start with these bytes. Needs `company_id`.
+    pub manufacturer_prefix: Vec<u8>,
+}
+impl ReportFilterConfig {
+    pub fn compile(&self) -> Result<ReportFilter, ConfigError> {
+        let min_rssi = match self.min_rssi {
+            Some(dbm) => Some(RSSI::try_from(dbm).map_err(|_| ConfigError::InvalidFilter)?),
+            None => None,
+        };
+        let manufacturer = match self.company_id {
+            Some(company_id) => Some(
+                ManufacturerDataMatcher::with_prefix(
+                    CompanyID(company_id),
+                    &self.manufacturer_prefix,
+                    &[],
+                )
+                .map_err(|_| ConfigError::InvalidFilter)?,
+            ),
+            None if self.manufacturer_prefix.is_empty() => None,
+            None => return Err(ConfigError::InvalidFilter),
+        };
+        Ok(ReportFilter {
+            min_rssi,
+            manufacturer,
+        })
+    }
+}
+/// Compiled [`ReportFilterConfig`].
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ReportFilter {
+    pub min_rssi: Option<RSSI>,
+    pub manufacturer: Option<ManufacturerDataMatcher>,
+}
+impl ReportFilter {
+    pub fn matches<T: AsRef<[u8]>>(&self, report: &ReportInfo<T>) -> bool {
+        let is_strong = match (self.min_rssi, report.rssi) {
+            (Some(min_rssi), Some(rssi)) => rssi >= min_rssi,
+            _ => true,
+        };
+        is_strong
+            && self
+                .manufacturer
+                .as_ref()
+                .is_none_or(|matcher| matcher.matches_report(report))
+    }
+}
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-1", serde(default))]
+pub struct AdvertisingSetConfig {
+    pub interval_ms: u32,
+    /// Connectable and scannable (`ADV_IND`) or neither (`ADV_NONCONN_IND`).
+    pub connectable: bool,
+    /// Raw advertising data (AD structures).
+    pub data: Vec<u8>,
+    pub scan_response: Vec<u8>,
+}
+impl AdvertisingSetConfig {
+    pub fn parameters(&self) -> Result<AdvertisingParameters, ConfigError> {
+        if self.data.len() > MAX_ADV_LEN || self.scan_response.len() > MAX_ADV_LEN {
+            return Err(ConfigError::AdvertisingDataTooLong);
+        }
+        let interval = ms_to_units(self.interval_ms)
+            .and_then(|units| AdvertisingInterval::try_from(units).ok())
+            .ok_or(ConfigError::InvalidAdvertisingInterval)?;
+        let advertising_type = if self.connectable {
+            AdvertisingType::AdvInd
+        } else {
+            AdvertisingType::AdvNonnConnInd
+        };
+        if !self.connectable && interval < AdvertisingInterval::MIN_NON_CONN {
+            return Err(ConfigError::InvalidAdvertisingInterval);
+        }
+        Ok(AdvertisingParameters {
+            interval_min: interval,
+            interval_max: interval,
+            advertising_type,
+            ..AdvertisingParameters::DEFAULT
+        })
+    }
+}
+impl Default for AdvertisingSetConfig {
+    /// Connectable advertising at [`AdvertisingInterval::DEFAULT`] (1.28 s) with no data.
+    fn default() -> Self {
+        AdvertisingSetConfig {
+            interval_ms: 1280,
+            connectable: true,
+            data: Vec::new(),
+            scan_response: Vec::new(),
+        }
+    }
+}
+/// Everything needed to bring one adapter up.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdapterProfile {
+    pub name: String,
+    /// Adapter URI for [`crate::hci::adapters::registry`]. `None` leaves the choice of adapter to
+    /// the application.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub uri: Option<String>,
+    /// Scanning is left disabled if `None`.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub scan: Option<ScanConfig>,
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub filter: ReportFilterConfig,
+    /// Advertising is left disabled if empty. Legacy advertising supports one set.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub advertising_sets: Vec<AdvertisingSetConfig>,
+}
+impl AdapterProfile {
+    /// Check the whole profile without touching an adapter.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(scan) = &self.scan {
+            scan.parameters()?;
+        }
+        self.filter.compile()?;
+        if self.advertising_sets.len() > 1 {
+            return Err(ConfigError::TooManyAdvertisingSets);
+        }
+        for set in &self.advertising_sets {
+            set.parameters()?;
+        }
+        Ok(())
+    }
+    /// Open the transport at `uri` with the process wide registry.
+    #[cfg(feature = "std")]
+    pub async fn connect(
+        &self,
+    ) -> Result<crate::hci::adapters::registry::DynStream, adapter::Error> {
+        match &self.uri {
+            Some(uri) => crate::hci::adapters::registry::connect(uri).await,
+            None => Err(adapter::Error::BadParameter),
+        }
+    }
+    /// Validate the profile then (re)configure advertising and scanning on `backend`. Both are
+    /// disabled while their parameters change and enabled again if the profile has them. The
+    /// report filter is applied on the host (see [`ReportFilterConfig::compile`]).
+    pub async fn apply<B: Observer + Advertiser>(
+        &self,
+        backend: &mut B,
+    ) -> Result<(), ConfigError> {
+        self.validate()?;
+        backend.set_advertising_enable(false).await?;
+        if let Some(set) = self.advertising_sets.first() {
+            backend
+                .set_advertising_parameters(set.parameters()?)
+                .await?;
+            backend.set_advertising_data(&set.data).await?;
+            backend.set_scan_response_data(&set.scan_response).await?;
+            backend.set_advertising_enable(true).await?;
+        }
+        backend.set_scan_enable(false, false).await?;
+        if let Some(scan) = &self.scan {
+            backend.set_scan_parameters(scan.parameters()?).await?;
+            backend
+                .set_scan_enable(true, scan.filter_duplicates)
+                .await?;
+        }
+        Ok(())
+    }
+}
+/// A set of named [`AdapterProfile`]s.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-1", serde(default))]
+pub struct ProfileConfig {
+    pub profiles: Vec<AdapterProfile>,
+}
+impl ProfileConfig {
+    pub fn get(&self, name: &str) -> Option<&AdapterProfile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+    /// Apply the profile called `name` to `backend`. See [`AdapterProfile::apply`].
+    pub async fn apply<B: Observer + Advertiser>(
+        &self,
+        name: &str,
+        backend: &mut B,
+    ) -> Result<&AdapterProfile, ConfigError> {
+        let profile = self.get(name).ok_or(ConfigError::UnknownProfile)?;
+        profile.apply(backend).await?;
+        Ok(profile)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::sim::SimTime;
+    use crate::hci::le::whitelist::WhitelistEntry;
+    use crate::le::advertisement::{RawAdvertisement, StaticAdvBuffer};
+    use alloc::boxed::Box;
+    use futures_util::future::LocalBoxFuture;
+    use futures_util::stream::{self, LocalBoxStream};
+    /// Records the calls made to it.
+    #[derive(Default)]
+    struct MockBackend {
+        calls: Vec<&'static str>,
+        scan_parameters: Option<ScanParameters>,
+    }
+    impl MockBackend {
+        fn call(&mut self, name: &'static str) -> LocalBoxFuture<'_, Result<(), adapter::Error>> {
+            self.calls.push(name);
+            Box::pin(async { Ok(()) })
+        }
+    }
+    impl Observer for MockBackend {
+        fn set_scan_parameters(
+            &mut self,
+            scan_parameters: ScanParameters,
+        ) -> LocalBoxFuture<'_, Result<(), adapter::Error>> {
+            self.scan_parameters = Some(scan_parameters);
+            self.call("scan parameters")
+        }
+
+        fn set_scan_enable(
+            &mut self,
+            is_enabled: bool,
+            _filter_duplicates: bool,
+        ) -> LocalBoxFuture<'_, Result<(), adapter::Error>> {
+            self.call(if is_enabled { "scan on" } else { "scan off" })
+        }
+
+        fn advertisement_stream<'a>(
+            &'a mut self,
+        ) -> LocalBoxFuture<
+            'a,
+            Result<
+                LocalBoxStream<'a, Result<ReportInfo<StaticAdvBuffer>, adapter::Error>>,
+                adapter::Error,
+            >,
+        > {
+            Box::pin(async { Ok(Box::pin(stream::empty()) as LocalBoxStream<'a, _>) })
+        }
+    }
+    impl Advertiser for MockBackend {
+        fn set_advertising_enable(
+            &mut self,
+            is_enabled: bool,
+        ) -> LocalBoxFuture<'_, Result<(), adapter::Error>> {
+            self.call(if is_enabled { "adv on" } else { "adv off" })
+        }
+
+        fn set_advertising_parameters(
+            &mut self,
+            _advertising_parameters: AdvertisingParameters,
+        ) -> LocalBoxFuture<'_, Result<(), adapter::Error>> {
+            self.call("adv parameters")
+        }
+
+        fn set_advertising_data<'d, 'a: 'd>(
+            &'a mut self,
+            _data: &'d [u8],
+        ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+            self.call("adv data")
+        }
+
+        fn set_scan_response_data<'d, 'a: 'd>(
+            &'a mut self,
+            _data: &'d [u8],
+        ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+            self.call("scan response")
+        }
+
+        fn set_whitelist<'d, 'a: 'd>(
+            &'a mut self,
+            _entries: &'d [WhitelistEntry],
+        ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+            self.call("whitelist")
+        }
+    }
+    #[test]
+    fn test_apply_profile() {
+        let gateway = AdapterProfile {
+            name: "gateway".into(),
+            scan: Some(ScanConfig {
+                active: true,
+                interval_ms: 100,
+                window_ms: 50,
+                filter_duplicates: true,
+            }),
+            advertising_sets: alloc::vec![AdvertisingSetConfig {
+                data: alloc::vec![0x02, 0x01, 0x06],
+                ..AdvertisingSetConfig::default()
+            }],
+            ..AdapterProfile::default()
+        };
+        let config = ProfileConfig {
+            profiles: alloc::vec![gateway],
+        };
+        let sim = SimTime::new();
+        let mut backend = MockBackend::default();
+        assert!(sim.block_on(config.apply("gateway", &mut backend)).is_ok());
+        assert_eq!(
+            backend.calls,
+            [
+                "adv off",
+                "adv parameters",
+                "adv data",
+                "scan response",
+                "adv on",
+                "scan off",
+                "scan parameters",
+                "scan on"
+            ]
+        );
+        let parameters = backend.scan_parameters.unwrap();
+        assert_eq!(parameters.scan_type, ScanType::Active);
+        assert_eq!(u16::from(parameters.scan_interval), 160);
+        assert_eq!(u16::from(parameters.scan_window), 80);
+
+        // Nothing is sent if any part of the profile is invalid.
+        let mut invalid = config.profiles[0].clone();
+        invalid.scan.as_mut().unwrap().window_ms = 200;
+        let mut backend = MockBackend::default();
+        assert_eq!(
+            sim.block_on(invalid.apply(&mut backend)),
+            Err(ConfigError::InvalidScanWindow)
+        );
+        assert!(backend.calls.is_empty());
+        assert!(matches!(
+            sim.block_on(config.apply("beacon", &mut backend)),
+            Err(ConfigError::UnknownProfile)
+        ));
+
+        let filter = ReportFilterConfig {
+            min_rssi: Some(-70),
+            company_id: Some(0x004C),
+            manufacturer_prefix: alloc::vec![0x02, 0x15],
+        }
+        .compile()
+        .unwrap();
+        let mut report = ReportInfo::<Vec<u8>> {
+            rssi: Some(RSSI::new(-60)),
+            ..ReportInfo::default()
+        };
+        assert!(!filter.matches(&report));
+        report.data = RawAdvertisement(alloc::vec![0x05, 0xFF, 0x4C, 0x00, 0x02, 0x15]);
+        assert!(filter.matches(&report));
+        report.rssi = Some(RSSI::new(-80));
+        assert!(!filter.matches(&report));
+        assert_eq!(
+            ReportFilterConfig {
+                manufacturer_prefix: alloc::vec![1],
+                ..ReportFilterConfig::default()
+            }
+            .compile(),
+            Err(ConfigError::InvalidFilter)
+        );
+    }
+}
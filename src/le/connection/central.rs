@@ -1,6 +1,265 @@
+use crate::hci::adapter;
+use crate::hci::adapters::le::LEAdapter;
+use crate::hci::adapters::pending::PendingCommand;
 use crate::hci::adapters::DummyUnrecognizedEventHandler;
+use crate::hci::le::connection::CreateConnection;
+use crate::hci::le::resolving_list::{PeerIdentity, ResolvingListEntry};
+use crate::hci::ErrorCode;
+use crate::key_store::KeyStore;
+use crate::le::connection::qos::ConnectionQos;
+use crate::le::connection::{CELength, InitiatorFilterPolicy};
+use crate::le::scan::{OwnAddressType, ScanInterval, ScanWindow};
+use crate::le::smp::keys::IRK;
+use crate::DeviceAddress;
 
 pub struct Central<A: crate::hci::adapter::Adapter> {
-    pub hci_adapter: crate::hci::adapters::le::LEAdapter<A, DummyUnrecognizedEventHandler>,
+    pub hci_adapter: LEAdapter<A, DummyUnrecognizedEventHandler>,
+    /// IRK we distribute when bonding. Given to the controller with each peer so our own
+    /// address is private too. [`IRK::ZEROED`] (the default) uses our public address.
+    pub local_irk: IRK,
+    /// Connection parameters of new connections.
+    pub qos: ConnectionQos,
+}
+impl<A: crate::hci::adapter::Adapter> Central<A> {
+    pub fn new(hci_adapter: LEAdapter<A, DummyUnrecognizedEventHandler>) -> Self {
+        Central {
+            hci_adapter,
+            local_irk: IRK::ZEROED,
+            qos: ConnectionQos::DEFAULT,
+        }
+    }
+    /// Reconnect to the bonded device with the identity address `identity`, whatever private
+    /// address it's advertising with. The peer's IRK from `keys` is put in the controller's
+    /// Resolving List (with address resolution enabled) and the White List is replaced by
+    /// `identity`, so the controller connects when it resolves one of the peer's addresses.
+    /// Peers bonded without an IRK have to advertise with their identity address.
+    ///
+    /// Await [`PendingCommand::complete`] for the connection (there's no timeout) or
+    /// [`PendingCommand::cancel`] it. Advertising, scanning and connecting have to be stopped
+    /// first so the lists can be changed.
+    /// # Errors
+    /// Returns `adapter::Error::BadParameter` if `identity` isn't bonded in `keys`.
+    pub async fn reconnect<K: KeyStore>(
+        &mut self,
+        keys: &K,
+        identity: DeviceAddress,
+    ) -> Result<
+        PendingCommand<'_, A, DummyUnrecognizedEventHandler, CreateConnection>,
+        adapter::Error,
+    > {
+        let bond = keys
+            .bond(identity.addr)
+            .ok_or(adapter::Error::BadParameter)?;
+        let peer = PeerIdentity::from(identity);
+        let own_address_type = match bond.peer_irk {
+            Some(peer_irk) => {
+                self.hci_adapter
+                    .set_address_resolution_enable(false)
+                    .await?;
+                // Replace the entry if the peer is already in the list (adding it again fails).
+                match self.hci_adapter.remove_from_resolving_list(peer).await {
+                    Ok(()) | Err(adapter::Error::ErrorCode(ErrorCode::NoConnection)) => (),
+                    Err(e) => return Err(e),
+                }
+                self.hci_adapter
+                    .add_to_resolving_list(ResolvingListEntry {
+                        peer,
+                        peer_irk,
+                        local_irk: self.local_irk,
+                    })
+                    .await?;
+                self.hci_adapter.set_address_resolution_enable(true).await?;
+                OwnAddressType::PrivateOrPublic
+            }
+            None => OwnAddressType::Public,
+        };
+        self.hci_adapter.load_whitelist(&[identity]).await?;
+        let parameters = self.qos.parameters();
+        let create = CreateConnection {
+            le_scan_interval: ScanInterval::DEFAULT,
+            le_scan_window: ScanWindow::DEFAULT,
+            initiator_filter_policy: InitiatorFilterPolicy::WhiteList,
+            peer_address_type: peer.address_type,
+            peer_address: peer.address,
+            own_address_type,
+            connection_interval_min: parameters.interval_min,
+            connection_interval_max: parameters.interval_max,
+            connection_latency: parameters.latency,
+            supervision_timeout: parameters.supervision_timeout,
+            min_ce_len: CELength::MIN,
+            max_ce_len: CELength::MIN,
+        };
+        self.hci_adapter.adapter.hci_start_command(create).await
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hci::adapters::tests::{command_complete, ScriptedAdapter};
+    use crate::hci::adapters::Adapter;
+    use crate::hci::command::Command;
+    use crate::hci::event::{CommandComplete, CommandStatus, Event, EventPacket};
+    use crate::hci::le::resolving_list::{
+        AddDeviceToResolvingList, RemoveDeviceFromResolvingList, SetAddressResolutionEnable,
+    };
+    use crate::hci::le::whitelist::{
+        AddDeviceToWhitelist, ClearWhitelist, ReadWhitelistSize, WhitelistEntry,
+        WhitelistSizeReturn,
+    };
+    use crate::hci::Opcode;
+    use crate::key_store::{Bond, MemoryKeyStore};
+    use crate::BTAddress;
+    use alloc::boxed::Box;
+    use alloc::collections::VecDeque;
+    use alloc::vec::Vec;
+    use futures_util::FutureExt;
+
+    const PEER: BTAddress = BTAddress([1, 2, 3, 4, 5, 6]);
+    const PEER_IRK: IRK = IRK([0x11; 16]);
+    const LOCAL_IRK: IRK = IRK([0x22; 16]);
+    fn command_status(opcode: Opcode) -> EventPacket<Box<[u8]>> {
+        CommandStatus {
+            status: ErrorCode::Ok,
+            num_command_packets: 1,
+            opcode,
+        }
+        .event_pack_packet::<Box<[u8]>>()
+        .unwrap()
+    }
+    fn whitelist_events() -> Vec<EventPacket<Box<[u8]>>> {
+        let size = CommandComplete {
+            num_command_packets: 1,
+            opcode: ReadWhitelistSize::opcode(),
+            params: WhitelistSizeReturn {
+                status: ErrorCode::Ok,
+                size: 8,
+            },
+        };
+        alloc::vec![
+            size.event_pack_packet::<Box<[u8]>>().unwrap(),
+            command_complete(ClearWhitelist::opcode(), ErrorCode::Ok),
+            command_complete(AddDeviceToWhitelist::opcode(), ErrorCode::Ok),
+            command_status(CreateConnection::opcode()),
+        ]
+    }
+    /// Run `Central::reconnect` to `PEER` against `events`. Returns the written commands.
+    fn reconnect(bond: Bond, events: VecDeque<EventPacket<Box<[u8]>>>) -> Vec<(Opcode, Box<[u8]>)> {
+        let adapter = ScriptedAdapter::new(events);
+        let written = adapter.written.clone();
+        let parameters = adapter.parameters.clone();
+        let mut keys = MemoryKeyStore::new();
+        keys.store_bond(bond);
+        let mut central = Central::new(Adapter::new(adapter).le());
+        central.local_irk = LOCAL_IRK;
+        central
+            .reconnect(&keys, DeviceAddress::public(PEER))
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert!(central
+            .hci_adapter
+            .adapter
+            .adapter
+            .events
+            .borrow()
+            .is_empty());
+        let written = written.borrow().clone();
+        let parameters = parameters.borrow().clone();
+        written.into_iter().zip(parameters).collect()
+    }
+    fn unpack<C: Command>(written: &(Opcode, Box<[u8]>)) -> C {
+        assert_eq!(written.0, C::opcode());
+        C::unpack_from(&written.1).unwrap()
+    }
+    fn check_create_connection(written: &(Opcode, Box<[u8]>), own_address_type: OwnAddressType) {
+        let create: CreateConnection = unpack(written);
+        assert_eq!(
+            create.initiator_filter_policy,
+            InitiatorFilterPolicy::WhiteList
+        );
+        assert_eq!(create.peer(), DeviceAddress::public(PEER).into());
+        assert_eq!(create.own_address_type, own_address_type);
+        assert_eq!(create.parameters(), ConnectionQos::DEFAULT.parameters());
+    }
+    #[test]
+    fn test_reconnect_with_irk() {
+        let mut bond = Bond::new(PEER);
+        bond.peer_irk = Some(PEER_IRK);
+        let mut events: VecDeque<_> = alloc::vec![
+            command_complete(SetAddressResolutionEnable::opcode(), ErrorCode::Ok),
+            // Not in the Resolving List yet.
+            command_complete(
+                RemoveDeviceFromResolvingList::opcode(),
+                ErrorCode::NoConnection,
+            ),
+            command_complete(AddDeviceToResolvingList::opcode(), ErrorCode::Ok),
+            command_complete(SetAddressResolutionEnable::opcode(), ErrorCode::Ok),
+        ]
+        .into_iter()
+        .collect();
+        events.extend(whitelist_events());
+        let written = reconnect(bond, events);
+        assert_eq!(written.len(), 8);
+        let peer = PeerIdentity::from(DeviceAddress::public(PEER));
+        assert_eq!(
+            unpack::<SetAddressResolutionEnable>(&written[0]),
+            SetAddressResolutionEnable { is_enabled: false }
+        );
+        assert_eq!(
+            unpack::<RemoveDeviceFromResolvingList>(&written[1]),
+            RemoveDeviceFromResolvingList(peer)
+        );
+        assert_eq!(
+            unpack::<AddDeviceToResolvingList>(&written[2]),
+            AddDeviceToResolvingList(ResolvingListEntry {
+                peer,
+                peer_irk: PEER_IRK,
+                local_irk: LOCAL_IRK,
+            })
+        );
+        assert_eq!(
+            unpack::<SetAddressResolutionEnable>(&written[3]),
+            SetAddressResolutionEnable { is_enabled: true }
+        );
+        unpack::<ReadWhitelistSize>(&written[4]);
+        unpack::<ClearWhitelist>(&written[5]);
+        assert_eq!(
+            unpack::<AddDeviceToWhitelist>(&written[6]),
+            AddDeviceToWhitelist(WhitelistEntry::public(PEER))
+        );
+        check_create_connection(&written[7], OwnAddressType::PrivateOrPublic);
+    }
+    #[test]
+    fn test_reconnect_without_irk() {
+        let written = reconnect(Bond::new(PEER), whitelist_events().into_iter().collect());
+        let opcodes: Vec<Opcode> = written.iter().map(|(opcode, _)| *opcode).collect();
+        assert_eq!(
+            opcodes,
+            [
+                ReadWhitelistSize::opcode(),
+                ClearWhitelist::opcode(),
+                AddDeviceToWhitelist::opcode(),
+                CreateConnection::opcode()
+            ]
+        );
+        assert_eq!(
+            unpack::<AddDeviceToWhitelist>(&written[2]),
+            AddDeviceToWhitelist(WhitelistEntry::public(PEER))
+        );
+        check_create_connection(&written[3], OwnAddressType::Public);
+    }
+    #[test]
+    fn test_reconnect_not_bonded() {
+        let adapter = ScriptedAdapter::new(VecDeque::new());
+        let written = adapter.written.clone();
+        let mut central = Central::new(Adapter::new(adapter).le());
+        assert!(matches!(
+            central
+                .reconnect(&MemoryKeyStore::new(), DeviceAddress::public(PEER))
+                .now_or_never()
+                .unwrap(),
+            Err(adapter::Error::BadParameter)
+        ));
+        assert!(written.borrow().is_empty());
+    }
 }
-impl<A: crate::hci::adapter::Adapter> Central<A> {}
@@ -1,7 +1,10 @@
 pub mod central;
+pub mod peripheral;
+pub mod qos;
 
-use crate::ConversionError;
+use crate::{ConversionError, PackError};
 use core::convert::TryFrom;
+use core::time::Duration;
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
 pub struct MTU(u16);
@@ -100,6 +103,71 @@ impl From<CELength> for u16 {
         l.0
     }
 }
+/// Link layer payload size in octets, for the data length extension.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct DataLengthOctets(u16);
+impl DataLengthOctets {
+    pub const BYTE_LEN: usize = 2;
+    /// Payload size without the data length extension.
+    pub const MIN_U16: u16 = 0x001B;
+    pub const MIN: DataLengthOctets = DataLengthOctets(Self::MIN_U16);
+    pub const MAX_U16: u16 = 0x00FB;
+    pub const MAX: DataLengthOctets = DataLengthOctets(Self::MAX_U16);
+    pub fn new(value: u16) -> Self {
+        match Self::new_checked(value) {
+            Some(s) => s,
+            None => panic!("data length octets out of range (`{}`)", value),
+        }
+    }
+    pub fn new_checked(value: u16) -> Option<Self> {
+        if value > Self::MAX_U16 || value < Self::MIN_U16 {
+            None
+        } else {
+            Some(Self(value))
+        }
+    }
+}
+impl From<DataLengthOctets> for u16 {
+    fn from(o: DataLengthOctets) -> Self {
+        o.0
+    }
+}
+/// Time to transmit a link layer packet in microseconds, for the data length extension.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct DataLengthTime(u16);
+impl DataLengthTime {
+    pub const BYTE_LEN: usize = 2;
+    /// A 27 octet payload on LE 1M.
+    pub const MIN_U16: u16 = 0x0148;
+    pub const MIN: DataLengthTime = DataLengthTime(Self::MIN_U16);
+    /// A 251 octet payload on LE 1M.
+    pub const MAX_UNCODED_U16: u16 = 0x0848;
+    pub const MAX_UNCODED: DataLengthTime = DataLengthTime(Self::MAX_UNCODED_U16);
+    /// A 251 octet payload on LE Coded (S=8).
+    pub const MAX_U16: u16 = 0x4290;
+    pub const MAX: DataLengthTime = DataLengthTime(Self::MAX_U16);
+    pub fn new(value: u16) -> Self {
+        match Self::new_checked(value) {
+            Some(s) => s,
+            None => panic!("data length time out of range (`{}`)", value),
+        }
+    }
+    pub fn new_checked(value: u16) -> Option<Self> {
+        if value > Self::MAX_U16 || value < Self::MIN_U16 {
+            None
+        } else {
+            Some(Self(value))
+        }
+    }
+    pub fn as_duration(self) -> Duration {
+        Duration::from_micros(self.0.into())
+    }
+}
+impl From<DataLengthTime> for u16 {
+    fn from(t: DataLengthTime) -> Self {
+        t.0
+    }
+}
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
 pub struct ConnectionLatency(u16);
 impl ConnectionLatency {
@@ -148,6 +216,17 @@ impl ConnectionHandle {
     pub const MIN: ConnectionHandle = ConnectionHandle(Self::MIN_U16);
     pub const MAX_U16: u16 = 0x0EFF;
     pub const MAX: ConnectionHandle = ConnectionHandle(Self::MAX_U16);
+    /// Unpack the little endian handle at `buf[index..index + 2]`.
+    pub fn unpack_from(buf: &[u8], index: usize) -> Result<ConnectionHandle, PackError> {
+        match buf.get(index..).and_then(|b| b.get(..Self::BYTE_LEN)) {
+            Some(b) => Self::new_checked(u16::from_le_bytes([b[0], b[1]]))
+                .ok_or(PackError::bad_index(index)),
+            None => Err(PackError::BadLength {
+                expected: index.saturating_add(Self::BYTE_LEN),
+                got: buf.len(),
+            }),
+        }
+    }
 }
 
 impl From<ConnectionHandle> for u16 {
@@ -261,3 +340,27 @@ impl TryFrom<u8> for MasterClockAccuracy {
         }
     }
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_unpack_handle() {
+        let buf = [0xFF, 0x01, 0x0E, 0xFF, 0x0F];
+        assert_eq!(
+            ConnectionHandle::unpack_from(&buf, 1),
+            Ok(ConnectionHandle::new(0x0E01))
+        );
+        assert_eq!(
+            ConnectionHandle::unpack_from(&buf, 3),
+            Err(PackError::bad_index(3))
+        );
+        assert_eq!(
+            ConnectionHandle::unpack_from(&buf, 4),
+            Err(PackError::BadLength {
+                expected: 6,
+                got: 5
+            })
+        );
+        assert!(ConnectionHandle::unpack_from(&buf, usize::MAX).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! Peripheral role. Centrals connect to us while we advertise (connectable), there's no request
+//! to answer first. Each new connection is checked by the application's [`AcceptPolicy`]:
+//! rejected peers are disconnected straight away and accepted ones come with the
+//! [`PostConnect`] settings the application wants for the link.
+use crate::bytes::Storage;
+use crate::hci::adapter;
+use crate::hci::adapters::le::LEAdapter;
+use crate::hci::adapters::{DummyUnrecognizedEventHandler, UnrecognizedEventHandler};
+use crate::hci::event::{Event, EventPacket};
+use crate::hci::le::connection::ConnectionCompleteEvent;
+use crate::hci::le::extended_connection::EnhancedConnectionComplete;
+use crate::hci::ErrorCode;
+use crate::le::att::pdus::exchange::request::ExchangeMTUReq;
+use crate::le::connection::qos::ConnectionParameters;
+use crate::le::connection::{
+    ConnectionHandle, ConnectionInterval, ConnectionLatency, Role, SupervisionTimeout, MTU,
+};
+use crate::DeviceAddress;
+
+/// What to do once a connection is accepted. `None` leaves the setting alone.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PostConnect {
+    /// ATT MTU to ask the central for.
+    pub mtu: Option<MTU>,
+    /// Connection parameters to ask the central for.
+    pub parameters: Option<ConnectionParameters>,
+}
+impl PostConnect {
+    pub const DEFAULT: PostConnect = PostConnect {
+        mtu: None,
+        parameters: None,
+    };
+    /// The Exchange MTU Request to send over ATT, if an MTU was asked for.
+    pub fn exchange_mtu_request(&self) -> Option<ExchangeMTUReq> {
+        self.mtu.map(ExchangeMTUReq)
+    }
+}
+impl Default for PostConnect {
+    fn default() -> Self {
+        PostConnect::DEFAULT
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum ConnectionDecision {
+    Accept(PostConnect),
+    /// Disconnect with the reason (see [`crate::hci::link_control::Disconnect`] for the allowed
+    /// ones).
+    Reject(ErrorCode),
+}
+impl ConnectionDecision {
+    pub const ACCEPT: ConnectionDecision = ConnectionDecision::Accept(PostConnect::DEFAULT);
+    pub const REJECT: ConnectionDecision =
+        ConnectionDecision::Reject(ErrorCode::OtherEndTerminatedConnectionUserEndedConnection);
+}
+/// Decides whether to keep a connection from the peer.
+pub type AcceptPolicy = fn(DeviceAddress) -> ConnectionDecision;
+/// [`AcceptPolicy`] that keeps every connection with the default [`PostConnect`].
+pub fn accept_all(_peer: DeviceAddress) -> ConnectionDecision {
+    ConnectionDecision::ACCEPT
+}
+/// A connection made to us and accepted by the [`AcceptPolicy`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct IncomingConnection {
+    pub connection_handle: ConnectionHandle,
+    /// Identity address if the controller resolved the peer's address.
+    pub peer: DeviceAddress,
+    pub connection_interval: ConnectionInterval,
+    pub connection_latency: ConnectionLatency,
+    pub supervision_timeout: SupervisionTimeout,
+    pub post_connect: PostConnect,
+}
+impl IncomingConnection {
+    /// The successful connection in the peripheral role reported by `event` (a Connection
+    /// Complete or Enhanced Connection Complete) or `None` for any other event.
+    pub fn from_event<S: Storage<u8>>(event: &EventPacket<S>) -> Option<IncomingConnection> {
+        let incoming = |status: ErrorCode, role: Role, connection| {
+            if status == ErrorCode::Ok && role == Role::Slave {
+                Some(connection)
+            } else {
+                None
+            }
+        };
+        if let Ok(c) = ConnectionCompleteEvent::unpack_event_packet(event) {
+            return incoming(
+                c.status,
+                c.role,
+                IncomingConnection {
+                    connection_handle: c.connection_handle,
+                    peer: c.peer(),
+                    connection_interval: c.connection_interval,
+                    connection_latency: c.connection_latency,
+                    supervision_timeout: c.supervision_timeout,
+                    post_connect: PostConnect::DEFAULT,
+                },
+            );
+        }
+        match EnhancedConnectionComplete::unpack_event_packet(event) {
+            Ok(c) => incoming(
+                c.status,
+                c.role,
+                IncomingConnection {
+                    connection_handle: c.connection_handle,
+                    peer: c.peer(),
+                    connection_interval: c.connection_interval,
+                    connection_latency: c.connection_latency,
+                    supervision_timeout: c.supervision_timeout,
+                    post_connect: PostConnect::DEFAULT,
+                },
+            ),
+            Err(_) => None,
+        }
+    }
+}
+pub struct Peripheral<A: crate::hci::adapter::Adapter> {
+    pub hci_adapter: LEAdapter<A, DummyUnrecognizedEventHandler>,
+    pub accept_policy: AcceptPolicy,
+}
+impl<A: crate::hci::adapter::Adapter> Peripheral<A> {
+    pub fn new(
+        hci_adapter: LEAdapter<A, DummyUnrecognizedEventHandler>,
+        accept_policy: AcceptPolicy,
+    ) -> Self {
+        Peripheral {
+            hci_adapter,
+            accept_policy,
+        }
+    }
+    /// Wait for the next connection the [`AcceptPolicy`] accepts. Rejected connections are
+    /// disconnected and other events go to the event handler. Connectable advertising has to be
+    /// started first and, once connected, restarted to accept more connections.
+    pub async fn next_connection(&mut self) -> Result<IncomingConnection, adapter::Error> {
+        loop {
+            let event = self.hci_adapter.adapter.hci_read_event().await?;
+            if let Some(incoming) = IncomingConnection::from_event(&event) {
+                match (self.accept_policy)(incoming.peer) {
+                    ConnectionDecision::Accept(post_connect) => {
+                        return Ok(IncomingConnection {
+                            post_connect,
+                            ..incoming
+                        })
+                    }
+                    ConnectionDecision::Reject(reason) => {
+                        self.hci_adapter
+                            .adapter
+                            .disconnect(incoming.connection_handle, reason)
+                            .await?;
+                        continue;
+                    }
+                }
+            }
+            self.hci_adapter.adapter.event_handler.handle(event)?;
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::le::advertiser::PeerAddressType;
+    use crate::le::connection::MasterClockAccuracy;
+    use crate::BTAddress;
+    use alloc::boxed::Box;
+    #[test]
+    fn test_incoming_connection() {
+        let mut complete = ConnectionCompleteEvent {
+            status: ErrorCode::Ok,
+            connection_handle: ConnectionHandle::new(0x0040),
+            role: Role::Slave,
+            peer_address_type: PeerAddressType::Public,
+            peer_address: BTAddress([1, 2, 3, 4, 5, 6]),
+            connection_interval: ConnectionInterval::new(24),
+            connection_latency: ConnectionLatency::new(0),
+            supervision_timeout: SupervisionTimeout::new(400),
+            master_clock_accuracy: MasterClockAccuracy::PPM50,
+        };
+        let packet = complete.event_pack_packet::<Box<[u8]>>().unwrap();
+        let incoming = IncomingConnection::from_event(&packet).unwrap();
+        assert_eq!(incoming.connection_handle, complete.connection_handle);
+        assert_eq!(incoming.peer, complete.peer());
+        assert_eq!(incoming.post_connect, PostConnect::DEFAULT);
+        // Connections we made (central role) aren't incoming.
+        complete.role = Role::Master;
+        let packet = complete.event_pack_packet::<Box<[u8]>>().unwrap();
+        assert!(IncomingConnection::from_event(&packet).is_none());
+
+        let policy: AcceptPolicy = |peer| {
+            if peer.addr == BTAddress([1, 2, 3, 4, 5, 6]) {
+                ConnectionDecision::Accept(PostConnect {
+                    mtu: Some(MTU::new(247)),
+                    ..PostConnect::DEFAULT
+                })
+            } else {
+                ConnectionDecision::REJECT
+            }
+        };
+        match policy(incoming.peer) {
+            ConnectionDecision::Accept(post_connect) => assert_eq!(
+                post_connect.exchange_mtu_request(),
+                Some(ExchangeMTUReq(MTU::new(247)))
+            ),
+            ConnectionDecision::Reject(_) => panic!("peer should be accepted"),
+        }
+        assert_eq!(accept_all(incoming.peer), ConnectionDecision::ACCEPT);
+    }
+}
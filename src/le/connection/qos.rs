@@ -0,0 +1,176 @@
+//! Connection QoS presets. Picking connection intervals, peripheral latency, PHY and data length
+//! by hand takes knowing the radio. [`ConnectionQos`] names the common trade-offs and translates
+//! them into the parameters for connection creation/update requests, the preferred PHY and the
+//! data length.
+use crate::le::connection::{
+    ConnectionInterval, ConnectionLatency, DataLengthOctets, DataLengthTime, SupervisionTimeout,
+};
+use crate::le::phy::Phy;
+use crate::PackError;
+
+/// Connection interval range, peripheral latency and supervision timeout. Packed in the order
+/// used by connection parameter update requests (and the LE Connection Update command).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ConnectionParameters {
+    pub interval_min: ConnectionInterval,
+    pub interval_max: ConnectionInterval,
+    pub latency: ConnectionLatency,
+    pub supervision_timeout: SupervisionTimeout,
+}
+impl ConnectionParameters {
+    pub const BYTE_LEN: usize = ConnectionInterval::BYTE_LEN * 2
+        + ConnectionLatency::BYTE_LEN
+        + SupervisionTimeout::BYTE_LEN;
+    /// `interval_min <= interval_max` and the supervision timeout is longer than
+    /// `(1 + latency) * interval_max * 2` (so the link isn't dropped while the peripheral is
+    /// allowed to sleep).
+    pub fn is_valid(&self) -> bool {
+        // Supervision timeout is in 10ms units, intervals in 1.25ms units.
+        let timeout = u32::from(u16::from(self.supervision_timeout)) * 8;
+        let sleep =
+            (1 + u32::from(u16::from(self.latency))) * u32::from(u16::from(self.interval_max)) * 2;
+        self.interval_min <= self.interval_max && timeout > sleep
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.interval_min).to_le_bytes());
+        buf[2..4].copy_from_slice(&u16::from(self.interval_max).to_le_bytes());
+        buf[4..6].copy_from_slice(&u16::from(self.latency).to_le_bytes());
+        buf[6..8].copy_from_slice(&u16::from(self.supervision_timeout).to_le_bytes());
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<ConnectionParameters, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        Ok(ConnectionParameters {
+            interval_min: ConnectionInterval::new_checked(u16_at(0))
+                .ok_or_else(|| PackError::bad_index(0))?,
+            interval_max: ConnectionInterval::new_checked(u16_at(2))
+                .ok_or_else(|| PackError::bad_index(2))?,
+            latency: ConnectionLatency::new_checked(u16_at(4))
+                .ok_or_else(|| PackError::bad_index(4))?,
+            supervision_timeout: SupervisionTimeout::new_checked(u16_at(6))
+                .ok_or_else(|| PackError::bad_index(6))?,
+        })
+    }
+}
+/// Maximum link layer payload size and time to transmit it, for the data length extension.
+/// Limits one direction of a connection.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct DataLength {
+    pub max_octets: DataLengthOctets,
+    pub max_time: DataLengthTime,
+}
+impl DataLength {
+    pub const BYTE_LEN: usize = DataLengthOctets::BYTE_LEN + DataLengthTime::BYTE_LEN;
+    /// Default without the data length extension.
+    pub const MIN: DataLength = DataLength {
+        max_octets: DataLengthOctets::MIN,
+        max_time: DataLengthTime::MIN,
+    };
+    /// Longest payload on an uncoded PHY.
+    pub const MAX_UNCODED: DataLength = DataLength {
+        max_octets: DataLengthOctets::MAX,
+        max_time: DataLengthTime::MAX_UNCODED,
+    };
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0..2].copy_from_slice(&u16::from(self.max_octets).to_le_bytes());
+        buf[2..4].copy_from_slice(&u16::from(self.max_time).to_le_bytes());
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<DataLength, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(DataLength {
+            max_octets: DataLengthOctets::new_checked(u16::from_le_bytes([buf[0], buf[1]]))
+                .ok_or_else(|| PackError::bad_index(0))?,
+            max_time: DataLengthTime::new_checked(u16::from_le_bytes([buf[2], buf[3]]))
+                .ok_or_else(|| PackError::bad_index(2))?,
+        })
+    }
+}
+/// Everything a [`ConnectionQos`] preset sets.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct QosSettings {
+    pub parameters: ConnectionParameters,
+    /// PHY to request for both directions.
+    pub phy: Phy,
+    pub data_length: DataLength,
+}
+/// Connection QoS preset.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum ConnectionQos {
+    /// Shortest intervals (7.5ms to 15ms), no peripheral latency, LE 2M and long packets. For
+    /// input devices, audio control and firmware updates. Uses the most power.
+    LatencyCritical,
+    /// 30ms to 50ms intervals, no peripheral latency and long packets on LE 1M.
+    Balanced,
+    /// 100ms to 200ms intervals, the peripheral can skip 4 events, short packets on LE 1M. For
+    /// sensors that report now and then.
+    PowerSave,
+}
+impl ConnectionQos {
+    pub const DEFAULT: ConnectionQos = ConnectionQos::Balanced;
+    pub fn settings(self) -> QosSettings {
+        // Intervals in 1.25ms units, supervision timeouts in 10ms units.
+        let (interval_min, interval_max, latency, timeout, phy, data_length) = match self {
+            ConnectionQos::LatencyCritical => (6, 12, 0, 200, Phy::LE2M, DataLength::MAX_UNCODED),
+            ConnectionQos::Balanced => (24, 40, 0, 400, Phy::LE1M, DataLength::MAX_UNCODED),
+            ConnectionQos::PowerSave => (80, 160, 4, 600, Phy::LE1M, DataLength::MIN),
+        };
+        QosSettings {
+            parameters: ConnectionParameters {
+                interval_min: ConnectionInterval::new(interval_min),
+                interval_max: ConnectionInterval::new(interval_max),
+                latency: ConnectionLatency::new(latency),
+                supervision_timeout: SupervisionTimeout::new(timeout),
+            },
+            phy,
+            data_length,
+        }
+    }
+    pub fn parameters(self) -> ConnectionParameters {
+        self.settings().parameters
+    }
+    pub fn phy(self) -> Phy {
+        self.settings().phy
+    }
+    pub fn data_length(self) -> DataLength {
+        self.settings().data_length
+    }
+}
+impl Default for ConnectionQos {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_presets() {
+        for qos in [
+            ConnectionQos::LatencyCritical,
+            ConnectionQos::Balanced,
+            ConnectionQos::PowerSave,
+        ]
+        .iter()
+        {
+            let parameters = qos.parameters();
+            assert!(parameters.is_valid(), "{:?}", qos);
+            let mut buf = [0_u8; ConnectionParameters::BYTE_LEN];
+            parameters.pack_into(&mut buf).unwrap();
+            assert_eq!(ConnectionParameters::unpack_from(&buf), Ok(parameters));
+        }
+        assert_eq!(
+            ConnectionQos::LatencyCritical.parameters().interval_min,
+            ConnectionInterval::MIN
+        );
+        // 200ms interval with 4 skipped events needs more than 2s of supervision timeout.
+        let too_short = ConnectionParameters {
+            supervision_timeout: SupervisionTimeout::new(200),
+            ..ConnectionQos::PowerSave.parameters()
+        };
+        assert!(!too_short.is_valid());
+    }
+}
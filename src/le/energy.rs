@@ -0,0 +1,365 @@
+//! Radio-on time budgeting for battery powered devices. [`EnergyBudgetManager`] estimates how long
+//! the radio is on for the running advertising and scanning (from their duty cycles) and checks
+//! new operations against a budget of radio-on time per period. Operations that would go over
+//! budget are throttled (lower duty cycle) or deferred to the next period.
+//!
+//! Estimates only use the configured parameters. Controllers add their own overhead (scan
+//! responses, channel switching, etc) so leave some headroom in the budget.
+use crate::le::advertiser::{AdvertisingInterval, AdvertisingParameters};
+use crate::le::scan::{ScanInterval, ScanParameters, ScanWindow};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::time::Duration;
+
+/// Duty cycles are in parts per million.
+pub const PPM: u64 = 1_000_000;
+/// Preamble (1) + access address (4) + header (2) + advertiser address (6) + CRC (3).
+const ADV_PDU_OVERHEAD: u64 = 16;
+/// Time spent listening for a scan/connect request (T_IFS) after each advertising PDU.
+const ADV_LISTEN_MICROS: u64 = 150;
+/// Average of the random 0-10 ms delay added to every advertising interval.
+const ADV_DELAY_MICROS: u64 = 5_000;
+/// Something that keeps the radio on part of the time.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum RadioActivity {
+    /// Receiving for `window` out of every `interval`.
+    Scanning {
+        interval: ScanInterval,
+        window: ScanWindow,
+    },
+    /// Legacy advertising on `channels` (1-3) channels with `data_len` bytes of advertising data
+    /// on the 1M PHY.
+    Advertising {
+        interval: AdvertisingInterval,
+        channels: u8,
+        data_len: u8,
+    },
+}
+impl RadioActivity {
+    pub fn scanning(parameters: &ScanParameters) -> RadioActivity {
+        RadioActivity::Scanning {
+            interval: parameters.scan_interval,
+            window: parameters.scan_window,
+        }
+    }
+    pub fn advertising(parameters: &AdvertisingParameters, data_len: u8) -> RadioActivity {
+        RadioActivity::Advertising {
+            interval: parameters.interval_max,
+            channels: u8::from(parameters.channel_map).count_ones() as u8,
+            data_len,
+        }
+    }
+    /// Whether the radio is mostly transmitting (advertising) or receiving (scanning).
+    pub fn is_transmit(self) -> bool {
+        match self {
+            RadioActivity::Scanning { .. } => false,
+            RadioActivity::Advertising { .. } => true,
+        }
+    }
+    /// Radio-on time of one advertising event.
+    fn advertising_event_micros(channels: u8, data_len: u8) -> u64 {
+        let air_time = (ADV_PDU_OVERHEAD + u64::from(data_len)) * 8;
+        u64::from(channels) * (air_time + ADV_LISTEN_MICROS)
+    }
+    /// Fraction of the time the radio is on in parts per million.
+    pub fn duty_cycle_ppm(self) -> u32 {
+        let ppm = match self {
+            RadioActivity::Scanning { interval, window } => {
+                u64::from(window.as_microseconds()) * PPM / u64::from(interval.as_microseconds())
+            }
+            RadioActivity::Advertising {
+                interval,
+                channels,
+                data_len,
+            } => {
+                Self::advertising_event_micros(channels, data_len) * PPM
+                    / (u64::from(interval.as_microseconds()) + ADV_DELAY_MICROS)
+            }
+        };
+        ppm.min(PPM) as u32
+    }
+    /// Radio-on time over `duration`.
+    pub fn radio_on(self, duration: Duration) -> Duration {
+        duration * self.duty_cycle_ppm() / PPM as u32
+    }
+    /// The same activity with a duty cycle of at most `max_ppm` (shorter scan window or longer
+    /// advertising interval). `None` if the parameters can't go that low.
+    pub fn throttled(self, max_ppm: u32) -> Option<RadioActivity> {
+        if self.duty_cycle_ppm() <= max_ppm {
+            return Some(self);
+        }
+        match self {
+            RadioActivity::Scanning {
+                interval,
+                window: _,
+            } => {
+                let window_micros =
+                    u64::from(interval.as_microseconds()) * u64::from(max_ppm) / PPM;
+                let window = ScanWindow::new_checked(u16::try_from(window_micros / 625).ok()?)?;
+                Some(RadioActivity::Scanning { interval, window })
+            }
+            RadioActivity::Advertising {
+                interval: _,
+                channels,
+                data_len,
+            } => {
+                if max_ppm == 0 {
+                    return None;
+                }
+                let event = Self::advertising_event_micros(channels, data_len);
+                let interval_micros =
+                    (event * PPM / u64::from(max_ppm)).saturating_sub(ADV_DELAY_MICROS);
+                let interval = u16::try_from((interval_micros + 624) / 625).ok()?;
+                if interval > AdvertisingInterval::MAX_U16 {
+                    return None;
+                }
+                Some(RadioActivity::Advertising {
+                    interval: AdvertisingInterval::new(interval.max(AdvertisingInterval::MIN_U16)),
+                    channels,
+                    data_len,
+                })
+            }
+        }
+    }
+}
+/// Allowed radio-on time per period.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct EnergyBudget {
+    pub period: Duration,
+    pub max_radio_on: Duration,
+}
+impl EnergyBudget {
+    /// # Panics
+    /// Panics if `period` is zero.
+    pub fn new(period: Duration, max_radio_on: Duration) -> EnergyBudget {
+        assert!(period > Duration::from_secs(0), "zero budget period");
+        EnergyBudget {
+            period,
+            max_radio_on,
+        }
+    }
+}
+/// Current drawn while the radio is on. Used to turn radio-on time into charge estimates.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct RadioCurrents {
+    pub tx_microamps: u32,
+    pub rx_microamps: u32,
+}
+impl RadioCurrents {
+    /// Charge used by `tx` transmitting and `rx` receiving in microamp hours.
+    pub fn charge_microamp_hours(&self, tx: Duration, rx: Duration) -> u64 {
+        let microamp_micros = u128::from(self.tx_microamps) * tx.as_micros()
+            + u128::from(self.rx_microamps) * rx.as_micros();
+        (microamp_micros / 3_600_000_000) as u64
+    }
+}
+/// Result of [`EnergyBudgetManager::check`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum BudgetDecision {
+    /// Fits in the budget as is.
+    Allow,
+    /// Fits in the budget with these (lower duty cycle) parameters.
+    Throttle(RadioActivity),
+    /// Doesn't fit. Try again at this time (the start of the next period).
+    Defer(Duration),
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ActivityId(pub u32);
+#[derive(Copy, Clone, Debug)]
+struct RunningActivity {
+    id: ActivityId,
+    activity: RadioActivity,
+    /// Radio-on time has been accounted up to here.
+    accounted_to: Duration,
+}
+/// Tracks radio-on time of running activities against an [`EnergyBudget`]. Time is passed in by
+/// the caller (any monotonic clock).
+#[derive(Clone, Debug)]
+pub struct EnergyBudgetManager {
+    budget: EnergyBudget,
+    running: Vec<RunningActivity>,
+    period_start: Duration,
+    /// Radio-on time in the current period.
+    used: Duration,
+    total_tx: Duration,
+    total_rx: Duration,
+    next_id: u32,
+}
+impl EnergyBudgetManager {
+    pub fn new(budget: EnergyBudget, now: Duration) -> EnergyBudgetManager {
+        EnergyBudgetManager {
+            budget,
+            running: Vec::new(),
+            period_start: now,
+            used: Duration::default(),
+            total_tx: Duration::default(),
+            total_rx: Duration::default(),
+            next_id: 0,
+        }
+    }
+    pub fn budget(&self) -> EnergyBudget {
+        self.budget
+    }
+    /// Combined duty cycle of the running activities.
+    pub fn duty_cycle_ppm(&self) -> u32 {
+        self.running
+            .iter()
+            .map(|r| r.activity.duty_cycle_ppm())
+            .sum::<u32>()
+            .min(PPM as u32)
+    }
+    fn account(&mut self, to: Duration) {
+        for running in self.running.iter_mut() {
+            let on = running
+                .activity
+                .radio_on(to.checked_sub(running.accounted_to).unwrap_or_default());
+            self.used += on;
+            if running.activity.is_transmit() {
+                self.total_tx += on;
+            } else {
+                self.total_rx += on;
+            }
+            running.accounted_to = to;
+        }
+    }
+    /// Account radio-on time up to `now`, starting new periods as needed.
+    fn advance(&mut self, now: Duration) {
+        let period = self.budget.period;
+        let end = self.period_start + period;
+        if now < end {
+            self.account(now);
+            return;
+        }
+        self.account(end);
+        // Skip any whole periods. They still count towards the totals.
+        let skipped = (now - end).as_nanos() / period.as_nanos();
+        self.period_start = end + period * skipped as u32;
+        self.account(self.period_start);
+        self.used = Duration::default();
+        self.account(now);
+    }
+    /// Radio-on time used in the current period.
+    pub fn used(&mut self, now: Duration) -> Duration {
+        self.advance(now);
+        self.used
+    }
+    /// Radio-on time left in the current period.
+    pub fn remaining(&mut self, now: Duration) -> Duration {
+        self.advance(now);
+        self.budget
+            .max_radio_on
+            .checked_sub(self.used)
+            .unwrap_or_default()
+    }
+    /// Radio-on time the current period will end with if nothing is started or stopped.
+    pub fn projected(&mut self, now: Duration) -> Duration {
+        self.advance(now);
+        let left = self.period_start + self.budget.period - now;
+        self.used + left * self.duty_cycle_ppm() / PPM as u32
+    }
+    /// Total (transmit, receive) radio-on time since the manager was created.
+    pub fn radio_on_totals(&mut self, now: Duration) -> (Duration, Duration) {
+        self.advance(now);
+        (self.total_tx, self.total_rx)
+    }
+    /// Estimated charge used since the manager was created.
+    pub fn charge_microamp_hours(&mut self, currents: &RadioCurrents, now: Duration) -> u64 {
+        let (tx, rx) = self.radio_on_totals(now);
+        currents.charge_microamp_hours(tx, rx)
+    }
+    /// Check if starting `activity` at `now` keeps the current period within budget.
+    pub fn check(&mut self, activity: RadioActivity, now: Duration) -> BudgetDecision {
+        let projected = self.projected(now);
+        let period_end = self.period_start + self.budget.period;
+        let left = period_end - now;
+        let available = match self.budget.max_radio_on.checked_sub(projected) {
+            Some(available) if left > Duration::default() => available,
+            _ => return BudgetDecision::Defer(period_end),
+        };
+        if activity.radio_on(left) <= available {
+            return BudgetDecision::Allow;
+        }
+        let max_ppm = (available.as_nanos() * u128::from(PPM) / left.as_nanos()) as u32;
+        match activity.throttled(max_ppm) {
+            Some(throttled) => BudgetDecision::Throttle(throttled),
+            None => BudgetDecision::Defer(period_end),
+        }
+    }
+    /// Start tracking `activity` (whether or not [`EnergyBudgetManager::check`] allowed it).
+    pub fn start(&mut self, activity: RadioActivity, now: Duration) -> ActivityId {
+        self.advance(now);
+        let id = ActivityId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        self.running.push(RunningActivity {
+            id,
+            activity,
+            accounted_to: now,
+        });
+        id
+    }
+    /// Stop tracking an activity. Returns `false` if `id` isn't running.
+    pub fn stop(&mut self, id: ActivityId, now: Duration) -> bool {
+        self.advance(now);
+        match self.running.iter().position(|r| r.id == id) {
+            Some(index) => {
+                self.running.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_budget() {
+        let scan = ScanParameters {
+            scan_interval: ScanInterval::new(160),
+            scan_window: ScanWindow::new(16),
+            ..ScanParameters::DEFAULT
+        };
+        let scanning = RadioActivity::scanning(&scan);
+        assert_eq!(scanning.duty_cycle_ppm(), 100_000);
+        let budget = EnergyBudget::new(Duration::from_secs(60), Duration::from_secs(3));
+        let mut manager = EnergyBudgetManager::new(budget, Duration::from_secs(0));
+        // 10% for 60s is 6s so it has to be throttled to 5%.
+        let throttled = match manager.check(scanning, Duration::from_secs(0)) {
+            BudgetDecision::Throttle(throttled) => throttled,
+            d => panic!("expected throttle got {:?}", d),
+        };
+        assert_eq!(throttled.duty_cycle_ppm(), 50_000);
+        let id = manager.start(throttled, Duration::from_secs(0));
+        assert_eq!(
+            manager.used(Duration::from_secs(20)),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            manager.projected(Duration::from_secs(20)),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            manager.check(scanning, Duration::from_secs(20)),
+            BudgetDecision::Defer(Duration::from_secs(60))
+        );
+        assert!(manager.stop(id, Duration::from_secs(30)));
+        assert_eq!(
+            manager.remaining(Duration::from_secs(30)),
+            Duration::from_millis(1500)
+        );
+        // New period.
+        assert_eq!(
+            manager.used(Duration::from_secs(130)),
+            Duration::from_secs(0)
+        );
+        assert_eq!(
+            manager.radio_on_totals(Duration::from_secs(130)),
+            (Duration::from_secs(0), Duration::from_millis(1500))
+        );
+        let advertising = RadioActivity::advertising(&AdvertisingParameters::DEFAULT, 31);
+        assert_eq!(
+            manager.check(advertising, Duration::from_secs(130)),
+            BudgetDecision::Allow
+        );
+    }
+}
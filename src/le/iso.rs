@@ -0,0 +1,196 @@
+//! LE Isochronous SDU scheduling. [`SduScheduler`] pulls SDUs from an [`IsoSource`] once per SDU
+//! interval and fragments them into HCI ISO Data packets. [`IsoSink`] reassembles received
+//! packets into SDUs which can then be pulled by the application.
+use crate::hci::iso::{fragment_sdu, DataLoadHeader, ISODataPacket, PacketStatus};
+use crate::le::connection::ConnectionHandle;
+use crate::PackError;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// Service Data Unit (SDU) with its sequence number and optional time stamp (microseconds).
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct Sdu<Buf = Box<[u8]>> {
+    pub sequence_number: u16,
+    pub time_stamp: Option<u32>,
+    pub status: PacketStatus,
+    pub data: Buf,
+}
+/// Provides outgoing SDUs. Pulled by the [`SduScheduler`] once per SDU interval.
+pub trait IsoSource {
+    /// Next SDU for `sequence_number`. Returning `None` skips the interval (nothing is sent).
+    fn pull_sdu(&mut self, sequence_number: u16) -> Option<Box<[u8]>>;
+}
+impl<F: FnMut(u16) -> Option<Box<[u8]>>> IsoSource for F {
+    fn pull_sdu(&mut self, sequence_number: u16) -> Option<Box<[u8]>> {
+        (self)(sequence_number)
+    }
+}
+/// Assigns sequence numbers and time stamps to outgoing SDUs. Sequence numbers are derived from
+/// elapsed time so a late call still uses the correct sequence number for the current interval.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SduScheduler {
+    pub handle: ConnectionHandle,
+    /// SDU interval in microseconds.
+    pub sdu_interval_us: u32,
+    /// Controller `ISO_Data_Packet_Length`.
+    pub max_data_len: usize,
+    /// Include time stamps in outgoing packets.
+    pub time_stamps: bool,
+    start_us: Option<u32>,
+    last_sequence_number: Option<u16>,
+}
+impl SduScheduler {
+    pub fn new(handle: ConnectionHandle, sdu_interval_us: u32, max_data_len: usize) -> Self {
+        SduScheduler {
+            handle,
+            sdu_interval_us,
+            max_data_len,
+            time_stamps: true,
+            start_us: None,
+            last_sequence_number: None,
+        }
+    }
+    /// Sequence number for the SDU interval containing `now_us`. The first call starts the
+    /// stream at sequence number 0.
+    pub fn sequence_number(&mut self, now_us: u32) -> u16 {
+        let start = *self.start_us.get_or_insert(now_us);
+        let intervals = now_us.wrapping_sub(start) / core::cmp::max(1, self.sdu_interval_us);
+        // Sequence numbers wrap at 16-bits.
+        (intervals & 0xFFFF) as u16
+    }
+    /// Pull an SDU from `source` for the interval containing `now_us` and fragment it into ISO Data
+    /// packets. Returns no packets if `source` has nothing or this interval already had an SDU.
+    pub fn poll_source<S: IsoSource>(
+        &mut self,
+        source: &mut S,
+        now_us: u32,
+    ) -> Result<Vec<ISODataPacket<Vec<u8>>>, PackError> {
+        let sequence_number = self.sequence_number(now_us);
+        if self.last_sequence_number == Some(sequence_number) {
+            return Ok(Vec::new());
+        }
+        self.last_sequence_number = Some(sequence_number);
+        match source.pull_sdu(sequence_number) {
+            Some(sdu) => self.fragment(sequence_number, now_us, sdu.as_ref()),
+            None => Ok(Vec::new()),
+        }
+    }
+    /// Fragment one SDU into ISO Data packets.
+    pub fn fragment(
+        &self,
+        sequence_number: u16,
+        now_us: u32,
+        sdu: &[u8],
+    ) -> Result<Vec<ISODataPacket<Vec<u8>>>, PackError> {
+        fragment_sdu(
+            self.handle,
+            DataLoadHeader {
+                time_stamp: if self.time_stamps { Some(now_us) } else { None },
+                sequence_number,
+                sdu_len: 0,
+                status: PacketStatus::Valid,
+            },
+            sdu,
+            self.max_data_len,
+        )
+    }
+}
+/// Reassembles incoming ISO Data packets into SDUs. Completed SDUs are queued until pulled with
+/// [`IsoSink::pull_sdu`]. Fragments that arrive out of order are dropped and the partial SDU is
+/// reported with `PacketStatus::LostData`.
+#[derive(Clone, Debug, Default)]
+pub struct IsoSink {
+    partial: Option<(DataLoadHeader, Vec<u8>)>,
+    ready: VecDeque<Sdu>,
+    /// Maximum number of completed SDUs to queue. Oldest SDUs are dropped first.
+    pub capacity: usize,
+}
+impl IsoSink {
+    pub const DEFAULT_CAPACITY: usize = 16;
+    pub fn new() -> IsoSink {
+        IsoSink::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+    pub fn with_capacity(capacity: usize) -> IsoSink {
+        IsoSink {
+            partial: None,
+            ready: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+    fn finish(&mut self, header: DataLoadHeader, data: Vec<u8>, status: PacketStatus) {
+        let status = if status == PacketStatus::Valid && data.len() != usize::from(header.sdu_len)
+        {
+            PacketStatus::LostData
+        } else {
+            status
+        };
+        if self.capacity == 0 {
+            return;
+        }
+        while self.ready.len() >= self.capacity {
+            self.ready.pop_front();
+        }
+        self.ready.push_back(Sdu {
+            sequence_number: header.sequence_number,
+            time_stamp: header.time_stamp,
+            status,
+            data: data.into_boxed_slice(),
+        });
+    }
+    /// Push a received ISO Data packet.
+    pub fn push_packet<Buf: AsRef<[u8]>>(&mut self, packet: &ISODataPacket<Buf>) {
+        if let Some(header) = packet.load_header {
+            // A new SDU started before the last one finished.
+            if let Some((old, data)) = self.partial.take() {
+                self.finish(old, data, PacketStatus::LostData);
+            }
+            self.partial = Some((header, packet.data.as_ref().to_vec()));
+        } else if let Some((_, data)) = self.partial.as_mut() {
+            data.extend_from_slice(packet.data.as_ref());
+        } else {
+            // Continuation without a start. Nothing to attach it to.
+            return;
+        }
+        if packet.boundary.is_end() {
+            if let Some((header, data)) = self.partial.take() {
+                let status = header.status;
+                self.finish(header, data, status);
+            }
+        }
+    }
+    /// Pull the oldest completed SDU.
+    pub fn pull_sdu(&mut self) -> Option<Sdu> {
+        self.ready.pop_front()
+    }
+    pub fn len(&self) -> usize {
+        self.ready.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ready.is_empty()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_schedule_and_reassemble() {
+        let handle = ConnectionHandle::new(0x10);
+        let mut scheduler = SduScheduler::new(handle, 10_000, 20);
+        let mut source = |seq: u16| Some(vec![seq as u8; 40].into_boxed_slice());
+        let mut sink = IsoSink::new();
+        for now in &[1_000_u32, 5_000, 11_000, 31_000] {
+            for packet in scheduler.poll_source(&mut source, *now).unwrap() {
+                sink.push_packet(&packet);
+            }
+        }
+        let sequence_numbers: Vec<u16> = core::iter::from_fn(|| sink.pull_sdu())
+            .map(|sdu| {
+                assert_eq!(sdu.status, PacketStatus::Valid);
+                assert_eq!(sdu.data.len(), 40);
+                sdu.sequence_number
+            })
+            .collect();
+        assert_eq!(sequence_numbers, vec![0, 1, 3]);
+    }
+}
@@ -0,0 +1,159 @@
+//! Manufacturer data filtering on raw advertising payloads. A [`ManufacturerDataMatcher`] is
+//! compiled once from offset/mask/value triples (like Android's `ScanFilter` manufacturer data
+//! masks) into flat mask and value arrays, then checked against each report's payload without
+//! parsing or copying its AD structures. Cheap enough to run on every report when only one beacon
+//! format out of a lot of traffic is wanted.
+use crate::bytes::ToFromBytesEndian;
+use crate::le::ad_writer::MAX_AD_DATA_LEN;
+use crate::le::advertisement::AdType;
+use crate::le::report::ReportInfo;
+use crate::{CompanyID, ConversionError};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Byte `offset` of the manufacturer data (after the company ID) must equal `value` in the bits
+/// set in `mask`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ByteMatch {
+    pub offset: u8,
+    pub mask: u8,
+    pub value: u8,
+}
+impl ByteMatch {
+    /// Whole byte must equal `value`.
+    pub const fn exact(offset: u8, value: u8) -> ByteMatch {
+        ByteMatch {
+            offset,
+            mask: 0xFF,
+            value,
+        }
+    }
+}
+/// Compiled manufacturer data pattern. Index 0 and 1 of `mask`/`value` are the company ID.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ManufacturerDataMatcher {
+    mask: Vec<u8>,
+    /// Already masked.
+    value: Vec<u8>,
+}
+impl ManufacturerDataMatcher {
+    /// Compile `bytes` (in any order) for manufacturer data from `company_id` (or any company if
+    /// `None`). Returns an error if two matches on the same byte contradict each other (so
+    /// nothing could match) or an offset is past the longest possible manufacturer data.
+    pub fn new(
+        company_id: Option<CompanyID>,
+        bytes: &[ByteMatch],
+    ) -> Result<ManufacturerDataMatcher, ConversionError> {
+        let len = bytes
+            .iter()
+            .map(|b| CompanyID::byte_len() + usize::from(b.offset) + 1)
+            .max()
+            .unwrap_or(0)
+            .max(if company_id.is_some() {
+                CompanyID::byte_len()
+            } else {
+                0
+            });
+        if len > MAX_AD_DATA_LEN {
+            return Err(ConversionError(()));
+        }
+        let mut mask = alloc::vec![0_u8; len];
+        let mut value = alloc::vec![0_u8; len];
+        if let Some(company_id) = company_id {
+            mask[..CompanyID::byte_len()].copy_from_slice(&[0xFF, 0xFF]);
+            value[..CompanyID::byte_len()].copy_from_slice(&company_id.to_bytes_le());
+        }
+        for b in bytes {
+            let i = CompanyID::byte_len() + usize::from(b.offset);
+            let overlap = mask[i] & b.mask;
+            if value[i] & overlap != b.value & overlap {
+                return Err(ConversionError(()));
+            }
+            mask[i] |= b.mask;
+            value[i] |= b.value & b.mask;
+        }
+        Ok(ManufacturerDataMatcher { mask, value })
+    }
+    /// Android style pattern: manufacturer data (after the company ID) starting with `data` in
+    /// the bits set in `mask`. A missing `mask` byte means the whole byte is compared.
+    pub fn with_prefix(
+        company_id: CompanyID,
+        data: &[u8],
+        mask: &[u8],
+    ) -> Result<ManufacturerDataMatcher, ConversionError> {
+        let mut bytes = Vec::with_capacity(data.len());
+        for (i, value) in data.iter().enumerate() {
+            bytes.push(ByteMatch {
+                offset: u8::try_from(i).map_err(|_| ConversionError(()))?,
+                mask: mask.get(i).copied().unwrap_or(0xFF),
+                value: *value,
+            });
+        }
+        Self::new(Some(company_id), &bytes)
+    }
+    /// Shortest manufacturer data (company ID included) that can match.
+    pub fn min_len(&self) -> usize {
+        self.mask.len()
+    }
+    /// Check the data of one Manufacturer Specific Data structure (company ID included).
+    pub fn matches_data(&self, data: &[u8]) -> bool {
+        data.len() >= self.mask.len()
+            && data
+                .iter()
+                .zip(self.mask.iter().zip(self.value.iter()))
+                .all(|(d, (m, v))| d & m == *v)
+    }
+    /// Check every Manufacturer Specific Data structure in a raw advertising payload. Stops at
+    /// the first malformed length byte.
+    pub fn matches(&self, payload: &[u8]) -> bool {
+        let mut rest = payload;
+        while rest.len() >= 2 {
+            let len = usize::from(rest[0]);
+            if len == 0 || len >= rest.len() {
+                return false;
+            }
+            if rest[1] == u8::from(AdType::ManufacturerData) && self.matches_data(&rest[2..=len]) {
+                return true;
+            }
+            rest = &rest[len + 1..];
+        }
+        false
+    }
+    pub fn matches_report<T: AsRef<[u8]>>(&self, report: &ReportInfo<T>) -> bool {
+        self.matches(report.data.as_ref())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_manufacturer_data_matcher() {
+        // iBeacon: Apple (0x004C), type 0x02, length 0x15.
+        let ibeacon =
+            ManufacturerDataMatcher::with_prefix(CompanyID(0x004C), &[0x02, 0x15], &[]).unwrap();
+        let mut payload = [0_u8; 30];
+        payload[..9].copy_from_slice(&[0x02, 0x01, 0x06, 0x1A, 0xFF, 0x4C, 0x00, 0x02, 0x15]);
+        assert!(ibeacon.matches(&payload[..]));
+        payload[7] = 0x12;
+        assert!(!ibeacon.matches(&payload[..]));
+        // Any company, high nibble of the first data byte is 0x1.
+        let nibble = ManufacturerDataMatcher::new(
+            None,
+            &[ByteMatch {
+                offset: 0,
+                mask: 0xF0,
+                value: 0x1F,
+            }],
+        )
+        .unwrap();
+        assert!(nibble.matches(&payload[..]));
+        assert_eq!(nibble.min_len(), 3);
+        assert!(!nibble.matches(&[0x03, 0xFF, 0x4C, 0x00]));
+        // Truncated structure.
+        assert!(!ibeacon.matches(&[0x1A, 0xFF, 0x4C, 0x00, 0x02, 0x15]));
+        assert_eq!(
+            ManufacturerDataMatcher::new(None, &[ByteMatch::exact(1, 1), ByteMatch::exact(1, 2)]),
+            Err(ConversionError(()))
+        );
+    }
+}
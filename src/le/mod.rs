@@ -1,10 +1,34 @@
+pub mod ad_corpus;
+pub mod ad_lint;
+pub mod ad_template;
+pub mod ad_writer;
 pub mod adapter;
 pub mod advertisement;
 pub mod advertisement_structures;
 pub mod advertiser;
 pub mod att;
+pub mod audio;
+pub mod batch;
+#[cfg(feature = "btleplug_compat")]
+pub mod btleplug_compat;
+pub mod calibration;
+pub mod config;
 pub mod connection;
+pub mod energy;
 pub mod gatt;
+pub mod iso;
 pub mod link;
+pub mod matcher;
+pub mod multiplexer;
+pub mod ota;
+pub mod phy;
+pub mod profile;
+pub mod proximity;
 pub mod report;
 pub mod scan;
+pub mod scan_limiter;
+pub mod sink;
+pub mod smp;
+#[cfg(feature = "soak")]
+pub mod soak;
+pub mod time_sync;
@@ -0,0 +1,313 @@
+//! Host-side advertising sets for controllers with only one (legacy) advertising instance.
+//! [`AdvertisementMultiplexer`] time-slices several logical advertisements onto one [`Advertiser`]
+//! by swapping the parameters and data every [`AdvertisementMultiplexer::slot`].
+use crate::bytes::Storage;
+use crate::hci::adapter;
+use crate::hci::le::advertise::TxPowerLevel;
+use crate::le::advertisement::{StaticAdvBuffer, MAX_ADV_LEN};
+use crate::le::advertiser::{
+    Advertiser, AdvertisingParameters, AdvertisingSetHandle, AdvertisingSets, AdvertisingTxPower,
+};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::time::Duration;
+use futures_util::future::LocalBoxFuture;
+
+struct LogicalAdvertisement {
+    handle: AdvertisingSetHandle,
+    parameters: AdvertisingParameters,
+    data: StaticAdvBuffer,
+}
+pub struct AdvertisementMultiplexer<A: Advertiser> {
+    advertiser: A,
+    sets: Vec<LogicalAdvertisement>,
+    /// Index of the set currently loaded into the advertiser.
+    current: Option<usize>,
+    is_enabled: bool,
+    /// When the current set went on air.
+    rotated_at: Option<Duration>,
+    /// How long each set is advertised before rotating to the next.
+    pub slot: Duration,
+}
+impl<A: Advertiser> AdvertisementMultiplexer<A> {
+    pub const DEFAULT_SLOT: Duration = Duration::from_millis(500);
+    pub fn new(advertiser: A) -> Self {
+        AdvertisementMultiplexer {
+            advertiser,
+            sets: Vec::new(),
+            current: None,
+            is_enabled: false,
+            rotated_at: None,
+            slot: Self::DEFAULT_SLOT,
+        }
+    }
+    pub fn advertiser(&self) -> &A {
+        &self.advertiser
+    }
+    pub fn into_inner(self) -> A {
+        self.advertiser
+    }
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+    /// Handle of the set currently on air.
+    pub fn current(&self) -> Option<AdvertisingSetHandle> {
+        self.current.map(|i| self.sets[i].handle)
+    }
+    fn index_of(&self, handle: AdvertisingSetHandle) -> Result<usize, adapter::Error> {
+        self.sets
+            .iter()
+            .position(|s| s.handle == handle)
+            .ok_or(adapter::Error::BadParameter)
+    }
+    fn next_handle(&self) -> Option<AdvertisingSetHandle> {
+        (0..=AdvertisingSetHandle::MAX_U8)
+            .map(AdvertisingSetHandle::new)
+            .find(|h| self.sets.iter().all(|s| s.handle != *h))
+    }
+    async fn load(&mut self, index: usize) -> Result<(), adapter::Error> {
+        if self.current.is_some() {
+            self.advertiser.set_advertising_enable(false).await?;
+            self.current = None;
+        }
+        let parameters = self.sets[index].parameters;
+        self.advertiser
+            .set_advertising_parameters(parameters)
+            .await?;
+        let data = self.sets[index].data;
+        self.advertiser.set_advertising_data(data.as_ref()).await?;
+        self.advertiser.set_advertising_enable(true).await?;
+        self.current = Some(index);
+        Ok(())
+    }
+    /// Swap to the next set. Does nothing while disabled or if only one set is already on air.
+    pub async fn rotate(&mut self) -> Result<(), adapter::Error> {
+        if !self.is_enabled || self.sets.is_empty() {
+            return Ok(());
+        }
+        let next = match self.current {
+            Some(current) => (current + 1) % self.sets.len(),
+            None => 0,
+        };
+        if Some(next) == self.current {
+            return Ok(());
+        }
+        self.load(next).await
+    }
+    /// Rotate if the current set has been on air for a [`AdvertisementMultiplexer::slot`] at
+    /// `now` (usually [`crate::clock::Clock::now`]). Lets the caller drive rotation from its own
+    /// timer instead of [`AdvertisementMultiplexer::run`]. Returns if it rotated.
+    pub async fn rotate_if_due(&mut self, now: Duration) -> Result<bool, adapter::Error> {
+        if let Some(rotated_at) = self.rotated_at {
+            if now.checked_sub(rotated_at).unwrap_or_default() < self.slot {
+                return Ok(false);
+            }
+        }
+        self.rotate().await?;
+        self.rotated_at = Some(now);
+        Ok(true)
+    }
+    /// Rotate through the sets forever. `delay` waits for the given time (usually the async
+    /// runtime's sleep). Only returns on error.
+    pub async fn run<F: FnMut(Duration) -> Fut, Fut: Future<Output = ()>>(
+        &mut self,
+        mut delay: F,
+    ) -> Result<(), adapter::Error> {
+        loop {
+            self.rotate().await?;
+            delay(self.slot).await;
+        }
+    }
+}
+impl<A: Advertiser> AdvertisingSets for AdvertisementMultiplexer<A> {
+    fn add_set<'d, 'a: 'd>(
+        &'a mut self,
+        advertising_parameters: AdvertisingParameters,
+        data: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<AdvertisingSetHandle, adapter::Error>> {
+        Box::pin(async move {
+            if data.len() > MAX_ADV_LEN {
+                return Err(adapter::Error::BadParameter);
+            }
+            let handle = self.next_handle().ok_or(adapter::Error::BadParameter)?;
+            self.sets.push(LogicalAdvertisement {
+                handle,
+                parameters: advertising_parameters,
+                data: StaticAdvBuffer::from_slice(data),
+            });
+            Ok(handle)
+        })
+    }
+
+    fn set_set_data<'d, 'a: 'd>(
+        &'a mut self,
+        handle: AdvertisingSetHandle,
+        data: &'d [u8],
+    ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            if data.len() > MAX_ADV_LEN {
+                return Err(adapter::Error::BadParameter);
+            }
+            let index = self.index_of(handle)?;
+            self.sets[index].data = StaticAdvBuffer::from_slice(data);
+            if self.current == Some(index) {
+                self.advertiser.set_advertising_data(data).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn remove_set<'a>(
+        &'a mut self,
+        handle: AdvertisingSetHandle,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            let index = self.index_of(handle)?;
+            match self.current {
+                Some(current) if current == index => {
+                    self.advertiser.set_advertising_enable(false).await?;
+                    self.current = None;
+                }
+                Some(current) if current > index => self.current = Some(current - 1),
+                _ => (),
+            }
+            self.sets.remove(index);
+            self.rotate().await
+        })
+    }
+
+    fn set_sets_enable<'a>(
+        &'a mut self,
+        is_enabled: bool,
+    ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+        Box::pin(async move {
+            self.is_enabled = is_enabled;
+            if is_enabled {
+                self.rotate().await
+            } else {
+                if self.current.take().is_some() {
+                    self.advertiser.set_advertising_enable(false).await?;
+                }
+                Ok(())
+            }
+        })
+    }
+}
+impl<A: Advertiser + AdvertisingTxPower> AdvertisingTxPower for AdvertisementMultiplexer<A> {
+    /// Every set goes out on the same legacy advertising instance so they share its TX power.
+    fn advertising_tx_power<'a>(
+        &'a mut self,
+        set: Option<AdvertisingSetHandle>,
+    ) -> LocalBoxFuture<'a, Result<TxPowerLevel, adapter::Error>> {
+        Box::pin(async move {
+            if let Some(handle) = set {
+                self.index_of(handle)?;
+            }
+            self.advertiser.advertising_tx_power(None).await
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hci::le::whitelist::WhitelistEntry;
+    use futures_util::FutureExt;
+    #[derive(Default)]
+    struct RecordingAdvertiser {
+        is_enabled: bool,
+        data: Vec<u8>,
+    }
+    impl Advertiser for RecordingAdvertiser {
+        fn set_advertising_enable<'a>(
+            &'a mut self,
+            is_enabled: bool,
+        ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+            self.is_enabled = is_enabled;
+            Box::pin(async { Ok(()) })
+        }
+
+        fn set_advertising_parameters<'a>(
+            &'a mut self,
+            _advertising_parameters: AdvertisingParameters,
+        ) -> LocalBoxFuture<'a, Result<(), adapter::Error>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn set_advertising_data<'d, 'a: 'd>(
+            &'a mut self,
+            data: &'d [u8],
+        ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+            self.data = data.to_vec();
+            Box::pin(async { Ok(()) })
+        }
+
+        fn set_scan_response_data<'d, 'a: 'd>(
+            &'a mut self,
+            _data: &'d [u8],
+        ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn set_whitelist<'d, 'a: 'd>(
+            &'a mut self,
+            _entries: &'d [WhitelistEntry],
+        ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+    #[test]
+    fn test_rotation() {
+        let mut mux = AdvertisementMultiplexer::new(RecordingAdvertiser::default());
+        let params = AdvertisingParameters::DEFAULT;
+        let a = mux.add_set(params, &[1]).now_or_never().unwrap().unwrap();
+        let b = mux.add_set(params, &[2]).now_or_never().unwrap().unwrap();
+        assert_ne!(a, b);
+        mux.rotate().now_or_never().unwrap().unwrap();
+        assert!(!mux.advertiser().is_enabled);
+        mux.set_sets_enable(true).now_or_never().unwrap().unwrap();
+        assert_eq!(mux.current(), Some(a));
+        assert_eq!(mux.advertiser().data, [1]);
+        mux.rotate().now_or_never().unwrap().unwrap();
+        assert_eq!(mux.current(), Some(b));
+        mux.set_set_data(b, &[3]).now_or_never().unwrap().unwrap();
+        assert_eq!(mux.advertiser().data, [3]);
+        mux.remove_set(b).now_or_never().unwrap().unwrap();
+        assert_eq!(mux.current(), Some(a));
+        assert_eq!(mux.advertiser().data, [1]);
+        mux.set_sets_enable(false).now_or_never().unwrap().unwrap();
+        assert!(!mux.advertiser().is_enabled);
+        assert!(mux.remove_set(b).now_or_never().unwrap().is_err());
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_timed_rotation() {
+        use crate::clock::sim::{delay, SimTime};
+        let sim = SimTime::new();
+        let mut mux = AdvertisementMultiplexer::new(RecordingAdvertiser::default());
+        let params = AdvertisingParameters::DEFAULT;
+        for data in &[[1], [2], [3]] {
+            mux.add_set(params, data).now_or_never().unwrap().unwrap();
+        }
+        mux.set_sets_enable(true).now_or_never().unwrap().unwrap();
+        // Enabling put set 1 on air, `run` then rotates at 0, 500 and 1000ms (sets 2, 3, 1).
+        let ran = sim.block_on_for(mux.run(delay), Duration::from_millis(1200));
+        assert!(ran.is_none());
+        assert_eq!(mux.advertiser().data, [1]);
+        // Driven by the caller's clock instead. The first call always rotates.
+        let mut rotate_at = |ms| {
+            sim.advance(Duration::from_millis(ms));
+            mux.rotate_if_due(sim.now())
+                .now_or_never()
+                .unwrap()
+                .unwrap()
+        };
+        assert!(rotate_at(0));
+        assert!(!rotate_at(400));
+        assert!(rotate_at(100));
+        assert_eq!(mux.advertiser().data, [3]);
+    }
+}
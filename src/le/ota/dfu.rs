@@ -0,0 +1,470 @@
+//! Nordic Secure DFU (Buttonless/Bootloader) control point protocol.
+use crate::le::ota::{crc32, Error, OtaTransport, Progress};
+use crate::{ConversionError, PackError};
+use core::convert::{TryFrom, TryInto};
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum Opcode {
+    ProtocolVersion = 0x00,
+    Create = 0x01,
+    SetReceiptNotification = 0x02,
+    CalculateChecksum = 0x03,
+    Execute = 0x04,
+    Select = 0x06,
+    GetMTU = 0x07,
+    Write = 0x08,
+    Ping = 0x09,
+    HardwareVersion = 0x0A,
+    FirmwareVersion = 0x0B,
+    Abort = 0x0C,
+    Response = 0x60,
+}
+impl From<Opcode> for u8 {
+    fn from(o: Opcode) -> Self {
+        o as u8
+    }
+}
+impl TryFrom<u8> for Opcode {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(Opcode::ProtocolVersion),
+            0x01 => Ok(Opcode::Create),
+            0x02 => Ok(Opcode::SetReceiptNotification),
+            0x03 => Ok(Opcode::CalculateChecksum),
+            0x04 => Ok(Opcode::Execute),
+            0x06 => Ok(Opcode::Select),
+            0x07 => Ok(Opcode::GetMTU),
+            0x08 => Ok(Opcode::Write),
+            0x09 => Ok(Opcode::Ping),
+            0x0A => Ok(Opcode::HardwareVersion),
+            0x0B => Ok(Opcode::FirmwareVersion),
+            0x0C => Ok(Opcode::Abort),
+            0x60 => Ok(Opcode::Response),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum ResultCode {
+    Invalid = 0x00,
+    Success = 0x01,
+    OpcodeNotSupported = 0x02,
+    InvalidParameter = 0x03,
+    InsufficientResources = 0x04,
+    InvalidObject = 0x05,
+    UnsupportedType = 0x07,
+    OperationNotPermitted = 0x08,
+    OperationFailed = 0x0A,
+    ExtendedError = 0x0B,
+}
+impl From<ResultCode> for u8 {
+    fn from(r: ResultCode) -> Self {
+        r as u8
+    }
+}
+impl TryFrom<u8> for ResultCode {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(ResultCode::Invalid),
+            0x01 => Ok(ResultCode::Success),
+            0x02 => Ok(ResultCode::OpcodeNotSupported),
+            0x03 => Ok(ResultCode::InvalidParameter),
+            0x04 => Ok(ResultCode::InsufficientResources),
+            0x05 => Ok(ResultCode::InvalidObject),
+            0x07 => Ok(ResultCode::UnsupportedType),
+            0x08 => Ok(ResultCode::OperationNotPermitted),
+            0x0A => Ok(ResultCode::OperationFailed),
+            0x0B => Ok(ResultCode::ExtendedError),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+impl ResultCode {
+    pub fn error(self) -> Result<(), Error> {
+        match self {
+            ResultCode::Success => Ok(()),
+            e => Err(Error::DfuResult(e)),
+        }
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum ObjectType {
+    Command = 0x01,
+    Data = 0x02,
+}
+impl From<ObjectType> for u8 {
+    fn from(o: ObjectType) -> Self {
+        o as u8
+    }
+}
+impl TryFrom<u8> for ObjectType {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(ObjectType::Command),
+            0x02 => Ok(ObjectType::Data),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// Control point request written by the DFU controller.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum Request {
+    Create { object_type: ObjectType, size: u32 },
+    SetReceiptNotification { packets: u16 },
+    CalculateChecksum,
+    Execute,
+    Select { object_type: ObjectType },
+    Abort,
+}
+impl Request {
+    pub const MAX_BYTE_LEN: usize = 6;
+    pub fn opcode(self) -> Opcode {
+        match self {
+            Request::Create { .. } => Opcode::Create,
+            Request::SetReceiptNotification { .. } => Opcode::SetReceiptNotification,
+            Request::CalculateChecksum => Opcode::CalculateChecksum,
+            Request::Execute => Opcode::Execute,
+            Request::Select { .. } => Opcode::Select,
+            Request::Abort => Opcode::Abort,
+        }
+    }
+    pub fn byte_len(self) -> usize {
+        match self {
+            Request::Create { .. } => 6,
+            Request::SetReceiptNotification { .. } => 3,
+            Request::Select { .. } => 2,
+            Request::CalculateChecksum | Request::Execute | Request::Abort => 1,
+        }
+    }
+    pub fn pack_into(self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        buf[0] = self.opcode().into();
+        match self {
+            Request::Create { object_type, size } => {
+                buf[1] = object_type.into();
+                buf[2..6].copy_from_slice(&size.to_le_bytes());
+            }
+            Request::SetReceiptNotification { packets } => {
+                buf[1..3].copy_from_slice(&packets.to_le_bytes());
+            }
+            Request::Select { object_type } => buf[1] = object_type.into(),
+            Request::CalculateChecksum | Request::Execute | Request::Abort => (),
+        }
+        Ok(())
+    }
+}
+/// Offset and CRC-32 reported by the target (`Select` and `CalculateChecksum` responses).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct Checksum {
+    pub offset: u32,
+    pub crc: u32,
+}
+impl Checksum {
+    pub const BYTE_LEN: usize = 8;
+    pub fn unpack_from(buf: &[u8]) -> Result<Checksum, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(Checksum {
+            offset: u32::from_le_bytes(buf[0..4].try_into().expect("length checked above")),
+            crc: u32::from_le_bytes(buf[4..8].try_into().expect("length checked above")),
+        })
+    }
+}
+/// Response to a `Select` request. Describes the current object on the target.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct SelectResponse {
+    pub max_size: u32,
+    pub checksum: Checksum,
+}
+impl SelectResponse {
+    pub const BYTE_LEN: usize = 4 + Checksum::BYTE_LEN;
+    pub fn unpack_from(buf: &[u8]) -> Result<SelectResponse, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(SelectResponse {
+            max_size: u32::from_le_bytes(buf[0..4].try_into().expect("length checked above")),
+            checksum: Checksum::unpack_from(&buf[4..])?,
+        })
+    }
+}
+/// Control point response notification header (`0x60`, request opcode, result code). Returns
+/// the response parameters after the header.
+pub fn unpack_response(request: Opcode, buf: &[u8]) -> Result<&[u8], Error> {
+    if buf.len() < 3 {
+        return Err(PackError::BadLength {
+            expected: 3,
+            got: buf.len(),
+        }
+        .into());
+    }
+    if Opcode::try_from(buf[0]) != Ok(Opcode::Response) || Opcode::try_from(buf[1]) != Ok(request) {
+        return Err(Error::UnexpectedResponse);
+    }
+    ResultCode::try_from(buf[2])
+        .map_err(|_| PackError::bad_index(2))?
+        .error()?;
+    Ok(&buf[3..])
+}
+/// Nordic Secure DFU controller. Sends the init packet (`Command` object) and firmware image
+/// (`Data` object). Transfers resume from the last object the target has validated.
+pub struct DfuController<T: OtaTransport> {
+    pub transport: T,
+}
+impl<T: OtaTransport> DfuController<T> {
+    pub fn new(transport: T) -> Self {
+        DfuController { transport }
+    }
+    async fn request(&mut self, request: Request) -> Result<alloc::boxed::Box<[u8]>, Error> {
+        let mut buf = [0_u8; Request::MAX_BYTE_LEN];
+        let len = request.byte_len();
+        request.pack_into(&mut buf[..len])?;
+        self.transport.write_control(&buf[..len]).await?;
+        let response = self.transport.read_notification().await?;
+        unpack_response(request.opcode(), response.as_ref())?;
+        Ok(response)
+    }
+    pub async fn set_receipt_notification(&mut self, packets: u16) -> Result<(), Error> {
+        self.request(Request::SetReceiptNotification { packets })
+            .await?;
+        Ok(())
+    }
+    pub async fn select(&mut self, object_type: ObjectType) -> Result<SelectResponse, Error> {
+        let response = self.request(Request::Select { object_type }).await?;
+        SelectResponse::unpack_from(&response[3..]).map_err(Error::from)
+    }
+    pub async fn calculate_checksum(&mut self) -> Result<Checksum, Error> {
+        let response = self.request(Request::CalculateChecksum).await?;
+        Checksum::unpack_from(&response[3..]).map_err(Error::from)
+    }
+    pub async fn abort(&mut self) -> Result<(), Error> {
+        let mut buf = [0_u8; 1];
+        Request::Abort.pack_into(&mut buf[..])?;
+        self.transport.write_control(&buf[..]).await
+    }
+    /// Transfer `data` as `object_type` objects. `on_progress` is called after every object is
+    /// executed. If the target already holds a valid prefix of `data`, the transfer resumes after it.
+    pub async fn transfer_object<F: FnMut(Progress)>(
+        &mut self,
+        object_type: ObjectType,
+        data: &[u8],
+        mut on_progress: F,
+    ) -> Result<(), Error> {
+        let select = self.select(object_type).await?;
+        let max_size = select.max_size as usize;
+        if max_size == 0 {
+            return Err(Error::DfuResult(ResultCode::InvalidObject));
+        }
+        // Resume from the start of the partially sent object if the target's CRC matches ours.
+        let resume_offset = core::cmp::min(select.checksum.offset as usize, data.len());
+        let mut offset =
+            if resume_offset > 0 && crc32(0, &data[..resume_offset]) == select.checksum.crc {
+                resume_offset - resume_offset % max_size
+            } else {
+                0
+            };
+        if offset == resume_offset && offset != 0 {
+            // The last object sent ends on an object boundary, make sure its executed.
+            self.request(Request::Execute).await?;
+            if offset == data.len() {
+                on_progress(Progress::new(offset, data.len()));
+                return Ok(());
+            }
+        }
+        let mut crc = crc32(0, &data[..offset]);
+        on_progress(Progress::new(offset, data.len()));
+        while offset < data.len() {
+            let object = &data[offset..core::cmp::min(offset + max_size, data.len())];
+            self.request(Request::Create {
+                object_type,
+                size: u32::try_from(object.len()).map_err(|_| PackError::InvalidFields)?,
+            })
+            .await?;
+            let chunk_len = core::cmp::max(1, self.transport.max_write_len());
+            for chunk in object.chunks(chunk_len) {
+                self.transport.write_data(chunk).await?;
+            }
+            crc = crc32(crc, object);
+            offset += object.len();
+            let checksum = self.calculate_checksum().await?;
+            if checksum.offset as usize != offset || checksum.crc != crc {
+                return Err(Error::ChecksumMismatch {
+                    expected_offset: u32::try_from(offset).unwrap_or(u32::MAX),
+                    expected_crc: crc,
+                    got_offset: checksum.offset,
+                    got_crc: checksum.crc,
+                });
+            }
+            self.request(Request::Execute).await?;
+            on_progress(Progress::new(offset, data.len()));
+        }
+        Ok(())
+    }
+    /// Run a full DFU. Sends the `init_packet` (`.dat`) followed by the `firmware` (`.bin`).
+    pub async fn update<F: FnMut(Progress)>(
+        &mut self,
+        init_packet: &[u8],
+        firmware: &[u8],
+        on_progress: F,
+    ) -> Result<(), Error> {
+        self.set_receipt_notification(0).await?;
+        self.transfer_object(ObjectType::Command, init_packet, |_| ())
+            .await?;
+        self.transfer_object(ObjectType::Data, firmware, on_progress)
+            .await
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LocalBoxFuture;
+    use alloc::boxed::Box;
+    use alloc::collections::VecDeque;
+    use alloc::vec::Vec;
+    use futures_util::FutureExt;
+
+    /// Simulated DFU target. Keeps the bytes it has received and answers control point
+    /// requests like a Nordic bootloader would.
+    struct SimTarget {
+        max_size: u32,
+        received: Vec<u8>,
+        executed: usize,
+        requests: Vec<Opcode>,
+        notifications: VecDeque<Box<[u8]>>,
+    }
+    impl SimTarget {
+        fn new(max_size: u32, received: &[u8], executed: usize) -> SimTarget {
+            SimTarget {
+                max_size,
+                received: received.to_vec(),
+                executed,
+                requests: Vec::new(),
+                notifications: VecDeque::new(),
+            }
+        }
+        fn checksum(&self) -> Vec<u8> {
+            let mut out = Vec::new();
+            out.extend_from_slice(&u32::try_from(self.received.len()).unwrap().to_le_bytes());
+            out.extend_from_slice(&crc32(0, &self.received).to_le_bytes());
+            out
+        }
+    }
+    impl OtaTransport for SimTarget {
+        fn max_write_len(&self) -> usize {
+            3
+        }
+        fn write_control<'a>(
+            &'a mut self,
+            data: &'a [u8],
+        ) -> LocalBoxFuture<'a, Result<(), Error>> {
+            let opcode = Opcode::try_from(data[0]).expect("valid opcode");
+            self.requests.push(opcode);
+            let mut response = alloc::vec![
+                u8::from(Opcode::Response),
+                data[0],
+                ResultCode::Success.into()
+            ];
+            match opcode {
+                Opcode::Select => {
+                    response.extend_from_slice(&self.max_size.to_le_bytes());
+                    response.extend_from_slice(&self.checksum());
+                }
+                Opcode::Create => self.received.truncate(self.executed),
+                Opcode::CalculateChecksum => response.extend_from_slice(&self.checksum()),
+                Opcode::Execute => self.executed = self.received.len(),
+                _ => (),
+            }
+            self.notifications.push_back(response.into_boxed_slice());
+            Box::pin(async { Ok(()) })
+        }
+        fn write_data<'a>(&'a mut self, data: &'a [u8]) -> LocalBoxFuture<'a, Result<(), Error>> {
+            self.received.extend_from_slice(data);
+            Box::pin(async { Ok(()) })
+        }
+        fn read_notification(&mut self) -> LocalBoxFuture<'_, Result<Box<[u8]>, Error>> {
+            let notification = self
+                .notifications
+                .pop_front()
+                .expect("a request was written");
+            Box::pin(async move { Ok(notification) })
+        }
+    }
+    fn transfer(target: SimTarget, data: &[u8]) -> (SimTarget, Vec<Progress>) {
+        let mut controller = DfuController::new(target);
+        let mut progress = Vec::new();
+        controller
+            .transfer_object(ObjectType::Data, data, |p| progress.push(p))
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        (controller.transport, progress)
+    }
+    const DATA: &[u8] = b"0123456789";
+    #[test]
+    fn test_transfer_fresh() {
+        let (target, progress) = transfer(SimTarget::new(4, &[], 0), DATA);
+        assert_eq!(target.received, DATA);
+        assert_eq!(target.executed, DATA.len());
+        assert_eq!(
+            target
+                .requests
+                .iter()
+                .filter(|&&o| o == Opcode::Create)
+                .count(),
+            3
+        );
+        assert_eq!(
+            progress,
+            [
+                Progress::new(0, 10),
+                Progress::new(4, 10),
+                Progress::new(8, 10),
+                Progress::new(10, 10)
+            ]
+        );
+    }
+    #[test]
+    fn test_transfer_resume_mid_object() {
+        // The second object was only partially sent, so it's sent again from its start.
+        let (target, progress) = transfer(SimTarget::new(4, &DATA[..6], 4), DATA);
+        assert_eq!(target.received, DATA);
+        assert_eq!(target.executed, DATA.len());
+        assert_eq!(
+            target.requests,
+            [
+                Opcode::Select,
+                Opcode::Create,
+                Opcode::CalculateChecksum,
+                Opcode::Execute,
+                Opcode::Create,
+                Opcode::CalculateChecksum,
+                Opcode::Execute
+            ]
+        );
+        assert_eq!(progress[0], Progress::new(4, 10));
+    }
+    #[test]
+    fn test_transfer_resume_object_boundary() {
+        // The second object was fully sent but never executed.
+        let (target, progress) = transfer(SimTarget::new(4, &DATA[..8], 4), DATA);
+        assert_eq!(target.received, DATA);
+        assert_eq!(target.executed, DATA.len());
+        assert_eq!(
+            target.requests,
+            [
+                Opcode::Select,
+                Opcode::Execute,
+                Opcode::Create,
+                Opcode::CalculateChecksum,
+                Opcode::Execute
+            ]
+        );
+        assert_eq!(progress[0], Progress::new(8, 10));
+    }
+}
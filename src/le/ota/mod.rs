@@ -0,0 +1,102 @@
+//! Firmware Over-The-Air (OTA) updates. Implements [`dfu`] (Nordic Secure DFU) and [`smp`]
+//! (zephyr MCUmgr SMP) on top of a generic GATT [`OtaTransport`].
+use crate::error::IOError;
+use crate::{LocalBoxFuture, PackError};
+use alloc::boxed::Box;
+use core::convert::TryFrom;
+
+pub mod dfu;
+pub mod smp;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum Error {
+    IOError(IOError),
+    PackError(PackError),
+    /// The DFU target responded with a non-success result code.
+    DfuResult(dfu::ResultCode),
+    /// The SMP server responded with a non-zero `rc`.
+    SmpReturnCode(i32),
+    /// The target returned a response to a request that wasn't sent.
+    UnexpectedResponse,
+    /// The target's reported offset/CRC doesn't match what was sent.
+    ChecksumMismatch {
+        expected_offset: u32,
+        expected_crc: u32,
+        got_offset: u32,
+        got_crc: u32,
+    },
+}
+impl From<IOError> for Error {
+    fn from(e: IOError) -> Self {
+        Error::IOError(e)
+    }
+}
+impl From<PackError> for Error {
+    fn from(e: PackError) -> Self {
+        Error::PackError(e)
+    }
+}
+impl crate::error::Error for Error {}
+
+/// GATT transport used by the OTA protocols. `write_control`/`read_notification` map onto the
+/// control point characteristic (or SMP characteristic) and `write_data` onto the data/packet
+/// characteristic (Write Without Response).
+pub trait OtaTransport {
+    /// Maximum number of bytes that can be written in one GATT write (ATT MTU - 3).
+    fn max_write_len(&self) -> usize;
+    fn write_control<'a>(&'a mut self, data: &'a [u8]) -> LocalBoxFuture<'a, Result<(), Error>>;
+    fn write_data<'a>(&'a mut self, data: &'a [u8]) -> LocalBoxFuture<'a, Result<(), Error>>;
+    fn read_notification(&mut self) -> LocalBoxFuture<'_, Result<Box<[u8]>, Error>>;
+}
+
+/// Progress of an OTA transfer in bytes.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct Progress {
+    pub sent: usize,
+    pub total: usize,
+}
+impl Progress {
+    pub fn new(sent: usize, total: usize) -> Progress {
+        Progress { sent, total }
+    }
+    pub fn is_done(self) -> bool {
+        self.sent >= self.total
+    }
+    /// Progress as a percentage (`0..=100`).
+    pub fn percent(self) -> u8 {
+        (core::cmp::min(self.sent, self.total) * 100)
+            .checked_div(self.total)
+            .map_or(100, |p| u8::try_from(p).expect("sent <= total so p <= 100"))
+    }
+}
+
+/// CRC-32 (IEEE 802.3) used by Nordic DFU to verify transferred objects.
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 0x01 == 0x01 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xCBF4_3926);
+    }
+    #[test]
+    fn test_progress() {
+        assert_eq!(Progress::new(0, 0).percent(), 100);
+        assert_eq!(Progress::new(50, 200).percent(), 25);
+        assert!(Progress::new(200, 200).is_done());
+    }
+}
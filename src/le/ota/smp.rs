@@ -0,0 +1,337 @@
+//! zephyr MCUmgr Simple Management Protocol (SMP) over BLE. Only the image upload command is
+//! implemented which is enough to push a new firmware image to an MCUboot target.
+use crate::le::ota::{Error, OtaTransport, Progress};
+use crate::{ConversionError, PackError};
+use alloc::vec::Vec;
+use core::convert::{TryFrom, TryInto};
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum Operation {
+    Read = 0,
+    ReadResponse = 1,
+    Write = 2,
+    WriteResponse = 3,
+}
+impl From<Operation> for u8 {
+    fn from(o: Operation) -> Self {
+        o as u8
+    }
+}
+impl TryFrom<u8> for Operation {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Operation::Read),
+            1 => Ok(Operation::ReadResponse),
+            2 => Ok(Operation::Write),
+            3 => Ok(Operation::WriteResponse),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u16)]
+pub enum Group {
+    OS = 0,
+    Image = 1,
+    Stat = 2,
+    Config = 3,
+    Log = 4,
+    Crash = 5,
+    Split = 6,
+    Run = 7,
+    FS = 8,
+    Shell = 9,
+}
+impl From<Group> for u16 {
+    fn from(g: Group) -> Self {
+        g as u16
+    }
+}
+impl TryFrom<u16> for Group {
+    type Error = ConversionError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Group::OS),
+            1 => Ok(Group::Image),
+            2 => Ok(Group::Stat),
+            3 => Ok(Group::Config),
+            4 => Ok(Group::Log),
+            5 => Ok(Group::Crash),
+            6 => Ok(Group::Split),
+            7 => Ok(Group::Run),
+            8 => Ok(Group::FS),
+            9 => Ok(Group::Shell),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// Image group command ID for `upload`.
+pub const IMAGE_UPLOAD_ID: u8 = 1;
+/// SMP Header (8 bytes). Multi-byte fields are Big Endian.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct Header {
+    pub operation: Operation,
+    pub flags: u8,
+    pub data_len: u16,
+    pub group: u16,
+    pub sequence: u8,
+    pub command_id: u8,
+}
+impl Header {
+    pub const BYTE_LEN: usize = 8;
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = u8::from(self.operation) & 0x07;
+        buf[1] = self.flags;
+        buf[2..4].copy_from_slice(&self.data_len.to_be_bytes());
+        buf[4..6].copy_from_slice(&self.group.to_be_bytes());
+        buf[6] = self.sequence;
+        buf[7] = self.command_id;
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<Header, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(Header {
+            operation: Operation::try_from(buf[0] & 0x07).map_err(|_| PackError::bad_index(0))?,
+            flags: buf[1],
+            data_len: u16::from_be_bytes(buf[2..4].try_into().expect("length checked above")),
+            group: u16::from_be_bytes(buf[4..6].try_into().expect("length checked above")),
+            sequence: buf[6],
+            command_id: buf[7],
+        })
+    }
+}
+#[allow(clippy::cast_possible_truncation)]
+fn cbor_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < 24 {
+        out.push(major | value as u8);
+    } else if value <= u64::from(u8::MAX) {
+        out.push(major | 24);
+        out.push(value as u8);
+    } else if value <= u64::from(u16::MAX) {
+        out.push(major | 25);
+        out.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= u64::from(u32::MAX) {
+        out.push(major | 26);
+        out.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+fn cbor_key(out: &mut Vec<u8>, key: &str) {
+    cbor_head(out, 3, key.len() as u64);
+    out.extend_from_slice(key.as_bytes());
+}
+/// Reads a CBOR head at `buf[*index]`. Returns `(major_type, value)`.
+fn cbor_read_head(buf: &[u8], index: &mut usize) -> Result<(u8, u64), PackError> {
+    let first = *buf.get(*index).ok_or(PackError::bad_index(*index))?;
+    let start = *index + 1;
+    let (len, value) = match first & 0x1F {
+        v @ 0..=23 => (0, u64::from(v)),
+        24 => (1, 0),
+        25 => (2, 0),
+        26 => (4, 0),
+        27 => (8, 0),
+        _ => return Err(PackError::bad_index(*index)),
+    };
+    let bytes = buf
+        .get(start..start + len)
+        .ok_or(PackError::bad_index(start))?;
+    let value = bytes
+        .iter()
+        .fold(value, |acc, &b| (acc << 8) | u64::from(b));
+    *index = start + len;
+    Ok((first >> 5, value))
+}
+/// Skips `len` bytes of a string at `buf[*index]`. Returns the skipped bytes.
+fn cbor_skip<'a>(buf: &'a [u8], index: &mut usize, len: u64) -> Result<&'a [u8], PackError> {
+    let start = *index;
+    let end = usize::try_from(len)
+        .ok()
+        .and_then(|len| start.checked_add(len))
+        .filter(|&end| end <= buf.len())
+        .ok_or(PackError::bad_index(start))?;
+    *index = end;
+    Ok(&buf[start..end])
+}
+/// `rc` and `off` fields of an image upload response.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct UploadResponse {
+    pub rc: i32,
+    pub offset: Option<u32>,
+}
+impl UploadResponse {
+    /// Unpacks the CBOR map payload. Unknown keys with simple values are skipped.
+    pub fn unpack_from(buf: &[u8]) -> Result<UploadResponse, PackError> {
+        let mut index = 0;
+        let (major, entries) = cbor_read_head(buf, &mut index)?;
+        if major != 5 {
+            return Err(PackError::bad_index(0));
+        }
+        let mut out = UploadResponse::default();
+        for _ in 0..entries {
+            let (major, key_len) = cbor_read_head(buf, &mut index)?;
+            if major != 3 {
+                return Err(PackError::bad_index(index));
+            }
+            let key = cbor_skip(buf, &mut index, key_len)?;
+            let value_index = index;
+            let (major, value) = cbor_read_head(buf, &mut index)?;
+            let value = match major {
+                0 => i64::try_from(value).map_err(|_| PackError::bad_index(value_index))?,
+                1 => -1 - i64::try_from(value).map_err(|_| PackError::bad_index(value_index))?,
+                2 | 3 => {
+                    cbor_skip(buf, &mut index, value)?;
+                    continue;
+                }
+                _ => return Err(PackError::bad_index(value_index)),
+            };
+            match key {
+                b"rc" => {
+                    out.rc = i32::try_from(value).map_err(|_| PackError::bad_index(value_index))?;
+                }
+                b"off" => {
+                    out.offset =
+                        Some(u32::try_from(value).map_err(|_| PackError::bad_index(value_index))?);
+                }
+                _ => (),
+            }
+        }
+        Ok(out)
+    }
+}
+/// Packs a full image upload SMP frame (header + CBOR payload) for `data` at `offset`. `total_len`
+/// is only sent with the first chunk (`offset == 0`).
+pub fn pack_upload_frame(sequence: u8, offset: u32, total_len: u32, data: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(data.len() + 24);
+    cbor_head(&mut payload, 5, if offset == 0 { 3 } else { 2 });
+    if offset == 0 {
+        cbor_key(&mut payload, "len");
+        cbor_head(&mut payload, 0, u64::from(total_len));
+    }
+    cbor_key(&mut payload, "off");
+    cbor_head(&mut payload, 0, u64::from(offset));
+    cbor_key(&mut payload, "data");
+    cbor_head(&mut payload, 2, data.len() as u64);
+    payload.extend_from_slice(data);
+    let header = Header {
+        operation: Operation::Write,
+        flags: 0,
+        data_len: u16::try_from(payload.len()).unwrap_or(u16::MAX),
+        group: Group::Image.into(),
+        sequence,
+        command_id: IMAGE_UPLOAD_ID,
+    };
+    let mut out = vec![0_u8; Header::BYTE_LEN];
+    header
+        .pack_into(&mut out[..])
+        .expect("buffer is header sized");
+    out.extend_from_slice(&payload);
+    out
+}
+/// Overhead of an upload frame (header + CBOR keys) not counting the image data.
+const UPLOAD_OVERHEAD: usize = Header::BYTE_LEN + 32;
+/// MCUmgr SMP client. Uploads images using the `Image` group.
+pub struct SmpClient<T: OtaTransport> {
+    pub transport: T,
+    sequence: u8,
+}
+impl<T: OtaTransport> SmpClient<T> {
+    pub fn new(transport: T) -> Self {
+        SmpClient {
+            transport,
+            sequence: 0,
+        }
+    }
+    /// Upload `image` starting at `offset`. Pass the last reported offset to resume an
+    /// interrupted upload. Returns once the target has acknowledged the whole image.
+    pub async fn upload_image<F: FnMut(Progress)>(
+        &mut self,
+        image: &[u8],
+        mut offset: usize,
+        mut on_progress: F,
+    ) -> Result<(), Error> {
+        let total_len = u32::try_from(image.len()).map_err(|_| PackError::InvalidFields)?;
+        let chunk_len = self
+            .transport
+            .max_write_len()
+            .checked_sub(UPLOAD_OVERHEAD)
+            .filter(|&l| l > 0)
+            .ok_or(Error::PackError(PackError::InvalidFields))?;
+        while offset < image.len() {
+            let end = core::cmp::min(offset + chunk_len, image.len());
+            let frame = pack_upload_frame(
+                self.sequence,
+                u32::try_from(offset).map_err(|_| PackError::InvalidFields)?,
+                total_len,
+                &image[offset..end],
+            );
+            self.transport.write_control(&frame).await?;
+            let response = self.transport.read_notification().await?;
+            if response.len() < Header::BYTE_LEN {
+                return Err(PackError::BadLength {
+                    expected: Header::BYTE_LEN,
+                    got: response.len(),
+                }
+                .into());
+            }
+            let header = Header::unpack_from(&response[..Header::BYTE_LEN])?;
+            if header.operation != Operation::WriteResponse
+                || header.sequence != self.sequence
+                || header.group != u16::from(Group::Image)
+            {
+                return Err(Error::UnexpectedResponse);
+            }
+            self.sequence = self.sequence.wrapping_add(1);
+            let upload = UploadResponse::unpack_from(&response[Header::BYTE_LEN..])?;
+            if upload.rc != 0 {
+                return Err(Error::SmpReturnCode(upload.rc));
+            }
+            offset = upload.offset.map_or(end, |o| o as usize);
+            on_progress(Progress::new(offset, image.len()));
+        }
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_upload_frame() {
+        let frame = pack_upload_frame(7, 0, 4, &[1, 2, 3, 4]);
+        let header = Header::unpack_from(&frame[..Header::BYTE_LEN]).unwrap();
+        assert_eq!(header.operation, Operation::Write);
+        assert_eq!(header.group, u16::from(Group::Image));
+        assert_eq!(header.sequence, 7);
+        assert_eq!(usize::from(header.data_len), frame.len() - Header::BYTE_LEN);
+    }
+    #[test]
+    fn test_upload_response() {
+        // {"rc": 0, "off": 512}
+        let buf = [
+            0xA2, 0x62, b'r', b'c', 0x00, 0x63, b'o', b'f', b'f', 0x19, 0x02, 0x00,
+        ];
+        assert_eq!(
+            UploadResponse::unpack_from(&buf[..]),
+            Ok(UploadResponse {
+                rc: 0,
+                offset: Some(512)
+            })
+        );
+        // 64-bit key and value lengths are rejected instead of overflowing the index.
+        let mut huge_key = vec![0xA1, 0x7B];
+        huge_key.extend_from_slice(&[0xFF; 8]);
+        assert!(UploadResponse::unpack_from(&huge_key).is_err());
+        let mut huge_value = vec![0xA1, 0x61, b'x', 0x5B];
+        huge_value.extend_from_slice(&[0xFF; 8]);
+        assert!(UploadResponse::unpack_from(&huge_value).is_err());
+        // A string running past the end of the payload.
+        assert!(UploadResponse::unpack_from(&[0xA1, 0x61, b'x', 0x45, 0x00]).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+//! LE physical layers (PHYs) and sets of them ([`PhyMask`]).
+use crate::ConversionError;
+use core::convert::TryFrom;
+
+/// LE PHY. LE Coded covers both S=2 and S=8 coding.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum Phy {
+    LE1M = 0x01,
+    LE2M = 0x02,
+    LECoded = 0x03,
+}
+impl Phy {
+    pub const BYTE_LEN: usize = 1;
+    pub const DEFAULT: Phy = Phy::LE1M;
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phy::LE1M => "LE 1M",
+            Phy::LE2M => "LE 2M",
+            Phy::LECoded => "LE Coded",
+        }
+    }
+    /// Unpack a PHY field where `0x00` means no packets on that PHY (the secondary PHY of
+    /// extended advertising reports).
+    pub fn maybe_phy(value: u8) -> Result<Option<Phy>, ConversionError> {
+        match value {
+            0x00 => Ok(None),
+            v => Phy::try_from(v).map(Some),
+        }
+    }
+}
+impl Default for Phy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+impl core::fmt::Display for Phy {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+impl From<Phy> for u8 {
+    fn from(p: Phy) -> Self {
+        p as u8
+    }
+}
+impl TryFrom<u8> for Phy {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Phy::LE1M),
+            0x02 => Ok(Phy::LE2M),
+            0x03 => Ok(Phy::LECoded),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// Set of PHYs (the `TX_PHYS`/`RX_PHYS` bit fields). Bit 0 is LE 1M, bit 1 LE 2M and bit 2 LE
+/// Coded.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct PhyMask(u8);
+impl PhyMask {
+    pub const BYTE_LEN: usize = 1;
+    pub const ZEROED: PhyMask = PhyMask(0);
+    pub const ALL: PhyMask = PhyMask(0b111);
+    pub const fn zeroed() -> PhyMask {
+        Self::ZEROED
+    }
+    pub fn enable(&mut self, phy: Phy) {
+        self.0 |= Self::bit(phy);
+    }
+    pub fn disable(&mut self, phy: Phy) {
+        self.0 &= !Self::bit(phy);
+    }
+    pub fn contains(self, phy: Phy) -> bool {
+        self.0 & Self::bit(phy) != 0
+    }
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    /// The PHYs in the set, slowest last.
+    pub fn phys(self) -> impl Iterator<Item = Phy> {
+        [Phy::LE2M, Phy::LE1M, Phy::LECoded]
+            .iter()
+            .copied()
+            .filter(move |phy| self.contains(*phy))
+    }
+    fn bit(phy: Phy) -> u8 {
+        1 << (u8::from(phy) - 1)
+    }
+}
+impl From<Phy> for PhyMask {
+    fn from(phy: Phy) -> Self {
+        PhyMask(PhyMask::bit(phy))
+    }
+}
+impl From<PhyMask> for u8 {
+    fn from(m: PhyMask) -> Self {
+        m.0
+    }
+}
+impl TryFrom<u8> for PhyMask {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value & !PhyMask::ALL.0 == 0 {
+            Ok(PhyMask(value))
+        } else {
+            Err(ConversionError(()))
+        }
+    }
+}
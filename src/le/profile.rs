@@ -0,0 +1,397 @@
+//! Opt-in report enrichment. Advertising data and scan responses carry a device's name and
+//! service UUIDs in pieces (shortened name in one, complete name in the other, UUIDs split
+//! between them). A [`ProfileCache`] merges them per device into a [`DeviceProfile`] and
+//! [`Enriched`] attaches the current profile to each report of a stream, so UI code gets display
+//! ready names and services without parsing AD structures itself.
+//!
+//! Devices advertising under more than one address (a public address in the scan response and a
+//! random static one in the advertisement, an identity address learned when bonding) can be
+//! linked with [`ProfileCache::link`] so they show up once. Addresses found in LE Bluetooth
+//! Device Address AD structures are linked automatically.
+use crate::le::advertisement::{AdType, RawAdvertisement, StaticAdvBuffer, UnpackableAdStructType};
+use crate::le::report::ReportInfo;
+use crate::le::smp::oob::LEDeviceAddress;
+use crate::uuid::{UUID, UUID16, UUID32};
+use crate::{AddressKind, AddressType, DeviceAddress, Stream};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// Advertised service UUID in the size it was advertised with.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum ServiceUUID {
+    UUID16(UUID16),
+    UUID32(UUID32),
+    UUID128(UUID),
+}
+impl ServiceUUID {
+    /// Parse one little endian UUID of `bytes.len()` (2, 4 or 16) bytes.
+    pub fn from_le_bytes(bytes: &[u8]) -> Option<ServiceUUID> {
+        match bytes.len() {
+            2 => Some(ServiceUUID::UUID16(UUID16(u16::from_le_bytes([
+                bytes[0], bytes[1],
+            ])))),
+            4 => Some(ServiceUUID::UUID32(UUID32(u32::from_le_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3],
+            ])))),
+            16 => {
+                let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+                let mut node = [0_u8; 8];
+                node[..6].copy_from_slice(&bytes[..6]);
+                Some(ServiceUUID::UUID128(UUID::from_fields(
+                    u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+                    u16_at(10),
+                    u16_at(8),
+                    u16_at(6),
+                    u64::from_le_bytes(node),
+                )))
+            }
+            _ => None,
+        }
+    }
+    /// Full 128-bit UUID. 16 and 32-bit UUIDs are expanded with the Bluetooth Base UUID
+    /// (`0000xxxx-0000-1000-8000-00805f9b34fb`).
+    pub fn to_uuid(self) -> UUID {
+        let short = match self {
+            ServiceUUID::UUID16(uuid) => u32::from(uuid.0),
+            ServiceUUID::UUID32(uuid) => uuid.0,
+            ServiceUUID::UUID128(uuid) => return uuid,
+        };
+        UUID::from_fields(short, 0x0000, 0x1000, 0x8000, 0x0080_5F9B_34FB)
+    }
+}
+/// Size of the UUIDs in a service UUID list structure.
+fn uuid_len(ad_type: AdType) -> Option<usize> {
+    match ad_type {
+        AdType::IncompleteList16bitUUID | AdType::CompleteList16bitUUID => Some(2),
+        AdType::IncompleteList32bitUUID | AdType::CompleteList32bitUUID => Some(4),
+        AdType::IncompleteList128bitUUID | AdType::CompleteList128bitUUID => Some(16),
+        _ => None,
+    }
+}
+/// How well `device` identifies its device. A device linked under several addresses is kept
+/// under the best one: public, then random static, then private.
+fn identity_rank(device: DeviceAddress) -> u8 {
+    match device.kind {
+        AddressKind::Public => 2,
+        AddressKind::Random(AddressType::StaticDevice) => 1,
+        AddressKind::Random(_) => 0,
+    }
+}
+/// Identity address `data` advertises in an LE Bluetooth Device Address structure.
+fn advertised_identity<T: AsRef<[u8]>>(data: &RawAdvertisement<T>) -> Option<DeviceAddress> {
+    data.iter()
+        .filter(|structure| structure.ad_type == AdType::LEDeviceAddress)
+        .filter_map(|structure| {
+            LEDeviceAddress::unpack_from(structure.ad_type, structure.buf.as_ref()).ok()
+        })
+        .map(|address| address.device_address())
+        .find(|device| device.is_identity())
+}
+/// Everything known about a device from its advertisements.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct DeviceProfile {
+    pub name: Option<String>,
+    /// Whether `name` came from a Complete Local Name (not a Shortened one).
+    pub is_name_complete: bool,
+    /// Advertised service UUIDs in the order they were first seen.
+    pub services: Vec<ServiceUUID>,
+}
+impl DeviceProfile {
+    /// Merge the names and service UUIDs of `data`. A complete name replaces any name, a
+    /// shortened one only replaces another shortened one. Names that aren't UTF-8 and UUID
+    /// lists that aren't a whole number of UUIDs are ignored. Returns if the profile changed.
+    pub fn merge<T: AsRef<[u8]>>(&mut self, data: &RawAdvertisement<T>) -> bool {
+        let mut changed = false;
+        for structure in data.iter() {
+            let buf = structure.buf.as_ref();
+            match structure.ad_type {
+                AdType::CompleteLocalName | AdType::ShortenLocalName => {
+                    let is_complete = structure.ad_type == AdType::CompleteLocalName;
+                    if !is_complete && self.is_name_complete {
+                        continue;
+                    }
+                    let name = match core::str::from_utf8(buf) {
+                        Ok(name) => name,
+                        Err(_) => continue,
+                    };
+                    if self.name.as_deref() != Some(name) || self.is_name_complete != is_complete {
+                        self.name = Some(String::from(name));
+                        self.is_name_complete = is_complete;
+                        changed = true;
+                    }
+                }
+                ad_type => {
+                    let len = match uuid_len(ad_type) {
+                        Some(len) if buf.len() % len == 0 => len,
+                        _ => continue,
+                    };
+                    for uuid in buf.chunks_exact(len).filter_map(ServiceUUID::from_le_bytes) {
+                        if !self.services.contains(&uuid) {
+                            self.services.push(uuid);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        changed
+    }
+    /// Merge `other` (the profile of another address of the same device). Names follow the same
+    /// rules as [`DeviceProfile::merge`].
+    pub fn merge_profile(&mut self, other: DeviceProfile) {
+        if other.name.is_some() && (other.is_name_complete || !self.is_name_complete) {
+            self.name = other.name;
+            self.is_name_complete = other.is_name_complete;
+        }
+        for uuid in other.services {
+            if !self.services.contains(&uuid) {
+                self.services.push(uuid);
+            }
+        }
+    }
+    pub fn has_service(&self, uuid: ServiceUUID) -> bool {
+        self.services.contains(&uuid)
+    }
+}
+/// A report with the profile of its device (after merging the report in).
+#[derive(Clone)]
+pub struct EnrichedReport<T = StaticAdvBuffer> {
+    pub report: ReportInfo<T>,
+    pub profile: DeviceProfile,
+}
+impl<T: AsRef<[u8]>> core::fmt::Debug for EnrichedReport<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("EnrichedReport")
+            .field("report", &self.report)
+            .field("profile", &self.profile)
+            .finish()
+    }
+}
+/// [`DeviceProfile`] of each device seen. Holds at most `max_devices` profiles, dropping the
+/// least recently seen device when full.
+#[derive(Clone, Debug)]
+pub struct ProfileCache {
+    /// Most recently seen last.
+    profiles: Vec<(DeviceAddress, DeviceProfile)>,
+    /// Linked addresses and the address their device's profile is kept under.
+    aliases: Vec<(DeviceAddress, DeviceAddress)>,
+    max_devices: usize,
+}
+impl ProfileCache {
+    pub const DEFAULT_MAX_DEVICES: usize = 256;
+    pub fn new(max_devices: usize) -> ProfileCache {
+        ProfileCache {
+            profiles: Vec::new(),
+            aliases: Vec::new(),
+            max_devices,
+        }
+    }
+    /// Address `device`'s profile is kept under (`device` unless it's been linked).
+    pub fn identity(&self, device: DeviceAddress) -> DeviceAddress {
+        self.aliases
+            .iter()
+            .find(|(alias, _)| *alias == device)
+            .map_or(device, |(_, identity)| *identity)
+    }
+    /// Addresses linked to `identity`.
+    pub fn aliases(&self, identity: DeviceAddress) -> impl Iterator<Item = DeviceAddress> + '_ {
+        self.aliases
+            .iter()
+            .filter(move |(_, i)| *i == identity)
+            .map(|(alias, _)| *alias)
+    }
+    /// Record that `alias` and `identity` are the same device (the address it advertised with
+    /// and the identity address from bonding, etc). Their profiles are merged and kept under
+    /// `identity`, unless `alias` identifies the device better (a public address over a random
+    /// static one, either over a private one). Returns the address the profile is kept under.
+    pub fn link(&mut self, alias: DeviceAddress, identity: DeviceAddress) -> DeviceAddress {
+        let (alias, identity) = (self.identity(alias), self.identity(identity));
+        if alias == identity {
+            return identity;
+        }
+        let (alias, identity) = if identity_rank(alias) > identity_rank(identity) {
+            (identity, alias)
+        } else {
+            (alias, identity)
+        };
+        for (_, linked) in self.aliases.iter_mut().filter(|(_, i)| *i == alias) {
+            *linked = identity;
+        }
+        self.aliases.push((alias, identity));
+        if let Some(i) = self.profiles.iter().position(|(d, _)| *d == alias) {
+            let (_, profile) = self.profiles.remove(i);
+            match self.profiles.iter_mut().find(|(d, _)| *d == identity) {
+                Some((_, merged)) => merged.merge_profile(profile),
+                None => self.profiles.push((identity, profile)),
+            }
+        }
+        identity
+    }
+    pub fn get(&self, device: DeviceAddress) -> Option<&DeviceProfile> {
+        let device = self.identity(device);
+        self.profiles
+            .iter()
+            .find(|(d, _)| *d == device)
+            .map(|(_, profile)| profile)
+    }
+    pub fn len(&self) -> usize {
+        self.profiles.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+    /// Remove the profile of `device` and forget its linked addresses.
+    pub fn remove(&mut self, device: DeviceAddress) -> Option<DeviceProfile> {
+        let device = self.identity(device);
+        self.aliases.retain(|(_, identity)| *identity != device);
+        let i = self.profiles.iter().position(|(d, _)| *d == device)?;
+        Some(self.profiles.remove(i).1)
+    }
+    pub fn clear(&mut self) {
+        self.profiles.clear();
+        self.aliases.clear();
+    }
+    /// Merge `report` into its device's profile and return the updated profile.
+    pub fn update<T: AsRef<[u8]>>(&mut self, report: &ReportInfo<T>) -> &DeviceProfile {
+        let mut device = self.identity(report.device_address());
+        if let Some(advertised) = advertised_identity(&report.data) {
+            device = self.link(device, advertised);
+        }
+        let mut entry = match self.profiles.iter().position(|(d, _)| *d == device) {
+            Some(i) => self.profiles.remove(i),
+            None => (device, DeviceProfile::default()),
+        };
+        entry.1.merge(&report.data);
+        if self.max_devices > 0 && self.profiles.len() >= self.max_devices {
+            let (evicted, _) = self.profiles.remove(0);
+            self.aliases.retain(|(_, identity)| *identity != evicted);
+        }
+        self.profiles.push(entry);
+        &self.profiles.last().expect("just pushed").1
+    }
+    pub fn enrich<T: AsRef<[u8]>>(&mut self, report: ReportInfo<T>) -> EnrichedReport<T> {
+        let profile = self.update(&report).clone();
+        EnrichedReport { report, profile }
+    }
+}
+impl Default for ProfileCache {
+    fn default() -> Self {
+        ProfileCache::new(Self::DEFAULT_MAX_DEVICES)
+    }
+}
+/// Report stream with the [`DeviceProfile`] of each report's device attached.
+pub struct Enriched<S> {
+    stream: S,
+    cache: ProfileCache,
+}
+impl<S> Enriched<S> {
+    pub fn new(stream: S, cache: ProfileCache) -> Self {
+        Enriched { stream, cache }
+    }
+    pub fn cache(&self) -> &ProfileCache {
+        &self.cache
+    }
+    pub fn cache_mut(&mut self) -> &mut ProfileCache {
+        &mut self.cache
+    }
+    pub fn into_inner(self) -> (S, ProfileCache) {
+        (self.stream, self.cache)
+    }
+}
+impl<S: Unpin> Unpin for Enriched<S> {}
+impl<S: Stream<Item = Result<ReportInfo<T>, E>> + Unpin, T: AsRef<[u8]>, E> Stream for Enriched<S> {
+    type Item = Result<EnrichedReport<T>, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        Pin::new(&mut this.stream)
+            .poll_next(cx)
+            .map(|item| item.map(|report| report.map(|report| this.cache.enrich(report))))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::le::advertisement::RawAdvertisement;
+    use crate::le::report::{AddressType, EventType};
+    use crate::BTAddress;
+    #[test]
+    fn test_profile_cache() {
+        let report = |event_type, data: &[u8]| ReportInfo::<Vec<u8>> {
+            event_type,
+            address: BTAddress([1, 2, 3, 4, 5, 6]),
+            data: RawAdvertisement(data.to_vec()),
+            ..ReportInfo::default()
+        };
+        let mut cache = ProfileCache::new(1);
+        // Shortened name and a 16-bit UUID in the advertisement.
+        let adv = report(
+            EventType::AdvInd,
+            &[4, 0x08, b'a', b'b', b'c', 3, 0x03, 0x0F, 0x18],
+        );
+        let enriched = cache.enrich(adv.clone());
+        assert_eq!(enriched.profile.name.as_deref(), Some("abc"));
+        assert!(!enriched.profile.is_name_complete);
+        // Complete name and a 128-bit UUID in the scan response.
+        let mut data = alloc::vec![6, 0x09, b'a', b'b', b'c', b'd', b'e', 17, 0x07];
+        data.extend_from_slice(&[
+            0xFB, 0x34, 0x9B, 0x5F, 0x80, 0x00, 0x00, 0x80, 0x00, 0x10, 0x00, 0x00, 0x0F, 0x18,
+            0x00, 0x00,
+        ]);
+        let profile = cache.update(&report(EventType::ScanRsp, &data)).clone();
+        assert_eq!(profile.name.as_deref(), Some("abcde"));
+        assert!(profile.has_service(ServiceUUID::UUID16(UUID16(0x180F))));
+        match profile.services[1] {
+            ServiceUUID::UUID128(uuid) => assert_eq!(
+                uuid,
+                UUID::from_fields(0x0000180F, 0x0000, 0x1000, 0x8000, 0x00805F9B34FB)
+            ),
+            _ => panic!("expected a 128-bit UUID"),
+        }
+        // The shortened name doesn't replace the complete one.
+        assert_eq!(cache.enrich(adv).profile.name.as_deref(), Some("abcde"));
+        let other = ReportInfo::<Vec<u8>> {
+            address: BTAddress([9; 6]),
+            ..report(EventType::AdvInd, &[])
+        };
+        cache.update(&other);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(other.device_address()).is_some());
+    }
+    #[test]
+    fn test_profile_aliases() {
+        let public = DeviceAddress::public(BTAddress([1, 2, 3, 4, 5, 6]));
+        let static_random = DeviceAddress::random(BTAddress([1, 2, 3, 4, 5, 0xC6]));
+        let private = DeviceAddress::random(BTAddress([1, 2, 3, 4, 5, 0x46]));
+        let report = |device: DeviceAddress, data: &[u8]| ReportInfo::<Vec<u8>> {
+            address: device.addr,
+            address_type: if device.is_random() {
+                AddressType::RandomDevice
+            } else {
+                AddressType::PublicDevice
+            },
+            data: RawAdvertisement(data.to_vec()),
+            ..ReportInfo::default()
+        };
+        let mut cache = ProfileCache::default();
+        cache.update(&report(private, &[2, 0x08, b'a']));
+        // The identity address from bonding is kept over the private one.
+        assert_eq!(cache.link(private, static_random), static_random);
+        // The random static address advertises its public address in the scan response.
+        let mut data = alloc::vec![8, 0x1B];
+        data.extend_from_slice(&public.addr.0);
+        data.extend_from_slice(&[0x00, 3, 0x09, b'a', b'b']);
+        let profile = cache.update(&report(static_random, &data)).clone();
+        assert_eq!(profile.name.as_deref(), Some("ab"));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.identity(private), public);
+        assert_eq!(cache.identity(static_random), public);
+        assert_eq!(cache.aliases(public).count(), 2);
+        assert_eq!(cache.get(private), Some(&profile));
+        cache.remove(static_random);
+        assert!(cache.is_empty());
+        assert_eq!(cache.identity(private), private);
+    }
+}
@@ -0,0 +1,231 @@
+//! RSSI based proximity events. [`ProximityMonitor`] smooths the RSSI of tracked devices and emits
+//! [`ProximityEvent`]s when a device comes near (`Enter`) or goes away (`Exit`). Separate enter and
+//! exit thresholds (hysteresis) plus a dwell time stop a device on the edge from flapping.
+use crate::le::report::ReportInfo;
+use crate::{BTAddress, RSSI};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Smoothed RSSI is kept in 1/16 dBm.
+const RSSI_SCALE: i32 = 16;
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ProximityConfig {
+    /// Smoothed RSSI must reach this to count as near.
+    pub enter: RSSI,
+    /// Smoothed RSSI must drop below this to count as away. Should be less than `enter`.
+    pub exit: RSSI,
+    /// How long the smoothed RSSI must stay past a threshold before the event is emitted.
+    pub dwell: Duration,
+    /// Devices not seen for this long exit regardless of their last RSSI.
+    pub timeout: Duration,
+    /// Exponential smoothing factor. Each sample moves the average `1/smoothing` of the way. `1`
+    /// disables smoothing.
+    pub smoothing: u8,
+}
+impl ProximityConfig {
+    pub const DEFAULT: ProximityConfig = ProximityConfig {
+        enter: RSSI(-60),
+        exit: RSSI(-70),
+        dwell: Duration::from_secs(2),
+        timeout: Duration::from_secs(10),
+        smoothing: 4,
+    };
+}
+impl Default for ProximityConfig {
+    fn default() -> Self {
+        ProximityConfig::DEFAULT
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum ProximityEventKind {
+    Enter,
+    Exit,
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ProximityEvent {
+    pub address: BTAddress,
+    pub kind: ProximityEventKind,
+    /// Smoothed RSSI when the event fired.
+    pub rssi: RSSI,
+}
+#[derive(Copy, Clone, Debug)]
+struct TrackedDevice {
+    address: BTAddress,
+    /// Smoothed RSSI in 1/16 dBm. `None` until the first sample.
+    smoothed: Option<i32>,
+    is_near: bool,
+    /// When the smoothed RSSI first crossed the threshold for the opposite state.
+    crossed_at: Option<Duration>,
+    last_seen: Duration,
+}
+impl TrackedDevice {
+    fn rssi(&self) -> RSSI {
+        let dbm = self
+            .smoothed
+            .unwrap_or(i32::from(RSSI::MIN_RSSI_I8) * RSSI_SCALE)
+            / RSSI_SCALE;
+        RSSI::new(
+            dbm.max(RSSI::MIN_RSSI_I8.into())
+                .min(RSSI::MAX_RSSI_I8.into()) as i8,
+        )
+    }
+    fn event(&self, kind: ProximityEventKind) -> ProximityEvent {
+        ProximityEvent {
+            address: self.address,
+            kind,
+            rssi: self.rssi(),
+        }
+    }
+}
+/// Tracks devices and emits Enter/Exit events from their RSSI. Time is passed in by the caller
+/// as a monotonic `Duration` (since any fixed point) so no clock is needed.
+#[derive(Clone, Debug)]
+pub struct ProximityMonitor {
+    config: ProximityConfig,
+    devices: Vec<TrackedDevice>,
+}
+impl ProximityMonitor {
+    pub fn new(config: ProximityConfig) -> ProximityMonitor {
+        ProximityMonitor {
+            config,
+            devices: Vec::new(),
+        }
+    }
+    pub fn config(&self) -> &ProximityConfig {
+        &self.config
+    }
+    /// Start tracking `address`. Devices start away.
+    pub fn track(&mut self, address: BTAddress, now: Duration) {
+        if !self.is_tracked(address) {
+            self.devices.push(TrackedDevice {
+                address,
+                smoothed: None,
+                is_near: false,
+                crossed_at: None,
+                last_seen: now,
+            });
+        }
+    }
+    pub fn untrack(&mut self, address: BTAddress) {
+        self.devices.retain(|d| d.address != address);
+    }
+    pub fn is_tracked(&self, address: BTAddress) -> bool {
+        self.devices.iter().any(|d| d.address == address)
+    }
+    /// Returns if `address` is tracked and currently near.
+    pub fn is_near(&self, address: BTAddress) -> bool {
+        self.devices
+            .iter()
+            .any(|d| d.address == address && d.is_near)
+    }
+    /// Smoothed RSSI of `address` (`None` if it isn't tracked or hasn't been seen yet).
+    pub fn smoothed_rssi(&self, address: BTAddress) -> Option<RSSI> {
+        self.devices
+            .iter()
+            .find(|d| d.address == address && d.smoothed.is_some())
+            .map(TrackedDevice::rssi)
+    }
+    /// Add an RSSI sample for `address`. Samples for untracked devices are ignored.
+    pub fn on_rssi(
+        &mut self,
+        address: BTAddress,
+        rssi: RSSI,
+        now: Duration,
+    ) -> Option<ProximityEvent> {
+        let config = self.config;
+        let device = self.devices.iter_mut().find(|d| d.address == address)?;
+        let sample = i32::from(i8::from(rssi)) * RSSI_SCALE;
+        let smoothed = match device.smoothed {
+            Some(smoothed) => smoothed + (sample - smoothed) / i32::from(config.smoothing.max(1)),
+            None => sample,
+        };
+        device.smoothed = Some(smoothed);
+        device.last_seen = now;
+        let crossed = if device.is_near {
+            smoothed < i32::from(i8::from(config.exit)) * RSSI_SCALE
+        } else {
+            smoothed >= i32::from(i8::from(config.enter)) * RSSI_SCALE
+        };
+        if !crossed {
+            device.crossed_at = None;
+            return None;
+        }
+        let crossed_at = *device.crossed_at.get_or_insert(now);
+        if now.checked_sub(crossed_at).unwrap_or_default() < config.dwell {
+            return None;
+        }
+        device.crossed_at = None;
+        device.is_near = !device.is_near;
+        Some(device.event(if device.is_near {
+            ProximityEventKind::Enter
+        } else {
+            ProximityEventKind::Exit
+        }))
+    }
+    /// [`ProximityMonitor::on_rssi`] for an advertising report. Reports without an RSSI are ignored.
+    pub fn on_report<T: AsRef<[u8]>>(
+        &mut self,
+        report: &ReportInfo<T>,
+        now: Duration,
+    ) -> Option<ProximityEvent> {
+        self.on_rssi(report.address, report.rssi?, now)
+    }
+    /// Exit near devices that haven't been seen for `config.timeout`. Should be called
+    /// periodically because missing devices don't produce reports.
+    pub fn check_timeouts(&mut self, now: Duration) -> Vec<ProximityEvent> {
+        let timeout = self.config.timeout;
+        let mut events = Vec::new();
+        for device in self.devices.iter_mut() {
+            if device.is_near && now.checked_sub(device.last_seen).unwrap_or_default() >= timeout {
+                device.is_near = false;
+                device.crossed_at = None;
+                events.push(device.event(ProximityEventKind::Exit));
+                device.smoothed = None;
+            }
+        }
+        events
+    }
+}
+impl Default for ProximityMonitor {
+    fn default() -> Self {
+        ProximityMonitor::new(ProximityConfig::default())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_hysteresis() {
+        let address = BTAddress([1, 2, 3, 4, 5, 6]);
+        let mut monitor = ProximityMonitor::new(ProximityConfig {
+            smoothing: 1,
+            ..ProximityConfig::DEFAULT
+        });
+        let s = Duration::from_secs;
+        assert_eq!(monitor.on_rssi(address, RSSI::new(-50), s(0)), None);
+        monitor.track(address, s(0));
+        assert_eq!(monitor.on_rssi(address, RSSI::new(-50), s(0)), None);
+        // Dwell time not reached yet.
+        assert_eq!(monitor.on_rssi(address, RSSI::new(-55), s(1)), None);
+        let enter = monitor.on_rssi(address, RSSI::new(-55), s(2)).unwrap();
+        assert_eq!(enter.kind, ProximityEventKind::Enter);
+        assert!(monitor.is_near(address));
+        // Between the thresholds so nothing changes.
+        assert_eq!(monitor.on_rssi(address, RSSI::new(-65), s(5)), None);
+        assert_eq!(monitor.on_rssi(address, RSSI::new(-75), s(6)), None);
+        // Back above exit resets the dwell timer.
+        assert_eq!(monitor.on_rssi(address, RSSI::new(-65), s(7)), None);
+        assert_eq!(monitor.on_rssi(address, RSSI::new(-75), s(8)), None);
+        let exit = monitor.on_rssi(address, RSSI::new(-75), s(10)).unwrap();
+        assert_eq!(exit.kind, ProximityEventKind::Exit);
+        assert_eq!(exit.rssi, RSSI::new(-75));
+        monitor.on_rssi(address, RSSI::new(-40), s(11));
+        monitor.on_rssi(address, RSSI::new(-40), s(13));
+        assert!(monitor.is_near(address));
+        assert!(monitor.check_timeouts(s(20)).is_empty());
+        assert_eq!(
+            monitor.check_timeouts(s(23))[0].kind,
+            ProximityEventKind::Exit
+        );
+    }
+}
@@ -1,8 +1,12 @@
+use crate::hci::le::advertise::TxPowerLevel;
 use crate::le::advertisement::{RawAdvertisement, StaticAdvBuffer};
+use crate::le::phy::Phy;
 use crate::ConversionError;
-use crate::{BTAddress, BT_ADDRESS_LEN, RSSI};
+use crate::{BTAddress, DeviceAddress, BT_ADDRESS_LEN, RSSI};
+use alloc::vec::Vec;
 use core::convert::TryFrom;
 use core::fmt::Formatter;
+use core::time::Duration;
 
 pub struct NumReports(u8);
 impl NumReports {
@@ -109,6 +113,114 @@ impl From<AddressType> for u8 {
         a as u8
     }
 }
+impl AddressType {
+    /// Whether the controller resolved the address to an identity address with its resolving
+    /// list.
+    pub fn is_resolved(self) -> bool {
+        matches!(
+            self,
+            AddressType::PublicIdentity | AddressType::RandomIdentity
+        )
+    }
+    pub fn device_address(self, address: BTAddress) -> DeviceAddress {
+        match self {
+            AddressType::PublicDevice | AddressType::PublicIdentity => {
+                DeviceAddress::public(address)
+            }
+            AddressType::RandomDevice | AddressType::RandomIdentity => {
+                DeviceAddress::random(address)
+            }
+        }
+    }
+}
+/// Advertising Set ID. Tells apart the advertising sets of one advertiser. Range `0x00-0x0F`.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct AdvertisingSID(u8);
+impl AdvertisingSID {
+    pub const BYTE_LEN: usize = 1;
+    pub const MAX_U8: u8 = 0x0F;
+    pub const MAX: AdvertisingSID = AdvertisingSID(Self::MAX_U8);
+    /// Creates a new `AdvertisingSID`.
+    /// # Panics
+    /// Panics if `sid > AdvertisingSID::MAX_U8`.
+    pub fn new(sid: u8) -> AdvertisingSID {
+        Self::new_checked(sid).expect("sid out of range")
+    }
+    pub fn new_checked(sid: u8) -> Option<AdvertisingSID> {
+        if sid <= Self::MAX_U8 {
+            Some(AdvertisingSID(sid))
+        } else {
+            None
+        }
+    }
+    /// Unpack a SID field where `0xFF` means no ADI field (legacy PDUs).
+    pub fn maybe_sid(value: u8) -> Result<Option<AdvertisingSID>, ConversionError> {
+        match value {
+            0xFF => Ok(None),
+            v => AdvertisingSID::try_from(v).map(Some),
+        }
+    }
+}
+impl From<AdvertisingSID> for u8 {
+    fn from(sid: AdvertisingSID) -> Self {
+        sid.0
+    }
+}
+impl TryFrom<u8> for AdvertisingSID {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        AdvertisingSID::new_checked(value).ok_or(ConversionError(()))
+    }
+}
+/// Identifies the local adapter a report was received on when scanning with more than one
+/// adapter. For HCI adapters this is the controller index (`hciN`).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct AdapterIndex(pub u16);
+impl core::fmt::Display for AdapterIndex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "hci{}", self.0)
+    }
+}
+/// Where and how a report was received. Observations on different PHYs (and adapters) have
+/// different path loss so locationing should weight them separately.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ReportOrigin {
+    /// Adapter that received the report or `None` if only one adapter is scanning.
+    pub adapter: Option<AdapterIndex>,
+    /// PHY of the advertising channel PDU.
+    pub primary_phy: Phy,
+    /// PHY of the auxiliary PDUs or `None` if there weren't any (legacy advertising).
+    pub secondary_phy: Option<Phy>,
+    /// Advertising Set ID or `None` for legacy advertising.
+    pub sid: Option<AdvertisingSID>,
+}
+impl ReportOrigin {
+    /// Legacy advertising report from the only adapter.
+    pub const LEGACY: ReportOrigin = ReportOrigin {
+        adapter: None,
+        primary_phy: Phy::LE1M,
+        secondary_phy: None,
+        sid: None,
+    };
+    pub const fn with_adapter(self, adapter: AdapterIndex) -> ReportOrigin {
+        ReportOrigin {
+            adapter: Some(adapter),
+            primary_phy: self.primary_phy,
+            secondary_phy: self.secondary_phy,
+            sid: self.sid,
+        }
+    }
+    /// PHY the advertising data was received on (the secondary PHY if there is one).
+    pub fn data_phy(&self) -> Phy {
+        self.secondary_phy.unwrap_or(self.primary_phy)
+    }
+}
+impl Default for ReportOrigin {
+    fn default() -> Self {
+        Self::LEGACY
+    }
+}
 /// BLE Advertising report from scanning for advertisements that contains advertisement type [`EventType`],
 /// address type [`AddressType`], bluetooth address [`BTAddress`], data (0-31 bytes) and
 /// maybe (`Option`) RSSI [`RSSI`].
@@ -128,6 +240,8 @@ pub struct ReportInfo<T = StaticAdvBuffer> {
     pub data: RawAdvertisement<T>,
     /// RSSI (-127dBm to +20dBm) or `None` if RSSI readings are unsupported by the adapter.
     pub rssi: Option<RSSI>,
+    /// Adapter, PHYs and SID the report was received with. Not part of the HCI report.
+    pub origin: ReportOrigin,
 }
 impl<T: AsRef<[u8]>> core::fmt::Debug for ReportInfo<T> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -137,6 +251,7 @@ impl<T: AsRef<[u8]>> core::fmt::Debug for ReportInfo<T> {
             .field("address", &self.address)
             .field("rssi", &self.rssi)
             .field("data", &self.data.as_ref())
+            .field("origin", &self.origin)
             .finish()
     }
 }
@@ -148,10 +263,15 @@ impl<T: AsRef<[u8]> + Default> Default for ReportInfo<T> {
             address: BTAddress::ZEROED,
             data: RawAdvertisement(T::default()),
             rssi: None,
+            origin: ReportOrigin::LEGACY,
         }
     }
 }
 impl<T: AsRef<[u8]>> ReportInfo<T> {
+    /// Advertiser address with its type (identity address if the controller resolved it).
+    pub fn device_address(&self) -> DeviceAddress {
+        self.address_type.device_address(self.address)
+    }
     pub fn byte_len(&self) -> usize {
         // event_type (1) + address_type (1) + address (6) + data (data.len()) + rssi (1)
         1 + 1 + BT_ADDRESS_LEN + self.data.as_ref().len() + 1
@@ -163,6 +283,346 @@ impl<T: AsRef<[u8]>> ReportInfo<T> {
             address: self.address,
             data: RawAdvertisement(self.data.as_ref()),
             rssi: self.rssi,
+            origin: self.origin,
+        }
+    }
+    /// Set the adapter the report was received on (for merging reports from several adapters).
+    pub fn with_adapter(mut self, adapter: AdapterIndex) -> Self {
+        self.origin = self.origin.with_adapter(adapter);
+        self
+    }
+}
+/// Status of the data in an extended advertising report.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum DataStatus {
+    Complete = 0b00,
+    /// More data is coming in later reports.
+    Incomplete = 0b01,
+    /// The controller stopped receiving the data (missed an auxiliary PDU or ran out of room).
+    Truncated = 0b10,
+}
+impl From<DataStatus> for u8 {
+    fn from(s: DataStatus) -> Self {
+        s as u8
+    }
+}
+impl TryFrom<u8> for DataStatus {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(DataStatus::Complete),
+            0b01 => Ok(DataStatus::Incomplete),
+            0b10 => Ok(DataStatus::Truncated),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+/// Event type bit field of an extended advertising report.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ExtendedEventType(u16);
+impl ExtendedEventType {
+    pub const BYTE_LEN: usize = 2;
+    pub const CONNECTABLE: u16 = 1 << 0;
+    pub const SCANNABLE: u16 = 1 << 1;
+    pub const DIRECTED: u16 = 1 << 2;
+    pub const SCAN_RESPONSE: u16 = 1 << 3;
+    pub const LEGACY: u16 = 1 << 4;
+    pub const DATA_STATUS_SHIFT: u16 = 5;
+    pub const DATA_STATUS_MASK: u16 = 0b11 << Self::DATA_STATUS_SHIFT;
+    /// Mask of the defined bits. The rest are reserved.
+    pub const MASK: u16 = 0x7F;
+    pub fn new(bits: u16) -> ExtendedEventType {
+        Self::new_checked(bits).expect("invalid extended event type")
+    }
+    /// Returns `None` if reserved bits are set or the data status is reserved.
+    pub fn new_checked(bits: u16) -> Option<ExtendedEventType> {
+        let data_status = ((bits & Self::DATA_STATUS_MASK) >> Self::DATA_STATUS_SHIFT) as u8;
+        if bits & !Self::MASK != 0 || DataStatus::try_from(data_status).is_err() {
+            None
+        } else {
+            Some(ExtendedEventType(bits))
+        }
+    }
+    pub fn is_connectable(self) -> bool {
+        self.0 & Self::CONNECTABLE != 0
+    }
+    pub fn is_scannable(self) -> bool {
+        self.0 & Self::SCANNABLE != 0
+    }
+    pub fn is_directed(self) -> bool {
+        self.0 & Self::DIRECTED != 0
+    }
+    pub fn is_scan_response(self) -> bool {
+        self.0 & Self::SCAN_RESPONSE != 0
+    }
+    /// Whether the report is from a legacy advertising PDU. Legacy reports are never fragmented.
+    pub fn is_legacy(self) -> bool {
+        self.0 & Self::LEGACY != 0
+    }
+    pub fn data_status(self) -> DataStatus {
+        DataStatus::try_from(((self.0 & Self::DATA_STATUS_MASK) >> Self::DATA_STATUS_SHIFT) as u8)
+            .expect("checked in new_checked")
+    }
+    pub fn with_data_status(self, status: DataStatus) -> ExtendedEventType {
+        ExtendedEventType(
+            (self.0 & !Self::DATA_STATUS_MASK)
+                | (u16::from(u8::from(status)) << Self::DATA_STATUS_SHIFT),
+        )
+    }
+}
+impl From<ExtendedEventType> for u16 {
+    fn from(e: ExtendedEventType) -> Self {
+        e.0
+    }
+}
+impl TryFrom<u16> for ExtendedEventType {
+    type Error = ConversionError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        ExtendedEventType::new_checked(value).ok_or(ConversionError(()))
+    }
+}
+/// One report of an LE Extended Advertising Report event. Extended advertising data (up to 1650
+/// bytes) can be split over several reports. See [`ExtendedReportAssembler`] to join them back up.
+#[derive(Copy, Clone)]
+pub struct ExtendedReportInfo<T> {
+    pub event_type: ExtendedEventType,
+    /// Bluetooth Address type or `None` for anonymous advertisements.
+    pub address_type: Option<AddressType>,
+    pub address: BTAddress,
+    pub primary_phy: Phy,
+    /// `None` if there were no auxiliary packets.
+    pub secondary_phy: Option<Phy>,
+    pub sid: Option<AdvertisingSID>,
+    pub tx_power: Option<TxPowerLevel>,
+    pub rssi: Option<RSSI>,
+    /// Periodic advertising interval (1.25 ms units) or `None` if there isn't periodic
+    /// advertising.
+    pub periodic_advertising_interval: Option<u16>,
+    /// Address type of the directed advertising target. `0xFE` is an unresolved RPA.
+    pub direct_address_type: u8,
+    pub direct_address: BTAddress,
+    pub data: RawAdvertisement<T>,
+}
+impl<T: AsRef<[u8]>> core::fmt::Debug for ExtendedReportInfo<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ExtendedReportInfo")
+            .field("event_type", &self.event_type)
+            .field("address_type", &self.address_type)
+            .field("address", &self.address)
+            .field("primary_phy", &self.primary_phy)
+            .field("secondary_phy", &self.secondary_phy)
+            .field("sid", &self.sid)
+            .field("tx_power", &self.tx_power)
+            .field("rssi", &self.rssi)
+            .field(
+                "periodic_advertising_interval",
+                &self.periodic_advertising_interval,
+            )
+            .field("direct_address_type", &self.direct_address_type)
+            .field("direct_address", &self.direct_address)
+            .field("data", &self.data.as_ref())
+            .finish()
+    }
+}
+impl<T: Default> Default for ExtendedReportInfo<T> {
+    fn default() -> Self {
+        Self {
+            event_type: ExtendedEventType::default(),
+            address_type: None,
+            address: BTAddress::ZEROED,
+            primary_phy: Phy::DEFAULT,
+            secondary_phy: None,
+            sid: None,
+            tx_power: None,
+            rssi: None,
+            periodic_advertising_interval: None,
+            direct_address_type: 0,
+            direct_address: BTAddress::ZEROED,
+            data: RawAdvertisement(T::default()),
+        }
+    }
+}
+impl<T: AsRef<[u8]>> ExtendedReportInfo<T> {
+    /// event_type (2) + address_type (1) + address (6) + primary_phy (1) + secondary_phy (1) +
+    /// sid (1) + tx_power (1) + rssi (1) + periodic_advertising_interval (2) +
+    /// direct_address_type (1) + direct_address (6) + data_len (1)
+    pub const HEADER_LEN: usize =
+        2 + 1 + BT_ADDRESS_LEN + 1 + 1 + 1 + 1 + 1 + 2 + 1 + BT_ADDRESS_LEN + 1;
+    pub fn byte_len(&self) -> usize {
+        Self::HEADER_LEN + self.data.as_ref().len()
+    }
+    /// Advertiser address with its type or `None` for anonymous advertisements.
+    pub fn device_address(&self) -> Option<DeviceAddress> {
+        self.address_type.map(|t| t.device_address(self.address))
+    }
+    pub fn origin(&self) -> ReportOrigin {
+        ReportOrigin {
+            adapter: None,
+            primary_phy: self.primary_phy,
+            secondary_phy: self.secondary_phy,
+            sid: self.sid,
+        }
+    }
+    pub fn as_ref(&self) -> ExtendedReportInfo<&[u8]> {
+        self.with_data(self.data.as_ref())
+    }
+    /// Copy of the report with different data.
+    pub fn with_data<U>(&self, data: U) -> ExtendedReportInfo<U> {
+        ExtendedReportInfo {
+            event_type: self.event_type,
+            address_type: self.address_type,
+            address: self.address,
+            primary_phy: self.primary_phy,
+            secondary_phy: self.secondary_phy,
+            sid: self.sid,
+            tx_power: self.tx_power,
+            rssi: self.rssi,
+            periodic_advertising_interval: self.periodic_advertising_interval,
+            direct_address_type: self.direct_address_type,
+            direct_address: self.direct_address,
+            data: RawAdvertisement(data),
         }
     }
+    /// Whether `other` is (part of) the same advertisement as `self`.
+    fn same_advertisement<U>(&self, other: &ExtendedReportInfo<U>) -> bool {
+        self.address_type == other.address_type
+            && self.address == other.address
+            && self.sid == other.sid
+            && self.event_type.is_scan_response() == other.event_type.is_scan_response()
+    }
+}
+struct PendingReport {
+    report: ExtendedReportInfo<Vec<u8>>,
+    started: Duration,
+}
+/// Joins extended advertising reports split over several events (data status
+/// [`DataStatus::Incomplete`]) back together. Fragments are matched by address, SID and if they
+/// are a scan response. Only complete advertisements are returned. Advertisements that stop
+/// arriving (or grow too large) are returned with [`DataStatus::Truncated`].
+pub struct ExtendedReportAssembler {
+    pending: Vec<PendingReport>,
+    abandoned: Vec<ExtendedReportInfo<Vec<u8>>>,
+    timeout: Duration,
+    max_data_len: usize,
+}
+impl ExtendedReportAssembler {
+    /// Longest extended advertising data.
+    pub const MAX_DATA_LEN: usize = 1650;
+    /// Advertisements reassembled at once. The oldest is abandoned when more start.
+    pub const MAX_PENDING: usize = 16;
+    /// Auxiliary packets are sent within a few ms of each other so anything waiting longer than
+    /// this has lost a fragment.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+    pub fn new(timeout: Duration) -> ExtendedReportAssembler {
+        ExtendedReportAssembler {
+            pending: Vec::new(),
+            abandoned: Vec::new(),
+            timeout,
+            max_data_len: Self::MAX_DATA_LEN,
+        }
+    }
+    /// Advertisements being reassembled.
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+    /// Add a report received at `now`. Returns the advertisement if `report` completed one.
+    pub fn push<T: AsRef<[u8]>>(
+        &mut self,
+        report: &ExtendedReportInfo<T>,
+        now: Duration,
+    ) -> Option<ExtendedReportInfo<Vec<u8>>> {
+        let data = report.data.as_ref();
+        let index = self
+            .pending
+            .iter()
+            .position(|p| p.report.same_advertisement(report));
+        let status = report.event_type.data_status();
+        let mut assembled = match index {
+            Some(index) => {
+                if status == DataStatus::Incomplete && self.has_room(index, data.len()) {
+                    self.pending[index].report.data.0.extend_from_slice(data);
+                    return None;
+                }
+                self.pending.swap_remove(index).report
+            }
+            None if status == DataStatus::Incomplete && !report.event_type.is_legacy() => {
+                if self.pending.len() >= Self::MAX_PENDING {
+                    self.abandon_oldest();
+                }
+                self.pending.push(PendingReport {
+                    report: report.with_data(Vec::new()),
+                    started: now,
+                });
+                let last = self.pending.len() - 1;
+                if self.has_room(last, data.len()) {
+                    self.pending[last].report.data.0.extend_from_slice(data);
+                    return None;
+                }
+                self.pending.swap_remove(last).report
+            }
+            None => return Some(report.with_data(data.to_vec())),
+        };
+        let room = self.max_data_len - assembled.data.0.len();
+        let status = if data.len() > room {
+            DataStatus::Truncated
+        } else {
+            status
+        };
+        assembled
+            .data
+            .0
+            .extend_from_slice(&data[..data.len().min(room)]);
+        // The final fragment has the latest RSSI.
+        assembled.rssi = report.rssi;
+        assembled.event_type = assembled.event_type.with_data_status(match status {
+            DataStatus::Incomplete => DataStatus::Truncated,
+            s => s,
+        });
+        Some(assembled)
+    }
+    /// Returns advertisements that timed out (or were abandoned to make room) as
+    /// [`DataStatus::Truncated`].
+    pub fn expire(&mut self, now: Duration) -> Vec<ExtendedReportInfo<Vec<u8>>> {
+        let mut out = core::mem::take(&mut self.abandoned);
+        let timeout = self.timeout;
+        let mut i = 0;
+        while i < self.pending.len() {
+            if now.checked_sub(self.pending[i].started).unwrap_or_default() >= timeout {
+                out.push(Self::truncate(self.pending.swap_remove(i).report));
+            } else {
+                i += 1;
+            }
+        }
+        out
+    }
+    /// Drop every partial advertisement (when scanning stops).
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.abandoned.clear();
+    }
+    fn has_room(&self, index: usize, len: usize) -> bool {
+        self.pending[index].report.data.0.len() + len <= self.max_data_len
+    }
+    fn abandon_oldest(&mut self) {
+        if let Some((index, _)) = self
+            .pending
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, p)| p.started)
+        {
+            let report = self.pending.swap_remove(index).report;
+            self.abandoned.push(Self::truncate(report));
+        }
+    }
+    fn truncate(mut report: ExtendedReportInfo<Vec<u8>>) -> ExtendedReportInfo<Vec<u8>> {
+        report.event_type = report.event_type.with_data_status(DataStatus::Truncated);
+        report
+    }
+}
+impl Default for ExtendedReportAssembler {
+    fn default() -> Self {
+        ExtendedReportAssembler::new(Self::DEFAULT_TIMEOUT)
+    }
 }
@@ -53,6 +53,13 @@ impl ScanInterval {
         );
         ScanInterval(interval)
     }
+    pub fn new_checked(interval: u16) -> Option<ScanInterval> {
+        if interval >= INTERVAL_MIN && interval <= INTERVAL_MAX {
+            Some(ScanInterval(interval))
+        } else {
+            None
+        }
+    }
     pub fn as_microseconds(self) -> u32 {
         u32::from(u16::from(self)) * 625
     }
@@ -83,6 +90,13 @@ impl ScanWindow {
         );
         ScanWindow(window)
     }
+    pub fn new_checked(window: u16) -> Option<ScanWindow> {
+        if window >= INTERVAL_MIN && window <= INTERVAL_MAX {
+            Some(ScanWindow(window))
+        } else {
+            None
+        }
+    }
     pub fn as_microseconds(self) -> u32 {
         u32::from(u16::from(self)) * 625
     }
@@ -0,0 +1,155 @@
+//! Host side limit on scan requests. An active scanner sends a scan request for every scannable
+//! advertisement it hears, so in dense deployments (many gateways, many tags) advertisers spend
+//! their listen windows answering the same scanners over and over and the advertising channels
+//! congest. Backends where the host decides when to send scan requests (host driven active
+//! scanning, simulated radios) ask a [`ScanRequestLimiter`] before each one so every advertiser
+//! gets at most [`ScanRequestLimit::max_requests`] per [`ScanRequestLimit::window`].
+//!
+//! HCI controllers send scan requests themselves. There, count the scan responses with
+//! [`ScanRequestLimiter::allow`] and fall back to passive scanning while most advertisers are
+//! over the limit.
+use crate::DeviceAddress;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ScanRequestLimit {
+    /// Scan requests allowed per advertiser per window. `0` never allows one.
+    pub max_requests: u16,
+    pub window: Duration,
+}
+impl ScanRequestLimit {
+    /// One scan request per advertiser every 10 seconds. Scan response data rarely changes
+    /// faster than that.
+    pub const DEFAULT: ScanRequestLimit = ScanRequestLimit {
+        max_requests: 1,
+        window: Duration::from_secs(10),
+    };
+}
+impl Default for ScanRequestLimit {
+    fn default() -> Self {
+        ScanRequestLimit::DEFAULT
+    }
+}
+/// Requests sent to one advertiser in its current window.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+struct RequestWindow {
+    device: DeviceAddress,
+    start: Duration,
+    requests: u16,
+}
+/// Fixed window scan request counter per advertiser. Tracks at most `max_devices` advertisers,
+/// forgetting the one requested least recently when full (its limit starts over if it's
+/// requested again).
+#[derive(Clone, Debug)]
+pub struct ScanRequestLimiter {
+    limit: ScanRequestLimit,
+    /// Most recently requested last.
+    windows: Vec<RequestWindow>,
+    max_devices: usize,
+    denied: u64,
+}
+impl ScanRequestLimiter {
+    pub const DEFAULT_MAX_DEVICES: usize = 256;
+    pub fn new(limit: ScanRequestLimit, max_devices: usize) -> ScanRequestLimiter {
+        ScanRequestLimiter {
+            limit,
+            windows: Vec::new(),
+            max_devices,
+            denied: 0,
+        }
+    }
+    pub fn limit(&self) -> ScanRequestLimit {
+        self.limit
+    }
+    /// Change the limit. Windows already running keep their start.
+    pub fn set_limit(&mut self, limit: ScanRequestLimit) {
+        self.limit = limit;
+    }
+    fn window(&self, device: DeviceAddress, now: Duration) -> Option<&RequestWindow> {
+        self.windows
+            .iter()
+            .find(|w| w.device == device)
+            .filter(|w| now < w.start + self.limit.window)
+    }
+    /// Whether a scan request to `device` would be allowed at `now` (without counting one).
+    pub fn is_allowed(&self, device: DeviceAddress, now: Duration) -> bool {
+        let sent = self.window(device, now).map_or(0, |w| w.requests);
+        sent < self.limit.max_requests
+    }
+    /// Count a scan request to `device` at `now` if it's allowed. Returns `false` (and counts
+    /// it as denied) if `device` already got its requests this window.
+    pub fn allow(&mut self, device: DeviceAddress, now: Duration) -> bool {
+        if !self.is_allowed(device, now) {
+            self.denied += 1;
+            return false;
+        }
+        let window = match self.window(device, now) {
+            Some(window) => RequestWindow {
+                requests: window.requests + 1,
+                ..*window
+            },
+            None => RequestWindow {
+                device,
+                start: now,
+                requests: 1,
+            },
+        };
+        self.windows.retain(|w| w.device != device);
+        if self.max_devices > 0 && self.windows.len() >= self.max_devices {
+            self.windows.remove(0);
+        }
+        self.windows.push(window);
+        true
+    }
+    /// Scan requests denied so far.
+    pub fn denied(&self) -> u64 {
+        self.denied
+    }
+    /// Number of advertisers still limited at `now`.
+    pub fn limited(&self, now: Duration) -> usize {
+        self.windows
+            .iter()
+            .filter(|w| !self.is_allowed(w.device, now))
+            .count()
+    }
+    pub fn remove(&mut self, device: DeviceAddress) {
+        self.windows.retain(|w| w.device != device);
+    }
+    pub fn clear(&mut self) {
+        self.windows.clear();
+    }
+}
+impl Default for ScanRequestLimiter {
+    fn default() -> Self {
+        ScanRequestLimiter::new(ScanRequestLimit::DEFAULT, Self::DEFAULT_MAX_DEVICES)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BTAddress;
+    #[test]
+    fn test_scan_request_limiter() {
+        let a = DeviceAddress::public(BTAddress([1; 6]));
+        let b = DeviceAddress::public(BTAddress([2; 6]));
+        let c = DeviceAddress::public(BTAddress([3; 6]));
+        let limit = ScanRequestLimit {
+            max_requests: 2,
+            window: Duration::from_secs(10),
+        };
+        let mut limiter = ScanRequestLimiter::new(limit, 2);
+        let at = Duration::from_secs;
+        assert!(limiter.allow(a, at(0)) && limiter.allow(a, at(1)));
+        assert!(!limiter.allow(a, at(9)));
+        assert!(limiter.allow(b, at(9)));
+        assert_eq!(limiter.limited(at(9)), 1);
+        // The window is over.
+        assert!(limiter.is_allowed(a, at(10)) && limiter.allow(a, at(10)));
+        assert_eq!(limiter.denied(), 1);
+        // `b` was requested least recently and is forgotten for `c`.
+        assert!(limiter.allow(c, at(11)));
+        assert!(limiter.allow(b, at(11)) && limiter.allow(b, at(11)));
+        assert!(!limiter.allow(b, at(12)));
+    }
+}
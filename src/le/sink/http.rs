@@ -0,0 +1,151 @@
+//! POST report batches to an HTTP endpoint (plain HTTP/1.1, one connection per batch).
+use crate::error::IOError;
+use crate::le::sink::ReportSink;
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Sends each batch as `application/json` to `http://host:port/path`.
+pub struct HttpSink {
+    host: String,
+    path: String,
+    timeout: Duration,
+    /// Status code of the last response.
+    last_status: Option<u16>,
+}
+impl HttpSink {
+    /// `host` is `host:port`. `path` starts with `/`.
+    pub fn new(host: &str, path: &str) -> HttpSink {
+        HttpSink {
+            host: String::from(host),
+            path: String::from(path),
+            timeout: Duration::from_secs(5),
+            last_status: None,
+        }
+    }
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+    pub fn last_status(&self) -> Option<u16> {
+        self.last_status
+    }
+    /// Request head for a batch of `len` bytes.
+    pub fn request_head(&self, len: usize) -> String {
+        alloc::format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            self.host,
+            len
+        )
+    }
+}
+/// Longest status line [`read_status_line`] will buffer.
+pub const MAX_STATUS_LINE_LEN: usize = 1024;
+/// Read from `reader` until the buffer holds a `\r\n`, the peer closes or
+/// [`MAX_STATUS_LINE_LEN`] bytes arrived. May return bytes past the status line.
+pub fn read_status_line<R: Read>(reader: &mut R) -> Result<Vec<u8>, IOError> {
+    let mut line = Vec::new();
+    let mut chunk = [0_u8; 64];
+    while line.len() < MAX_STATUS_LINE_LEN {
+        let len = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        // The CRLF may straddle two reads.
+        let search_from = line.len().saturating_sub(1);
+        line.extend_from_slice(&chunk[..len]);
+        if line[search_from..].windows(2).any(|w| w == b"\r\n") {
+            break;
+        }
+    }
+    Ok(line)
+}
+/// Status code from the first line of a HTTP response.
+pub fn parse_status(response: &[u8]) -> Option<u16> {
+    let line = response.split(|&b| b == b'\r').next()?;
+    let line = core::str::from_utf8(line).ok()?;
+    let mut parts = line.split(' ');
+    if !parts.next()?.starts_with("HTTP/1.") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+impl ReportSink for HttpSink {
+    /// Non 2xx responses are `IOError::Refused`.
+    fn send_batch(&mut self, batch: &[u8]) -> Result<(), IOError> {
+        let addr = self
+            .host
+            .to_socket_addrs()?
+            .next()
+            .ok_or(IOError::NotFound)?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.write_all(self.request_head(batch.len()).as_bytes())?;
+        stream.write_all(batch)?;
+        let response = read_status_line(&mut stream)?;
+        let status = parse_status(&response).ok_or(IOError::InvalidData)?;
+        self.last_status = Some(status);
+        if (200..300).contains(&status) {
+            Ok(())
+        } else {
+            Err(IOError::Refused)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    /// Hands out `data` at most `step` bytes per read.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = self.step.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+    #[test]
+    fn test_split_status_line() {
+        let response = b"HTTP/1.1 204 No Content\r\nServer: test\r\n\r\n";
+        for step in 1..response.len() {
+            let mut reader = Trickle {
+                data: response,
+                step,
+            };
+            let line = read_status_line(&mut reader).unwrap();
+            assert!(line.windows(2).any(|w| w == b"\r\n"), "step {}", step);
+            assert_eq!(parse_status(&line), Some(204), "step {}", step);
+        }
+    }
+    #[test]
+    fn test_status_line_eof() {
+        let mut reader = Trickle {
+            data: b"HTTP/1.0 503",
+            step: 5,
+        };
+        let line = read_status_line(&mut reader).unwrap();
+        assert_eq!(parse_status(&line), Some(503));
+        let mut reader = Trickle { data: b"", step: 5 };
+        assert_eq!(parse_status(&read_status_line(&mut reader).unwrap()), None);
+    }
+    #[test]
+    fn test_status_line_cap() {
+        let long = alloc::vec![b'a'; 4 * MAX_STATUS_LINE_LEN];
+        let mut reader = Trickle {
+            data: &long,
+            step: 64,
+        };
+        let line = read_status_line(&mut reader).unwrap();
+        assert_eq!(line.len(), MAX_STATUS_LINE_LEN);
+    }
+}
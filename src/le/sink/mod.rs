@@ -0,0 +1,221 @@
+//! Forward scan reports to other services. [`BatchingSink`] serializes reports as JSON and sends
+//! them in batches (every `max_reports` reports or `max_delay`, whichever comes first) to a
+//! [`ReportSink`], retrying failed batches.
+//!
+//! Sinks are behind features: `mqtt_sink` ([`mqtt::MqttSink`]) publishes batches to a MQTT topic
+//! and `http_sink` ([`http::HttpSink`]) POSTs them to an HTTP endpoint.
+use crate::bytes::HexSlice;
+use crate::error::IOError;
+use crate::le::report::ReportInfo;
+use alloc::string::String;
+use core::fmt::Write;
+use core::time::Duration;
+
+#[cfg(feature = "http_sink")]
+pub mod http;
+#[cfg(feature = "mqtt_sink")]
+pub mod mqtt;
+
+/// Somewhere to send batches of serialized reports.
+pub trait ReportSink {
+    /// Send one batch (a JSON array of reports).
+    fn send_batch(&mut self, batch: &[u8]) -> Result<(), IOError>;
+}
+/// Append `report` to `out` as a JSON object like
+/// `{"address":"01:02:03:04:05:06","address_type":"RandomDevice","event_type":"ADV_IND",
+/// "rssi":-60,"data":"020106"}`. `rssi` is `null` if unsupported.
+pub fn write_report_json<T: AsRef<[u8]>>(report: &ReportInfo<T>, out: &mut String) {
+    let _ = write!(
+        out,
+        "{{\"address\":\"{}\",\"address_type\":\"{:?}\",\"event_type\":\"{}\",\"rssi\":",
+        report.address, report.address_type, report.event_type
+    );
+    let _ = match report.rssi {
+        Some(rssi) => write!(out, "{}", i8::from(rssi)),
+        None => write!(out, "null"),
+    };
+    if let Some(adapter) = report.origin.adapter {
+        let _ = write!(out, ",\"adapter\":\"{}\"", adapter);
+    }
+    let _ = write!(out, ",\"data\":\"{}\"}}", HexSlice(report.data.as_ref()));
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct BatchConfig {
+    /// Send once this many reports are waiting.
+    pub max_reports: usize,
+    /// Send once the oldest waiting report is this old.
+    pub max_delay: Duration,
+    /// Times a failed batch is retried before it's dropped.
+    pub max_retries: u8,
+}
+impl BatchConfig {
+    pub const DEFAULT: BatchConfig = BatchConfig {
+        max_reports: 50,
+        max_delay: Duration::from_secs(1),
+        max_retries: 3,
+    };
+}
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig::DEFAULT
+    }
+}
+/// Batches reports for a [`ReportSink`]. Time is passed in by the caller so reports only go out
+/// when [`BatchingSink::push`] or [`BatchingSink::poll`] is called.
+pub struct BatchingSink<S: ReportSink> {
+    sink: S,
+    config: BatchConfig,
+    batch: String,
+    len: usize,
+    oldest: Option<Duration>,
+    failures: u8,
+    dropped_reports: u64,
+}
+impl<S: ReportSink> BatchingSink<S> {
+    pub fn new(sink: S, config: BatchConfig) -> BatchingSink<S> {
+        BatchingSink {
+            sink,
+            config,
+            batch: String::new(),
+            len: 0,
+            oldest: None,
+            failures: 0,
+            dropped_reports: 0,
+        }
+    }
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+    /// Reports waiting to be sent.
+    pub fn pending(&self) -> usize {
+        self.len
+    }
+    /// Reports dropped after their batch ran out of retries.
+    pub fn dropped_reports(&self) -> u64 {
+        self.dropped_reports
+    }
+    /// Add a report received at `now`. Sends the batch if it's full. Errors are from sending the
+    /// batch (the report is still queued).
+    pub fn push<T: AsRef<[u8]>>(
+        &mut self,
+        report: &ReportInfo<T>,
+        now: Duration,
+    ) -> Result<(), IOError> {
+        self.batch.push(if self.len == 0 { '[' } else { ',' });
+        write_report_json(report, &mut self.batch);
+        self.len += 1;
+        self.oldest.get_or_insert(now);
+        self.poll(now)
+    }
+    /// Send the batch if it's full or old enough.
+    pub fn poll(&mut self, now: Duration) -> Result<(), IOError> {
+        let is_due = match self.oldest {
+            Some(oldest) => {
+                self.len >= self.config.max_reports
+                    || now.checked_sub(oldest).unwrap_or_default() >= self.config.max_delay
+            }
+            None => false,
+        };
+        if is_due {
+            self.flush()
+        } else {
+            Ok(())
+        }
+    }
+    /// Send any waiting reports now. A failed batch is kept (and added to) until it has failed
+    /// `max_retries` times, then it's dropped.
+    pub fn flush(&mut self) -> Result<(), IOError> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        self.batch.push(']');
+        let result = self.sink.send_batch(self.batch.as_bytes());
+        self.batch.pop();
+        match result {
+            Ok(()) => self.clear(),
+            Err(e) => {
+                self.failures += 1;
+                if self.failures > self.config.max_retries {
+                    self.dropped_reports += self.len as u64;
+                    self.clear();
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+    fn clear(&mut self) {
+        self.batch.clear();
+        self.len = 0;
+        self.oldest = None;
+        self.failures = 0;
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes::Storage;
+    use crate::le::advertisement::{RawAdvertisement, StaticAdvBuffer};
+    use crate::le::report::{AddressType, EventType, ReportOrigin};
+    use crate::{BTAddress, RSSI};
+    use alloc::vec::Vec;
+    struct Collect {
+        batches: Vec<String>,
+        fail: bool,
+    }
+    impl ReportSink for Collect {
+        fn send_batch(&mut self, batch: &[u8]) -> Result<(), IOError> {
+            if self.fail {
+                return Err(IOError::TimedOut);
+            }
+            self.batches
+                .push(String::from(core::str::from_utf8(batch).unwrap()));
+            Ok(())
+        }
+    }
+    #[test]
+    fn test_batching() {
+        let report = ReportInfo {
+            event_type: EventType::AdvInd,
+            address_type: AddressType::RandomDevice,
+            address: BTAddress([1, 2, 3, 4, 5, 6]),
+            data: RawAdvertisement(StaticAdvBuffer::from_slice(&[0x02, 0x01, 0x06])),
+            rssi: Some(RSSI::new(-60)),
+            origin: ReportOrigin::LEGACY,
+        };
+        let config = BatchConfig {
+            max_reports: 2,
+            max_delay: Duration::from_millis(100),
+            max_retries: 1,
+        };
+        let sink = Collect {
+            batches: Vec::new(),
+            fail: false,
+        };
+        let mut batching = BatchingSink::new(sink, config);
+        let ms = Duration::from_millis;
+        batching.push(&report, ms(0)).unwrap();
+        batching.push(&report, ms(10)).unwrap();
+        batching.push(&report, ms(20)).unwrap();
+        batching.poll(ms(50)).unwrap();
+        assert_eq!(batching.sink().batches.len(), 1);
+        batching.poll(ms(120)).unwrap();
+        let batches = &batching.sink().batches;
+        assert_eq!(batches.len(), 2);
+        assert_eq!(
+            batches[1],
+            "[{\"address\":\"01:02:03:04:05:06\",\"address_type\":\"RandomDevice\",\
+             \"event_type\":\"ADV_IND\",\"rssi\":-60,\"data\":\"020106\"}]"
+        );
+        // Retried once then dropped.
+        batching.sink_mut().fail = true;
+        batching.push(&report, ms(200)).unwrap();
+        assert!(batching.push(&report, ms(210)).is_err());
+        assert!(batching.flush().is_err());
+        assert_eq!(batching.pending(), 0);
+        assert_eq!(batching.dropped_reports(), 2);
+    }
+}
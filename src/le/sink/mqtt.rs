@@ -0,0 +1,142 @@
+//! Publish report batches to a MQTT (3.1.1) broker. Only what a sink needs is implemented:
+//! CONNECT (clean session, no auth) and QoS 0 PUBLISH.
+use crate::error::IOError;
+use crate::le::sink::ReportSink;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+/// Largest MQTT remaining length (4 byte variable length integer).
+pub const MAX_REMAINING_LEN: usize = 268_435_455;
+fn write_remaining_len(out: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        if len == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+fn write_str(out: &mut Vec<u8>, s: &str) -> Result<(), IOError> {
+    let len = u16::try_from(s.len()).map_err(|_| IOError::InvalidArgument)?;
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+/// CONNECT packet with a clean session and `keep_alive` seconds.
+pub fn connect_packet(client_id: &str, keep_alive: u16) -> Result<Vec<u8>, IOError> {
+    let mut body = Vec::new();
+    write_str(&mut body, "MQTT")?;
+    // Protocol level 4 (3.1.1) and the clean session flag.
+    body.extend_from_slice(&[4, 0x02]);
+    body.extend_from_slice(&keep_alive.to_be_bytes());
+    write_str(&mut body, client_id)?;
+    let mut out = alloc::vec![CONNECT];
+    write_remaining_len(&mut out, body.len());
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+/// QoS 0 PUBLISH packet.
+pub fn publish_packet(topic: &str, payload: &[u8]) -> Result<Vec<u8>, IOError> {
+    let remaining = 2 + topic.len() + payload.len();
+    if remaining > MAX_REMAINING_LEN {
+        return Err(IOError::Overflow);
+    }
+    let mut out = alloc::vec![PUBLISH];
+    write_remaining_len(&mut out, remaining);
+    write_str(&mut out, topic)?;
+    out.extend_from_slice(payload);
+    Ok(out)
+}
+/// MQTT connection publishing every batch to one topic. Reconnects on the next batch after a
+/// failed write.
+pub struct MqttSink<S: Read + Write = TcpStream> {
+    stream: Option<S>,
+    connect: fn(&str) -> Result<S, IOError>,
+    broker: String,
+    client_id: String,
+    topic: String,
+    keep_alive: u16,
+}
+impl MqttSink<TcpStream> {
+    /// `broker` is `host:port`. Connects on the first batch.
+    pub fn new(broker: &str, client_id: &str, topic: &str) -> MqttSink<TcpStream> {
+        MqttSink::with_connector(broker, client_id, topic, |broker| {
+            let addr = broker.to_socket_addrs()?.next().ok_or(IOError::NotFound)?;
+            Ok(TcpStream::connect(addr)?)
+        })
+    }
+}
+impl<S: Read + Write> MqttSink<S> {
+    /// Use `connect` to open the byte stream to `broker` (TLS, proxies, etc).
+    pub fn with_connector(
+        broker: &str,
+        client_id: &str,
+        topic: &str,
+        connect: fn(&str) -> Result<S, IOError>,
+    ) -> MqttSink<S> {
+        MqttSink {
+            stream: None,
+            connect,
+            broker: String::from(broker),
+            client_id: String::from(client_id),
+            topic: String::from(topic),
+            keep_alive: 60,
+        }
+    }
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+    fn connected(&mut self) -> Result<&mut S, IOError> {
+        if self.stream.is_none() {
+            let mut stream = (self.connect)(&self.broker)?;
+            stream.write_all(&connect_packet(&self.client_id, self.keep_alive)?)?;
+            let mut connack = [0_u8; 4];
+            stream.read_exact(&mut connack)?;
+            if connack[0] != CONNACK || connack[1] != 2 {
+                return Err(IOError::InvalidData);
+            }
+            if connack[3] != 0 {
+                return Err(IOError::Refused);
+            }
+            self.stream = Some(stream);
+        }
+        Ok(self.stream.as_mut().expect("just connected"))
+    }
+}
+impl<S: Read + Write> ReportSink for MqttSink<S> {
+    fn send_batch(&mut self, batch: &[u8]) -> Result<(), IOError> {
+        let packet = publish_packet(&self.topic, batch)?;
+        let result = self
+            .connected()
+            .and_then(|stream| Ok(stream.write_all(&packet)?));
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_packets() {
+        let publish = publish_packet("btle/reports", b"[]").unwrap();
+        assert_eq!(&publish[..4], &[PUBLISH, 16, 0, 12]);
+        assert_eq!(&publish[4..16], b"btle/reports");
+        assert_eq!(&publish[16..], b"[]");
+        let mut long = Vec::new();
+        write_remaining_len(&mut long, 321);
+        assert_eq!(long, [0xC1, 0x02]);
+        let connect = connect_packet("gw", 60).unwrap();
+        assert_eq!(connect[1] as usize, connect.len() - 2);
+        assert_eq!(&connect[2..8], b"\x00\x04MQTT");
+    }
+}
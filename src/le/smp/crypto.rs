@@ -0,0 +1,216 @@
+//! Security Manager cryptographic toolbox (AES-128, AES-CMAC and the LE Secure Connections
+//! functions). All functions use the spec's MSB-first byte order. Values sent in SMP PDUs are
+//! Little Endian so they must be reversed before/after using these functions.
+
+/// 128-bit key/block in MSB-first byte order.
+pub type Block = [u8; 16];
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+const RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ if b & 0x80 == 0x80 { 0x1b } else { 0x00 }
+}
+fn expand_key(key: &Block) -> [Block; 11] {
+    let mut round_keys = [[0_u8; 16]; 11];
+    round_keys[0] = *key;
+    for round in 1..11 {
+        let prev = round_keys[round - 1];
+        let mut word = [prev[13], prev[14], prev[15], prev[12]];
+        for b in word.iter_mut() {
+            *b = SBOX[usize::from(*b)];
+        }
+        word[0] ^= RCON[round - 1];
+        let mut next = [0_u8; 16];
+        for i in 0..16 {
+            let w = if i < 4 { word[i] } else { next[i - 4] };
+            next[i] = prev[i] ^ w;
+        }
+        round_keys[round] = next;
+    }
+    round_keys
+}
+/// AES-128 block encryption (the Security Function `e`).
+pub fn aes128(key: &Block, plaintext: &Block) -> Block {
+    let round_keys = expand_key(key);
+    let mut state = *plaintext;
+    for (s, k) in state.iter_mut().zip(round_keys[0].iter()) {
+        *s ^= k;
+    }
+    for (round, round_key) in round_keys.iter().enumerate().skip(1) {
+        // SubBytes + ShiftRows
+        let mut shifted = [0_u8; 16];
+        for col in 0..4 {
+            for row in 0..4 {
+                shifted[col * 4 + row] = SBOX[usize::from(state[((col + row) % 4) * 4 + row])];
+            }
+        }
+        state = shifted;
+        // MixColumns (skipped in the final round)
+        if round != 10 {
+            for col in state.chunks_mut(4) {
+                let all = col[0] ^ col[1] ^ col[2] ^ col[3];
+                let first = col[0];
+                col[0] ^= all ^ xtime(col[0] ^ col[1]);
+                col[1] ^= all ^ xtime(col[1] ^ col[2]);
+                col[2] ^= all ^ xtime(col[2] ^ col[3]);
+                col[3] ^= all ^ xtime(col[3] ^ first);
+            }
+        }
+        for (s, k) in state.iter_mut().zip(round_key.iter()) {
+            *s ^= k;
+        }
+    }
+    state
+}
+fn shift_left(block: &Block) -> Block {
+    let mut out = [0_u8; 16];
+    for i in 0..16 {
+        out[i] = block[i] << 1;
+        if i < 15 {
+            out[i] |= block[i + 1] >> 7;
+        }
+    }
+    out
+}
+fn subkey(block: &Block) -> Block {
+    let mut out = shift_left(block);
+    if block[0] & 0x80 == 0x80 {
+        out[15] ^= 0x87;
+    }
+    out
+}
+/// AES-CMAC (RFC 4493) of `message` using `key`.
+pub fn aes_cmac(key: &Block, message: &[u8]) -> Block {
+    let k1 = subkey(&aes128(key, &[0_u8; 16]));
+    let k2 = subkey(&k1);
+    let block_count = core::cmp::max(1, (message.len() + 15) / 16);
+    let mut x = [0_u8; 16];
+    for (i, chunk) in message
+        .chunks(16)
+        .chain(core::iter::once(&[][..]))
+        .take(block_count)
+        .enumerate()
+    {
+        let mut block = [0_u8; 16];
+        block[..chunk.len()].copy_from_slice(chunk);
+        if i == block_count - 1 {
+            let last_key = if chunk.len() == 16 {
+                k1
+            } else {
+                block[chunk.len()] = 0x80;
+                k2
+            };
+            for (b, k) in block.iter_mut().zip(last_key.iter()) {
+                *b ^= k;
+            }
+        }
+        for (b, x) in block.iter_mut().zip(x.iter()) {
+            *b ^= x;
+        }
+        x = aes128(key, &block);
+    }
+    x
+}
+/// LE Secure Connections confirm value generation function `f4(U, V, X, Z)`.
+pub fn f4(u: &[u8; 32], v: &[u8; 32], x: &Block, z: u8) -> Block {
+    let mut m = [0_u8; 65];
+    m[..32].copy_from_slice(&u[..]);
+    m[32..64].copy_from_slice(&v[..]);
+    m[64] = z;
+    aes_cmac(x, &m[..])
+}
+/// LE Secure Connections numeric comparison value generation function `g2(U, V, X, Y)`. The
+/// 6-digit value to display is `g2 % 1_000_000`.
+pub fn g2(u: &[u8; 32], v: &[u8; 32], x: &Block, y: &Block) -> u32 {
+    let mut m = [0_u8; 80];
+    m[..32].copy_from_slice(&u[..]);
+    m[32..64].copy_from_slice(&v[..]);
+    m[64..].copy_from_slice(&y[..]);
+    let mac = aes_cmac(x, &m[..]);
+    u32::from_be_bytes([mac[12], mac[13], mac[14], mac[15]])
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const KEY: Block = [
+        0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f,
+        0x3c,
+    ];
+    #[test]
+    fn test_aes128() {
+        let plaintext = [
+            0x32, 0x43, 0xf6, 0xa8, 0x88, 0x5a, 0x30, 0x8d, 0x31, 0x31, 0x98, 0xa2, 0xe0, 0x37,
+            0x07, 0x34,
+        ];
+        assert_eq!(
+            aes128(&KEY, &plaintext),
+            [
+                0x39, 0x25, 0x84, 0x1d, 0x02, 0xdc, 0x09, 0xfb, 0xdc, 0x11, 0x85, 0x97, 0x19, 0x6a,
+                0x0b, 0x32
+            ]
+        );
+    }
+    #[test]
+    fn test_aes_cmac() {
+        assert_eq!(
+            aes_cmac(&KEY, &[]),
+            [
+                0xbb, 0x1d, 0x69, 0x29, 0xe9, 0x59, 0x37, 0x28, 0x7f, 0xa3, 0x7d, 0x12, 0x9b, 0x75,
+                0x67, 0x46
+            ]
+        );
+        let message = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a,
+        ];
+        assert_eq!(
+            aes_cmac(&KEY, &message),
+            [
+                0x07, 0x0a, 0x16, 0xb4, 0x6b, 0x4d, 0x41, 0x44, 0xf7, 0x9b, 0xdd, 0x9d, 0xd0, 0x4a,
+                0x28, 0x7c
+            ]
+        );
+    }
+    #[test]
+    fn test_f4() {
+        let u = [
+            0x20, 0xb0, 0x03, 0xd2, 0xf2, 0x97, 0xbe, 0x2c, 0x5e, 0x2c, 0x83, 0xa7, 0xe9, 0xf9,
+            0xa5, 0xb9, 0xef, 0xf4, 0x91, 0x11, 0xac, 0xf4, 0xfd, 0xdb, 0xcc, 0x03, 0x01, 0x48,
+            0x0e, 0x35, 0x9d, 0xe6,
+        ];
+        let v = [
+            0x55, 0x18, 0x8b, 0x3d, 0x32, 0xf6, 0xbb, 0x9a, 0x90, 0x0a, 0xfc, 0xfb, 0xee, 0xd4,
+            0xe7, 0x2a, 0x59, 0xcb, 0x9a, 0xc2, 0xf1, 0x9d, 0x7c, 0xfb, 0x6b, 0x4f, 0xdd, 0x49,
+            0xf4, 0x7f, 0xc5, 0xfd,
+        ];
+        let x = [
+            0xd5, 0xcb, 0x84, 0x54, 0xd1, 0x77, 0x73, 0x3e, 0xff, 0xff, 0xb2, 0xec, 0x71, 0x2b,
+            0xae, 0xab,
+        ];
+        assert_eq!(
+            f4(&u, &v, &x, 0),
+            [
+                0xf2, 0xc9, 0x16, 0xf1, 0x07, 0xa9, 0xbd, 0x1c, 0xf1, 0xed, 0xa1, 0xbe, 0xa9, 0x74,
+                0x87, 0x2d
+            ]
+        );
+    }
+}
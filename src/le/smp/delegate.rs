@@ -0,0 +1,216 @@
+//! Pairing user interaction. SMP calls into a [`PairingDelegate`] whenever the chosen
+//! [`PairingMethod`] requires the user to display, input or confirm a value.
+use crate::le::smp::{IOCapability, OOBDataFlag, PairingFeatures};
+use crate::{BTAddress, LocalBoxFuture};
+use alloc::boxed::Box;
+
+/// 6-digit passkey (`000000` to `999999`).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct Passkey(u32);
+impl Passkey {
+    pub const MAX_U32: u32 = 999_999;
+    pub const MAX: Passkey = Passkey(Self::MAX_U32);
+    pub fn new(value: u32) -> Self {
+        match Self::new_checked(value) {
+            Some(s) => s,
+            None => panic!("passkey out of range (`{}`)", value),
+        }
+    }
+    pub fn new_checked(value: u32) -> Option<Self> {
+        if value > Self::MAX_U32 {
+            None
+        } else {
+            Some(Self(value))
+        }
+    }
+}
+impl Passkey {
+    /// Numeric comparison value from the output of `crypto::g2`.
+    pub fn from_g2(value: u32) -> Passkey {
+        Passkey(value % (Self::MAX_U32 + 1))
+    }
+}
+impl From<Passkey> for u32 {
+    fn from(p: Passkey) -> Self {
+        p.0
+    }
+}
+impl core::fmt::Display for Passkey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:06}", self.0)
+    }
+}
+/// Pairing association model picked from both devices' features.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum PairingMethod {
+    JustWorks,
+    /// The local device displays the passkey and the peer inputs it.
+    PasskeyDisplay,
+    /// The local device inputs the passkey displayed on the peer.
+    PasskeyInput,
+    /// Both devices input the passkey (legacy `KeyboardOnly` + `KeyboardOnly`).
+    PasskeyBothInput,
+    NumericComparison,
+    OutOfBand,
+}
+impl PairingMethod {
+    /// Select the pairing method (Core Spec Vol 3, Part H, 2.3.5.1) as the `initiator` or
+    /// responder.
+    pub fn select(
+        local: &PairingFeatures,
+        remote: &PairingFeatures,
+        is_initiator: bool,
+    ) -> PairingMethod {
+        // Method from the initiator's point of view. `InitiatorInputs` means the initiator inputs
+        // the passkey and the responder displays it.
+        enum Model {
+            JustWorks,
+            InitiatorInputs,
+            ResponderInputs,
+            BothInput,
+            Numeric,
+        }
+        use IOCapability::{
+            DisplayOnly, DisplayYesNo, KeyboardDisplay, KeyboardOnly, NoInputNoOutput,
+        };
+        let secure_connections =
+            local.auth_req.secure_connections() && remote.auth_req.secure_connections();
+        let oob = if secure_connections {
+            local.oob_data_flag == OOBDataFlag::Present
+                || remote.oob_data_flag == OOBDataFlag::Present
+        } else {
+            local.oob_data_flag == OOBDataFlag::Present
+                && remote.oob_data_flag == OOBDataFlag::Present
+        };
+        if oob {
+            return PairingMethod::OutOfBand;
+        }
+        if !local.auth_req.mitm() && !remote.auth_req.mitm() {
+            return PairingMethod::JustWorks;
+        }
+        let (initiator, responder) = if is_initiator {
+            (local.io_capability, remote.io_capability)
+        } else {
+            (remote.io_capability, local.io_capability)
+        };
+        let model = match (initiator, responder) {
+            (NoInputNoOutput, _)
+            | (_, NoInputNoOutput)
+            | (DisplayOnly, DisplayOnly)
+            | (DisplayOnly, DisplayYesNo)
+            | (DisplayYesNo, DisplayOnly) => Model::JustWorks,
+            (DisplayYesNo, DisplayYesNo) => {
+                if secure_connections {
+                    Model::Numeric
+                } else {
+                    Model::JustWorks
+                }
+            }
+            (DisplayOnly, _) | (DisplayYesNo, KeyboardOnly) => Model::ResponderInputs,
+            (KeyboardOnly, KeyboardOnly) => Model::BothInput,
+            (KeyboardOnly, _) | (KeyboardDisplay, DisplayOnly) => Model::InitiatorInputs,
+            (DisplayYesNo, KeyboardDisplay)
+            | (KeyboardDisplay, DisplayYesNo)
+            | (KeyboardDisplay, KeyboardDisplay) => {
+                if secure_connections {
+                    Model::Numeric
+                } else if initiator == KeyboardDisplay && responder == DisplayYesNo {
+                    Model::InitiatorInputs
+                } else {
+                    Model::ResponderInputs
+                }
+            }
+            (KeyboardDisplay, KeyboardOnly) => Model::ResponderInputs,
+        };
+        match (model, is_initiator) {
+            (Model::JustWorks, _) => PairingMethod::JustWorks,
+            (Model::Numeric, _) => PairingMethod::NumericComparison,
+            (Model::BothInput, _) => PairingMethod::PasskeyBothInput,
+            (Model::InitiatorInputs, true) | (Model::ResponderInputs, false) => {
+                PairingMethod::PasskeyInput
+            }
+            (Model::InitiatorInputs, false) | (Model::ResponderInputs, true) => {
+                PairingMethod::PasskeyDisplay
+            }
+        }
+    }
+}
+/// Application callbacks for pairing. All methods return futures so UI can be awaited.
+pub trait PairingDelegate {
+    /// IO Capability advertised in the Pairing Request/Response.
+    fn io_capability(&self) -> IOCapability;
+    /// Show `passkey` to the user so it can be typed on the peer.
+    fn display_passkey(&mut self, peer: BTAddress, passkey: Passkey) -> LocalBoxFuture<'_, ()>;
+    /// Ask the user for the passkey shown on the peer. `None` rejects the pairing.
+    fn input_passkey(&mut self, peer: BTAddress) -> LocalBoxFuture<'_, Option<Passkey>>;
+    /// Ask the user if `value` matches the value shown on the peer.
+    fn confirm_numeric_value(
+        &mut self,
+        peer: BTAddress,
+        value: Passkey,
+    ) -> LocalBoxFuture<'_, bool>;
+    /// Ask the user to accept a Just Works pairing (or a Security Request) from `peer`.
+    fn authorize(&mut self, peer: BTAddress) -> LocalBoxFuture<'_, bool>;
+}
+/// Default delegate. `NoInputNoOutput` so every pairing is Just Works and is always accepted.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct JustWorksDelegate;
+impl PairingDelegate for JustWorksDelegate {
+    fn io_capability(&self) -> IOCapability {
+        IOCapability::NoInputNoOutput
+    }
+
+    fn display_passkey(&mut self, _peer: BTAddress, _passkey: Passkey) -> LocalBoxFuture<'_, ()> {
+        Box::pin(async move {})
+    }
+
+    fn input_passkey(&mut self, _peer: BTAddress) -> LocalBoxFuture<'_, Option<Passkey>> {
+        Box::pin(async move { None })
+    }
+
+    fn confirm_numeric_value(
+        &mut self,
+        _peer: BTAddress,
+        _value: Passkey,
+    ) -> LocalBoxFuture<'_, bool> {
+        Box::pin(async move { true })
+    }
+
+    fn authorize(&mut self, _peer: BTAddress) -> LocalBoxFuture<'_, bool> {
+        Box::pin(async move { true })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::le::smp::AuthReq;
+    fn features(io_capability: IOCapability) -> PairingFeatures {
+        PairingFeatures {
+            io_capability,
+            auth_req: AuthReq(AuthReq::MITM | AuthReq::SECURE_CONNECTIONS),
+            ..PairingFeatures::DEFAULT
+        }
+    }
+    #[test]
+    fn test_select_method() {
+        let display = features(IOCapability::DisplayOnly);
+        let keyboard = features(IOCapability::KeyboardOnly);
+        let yes_no = features(IOCapability::DisplayYesNo);
+        assert_eq!(
+            PairingMethod::select(&keyboard, &display, true),
+            PairingMethod::PasskeyInput
+        );
+        assert_eq!(
+            PairingMethod::select(&display, &keyboard, false),
+            PairingMethod::PasskeyDisplay
+        );
+        assert_eq!(
+            PairingMethod::select(&yes_no, &yes_no, true),
+            PairingMethod::NumericComparison
+        );
+        assert_eq!(
+            PairingMethod::select(&PairingFeatures::DEFAULT, &yes_no, true),
+            PairingMethod::JustWorks
+        );
+    }
+}
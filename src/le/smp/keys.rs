@@ -0,0 +1,186 @@
+//! LE Secure Connections P-256 key pairs, the debug key mode, Identity Resolving Keys and Long Term
+//! Keys. Keys are stored Little Endian (the same as the Pairing Public Key and Identity Information
+//! PDUs).
+use crate::PackError;
+use core::convert::TryInto;
+
+/// P-256 private key (Little Endian).
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct PrivateKey(pub [u8; 32]);
+impl core::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Don't leak private keys into logs.
+        write!(f, "PrivateKey(..)")
+    }
+}
+/// Identity Resolving Key (Little Endian). Generates and resolves resolvable private addresses.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct IRK(pub [u8; 16]);
+impl IRK {
+    pub const BYTE_LEN: usize = 16;
+    /// All zeros. Given as the local IRK of a resolving list entry, the controller uses the
+    /// identity address instead of a RPA.
+    pub const ZEROED: IRK = IRK([0_u8; 16]);
+}
+impl core::fmt::Debug for IRK {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Don't leak keys into logs.
+        write!(f, "IRK(..)")
+    }
+}
+/// Long Term Key (Little Endian). Encrypts LE links.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct LTK(pub [u8; 16]);
+impl LTK {
+    pub const BYTE_LEN: usize = 16;
+}
+impl core::fmt::Debug for LTK {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Don't leak keys into logs.
+        write!(f, "LTK(..)")
+    }
+}
+/// P-256 public key (Little Endian X and Y coordinates).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PublicKey {
+    pub x: [u8; 32],
+    pub y: [u8; 32],
+}
+impl PublicKey {
+    pub const BYTE_LEN: usize = 64;
+    /// Returns `true` if the key is the spec's debug public key. A controller/host should only
+    /// accept debug keys from a peer while debug mode is enabled.
+    pub fn is_debug_key(&self) -> bool {
+        *self == KeyPair::DEBUG.public
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[..32].copy_from_slice(&self.x[..]);
+        buf[32..].copy_from_slice(&self.y[..]);
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<PublicKey, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(PublicKey {
+            x: buf[..32].try_into().expect("length checked above"),
+            y: buf[32..].try_into().expect("length checked above"),
+        })
+    }
+}
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct KeyPair {
+    pub private: PrivateKey,
+    pub public: PublicKey,
+}
+impl KeyPair {
+    /// LE Secure Connections debug key pair (Core Spec Vol 3, Part H, 2.3.5.6.1). Traffic
+    /// encrypted with a debug key can be decrypted by sniffers such as Wireshark.
+    pub const DEBUG: KeyPair = KeyPair {
+        private: PrivateKey([
+            0xbd, 0x1a, 0x3c, 0xcd, 0xa6, 0xb8, 0x99, 0x58, 0x99, 0xb7, 0x40, 0xeb, 0x7b, 0x60,
+            0xff, 0x4a, 0x50, 0x3f, 0x10, 0xd2, 0xe3, 0xb3, 0xc9, 0x74, 0x38, 0x5f, 0xc5, 0xa3,
+            0xd4, 0xf6, 0x49, 0x3f,
+        ]),
+        public: PublicKey {
+            x: [
+                0xe6, 0x9d, 0x35, 0x0e, 0x48, 0x01, 0x03, 0xcc, 0xdb, 0xfd, 0xf4, 0xac, 0x11, 0x91,
+                0xf4, 0xef, 0xb9, 0xa5, 0xf9, 0xe9, 0xa7, 0x83, 0x2c, 0x5e, 0x2c, 0xbe, 0x97, 0xf2,
+                0xd2, 0x03, 0xb0, 0x20,
+            ],
+            y: [
+                0x8b, 0xd2, 0x89, 0x15, 0xd0, 0x8e, 0x1c, 0x74, 0x24, 0x30, 0xed, 0x8f, 0xc2, 0x45,
+                0x63, 0x76, 0x5c, 0x15, 0x52, 0x5a, 0xbf, 0x9a, 0x32, 0x63, 0x6d, 0xeb, 0x2a, 0x65,
+                0x49, 0x9c, 0x80, 0xdc,
+            ],
+        },
+    };
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum KeyMode {
+    /// Use the generated (random) key pair.
+    Normal,
+    /// Use the spec debug key pair. Only for development!
+    Debug,
+}
+impl KeyMode {
+    pub const DEFAULT: KeyMode = KeyMode::Normal;
+}
+impl Default for KeyMode {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+/// Local LE Secure Connections keys. The debug key mode can be toggled at runtime and applies to
+/// the next pairing.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Default)]
+pub struct LocalKeys {
+    mode: KeyMode,
+    generated: Option<KeyPair>,
+}
+impl LocalKeys {
+    pub fn new(generated: Option<KeyPair>) -> LocalKeys {
+        LocalKeys {
+            mode: KeyMode::DEFAULT,
+            generated,
+        }
+    }
+    pub fn mode(&self) -> KeyMode {
+        self.mode
+    }
+    pub fn set_mode(&mut self, mode: KeyMode) {
+        self.mode = mode;
+    }
+    pub fn set_generated(&mut self, key_pair: KeyPair) {
+        self.generated = Some(key_pair);
+    }
+    /// Key pair to use for the next pairing. Returns `None` if in `KeyMode::Normal` and no key
+    /// pair has been generated yet.
+    pub fn key_pair(&self) -> Option<KeyPair> {
+        match self.mode {
+            KeyMode::Normal => self.generated,
+            KeyMode::Debug => Some(KeyPair::DEBUG),
+        }
+    }
+    /// Whether a peer's public key should be accepted. Debug keys are rejected unless
+    /// debug mode is enabled.
+    pub fn accepts_peer_key(&self, peer: &PublicKey) -> bool {
+        self.mode == KeyMode::Debug || !peer.is_debug_key()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn generated() -> KeyPair {
+        KeyPair {
+            private: PrivateKey([0x01; 32]),
+            public: PublicKey {
+                x: [0x02; 32],
+                y: [0x03; 32],
+            },
+        }
+    }
+    #[test]
+    fn test_key_mode() {
+        let mut keys = LocalKeys::new(None);
+        assert_eq!(keys.mode(), KeyMode::Normal);
+        assert_eq!(keys.key_pair(), None);
+        keys.set_generated(generated());
+        assert_eq!(keys.key_pair(), Some(generated()));
+        keys.set_mode(KeyMode::Debug);
+        assert_eq!(keys.key_pair(), Some(KeyPair::DEBUG));
+        // Debug mode doesn't replace the generated pair.
+        keys.set_mode(KeyMode::Normal);
+        assert_eq!(keys.key_pair(), Some(generated()));
+    }
+    #[test]
+    fn test_accepts_peer_key() {
+        let mut keys = LocalKeys::new(Some(generated()));
+        assert!(!keys.accepts_peer_key(&KeyPair::DEBUG.public));
+        assert!(keys.accepts_peer_key(&generated().public));
+        keys.set_mode(KeyMode::Debug);
+        assert!(keys.accepts_peer_key(&KeyPair::DEBUG.public));
+        assert!(keys.accepts_peer_key(&generated().public));
+        keys.set_mode(KeyMode::Normal);
+        assert!(!keys.accepts_peer_key(&KeyPair::DEBUG.public));
+    }
+}
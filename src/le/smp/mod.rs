@@ -0,0 +1,253 @@
+//! LE Security Manager Protocol (SMP). Pairing PDU types, user interaction ([`delegate`]), the
+//! cryptographic toolbox ([`crypto`]), key pairs ([`keys`]) and Out-Of-Band pairing data
+//! ([`oob`]).
+use crate::{ConversionError, PackError};
+use core::convert::TryFrom;
+
+pub mod crypto;
+pub mod delegate;
+pub mod keys;
+pub mod oob;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum Opcode {
+    PairingRequest = 0x01,
+    PairingResponse = 0x02,
+    PairingConfirm = 0x03,
+    PairingRandom = 0x04,
+    PairingFailed = 0x05,
+    EncryptionInformation = 0x06,
+    CentralIdentification = 0x07,
+    IdentityInformation = 0x08,
+    IdentityAddressInformation = 0x09,
+    SigningInformation = 0x0A,
+    SecurityRequest = 0x0B,
+    PairingPublicKey = 0x0C,
+    PairingDHKeyCheck = 0x0D,
+    PairingKeypressNotification = 0x0E,
+}
+impl From<Opcode> for u8 {
+    fn from(o: Opcode) -> Self {
+        o as u8
+    }
+}
+impl TryFrom<u8> for Opcode {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Opcode::PairingRequest),
+            0x02 => Ok(Opcode::PairingResponse),
+            0x03 => Ok(Opcode::PairingConfirm),
+            0x04 => Ok(Opcode::PairingRandom),
+            0x05 => Ok(Opcode::PairingFailed),
+            0x06 => Ok(Opcode::EncryptionInformation),
+            0x07 => Ok(Opcode::CentralIdentification),
+            0x08 => Ok(Opcode::IdentityInformation),
+            0x09 => Ok(Opcode::IdentityAddressInformation),
+            0x0A => Ok(Opcode::SigningInformation),
+            0x0B => Ok(Opcode::SecurityRequest),
+            0x0C => Ok(Opcode::PairingPublicKey),
+            0x0D => Ok(Opcode::PairingDHKeyCheck),
+            0x0E => Ok(Opcode::PairingKeypressNotification),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum IOCapability {
+    DisplayOnly = 0x00,
+    DisplayYesNo = 0x01,
+    KeyboardOnly = 0x02,
+    NoInputNoOutput = 0x03,
+    KeyboardDisplay = 0x04,
+}
+impl From<IOCapability> for u8 {
+    fn from(i: IOCapability) -> Self {
+        i as u8
+    }
+}
+impl TryFrom<u8> for IOCapability {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(IOCapability::DisplayOnly),
+            0x01 => Ok(IOCapability::DisplayYesNo),
+            0x02 => Ok(IOCapability::KeyboardOnly),
+            0x03 => Ok(IOCapability::NoInputNoOutput),
+            0x04 => Ok(IOCapability::KeyboardDisplay),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+impl Default for IOCapability {
+    fn default() -> Self {
+        IOCapability::NoInputNoOutput
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum OOBDataFlag {
+    NotPresent = 0x00,
+    Present = 0x01,
+}
+impl From<OOBDataFlag> for u8 {
+    fn from(o: OOBDataFlag) -> Self {
+        o as u8
+    }
+}
+impl TryFrom<u8> for OOBDataFlag {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(OOBDataFlag::NotPresent),
+            0x01 => Ok(OOBDataFlag::Present),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
+impl Default for OOBDataFlag {
+    fn default() -> Self {
+        OOBDataFlag::NotPresent
+    }
+}
+/// Authentication Requirements bit field.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct AuthReq(pub u8);
+impl AuthReq {
+    pub const BONDING: u8 = 0x01;
+    pub const MITM: u8 = 0x04;
+    pub const SECURE_CONNECTIONS: u8 = 0x08;
+    pub const KEYPRESS: u8 = 0x10;
+    pub const CT2: u8 = 0x20;
+    pub fn bonding(self) -> bool {
+        self.0 & Self::BONDING != 0
+    }
+    pub fn mitm(self) -> bool {
+        self.0 & Self::MITM != 0
+    }
+    pub fn secure_connections(self) -> bool {
+        self.0 & Self::SECURE_CONNECTIONS != 0
+    }
+    pub fn keypress(self) -> bool {
+        self.0 & Self::KEYPRESS != 0
+    }
+}
+/// Initiator/Responder Key Distribution bit field.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct KeyDistribution(pub u8);
+impl KeyDistribution {
+    pub const ENC_KEY: u8 = 0x01;
+    pub const ID_KEY: u8 = 0x02;
+    pub const SIGN_KEY: u8 = 0x04;
+    pub const LINK_KEY: u8 = 0x08;
+    pub const ALL_LE: KeyDistribution =
+        KeyDistribution(Self::ENC_KEY | Self::ID_KEY | Self::SIGN_KEY);
+}
+/// Pairing Request/Response parameters.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct PairingFeatures {
+    pub io_capability: IOCapability,
+    pub oob_data_flag: OOBDataFlag,
+    pub auth_req: AuthReq,
+    pub max_encryption_key_size: u8,
+    pub initiator_key_distribution: KeyDistribution,
+    pub responder_key_distribution: KeyDistribution,
+}
+impl PairingFeatures {
+    pub const BYTE_LEN: usize = 6;
+    pub const MIN_KEY_SIZE: u8 = 7;
+    pub const MAX_KEY_SIZE: u8 = 16;
+    pub const DEFAULT: PairingFeatures = PairingFeatures {
+        io_capability: IOCapability::NoInputNoOutput,
+        oob_data_flag: OOBDataFlag::NotPresent,
+        auth_req: AuthReq(AuthReq::BONDING | AuthReq::SECURE_CONNECTIONS),
+        max_encryption_key_size: Self::MAX_KEY_SIZE,
+        initiator_key_distribution: KeyDistribution::ALL_LE,
+        responder_key_distribution: KeyDistribution::ALL_LE,
+    };
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        buf[0] = self.io_capability.into();
+        buf[1] = self.oob_data_flag.into();
+        buf[2] = self.auth_req.0;
+        buf[3] = self.max_encryption_key_size;
+        buf[4] = self.initiator_key_distribution.0;
+        buf[5] = self.responder_key_distribution.0;
+        Ok(())
+    }
+    pub fn unpack_from(buf: &[u8]) -> Result<Self, PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let max_encryption_key_size = buf[3];
+        if max_encryption_key_size < Self::MIN_KEY_SIZE
+            || max_encryption_key_size > Self::MAX_KEY_SIZE
+        {
+            return Err(PackError::bad_index(3));
+        }
+        Ok(PairingFeatures {
+            io_capability: IOCapability::try_from(buf[0]).map_err(|_| PackError::bad_index(0))?,
+            oob_data_flag: OOBDataFlag::try_from(buf[1]).map_err(|_| PackError::bad_index(1))?,
+            auth_req: AuthReq(buf[2]),
+            max_encryption_key_size,
+            initiator_key_distribution: KeyDistribution(buf[4]),
+            responder_key_distribution: KeyDistribution(buf[5]),
+        })
+    }
+}
+impl Default for PairingFeatures {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum PairingFailedReason {
+    PasskeyEntryFailed = 0x01,
+    OOBNotAvailable = 0x02,
+    AuthenticationRequirements = 0x03,
+    ConfirmValueFailed = 0x04,
+    PairingNotSupported = 0x05,
+    EncryptionKeySize = 0x06,
+    CommandNotSupported = 0x07,
+    UnspecifiedReason = 0x08,
+    RepeatedAttempts = 0x09,
+    InvalidParameters = 0x0A,
+    DHKeyCheckFailed = 0x0B,
+    NumericComparisonFailed = 0x0C,
+    BREDRPairingInProgress = 0x0D,
+    CrossTransportKeyDerivationNotAllowed = 0x0E,
+    KeyRejected = 0x0F,
+}
+impl From<PairingFailedReason> for u8 {
+    fn from(r: PairingFailedReason) -> Self {
+        r as u8
+    }
+}
+impl TryFrom<u8> for PairingFailedReason {
+    type Error = ConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(PairingFailedReason::PasskeyEntryFailed),
+            0x02 => Ok(PairingFailedReason::OOBNotAvailable),
+            0x03 => Ok(PairingFailedReason::AuthenticationRequirements),
+            0x04 => Ok(PairingFailedReason::ConfirmValueFailed),
+            0x05 => Ok(PairingFailedReason::PairingNotSupported),
+            0x06 => Ok(PairingFailedReason::EncryptionKeySize),
+            0x07 => Ok(PairingFailedReason::CommandNotSupported),
+            0x08 => Ok(PairingFailedReason::UnspecifiedReason),
+            0x09 => Ok(PairingFailedReason::RepeatedAttempts),
+            0x0A => Ok(PairingFailedReason::InvalidParameters),
+            0x0B => Ok(PairingFailedReason::DHKeyCheckFailed),
+            0x0C => Ok(PairingFailedReason::NumericComparisonFailed),
+            0x0D => Ok(PairingFailedReason::BREDRPairingInProgress),
+            0x0E => Ok(PairingFailedReason::CrossTransportKeyDerivationNotAllowed),
+            0x0F => Ok(PairingFailedReason::KeyRejected),
+            _ => Err(ConversionError(())),
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Out-Of-Band (OOB) pairing data. Used to pass pairing values over NFC, QR codes, etc.
+//! Values are stored Little Endian (the same as the SMP PDUs and AD structures).
+use crate::le::advertisement::{
+    AdStructureType, AdType, ConstAdStructType, RawAdvertisement, UnpackableAdStructType,
+};
+use crate::le::advertiser::{DirectedPeer, PeerAddressType};
+use crate::le::smp::{crypto, OOBDataFlag};
+use crate::{BTAddress, DeviceAddress, PackError};
+use core::convert::{TryFrom, TryInto};
+
+/// 128-bit Little Endian value.
+pub type Value128 = [u8; 16];
+/// 256-bit Little Endian P-256 public key X coordinate.
+pub type PublicKeyX = [u8; 32];
+
+fn reversed<T: AsMut<[u8]> + Copy>(value: &T) -> T {
+    let mut out = *value;
+    out.as_mut().reverse();
+    out
+}
+/// Packs `ad_struct` with its length and AD type at the start of `buf`. Returns the number of
+/// bytes written.
+fn pack_ad_struct<T: AdStructureType>(ad_struct: &T, buf: &mut [u8]) -> Result<usize, PackError> {
+    let len = ad_struct.byte_len();
+    if buf.len() < len + 2 {
+        return Err(PackError::BadLength {
+            expected: len + 2,
+            got: buf.len(),
+        });
+    }
+    buf[0] = u8::try_from(len + 1).map_err(|_| PackError::InvalidFields)?;
+    buf[1] = ad_struct.ad_type().into();
+    ad_struct.pack_into(&mut buf[2..len + 2])?;
+    Ok(len + 2)
+}
+/// LE Secure Connections Confirmation Value AD Structure (`Ca`/`Cb`).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct ConfirmValue(pub Value128);
+/// LE Secure Connections Random Value AD Structure (`ra`/`rb`).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct RandomValue(pub Value128);
+/// Legacy pairing Security Manager TK Value AD Structure.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct TKValue(pub Value128);
+macro_rules! value_128_ad_struct {
+    ($name:ident, $ad_type:expr) => {
+        impl $name {
+            pub const BYTE_LEN: usize = 16;
+        }
+        impl AdStructureType for $name {
+            fn ad_type(&self) -> AdType {
+                $ad_type
+            }
+
+            fn byte_len(&self) -> usize {
+                Self::BYTE_LEN
+            }
+
+            fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+                PackError::expect_length(Self::BYTE_LEN, buf)?;
+                buf.copy_from_slice(&self.0[..]);
+                Ok(())
+            }
+        }
+        impl UnpackableAdStructType for $name {
+            fn unpack_from(ad_type: AdType, buf: &[u8]) -> Result<Self, PackError> {
+                if ad_type != $ad_type {
+                    return Err(PackError::InvalidFields);
+                }
+                PackError::expect_length(Self::BYTE_LEN, buf)?;
+                Ok($name(buf.try_into().expect("length checked above")))
+            }
+        }
+        impl ConstAdStructType for $name {
+            const AD_TYPE: AdType = $ad_type;
+        }
+    };
+}
+value_128_ad_struct!(ConfirmValue, AdType::LESecureConfirmValue);
+value_128_ad_struct!(RandomValue, AdType::LEConfirmRandomValue);
+value_128_ad_struct!(TKValue, AdType::SecurityManagerTKValue);
+
+/// LE Bluetooth Device Address AD Structure (address + address type).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct LEDeviceAddress {
+    pub address: BTAddress,
+    pub address_type: PeerAddressType,
+}
+impl LEDeviceAddress {
+    pub const BYTE_LEN: usize = BTAddress::LEN + 1;
+    pub fn device_address(&self) -> DeviceAddress {
+        DirectedPeer::new(self.address_type, self.address).into()
+    }
+}
+impl From<DeviceAddress> for LEDeviceAddress {
+    fn from(address: DeviceAddress) -> Self {
+        LEDeviceAddress {
+            address: address.addr,
+            address_type: address.kind.into(),
+        }
+    }
+}
+impl AdStructureType for LEDeviceAddress {
+    fn ad_type(&self) -> AdType {
+        AdType::LEDeviceAddress
+    }
+
+    fn byte_len(&self) -> usize {
+        Self::BYTE_LEN
+    }
+
+    fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        self.address.pack_into(&mut buf[..BTAddress::LEN])?;
+        buf[BTAddress::LEN] = self.address_type.into();
+        Ok(())
+    }
+}
+impl UnpackableAdStructType for LEDeviceAddress {
+    fn unpack_from(ad_type: AdType, buf: &[u8]) -> Result<Self, PackError> {
+        if ad_type != AdType::LEDeviceAddress {
+            return Err(PackError::InvalidFields);
+        }
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        Ok(LEDeviceAddress {
+            address: BTAddress::unpack_from(&buf[..BTAddress::LEN])?,
+            // Only bit 0 is defined. The rest are RFU.
+            address_type: PeerAddressType::try_from(buf[BTAddress::LEN] & 0x01)
+                .expect("masked to 1 bit"),
+        })
+    }
+}
+impl ConstAdStructType for LEDeviceAddress {
+    const AD_TYPE: AdType = AdType::LEDeviceAddress;
+}
+/// LE Secure Connections OOB data for one device (address, confirm and random values).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SecureConnectionsOOBData {
+    pub address: LEDeviceAddress,
+    pub confirm: ConfirmValue,
+    pub random: RandomValue,
+}
+impl SecureConnectionsOOBData {
+    /// Generate local OOB data from the local public key X coordinate and a random value `r`.
+    /// `Ca = f4(PKax, PKax, ra, 0)`.
+    pub fn generate(
+        address: LEDeviceAddress,
+        public_key_x: &PublicKeyX,
+        random: Value128,
+    ) -> SecureConnectionsOOBData {
+        SecureConnectionsOOBData {
+            address,
+            confirm: ConfirmValue(Self::confirm_value(public_key_x, &random)),
+            random: RandomValue(random),
+        }
+    }
+    fn confirm_value(public_key_x: &PublicKeyX, random: &Value128) -> Value128 {
+        let pkx = reversed(public_key_x);
+        reversed(&crypto::f4(&pkx, &pkx, &reversed(random), 0))
+    }
+    /// Checks the peer's OOB confirm value against the public key received during pairing.
+    pub fn verify(&self, peer_public_key_x: &PublicKeyX) -> bool {
+        Self::confirm_value(peer_public_key_x, &self.random.0) == self.confirm.0
+    }
+    /// Length of the OOB data packed as AD structures (each with a length and AD type byte).
+    pub const BYTE_LEN: usize = 2 + LEDeviceAddress::BYTE_LEN + 2 + 16 + 2 + 16;
+    /// Pack the OOB data as AD structures (e.g. for an NFC/QR payload). The OOB data block is
+    /// longer than a legacy advertisement so it can't be packed into a `RawAdvertisement`.
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(Self::BYTE_LEN, buf)?;
+        let mut index = pack_ad_struct(&self.address, buf)?;
+        index += pack_ad_struct(&self.confirm, &mut buf[index..])?;
+        pack_ad_struct(&self.random, &mut buf[index..])?;
+        Ok(())
+    }
+    /// Unpack OOB data from AD structures. Returns `None` if any of the address, confirm or
+    /// random structures are missing.
+    pub fn unpack_from(buf: &[u8]) -> Result<Option<SecureConnectionsOOBData>, PackError> {
+        let mut address = None;
+        let mut confirm = None;
+        let mut random = None;
+        for ad_struct in RawAdvertisement(buf).iter() {
+            let buf = ad_struct.buf.as_ref();
+            match ad_struct.ad_type {
+                AdType::LEDeviceAddress => {
+                    address = Some(LEDeviceAddress::unpack_from(ad_struct.ad_type, buf)?)
+                }
+                AdType::LESecureConfirmValue => {
+                    confirm = Some(ConfirmValue::unpack_from(ad_struct.ad_type, buf)?)
+                }
+                AdType::LEConfirmRandomValue => {
+                    random = Some(RandomValue::unpack_from(ad_struct.ad_type, buf)?)
+                }
+                _ => (),
+            }
+        }
+        Ok(match (address, confirm, random) {
+            (Some(address), Some(confirm), Some(random)) => Some(SecureConnectionsOOBData {
+                address,
+                confirm,
+                random,
+            }),
+            _ => None,
+        })
+    }
+}
+/// OOB data available for a pairing. `local` is sent to the peer out-of-band and `remote` is
+/// received from the peer out-of-band.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct OOBPairingData {
+    pub local: Option<SecureConnectionsOOBData>,
+    pub remote: Option<SecureConnectionsOOBData>,
+    pub legacy_tk: Option<TKValue>,
+}
+impl OOBPairingData {
+    /// OOB Data Flag to send in the Pairing Request/Response.
+    pub fn oob_data_flag(&self) -> OOBDataFlag {
+        if self.remote.is_some() || self.legacy_tk.is_some() {
+            OOBDataFlag::Present
+        } else {
+            OOBDataFlag::NotPresent
+        }
+    }
+    /// `ra`/`rb` to use in the LE SC check values. Zero if no OOB data was received.
+    pub fn remote_random(&self) -> Value128 {
+        self.remote.map_or([0_u8; 16], |r| r.random.0)
+    }
+    /// `ra`/`rb` to use in the LE SC check values. Zero if no local OOB data was sent.
+    pub fn local_random(&self) -> Value128 {
+        self.local.map_or([0_u8; 16], |l| l.random.0)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_oob_round_trip() {
+        let address = LEDeviceAddress {
+            address: BTAddress([1, 2, 3, 4, 5, 6]),
+            address_type: PeerAddressType::Random,
+        };
+        let public_key_x = [0x55_u8; 32];
+        let data = SecureConnectionsOOBData::generate(address, &public_key_x, [0xAA; 16]);
+        assert!(data.verify(&public_key_x));
+        assert!(!data.verify(&[0x00; 32]));
+        let mut buf = [0_u8; SecureConnectionsOOBData::BYTE_LEN];
+        data.pack_into(&mut buf[..]).unwrap();
+        assert_eq!(
+            SecureConnectionsOOBData::unpack_from(&buf[..]),
+            Ok(Some(data))
+        );
+    }
+}
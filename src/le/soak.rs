@@ -0,0 +1,472 @@
+//! Soak harness for long-running gateways. [`SoakHarness::run`] repeats scan/advertise cycles
+//! against any [`Observer`] + [`Advertiser`] backend (a real adapter or a mock) for hours,
+//! sampling memory and open handles through [`Probe`]s and measuring how late each cycle starts.
+//! Slow leaks and timer drift that never show up in short tests show up as steady growth over
+//! the run (see [`SoakReport::findings`]).
+//!
+//! Memory is best counted by installing [`CountingAllocator`] as the soak binary's
+//! `#[global_allocator]` and probing [`CountingAllocator::live_bytes`].
+use crate::clock::Clock;
+use crate::hci::adapter;
+use crate::hci::adapters::retry::DelayFn;
+use crate::le::advertiser::Advertiser;
+use crate::le::scan::Observer;
+use alloc::vec::Vec;
+use core::alloc::{GlobalAlloc, Layout};
+use core::convert::TryFrom;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+use futures_util::future::{self, Either};
+use futures_util::StreamExt;
+
+/// Reads a resource count (live heap bytes, open file descriptors, etc). `None` skips the
+/// sample.
+pub type Probe = fn() -> Option<u64>;
+/// Called after every cycle with the report so far.
+pub type CycleHook = fn(&SoakReport);
+
+/// `GlobalAlloc` wrapper counting live allocations.
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator<std::alloc::System> = CountingAllocator::new(std::alloc::System);
+/// harness.memory_probe = Some(|| Some(ALLOCATOR.live_bytes() as u64));
+/// ```
+#[derive(Debug, Default)]
+pub struct CountingAllocator<A> {
+    inner: A,
+    live_bytes: AtomicUsize,
+    live_allocations: AtomicUsize,
+}
+impl<A> CountingAllocator<A> {
+    pub const fn new(inner: A) -> Self {
+        CountingAllocator {
+            inner,
+            live_bytes: AtomicUsize::new(0),
+            live_allocations: AtomicUsize::new(0),
+        }
+    }
+    pub fn live_bytes(&self) -> usize {
+        self.live_bytes.load(Ordering::Relaxed)
+    }
+    pub fn live_allocations(&self) -> usize {
+        self.live_allocations.load(Ordering::Relaxed)
+    }
+}
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            self.live_bytes.fetch_add(layout.size(), Ordering::Relaxed);
+            self.live_allocations.fetch_add(1, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        self.live_bytes.fetch_sub(layout.size(), Ordering::Relaxed);
+        self.live_allocations.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            self.live_bytes.fetch_sub(layout.size(), Ordering::Relaxed);
+            self.live_bytes.fetch_add(new_size, Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+/// [`Probe`] counting this process's open file descriptors (sockets included).
+#[cfg(target_os = "linux")]
+pub fn open_fd_count() -> Option<u64> {
+    let entries = std::fs::read_dir("/proc/self/fd").ok()?;
+    u64::try_from(entries.count()).ok()
+}
+/// Growth of a sampled value since the first sample. Only keeps the first, last and peak samples
+/// so it doesn't grow itself over long runs.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default)]
+pub struct GrowthTracker {
+    first: Option<(Duration, u64)>,
+    last: Option<(Duration, u64)>,
+    peak: u64,
+    samples: u64,
+}
+impl GrowthTracker {
+    pub fn sample(&mut self, at: Duration, value: u64) {
+        if self.first.is_none() {
+            self.first = Some((at, value));
+        }
+        self.last = Some((at, value));
+        self.peak = self.peak.max(value);
+        self.samples += 1;
+    }
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+    pub fn last(&self) -> Option<u64> {
+        self.last.map(|(_, value)| value)
+    }
+    pub fn peak(&self) -> u64 {
+        self.peak
+    }
+    /// Last sample minus the first (negative if it shrank).
+    pub fn growth(&self) -> i64 {
+        match (self.first, self.last) {
+            (Some((_, first)), Some((_, last))) => {
+                i64::try_from(i128::from(last) - i128::from(first)).unwrap_or(i64::MAX)
+            }
+            _ => 0,
+        }
+    }
+    /// [`GrowthTracker::growth`] scaled to one hour. `None` until samples span some time.
+    pub fn growth_per_hour(&self) -> Option<i64> {
+        let (first_at, last_at) = (self.first?.0, self.last?.0);
+        let span = last_at.checked_sub(first_at)?.as_millis();
+        if span == 0 {
+            return None;
+        }
+        let per_hour = i128::from(self.growth()) * 3_600_000 / i128::try_from(span).ok()?;
+        i64::try_from(per_hour).ok()
+    }
+}
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SoakConfig {
+    /// How long to keep cycling.
+    pub duration: Duration,
+    /// Time between cycle starts. Cycles are scheduled from the run's start so lateness doesn't
+    /// add up.
+    pub cycle_period: Duration,
+    /// Scan time per cycle (`0` to skip scanning).
+    pub scan_time: Duration,
+    /// Advertise time per cycle (`0` to skip advertising).
+    pub advertise_time: Duration,
+    /// Cycles run before probes are sampled, so caches and buffers can fill first.
+    pub warmup_cycles: u32,
+    /// Memory growth per hour (in probe units, usually bytes) reported as a leak.
+    pub max_memory_growth_per_hour: i64,
+    /// Handle growth over the whole run reported as a leak. Handles shouldn't grow at all.
+    pub max_handle_growth: i64,
+    /// Latest a cycle may start.
+    pub max_lateness: Duration,
+}
+impl SoakConfig {
+    pub const DEFAULT: SoakConfig = SoakConfig {
+        duration: Duration::from_hours(4),
+        cycle_period: Duration::from_secs(10),
+        scan_time: Duration::from_secs(4),
+        advertise_time: Duration::from_secs(4),
+        warmup_cycles: 6,
+        max_memory_growth_per_hour: 64 * 1024,
+        max_handle_growth: 0,
+        max_lateness: Duration::from_millis(500),
+    };
+}
+impl Default for SoakConfig {
+    fn default() -> Self {
+        SoakConfig::DEFAULT
+    }
+}
+/// Problem found by a soak run.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum SoakFinding {
+    MemoryGrowth {
+        per_hour: i64,
+    },
+    HandleGrowth {
+        growth: i64,
+    },
+    /// A cycle started `lateness` after it was scheduled.
+    TimerDrift {
+        lateness: Duration,
+    },
+    /// Cycles took longer than the period and the next ones had to be skipped.
+    Overruns {
+        skipped: u32,
+    },
+    AdapterErrors {
+        count: u32,
+        first: adapter::Error,
+    },
+}
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash, Default)]
+pub struct SoakReport {
+    pub cycles: u32,
+    pub skipped_cycles: u32,
+    /// Advertising reports seen while scanning.
+    pub reports: u64,
+    pub errors: u32,
+    pub first_error: Option<adapter::Error>,
+    pub memory: GrowthTracker,
+    pub handles: GrowthTracker,
+    /// How late each cycle started, in microseconds. Steady growth means the timers drift.
+    pub lateness: GrowthTracker,
+}
+impl SoakReport {
+    /// Everything over the `config` limits.
+    pub fn findings(&self, config: &SoakConfig) -> Vec<SoakFinding> {
+        let mut findings = Vec::new();
+        if let Some(per_hour) = self.memory.growth_per_hour() {
+            if per_hour > config.max_memory_growth_per_hour {
+                findings.push(SoakFinding::MemoryGrowth { per_hour });
+            }
+        }
+        if self.handles.growth() > config.max_handle_growth {
+            findings.push(SoakFinding::HandleGrowth {
+                growth: self.handles.growth(),
+            });
+        }
+        let lateness = Duration::from_micros(self.lateness.peak());
+        if lateness > config.max_lateness {
+            findings.push(SoakFinding::TimerDrift { lateness });
+        }
+        if self.skipped_cycles > 0 {
+            findings.push(SoakFinding::Overruns {
+                skipped: self.skipped_cycles,
+            });
+        }
+        if let Some(first) = self.first_error {
+            findings.push(SoakFinding::AdapterErrors {
+                count: self.errors,
+                first,
+            });
+        }
+        findings
+    }
+}
+pub struct SoakHarness<C: Clock> {
+    pub config: SoakConfig,
+    pub clock: C,
+    /// Waits out scan/advertise times and the rest of each cycle.
+    pub delay: DelayFn,
+    /// Advertising data set every cycle.
+    pub advertising_data: Vec<u8>,
+    pub memory_probe: Option<Probe>,
+    pub handle_probe: Option<Probe>,
+    pub on_cycle: Option<CycleHook>,
+}
+impl<C: Clock> SoakHarness<C> {
+    /// Flags only advertising data (LE General Discoverable, BR/EDR Not Supported).
+    pub const DEFAULT_ADVERTISING_DATA: [u8; 3] = [0x02, 0x01, 0x06];
+    pub fn new(config: SoakConfig, clock: C, delay: DelayFn) -> Self {
+        SoakHarness {
+            config,
+            clock,
+            delay,
+            advertising_data: Self::DEFAULT_ADVERTISING_DATA.to_vec(),
+            memory_probe: None,
+            handle_probe: None,
+            on_cycle: None,
+        }
+    }
+    /// Cycle until [`SoakConfig::duration`] is up. Adapter errors are counted and the run keeps
+    /// going (a gateway would too).
+    pub async fn run<B: Observer + Advertiser>(&self, backend: &mut B) -> SoakReport {
+        let start = self.clock.now();
+        let mut report = SoakReport::default();
+        let mut slot = 0_u32;
+        while self.clock.elapsed_since(start) < self.config.duration {
+            let now = self.clock.now();
+            let late = self
+                .clock
+                .elapsed_since(start + self.config.cycle_period * slot);
+            report.lateness.sample(now, duration_micros(late));
+            match self.cycle(backend).await {
+                Ok(reports) => report.reports += reports,
+                Err(e) => {
+                    report.errors += 1;
+                    report.first_error.get_or_insert(e);
+                }
+            }
+            report.cycles += 1;
+            if report.cycles > self.config.warmup_cycles {
+                self.sample_probes(&mut report);
+            }
+            if let Some(on_cycle) = self.on_cycle {
+                on_cycle(&report);
+            }
+            // Next slot that hasn't started yet.
+            let elapsed = self.clock.elapsed_since(start);
+            let next = slot + 1;
+            slot = match u32::try_from(elapsed.as_nanos() / self.config.cycle_period.as_nanos()) {
+                Ok(current) if current >= next => {
+                    report.skipped_cycles += current - next + 1;
+                    current + 1
+                }
+                Ok(_) => next,
+                Err(_) => break,
+            };
+            let wait = (start + self.config.cycle_period * slot).checked_sub(self.clock.now());
+            if let Some(wait) = wait {
+                (self.delay)(wait).await;
+            }
+        }
+        report
+    }
+    async fn cycle<B: Observer + Advertiser>(
+        &self,
+        backend: &mut B,
+    ) -> Result<u64, adapter::Error> {
+        let mut reports = 0_u64;
+        if self.config.scan_time > Duration::default() {
+            backend.set_scan_enable(true, false).await?;
+            {
+                let mut stream = backend.advertisement_stream().await?;
+                let mut timeout = (self.delay)(self.config.scan_time);
+                loop {
+                    match future::select(stream.next(), timeout).await {
+                        Either::Left((Some(report), rest)) => {
+                            report?;
+                            reports += 1;
+                            timeout = rest;
+                        }
+                        Either::Left((None, rest)) => {
+                            rest.await;
+                            break;
+                        }
+                        Either::Right(_) => break,
+                    }
+                }
+            }
+            backend.set_scan_enable(false, false).await?;
+        }
+        if self.config.advertise_time > Duration::default() {
+            backend.set_advertising_data(&self.advertising_data).await?;
+            backend.set_advertising_enable(true).await?;
+            (self.delay)(self.config.advertise_time).await;
+            backend.set_advertising_enable(false).await?;
+        }
+        Ok(reports)
+    }
+    fn sample_probes(&self, report: &mut SoakReport) {
+        let now = self.clock.now();
+        if let Some(value) = self.memory_probe.and_then(|probe| probe()) {
+            report.memory.sample(now, value);
+        }
+        if let Some(value) = self.handle_probe.and_then(|probe| probe()) {
+            report.handles.sample(now, value);
+        }
+    }
+}
+fn duration_micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::sim::{delay, SimTime};
+    use crate::hci::le::whitelist::WhitelistEntry;
+    use crate::le::advertisement::StaticAdvBuffer;
+    use crate::le::advertiser::AdvertisingParameters;
+    use crate::le::report::ReportInfo;
+    use crate::le::scan::ScanParameters;
+    use alloc::boxed::Box;
+    use core::cell::Cell;
+    use futures_util::future::LocalBoxFuture;
+    use futures_util::stream::{self, LocalBoxStream};
+    struct MockBackend {
+        enables: u32,
+    }
+    impl Observer for MockBackend {
+        fn set_scan_parameters(
+            &mut self,
+            _scan_parameters: ScanParameters,
+        ) -> LocalBoxFuture<'_, Result<(), adapter::Error>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn set_scan_enable(
+            &mut self,
+            _is_enabled: bool,
+            _filter_duplicates: bool,
+        ) -> LocalBoxFuture<'_, Result<(), adapter::Error>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn advertisement_stream<'a>(
+            &'a mut self,
+        ) -> LocalBoxFuture<
+            'a,
+            Result<
+                LocalBoxStream<'a, Result<ReportInfo<StaticAdvBuffer>, adapter::Error>>,
+                adapter::Error,
+            >,
+        > {
+            Box::pin(async { Ok(Box::pin(stream::empty()) as LocalBoxStream<'a, _>) })
+        }
+    }
+    impl Advertiser for MockBackend {
+        fn set_advertising_enable(
+            &mut self,
+            is_enabled: bool,
+        ) -> LocalBoxFuture<'_, Result<(), adapter::Error>> {
+            self.enables += u32::from(is_enabled);
+            Box::pin(async { Ok(()) })
+        }
+
+        fn set_advertising_parameters(
+            &mut self,
+            _advertising_parameters: AdvertisingParameters,
+        ) -> LocalBoxFuture<'_, Result<(), adapter::Error>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn set_advertising_data<'d, 'a: 'd>(
+            &'a mut self,
+            _data: &'d [u8],
+        ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn set_scan_response_data<'d, 'a: 'd>(
+            &'a mut self,
+            _data: &'d [u8],
+        ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn set_whitelist<'d, 'a: 'd>(
+            &'a mut self,
+            _entries: &'d [WhitelistEntry],
+        ) -> LocalBoxFuture<'d, Result<(), adapter::Error>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+    std::thread_local! {
+        static LEAKED: Cell<u64> = const { Cell::new(0) };
+    }
+    #[test]
+    fn test_soak_run() {
+        let sim = SimTime::new();
+        let config = SoakConfig {
+            duration: Duration::from_hours(1),
+            ..SoakConfig::DEFAULT
+        };
+        let mut harness = SoakHarness::new(config, sim.clock(), delay);
+        // Leaks 100 bytes a cycle (36KB/hour) while handles stay put.
+        harness.memory_probe = Some(|| {
+            Some(LEAKED.with(|leaked| {
+                leaked.set(leaked.get() + 100);
+                leaked.get()
+            }))
+        });
+        harness.handle_probe = Some(|| Some(8));
+        let mut backend = MockBackend { enables: 0 };
+        let report = sim.block_on(harness.run(&mut backend));
+        assert_eq!(report.cycles, 360);
+        assert_eq!(backend.enables, 360);
+        assert_eq!(report.skipped_cycles, 0);
+        assert_eq!(report.lateness.peak(), 0);
+        assert_eq!(report.handles.growth(), 0);
+        assert!(report.findings(&config).is_empty());
+        let strict = SoakConfig {
+            max_memory_growth_per_hour: 1024,
+            ..config
+        };
+        match report.findings(&strict).as_slice() {
+            [SoakFinding::MemoryGrowth { per_hour }] => assert!(*per_hour > 30_000),
+            findings => panic!("unexpected findings {:?}", findings),
+        }
+    }
+}
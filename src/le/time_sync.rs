@@ -0,0 +1,218 @@
+//! Clock offsets between adapters (or gateways) for time difference of arrival (TDOA)
+//! locationing. Every adapter syncs to the same periodic advertising train (a reference beacon)
+//! and hands the time it received each train event to a [`TimeSync`]. All adapters receive an
+//! event at the same instant (give or take the propagation delay, constant for fixed gateways),
+//! so the difference between their receive times is the offset between their clocks. Offsets are
+//! relative to one reference adapter and smoothed, and [`TimeSync::correct`] maps any timestamp
+//! (usually the receive time of a report) onto the reference clock.
+//!
+//! Timestamps are whatever the adapters provide: host receive times (good to a few ms) or
+//! controller/vendor timestamps (good to a few µs). Events are matched by their periodic event
+//! counter if the controller reports it, otherwise the clocks must already agree to within half
+//! the train interval (NTP, etc) to tell events apart.
+use crate::hci::le::periodic_advertise::PeriodicAdvertisingInterval;
+use crate::le::report::{AdapterIndex, ReportInfo};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct TimeSyncConfig {
+    /// Interval of the reference train (from its Periodic Advertising Sync Established event).
+    pub interval: PeriodicAdvertisingInterval,
+    /// Exponential smoothing factor. Each sample moves the offset `1/smoothing` of the way. `1`
+    /// disables smoothing.
+    pub smoothing: u8,
+}
+impl TimeSyncConfig {
+    pub const DEFAULT: TimeSyncConfig = TimeSyncConfig {
+        interval: PeriodicAdvertisingInterval::DEFAULT,
+        smoothing: 8,
+    };
+}
+impl Default for TimeSyncConfig {
+    fn default() -> Self {
+        TimeSyncConfig::DEFAULT
+    }
+}
+/// An adapter received one event of the reference train.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct SyncObservation {
+    pub adapter: AdapterIndex,
+    /// Receive time on the adapter's clock.
+    pub timestamp: Duration,
+    /// Periodic event counter of the event or `None` if the controller doesn't report it.
+    pub event_counter: Option<u16>,
+}
+/// Offset of an adapter's clock from the reference adapter's.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct ClockOffset {
+    pub adapter: AdapterIndex,
+    /// Microseconds the adapter's clock is ahead of the reference clock (negative if behind).
+    pub offset_micros: i64,
+    /// Number of events the offset was estimated from.
+    pub samples: u32,
+}
+fn as_micros(timestamp: Duration) -> i64 {
+    timestamp.as_micros() as i64
+}
+/// Estimates clock offsets from [`SyncObservation`]s. Time is passed in by the caller so no
+/// clock is needed.
+#[derive(Clone, Debug)]
+pub struct TimeSync {
+    config: TimeSyncConfig,
+    reference: AdapterIndex,
+    /// Latest observation of each adapter.
+    latest: Vec<SyncObservation>,
+    offsets: Vec<ClockOffset>,
+}
+impl TimeSync {
+    /// Offsets are estimated relative to `reference`.
+    pub fn new(reference: AdapterIndex, config: TimeSyncConfig) -> TimeSync {
+        TimeSync {
+            config,
+            reference,
+            latest: Vec::new(),
+            offsets: Vec::new(),
+        }
+    }
+    pub fn config(&self) -> &TimeSyncConfig {
+        &self.config
+    }
+    pub fn reference(&self) -> AdapterIndex {
+        self.reference
+    }
+    /// Whether `a` and `b` are the same train event.
+    fn is_same_event(&self, a: &SyncObservation, b: &SyncObservation) -> bool {
+        match (a.event_counter, b.event_counter) {
+            (Some(a), Some(b)) => a == b,
+            _ => {
+                let half_interval = i64::from(self.config.interval.as_microseconds() / 2);
+                (as_micros(a.timestamp) - as_micros(b.timestamp)).abs() < half_interval
+            }
+        }
+    }
+    fn add_sample(&mut self, adapter: AdapterIndex, sample: i64) {
+        let smoothing = i64::from(self.config.smoothing.max(1));
+        match self.offsets.iter_mut().find(|o| o.adapter == adapter) {
+            Some(offset) => {
+                offset.offset_micros += (sample - offset.offset_micros) / smoothing;
+                offset.samples = offset.samples.saturating_add(1);
+            }
+            None => self.offsets.push(ClockOffset {
+                adapter,
+                offset_micros: sample,
+                samples: 1,
+            }),
+        }
+    }
+    /// Add an observation of the reference train. It's paired with the latest observation of the
+    /// same event by the reference adapter (or, from the reference adapter, by every other one).
+    pub fn on_sync_event(&mut self, observation: SyncObservation) {
+        let mut samples = Vec::new();
+        for other in self.latest.iter() {
+            if other.adapter == observation.adapter || !self.is_same_event(other, &observation) {
+                continue;
+            }
+            let difference = as_micros(observation.timestamp) - as_micros(other.timestamp);
+            if observation.adapter == self.reference {
+                samples.push((other.adapter, -difference));
+            } else if other.adapter == self.reference {
+                samples.push((observation.adapter, difference));
+            }
+        }
+        for (adapter, sample) in samples {
+            self.add_sample(adapter, sample);
+        }
+        match self
+            .latest
+            .iter_mut()
+            .find(|o| o.adapter == observation.adapter)
+        {
+            Some(latest) => *latest = observation,
+            None => self.latest.push(observation),
+        }
+    }
+    /// Offset of `adapter` or `None` if it hasn't been estimated yet. The reference adapter is
+    /// always `0`.
+    pub fn offset(&self, adapter: AdapterIndex) -> Option<ClockOffset> {
+        if adapter == self.reference {
+            return Some(ClockOffset {
+                adapter,
+                offset_micros: 0,
+                samples: 0,
+            });
+        }
+        self.offsets.iter().find(|o| o.adapter == adapter).copied()
+    }
+    /// Offsets of every adapter estimated so far (the reference adapter isn't included).
+    pub fn offsets(&self) -> &[ClockOffset] {
+        &self.offsets
+    }
+    /// Map `timestamp` from `adapter`'s clock onto the reference clock. `None` as the adapter
+    /// means the reference adapter (only one adapter is scanning). Returns `None` if the offset of
+    /// `adapter` isn't known yet or the corrected time would be negative.
+    pub fn correct(&self, adapter: Option<AdapterIndex>, timestamp: Duration) -> Option<Duration> {
+        let offset = self
+            .offset(adapter.unwrap_or(self.reference))?
+            .offset_micros;
+        let corrected = as_micros(timestamp).checked_sub(offset)?;
+        if corrected < 0 {
+            None
+        } else {
+            Some(Duration::from_micros(corrected as u64))
+        }
+    }
+    /// [`TimeSync::correct`] for a report received at `timestamp` on the adapter in its origin.
+    pub fn correct_report<T: AsRef<[u8]>>(
+        &self,
+        report: &ReportInfo<T>,
+        timestamp: Duration,
+    ) -> Option<Duration> {
+        self.correct(report.origin.adapter, timestamp)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_offsets() {
+        let reference = AdapterIndex(0);
+        let gateway = AdapterIndex(1);
+        let mut sync = TimeSync::new(
+            reference,
+            TimeSyncConfig {
+                smoothing: 2,
+                ..TimeSyncConfig::DEFAULT
+            },
+        );
+        let observe = |adapter, micros, event_counter| SyncObservation {
+            adapter,
+            timestamp: Duration::from_micros(micros),
+            event_counter,
+        };
+        assert_eq!(sync.correct(Some(gateway), Duration::from_secs(1)), None);
+        // The gateway's clock is 2ms ahead (then 2.2ms), events are 100ms apart.
+        sync.on_sync_event(observe(reference, 1_000_000, None));
+        sync.on_sync_event(observe(gateway, 1_002_000, None));
+        assert_eq!(sync.offset(gateway).unwrap().offset_micros, 2_000);
+        sync.on_sync_event(observe(gateway, 1_102_200, None));
+        sync.on_sync_event(observe(reference, 1_100_000, None));
+        let offset = sync.offset(gateway).unwrap();
+        assert_eq!((offset.offset_micros, offset.samples), (2_100, 2));
+        assert_eq!(
+            sync.correct(Some(gateway), Duration::from_micros(1_502_100)),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            sync.correct(None, Duration::from_millis(1500)),
+            Some(Duration::from_millis(1500))
+        );
+        // Event counters match events even if the clocks are far apart.
+        let far = AdapterIndex(2);
+        sync.on_sync_event(observe(far, 5_000_000, Some(7)));
+        sync.on_sync_event(observe(reference, 1_200_000, Some(7)));
+        assert_eq!(sync.offset(far).unwrap().offset_micros, 3_800_000);
+        sync.on_sync_event(observe(far, 5_100_000, Some(9)));
+        assert_eq!(sync.offset(far).unwrap().samples, 1);
+    }
+}
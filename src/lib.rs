@@ -32,11 +32,17 @@ pub type BoxStream<'a, T> = core::pin::Pin<Box<dyn Stream<Item = T> + 'a>>;
 extern crate core;
 pub mod bytes;
 pub mod channel;
+#[cfg(all(target_os = "linux", feature = "bluez_dbus"))]
+pub mod bluez_dbus;
 #[cfg(feature = "classic")]
 pub mod classic;
+pub mod clock;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 #[cfg(feature = "hci")]
 pub mod hci;
+pub mod key_store;
 pub mod le;
 pub mod uri;
 pub mod uuid;
@@ -207,6 +213,47 @@ impl BTAddress {
             _ => AddressType::RFU,
         }
     }
+    /// Parses `AA:BB:CC:DD:EE:FF` (or `AA-BB-CC-DD-EE-FF`) in a const context. Unlike
+    /// [`core::str::FromStr`] every byte must be exactly two hex digits. Returns `None` if `s`
+    /// isn't an address.
+    pub const fn parse_const(s: &str) -> Option<BTAddress> {
+        const fn hex_digit(c: u8) -> Option<u8> {
+            match c {
+                b'0'..=b'9' => Some(c - b'0'),
+                b'a'..=b'f' => Some(c - b'a' + 10),
+                b'A'..=b'F' => Some(c - b'A' + 10),
+                _ => None,
+            }
+        }
+        let s = s.as_bytes();
+        if s.len() != BT_ADDRESS_LEN * 3 - 1 {
+            return None;
+        }
+        let mut out = [0_u8; BT_ADDRESS_LEN];
+        let mut i = 0;
+        while i < BT_ADDRESS_LEN {
+            let at = i * 3;
+            if i > 0 && s[at - 1] != b':' && s[at - 1] != b'-' {
+                return None;
+            }
+            out[i] = match (hex_digit(s[at]), hex_digit(s[at + 1])) {
+                (Some(high), Some(low)) => high << 4 | low,
+                _ => return None,
+            };
+            i += 1;
+        }
+        Some(BTAddress(out))
+    }
+    /// [`BTAddress::parse_const`] for constants. Use [`bt_addr!`] to make sure it's evaluated at
+    /// compile time.
+    /// # Panics
+    /// Panics (a compile error in a const) if `s` isn't an address.
+    pub const fn from_str_const(s: &str) -> BTAddress {
+        match Self::parse_const(s) {
+            Some(address) => address,
+            None => panic!("invalid bluetooth address"),
+        }
+    }
     /// Returns `hash` (24-bit) and `prand` (24-bit) of the resolvable private address.
     /// `prand` includes the address type bits.
     pub fn private_address_parts(self) -> Option<(u32, u32)> {
@@ -254,6 +301,23 @@ impl core::str::FromStr for BTAddress {
         Ok(BTAddress(out))
     }
 }
+/// [`BTAddress`] parsed at compile time. An invalid address doesn't compile.
+/// ```
+/// use btle::{bt_addr, BTAddress};
+/// const PEER: BTAddress = bt_addr!("AA:BB:CC:DD:EE:FF");
+/// assert_eq!(PEER, BTAddress([0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF]));
+/// assert_eq!(PEER, "AA:BB:CC:DD:EE:FF".parse().unwrap());
+/// assert_eq!(BTAddress::parse_const("aa-bb-cc-dd-ee-ff"), Some(PEER));
+/// assert_eq!(BTAddress::parse_const("AA:BB:CC:DD:EE"), None);
+/// assert_eq!(BTAddress::parse_const("AA:BB:CC:DD:EE:FG"), None);
+/// ```
+#[macro_export]
+macro_rules! bt_addr {
+    ($address:expr) => {{
+        const ADDRESS: $crate::BTAddress = $crate::BTAddress::from_str_const($address);
+        ADDRESS
+    }};
+}
 #[derive(Copy, Clone, PartialOrd, PartialEq, Ord, Eq, Debug, Hash)]
 pub enum AddressType {
     NonResolvablePrivate = 0b00,
@@ -261,6 +325,116 @@ pub enum AddressType {
     RFU = 0b10,
     StaticDevice = 0b11,
 }
+/// Public or random (with the random address's sub-type).
+#[derive(Copy, Clone, PartialOrd, PartialEq, Ord, Eq, Debug, Hash)]
+pub enum AddressKind {
+    Public,
+    Random(AddressType),
+}
+impl AddressKind {
+    pub fn is_random(self) -> bool {
+        matches!(self, AddressKind::Random(_))
+    }
+    fn label(self) -> &'static str {
+        match self {
+            AddressKind::Public => "public",
+            AddressKind::Random(AddressType::StaticDevice) => "random static",
+            AddressKind::Random(AddressType::ResolvablePrivateAddress) => "random resolvable",
+            AddressKind::Random(AddressType::NonResolvablePrivate) => "random non-resolvable",
+            AddressKind::Random(AddressType::RFU) => "random",
+        }
+    }
+}
+/// [`BTAddress`] tagged with whether it's public or random. Almost every HCI command that takes
+/// an address also needs its type bit, which a bare [`BTAddress`] loses. Build it with
+/// [`DeviceAddress::public`] or [`DeviceAddress::random`] so the random sub-type matches the
+/// address.
+///
+/// Ordered by address first so the public and random address with the same bytes sort next to
+/// each other. Displays as `AA:BB:CC:DD:EE:FF (random static)` and parses back from that (or
+/// from `AA:BB:CC:DD:EE:FF (public|random)`).
+/// ```
+/// use btle::{bt_addr, AddressKind, AddressType, DeviceAddress};
+/// let device = DeviceAddress::random(bt_addr!("11:22:33:44:55:C6"));
+/// assert_eq!(device.kind, AddressKind::Random(AddressType::StaticDevice));
+/// assert_eq!(device.to_string(), "11:22:33:44:55:C6 (random static)");
+/// assert_eq!(device.to_string().parse(), Ok(device));
+/// let public = DeviceAddress::public(device.addr);
+/// assert_eq!("11:22:33:44:55:C6 (public)".parse(), Ok(public));
+/// assert!(public < device);
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct DeviceAddress {
+    pub kind: AddressKind,
+    pub addr: BTAddress,
+}
+impl DeviceAddress {
+    pub const fn public(addr: BTAddress) -> DeviceAddress {
+        DeviceAddress {
+            kind: AddressKind::Public,
+            addr,
+        }
+    }
+    pub fn random(addr: BTAddress) -> DeviceAddress {
+        DeviceAddress {
+            kind: AddressKind::Random(addr.address_type()),
+            addr,
+        }
+    }
+    /// `random` is the HCI address type bit.
+    pub fn new(random: bool, addr: BTAddress) -> DeviceAddress {
+        if random {
+            Self::random(addr)
+        } else {
+            Self::public(addr)
+        }
+    }
+    pub fn is_random(self) -> bool {
+        self.kind.is_random()
+    }
+    /// Whether the address identifies the device (public or random static) instead of changing
+    /// over time.
+    pub fn is_identity(self) -> bool {
+        matches!(
+            self.kind,
+            AddressKind::Public | AddressKind::Random(AddressType::StaticDevice)
+        )
+    }
+}
+impl Ord for DeviceAddress {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.addr.cmp(&other.addr).then(self.kind.cmp(&other.kind))
+    }
+}
+impl PartialOrd for DeviceAddress {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl core::fmt::Display for DeviceAddress {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} ({})", self.addr, self.kind.label())
+    }
+}
+impl core::str::FromStr for DeviceAddress {
+    type Err = ConversionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, kind) = s
+            .strip_suffix(')')
+            .and_then(|s| s.split_once(" ("))
+            .ok_or(ConversionError(()))?;
+        let addr = addr.parse()?;
+        // The random sub-type comes from the address itself.
+        match kind {
+            "public" => Ok(DeviceAddress::public(addr)),
+            "random" | "random static" | "random resolvable" | "random non-resolvable" => {
+                Ok(DeviceAddress::random(addr))
+            }
+            _ => Err(ConversionError(())),
+        }
+    }
+}
 /// 16-bit Bluetooth Company Identifier. Companies are assigned unique Company Identifiers to
 /// Bluetooth SIG members requesting them. [See here for more](https://www.bluetooth.com/specifications/assigned-numbers/company-identifiers/)
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
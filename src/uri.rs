@@ -1,4 +1,5 @@
 //! Bluetooth URI type. According to Bluetooth Assigned Numbers.
+use crate::PackError;
 use core::convert::TryFrom;
 use core::fmt::{Display, Error, Formatter};
 use core::str::FromStr;
@@ -784,3 +785,93 @@ impl Display for URIName {
         f.write_str(self.as_str())
     }
 }
+/// Code point used in the URI AD type for URIs without a known scheme.
+pub const NO_SCHEME_CODE: u8 = 0x01;
+/// URI as carried in the URI AD type (`0x24`). The scheme is compressed into the UTF-8 code
+/// point of its [`URIName`] value and the rest of the URI follows as is.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct EncodedURI<'a> {
+    /// `None` if the scheme isn't in the assigned numbers (and is still in `rest`).
+    pub scheme: Option<URIName>,
+    /// URI after the scheme (`//www.bluetooth.com` for `https://www.bluetooth.com`).
+    pub rest: &'a str,
+}
+impl<'a> EncodedURI<'a> {
+    /// Split `uri` into a known scheme and the rest.
+    pub fn from_uri(uri: &'a str) -> EncodedURI<'a> {
+        if let Some(colon) = uri.find(':') {
+            if let Ok(scheme) = URIName::from_str(&uri[..colon]) {
+                return EncodedURI {
+                    scheme: Some(scheme),
+                    rest: &uri[colon + 1..],
+                };
+            }
+        }
+        EncodedURI {
+            scheme: None,
+            rest: uri,
+        }
+    }
+    fn scheme_char(&self) -> char {
+        char::from(self.scheme.map_or(NO_SCHEME_CODE, u8::from))
+    }
+    pub fn byte_len(&self) -> usize {
+        self.scheme_char().len_utf8() + self.rest.len()
+    }
+    pub fn pack_into(&self, buf: &mut [u8]) -> Result<(), PackError> {
+        PackError::expect_length(self.byte_len(), buf)?;
+        let scheme_len = self.scheme_char().encode_utf8(buf).len();
+        buf[scheme_len..].copy_from_slice(self.rest.as_bytes());
+        Ok(())
+    }
+    pub fn unpack_from(buf: &'a [u8]) -> Result<EncodedURI<'a>, PackError> {
+        let s = core::str::from_utf8(buf).map_err(|e| PackError::bad_index(e.valid_up_to()))?;
+        let first = s.chars().next().ok_or(PackError::BadLength {
+            expected: 1,
+            got: 0,
+        })?;
+        let rest = &s[first.len_utf8()..];
+        let code = u8::try_from(u32::from(first)).map_err(|_| PackError::bad_index(0))?;
+        let scheme = if code == NO_SCHEME_CODE {
+            None
+        } else {
+            Some(URIName::try_from(code).map_err(|_| PackError::bad_index(0))?)
+        };
+        Ok(EncodedURI { scheme, rest })
+    }
+}
+impl Display for EncodedURI<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        if let Some(scheme) = self.scheme {
+            f.write_str(scheme.as_str())?;
+        }
+        f.write_str(self.rest)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_encoded_uri() {
+        let uri = EncodedURI::from_uri("https://www.bluetooth.com");
+        assert_eq!(uri.scheme, Some(URIName::Https));
+        let mut buf = [0_u8; 20];
+        uri.pack_into(&mut buf[..uri.byte_len()]).unwrap();
+        assert_eq!(&buf[..3], &[0x17, b'/', b'/']);
+        assert_eq!(EncodedURI::unpack_from(&buf[..uri.byte_len()]), Ok(uri));
+        // Scheme codes above 0x7F take two bytes in UTF-8.
+        let uri = EncodedURI::from_uri("ssh:host");
+        assert_eq!(uri.byte_len(), 6);
+        uri.pack_into(&mut buf[..6]).unwrap();
+        assert_eq!(&buf[..2], &[0xC2, 0xA7]);
+        assert_eq!(EncodedURI::unpack_from(&buf[..6]), Ok(uri));
+        let unknown = EncodedURI::from_uri("made-up:thing");
+        assert_eq!(unknown.scheme, None);
+        unknown.pack_into(&mut buf[..14]).unwrap();
+        assert_eq!(buf[0], NO_SCHEME_CODE);
+        assert_eq!(
+            EncodedURI::unpack_from(&buf[..14]).unwrap().to_string(),
+            "made-up:thing"
+        );
+    }
+}
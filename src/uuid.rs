@@ -57,14 +57,7 @@ impl UUID {
     #[must_use]
     pub fn uuid_bytes_from_str(s: &str) -> Option<UUIDBytes> {
         let mut out = [0_u8; 16];
-        let buf = out.as_mut();
-        if buf.len() == 0 || buf.len() * 2 != s.len() {
-            return None;
-        }
-        for (i, c) in s.chars().enumerate() {
-            let v = u8::try_from(c.to_digit(16)?).expect("only returns [0..=15]");
-            buf[i / 2] |= v << u8::try_from(((i + 1) % 2) * 4).expect("only returns 0 or 4");
-        }
+        crate::bytes::decode_hex(s.as_bytes(), &mut out).ok()?;
         Some(out)
     }
 }
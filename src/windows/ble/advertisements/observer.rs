@@ -1,10 +1,12 @@
+use crate::hci::adapters::retry::{DelayFn, RetryPolicy};
+use crate::le::adapter::{Backend, Capabilities};
 use crate::le::scan;
 use crate::le::scan::ScanType;
 use crate::windows::WindowsError;
 use crate::{
     bytes::Storage,
     le::advertisement::{AdType, RawAdStructureBuffer, RawAdvertisement, StaticAdvStructBuf},
-    le::report::{AddressType, EventType, ReportInfo},
+    le::report::{AddressType, EventType, ReportInfo, ReportOrigin},
     BTAddress, RSSI,
 };
 use core::{
@@ -12,7 +14,7 @@ use core::{
     pin::Pin,
     task::{Context, Poll},
 };
-use futures_util::stream::Stream;
+use futures_util::stream::{LocalBoxStream, Stream};
 use std::marker::PhantomData;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::error::TrySendError;
@@ -20,8 +22,10 @@ use winrt_bluetooth_bindings::windows::{
     devices::bluetooth::advertisement::{
         BluetoothLEAdvertisementDataSection, BluetoothLEAdvertisementFilter,
         BluetoothLEAdvertisementReceivedEventArgs, BluetoothLEAdvertisementType,
-        BluetoothLEAdvertisementWatcher, BluetoothLEScanningMode,
+        BluetoothLEAdvertisementWatcher, BluetoothLEAdvertisementWatcherStoppedEventArgs,
+        BluetoothLEScanningMode,
     },
+    devices::bluetooth::BluetoothError,
     foundation,
     storage::streams::DataReader,
 };
@@ -42,6 +46,42 @@ where
         (self)(args)
     }
 }
+/// Why a watcher stopped (`BluetoothError` of the `Stopped` event).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum WatcherStopReason {
+    /// Stopped with [`RawWatcher::set_scan_enable`].
+    Requested,
+    /// The radio was turned off (or the adapter removed).
+    RadioNotAvailable,
+    ResourceInUse,
+    DisabledByPolicy,
+    DisabledByUser,
+    NotSupported,
+    Other,
+}
+impl WatcherStopReason {
+    fn from_bluetooth_error(error: BluetoothError) -> WatcherStopReason {
+        match error {
+            BluetoothError::Success => WatcherStopReason::Requested,
+            BluetoothError::RadioNotAvailable => WatcherStopReason::RadioNotAvailable,
+            BluetoothError::ResourceInUse => WatcherStopReason::ResourceInUse,
+            BluetoothError::DisabledByPolicy => WatcherStopReason::DisabledByPolicy,
+            BluetoothError::DisabledByUser => WatcherStopReason::DisabledByUser,
+            BluetoothError::NotSupported => WatcherStopReason::NotSupported,
+            _ => WatcherStopReason::Other,
+        }
+    }
+    /// Returns if restarting the watcher can help. Stops asked for by the user or by policy
+    /// aren't undone.
+    pub fn is_recoverable(self) -> bool {
+        match self {
+            WatcherStopReason::RadioNotAvailable
+            | WatcherStopReason::ResourceInUse
+            | WatcherStopReason::Other => true,
+            _ => false,
+        }
+    }
+}
 /// Wrapper around `winrt`'s `BluetoothLEAdvertisementWatcher`.
 pub struct RawWatcher<Callback> {
     watcher: BluetoothLEAdvertisementWatcher,
@@ -70,6 +110,20 @@ impl<Callback: RawWatcherCallback> RawWatcher<Callback> {
         }
         Ok(())
     }
+    /// Call `on_stopped` every time Windows stops the watcher (radio toggled, adapter removed,
+    /// etc) or it's stopped with [`RawWatcher::set_scan_enable`].
+    pub fn on_stopped<F: FnMut(WatcherStopReason) + 'static>(
+        &mut self,
+        mut on_stopped: F,
+    ) -> Result<(), WindowsError> {
+        self.watcher.stopped(&foundation::TypedEventHandler::new(
+            move |_sender, args: &BluetoothLEAdvertisementWatcherStoppedEventArgs| {
+                on_stopped(WatcherStopReason::from_bluetooth_error(args.error()?));
+                Ok(())
+            },
+        ))?;
+        Ok(())
+    }
     pub fn set_scanning_mode(&mut self, scanning_mode: scan::ScanType) -> Result<(), WindowsError> {
         let mode = match scanning_mode {
             ScanType::Passive => BluetoothLEScanningMode::Passive,
@@ -151,6 +205,7 @@ impl ReportInfoCallback {
                     .try_into()
                     .expect("invalid rssi"),
             )),
+            origin: ReportOrigin::LEGACY,
         })
     }
 }
@@ -162,9 +217,29 @@ impl RawWatcherCallback for ReportInfoCallback {
         self.callback(args)
     }
 }
+/// Watcher state changes reported by [`ReportInfoWatcher::status_stream`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum WatcherStatus {
+    Stopped(WatcherStopReason),
+    /// Restarted on the `attempt`th try after a recoverable stop.
+    Restarted {
+        attempt: u8,
+    },
+    /// Every restart allowed by the restart policy failed. The watcher stays stopped until
+    /// [`ReportInfoWatcher::set_scan_enable`] is called again.
+    GaveUp {
+        reason: WatcherStopReason,
+    },
+}
 pub struct ReportInfoWatcher {
     watcher: RawWatcher<ReportInfoCallback>,
     rx: mpsc::Receiver<ReportInfo>,
+    status_rx: mpsc::UnboundedReceiver<WatcherStopReason>,
+    is_enabled: bool,
+    /// Restarts after a recoverable stop. Uses [`RetryPolicy::backoff`] between attempts.
+    pub restart_policy: RetryPolicy,
+    /// Waits between restarts. Without one, restarts are attempted immediately.
+    pub delay: Option<DelayFn>,
 }
 impl ReportInfoWatcher {
     const DEFAULT_CAPACITY: usize = 16;
@@ -174,12 +249,72 @@ impl ReportInfoWatcher {
     }
     pub fn with_capacity(capacity: usize) -> Result<Self, WindowsError> {
         let (tx, rx) = mpsc::channel(capacity);
-        let watcher = RawWatcher::new(ReportInfoCallback::from_sender(tx))?;
-        Ok(Self { watcher, rx })
+        let (status_tx, status_rx) = mpsc::unbounded_channel();
+        let mut watcher = RawWatcher::new(ReportInfoCallback::from_sender(tx))?;
+        watcher.on_stopped(move |reason| {
+            // Nobody is listening for status changes anymore.
+            let _ = status_tx.send(reason);
+        })?;
+        Ok(Self {
+            watcher,
+            rx,
+            status_rx,
+            is_enabled: false,
+            restart_policy: RetryPolicy::DEFAULT,
+            delay: None,
+        })
+    }
+    pub fn set_scan_enable(&mut self, is_enabled: bool) -> Result<(), WindowsError> {
+        self.is_enabled = is_enabled;
+        self.watcher.set_scan_enable(is_enabled)
+    }
+    pub fn set_scanning_mode(&mut self, scanning_mode: scan::ScanType) -> Result<(), WindowsError> {
+        self.watcher.set_scanning_mode(scanning_mode)
     }
     pub fn advertisement_stream(&mut self) -> AdvertisementStream<'_> {
         AdvertisementStream::new(self)
     }
+    /// Wait for the next status change. Recoverable stops of an enabled watcher are followed
+    /// by restarts (with backoff) until one succeeds or the restart policy runs out. Only
+    /// restarts while being polled.
+    pub async fn next_status(&mut self) -> Option<WatcherStatus> {
+        let reason = self.status_rx.recv().await?;
+        if !self.is_enabled || !reason.is_recoverable() {
+            if reason != WatcherStopReason::Requested {
+                self.is_enabled = false;
+            }
+            return Some(WatcherStatus::Stopped(reason));
+        }
+        for attempt in 1..=self.restart_policy.max_retries {
+            if let Some(delay) = self.delay {
+                delay(self.restart_policy.backoff(attempt)).await;
+            }
+            if self.watcher.set_scan_enable(true).is_ok() {
+                return Some(WatcherStatus::Restarted { attempt });
+            }
+        }
+        self.is_enabled = false;
+        Some(WatcherStatus::GaveUp { reason })
+    }
+    /// Stream of [`ReportInfoWatcher::next_status`]. Must be polled for automatic restarts to
+    /// happen.
+    pub fn status_stream(&mut self) -> LocalBoxStream<'_, WatcherStatus> {
+        Box::pin(futures_util::stream::unfold(self, |watcher| async move {
+            let status = watcher.next_status().await?;
+            Some((status, watcher))
+        }))
+    }
+}
+impl Backend for ReportInfoWatcher {
+    /// The watcher only scans (actively or passively, see
+    /// [`ReportInfoWatcher::set_scanning_mode`]).
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_active_scan: true,
+            supports_passive_scan: true,
+            ..Capabilities::NONE
+        }
+    }
 }
 
 pub struct AdvertisementStream<'a>(&'a mut ReportInfoWatcher);